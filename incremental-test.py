@@ -103,7 +103,7 @@ for i in range (1, NODE_COUNT+1) :
                 if j % DOWNLOAD_EVERY == 0 and random.random()*100 <= DOWNLOAD_PERCENT:
                     measurements+=1
                     t0 = time.time()
-                    RESULT=subprocess.run([CLI_BIN, "-d", "download-file", N_NAMES[j], FILE_ID], stdout=subprocess.PIPE).stdout.decode().strip()
+                    RESULT=subprocess.run([CLI_BIN, "-d", "download-file", N_NAMES[j], FILE_ID, "-o", "-"], stdout=subprocess.PIPE).stdout.decode().strip()
                     t = time.time()-t0
                     cmp = FILE_CONTENT == RESULT
                     results_temp[1].append((j,cmp))
@@ -0,0 +1,79 @@
+use anyhow::{anyhow, bail, Result};
+use clap_complete::engine::CompletionCandidate;
+use liberum_core::{daemon_config, DaemonRequest, DaemonResponse};
+use std::ffi::OsStr;
+use std::future::Future;
+
+///! Dynamic completion of the arguments that need data from the running daemon.
+///! The completers are called by the shell before the CLI runs its tokio runtime,
+///! so every completer creates its own runtime. If the daemon is not running
+///! nothing is completed.
+
+/// Completes the names of the nodes known to the daemon
+pub fn complete_node_names(current: &OsStr) -> Vec<CompletionCandidate> {
+    candidates(current, block_on(node_names()))
+}
+
+/// Completes the IDs of the objects published by the running nodes
+pub fn complete_object_ids(current: &OsStr) -> Vec<CompletionCandidate> {
+    candidates(current, block_on(published_object_ids()))
+}
+
+fn candidates(current: &OsStr, values: Result<Vec<String>>) -> Vec<CompletionCandidate> {
+    let Some(current) = current.to_str() else {
+        return vec![];
+    };
+
+    values
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|v| v.starts_with(current))
+        .map(CompletionCandidate::new)
+        .collect()
+}
+
+fn block_on<F: Future<Output = Result<Vec<String>>>>(future: F) -> Result<Vec<String>> {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?
+        .block_on(future)
+}
+
+async fn send_request(request: DaemonRequest) -> Result<DaemonResponse> {
+    let (req, mut res) = liberum_core::connect(daemon_config::client_socket_path()).await?;
+    req.send(request).await?;
+
+    Ok(res
+        .recv()
+        .await
+        .ok_or(anyhow!("Daemon returned no response"))??)
+}
+
+async fn node_names() -> Result<Vec<String>> {
+    match send_request(DaemonRequest::ListNodes).await? {
+        DaemonResponse::NodeList(nodes) => Ok(nodes.into_iter().map(|n| n.name).collect()),
+        _ => bail!("Daemon returned wrong response"),
+    }
+}
+
+async fn published_object_ids() -> Result<Vec<String>> {
+    let nodes = match send_request(DaemonRequest::ListNodes).await? {
+        DaemonResponse::NodeList(nodes) => nodes,
+        _ => bail!("Daemon returned wrong response"),
+    };
+
+    let mut ids = Vec::new();
+    for node in nodes.into_iter().filter(|n| n.is_running) {
+        let request = DaemonRequest::GetPublishedObjects {
+            node_name: node.name,
+        };
+        if let DaemonResponse::PublishedObjectsList { object_infos } = send_request(request).await?
+        {
+            ids.extend(object_infos.into_iter().map(|info| info.id));
+        }
+    }
+    ids.sort();
+    ids.dedup();
+
+    Ok(ids)
+}
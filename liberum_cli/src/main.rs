@@ -1,12 +1,34 @@
 use anyhow::{anyhow, bail, Result};
-use clap::{Parser, Subcommand};
-use liberum_core::node_config::NodeConfig;
-use liberum_core::types::{NodeInfo, TypedObjectInfo};
-use liberum_core::{node_config::BootstrapNode, DaemonError, DaemonRequest, DaemonResponse};
+mod completion;
+mod mount;
+
+use clap::{Args, CommandFactory, Parser, Subcommand};
+use clap_complete::engine::ArgValueCompleter;
+use clap_complete::{generate, CompleteEnv, Shell};
+use liberum_core::daemon_config;
+use liberum_core::journal::{self, ConnectionSpan, JournalEvent, QuerySpan};
+use liberum_core::node_config::{
+    self, AddressPolicy, AddressPreference, CacheConfig, ChunkingConfig, ErasureCodingConfig,
+    IpStack, NodeConfig, PeerExchangeConfig, RestartPolicy, TextIndexConfig, UploadLimits,
+    WatchDir,
+};
+use liberum_core::proto::{PlainFileObject, TypedObject};
+use liberum_core::render::{self, RenderFormat};
+use liberum_core::types::{
+    AuditEntry, AuditFilter, AuditRequestKind, BucketInfo, Contact, GroupPost, InboxMessage,
+    MessageContent, ModuleInfo, NodeInfo, NodeStatsSample, NodeStatus, ObjectAccess, ObjectInfo,
+    ObjectLayer, ObjectPopularity, ObjectVerification, PeerInfo, PeerScore, PublishFileResult,
+    ScheduledTask, ScheduledTaskInfo, SearchResult, TextMatch, TrustLevel, TypedObjectInfo,
+};
+use liberum_core::{
+    erasure, node_config::BootstrapNode, DaemonError, DaemonRequest, DaemonResponse, NodeOperation,
+};
 use libp2p::Multiaddr;
+use std::io::{self, Write};
 use std::path::Path;
 use std::path::PathBuf;
 use std::str::FromStr;
+use std::time::{Duration, Instant, SystemTime};
 use tabled::settings::Style;
 use tabled::{Table, Tabled};
 use tokio::sync::mpsc::{Receiver, Sender};
@@ -24,6 +46,12 @@ struct Cli {
     debug_log: bool,
     #[arg(long, short)]
     machine_readable: bool,
+    /// Print the responses of the daemon as JSON, one object per line
+    #[arg(long)]
+    json: bool,
+    /// Authenticate with the token from the daemon config, to get its role
+    #[arg(long)]
+    token: Option<String>,
 }
 
 /// Subcommands for the CLI
@@ -33,20 +61,132 @@ struct Cli {
 enum Command {
     /// Creates a new node
     NewNode(NewNode),
+    /// Starts the nodes, many of them at once
     StartNode(StartNode),
     ConfigNode(ConfigNode),
     ListNodes,
     GetNodeDetails(GetNodeDetails),
     GetNodeAddresses(GetNodeAddresses),
+    NodeStatus(NodeStatusCmd),
+    GetPeerScores(GetPeerScores),
+    /// Forgets the misbehaviours of the peer recorded by the running node, which
+    /// lifts its ban. Only for the admins
+    ResetPeerScore(ResetPeerScore),
+    /// Lists the buckets of the routing table of the node, the peers in them and the
+    /// connected peers
+    RoutingTable(RoutingTableCmd),
+    /// Stops the nodes, many of them at once
     StopNode(StopNode),
+    /// Applies the stored configs to the running nodes, many of them at once
+    ReloadNodes(ReloadNodes),
     ProvideFile(ProvideFile),
     GetProviders(GetProviders),
     DownloadFile(DownloadFile),
     GetPeerID(GetPeerID),
     Dial(Dial),
     PublishFile(PublishFile),
+    /// Publishes all the files matching the glob patterns
+    PublishFiles(PublishFiles),
     GetPublishedObjects(GetPublishedObjects),
+    /// Deletes the object from all the nodes providing it
+    #[command(visible_alias = "unpublish")]
     DeleteObject(DeleteObject),
+    /// Stops providing the object by this node only
+    StopProviding(StopProviding),
+    /// Checks the integrity of the object and the number of its providers
+    Verify(Verify),
+    /// Describes the object stored by the node and where it came from
+    ObjectInfo(ObjectInfoCmd),
+    /// Prints the object as the tree of the objects nested in it, or as JSON
+    Cat(Cat),
+    /// Writes the keypair of the node encrypted with a passphrase to a file
+    ExportIdentity(ExportIdentity),
+    /// Creates a new node with the keypair from an exported identity file
+    ImportIdentity(ImportIdentity),
+    /// Decrypts the keypair of a protected node, so it can be started
+    UnlockNode(UnlockNode),
+    /// Encrypts the keypair of the node on disk with a passphrase
+    SetPassphrase(SetPassphrase),
+    /// Prints the state changes of the nodes as they happen, until interrupted
+    Watch,
+    /// Prints the config the daemon runs with, after the overrides by the environment
+    /// variables and the flags
+    DaemonConfig,
+    /// Checks the daemon is alive and answers in time, fails otherwise
+    PingDaemon(PingDaemon),
+    RenameNode(RenameNode),
+    /// Replaces the keypair of the node, e.g. when it leaked. The peer ID changes,
+    /// peers find the new one from the old one in the network
+    RotateKey(RotateKey),
+    /// Deletes the node together with its vault
+    DeleteNode(DeleteNode),
+    /// Creates a new node with the configuration of an existing one
+    CloneNode(CloneNode),
+    /// Asks the network for the objects matching the query, handled by external modules
+    Query(QueryCmd),
+    /// Publishes the tags of the object, for the peers to find it with `search`
+    Tag(Tag),
+    /// Searches the objects by their tags, the ones matching the most tags first
+    Search(Search),
+    /// Searches the text of the plain text files stored by the node
+    SearchText(SearchText),
+    /// Lists the revisions of the object known to the node, the latest one first
+    History(History),
+    /// Packs all the objects and fragments of the node's vault into an archive
+    ExportVault(ExportVault),
+    /// Merges a vault archive into the node's vault, skipping what it already has
+    ImportVault(ImportVault),
+    /// Changes the log level of the daemon without restarting it
+    SetLogLevel(SetLogLevel),
+    /// Prints the recent log lines of the daemon or of a node
+    Logs(Logs),
+    /// Sets the profile of the node, shown to other peers
+    SetProfile(SetProfile),
+    /// Finds the profile a peer published in the network
+    GetProfile(GetProfile),
+    /// Adds the peer to the contacts of the node, or changes the contact
+    AddContact(AddContact),
+    RemoveContact(RemoveContact),
+    ListContacts(ListContacts),
+    /// Publishes the files created or changed in the directory, while the node runs
+    AddWatchDir(AddWatchDir),
+    /// Stops watching the directory, its files stay published
+    RemoveWatchDir(RemoveWatchDir),
+    ListWatchDirs(ListWatchDirs),
+    /// Sends a text or a file directly to a peer
+    SendMessage(SendMessage),
+    /// Prints the messages received by the node
+    Inbox(Inbox),
+    /// Creates a group owned by the node, its members share a feed of posts
+    CreateGroup(CreateGroup),
+    /// Prints an invitation to the group for the peer. Only the owner can invite
+    InviteToGroup(InviteToGroup),
+    /// Joins the group using the invitation received from its owner
+    JoinGroup(JoinGroup),
+    /// Posts a text or a file in the feed of the group
+    PostToGroup(PostToGroup),
+    /// Prints the posts of the group received by the node
+    GroupFeed(GroupFeed),
+    /// Prints the requests of other peers served or rejected by the node
+    AuditLog(AuditLog),
+    /// Prints the stats of the node recorded while it was running
+    StatsHistory(StatsHistory),
+    /// Prints the maintenance tasks of the running node and their last results
+    ScheduledTasks(ScheduledTasks),
+    /// Runs the maintenance task of the running node now and waits for it to finish
+    RunTask(RunTask),
+    /// Prints the objects most often fetched from the node by other peers
+    PopularObjects(PopularObjects),
+    /// Prints the timeline of the queries and the connections from the journal of
+    /// a node, recorded if its config sets `journal_path`. Works without the daemon
+    Replay(Replay),
+    /// Prints the completion script for the shell. For completion of node names
+    /// and object IDs, which are queried from the running daemon, register the
+    /// dynamic completion instead, e.g. `source <(COMPLETE=bash liberum_cli)`
+    Completions(Completions),
+    /// Mounts the files published on the network as a read-only filesystem, until
+    /// interrupted. Needs FUSE
+    Mount(Mount),
 }
 
 #[derive(Parser)]
@@ -59,10 +199,39 @@ struct NewNode {
     id_seed: Option<String>,
 }
 
+/// The nodes of a command which applies to many of them
+#[derive(Args)]
+struct NodeNames {
+    #[arg(
+        required_unless_present_any = ["all_nodes", "group"],
+        add = ArgValueCompleter::new(completion::complete_node_names)
+    )]
+    names: Vec<String>,
+    /// All the nodes the user may see
+    #[arg(long, conflicts_with_all = ["names", "group"])]
+    all_nodes: bool,
+    /// The nodes of the group from the daemon config
+    #[arg(long, conflicts_with = "names")]
+    group: Option<String>,
+}
+
+impl NodeNames {
+    /// The only node named, None if the command applies to many nodes
+    fn single(&self) -> Option<&str> {
+        match (
+            self.names.as_slice(),
+            self.all_nodes || self.group.is_some(),
+        ) {
+            ([name], false) => Some(name.as_str()),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Parser)]
 struct StartNode {
-    #[arg()]
-    name: String,
+    #[command(flatten)]
+    nodes: NodeNames,
 }
 
 #[derive(Parser)]
@@ -75,8 +244,8 @@ struct ConfigNode {
 
 #[derive(Parser)]
 struct GetNodeDetails {
-    #[arg()]
-    name: String,
+    #[command(flatten)]
+    nodes: NodeNames,
 }
 
 #[derive(Parser)]
@@ -86,15 +255,85 @@ struct GetNodeAddresses {
 }
 
 #[derive(Parser)]
-struct StopNode {
+struct NodeStatusCmd {
     #[arg()]
     name: String,
 }
 
+#[derive(Parser)]
+struct GetPeerScores {
+    #[arg()]
+    node_name: String,
+}
+
+#[derive(Parser)]
+struct ResetPeerScore {
+    #[arg()]
+    node_name: String,
+    #[arg()]
+    peer_id: String,
+}
+
+#[derive(Parser)]
+struct RoutingTableCmd {
+    #[arg()]
+    node_name: String,
+}
+
+#[derive(Parser)]
+struct StopNode {
+    #[command(flatten)]
+    nodes: NodeNames,
+}
+
+#[derive(Parser)]
+struct ReloadNodes {
+    #[command(flatten)]
+    nodes: NodeNames,
+}
+
 #[derive(Subcommand)]
 enum ConfigNodeCommand {
     AddBootstrapNode(AddBootstrapNode),
+    /// Add a hostname with dnsaddr TXT records, resolved to bootstrap nodes when the node starts
+    AddBootstrapDnsSeed(AddBootstrapDnsSeed),
     AddExternalAddr(AddExternalAddr),
+    /// Refuse connections from the peer, applied immediately also to a running node
+    BlockPeer(BlockPeer),
+    /// Remove the peer from the blocklist
+    UnblockPeer(UnblockPeer),
+    /// Apply the stored bootstrap nodes and addresses to the running node
+    Reload,
+    /// List the modules of the node, the object types it handles
+    ListModules,
+    /// Handle the objects of the module, applied immediately also to a running node
+    EnableModule(ModuleArg),
+    /// Reject the objects of the module received from other peers
+    DisableModule(ModuleArg),
+    /// Set how often the maintenance task runs, 0 runs it only with `run-task`
+    SetTaskInterval(SetTaskInterval),
+    /// Set the IP versions to listen on, the order to dial the addresses of the
+    /// peers in and whether the unroutable addresses are announced
+    SetAddressPolicy(SetAddressPolicy),
+    /// Set how much the node serves to other peers, 0 is no limit
+    SetUploadLimits(SetUploadLimits),
+    /// Set the size of the objects stored for other peers above which the least
+    /// requested ones are removed, 0 is no limit
+    SetCacheLimit(SetCacheLimit),
+    /// Turn the full-text index of the plain text files on or off, turning it off
+    /// removes what was indexed
+    SetTextIndex(SetTextIndex),
+    /// Turn the publishing of the big files in chunks on or off, the new revisions
+    /// of the files published in chunks publish only the changed chunks
+    SetChunking(SetChunking),
+    /// Turn the peer exchange on or off, the connected peers share the peers they
+    /// are connected to
+    SetPeerExchange(SetPeerExchange),
+    /// Put the node into a private network, only the nodes with the same key can
+    /// connect to it. Takes effect when the node starts
+    SetNetworkKey(SetNetworkKey),
+    /// Set when the daemon restarts the node which stopped without being asked to
+    SetRestartPolicy(SetRestartPolicy),
 }
 
 #[derive(Parser)]
@@ -102,12 +341,177 @@ struct AddBootstrapNode {
     #[arg()]
     id: String,
     addr: String,
+    /// Apply to the running node without restarting it
+    #[arg(long)]
+    live: bool,
+}
+
+#[derive(Parser)]
+struct AddBootstrapDnsSeed {
+    /// e.g. bootstrap.example.org for the records at _dnsaddr.bootstrap.example.org
+    #[arg()]
+    hostname: String,
 }
 
 #[derive(Parser)]
 struct AddExternalAddr {
     #[arg()]
     addr: String,
+    /// Apply to the running node without restarting it
+    #[arg(long)]
+    live: bool,
+}
+
+#[derive(Parser)]
+struct SetTaskInterval {
+    /// One of reprovide, replication, mailbox-fetch, bootstrap-health, cache-gc,
+    /// peer-exchange and stats-sample
+    #[arg()]
+    task: ScheduledTask,
+    #[arg()]
+    interval_secs: u64,
+    /// Apply to the running node without restarting it
+    #[arg(long)]
+    live: bool,
+}
+
+#[derive(Parser)]
+struct SetAddressPolicy {
+    /// One of dual, ipv4 and ipv6, used when the node has no external addresses
+    #[arg(long, default_value = "dual")]
+    stack: IpStack,
+    /// One of ipv6, ipv4, private, public and quic, can be repeated, the first one
+    /// decides and the next ones break the ties
+    #[arg(long)]
+    prefer: Vec<AddressPreference>,
+    /// Keep the loopback and link-local addresses, for networks on a single machine
+    #[arg(long)]
+    allow_unroutable: bool,
+    /// Apply to the running node without restarting it, the IP versions change
+    /// only if the node has no external addresses
+    #[arg(long)]
+    live: bool,
+}
+
+#[derive(Parser)]
+struct SetUploadLimits {
+    /// The bytes served to a single peer in an hour
+    #[arg()]
+    bytes_per_peer_per_hour: u64,
+    /// The objects sent to the peers at the same time
+    #[arg()]
+    max_concurrent_uploads: usize,
+    /// Apply to the running node without restarting it
+    #[arg(long)]
+    live: bool,
+}
+
+#[derive(Parser)]
+struct SetRestartPolicy {
+    /// One of never, on-failure and always
+    #[arg()]
+    policy: RestartPolicy,
+    /// Delay before the first restart, doubled for every next one
+    #[arg(long)]
+    initial_backoff_ms: Option<u64>,
+    #[arg(long)]
+    max_backoff_ms: Option<u64>,
+    /// The most restarts in the window, after them the node stays stopped
+    #[arg(long)]
+    max_restarts: Option<u32>,
+    #[arg(long)]
+    window_secs: Option<u64>,
+    /// Apply to the running node without restarting it
+    #[arg(long)]
+    live: bool,
+}
+
+#[derive(Parser)]
+struct SetCacheLimit {
+    #[arg()]
+    max_stored_bytes: u64,
+    /// Apply to the running node without restarting it
+    #[arg(long)]
+    live: bool,
+}
+
+#[derive(Parser)]
+struct SetTextIndex {
+    #[arg(long)]
+    disable: bool,
+    /// The largest file indexed, the current one is kept if not given
+    #[arg(long)]
+    max_indexed_bytes: Option<u64>,
+    /// Apply to the running node without restarting it
+    #[arg(long)]
+    live: bool,
+}
+
+#[derive(Parser)]
+struct SetNetworkKey {
+    /// The pre-shared key of the network, 64 hex digits
+    #[arg(required_unless_present_any = ["generate", "remove"])]
+    key: Option<String>,
+    /// Generate the key of a new network and print it
+    #[arg(long, conflicts_with_all = ["key", "remove"])]
+    generate: bool,
+    /// Leave the private network
+    #[arg(long, conflicts_with = "key")]
+    remove: bool,
+}
+
+#[derive(Parser)]
+struct SetPeerExchange {
+    #[arg(long)]
+    disable: bool,
+    /// The most peers sent and taken in an exchange, the current one is kept if not given
+    #[arg(long)]
+    max_peers: Option<usize>,
+    /// How often a peer may ask for the peers, the current one is kept if not given
+    #[arg(long)]
+    min_request_interval_secs: Option<u64>,
+    /// Apply to the running node without restarting it
+    #[arg(long)]
+    live: bool,
+}
+
+#[derive(Parser)]
+struct SetChunking {
+    #[arg(long)]
+    disable: bool,
+    /// The smallest file published in chunks, the current one is kept if not given
+    #[arg(long)]
+    min_file_size: Option<u64>,
+    /// The chunks coded together, with the parity chunks enables the erasure coding
+    #[arg(long, requires = "parity_shards")]
+    data_shards: Option<u8>,
+    /// The parity chunks of every group of the data chunks
+    #[arg(long, requires = "data_shards")]
+    parity_shards: Option<u8>,
+    #[arg(long, conflicts_with = "data_shards")]
+    no_erasure_coding: bool,
+    /// Apply to the running node without restarting it
+    #[arg(long)]
+    live: bool,
+}
+
+#[derive(Parser)]
+struct ModuleArg {
+    /// Name or UUID of the module, e.g. SignedObject
+    #[arg()]
+    module: String,
+}
+
+#[derive(Parser)]
+struct BlockPeer {
+    #[arg()]
+    peer_id: String,
+}
+
+#[derive(Parser)]
+struct UnblockPeer {
+    #[arg()]
+    peer_id: String,
 }
 
 #[derive(Parser)]
@@ -124,14 +528,28 @@ struct GetProviders {
     node_name: String,
     #[arg()]
     id: String,
+    /// Prints the providers as soon as they are found
+    #[arg(long)]
+    stream: bool,
 }
 
 #[derive(Parser)]
 struct DownloadFile {
-    #[arg()]
+    #[arg(add = ArgValueCompleter::new(completion::complete_node_names))]
     node_name: String,
-    #[arg()]
+    #[arg(add = ArgValueCompleter::new(completion::complete_object_ids))]
     id: String,
+    /// ID of the group the file was published to, the membership of the node
+    /// in the group is sent to the providers
+    #[arg(long)]
+    group: Option<String>,
+    /// Path the file is written to, `-` writes it to the standard output.
+    /// Defaults to a file named after the object ID in the current directory
+    #[arg(long, short)]
+    output: Option<PathBuf>,
+    /// Download the file from the network even if it's in the vault of the node
+    #[arg(long)]
+    force_network: bool,
 }
 
 #[derive(Parser)]
@@ -144,10 +562,12 @@ struct GetPeerID {
 struct Dial {
     #[arg()]
     node_name: String,
-    #[arg()]
-    peer_id: String,
+    /// The address of the peer, it may end with `/p2p/<peer ID>`
     #[arg()]
     addr: String,
+    /// Connect only to the peer with the ID
+    #[arg(long)]
+    peer_id: Option<String>,
 }
 
 #[derive(Parser)]
@@ -156,6 +576,26 @@ struct PublishFile {
     node_name: String,
     #[arg()]
     path: PathBuf,
+    /// Only the contacts of the node can download the file
+    #[arg(long)]
+    contacts_only: bool,
+    /// Only the members of the group with the ID can download the file
+    #[arg(long, conflicts_with = "contacts_only")]
+    group: Option<String>,
+    /// Publish the file as a new revision of the object with the ID
+    #[arg(long, add = ArgValueCompleter::new(completion::complete_object_ids))]
+    previous: Option<String>,
+}
+
+#[derive(Parser)]
+struct PublishFiles {
+    #[arg()]
+    node_name: String,
+    #[arg(required = true)]
+    patterns: Vec<String>,
+    /// Maximum number of files published at the same time
+    #[arg(long, default_value_t = 4)]
+    max_concurrency: usize,
 }
 
 #[derive(Parser)]
@@ -164,6 +604,14 @@ struct GetPublishedObjects {
     node_name: String,
 }
 
+#[derive(Parser)]
+struct Mount {
+    #[arg(add = ArgValueCompleter::new(completion::complete_node_names))]
+    node_name: String,
+    #[arg()]
+    mountpoint: PathBuf,
+}
+
 #[derive(Parser)]
 struct DeleteObject {
     #[arg()]
@@ -172,152 +620,3475 @@ struct DeleteObject {
     object_id: String,
 }
 
-#[derive(Tabled)]
-struct NodeInfoRow {
-    pub name: String,
-    pub peer_id: String,
-    pub is_running: bool,
-    pub first_cfg_address: String,
-    pub first_run_address: String,
+#[derive(Parser)]
+struct QueryCmd {
+    #[arg(add = ArgValueCompleter::new(completion::complete_node_names))]
+    node_name: String,
+    /// The query as `{"uuid": "<type UUID>", "data": <any JSON>}`
+    #[arg()]
+    query_json: String,
 }
 
-#[derive(Tabled)]
-struct TypedObjectInfoRow {
-    pub id: String,
-    pub type_id: String,
+#[derive(Parser)]
+struct Tag {
+    #[arg(add = ArgValueCompleter::new(completion::complete_node_names))]
+    node_name: String,
+    #[arg(add = ArgValueCompleter::new(completion::complete_object_ids))]
+    id: String,
+    #[arg(required = true)]
+    tags: Vec<String>,
 }
 
-struct HandlerContext {
-    machine_readable: bool,
+#[derive(Parser)]
+struct Search {
+    #[arg(add = ArgValueCompleter::new(completion::complete_node_names))]
+    node_name: String,
+    /// The tags, separated by whitespace or commas
+    #[arg(required = true)]
+    query: Vec<String>,
+    /// Ask the peers too, not only the index of the node
+    #[arg(long)]
+    network: bool,
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    let path = Path::new("/tmp/liberum-core/");
-    let conn = liberum_core::connect(path.join("liberum-core-socket")).await;
+#[derive(Parser)]
+struct History {
+    #[arg(add = ArgValueCompleter::new(completion::complete_node_names))]
+    node_name: String,
+    #[arg(add = ArgValueCompleter::new(completion::complete_object_ids))]
+    id: String,
+}
 
-    let (request_sender, response_receiver) = match conn {
-        Ok(c) => c,
-        Err(e) => {
-            error!(
-                err = e.to_string(),
-                "Failed to connect to the core. Make sure the client is running!"
-            );
-            Err(anyhow!(e))?
-        }
-    };
+#[derive(Parser)]
+struct SearchText {
+    #[arg(add = ArgValueCompleter::new(completion::complete_node_names))]
+    node_name: String,
+    /// The words all the matching files contain
+    #[arg(required = true)]
+    query: Vec<String>,
+    /// The most matches shown, 20 if not given
+    #[arg(long)]
+    limit: Option<usize>,
+}
 
-    let cli = Cli::parse();
+#[derive(Parser)]
+struct ExportVault {
+    #[arg(add = ArgValueCompleter::new(completion::complete_node_names))]
+    node_name: String,
+    #[arg()]
+    output: PathBuf,
+}
 
-    if cli.debug_log {
-        tracing_subscriber::fmt()
-            .with_max_level(tracing::Level::DEBUG)
-            .init();
-    }
+#[derive(Parser)]
+struct ImportVault {
+    #[arg(add = ArgValueCompleter::new(completion::complete_node_names))]
+    node_name: String,
+    #[arg()]
+    path: PathBuf,
+}
 
-    let ctx = HandlerContext {
-        machine_readable: cli.machine_readable,
-    };
-    handle_command(ctx, cli.command, request_sender, response_receiver).await?;
+#[derive(Parser)]
+struct Verify {
+    #[arg(add = ArgValueCompleter::new(completion::complete_node_names))]
+    node_name: String,
+    #[arg(add = ArgValueCompleter::new(completion::complete_object_ids))]
+    object_id: String,
+    /// Peer ID of the publisher, its signature is verified in addition to the node's own
+    #[arg(long)]
+    signer: Option<String>,
+}
 
-    Ok(())
+#[derive(Parser)]
+struct ObjectInfoCmd {
+    #[arg(add = ArgValueCompleter::new(completion::complete_node_names))]
+    node_name: String,
+    #[arg(add = ArgValueCompleter::new(completion::complete_object_ids))]
+    object_id: String,
 }
 
-async fn handle_command(
-    ctx: HandlerContext,
-    cmd: Command,
-    req: RequestSender,
-    res: ReseponseReceiver,
-) -> Result<()> {
-    match cmd {
-        Command::NewNode(cmd) => handle_new_node(cmd, req, res).await,
-        Command::StartNode(cmd) => handle_start_node(cmd, req, res).await,
-        Command::ConfigNode(cmd) => handle_config_node(cmd, req, res).await,
-        Command::ListNodes => handle_list_nodes(ctx, req, res).await,
-        Command::GetNodeDetails(cmd) => handle_get_node_details(ctx, cmd, req, res).await,
-        Command::GetNodeAddresses(cmd) => handle_get_node_addresses(ctx, cmd, req, res).await,
-        Command::StopNode(cmd) => handle_stop_node(cmd, req, res).await,
-        Command::ProvideFile(cmd) => handle_provide_file(cmd, req, res).await,
-        Command::DownloadFile(cmd) => handle_download_file(cmd, req, res).await,
-        Command::GetProviders(cmd) => handle_get_providers(cmd, req, res).await,
-        Command::GetPeerID(cmd) => handle_get_peer_id(cmd, req, res).await,
-        Command::Dial(cmd) => handle_dial(cmd, req, res).await,
-        Command::PublishFile(cmd) => handle_publish_file(cmd, req, res).await,
-        Command::GetPublishedObjects(cmd) => handle_get_published_objects(ctx, cmd, req, res).await,
-        Command::DeleteObject(cmd) => handle_delete_object(cmd, req, res).await,
-    }
+#[derive(Parser)]
+struct Cat {
+    #[arg(add = ArgValueCompleter::new(completion::complete_node_names))]
+    node_name: String,
+    #[arg(add = ArgValueCompleter::new(completion::complete_object_ids))]
+    object_id: String,
+    /// tree or json
+    #[arg(long, default_value_t = RenderFormat::Tree)]
+    format: RenderFormat,
 }
 
-async fn handle_new_node(
-    cmd: NewNode,
-    req: RequestSender,
-    mut res: ReseponseReceiver,
-) -> Result<()> {
-    debug!(name = cmd.name, "Creating node");
-    req.send(DaemonRequest::NewNode {
-        node_name: cmd.name,
-        id_seed: cmd.id_seed,
-    })
-    .await
-    .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
+#[derive(Parser)]
+struct Completions {
+    #[arg()]
+    shell: Shell,
+}
 
-    handle_response(&mut res).await
+#[derive(Parser)]
+struct Replay {
+    #[arg()]
+    journal: PathBuf,
+    /// Prints all the recorded events, also the messages to the swarm and the
+    /// events other than the queries and the connections
+    #[arg(long)]
+    all: bool,
 }
 
-async fn handle_start_node(
-    cmd: StartNode,
-    req: RequestSender,
-    mut res: ReseponseReceiver,
-) -> Result<()> {
-    debug!(name = cmd.name, "Starting node");
-    req.send(DaemonRequest::StartNode {
-        node_name: cmd.name,
-    })
-    .await
-    .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
+#[derive(Parser)]
+struct StopProviding {
+    #[arg()]
+    node_name: String,
+    #[arg()]
+    object_id: String,
+    /// Keep the object in the vault of the node
+    #[arg(long)]
+    keep_in_vault: bool,
+}
 
-    handle_response(&mut res).await
+#[derive(Parser)]
+struct ExportIdentity {
+    #[arg(add = ArgValueCompleter::new(completion::complete_node_names))]
+    node_name: String,
+    #[arg()]
+    output: PathBuf,
+    /// Passphrase the keypair is encrypted with, asked for if not given
+    #[arg(long)]
+    passphrase: Option<String>,
 }
 
-async fn handle_config_node(
-    cmd: ConfigNode,
-    req: RequestSender,
+#[derive(Parser)]
+struct ImportIdentity {
+    #[arg()]
+    node_name: String,
+    #[arg()]
+    path: PathBuf,
+    /// Passphrase the keypair was encrypted with, asked for if not given
+    #[arg(long)]
+    passphrase: Option<String>,
+}
+
+#[derive(Parser)]
+struct UnlockNode {
+    #[arg(add = ArgValueCompleter::new(completion::complete_node_names))]
+    name: String,
+    /// Asked for if not given
+    #[arg(long)]
+    passphrase: Option<String>,
+}
+
+#[derive(Parser)]
+struct SetPassphrase {
+    #[arg(add = ArgValueCompleter::new(completion::complete_node_names))]
+    name: String,
+    /// Asked for if not given
+    #[arg(long, conflicts_with = "remove")]
+    passphrase: Option<String>,
+    /// Store the keypair unencrypted again
+    #[arg(long)]
+    remove: bool,
+}
+
+#[derive(Parser)]
+struct PingDaemon {
+    /// How long to wait for the answer
+    #[arg(long, default_value_t = 5000)]
+    timeout_ms: u64,
+}
+
+#[derive(Parser)]
+struct RenameNode {
+    #[arg(add = ArgValueCompleter::new(completion::complete_node_names))]
+    name: String,
+    #[arg()]
+    new_name: String,
+    /// Stop the node first if it is running
+    #[arg(long)]
+    force: bool,
+}
+
+#[derive(Parser)]
+struct RotateKey {
+    #[arg(add = ArgValueCompleter::new(completion::complete_node_names))]
+    name: String,
+    /// The passphrase of a protected node, the new keypair is encrypted with it
+    #[arg(long)]
+    passphrase: Option<String>,
+    /// Stop the node first if it is running
+    #[arg(long)]
+    force: bool,
+}
+
+#[derive(Parser)]
+struct DeleteNode {
+    #[arg(add = ArgValueCompleter::new(completion::complete_node_names))]
+    name: String,
+    /// Stop the node first if it is running
+    #[arg(long)]
+    force: bool,
+    /// Keep the vault, it is reused by a new node you create with the same name
+    #[arg(long)]
+    keep_vault: bool,
+}
+
+#[derive(Parser)]
+struct SetLogLevel {
+    /// One of off, error, warn, info, debug, trace
+    #[arg()]
+    level: String,
+    /// The module to set the level of, e.g. liberum_core::swarm_runner
+    #[arg(long, default_value = "liberum_core")]
+    target: String,
+}
+
+#[derive(Parser)]
+struct Logs {
+    /// Prints only the lines logged by the node
+    #[arg(long)]
+    node: Option<String>,
+    /// Keeps printing the lines as they are logged
+    #[arg(long, short)]
+    follow: bool,
+    /// Number of the recent lines to print
+    #[arg(long, short = 'n', default_value_t = 100)]
+    lines: usize,
+}
+
+#[derive(Parser)]
+struct SetProfile {
+    #[arg(add = ArgValueCompleter::new(completion::complete_node_names))]
+    node_name: String,
+    #[arg()]
+    display_name: String,
+    #[arg(long, default_value = "")]
+    description: String,
+    /// ID of the published object with the avatar image
+    #[arg(long)]
+    avatar: Option<String>,
+}
+
+#[derive(Parser)]
+struct GetProfile {
+    #[arg(add = ArgValueCompleter::new(completion::complete_node_names))]
+    node_name: String,
+    #[arg()]
+    peer_id: String,
+}
+
+#[derive(Parser)]
+struct AddContact {
+    #[arg(add = ArgValueCompleter::new(completion::complete_node_names))]
+    node_name: String,
+    #[arg()]
+    peer_id: String,
+    /// Name of the contact, the peer ID if not given
+    #[arg(long, default_value = "")]
+    alias: String,
+    /// Address the node dials the contact at when it starts
+    #[arg(long)]
+    address: Option<String>,
+    /// One of unknown, known and trusted
+    #[arg(long, default_value_t = TrustLevel::Known)]
+    trust: TrustLevel,
+}
+
+#[derive(Parser)]
+struct RemoveContact {
+    #[arg(add = ArgValueCompleter::new(completion::complete_node_names))]
+    node_name: String,
+    #[arg()]
+    peer_id: String,
+}
+
+#[derive(Parser)]
+struct ListContacts {
+    #[arg(add = ArgValueCompleter::new(completion::complete_node_names))]
+    node_name: String,
+}
+
+#[derive(Parser)]
+struct AddWatchDir {
+    #[arg(add = ArgValueCompleter::new(completion::complete_node_names))]
+    node_name: String,
+    #[arg()]
+    path: PathBuf,
+    /// Glob pattern of the files which are not published, e.g. `*.tmp`. Can be repeated
+    #[arg(long)]
+    ignore: Vec<String>,
+    /// Unpublish the previous version of a changed file
+    #[arg(long)]
+    unpublish_old: bool,
+}
+
+#[derive(Parser)]
+struct RemoveWatchDir {
+    #[arg(add = ArgValueCompleter::new(completion::complete_node_names))]
+    node_name: String,
+    #[arg()]
+    path: PathBuf,
+}
+
+#[derive(Parser)]
+struct ListWatchDirs {
+    #[arg(add = ArgValueCompleter::new(completion::complete_node_names))]
+    node_name: String,
+}
+
+#[derive(Parser)]
+struct SendMessage {
+    #[arg(add = ArgValueCompleter::new(completion::complete_node_names))]
+    node_name: String,
+    #[arg()]
+    peer_id: String,
+    #[arg(required_unless_present = "file")]
+    text: Option<String>,
+    /// Send the contents of the file instead of a text
+    #[arg(long, conflicts_with = "text")]
+    file: Option<PathBuf>,
+    /// Encrypt the message, so only the peer can read it
+    #[arg(long)]
+    encrypt: bool,
+}
+
+#[derive(Parser)]
+struct Inbox {
+    #[arg(add = ArgValueCompleter::new(completion::complete_node_names))]
+    node_name: String,
+}
+
+#[derive(Parser)]
+struct CreateGroup {
+    #[arg(add = ArgValueCompleter::new(completion::complete_node_names))]
+    node_name: String,
+    #[arg()]
+    group_name: String,
+}
+
+#[derive(Parser)]
+struct InviteToGroup {
+    #[arg(add = ArgValueCompleter::new(completion::complete_node_names))]
+    node_name: String,
+    #[arg()]
+    group_id: String,
+    #[arg()]
+    peer_id: String,
+    /// Number of days the membership is valid for
+    #[arg(long, default_value_t = 365)]
+    days: u64,
+}
+
+#[derive(Parser)]
+struct JoinGroup {
+    #[arg(add = ArgValueCompleter::new(completion::complete_node_names))]
+    node_name: String,
+    #[arg()]
+    invitation: String,
+}
+
+#[derive(Parser)]
+struct PostToGroup {
+    #[arg(add = ArgValueCompleter::new(completion::complete_node_names))]
+    node_name: String,
+    #[arg()]
+    group_id: String,
+    #[arg(required_unless_present = "file")]
+    text: Option<String>,
+    /// Post the contents of the file instead of a text
+    #[arg(long, conflicts_with = "text")]
+    file: Option<PathBuf>,
+}
+
+#[derive(Parser)]
+struct GroupFeed {
+    #[arg(add = ArgValueCompleter::new(completion::complete_node_names))]
+    node_name: String,
+    #[arg()]
+    group_id: String,
+}
+
+#[derive(Parser)]
+struct ScheduledTasks {
+    #[arg(add = ArgValueCompleter::new(completion::complete_node_names))]
+    node_name: String,
+}
+
+#[derive(Parser)]
+struct RunTask {
+    #[arg(add = ArgValueCompleter::new(completion::complete_node_names))]
+    node_name: String,
+    /// One of reprovide, replication, mailbox-fetch, bootstrap-health, cache-gc,
+    /// peer-exchange and stats-sample
+    #[arg()]
+    task: ScheduledTask,
+}
+
+#[derive(Parser)]
+struct PopularObjects {
+    #[arg(add = ArgValueCompleter::new(completion::complete_node_names))]
+    node_name: String,
+    /// Print only the given number of the most popular objects
+    #[arg(long)]
+    limit: Option<usize>,
+}
+
+#[derive(Parser)]
+struct AuditLog {
+    #[arg(add = ArgValueCompleter::new(completion::complete_node_names))]
+    node_name: String,
+    /// Only the requests of the peer
+    #[arg(long)]
+    peer: Option<String>,
+    /// Only the requests for the object
+    #[arg(long, add = ArgValueCompleter::new(completion::complete_object_ids))]
+    object: Option<String>,
+    /// One of get, store and delete
+    #[arg(long)]
+    kind: Option<AuditRequestKind>,
+    /// Only the requests from the last number of seconds
+    #[arg(long)]
+    since_secs: Option<u64>,
+    /// Only the rejected requests
+    #[arg(long)]
+    rejected: bool,
+    /// Print only the newest requests, up to the number
+    #[arg(long)]
+    limit: Option<usize>,
+}
+
+#[derive(Parser)]
+struct StatsHistory {
+    #[arg(add = ArgValueCompleter::new(completion::complete_node_names))]
+    node_name: String,
+    /// Only the stats from the last number of seconds
+    #[arg(long, default_value_t = 24 * 60 * 60)]
+    since_secs: u64,
+}
+
+#[derive(Parser)]
+struct CloneNode {
+    #[arg(add = ArgValueCompleter::new(completion::complete_node_names))]
+    source: String,
+    #[arg()]
+    target: String,
+    /// Copy also the keypair. Both nodes can't be run in the same network at once
+    #[arg(long)]
+    keep_identity: bool,
+}
+
+#[derive(Tabled)]
+struct NodeInfoRow {
+    pub name: String,
+    pub peer_id: String,
+    pub is_running: bool,
+    pub is_locked: bool,
+    pub first_cfg_address: String,
+    pub first_run_address: String,
+}
+
+#[derive(Tabled)]
+struct NodeResultRow {
+    pub node: String,
+    pub result: String,
+}
+
+#[derive(Tabled)]
+struct NodeStatusRow {
+    pub property: String,
+    pub value: String,
+}
+
+#[derive(Tabled)]
+struct PeerLatencyRow {
+    pub peer_id: String,
+    pub latency_ms: u128,
+}
+
+#[derive(Tabled)]
+struct BucketInfoRow {
+    pub bucket: u32,
+    pub entries: usize,
+    pub connected: usize,
+}
+
+#[derive(Tabled)]
+struct RoutingPeerRow {
+    pub peer_id: String,
+    pub bucket: String,
+    pub connected: bool,
+    pub latency_ms: String,
+    pub addresses: String,
+}
+
+#[derive(Tabled)]
+struct PeerScoreRow {
+    pub peer_id: String,
+    pub failed_integrity_checks: u32,
+    pub timeouts: u32,
+    pub protocol_violations: u32,
+}
+
+#[derive(Tabled)]
+struct ContactRow {
+    pub alias: String,
+    pub peer_id: String,
+    pub trust: String,
+    pub address: String,
+    pub last_seen: String,
+}
+
+#[derive(Tabled)]
+struct WatchDirRow {
+    pub path: String,
+    pub ignore: String,
+    pub unpublish_old: bool,
+}
+
+#[derive(Tabled)]
+struct InboxMessageRow {
+    pub id: String,
+    pub sender: String,
+    pub received: String,
+    pub encrypted: bool,
+    pub content: String,
+}
+
+#[derive(Tabled)]
+struct GroupPostRow {
+    pub id: String,
+    pub author: String,
+    pub posted: String,
+    pub content: String,
+}
+
+#[derive(Tabled)]
+struct ScheduledTaskRow {
+    pub task: String,
+    pub interval: String,
+    pub next_run: String,
+    pub runs: u64,
+    pub last_run: String,
+    pub result: String,
+}
+
+#[derive(Tabled)]
+struct SearchResultRow {
+    pub id: String,
+    pub matched_tags: String,
+    pub local: bool,
+}
+
+#[derive(Tabled)]
+struct HistoryRow {
+    pub revision: usize,
+    pub id: String,
+}
+
+#[derive(Tabled)]
+struct TextMatchRow {
+    pub id: String,
+    pub name: String,
+    pub snippet: String,
+}
+
+#[derive(Tabled)]
+struct PopularObjectRow {
+    pub id: String,
+    pub fetches: u64,
+    pub last_served: String,
+    pub published: bool,
+}
+
+#[derive(Tabled)]
+struct AuditEntryRow {
+    pub at: String,
+    pub kind: String,
+    pub peer_id: String,
+    pub object_id: String,
+    pub decision: String,
+}
+
+#[derive(Tabled)]
+struct ObjectLayerRow {
+    pub depth: usize,
+    pub type_name: String,
+    pub size: u64,
+    pub fields: String,
+}
+
+#[derive(Tabled)]
+struct StatsSampleRow {
+    pub at: String,
+    pub connected_peers: usize,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub objects_served: u64,
+}
+
+#[derive(Tabled)]
+struct QuerySpanRow {
+    pub id: String,
+    pub kind: String,
+    pub started: String,
+    pub duration: String,
+    pub steps: usize,
+    pub result: String,
+}
+
+#[derive(Tabled)]
+struct ConnectionSpanRow {
+    pub peer_id: String,
+    pub address: String,
+    pub direction: String,
+    pub opened: String,
+    pub duration: String,
+    pub cause: String,
+}
+
+#[derive(Tabled)]
+struct PublishFileResultRow {
+    pub path: String,
+    pub id: String,
+    pub error: String,
+}
+
+#[derive(Tabled)]
+struct ModuleInfoRow {
+    pub name: String,
+    pub uuid: String,
+    pub enabled: bool,
+    pub settings: String,
+}
+
+#[derive(Tabled)]
+struct TypedObjectInfoRow {
+    pub id: String,
+    pub type_id: String,
+}
+
+#[derive(Clone, Copy)]
+struct HandlerContext {
+    machine_readable: bool,
+    json: bool,
+}
+
+fn main() -> Result<()> {
+    // Must be called before anything else, when the CLI is called by the shell
+    // to complete the arguments it prints the completions and exits
+    CompleteEnv::with_factory(Cli::command).complete();
+
+    let cli = Cli::parse();
+
+    if let Command::Completions(cmd) = &cli.command {
+        generate(
+            cmd.shell,
+            &mut Cli::command(),
+            "liberum_cli",
+            &mut io::stdout(),
+        );
+        return Ok(());
+    }
+
+    if let Command::Replay(cmd) = &cli.command {
+        let ctx = HandlerContext {
+            machine_readable: cli.machine_readable,
+            json: cli.json,
+        };
+        return handle_replay(ctx, cmd);
+    }
+
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?
+        .block_on(run(cli))
+}
+
+async fn run(cli: Cli) -> Result<()> {
+    let conn = liberum_core::connect(daemon_config::client_socket_path()).await;
+
+    let (request_sender, mut response_receiver) = match conn {
+        Ok(c) => c,
+        Err(e) => {
+            error!(
+                err = e.to_string(),
+                "Failed to connect to the core. Make sure the client is running!"
+            );
+            Err(anyhow!(e))?
+        }
+    };
+
+    if cli.debug_log {
+        tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::DEBUG)
+            .init();
+    }
+
+    if let Some(token) = cli.token {
+        request_sender
+            .send(DaemonRequest::Authenticate { token })
+            .await?;
+        match response_receiver.recv().await {
+            Some(Ok(DaemonResponse::Authenticated { role })) => {
+                debug!(role = role.to_string(), "Authenticated")
+            }
+            Some(Err(e)) => return Err(e.into()),
+            _ => return Err(anyhow!("Failed to authenticate")),
+        }
+    }
+
+    let ctx = HandlerContext {
+        machine_readable: cli.machine_readable,
+        json: cli.json,
+    };
+    handle_command(ctx, cli.command, request_sender, response_receiver).await?;
+
+    Ok(())
+}
+
+async fn handle_command(
+    ctx: HandlerContext,
+    cmd: Command,
+    req: RequestSender,
+    res: ReseponseReceiver,
+) -> Result<()> {
+    match cmd {
+        Command::NewNode(cmd) => handle_new_node(ctx, cmd, req, res).await,
+        Command::StartNode(cmd) => handle_start_node(ctx, cmd, req, res).await,
+        Command::ConfigNode(cmd) => handle_config_node(ctx, cmd, req, res).await,
+        Command::ListNodes => handle_list_nodes(ctx, req, res).await,
+        Command::GetNodeDetails(cmd) => handle_get_node_details(ctx, cmd, req, res).await,
+        Command::GetNodeAddresses(cmd) => handle_get_node_addresses(ctx, cmd, req, res).await,
+        Command::NodeStatus(cmd) => handle_node_status(ctx, cmd, req, res).await,
+        Command::GetPeerScores(cmd) => handle_get_peer_scores(ctx, cmd, req, res).await,
+        Command::ResetPeerScore(cmd) => handle_reset_peer_score(ctx, cmd, req, res).await,
+        Command::RoutingTable(cmd) => handle_routing_table(ctx, cmd, req, res).await,
+        Command::StopNode(cmd) => handle_stop_node(ctx, cmd, req, res).await,
+        Command::ReloadNodes(cmd) => handle_reload_nodes(ctx, cmd, req, res).await,
+        Command::ProvideFile(cmd) => handle_provide_file(ctx, cmd, req, res).await,
+        Command::DownloadFile(cmd) => handle_download_file(ctx, cmd, req, res).await,
+        Command::GetProviders(cmd) => handle_get_providers(ctx, cmd, req, res).await,
+        Command::GetPeerID(cmd) => handle_get_peer_id(ctx, cmd, req, res).await,
+        Command::Dial(cmd) => handle_dial(ctx, cmd, req, res).await,
+        Command::PublishFile(cmd) => handle_publish_file(ctx, cmd, req, res).await,
+        Command::PublishFiles(cmd) => handle_publish_files(ctx, cmd, req, res).await,
+        Command::GetPublishedObjects(cmd) => handle_get_published_objects(ctx, cmd, req, res).await,
+        Command::DeleteObject(cmd) => handle_delete_object(ctx, cmd, req, res).await,
+        Command::StopProviding(cmd) => handle_stop_providing(ctx, cmd, req, res).await,
+        Command::Verify(cmd) => handle_verify(ctx, cmd, req, res).await,
+        Command::ObjectInfo(cmd) => handle_object_info(ctx, cmd, req, res).await,
+        Command::Cat(cmd) => handle_cat(ctx, cmd, req, res).await,
+        Command::Mount(cmd) => handle_mount(cmd, req, res).await,
+        Command::ExportIdentity(cmd) => handle_export_identity(ctx, cmd, req, res).await,
+        Command::ImportIdentity(cmd) => handle_import_identity(ctx, cmd, req, res).await,
+        Command::UnlockNode(cmd) => handle_unlock_node(ctx, cmd, req, res).await,
+        Command::SetPassphrase(cmd) => handle_set_passphrase(ctx, cmd, req, res).await,
+        Command::Watch => handle_watch(ctx).await,
+        Command::DaemonConfig => handle_daemon_config(ctx, req, res).await,
+        Command::PingDaemon(cmd) => handle_ping_daemon(ctx, cmd, req, res).await,
+        Command::RenameNode(cmd) => handle_rename_node(ctx, cmd, req, res).await,
+        Command::RotateKey(cmd) => handle_rotate_key(ctx, cmd, req, res).await,
+        Command::DeleteNode(cmd) => handle_delete_node(ctx, cmd, req, res).await,
+        Command::CloneNode(cmd) => handle_clone_node(ctx, cmd, req, res).await,
+        Command::Query(cmd) => handle_query(ctx, cmd, req, res).await,
+        Command::Tag(cmd) => handle_tag(ctx, cmd, req, res).await,
+        Command::Search(cmd) => handle_search(ctx, cmd, req, res).await,
+        Command::SearchText(cmd) => handle_search_text(ctx, cmd, req, res).await,
+        Command::History(cmd) => handle_history(ctx, cmd, req, res).await,
+        Command::ExportVault(cmd) => handle_export_vault(ctx, cmd, req, res).await,
+        Command::ImportVault(cmd) => handle_import_vault(ctx, cmd, req, res).await,
+        Command::SetLogLevel(cmd) => handle_set_log_level(ctx, cmd, req, res).await,
+        Command::Logs(cmd) => handle_logs(ctx, cmd, req, res).await,
+        Command::SetProfile(cmd) => handle_set_profile(ctx, cmd, req, res).await,
+        Command::GetProfile(cmd) => handle_get_profile(ctx, cmd, req, res).await,
+        Command::AddContact(cmd) => handle_add_contact(ctx, cmd, req, res).await,
+        Command::RemoveContact(cmd) => handle_remove_contact(ctx, cmd, req, res).await,
+        Command::ListContacts(cmd) => handle_list_contacts(ctx, cmd, req, res).await,
+        Command::AddWatchDir(cmd) => handle_add_watch_dir(ctx, cmd, req, res).await,
+        Command::RemoveWatchDir(cmd) => handle_remove_watch_dir(ctx, cmd, req, res).await,
+        Command::ListWatchDirs(cmd) => handle_list_watch_dirs(ctx, cmd, req, res).await,
+        Command::SendMessage(cmd) => handle_send_message(ctx, cmd, req, res).await,
+        Command::Inbox(cmd) => handle_inbox(ctx, cmd, req, res).await,
+        Command::CreateGroup(cmd) => handle_create_group(ctx, cmd, req, res).await,
+        Command::InviteToGroup(cmd) => handle_invite_to_group(ctx, cmd, req, res).await,
+        Command::JoinGroup(cmd) => handle_join_group(ctx, cmd, req, res).await,
+        Command::PostToGroup(cmd) => handle_post_to_group(ctx, cmd, req, res).await,
+        Command::GroupFeed(cmd) => handle_group_feed(ctx, cmd, req, res).await,
+        Command::AuditLog(cmd) => handle_audit_log(ctx, cmd, req, res).await,
+        Command::StatsHistory(cmd) => handle_stats_history(ctx, cmd, req, res).await,
+        Command::ScheduledTasks(cmd) => handle_scheduled_tasks(ctx, cmd, req, res).await,
+        Command::RunTask(cmd) => handle_run_task(ctx, cmd, req, res).await,
+        Command::PopularObjects(cmd) => handle_popular_objects(ctx, cmd, req, res).await,
+        Command::Completions(_) => unreachable!("completions are generated before connecting"),
+        Command::Replay(_) => unreachable!("journals are replayed before connecting"),
+    }
+}
+
+async fn handle_new_node(
+    ctx: HandlerContext,
+    cmd: NewNode,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    debug!(name = cmd.name, "Creating node");
+    req.send(DaemonRequest::NewNode {
+        node_name: cmd.name,
+        id_seed: cmd.id_seed,
+    })
+    .await
+    .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
+
+    handle_response(ctx, &mut res).await
+}
+
+async fn handle_start_node(
+    ctx: HandlerContext,
+    cmd: StartNode,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    let Some(name) = cmd.nodes.single() else {
+        return handle_batch(ctx, cmd.nodes, NodeOperation::Start, req, res).await;
+    };
+    debug!(name = name, "Starting node");
+    req.send(DaemonRequest::StartNode {
+        node_name: name.to_string(),
+    })
+    .await
+    .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
+
+    handle_response(ctx, &mut res).await
+}
+
+async fn handle_config_node(
+    ctx: HandlerContext,
+    cmd: ConfigNode,
+    req: RequestSender,
+    res: ReseponseReceiver,
+) -> Result<()> {
+    match cmd.subcommand {
+        ConfigNodeCommand::AddBootstrapNode(sub_cmd) => {
+            handle_add_bootstrap_node(ctx, &cmd.name, sub_cmd, req, res).await?
+        }
+        ConfigNodeCommand::AddBootstrapDnsSeed(sub_cmd) => {
+            handle_add_bootstrap_dns_seed(ctx, &cmd.name, sub_cmd, req, res).await?
+        }
+        ConfigNodeCommand::AddExternalAddr(sub_cmd) => {
+            handle_add_external_addr(ctx, &cmd.name, sub_cmd, req, res).await?
+        }
+        ConfigNodeCommand::BlockPeer(sub_cmd) => {
+            handle_block_peer(ctx, &cmd.name, sub_cmd, req, res).await?
+        }
+        ConfigNodeCommand::UnblockPeer(sub_cmd) => {
+            handle_unblock_peer(ctx, &cmd.name, sub_cmd, req, res).await?
+        }
+        ConfigNodeCommand::Reload => apply_config(ctx, &cmd.name, req, res).await?,
+        ConfigNodeCommand::ListModules => handle_list_modules(ctx, &cmd.name, req, res).await?,
+        ConfigNodeCommand::EnableModule(sub_cmd) => {
+            handle_set_module_enabled(ctx, &cmd.name, sub_cmd, true, req, res).await?
+        }
+        ConfigNodeCommand::DisableModule(sub_cmd) => {
+            handle_set_module_enabled(ctx, &cmd.name, sub_cmd, false, req, res).await?
+        }
+        ConfigNodeCommand::SetTaskInterval(sub_cmd) => {
+            handle_set_task_interval(ctx, &cmd.name, sub_cmd, req, res).await?
+        }
+        ConfigNodeCommand::SetAddressPolicy(sub_cmd) => {
+            handle_set_address_policy(ctx, &cmd.name, sub_cmd, req, res).await?
+        }
+        ConfigNodeCommand::SetUploadLimits(sub_cmd) => {
+            handle_set_upload_limits(ctx, &cmd.name, sub_cmd, req, res).await?
+        }
+        ConfigNodeCommand::SetCacheLimit(sub_cmd) => {
+            handle_set_cache_limit(ctx, &cmd.name, sub_cmd, req, res).await?
+        }
+        ConfigNodeCommand::SetTextIndex(sub_cmd) => {
+            handle_set_text_index(ctx, &cmd.name, sub_cmd, req, res).await?
+        }
+        ConfigNodeCommand::SetChunking(sub_cmd) => {
+            handle_set_chunking(ctx, &cmd.name, sub_cmd, req, res).await?
+        }
+        ConfigNodeCommand::SetPeerExchange(sub_cmd) => {
+            handle_set_peer_exchange(ctx, &cmd.name, sub_cmd, req, res).await?
+        }
+        ConfigNodeCommand::SetNetworkKey(sub_cmd) => {
+            handle_set_network_key(ctx, &cmd.name, sub_cmd, req, res).await?
+        }
+        ConfigNodeCommand::SetRestartPolicy(sub_cmd) => {
+            handle_set_restart_policy(ctx, &cmd.name, sub_cmd, req, res).await?
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_list_nodes(
+    ctx: HandlerContext,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    debug!("Listing nodes...");
+    req.send(DaemonRequest::ListNodes)
+        .await
+        .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
+
+    let response = res
+        .recv()
+        .await
+        .ok_or(anyhow!("Daemon returned no response"))?;
+    if ctx.json {
+        return print_json(&response);
+    }
+
+    let node_infos = response?;
+
+    match node_infos {
+        DaemonResponse::NodeList(node_infos) => {
+            let node_info_rows = node_infos
+                .iter()
+                .map(|info| info.into())
+                .collect::<Vec<NodeInfoRow>>();
+            let mut table = Table::new(node_info_rows);
+
+            if ctx.machine_readable {
+                table.with(Style::blank());
+            } else {
+                table.with(Style::modern());
+            }
+
+            let table = table.to_string();
+            println!("{table}");
+        }
+        _ => {
+            bail!("Daemon returned wrong response");
+        }
+    }
+
+    if !ctx.machine_readable {
+        req.send(DaemonRequest::Ping).await?;
+        if let Some(Ok(DaemonResponse::Pong(health))) = res.recv().await {
+            println!(
+                "Daemon {} (protocol {})",
+                health.version, health.protocol_version
+            );
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_get_node_details(
+    ctx: HandlerContext,
+    cmd: GetNodeDetails,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    let Some(name) = cmd.nodes.single() else {
+        return handle_batch(ctx, cmd.nodes, NodeOperation::GetDetails, req, res).await;
+    };
+    req.send(DaemonRequest::GetNodeDetails {
+        node_name: name.to_string(),
+    })
+    .await
+    .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
+
+    let response = res
+        .recv()
+        .await
+        .ok_or(anyhow!("Daemon returned no response"))?;
+    if ctx.json {
+        return print_json(&response);
+    }
+
+    let node_infos = response?;
+
+    match node_infos {
+        DaemonResponse::NodeDetails(details) => {
+            let details_row = vec![NodeInfoRow::from(&details)];
+            let mut table = Table::new(details_row);
+
+            if ctx.machine_readable {
+                table.with(Style::blank());
+            } else {
+                table.with(Style::modern());
+            }
+
+            let table = table.to_string();
+            println!("{table}");
+
+            if details.peer_latencies.is_empty() {
+                return Ok(());
+            }
+
+            let mut latency_rows = details
+                .peer_latencies
+                .iter()
+                .map(|(peer_id, latency)| PeerLatencyRow {
+                    peer_id: peer_id.clone(),
+                    latency_ms: latency.as_millis(),
+                })
+                .collect::<Vec<_>>();
+            latency_rows.sort_by_key(|r| r.latency_ms);
+            let mut table = Table::new(latency_rows);
+
+            if ctx.machine_readable {
+                table.with(Style::blank());
+            } else {
+                table.with(Style::modern());
+            }
+
+            println!("{table}");
+        }
+        _ => {
+            bail!("Daemon returned wrong response");
+        }
+    }
+
+    Ok(())
+}
+
+/// Applies the operation to the nodes at once and prints the result of every one.
+/// Fails if any of the nodes failed
+async fn handle_batch(
+    ctx: HandlerContext,
+    nodes: NodeNames,
+    operation: NodeOperation,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    let request = match (nodes.group, nodes.all_nodes) {
+        (Some(group), _) => DaemonRequest::BatchGroup { group, operation },
+        (None, true) => DaemonRequest::BatchNodes {
+            node_names: vec![],
+            operation,
+        },
+        (None, false) => DaemonRequest::BatchNodes {
+            node_names: nodes.names,
+            operation,
+        },
+    };
+    req.send(request)
+        .await
+        .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
+
+    let response = res
+        .recv()
+        .await
+        .ok_or(anyhow!("Daemon returned no response"))?;
+    let failed = match &response {
+        Ok(DaemonResponse::BatchResults(results)) => {
+            results.iter().filter(|r| r.result.is_err()).count()
+        }
+        _ => 0,
+    };
+    if ctx.json {
+        print_json(&response)?;
+    } else {
+        let DaemonResponse::BatchResults(results) = response? else {
+            bail!("Daemon returned wrong response");
+        };
+        let rows = results.into_iter().map(|r| match r.result {
+            Ok(DaemonResponse::NodeDetails(details)) => NodeResultRow {
+                node: r.node_name,
+                result: format!(
+                    "running: {}, peer ID: {}",
+                    details.is_running, details.peer_id
+                ),
+            },
+            Ok(_) => NodeResultRow {
+                node: r.node_name,
+                result: "ok".to_string(),
+            },
+            Err(e) => NodeResultRow {
+                node: r.node_name,
+                result: e.to_string(),
+            },
+        });
+        let mut table = Table::new(rows);
+        if ctx.machine_readable {
+            table.with(Style::blank());
+        } else {
+            table.with(Style::modern());
+        }
+        println!("{table}");
+    }
+
+    if failed > 0 {
+        bail!("The operation failed on {failed} of the nodes");
+    }
+    Ok(())
+}
+
+async fn handle_get_node_addresses(
+    ctx: HandlerContext,
+    cmd: GetNodeAddresses,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    req.send(DaemonRequest::GetNodeDetails {
+        node_name: cmd.name,
+    })
+    .await
+    .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
+
+    let response = res
+        .recv()
+        .await
+        .ok_or(anyhow!("Daemon returned no response"))?;
+    if ctx.json {
+        return print_json(&response);
+    }
+
+    let node_infos = response?;
+
+    match node_infos {
+        DaemonResponse::NodeDetails(details) => {
+            for addr in details.running_addresses {
+                println!("{}", addr);
+            }
+        }
+        _ => {
+            bail!("Daemon returned wrong response");
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_node_status(
+    ctx: HandlerContext,
+    cmd: NodeStatusCmd,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    req.send(DaemonRequest::GetNodeStatus {
+        node_name: cmd.name,
+    })
+    .await
+    .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
+
+    let response = res
+        .recv()
+        .await
+        .ok_or(anyhow!("Daemon returned no response"))?;
+    if ctx.json {
+        return print_json(&response);
+    }
+
+    let response = response?;
+
+    match response {
+        DaemonResponse::NodeStatus(status) => {
+            let mut table = Table::new(node_status_rows(&status));
+            let mut buckets_table = Table::new(
+                status
+                    .buckets
+                    .iter()
+                    .map(|b| b.into())
+                    .collect::<Vec<BucketInfoRow>>(),
+            );
+
+            if ctx.machine_readable {
+                table.with(Style::blank());
+                buckets_table.with(Style::blank());
+            } else {
+                table.with(Style::modern());
+                buckets_table.with(Style::modern());
+            }
+
+            println!("{table}");
+            println!("{buckets_table}");
+        }
+        _ => {
+            bail!("Daemon returned wrong response");
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_routing_table(
+    ctx: HandlerContext,
+    cmd: RoutingTableCmd,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    req.send(DaemonRequest::GetRoutingTable {
+        node_name: cmd.node_name,
+    })
+    .await
+    .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
+
+    let response = res
+        .recv()
+        .await
+        .ok_or(anyhow!("Daemon returned no response"))?;
+    if ctx.json {
+        return print_json(&response);
+    }
+
+    let response = response?;
+
+    match response {
+        DaemonResponse::RoutingTable { buckets, peers } => {
+            let mut buckets_table = Table::new(
+                buckets
+                    .iter()
+                    .map(|b| b.into())
+                    .collect::<Vec<BucketInfoRow>>(),
+            );
+            let mut peers_table = Table::new(
+                peers
+                    .iter()
+                    .map(|p| p.into())
+                    .collect::<Vec<RoutingPeerRow>>(),
+            );
+
+            if ctx.machine_readable {
+                buckets_table.with(Style::blank());
+                peers_table.with(Style::blank());
+            } else {
+                buckets_table.with(Style::modern());
+                peers_table.with(Style::modern());
+            }
+
+            println!("{buckets_table}");
+            println!("{peers_table}");
+        }
+        _ => {
+            bail!("Daemon returned wrong response");
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_get_peer_scores(
+    ctx: HandlerContext,
+    cmd: GetPeerScores,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    req.send(DaemonRequest::GetPeerScores {
+        node_name: cmd.node_name,
+    })
+    .await
+    .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
+
+    let response = res
+        .recv()
+        .await
+        .ok_or(anyhow!("Daemon returned no response"))?;
+    if ctx.json {
+        return print_json(&response);
+    }
+
+    let response = response?;
+
+    match response {
+        DaemonResponse::PeerScores { scores } => {
+            let score_rows = scores
+                .iter()
+                .map(|score| score.into())
+                .collect::<Vec<PeerScoreRow>>();
+            let mut table = Table::new(score_rows);
+
+            if ctx.machine_readable {
+                table.with(Style::blank());
+            } else {
+                table.with(Style::modern());
+            }
+
+            let table = table.to_string();
+            println!("{table}");
+        }
+        _ => {
+            bail!("Daemon returned wrong response");
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_add_bootstrap_node(
+    ctx: HandlerContext,
+    name: &str,
+    cmd: AddBootstrapNode,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    debug!(name = name, "Adding bootstrap node");
+    let mut config = get_current_config(name, &req, &mut res).await?;
+    let new_bootstrap_node = BootstrapNode::from_strings(&cmd.id, &cmd.addr)?;
+    config.bootstrap_nodes.push(new_bootstrap_node);
+
+    if cmd.live {
+        return save_and_apply_config(ctx, name, config, req, res).await;
+    }
+
+    req.send(DaemonRequest::OverwriteNodeConfig {
+        node_name: name.to_string(),
+        new_cfg: config,
+    })
+    .await?;
+
+    handle_response(ctx, &mut res).await
+}
+
+async fn handle_add_bootstrap_dns_seed(
+    ctx: HandlerContext,
+    name: &str,
+    sub_cmd: AddBootstrapDnsSeed,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    debug!(name = name, "Adding bootstrap DNS seed");
+    let mut config = get_current_config(name, &req, &mut res).await?;
+    if !config.bootstrap_dns_seeds.contains(&sub_cmd.hostname) {
+        config.bootstrap_dns_seeds.push(sub_cmd.hostname);
+    }
+
+    req.send(DaemonRequest::OverwriteNodeConfig {
+        node_name: name.to_string(),
+        new_cfg: config,
+    })
+    .await?;
+
+    handle_response(ctx, &mut res).await
+}
+
+async fn handle_add_external_addr(
+    ctx: HandlerContext,
+    name: &str,
+    sub_cmd: AddExternalAddr,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    debug!(name = name, "Adding external address");
+    let mut config = get_current_config(name, &req, &mut res).await?;
+    let new_external_addr = Multiaddr::from_str(&sub_cmd.addr)?;
+    config.external_addresses.push(new_external_addr);
+
+    if sub_cmd.live {
+        return save_and_apply_config(ctx, name, config, req, res).await;
+    }
+
+    req.send(DaemonRequest::OverwriteNodeConfig {
+        node_name: name.to_string(),
+        new_cfg: config,
+    })
+    .await?;
+
+    handle_response(ctx, &mut res).await
+}
+
+/// Saves the config and applies it to the node if it is running. A stopped node
+/// uses the saved config when it starts
+async fn save_and_apply_config(
+    ctx: HandlerContext,
+    name: &str,
+    config: NodeConfig,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    debug!(name = name, "Saving node config");
+    req.send(DaemonRequest::OverwriteNodeConfig {
+        node_name: name.to_string(),
+        new_cfg: config,
+    })
+    .await?;
+
+    let response = res
+        .recv()
+        .await
+        .ok_or(anyhow!("Daemon returned no response"))?;
+    if response.is_err() && ctx.json {
+        return print_json(&response);
+    }
+    let saved = response?;
+
+    match reload_config(name, &req, &mut res).await? {
+        Err(DaemonError::NodeNotRunning(_)) => {
+            if ctx.json {
+                return print_json(&Ok(saved));
+            }
+            println!("The config is saved, the node is not running so it applies when it starts");
+            Ok(())
+        }
+        response => print_reload_summary(ctx, response),
+    }
+}
+
+/// Applies the stored config to the running node
+async fn apply_config(
+    ctx: HandlerContext,
+    name: &str,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    let response = reload_config(name, &req, &mut res).await?;
+    print_reload_summary(ctx, response)
+}
+
+async fn reload_config(
+    name: &str,
+    req: &RequestSender,
+    res: &mut ReseponseReceiver,
+) -> Result<Result<DaemonResponse, DaemonError>> {
+    debug!(name = name, "Reloading node config");
+    req.send(DaemonRequest::ReloadNodeConfig {
+        node_name: name.to_string(),
+        new_cfg: None,
+    })
+    .await?;
+
+    res.recv()
+        .await
+        .ok_or(anyhow!("Daemon returned no response"))
+}
+
+fn print_reload_summary(
+    ctx: HandlerContext,
+    response: Result<DaemonResponse, DaemonError>,
+) -> Result<()> {
+    if ctx.json {
+        return print_json(&response);
+    }
+
+    match response? {
+        DaemonResponse::NodeConfigReloaded(summary) => {
+            let rows = [
+                ("added_bootstrap_nodes", &summary.added_bootstrap_nodes),
+                ("removed_bootstrap_nodes", &summary.removed_bootstrap_nodes),
+                ("added_listen_addresses", &summary.added_listen_addresses),
+                (
+                    "removed_listen_addresses",
+                    &summary.removed_listen_addresses,
+                ),
+                ("errors", &summary.errors),
+            ]
+            .into_iter()
+            .map(|(property, values)| NodeStatusRow {
+                property: property.to_string(),
+                value: values.join(", "),
+            })
+            .collect::<Vec<_>>();
+            let mut table = Table::new(rows);
+
+            if ctx.machine_readable {
+                table.with(Style::blank());
+            } else {
+                table.with(Style::modern());
+            }
+
+            println!("{table}");
+            Ok(())
+        }
+        _ => {
+            bail!("Daemon returned wrong response");
+        }
+    }
+}
+
+async fn handle_block_peer(
+    ctx: HandlerContext,
+    name: &str,
+    sub_cmd: BlockPeer,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    debug!(name = name, peer_id = sub_cmd.peer_id, "Blocking peer");
+    req.send(DaemonRequest::BlockPeer {
+        node_name: name.to_string(),
+        peer_id: sub_cmd.peer_id,
+    })
+    .await?;
+
+    handle_response(ctx, &mut res).await
+}
+
+async fn handle_unblock_peer(
+    ctx: HandlerContext,
+    name: &str,
+    sub_cmd: UnblockPeer,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    debug!(name = name, peer_id = sub_cmd.peer_id, "Unblocking peer");
+    req.send(DaemonRequest::UnblockPeer {
+        node_name: name.to_string(),
+        peer_id: sub_cmd.peer_id,
+    })
+    .await?;
+
+    handle_response(ctx, &mut res).await
+}
+
+async fn handle_list_modules(
+    ctx: HandlerContext,
+    name: &str,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    req.send(DaemonRequest::ListModules {
+        node_name: name.to_string(),
+    })
+    .await?;
+
+    let resp = res
+        .recv()
+        .await
+        .ok_or(anyhow!("Daemon returned no response"))?;
+    if ctx.json {
+        return print_json(&resp);
+    }
+
+    match resp {
+        Ok(DaemonResponse::Modules(modules)) => {
+            let rows = modules
+                .iter()
+                .map(|module| module.into())
+                .collect::<Vec<ModuleInfoRow>>();
+            let mut table = Table::new(rows);
+
+            if ctx.machine_readable {
+                table.with(Style::blank());
+            } else {
+                table.with(Style::modern());
+            }
+
+            println!("{table}");
+        }
+        Err(e) => {
+            println!("Error getting modules: {e}");
+            bail!("Error getting modules");
+        }
+        _ => {
+            bail!("Daemon returned wrong response");
+        }
+    }
+    Ok(())
+}
+
+async fn handle_set_module_enabled(
+    ctx: HandlerContext,
+    name: &str,
+    sub_cmd: ModuleArg,
+    enabled: bool,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    debug!(
+        name = name,
+        module = sub_cmd.module,
+        enabled,
+        "Toggling module"
+    );
+    req.send(DaemonRequest::SetModuleEnabled {
+        node_name: name.to_string(),
+        module: sub_cmd.module,
+        enabled,
+    })
+    .await?;
+
+    handle_response(ctx, &mut res).await
+}
+
+async fn handle_set_task_interval(
+    ctx: HandlerContext,
+    name: &str,
+    sub_cmd: SetTaskInterval,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    debug!(
+        name = name,
+        task = sub_cmd.task.to_string(),
+        "Setting task interval"
+    );
+    let mut config = get_current_config(name, &req, &mut res).await?;
+    config
+        .schedule
+        .intervals
+        .insert(sub_cmd.task, sub_cmd.interval_secs);
+
+    if sub_cmd.live {
+        return save_and_apply_config(ctx, name, config, req, res).await;
+    }
+
+    req.send(DaemonRequest::OverwriteNodeConfig {
+        node_name: name.to_string(),
+        new_cfg: config,
+    })
+    .await?;
+
+    handle_response(ctx, &mut res).await
+}
+
+async fn handle_set_address_policy(
+    ctx: HandlerContext,
+    name: &str,
+    sub_cmd: SetAddressPolicy,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    debug!(name = name, "Setting address policy");
+    let mut config = get_current_config(name, &req, &mut res).await?;
+    config.address_policy = AddressPolicy {
+        stack: sub_cmd.stack,
+        prefer: sub_cmd.prefer,
+        allow_unroutable: sub_cmd.allow_unroutable,
+    };
+
+    if sub_cmd.live {
+        return save_and_apply_config(ctx, name, config, req, res).await;
+    }
+
+    req.send(DaemonRequest::OverwriteNodeConfig {
+        node_name: name.to_string(),
+        new_cfg: config,
+    })
+    .await?;
+
+    handle_response(ctx, &mut res).await
+}
+
+async fn handle_set_upload_limits(
+    ctx: HandlerContext,
+    name: &str,
+    sub_cmd: SetUploadLimits,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    debug!(name = name, "Setting upload limits");
+    let mut config = get_current_config(name, &req, &mut res).await?;
+    config.upload_limits = UploadLimits {
+        bytes_per_peer_per_hour: sub_cmd.bytes_per_peer_per_hour,
+        max_concurrent_uploads: sub_cmd.max_concurrent_uploads,
+    };
+
+    if sub_cmd.live {
+        return save_and_apply_config(ctx, name, config, req, res).await;
+    }
+
+    req.send(DaemonRequest::OverwriteNodeConfig {
+        node_name: name.to_string(),
+        new_cfg: config,
+    })
+    .await?;
+
+    handle_response(ctx, &mut res).await
+}
+
+async fn handle_set_restart_policy(
+    ctx: HandlerContext,
+    name: &str,
+    sub_cmd: SetRestartPolicy,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    debug!(name = name, "Setting restart policy");
+    let mut config = get_current_config(name, &req, &mut res).await?;
+    let restart = &mut config.restart;
+    restart.policy = sub_cmd.policy;
+    if let Some(initial_backoff_ms) = sub_cmd.initial_backoff_ms {
+        restart.initial_backoff_ms = initial_backoff_ms;
+    }
+    if let Some(max_backoff_ms) = sub_cmd.max_backoff_ms {
+        restart.max_backoff_ms = max_backoff_ms;
+    }
+    if let Some(max_restarts) = sub_cmd.max_restarts {
+        restart.max_restarts = max_restarts;
+    }
+    if let Some(window_secs) = sub_cmd.window_secs {
+        restart.window_secs = window_secs;
+    }
+
+    if sub_cmd.live {
+        return save_and_apply_config(ctx, name, config, req, res).await;
+    }
+
+    req.send(DaemonRequest::OverwriteNodeConfig {
+        node_name: name.to_string(),
+        new_cfg: config,
+    })
+    .await?;
+
+    handle_response(ctx, &mut res).await
+}
+
+async fn handle_set_cache_limit(
+    ctx: HandlerContext,
+    name: &str,
+    sub_cmd: SetCacheLimit,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    debug!(name = name, "Setting cache limit");
+    let mut config = get_current_config(name, &req, &mut res).await?;
+    config.cache = CacheConfig {
+        max_stored_bytes: sub_cmd.max_stored_bytes,
+    };
+
+    if sub_cmd.live {
+        return save_and_apply_config(ctx, name, config, req, res).await;
+    }
+
+    req.send(DaemonRequest::OverwriteNodeConfig {
+        node_name: name.to_string(),
+        new_cfg: config,
+    })
+    .await?;
+
+    handle_response(ctx, &mut res).await
+}
+
+async fn handle_set_text_index(
+    ctx: HandlerContext,
+    name: &str,
+    sub_cmd: SetTextIndex,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    debug!(name = name, "Setting text index");
+    let mut config = get_current_config(name, &req, &mut res).await?;
+    config.text_index = TextIndexConfig {
+        enabled: !sub_cmd.disable,
+        max_indexed_bytes: sub_cmd
+            .max_indexed_bytes
+            .unwrap_or(config.text_index.max_indexed_bytes),
+    };
+
+    if sub_cmd.live {
+        return save_and_apply_config(ctx, name, config, req, res).await;
+    }
+
+    req.send(DaemonRequest::OverwriteNodeConfig {
+        node_name: name.to_string(),
+        new_cfg: config,
+    })
+    .await?;
+
+    handle_response(ctx, &mut res).await
+}
+
+async fn handle_set_chunking(
+    ctx: HandlerContext,
+    name: &str,
+    sub_cmd: SetChunking,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    debug!(name = name, "Setting chunking");
+    let erasure_coding = match (sub_cmd.data_shards, sub_cmd.parity_shards) {
+        (Some(data_shards), Some(parity_shards)) => {
+            if !erasure::is_valid(data_shards as usize, parity_shards as usize) {
+                bail!("Both numbers of the chunks must be positive and at most 256 together");
+            }
+            Some(ErasureCodingConfig {
+                data_shards,
+                parity_shards,
+            })
+        }
+        _ => None,
+    };
+    let mut config = get_current_config(name, &req, &mut res).await?;
+    config.chunking = ChunkingConfig {
+        enabled: !sub_cmd.disable,
+        min_file_size: sub_cmd
+            .min_file_size
+            .unwrap_or(config.chunking.min_file_size),
+        erasure_coding: match sub_cmd.no_erasure_coding {
+            true => None,
+            false => erasure_coding.or(config.chunking.erasure_coding),
+        },
+    };
+
+    if sub_cmd.live {
+        return save_and_apply_config(ctx, name, config, req, res).await;
+    }
+
+    req.send(DaemonRequest::OverwriteNodeConfig {
+        node_name: name.to_string(),
+        new_cfg: config,
+    })
+    .await?;
+
+    handle_response(ctx, &mut res).await
+}
+
+async fn handle_set_peer_exchange(
+    ctx: HandlerContext,
+    name: &str,
+    sub_cmd: SetPeerExchange,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    debug!(name = name, "Setting peer exchange");
+    let mut config = get_current_config(name, &req, &mut res).await?;
+    config.peer_exchange = PeerExchangeConfig {
+        enabled: !sub_cmd.disable,
+        max_peers: sub_cmd.max_peers.unwrap_or(config.peer_exchange.max_peers),
+        min_request_interval_secs: sub_cmd
+            .min_request_interval_secs
+            .unwrap_or(config.peer_exchange.min_request_interval_secs),
+    };
+
+    if sub_cmd.live {
+        return save_and_apply_config(ctx, name, config, req, res).await;
+    }
+
+    req.send(DaemonRequest::OverwriteNodeConfig {
+        node_name: name.to_string(),
+        new_cfg: config,
+    })
+    .await?;
+
+    handle_response(ctx, &mut res).await
+}
+
+async fn handle_set_network_key(
+    ctx: HandlerContext,
+    name: &str,
+    sub_cmd: SetNetworkKey,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    debug!(name = name, "Setting network key");
+    let key = match (sub_cmd.key, sub_cmd.generate) {
+        (Some(key), _) => {
+            node_config::parse_network_key(&key)?;
+            Some(key.trim().to_lowercase())
+        }
+        (None, true) => Some(node_config::generate_network_key()),
+        (None, false) => None,
+    };
+    let mut config = get_current_config(name, &req, &mut res).await?;
+    config.network_key = key.clone();
+
+    req.send(DaemonRequest::OverwriteNodeConfig {
+        node_name: name.to_string(),
+        new_cfg: config,
+    })
+    .await?;
+    handle_response(ctx, &mut res).await?;
+
+    if let (true, false, Some(key)) = (sub_cmd.generate, ctx.json, key) {
+        println!("{key}");
+    }
+    Ok(())
+}
+
+async fn get_current_config(
+    node_name: &str,
+    req: &RequestSender,
+    res: &mut ReseponseReceiver,
+) -> Result<NodeConfig> {
+    req.send(DaemonRequest::GetNodeConfig {
+        node_name: node_name.to_string(),
+    })
+    .await?;
+
+    let current_config = res
+        .recv()
+        .await
+        .ok_or(anyhow!("failed to get response"))
+        .inspect_err(|e| error!(err = e.to_string(), "Failed to receive response"))?
+        .inspect_err(|e| error!(err = e.to_string(), "Failed to get current config"))?;
+
+    let config = match current_config {
+        DaemonResponse::NodeConfig(cfg) => cfg,
+        _ => {
+            error!("Expected response to be NodeConfig, but it is not");
+            bail!("Response is not NodeConfig");
+        }
+    };
+
+    Ok(config)
+}
+
+async fn handle_stop_node(
+    ctx: HandlerContext,
+    cmd: StopNode,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    let Some(name) = cmd.nodes.single() else {
+        return handle_batch(ctx, cmd.nodes, NodeOperation::Stop, req, res).await;
+    };
+    debug!(name = name, "Stopping node");
+    req.send(DaemonRequest::StopNode {
+        node_name: name.to_string(),
+    })
+    .await
+    .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
+
+    handle_response(ctx, &mut res).await
+}
+
+async fn handle_reload_nodes(
+    ctx: HandlerContext,
+    cmd: ReloadNodes,
+    req: RequestSender,
     res: ReseponseReceiver,
 ) -> Result<()> {
-    match cmd.subcommand {
-        ConfigNodeCommand::AddBootstrapNode(sub_cmd) => {
-            handle_add_bootstrap_node(&cmd.name, sub_cmd, req, res).await?
+    let Some(name) = cmd.nodes.single() else {
+        return handle_batch(ctx, cmd.nodes, NodeOperation::ReloadConfig, req, res).await;
+    };
+    apply_config(ctx, name, req, res).await
+}
+
+async fn handle_provide_file(
+    ctx: HandlerContext,
+    cmd: ProvideFile,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    debug!(path = format!("{:?}", &cmd.path), "Providing file");
+    let path = std::path::absolute(&cmd.path).expect("Path to be converted into absolute path");
+
+    req.send(DaemonRequest::ProvideFile {
+        node_name: cmd.node_name,
+        path,
+    })
+    .await
+    .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
+
+    let response = res
+        .recv()
+        .await
+        .ok_or(anyhow!("Daemon returned no response"))?;
+    if ctx.json {
+        return print_json(&response);
+    }
+
+    match response {
+        Ok(DaemonResponse::FileProvided { id }) => {
+            info!(id = id, "File provided");
+            println!("{id}");
+            return Ok(());
+        }
+        Err(e) => {
+            println!("Error providing file: {e}");
+            bail!("Error providing file");
+        }
+        _ => {
+            bail!("Daemon returned wrong response");
+        }
+    }
+}
+
+async fn handle_download_file(
+    ctx: HandlerContext,
+    cmd: DownloadFile,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    let output = cmd.output.unwrap_or_else(|| PathBuf::from(&cmd.id));
+
+    req.send(DaemonRequest::DownloadFile {
+        node_name: cmd.node_name,
+        id: cmd.id,
+        group: cmd.group,
+        force_network: cmd.force_network,
+    })
+    .await
+    .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
+
+    let response = res
+        .recv()
+        .await
+        .ok_or(anyhow!("Daemon returned no response"))?;
+    if ctx.json && response.is_err() {
+        return print_json(&response);
+    }
+
+    match response {
+        Ok(DaemonResponse::FileDownloaded { data, stats }) => {
+            let from_cache = stats.is_some_and(|stats| stats.from_cache);
+            if output == Path::new("-") {
+                io::stdout().write_all(&data.content)?;
+                return Ok(());
+            }
+
+            // The content is written as is, files are not required to be valid UTF-8
+            tokio::fs::write(&output, &data.content)
+                .await
+                .inspect_err(|e| error!(err = e.to_string(), "Failed to write file"))?;
+            info!(path = output.display().to_string(), "File downloaded");
+
+            if ctx.json {
+                let json = serde_json::json!({ "ok": {
+                    "name": data.name,
+                    "path": output,
+                    "size": data.content.len(),
+                    "from_cache": from_cache,
+                }});
+                println!("{}", serde_json::to_string(&json)?);
+            } else {
+                println!(
+                    "Downloaded {} ({} bytes) to {}",
+                    data.name,
+                    data.content.len(),
+                    output.display()
+                );
+            }
+        }
+        Err(DaemonError::ObjectNotFound(id)) => {
+            println!("Failed to download file, object {id} was not found");
+        }
+        Err(DaemonError::NoProviders(id)) => {
+            println!("Failed to download file, nobody provides {id}");
+        }
+        Err(DaemonError::InvalidArgument(e)) => {
+            println!("Failed to download file: {e}");
+        }
+        Err(_) => {
+            println!("Failed to download file");
+        }
+        _ => {
+            bail!("Daemon returned wrong response");
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_get_providers(
+    ctx: HandlerContext,
+    cmd: GetProviders,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    req.send(DaemonRequest::GetProviders {
+        node_name: cmd.node_name,
+        id: cmd.id,
+        stream: cmd.stream,
+    })
+    .await
+    .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
+
+    if cmd.stream {
+        return print_streamed_providers(ctx, res).await;
+    }
+
+    let response = res
+        .recv()
+        .await
+        .ok_or(anyhow!("Daemon returned no response"))?;
+    if ctx.json {
+        return print_json(&response);
+    }
+
+    match response {
+        Ok(DaemonResponse::Providers { ids, .. }) => {
+            for provider in ids {
+                println!("{provider}");
+            }
+        }
+        Err(e) => {
+            error!(err = e.to_string(), "Error publishing file");
+        }
+        _ => {
+            bail!("Daemon returned wrong response");
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints the providers of a streamed lookup as they come, until the final response
+async fn print_streamed_providers(ctx: HandlerContext, mut res: ReseponseReceiver) -> Result<()> {
+    loop {
+        let response = res
+            .recv()
+            .await
+            .ok_or(anyhow!("Daemon returned no response"))?;
+        if ctx.json {
+            print_json(&response)?;
+            if let Ok(DaemonResponse::Providers { .. }) = response {
+                return Ok(());
+            }
+            continue;
+        }
+
+        match response? {
+            DaemonResponse::ProvidersFound { ids } => {
+                for provider in ids {
+                    println!("{provider}");
+                }
+            }
+            // All the providers were already printed
+            DaemonResponse::Providers { .. } => return Ok(()),
+            _ => {
+                bail!("Daemon returned wrong response");
+            }
+        }
+    }
+}
+
+async fn handle_get_peer_id(
+    ctx: HandlerContext,
+    cmd: GetPeerID,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    req.send(DaemonRequest::GetPeerId {
+        node_name: cmd.node_name,
+    })
+    .await
+    .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
+
+    let response = res
+        .recv()
+        .await
+        .ok_or(anyhow!("Daemon returned no response"))?;
+    if ctx.json {
+        return print_json(&response);
+    }
+
+    let response = response?;
+
+    match response {
+        DaemonResponse::PeerId { id } => {
+            println!("{id}");
+        }
+        _ => {
+            bail!("Daemon returned wrong response");
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_dial(
+    ctx: HandlerContext,
+    cmd: Dial,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    req.send(DaemonRequest::Dial {
+        node_name: cmd.node_name,
+        peer_id: cmd.peer_id,
+        addr: cmd.addr,
+    })
+    .await
+    .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
+
+    let response = res.recv().await;
+    if let (true, Some(response)) = (ctx.json, &response) {
+        return print_json(response);
+    }
+
+    match response {
+        Some(Ok(DaemonResponse::Dialed { peer_id })) => {
+            println!("Dialing successful, connected to {peer_id}");
+        }
+        Some(Ok(r)) => {
+            info!(response = format!("{r:?}"), "Daemon responds");
+            println!("Dialing successful");
+        }
+        Some(Err(e)) => {
+            error!(err = e.to_string(), "Error dialing peer");
+            println!("Error dialing peer");
+        }
+        None => {
+            error!("Failed to receive response");
+        }
+    };
+    Ok(())
+}
+
+async fn handle_publish_file(
+    ctx: HandlerContext,
+    cmd: PublishFile,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    let access = match (cmd.group, cmd.contacts_only) {
+        (Some(group), _) => ObjectAccess::Group(group),
+        (None, true) => ObjectAccess::Contacts,
+        (None, false) => ObjectAccess::Public,
+    };
+
+    req.send(DaemonRequest::PublishFile {
+        node_name: cmd.node_name,
+        path: cmd.path,
+        access,
+        previous: cmd.previous,
+    })
+    .await
+    .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
+
+    let resp = res
+        .recv()
+        .await
+        .ok_or(anyhow!("Daemon returned no response"))?;
+    if ctx.json {
+        return print_json(&resp);
+    }
+
+    match resp {
+        Ok(DaemonResponse::FilePublished { id }) => {
+            info!(id = id, "File published");
+            println!("{id}");
+            return Ok(());
+        }
+        Err(e) => {
+            println!("Error publishing file: {e}");
+            bail!("Error publishing file");
+        }
+        _ => {
+            bail!("Daemon returned wrong response");
+        }
+    }
+}
+
+async fn handle_publish_files(
+    ctx: HandlerContext,
+    cmd: PublishFiles,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    let paths = expand_patterns(&cmd.patterns)?;
+    if paths.is_empty() {
+        bail!("No files match the patterns");
+    }
+    debug!(count = paths.len(), "Publishing files");
+
+    req.send(DaemonRequest::PublishFiles {
+        node_name: cmd.node_name,
+        paths,
+        max_concurrency: cmd.max_concurrency,
+    })
+    .await
+    .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
+
+    let resp = res
+        .recv()
+        .await
+        .ok_or(anyhow!("Daemon returned no response"))?;
+    if ctx.json {
+        return print_json(&resp);
+    }
+
+    match resp {
+        Ok(DaemonResponse::FilesPublished { results }) => {
+            let failed = results.iter().filter(|r| r.result.is_err()).count();
+            let published = results.len() - failed;
+            let result_rows = results
+                .iter()
+                .map(|result| result.into())
+                .collect::<Vec<PublishFileResultRow>>();
+            let mut table = Table::new(result_rows);
+
+            if ctx.machine_readable {
+                table.with(Style::blank());
+            } else {
+                table.with(Style::modern());
+            }
+
+            let table = table.to_string();
+            println!("{table}");
+            println!("Published: {published}, failed: {failed}");
+
+            if failed > 0 {
+                bail!("Failed to publish {failed} files");
+            }
+        }
+        Err(e) => {
+            println!("Error publishing files: {e}");
+            bail!("Error publishing files");
+        }
+        _ => {
+            bail!("Daemon returned wrong response");
+        }
+    }
+    Ok(())
+}
+
+/// Expands the glob patterns to absolute paths of the matched files,
+/// the daemon may run in a different working directory
+fn expand_patterns(patterns: &[String]) -> Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for pattern in patterns {
+        for entry in glob::glob(pattern)? {
+            let path = entry?;
+            if path.is_file() {
+                paths.push(std::path::absolute(&path)?);
+            }
+        }
+    }
+    paths.sort();
+    paths.dedup();
+
+    Ok(paths)
+}
+
+async fn handle_verify(
+    ctx: HandlerContext,
+    cmd: Verify,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    req.send(DaemonRequest::VerifyObject {
+        node_name: cmd.node_name,
+        object_id: cmd.object_id,
+        signer: cmd.signer,
+    })
+    .await
+    .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
+
+    let response = res
+        .recv()
+        .await
+        .ok_or(anyhow!("Daemon returned no response"))?;
+    if ctx.json {
+        return print_json(&response);
+    }
+
+    match response? {
+        DaemonResponse::ObjectVerified(verification) => {
+            let mut table = Table::new(object_verification_rows(&verification));
+
+            if ctx.machine_readable {
+                table.with(Style::blank());
+            } else {
+                table.with(Style::modern());
+            }
+
+            println!("{table}");
+            if !verification.is_healthy() {
+                bail!("Object is not healthy");
+            }
+        }
+        _ => {
+            bail!("Daemon returned wrong response");
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_object_info(
+    ctx: HandlerContext,
+    cmd: ObjectInfoCmd,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    req.send(DaemonRequest::GetObjectInfo {
+        node_name: cmd.node_name,
+        object_id: cmd.object_id,
+    })
+    .await
+    .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
+
+    let response = res
+        .recv()
+        .await
+        .ok_or(anyhow!("Daemon returned no response"))?;
+    if ctx.json {
+        return print_json(&response);
+    }
+
+    match response? {
+        DaemonResponse::ObjectInfo(info) => {
+            let mut table = Table::new(object_info_rows(&info));
+            let layers = info.layers.iter().enumerate().map(ObjectLayerRow::from);
+            let mut layers_table = Table::new(layers);
+
+            if ctx.machine_readable {
+                table.with(Style::blank());
+                layers_table.with(Style::blank());
+            } else {
+                table.with(Style::modern());
+                layers_table.with(Style::modern());
+            }
+
+            println!("{table}");
+            println!("{layers_table}");
+        }
+        _ => {
+            bail!("Daemon returned wrong response");
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_cat(
+    ctx: HandlerContext,
+    cmd: Cat,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    req.send(DaemonRequest::GetObject {
+        node_name: cmd.node_name,
+        object_id: cmd.object_id.clone(),
+    })
+    .await
+    .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
+
+    let response = res
+        .recv()
+        .await
+        .ok_or(anyhow!("Daemon returned no response"))?;
+    if ctx.json {
+        return print_json(&response);
+    }
+
+    match response? {
+        DaemonResponse::Object(object) => {
+            let text = render::render(&cmd.object_id, &object, cmd.format)?;
+            println!("{}", text.trim_end());
+        }
+        _ => {
+            bail!("Daemon returned wrong response");
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_query(
+    ctx: HandlerContext,
+    cmd: QueryCmd,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    let query =
+        TypedObject::from_json(&cmd.query_json).map_err(|e| anyhow!("Invalid query JSON: {e}"))?;
+    req.send(DaemonRequest::Query {
+        node_name: cmd.node_name,
+        query,
+    })
+    .await
+    .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
+
+    let response = res
+        .recv()
+        .await
+        .ok_or(anyhow!("Daemon returned no response"))?;
+    if ctx.json {
+        return print_json(&response);
+    }
+
+    match response? {
+        DaemonResponse::QueryResults(results) => {
+            for id in &results.ids {
+                println!("{id}");
+            }
+            if !ctx.machine_readable {
+                println!(
+                    "{} objects found, {} of {} peers answered",
+                    results.ids.len(),
+                    results.peers_answered,
+                    results.peers_asked
+                );
+            }
+        }
+        _ => {
+            bail!("Daemon returned wrong response");
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_export_vault(
+    ctx: HandlerContext,
+    cmd: ExportVault,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    // The archive is written by the daemon
+    let path = std::path::absolute(&cmd.output)?;
+    req.send(DaemonRequest::ExportVaultSnapshot {
+        node_name: cmd.node_name,
+        path: path.clone(),
+    })
+    .await
+    .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
+
+    let response = res
+        .recv()
+        .await
+        .ok_or(anyhow!("Daemon returned no response"))?;
+    if ctx.json {
+        return print_json(&response);
+    }
+
+    match response? {
+        DaemonResponse::VaultSnapshotExported(summary) => {
+            println!(
+                "Exported {} typed objects, {} published objects and {} fragments to {}",
+                summary.typed_objects,
+                summary.published_objects,
+                summary.fragments,
+                path.display()
+            );
+        }
+        _ => {
+            bail!("Daemon returned wrong response");
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_import_vault(
+    ctx: HandlerContext,
+    cmd: ImportVault,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    let path = std::path::absolute(&cmd.path)?;
+    req.send(DaemonRequest::ImportVaultSnapshot {
+        node_name: cmd.node_name,
+        path,
+    })
+    .await
+    .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
+
+    let response = res
+        .recv()
+        .await
+        .ok_or(anyhow!("Daemon returned no response"))?;
+    if ctx.json {
+        return print_json(&response);
+    }
+
+    match response? {
+        DaemonResponse::VaultSnapshotImported(summary) => {
+            println!(
+                "Imported {} typed objects, {} published objects and {} fragments, {} already present",
+                summary.typed_objects,
+                summary.published_objects,
+                summary.fragments,
+                summary.skipped
+            );
+        }
+        _ => {
+            bail!("Daemon returned wrong response");
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_get_published_objects(
+    ctx: HandlerContext,
+    cmd: GetPublishedObjects,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    req.send(DaemonRequest::GetPublishedObjects {
+        node_name: cmd.node_name,
+    })
+    .await
+    .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
+
+    let resp = res
+        .recv()
+        .await
+        .ok_or(anyhow!("Daemon returned no response"))?;
+    if ctx.json {
+        return print_json(&resp);
+    }
+
+    match resp {
+        Ok(DaemonResponse::PublishedObjectsList {
+            object_infos: files,
+        }) => {
+            let file_info_rows = files
+                .iter()
+                .map(|info| info.into())
+                .collect::<Vec<TypedObjectInfoRow>>();
+            let mut table = Table::new(file_info_rows);
+
+            if ctx.machine_readable {
+                table.with(Style::blank());
+            } else {
+                table.with(Style::modern());
+            }
+
+            let table = table.to_string();
+            println!("{table}");
+        }
+        Err(e) => {
+            println!("Error getting published files list: {e}");
+            bail!("Error getting published files list");
+        }
+        _ => {
+            bail!("Daemon returned wrong response");
+        }
+    }
+    Ok(())
+}
+
+async fn handle_mount(cmd: Mount, req: RequestSender, mut res: ReseponseReceiver) -> Result<()> {
+    req.send(DaemonRequest::GetPublishedObjects {
+        node_name: cmd.node_name.clone(),
+    })
+    .await
+    .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
+
+    let published_ids = match res.recv().await {
+        Some(Ok(DaemonResponse::PublishedObjectsList { object_infos })) => object_infos
+            .into_iter()
+            .filter(|info| info.type_id == PlainFileObject::UUID)
+            .map(|info| info.id)
+            .collect(),
+        Some(Err(e)) => bail!("Error getting published files list: {e}"),
+        Some(Ok(_)) => bail!("Daemon returned wrong response"),
+        None => bail!("Daemon returned no response"),
+    };
+
+    let fs = mount::VaultFs::new(cmd.node_name, published_ids, req, res);
+    // Unmounted when the session is dropped
+    let _session = fuser::spawn_mount2(fs, &cmd.mountpoint, &mount::mount_options())
+        .inspect_err(|e| error!(err = e.to_string(), "Failed to mount"))?;
+    info!(path = cmd.mountpoint.display().to_string(), "Mounted");
+    println!(
+        "Mounted at {}, press Ctrl-C to unmount",
+        cmd.mountpoint.display()
+    );
+
+    tokio::signal::ctrl_c().await?;
+    Ok(())
+}
+
+async fn handle_delete_object(
+    ctx: HandlerContext,
+    cmd: DeleteObject,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    req.send(DaemonRequest::DeleteObject {
+        node_name: cmd.node_name,
+        object_id: cmd.object_id,
+    })
+    .await
+    .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
+
+    let resp = res
+        .recv()
+        .await
+        .ok_or(anyhow!("Daemon returned no response"))?;
+    if ctx.json {
+        return print_json(&resp);
+    }
+
+    match resp {
+        Ok(DaemonResponse::ObjectDeleted {
+            deleted_myself,
+            deleted_count,
+            failed_count,
+        }) => {
+            println!("Deleted myself: {deleted_myself}");
+            println!("Successful deletes: {deleted_count}");
+            println!("Failed deletes: {failed_count}");
+        }
+        Err(e) => {
+            println!("Error deleting object: {e}");
+            bail!("Error deleting object");
+        }
+        _ => {
+            bail!("Daemon returned wrong response");
+        }
+    }
+    Ok(())
+}
+
+async fn handle_reset_peer_score(
+    ctx: HandlerContext,
+    cmd: ResetPeerScore,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    req.send(DaemonRequest::ResetPeerScore {
+        node_name: cmd.node_name,
+        peer_id: cmd.peer_id.clone(),
+    })
+    .await
+    .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
+
+    let response = res
+        .recv()
+        .await
+        .ok_or(anyhow!("Daemon returned no response"))?;
+    if ctx.json {
+        return print_json(&response);
+    }
+
+    match response {
+        Ok(DaemonResponse::PeerScoreReset) => {
+            println!("Reset the score of {}", cmd.peer_id);
+            Ok(())
+        }
+        Err(e) => {
+            println!("Error resetting the peer score: {e}");
+            bail!("Error resetting the peer score");
+        }
+        _ => {
+            bail!("Daemon returned wrong response");
+        }
+    }
+}
+
+async fn handle_stop_providing(
+    ctx: HandlerContext,
+    cmd: StopProviding,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    req.send(DaemonRequest::StopProviding {
+        node_name: cmd.node_name,
+        object_id: cmd.object_id.clone(),
+        keep_in_vault: cmd.keep_in_vault,
+    })
+    .await
+    .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
+
+    let response = res
+        .recv()
+        .await
+        .ok_or(anyhow!("Daemon returned no response"))?;
+    if ctx.json {
+        return print_json(&response);
+    }
+
+    match response {
+        Ok(DaemonResponse::StoppedProviding) => {
+            println!("Stopped providing {}", cmd.object_id);
+            Ok(())
+        }
+        Err(e) => {
+            println!("Error stopping providing: {e}");
+            bail!("Error stopping providing");
+        }
+        _ => {
+            bail!("Daemon returned wrong response");
+        }
+    }
+}
+
+async fn handle_export_identity(
+    ctx: HandlerContext,
+    cmd: ExportIdentity,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    let passphrase = read_passphrase(cmd.passphrase)?;
+    req.send(DaemonRequest::ExportNodeIdentity {
+        node_name: cmd.node_name.clone(),
+        passphrase,
+    })
+    .await
+    .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
+
+    let response = res
+        .recv()
+        .await
+        .ok_or(anyhow!("Daemon returned no response"))?;
+    if ctx.json && response.is_err() {
+        return print_json(&response);
+    }
+
+    match response {
+        Ok(DaemonResponse::NodeIdentityExported { bundle }) => {
+            tokio::fs::write(&cmd.output, &bundle)
+                .await
+                .inspect_err(|e| error!(err = e.to_string(), "Failed to write identity"))?;
+
+            if ctx.json {
+                let json = serde_json::json!({ "ok": { "path": cmd.output } });
+                println!("{}", serde_json::to_string(&json)?);
+            } else {
+                println!(
+                    "Exported identity of {} to {}",
+                    cmd.node_name,
+                    cmd.output.display()
+                );
+            }
+            Ok(())
+        }
+        Err(e) => {
+            println!("Error exporting identity: {e}");
+            bail!("Error exporting identity");
+        }
+        _ => {
+            bail!("Daemon returned wrong response");
+        }
+    }
+}
+
+async fn handle_import_identity(
+    ctx: HandlerContext,
+    cmd: ImportIdentity,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    let bundle = tokio::fs::read(&cmd.path)
+        .await
+        .inspect_err(|e| error!(err = e.to_string(), "Failed to read identity"))?;
+    let passphrase = read_passphrase(cmd.passphrase)?;
+    req.send(DaemonRequest::ImportNodeIdentity {
+        node_name: cmd.node_name.clone(),
+        bundle,
+        passphrase,
+    })
+    .await
+    .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
+
+    let response = res
+        .recv()
+        .await
+        .ok_or(anyhow!("Daemon returned no response"))?;
+    if ctx.json {
+        return print_json(&response);
+    }
+
+    match response {
+        Ok(DaemonResponse::NodeIdentityImported { peer_id }) => {
+            println!("Created node {} with peer ID {peer_id}", cmd.node_name);
+            Ok(())
+        }
+        Err(e) => {
+            println!("Error importing identity: {e}");
+            bail!("Error importing identity");
+        }
+        _ => {
+            bail!("Daemon returned wrong response");
+        }
+    }
+}
+
+async fn handle_unlock_node(
+    ctx: HandlerContext,
+    cmd: UnlockNode,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    let passphrase = read_passphrase(cmd.passphrase)?;
+    debug!(name = cmd.name, "Unlocking node");
+    req.send(DaemonRequest::UnlockNode {
+        node_name: cmd.name,
+        passphrase,
+    })
+    .await
+    .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
+
+    handle_response(ctx, &mut res).await
+}
+
+async fn handle_set_passphrase(
+    ctx: HandlerContext,
+    cmd: SetPassphrase,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    let passphrase = match cmd.remove {
+        true => None,
+        false => Some(read_passphrase(cmd.passphrase)?),
+    };
+    debug!(name = cmd.name, "Setting node passphrase");
+    req.send(DaemonRequest::SetNodePassphrase {
+        node_name: cmd.name,
+        passphrase,
+    })
+    .await
+    .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
+
+    handle_response(ctx, &mut res).await
+}
+
+async fn handle_rename_node(
+    ctx: HandlerContext,
+    cmd: RenameNode,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    debug!(name = cmd.name, new_name = cmd.new_name, "Renaming node");
+    req.send(DaemonRequest::RenameNode {
+        node_name: cmd.name,
+        new_name: cmd.new_name,
+        force: cmd.force,
+    })
+    .await
+    .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
+
+    handle_response(ctx, &mut res).await
+}
+
+async fn handle_rotate_key(
+    ctx: HandlerContext,
+    cmd: RotateKey,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    debug!(name = cmd.name, "Rotating node key");
+    req.send(DaemonRequest::RotateNodeKey {
+        node_name: cmd.name,
+        passphrase: cmd.passphrase,
+        force: cmd.force,
+    })
+    .await
+    .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
+
+    let response = res
+        .recv()
+        .await
+        .ok_or(anyhow!("Daemon returned no response"))?;
+    if ctx.json {
+        return print_json(&response);
+    }
+
+    match response? {
+        DaemonResponse::NodeKeyRotated {
+            old_peer_id,
+            new_peer_id,
+        } => println!("Key rotated; old_peer_id={old_peer_id} new_peer_id={new_peer_id}"),
+        _ => {
+            bail!("Daemon returned wrong response");
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_delete_node(
+    ctx: HandlerContext,
+    cmd: DeleteNode,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    debug!(name = cmd.name, "Deleting node");
+    req.send(DaemonRequest::DeleteNode {
+        node_name: cmd.name,
+        force: cmd.force,
+        keep_vault: cmd.keep_vault,
+    })
+    .await
+    .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
+
+    handle_response(ctx, &mut res).await
+}
+
+async fn handle_clone_node(
+    ctx: HandlerContext,
+    cmd: CloneNode,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    debug!(source = cmd.source, target = cmd.target, "Cloning node");
+    req.send(DaemonRequest::CloneNode {
+        source: cmd.source,
+        target: cmd.target.clone(),
+        fresh_identity: !cmd.keep_identity,
+    })
+    .await
+    .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
+
+    let response = res
+        .recv()
+        .await
+        .ok_or(anyhow!("Daemon returned no response"))?;
+    if ctx.json {
+        return print_json(&response);
+    }
+
+    match response {
+        Ok(DaemonResponse::NodeCloned { peer_id }) => {
+            println!("Created node {} with peer ID {peer_id}", cmd.target);
+            Ok(())
+        }
+        Err(e) => {
+            println!("Error cloning node: {e}");
+            bail!("Error cloning node");
+        }
+        _ => {
+            bail!("Daemon returned wrong response");
+        }
+    }
+}
+
+async fn handle_set_log_level(
+    ctx: HandlerContext,
+    cmd: SetLogLevel,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    req.send(DaemonRequest::SetLogLevel {
+        target: cmd.target,
+        level: cmd.level,
+    })
+    .await
+    .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
+
+    let response = res
+        .recv()
+        .await
+        .ok_or(anyhow!("Daemon returned no response"))?;
+    if ctx.json {
+        return print_json(&response);
+    }
+
+    match response? {
+        DaemonResponse::LogLevelSet { filter } => println!("Log filter: {filter}"),
+        _ => {
+            bail!("Daemon returned wrong response");
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_logs(
+    ctx: HandlerContext,
+    cmd: Logs,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    if cmd.follow {
+        let mut lines =
+            liberum_core::follow_logs(daemon_config::client_socket_path(), cmd.node, cmd.lines)
+                .await
+                .inspect_err(|e| error!(err = e.to_string(), "Failed to follow the logs"))?;
+
+        while let Some(line) = lines.recv().await {
+            if ctx.json {
+                println!("{}", serde_json::to_string(&line)?);
+            } else {
+                println!("{line}");
+            }
+        }
+
+        return Ok(());
+    }
+
+    req.send(DaemonRequest::TailLogs {
+        node_name: cmd.node,
+        follow: false,
+        lines: cmd.lines,
+    })
+    .await
+    .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
+
+    let response = res
+        .recv()
+        .await
+        .ok_or(anyhow!("Daemon returned no response"))?;
+    if ctx.json {
+        return print_json(&response);
+    }
+
+    match response? {
+        DaemonResponse::LogLines { lines } => {
+            for line in lines {
+                println!("{line}");
+            }
+        }
+        _ => {
+            bail!("Daemon returned wrong response");
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_set_profile(
+    ctx: HandlerContext,
+    cmd: SetProfile,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    req.send(DaemonRequest::SetProfile {
+        node_name: cmd.node_name,
+        display_name: cmd.display_name,
+        description: cmd.description,
+        avatar: cmd.avatar,
+    })
+    .await
+    .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
+
+    handle_response(ctx, &mut res).await
+}
+
+async fn handle_get_profile(
+    ctx: HandlerContext,
+    cmd: GetProfile,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    req.send(DaemonRequest::GetPeerProfile {
+        node_name: cmd.node_name,
+        peer_id: cmd.peer_id,
+    })
+    .await
+    .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
+
+    let response = res
+        .recv()
+        .await
+        .ok_or(anyhow!("Daemon returned no response"))?;
+    if ctx.json {
+        return print_json(&response);
+    }
+
+    match response? {
+        DaemonResponse::PeerProfile(Some(profile)) => {
+            println!("Peer ID: {}", profile.peer_id);
+            println!("Display name: {}", profile.display_name);
+            if !profile.description.is_empty() {
+                println!("Description: {}", profile.description);
+            }
+            if let Some(avatar) = profile.avatar {
+                println!("Avatar: {avatar}");
+            }
         }
-        ConfigNodeCommand::AddExternalAddr(sub_cmd) => {
-            handle_add_external_addr(&cmd.name, sub_cmd, req, res).await?
+        DaemonResponse::PeerProfile(None) => bail!("The peer has not published a profile"),
+        _ => {
+            bail!("Daemon returned wrong response");
         }
     }
 
     Ok(())
 }
 
-async fn handle_list_nodes(
+async fn handle_daemon_config(
     ctx: HandlerContext,
     req: RequestSender,
     mut res: ReseponseReceiver,
 ) -> Result<()> {
-    debug!("Listing nodes...");
-    req.send(DaemonRequest::ListNodes)
+    req.send(DaemonRequest::GetDaemonConfig)
         .await
         .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
 
-    let node_infos = res
+    let response = res
         .recv()
         .await
-        .ok_or(anyhow!("Daemon returned no response"))??;
+        .ok_or(anyhow!("Daemon returned no response"))?;
+    if ctx.json {
+        return print_json(&response);
+    }
 
-    match node_infos {
-        DaemonResponse::NodeList(node_infos) => {
-            let node_info_rows = node_infos
+    match response? {
+        DaemonResponse::DaemonConfig(config) => {
+            println!("{}", serde_json::to_string_pretty(&config)?);
+        }
+        _ => bail!("Daemon returned wrong response"),
+    }
+
+    Ok(())
+}
+
+async fn handle_ping_daemon(
+    ctx: HandlerContext,
+    cmd: PingDaemon,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    let sent_at = Instant::now();
+    req.send(DaemonRequest::Ping)
+        .await
+        .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
+
+    let response = tokio::time::timeout(Duration::from_millis(cmd.timeout_ms), res.recv())
+        .await
+        .map_err(|_| anyhow!("Daemon did not answer in {} ms", cmd.timeout_ms))?
+        .ok_or(anyhow!("Daemon returned no response"))?;
+    let latency = sent_at.elapsed();
+    if ctx.json {
+        return print_json(&response);
+    }
+
+    match response? {
+        DaemonResponse::Pong(health) => {
+            println!("version: {}", health.version);
+            println!("protocol_version: {}", health.protocol_version);
+            println!("uptime: {}s", health.uptime.as_secs());
+            println!("running_nodes: {}", health.running_nodes);
+            println!("queued_requests: {}", health.queued_requests);
+            println!("latency: {}ms", latency.as_millis());
+        }
+        _ => bail!("Daemon returned wrong response"),
+    }
+
+    Ok(())
+}
+
+async fn handle_watch(ctx: HandlerContext) -> Result<()> {
+    let mut notifications = liberum_core::subscribe(daemon_config::client_socket_path())
+        .await
+        .inspect_err(|e| error!(err = e.to_string(), "Failed to subscribe"))?;
+
+    while let Some(notification) = notifications.recv().await {
+        if ctx.json {
+            println!("{}", serde_json::to_string(&notification)?);
+        } else {
+            println!("{notification:?}");
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the passphrase given as an argument or reads it from the terminal,
+/// without echoing it
+fn read_passphrase(passphrase: Option<String>) -> Result<String> {
+    if let Some(passphrase) = passphrase {
+        return Ok(passphrase);
+    }
+
+    Ok(rpassword::prompt_password("Passphrase: ")?)
+}
+
+async fn handle_add_contact(
+    ctx: HandlerContext,
+    cmd: AddContact,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    req.send(DaemonRequest::AddContact {
+        node_name: cmd.node_name,
+        peer_id: cmd.peer_id,
+        alias: cmd.alias,
+        address: cmd.address,
+        trust: cmd.trust,
+    })
+    .await
+    .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
+
+    handle_response(ctx, &mut res).await
+}
+
+async fn handle_remove_contact(
+    ctx: HandlerContext,
+    cmd: RemoveContact,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    req.send(DaemonRequest::RemoveContact {
+        node_name: cmd.node_name,
+        peer_id: cmd.peer_id,
+    })
+    .await
+    .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
+
+    let response = res
+        .recv()
+        .await
+        .ok_or(anyhow!("Daemon returned no response"))?;
+    if ctx.json {
+        return print_json(&response);
+    }
+
+    match response? {
+        DaemonResponse::ContactRemoved { removed: true } => {}
+        DaemonResponse::ContactRemoved { removed: false } => bail!("No such contact"),
+        _ => {
+            bail!("Daemon returned wrong response");
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_list_contacts(
+    ctx: HandlerContext,
+    cmd: ListContacts,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    req.send(DaemonRequest::ListContacts {
+        node_name: cmd.node_name,
+    })
+    .await
+    .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
+
+    let response = res
+        .recv()
+        .await
+        .ok_or(anyhow!("Daemon returned no response"))?;
+    if ctx.json {
+        return print_json(&response);
+    }
+
+    match response? {
+        DaemonResponse::Contacts(contacts) => {
+            let rows = contacts
                 .iter()
-                .map(|info| info.into())
-                .collect::<Vec<NodeInfoRow>>();
-            let mut table = Table::new(node_info_rows);
+                .map(|c| c.into())
+                .collect::<Vec<ContactRow>>();
+            let mut table = Table::new(rows);
+
+            if ctx.machine_readable {
+                table.with(Style::blank());
+            } else {
+                table.with(Style::modern());
+            }
+
+            println!("{table}");
+        }
+        _ => {
+            bail!("Daemon returned wrong response");
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_add_watch_dir(
+    ctx: HandlerContext,
+    cmd: AddWatchDir,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    // The daemon doesn't know the working directory of the CLI
+    let path = tokio::fs::canonicalize(&cmd.path)
+        .await
+        .map_err(|e| anyhow!("Invalid directory {}: {e}", cmd.path.display()))?;
+
+    req.send(DaemonRequest::AddWatchDir {
+        node_name: cmd.node_name,
+        dir: WatchDir {
+            path,
+            ignore: cmd.ignore,
+            unpublish_old: cmd.unpublish_old,
+        },
+    })
+    .await
+    .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
+
+    handle_response(ctx, &mut res).await
+}
+
+async fn handle_remove_watch_dir(
+    ctx: HandlerContext,
+    cmd: RemoveWatchDir,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    // The directory may be already removed, then the path is passed as it is
+    let path = tokio::fs::canonicalize(&cmd.path).await.unwrap_or(cmd.path);
+
+    req.send(DaemonRequest::RemoveWatchDir {
+        node_name: cmd.node_name,
+        path,
+    })
+    .await
+    .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
+
+    handle_response(ctx, &mut res).await
+}
+
+async fn handle_list_watch_dirs(
+    ctx: HandlerContext,
+    cmd: ListWatchDirs,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    req.send(DaemonRequest::ListWatchDirs {
+        node_name: cmd.node_name,
+    })
+    .await
+    .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
+
+    let response = res
+        .recv()
+        .await
+        .ok_or(anyhow!("Daemon returned no response"))?;
+    if ctx.json {
+        return print_json(&response);
+    }
+
+    match response? {
+        DaemonResponse::WatchDirs(dirs) => {
+            let rows = dirs.iter().map(|d| d.into()).collect::<Vec<WatchDirRow>>();
+            let mut table = Table::new(rows);
+
+            if ctx.machine_readable {
+                table.with(Style::blank());
+            } else {
+                table.with(Style::modern());
+            }
+
+            println!("{table}");
+        }
+        _ => {
+            bail!("Daemon returned wrong response");
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_send_message(
+    ctx: HandlerContext,
+    cmd: SendMessage,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    let content = match (cmd.text, cmd.file) {
+        (Some(text), _) => MessageContent::Text(text),
+        (None, Some(path)) => MessageContent::Binary(std::fs::read(path)?),
+        (None, None) => bail!("Nothing to send"),
+    };
+
+    req.send(DaemonRequest::SendMessage {
+        node_name: cmd.node_name,
+        peer_id: cmd.peer_id,
+        content,
+        encrypt: cmd.encrypt,
+    })
+    .await
+    .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
+
+    let response = res
+        .recv()
+        .await
+        .ok_or(anyhow!("Daemon returned no response"))?;
+    if ctx.json {
+        return print_json(&response);
+    }
+
+    match response? {
+        DaemonResponse::MessageSent { id } => println!("Message delivered; id={id}"),
+        DaemonResponse::MessageStored { id, peers } => {
+            println!("Peer is offline, message left at {peers} peers; id={id}")
+        }
+        _ => {
+            bail!("Daemon returned wrong response");
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_inbox(
+    ctx: HandlerContext,
+    cmd: Inbox,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    req.send(DaemonRequest::GetInbox {
+        node_name: cmd.node_name,
+    })
+    .await
+    .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
+
+    let response = res
+        .recv()
+        .await
+        .ok_or(anyhow!("Daemon returned no response"))?;
+    if ctx.json {
+        return print_json(&response);
+    }
+
+    match response? {
+        DaemonResponse::Inbox(messages) => {
+            let rows = messages
+                .iter()
+                .map(|m| m.into())
+                .collect::<Vec<InboxMessageRow>>();
+            let mut table = Table::new(rows);
 
             if ctx.machine_readable {
                 table.with(Style::blank());
@@ -325,9 +4096,39 @@ async fn handle_list_nodes(
                 table.with(Style::modern());
             }
 
-            let table = table.to_string();
-            println!("{table}");
-        }
+            println!("{table}");
+        }
+        _ => {
+            bail!("Daemon returned wrong response");
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_create_group(
+    ctx: HandlerContext,
+    cmd: CreateGroup,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    req.send(DaemonRequest::CreateGroup {
+        node_name: cmd.node_name,
+        group_name: cmd.group_name,
+    })
+    .await
+    .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
+
+    let response = res
+        .recv()
+        .await
+        .ok_or(anyhow!("Daemon returned no response"))?;
+    if ctx.json {
+        return print_json(&response);
+    }
+
+    match response? {
+        DaemonResponse::GroupCreated { id } => println!("Group created; id={id}"),
         _ => {
             bail!("Daemon returned wrong response");
         }
@@ -336,37 +4137,31 @@ async fn handle_list_nodes(
     Ok(())
 }
 
-async fn handle_get_node_details(
+async fn handle_invite_to_group(
     ctx: HandlerContext,
-    cmd: GetNodeDetails,
+    cmd: InviteToGroup,
     req: RequestSender,
     mut res: ReseponseReceiver,
 ) -> Result<()> {
-    req.send(DaemonRequest::GetNodeDetails {
-        node_name: cmd.name,
+    req.send(DaemonRequest::InviteToGroup {
+        node_name: cmd.node_name,
+        group_id: cmd.group_id,
+        peer_id: cmd.peer_id,
+        valid_for_secs: cmd.days * 24 * 60 * 60,
     })
     .await
     .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
 
-    let node_infos = res
+    let response = res
         .recv()
         .await
-        .ok_or(anyhow!("Daemon returned no response"))??;
-
-    match node_infos {
-        DaemonResponse::NodeDetails(details) => {
-            let details_row = vec![NodeInfoRow::from(&details)];
-            let mut table = Table::new(details_row);
-
-            if ctx.machine_readable {
-                table.with(Style::blank());
-            } else {
-                table.with(Style::modern());
-            }
+        .ok_or(anyhow!("Daemon returned no response"))?;
+    if ctx.json {
+        return print_json(&response);
+    }
 
-            let table = table.to_string();
-            println!("{table}");
-        }
+    match response? {
+        DaemonResponse::GroupInvitation { invitation } => println!("{invitation}"),
         _ => {
             bail!("Daemon returned wrong response");
         }
@@ -375,29 +4170,29 @@ async fn handle_get_node_details(
     Ok(())
 }
 
-async fn handle_get_node_addresses(
-    _: HandlerContext,
-    cmd: GetNodeAddresses,
+async fn handle_join_group(
+    ctx: HandlerContext,
+    cmd: JoinGroup,
     req: RequestSender,
     mut res: ReseponseReceiver,
 ) -> Result<()> {
-    req.send(DaemonRequest::GetNodeDetails {
-        node_name: cmd.name,
+    req.send(DaemonRequest::JoinGroup {
+        node_name: cmd.node_name,
+        invitation: cmd.invitation,
     })
     .await
     .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
 
-    let node_infos = res
+    let response = res
         .recv()
         .await
-        .ok_or(anyhow!("Daemon returned no response"))??;
+        .ok_or(anyhow!("Daemon returned no response"))?;
+    if ctx.json {
+        return print_json(&response);
+    }
 
-    match node_infos {
-        DaemonResponse::NodeDetails(details) => {
-            for addr in details.running_addresses {
-                println!("{}", addr);
-            }
-        }
+    match response? {
+        DaemonResponse::GroupJoined { id } => println!("Joined group; id={id}"),
         _ => {
             bail!("Daemon returned wrong response");
         }
@@ -406,100 +4201,156 @@ async fn handle_get_node_addresses(
     Ok(())
 }
 
-async fn handle_add_bootstrap_node(
-    name: &str,
-    cmd: AddBootstrapNode,
+async fn handle_post_to_group(
+    ctx: HandlerContext,
+    cmd: PostToGroup,
     req: RequestSender,
     mut res: ReseponseReceiver,
 ) -> Result<()> {
-    debug!(name = name, "Adding bootstrap node");
-    let mut config = get_current_config(name, &req, &mut res).await?;
-    let new_bootstrap_node = BootstrapNode::from_strings(&cmd.id, &cmd.addr)?;
-    config.bootstrap_nodes.push(new_bootstrap_node);
+    let content = match (cmd.text, cmd.file) {
+        (Some(text), _) => MessageContent::Text(text),
+        (None, Some(path)) => MessageContent::Binary(std::fs::read(path)?),
+        (None, None) => bail!("Nothing to post"),
+    };
 
-    req.send(DaemonRequest::OverwriteNodeConfig {
-        node_name: name.to_string(),
-        new_cfg: config,
+    req.send(DaemonRequest::PostToGroup {
+        node_name: cmd.node_name,
+        group_id: cmd.group_id,
+        content,
     })
-    .await?;
+    .await
+    .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
+
+    let response = res
+        .recv()
+        .await
+        .ok_or(anyhow!("Daemon returned no response"))?;
+    if ctx.json {
+        return print_json(&response);
+    }
+
+    match response? {
+        DaemonResponse::GroupPosted { id } => println!("Posted; id={id}"),
+        _ => {
+            bail!("Daemon returned wrong response");
+        }
+    }
 
-    handle_response(&mut res).await
+    Ok(())
 }
 
-async fn handle_add_external_addr(
-    name: &str,
-    sub_cmd: AddExternalAddr,
+async fn handle_group_feed(
+    ctx: HandlerContext,
+    cmd: GroupFeed,
     req: RequestSender,
     mut res: ReseponseReceiver,
 ) -> Result<()> {
-    debug!(name = name, "Adding external address");
-    let mut config = get_current_config(name, &req, &mut res).await?;
-    let new_external_addr = Multiaddr::from_str(&sub_cmd.addr)?;
-    config.external_addresses.push(new_external_addr);
-
-    req.send(DaemonRequest::OverwriteNodeConfig {
-        node_name: name.to_string(),
-        new_cfg: config,
-    })
-    .await?;
-
-    handle_response(&mut res).await
-}
-
-async fn get_current_config(
-    node_name: &str,
-    req: &RequestSender,
-    res: &mut ReseponseReceiver,
-) -> Result<NodeConfig> {
-    req.send(DaemonRequest::GetNodeConfig {
-        node_name: node_name.to_string(),
+    req.send(DaemonRequest::GetGroupFeed {
+        node_name: cmd.node_name,
+        group_id: cmd.group_id,
     })
-    .await?;
+    .await
+    .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
 
-    let current_config = res
+    let response = res
         .recv()
         .await
-        .ok_or(anyhow!("failed to get response"))
-        .inspect_err(|e| error!(err = e.to_string(), "Failed to receive response"))?
-        .inspect_err(|e| error!(err = e.to_string(), "Failed to get current config"))?;
+        .ok_or(anyhow!("Daemon returned no response"))?;
+    if ctx.json {
+        return print_json(&response);
+    }
 
-    let config = match current_config {
-        DaemonResponse::NodeConfig(cfg) => cfg,
+    match response? {
+        DaemonResponse::GroupFeed(posts) => {
+            let rows = posts
+                .iter()
+                .map(|p| p.into())
+                .collect::<Vec<GroupPostRow>>();
+            let mut table = Table::new(rows);
+
+            if ctx.machine_readable {
+                table.with(Style::blank());
+            } else {
+                table.with(Style::modern());
+            }
+
+            println!("{table}");
+        }
         _ => {
-            error!("Expected response to be NodeConfig, but it is not");
-            bail!("Response is not NodeConfig");
+            bail!("Daemon returned wrong response");
         }
-    };
+    }
 
-    Ok(config)
+    Ok(())
 }
 
-async fn handle_stop_node(
-    cmd: StopNode,
+async fn handle_audit_log(
+    ctx: HandlerContext,
+    cmd: AuditLog,
     req: RequestSender,
     mut res: ReseponseReceiver,
 ) -> Result<()> {
-    debug!(name = cmd.name, "Stopping node");
-    req.send(DaemonRequest::StopNode {
-        node_name: cmd.name,
+    let since = cmd
+        .since_secs
+        .map(|secs| SystemTime::now() - Duration::from_secs(secs));
+    let filter = AuditFilter {
+        peer_id: cmd.peer,
+        object_id: cmd.object,
+        kind: cmd.kind,
+        since,
+        rejected_only: cmd.rejected,
+        limit: cmd.limit,
+    };
+
+    req.send(DaemonRequest::GetAuditLog {
+        node_name: cmd.node_name,
+        filter,
     })
     .await
     .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
 
-    handle_response(&mut res).await
+    let response = res
+        .recv()
+        .await
+        .ok_or(anyhow!("Daemon returned no response"))?;
+    if ctx.json {
+        return print_json(&response);
+    }
+
+    match response? {
+        DaemonResponse::AuditLog(entries) => {
+            let rows = entries
+                .iter()
+                .map(|e| e.into())
+                .collect::<Vec<AuditEntryRow>>();
+            let mut table = Table::new(rows);
+
+            if ctx.machine_readable {
+                table.with(Style::blank());
+            } else {
+                table.with(Style::modern());
+            }
+
+            println!("{table}");
+        }
+        _ => {
+            bail!("Daemon returned wrong response");
+        }
+    }
+
+    Ok(())
 }
 
-async fn handle_provide_file(
-    cmd: ProvideFile,
+async fn handle_stats_history(
+    ctx: HandlerContext,
+    cmd: StatsHistory,
     req: RequestSender,
     mut res: ReseponseReceiver,
 ) -> Result<()> {
-    debug!(path = format!("{:?}", &cmd.path), "Providing file");
-    let path = std::path::absolute(&cmd.path).expect("Path to be converted into absolute path");
-
-    req.send(DaemonRequest::ProvideFile {
+    req.send(DaemonRequest::GetNodeStatsHistory {
         node_name: cmd.node_name,
-        path,
+        since: Some(SystemTime::now() - Duration::from_secs(cmd.since_secs)),
+        until: None,
     })
     .await
     .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
@@ -508,31 +4359,42 @@ async fn handle_provide_file(
         .recv()
         .await
         .ok_or(anyhow!("Daemon returned no response"))?;
+    if ctx.json {
+        return print_json(&response);
+    }
 
-    match response {
-        Ok(DaemonResponse::FileProvided { id }) => {
-            info!(id = id, "File provided");
-            println!("{id}");
-            return Ok(());
-        }
-        Err(e) => {
-            println!("Error providing file: {e}");
-            bail!("Error providing file");
+    match response? {
+        DaemonResponse::NodeStatsHistory(samples) => {
+            let rows = samples
+                .iter()
+                .map(|s| s.into())
+                .collect::<Vec<StatsSampleRow>>();
+            let mut table = Table::new(rows);
+
+            if ctx.machine_readable {
+                table.with(Style::blank());
+            } else {
+                table.with(Style::modern());
+            }
+
+            println!("{table}");
         }
         _ => {
             bail!("Daemon returned wrong response");
         }
     }
+
+    Ok(())
 }
 
-async fn handle_download_file(
-    cmd: DownloadFile,
+async fn handle_scheduled_tasks(
+    ctx: HandlerContext,
+    cmd: ScheduledTasks,
     req: RequestSender,
     mut res: ReseponseReceiver,
 ) -> Result<()> {
-    req.send(DaemonRequest::DownloadFile {
+    req.send(DaemonRequest::ListScheduledTasks {
         node_name: cmd.node_name,
-        id: cmd.id,
     })
     .await
     .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
@@ -541,30 +4403,27 @@ async fn handle_download_file(
         .recv()
         .await
         .ok_or(anyhow!("Daemon returned no response"))?;
+    if ctx.json {
+        return print_json(&response);
+    }
 
-    match response {
-        Ok(DaemonResponse::FileDownloaded { data, .. }) => {
-            println!("{}", String::from_utf8(data.content)?);
-        }
-        Err(DaemonError::Other(_)) => {
-            println!("Failed to download file");
-        }
-        _ => {
-            bail!("Daemon returned wrong response");
-        }
+    match response? {
+        DaemonResponse::ScheduledTasks(tasks) => print_scheduled_tasks(ctx, &tasks),
+        _ => bail!("Daemon returned wrong response"),
     }
 
     Ok(())
 }
 
-async fn handle_get_providers(
-    cmd: GetProviders,
+async fn handle_run_task(
+    ctx: HandlerContext,
+    cmd: RunTask,
     req: RequestSender,
     mut res: ReseponseReceiver,
 ) -> Result<()> {
-    req.send(DaemonRequest::GetProviders {
+    req.send(DaemonRequest::RunTaskNow {
         node_name: cmd.node_name,
-        id: cmd.id,
+        task: cmd.task,
     })
     .await
     .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
@@ -573,30 +4432,49 @@ async fn handle_get_providers(
         .recv()
         .await
         .ok_or(anyhow!("Daemon returned no response"))?;
-    match response {
-        Ok(DaemonResponse::Providers { ids, .. }) => {
-            for provider in ids {
-                println!("{provider}");
+    if ctx.json {
+        return print_json(&response);
+    }
+
+    match response? {
+        DaemonResponse::TaskRun(info) => {
+            print_scheduled_tasks(ctx, std::slice::from_ref(&info));
+            if let Some(e) = info.last_error {
+                bail!("Task {} failed: {e}", info.task);
             }
         }
-        Err(e) => {
-            error!(err = e.to_string(), "Error publishing file");
-        }
-        _ => {
-            bail!("Daemon returned wrong response");
-        }
+        _ => bail!("Daemon returned wrong response"),
     }
 
     Ok(())
 }
 
-async fn handle_get_peer_id(
-    cmd: GetPeerID,
+fn print_scheduled_tasks(ctx: HandlerContext, tasks: &[ScheduledTaskInfo]) {
+    let rows = tasks
+        .iter()
+        .map(|t| t.into())
+        .collect::<Vec<ScheduledTaskRow>>();
+    let mut table = Table::new(rows);
+
+    if ctx.machine_readable {
+        table.with(Style::blank());
+    } else {
+        table.with(Style::modern());
+    }
+
+    println!("{table}");
+}
+
+async fn handle_tag(
+    ctx: HandlerContext,
+    cmd: Tag,
     req: RequestSender,
     mut res: ReseponseReceiver,
 ) -> Result<()> {
-    req.send(DaemonRequest::GetPeerId {
+    req.send(DaemonRequest::TagObject {
         node_name: cmd.node_name,
+        id: cmd.id,
+        tags: cmd.tags,
     })
     .await
     .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
@@ -604,102 +4482,143 @@ async fn handle_get_peer_id(
     let response = res
         .recv()
         .await
-        .ok_or(anyhow!("Daemon returned no response"))??;
+        .ok_or(anyhow!("Daemon returned no response"))?;
+    if ctx.json {
+        return print_json(&response);
+    }
 
-    match response {
-        DaemonResponse::PeerId { id } => {
+    match response? {
+        DaemonResponse::TagsPublished { id } => {
+            info!(id = id, "Tags published");
             println!("{id}");
         }
-        _ => {
-            bail!("Daemon returned wrong response");
-        }
+        _ => bail!("Daemon returned wrong response"),
     }
 
     Ok(())
 }
 
-async fn handle_dial(cmd: Dial, req: RequestSender, mut res: ReseponseReceiver) -> Result<()> {
-    req.send(DaemonRequest::Dial {
+async fn handle_search(
+    ctx: HandlerContext,
+    cmd: Search,
+    req: RequestSender,
+    mut res: ReseponseReceiver,
+) -> Result<()> {
+    req.send(DaemonRequest::SearchObjects {
         node_name: cmd.node_name,
-        peer_id: cmd.peer_id.clone(),
-        addr: cmd.addr.clone(),
+        query: cmd.query.join(" "),
+        network: cmd.network,
     })
     .await
     .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
 
-    match res.recv().await {
-        Some(Ok(r)) => {
-            info!(response = format!("{r:?}"), "Daemon responds");
-            println!("Dialing successful");
-        }
-        Some(Err(e)) => {
-            error!(err = e.to_string(), "Error dialing peer");
-            println!("Error dialing peer");
-        }
-        None => {
-            error!("Failed to receive response");
+    let response = res
+        .recv()
+        .await
+        .ok_or(anyhow!("Daemon returned no response"))?;
+    if ctx.json {
+        return print_json(&response);
+    }
+
+    match response? {
+        DaemonResponse::SearchResults(results) => {
+            let rows = results
+                .iter()
+                .map(|r| r.into())
+                .collect::<Vec<SearchResultRow>>();
+            let mut table = Table::new(rows);
+
+            if ctx.machine_readable {
+                table.with(Style::blank());
+            } else {
+                table.with(Style::modern());
+            }
+
+            println!("{table}");
         }
-    };
+        _ => bail!("Daemon returned wrong response"),
+    }
+
     Ok(())
 }
 
-async fn handle_publish_file(
-    cmd: PublishFile,
+async fn handle_history(
+    ctx: HandlerContext,
+    cmd: History,
     req: RequestSender,
     mut res: ReseponseReceiver,
 ) -> Result<()> {
-    req.send(DaemonRequest::PublishFile {
+    req.send(DaemonRequest::GetHistory {
         node_name: cmd.node_name,
-        path: cmd.path,
+        id: cmd.id,
     })
     .await
     .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
 
-    let resp = res
+    let response = res
         .recv()
         .await
         .ok_or(anyhow!("Daemon returned no response"))?;
-    match resp {
-        Ok(DaemonResponse::FilePublished { id }) => {
-            info!(id = id, "File published");
-            println!("{id}");
-            return Ok(());
-        }
-        Err(e) => {
-            println!("Error publishing file: {e}");
-            bail!("Error publishing file");
-        }
-        _ => {
-            bail!("Daemon returned wrong response");
+    if ctx.json {
+        return print_json(&response);
+    }
+
+    match response? {
+        DaemonResponse::History { revisions } => {
+            // The first revision is 1, the latest one is listed first
+            let rows = revisions
+                .iter()
+                .enumerate()
+                .map(|(i, id)| HistoryRow {
+                    revision: revisions.len() - i,
+                    id: id.clone(),
+                })
+                .collect::<Vec<HistoryRow>>();
+            let mut table = Table::new(rows);
+
+            if ctx.machine_readable {
+                table.with(Style::blank());
+            } else {
+                table.with(Style::modern());
+            }
+
+            println!("{table}");
         }
+        _ => bail!("Daemon returned wrong response"),
     }
+
+    Ok(())
 }
 
-async fn handle_get_published_objects(
+async fn handle_search_text(
     ctx: HandlerContext,
-    cmd: GetPublishedObjects,
+    cmd: SearchText,
     req: RequestSender,
     mut res: ReseponseReceiver,
 ) -> Result<()> {
-    req.send(DaemonRequest::GetPublishedObjects {
+    req.send(DaemonRequest::SearchText {
         node_name: cmd.node_name,
+        query: cmd.query.join(" "),
+        limit: cmd.limit,
     })
     .await
     .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
 
-    let resp = res
+    let response = res
         .recv()
         .await
         .ok_or(anyhow!("Daemon returned no response"))?;
-    match resp {
-        Ok(DaemonResponse::PublishedObjectsList {
-            object_infos: files,
-        }) => {
-            let file_info_rows = files
+    if ctx.json {
+        return print_json(&response);
+    }
+
+    match response? {
+        DaemonResponse::TextSearchResults(matches) => {
+            let rows = matches
                 .iter()
-                .map(|info| info.into())
-                .collect::<Vec<TypedObjectInfoRow>>();
-            let mut table = Table::new(file_info_rows);
+                .map(|m| m.into())
+                .collect::<Vec<TextMatchRow>>();
+            let mut table = Table::new(rows);
 
             if ctx.machine_readable {
                 table.with(Style::blank());
@@ -707,64 +4626,138 @@ async fn handle_get_published_objects(
                 table.with(Style::modern());
             }
 
-            let table = table.to_string();
             println!("{table}");
         }
-        Err(e) => {
-            println!("Error getting published files list: {e}");
-            bail!("Error getting published files list");
-        }
-        _ => {
-            bail!("Daemon returned wrong response");
-        }
+        _ => bail!("Daemon returned wrong response"),
     }
+
     Ok(())
 }
 
-async fn handle_delete_object(
-    cmd: DeleteObject,
+async fn handle_popular_objects(
+    ctx: HandlerContext,
+    cmd: PopularObjects,
     req: RequestSender,
     mut res: ReseponseReceiver,
 ) -> Result<()> {
-    req.send(DaemonRequest::DeleteObject {
+    req.send(DaemonRequest::GetPopularObjects {
         node_name: cmd.node_name,
-        object_id: cmd.object_id,
+        limit: cmd.limit,
     })
     .await
     .inspect_err(|e| error!(err = e.to_string(), "Failed to send message"))?;
 
-    let resp = res
+    let response = res
         .recv()
         .await
         .ok_or(anyhow!("Daemon returned no response"))?;
-    match resp {
-        Ok(DaemonResponse::ObjectDeleted {
-            deleted_myself,
-            deleted_count,
-            failed_count,
-        }) => {
-            println!("Deleted myself: {deleted_myself}");
-            println!("Successful deletes: {deleted_count}");
-            println!("Failed deletes: {failed_count}");
+    if ctx.json {
+        return print_json(&response);
+    }
+
+    match response? {
+        DaemonResponse::PopularObjects(objects) => {
+            let rows = objects
+                .iter()
+                .map(|o| o.into())
+                .collect::<Vec<PopularObjectRow>>();
+            let mut table = Table::new(rows);
+
+            if ctx.machine_readable {
+                table.with(Style::blank());
+            } else {
+                table.with(Style::modern());
+            }
+
+            println!("{table}");
         }
-        Err(e) => {
-            println!("Error deleting object: {e}");
-            bail!("Error deleting object");
+        _ => bail!("Daemon returned wrong response"),
+    }
+
+    Ok(())
+}
+
+/// Prints the events of the journal with their time since the first one, then
+/// the queries and the connections reconstructed from them
+fn handle_replay(ctx: HandlerContext, cmd: &Replay) -> Result<()> {
+    let entries = journal::read_journal(&cmd.journal)
+        .inspect_err(|e| error!(err = e.to_string(), "Failed to read the journal"))?;
+    if ctx.json {
+        for entry in &entries {
+            println!("{}", serde_json::to_string(entry)?);
         }
-        _ => {
-            bail!("Daemon returned wrong response");
+        return Ok(());
+    }
+
+    let Some(first) = entries.first() else {
+        println!("The journal is empty");
+        return Ok(());
+    };
+    let start = first.at;
+    for entry in &entries {
+        let shown = cmd.all
+            || !matches!(
+                entry.event,
+                JournalEvent::Message { .. } | JournalEvent::Other { .. }
+            );
+        if shown {
+            println!("{} {}", offset(start, entry.at), entry.event);
         }
     }
+
+    let queries = journal::queries(&entries)
+        .iter()
+        .map(|q| QuerySpanRow::new(start, q))
+        .collect::<Vec<_>>();
+    let connections = journal::connections(&entries)
+        .iter()
+        .map(|c| ConnectionSpanRow::new(start, c))
+        .collect::<Vec<_>>();
+    let mut queries = Table::new(queries);
+    let mut connections = Table::new(connections);
+
+    if ctx.machine_readable {
+        queries.with(Style::blank());
+        connections.with(Style::blank());
+    } else {
+        queries.with(Style::modern());
+        connections.with(Style::modern());
+    }
+
+    println!("\n{queries}\n\n{connections}");
+
     Ok(())
 }
 
+/// The time since the start of the journal
+fn offset(start: SystemTime, at: SystemTime) -> String {
+    let offset = at.duration_since(start).unwrap_or_default();
+    format!("+{:.3}s", offset.as_secs_f64())
+}
+
+/// The time between the events, if the second one happened
+fn span_duration(from: SystemTime, to: Option<SystemTime>) -> String {
+    match to {
+        Some(to) => format!(
+            "{:.3}s",
+            to.duration_since(from).unwrap_or_default().as_secs_f64()
+        ),
+        None => "unfinished".to_string(),
+    }
+}
+
 async fn handle_response(
+    ctx: HandlerContext,
     response_receiver: &mut tokio::sync::mpsc::Receiver<Result<DaemonResponse, DaemonError>>,
 ) -> Result<()> {
     match response_receiver.recv().await {
         Some(r) => {
             info!(response = format!("{r:?}"), "Daemon responds");
 
+            if ctx.json {
+                return print_json(&r);
+            }
+
             if let Err(e) = r {
                 return Err(e.into());
             }
@@ -777,12 +4770,40 @@ async fn handle_response(
     Ok(())
 }
 
+/// Prints the response of the daemon as a single line of JSON. Successful responses
+/// are printed as `{"ok": <response>}`, errors as `{"error": <error>}`.
+/// An error response is also returned as an error, so the exit code is not 0
+fn print_json(response: &Result<DaemonResponse, DaemonError>) -> Result<()> {
+    let json = match response {
+        Ok(r) => serde_json::json!({ "ok": r }),
+        Err(e) => serde_json::json!({ "error": e }),
+    };
+    println!("{}", serde_json::to_string(&json)?);
+
+    match response {
+        Ok(_) => Ok(()),
+        Err(e) => Err(anyhow!(e.to_string())),
+    }
+}
+
+impl From<&ModuleInfo> for ModuleInfoRow {
+    fn from(value: &ModuleInfo) -> Self {
+        Self {
+            name: value.name.clone(),
+            uuid: value.uuid.to_string(),
+            enabled: value.enabled,
+            settings: value.settings.clone().unwrap_or("N/A".to_string()),
+        }
+    }
+}
+
 impl From<&NodeInfo> for NodeInfoRow {
     fn from(value: &NodeInfo) -> Self {
         Self {
             name: value.name.to_string(),
             peer_id: value.peer_id.clone(),
             is_running: value.is_running,
+            is_locked: value.is_locked,
             first_cfg_address: value
                 .config_addresses
                 .first()
@@ -797,6 +4818,418 @@ impl From<&NodeInfo> for NodeInfoRow {
     }
 }
 
+fn node_status_rows(status: &NodeStatus) -> Vec<NodeStatusRow> {
+    vec![
+        ("uptime", format!("{}s", status.uptime.as_secs())),
+        ("connected_peers", status.connected_peers.to_string()),
+        ("routing_table_size", status.routing_table_size.to_string()),
+        ("pending_queries", status.pending_queries.to_string()),
+        ("bytes_sent", status.bytes_sent.to_string()),
+        ("bytes_received", status.bytes_received.to_string()),
+        ("objects_served", status.objects_served.to_string()),
+        ("active_uploads", status.uploads.active_uploads.to_string()),
+        (
+            "uploaded_last_hour",
+            status.uploads.bytes_last_hour.to_string(),
+        ),
+        (
+            "rejected_uploads",
+            status.uploads.rejected_requests.to_string(),
+        ),
+        (
+            "rejected_records",
+            status.rejected_records.total().to_string(),
+        ),
+    ]
+    .into_iter()
+    .map(|(property, value)| NodeStatusRow {
+        property: property.to_string(),
+        value,
+    })
+    .collect()
+}
+
+fn object_verification_rows(verification: &ObjectVerification) -> Vec<NodeStatusRow> {
+    let signatures = match verification.signatures.is_empty() {
+        true => "not signed".to_string(),
+        false => verification
+            .signatures
+            .iter()
+            .map(|s| format!("{s:?}"))
+            .collect::<Vec<_>>()
+            .join(", "),
+    };
+    let or_na = |v: Option<String>| v.unwrap_or("N/A".to_string());
+
+    vec![
+        ("id", verification.id.clone()),
+        ("stored_locally", verification.stored_locally.to_string()),
+        ("published", verification.published.to_string()),
+        (
+            "hash_matches",
+            or_na(verification.hash_matches.map(|h| h.to_string())),
+        ),
+        (
+            "type_id",
+            or_na(verification.type_id.map(|t| t.to_string())),
+        ),
+        ("signatures", signatures),
+        ("providers", verification.providers.to_string()),
+        ("healthy", verification.is_healthy().to_string()),
+    ]
+    .into_iter()
+    .map(|(property, value)| NodeStatusRow {
+        property: property.to_string(),
+        value,
+    })
+    .collect()
+}
+
+fn object_info_rows(info: &ObjectInfo) -> Vec<NodeStatusRow> {
+    let ago = |at: SystemTime| {
+        SystemTime::now()
+            .duration_since(at)
+            .map(|d| format!("{}s ago", d.as_secs()))
+            .unwrap_or_else(|_| "now".to_string())
+    };
+    let mut rows = vec![
+        ("id", info.id.clone()),
+        ("type_id", info.type_id.to_string()),
+        ("size", info.size.to_string()),
+        ("stored_locally", info.stored_locally.to_string()),
+        ("published", info.published.to_string()),
+        (
+            "signer",
+            info.signer.clone().unwrap_or("unknown".to_string()),
+        ),
+        (
+            "created",
+            info.created_at.map(ago).unwrap_or("unknown".to_string()),
+        ),
+    ];
+    match &info.provenance {
+        Some(provenance) => {
+            let received = ago(provenance.received_at);
+            let signature = match provenance.signature {
+                Some(status) => format!("{status:?}"),
+                None => "not checked".to_string(),
+            };
+            rows.extend([
+                ("source", provenance.kind.to_string()),
+                (
+                    "source_peer",
+                    provenance.source_peer.clone().unwrap_or("N/A".to_string()),
+                ),
+                ("received", received),
+                ("signature", signature),
+            ]);
+        }
+        None => rows.push(("source", "unknown".to_string())),
+    }
+
+    rows.into_iter()
+        .map(|(property, value)| NodeStatusRow {
+            property: property.to_string(),
+            value,
+        })
+        .collect()
+}
+
+impl From<(usize, &ObjectLayer)> for ObjectLayerRow {
+    fn from((depth, layer): (usize, &ObjectLayer)) -> Self {
+        let fields = layer
+            .properties
+            .iter()
+            .map(|(name, value)| format!("{name}: {value}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        Self {
+            depth,
+            type_name: layer
+                .type_name
+                .clone()
+                .unwrap_or_else(|| layer.type_id.to_string()),
+            size: layer.size,
+            fields,
+        }
+    }
+}
+
+impl From<&BucketInfo> for BucketInfoRow {
+    fn from(value: &BucketInfo) -> Self {
+        Self {
+            bucket: value.index,
+            entries: value.num_entries,
+            connected: value.num_connected,
+        }
+    }
+}
+
+impl From<&PeerInfo> for RoutingPeerRow {
+    fn from(value: &PeerInfo) -> Self {
+        Self {
+            peer_id: value.peer_id.clone(),
+            bucket: value
+                .bucket
+                .map(|b| b.to_string())
+                .unwrap_or_else(|| "-".to_string()),
+            connected: value.connected,
+            latency_ms: value
+                .latency
+                .map(|l| l.as_millis().to_string())
+                .unwrap_or_else(|| "-".to_string()),
+            addresses: value.addresses.join("\n"),
+        }
+    }
+}
+
+impl From<&PeerScore> for PeerScoreRow {
+    fn from(value: &PeerScore) -> Self {
+        Self {
+            peer_id: value.peer_id.clone(),
+            failed_integrity_checks: value.failed_integrity_checks,
+            timeouts: value.timeouts,
+            protocol_violations: value.protocol_violations,
+        }
+    }
+}
+
+impl From<&Contact> for ContactRow {
+    fn from(value: &Contact) -> Self {
+        let last_seen = match value.last_seen {
+            Some(time) => SystemTime::now()
+                .duration_since(time)
+                .map(|d| format!("{}s ago", d.as_secs()))
+                .unwrap_or_else(|_| "now".to_string()),
+            None => "never".to_string(),
+        };
+
+        Self {
+            alias: value.alias.clone(),
+            peer_id: value.peer_id.clone(),
+            trust: value.trust.to_string(),
+            address: value.address.clone().unwrap_or_default(),
+            last_seen,
+        }
+    }
+}
+
+impl From<&WatchDir> for WatchDirRow {
+    fn from(value: &WatchDir) -> Self {
+        Self {
+            path: value.path.display().to_string(),
+            ignore: value.ignore.join(" "),
+            unpublish_old: value.unpublish_old,
+        }
+    }
+}
+
+impl From<&InboxMessage> for InboxMessageRow {
+    fn from(value: &InboxMessage) -> Self {
+        let received = SystemTime::now()
+            .duration_since(value.received_at)
+            .map(|d| format!("{}s ago", d.as_secs()))
+            .unwrap_or_else(|_| "now".to_string());
+        let content = match &value.content {
+            MessageContent::Text(text) => text.clone(),
+            MessageContent::Binary(data) => format!("<{} bytes>", data.len()),
+        };
+
+        Self {
+            id: value.id.clone(),
+            sender: value.sender.clone(),
+            received,
+            encrypted: value.encrypted,
+            content,
+        }
+    }
+}
+
+impl From<&GroupPost> for GroupPostRow {
+    fn from(value: &GroupPost) -> Self {
+        let posted = SystemTime::now()
+            .duration_since(value.posted_at)
+            .map(|d| format!("{}s ago", d.as_secs()))
+            .unwrap_or_else(|_| "now".to_string());
+        let content = match &value.content {
+            MessageContent::Text(text) => text.clone(),
+            MessageContent::Binary(data) => format!("<{} bytes>", data.len()),
+        };
+
+        Self {
+            id: value.id.clone(),
+            author: value.author.clone(),
+            posted,
+            content,
+        }
+    }
+}
+
+impl From<&SearchResult> for SearchResultRow {
+    fn from(value: &SearchResult) -> Self {
+        Self {
+            id: value.id.clone(),
+            matched_tags: value.matched_tags.join(", "),
+            local: value.local,
+        }
+    }
+}
+
+impl From<&TextMatch> for TextMatchRow {
+    fn from(value: &TextMatch) -> Self {
+        Self {
+            id: value.id.clone(),
+            name: value.name.clone(),
+            snippet: value.snippet.clone(),
+        }
+    }
+}
+
+impl From<&ObjectPopularity> for PopularObjectRow {
+    fn from(value: &ObjectPopularity) -> Self {
+        let last_served = SystemTime::now()
+            .duration_since(value.last_served)
+            .map(|d| format!("{}s ago", d.as_secs()))
+            .unwrap_or_else(|_| "now".to_string());
+
+        Self {
+            id: value.id.clone(),
+            fetches: value.fetch_count,
+            last_served,
+            published: value.published,
+        }
+    }
+}
+
+impl From<&ScheduledTaskInfo> for ScheduledTaskRow {
+    fn from(value: &ScheduledTaskInfo) -> Self {
+        let now = SystemTime::now();
+        let next_run = match (value.running, value.next_run) {
+            (true, _) => "running".to_string(),
+            (false, Some(at)) => at
+                .duration_since(now)
+                .map(|d| format!("in {}s", d.as_secs()))
+                .unwrap_or_else(|_| "now".to_string()),
+            (false, None) => "on demand".to_string(),
+        };
+        let last_run = match value.last_run {
+            Some(at) => now
+                .duration_since(at)
+                .map(|d| format!("{}s ago", d.as_secs()))
+                .unwrap_or_else(|_| "now".to_string()),
+            None => "never".to_string(),
+        };
+        let result = match (&value.last_error, value.last_duration) {
+            (Some(e), _) => format!("failed: {e}"),
+            (None, Some(duration)) => format!("ok in {}ms", duration.as_millis()),
+            (None, None) => String::new(),
+        };
+
+        Self {
+            task: value.task.to_string(),
+            interval: value
+                .interval
+                .map(|i| format!("{}s", i.as_secs()))
+                .unwrap_or_else(|| "-".to_string()),
+            next_run,
+            runs: value.runs,
+            last_run,
+            result,
+        }
+    }
+}
+
+impl From<&NodeStatsSample> for StatsSampleRow {
+    fn from(value: &NodeStatsSample) -> Self {
+        let at = SystemTime::now()
+            .duration_since(value.at)
+            .map(|d| format!("{}s ago", d.as_secs()))
+            .unwrap_or_else(|_| "now".to_string());
+
+        Self {
+            at,
+            connected_peers: value.connected_peers,
+            bytes_sent: value.bytes_sent,
+            bytes_received: value.bytes_received,
+            objects_served: value.objects_served,
+        }
+    }
+}
+
+impl From<&AuditEntry> for AuditEntryRow {
+    fn from(value: &AuditEntry) -> Self {
+        let at = SystemTime::now()
+            .duration_since(value.at)
+            .map(|d| format!("{}s ago", d.as_secs()))
+            .unwrap_or_else(|_| "now".to_string());
+        let decision = match &value.rejection {
+            Some(reason) => format!("rejected: {reason}"),
+            None => "served".to_string(),
+        };
+
+        Self {
+            at,
+            kind: value.kind.to_string(),
+            peer_id: value.peer_id.clone(),
+            object_id: value.object_id.clone(),
+            decision,
+        }
+    }
+}
+
+impl QuerySpanRow {
+    fn new(start: SystemTime, value: &QuerySpan) -> Self {
+        let result = match (&value.error, value.finished_at) {
+            (Some(e), _) => format!("failed: {e}"),
+            (None, Some(_)) => "finished".to_string(),
+            (None, None) => "running".to_string(),
+        };
+
+        Self {
+            id: value.id.clone(),
+            kind: value.kind.clone(),
+            started: offset(start, value.started_at),
+            duration: span_duration(value.started_at, value.finished_at),
+            steps: value.steps,
+            result,
+        }
+    }
+}
+
+impl ConnectionSpanRow {
+    fn new(start: SystemTime, value: &ConnectionSpan) -> Self {
+        let direction = match value.outbound {
+            true => "outbound",
+            false => "inbound",
+        };
+
+        Self {
+            peer_id: value.peer_id.clone(),
+            address: value.address.clone(),
+            direction: direction.to_string(),
+            opened: offset(start, value.opened_at),
+            duration: span_duration(value.opened_at, value.closed_at),
+            cause: value.cause.clone().unwrap_or_default(),
+        }
+    }
+}
+
+impl From<&PublishFileResult> for PublishFileResultRow {
+    fn from(value: &PublishFileResult) -> Self {
+        let (id, error) = match &value.result {
+            Ok(id) => (id.clone(), String::new()),
+            Err(e) => (String::new(), e.clone()),
+        };
+
+        Self {
+            path: value.path.display().to_string(),
+            id,
+            error,
+        }
+    }
+}
+
 impl From<&TypedObjectInfo> for TypedObjectInfoRow {
     fn from(value: &TypedObjectInfo) -> Self {
         Self {
@@ -0,0 +1,242 @@
+use anyhow::{anyhow, Result};
+use fuser::{
+    FileAttr, FileType, Filesystem, MountOption, ReplyAttr, ReplyData, ReplyDirectory, ReplyEntry,
+    Request,
+};
+use liberum_core::{DaemonError, DaemonRequest, DaemonResponse};
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::time::{Duration, SystemTime};
+use tokio::runtime::Handle;
+use tokio::sync::mpsc::{Receiver, Sender};
+use tracing::debug;
+
+///! The module contains the read-only filesystem the `mount` command mounts, so
+///! the files published on the network can be read with the ordinary tools. The
+///! root directory lists the files published by the node, each named by its ID.
+///! Any other file can be opened by its ID too, e.g. `cat <mountpoint>/<id>`, it
+///! is added to the directory once it's found.
+///!
+///! The content is asked from the daemon when the file is first looked at, the
+///! node reads it from its vault or downloads it from the providers, and is kept
+///! in memory until the filesystem is unmounted. There are no directory objects in
+///! the network yet, so the root is the only directory.
+
+const TTL: Duration = Duration::from_secs(1);
+const ROOT_INO: u64 = 1;
+const BLOCK_SIZE: u32 = 512;
+
+/// The options the filesystem is mounted with
+pub fn mount_options() -> Vec<MountOption> {
+    vec![
+        MountOption::RO,
+        MountOption::NoExec,
+        MountOption::FSName("liberum".to_string()),
+    ]
+}
+
+struct Entry {
+    id: String,
+    content: Option<Vec<u8>>,
+}
+
+pub struct VaultFs {
+    node_name: String,
+    req: Sender<DaemonRequest>,
+    res: Receiver<Result<DaemonResponse, DaemonError>>,
+    runtime: Handle,
+    /// The inode of an entry is its index plus 2, the root is 1
+    entries: Vec<Entry>,
+    inodes: HashMap<String, u64>,
+    mounted_at: SystemTime,
+    uid: u32,
+    gid: u32,
+}
+
+impl VaultFs {
+    /// The filesystem with the published files. Must be created in the runtime
+    /// the daemon connection runs in
+    pub fn new(
+        node_name: String,
+        published_ids: Vec<String>,
+        req: Sender<DaemonRequest>,
+        res: Receiver<Result<DaemonResponse, DaemonError>>,
+    ) -> VaultFs {
+        let mut fs = VaultFs {
+            node_name,
+            req,
+            res,
+            runtime: Handle::current(),
+            entries: vec![],
+            inodes: HashMap::new(),
+            mounted_at: SystemTime::now(),
+            // SAFETY: the calls can't fail and don't touch any memory
+            uid: unsafe { libc::getuid() },
+            gid: unsafe { libc::getgid() },
+        };
+        for id in published_ids {
+            fs.add_entry(id);
+        }
+        fs
+    }
+
+    fn add_entry(&mut self, id: String) -> u64 {
+        if let Some(ino) = self.inodes.get(&id) {
+            return *ino;
+        }
+        let ino = self.entries.len() as u64 + 2;
+        self.inodes.insert(id.clone(), ino);
+        self.entries.push(Entry { id, content: None });
+        ino
+    }
+
+    fn entry(&mut self, ino: u64) -> Option<&mut Entry> {
+        let index = ino.checked_sub(2)?;
+        self.entries.get_mut(index as usize)
+    }
+
+    /// The content of the file, downloaded by the daemon the first time
+    fn content(&mut self, ino: u64) -> Option<&[u8]> {
+        let entry = self.entry(ino)?;
+        if entry.content.is_none() {
+            let id = entry.id.clone();
+            match self.download(id.clone()) {
+                Ok(content) => self.entry(ino)?.content = Some(content),
+                Err(e) => {
+                    debug!(id = id, err = e.to_string(), "Could not get mounted file");
+                    return None;
+                }
+            }
+        }
+        self.entry(ino)?.content.as_deref()
+    }
+
+    fn download(&mut self, id: String) -> Result<Vec<u8>> {
+        let request = DaemonRequest::DownloadFile {
+            node_name: self.node_name.clone(),
+            id,
+            group: None,
+            force_network: false,
+        };
+        let runtime = self.runtime.clone();
+        runtime.block_on(async {
+            self.req.send(request).await?;
+            match self.res.recv().await {
+                Some(Ok(DaemonResponse::FileDownloaded { data, .. })) => Ok(data.content),
+                Some(Ok(_)) => Err(anyhow!("Daemon returned wrong response")),
+                Some(Err(e)) => Err(anyhow!("{e}")),
+                None => Err(anyhow!("Daemon returned no response")),
+            }
+        })
+    }
+
+    fn attr(&mut self, ino: u64) -> Option<FileAttr> {
+        let (kind, perm, size) = match ino {
+            ROOT_INO => (FileType::Directory, 0o555, 0),
+            ino => (
+                FileType::RegularFile,
+                0o444,
+                self.content(ino)?.len() as u64,
+            ),
+        };
+        Some(FileAttr {
+            ino,
+            size,
+            blocks: size.div_ceil(BLOCK_SIZE as u64),
+            atime: self.mounted_at,
+            mtime: self.mounted_at,
+            ctime: self.mounted_at,
+            crtime: self.mounted_at,
+            kind,
+            perm,
+            nlink: if kind == FileType::Directory { 2 } else { 1 },
+            uid: self.uid,
+            gid: self.gid,
+            rdev: 0,
+            blksize: BLOCK_SIZE,
+            flags: 0,
+        })
+    }
+}
+
+impl Filesystem for VaultFs {
+    fn lookup(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEntry) {
+        let Some(id) = name.to_str().filter(|_| parent == ROOT_INO) else {
+            return reply.error(libc::ENOENT);
+        };
+        let known = self.inodes.get(id).copied();
+        let ino = match known {
+            Some(ino) => ino,
+            // Not published by the node, but maybe provided by someone else
+            None if liberum_core::str_to_file_id(id).is_ok() => self.add_entry(id.to_string()),
+            None => return reply.error(libc::ENOENT),
+        };
+        match self.attr(ino) {
+            Some(attr) => reply.entry(&TTL, &attr, 0),
+            None => {
+                if known.is_none() {
+                    self.inodes.remove(id);
+                    self.entries.pop();
+                }
+                reply.error(libc::ENOENT)
+            }
+        }
+    }
+
+    fn getattr(&mut self, _req: &Request<'_>, ino: u64, _fh: Option<u64>, reply: ReplyAttr) {
+        match self.attr(ino) {
+            Some(attr) => reply.attr(&TTL, &attr),
+            None => reply.error(libc::ENOENT),
+        }
+    }
+
+    fn read(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        size: u32,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        reply: ReplyData,
+    ) {
+        let Some(content) = self.content(ino) else {
+            return reply.error(libc::ENOENT);
+        };
+        let start = (offset.max(0) as usize).min(content.len());
+        let end = start.saturating_add(size as usize).min(content.len());
+        reply.data(&content[start..end]);
+    }
+
+    fn readdir(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        mut reply: ReplyDirectory,
+    ) {
+        if ino != ROOT_INO {
+            return reply.error(libc::ENOTDIR);
+        }
+        let entries = [
+            (ROOT_INO, FileType::Directory, "."),
+            (ROOT_INO, FileType::Directory, ".."),
+        ]
+        .into_iter()
+        .chain(
+            self.entries
+                .iter()
+                .enumerate()
+                .map(|(index, entry)| (index as u64 + 2, FileType::RegularFile, entry.id.as_str())),
+        );
+        for (i, (ino, kind, name)) in entries.enumerate().skip(offset.max(0) as usize) {
+            // The offset of the next entry
+            if reply.add(ino, i as i64 + 1, kind, name) {
+                break;
+            }
+        }
+        reply.ok();
+    }
+}
@@ -0,0 +1,54 @@
+use std::time::Instant;
+
+use liberum_core::compression::Compressed;
+use rand::RngCore;
+
+///! Compares the bytes sent or stored with and without the compression, and the
+///! time it takes, for the payloads the nodes typically share. Run with
+///! `cargo bench --bench compression`.
+
+const SIZE: usize = 4 * 1024 * 1024;
+const ROUNDS: u32 = 10;
+
+fn main() {
+    let text = include_str!("../src/lib/proto.rs")
+        .bytes()
+        .cycle()
+        .take(SIZE)
+        .collect::<Vec<u8>>();
+    let json = (0..)
+        .map(|i| format!(r#"{{"id":{i},"name":"file-{i}.txt","tags":["doc","shared"]}},"#))
+        .flat_map(String::into_bytes)
+        .take(SIZE)
+        .collect::<Vec<u8>>();
+    let mut random = vec![0; SIZE];
+    rand::thread_rng().fill_bytes(&mut random);
+
+    println!(
+        "{:<10} {:>6} {:>12} {:>8} {:>12}",
+        "payload", "level", "bytes", "ratio", "MiB/s"
+    );
+    for (name, data) in [("text", text), ("json", json), ("random", random)] {
+        for level in [None, Some(1), Some(3), Some(9)] {
+            let start = Instant::now();
+            let compressed = (0..ROUNDS)
+                .map(|_| Compressed::new(data.clone(), level))
+                .last()
+                .unwrap();
+            let elapsed = start.elapsed() / ROUNDS;
+            let bytes = compressed.data.len();
+            assert_eq!(compressed.decompress(SIZE).unwrap(), data);
+            // The incompressible payloads are never sent bigger than they are
+            assert!(bytes <= data.len());
+
+            println!(
+                "{:<10} {:>6} {:>12} {:>8.3} {:>12.1}",
+                name,
+                level.map_or("off".to_string(), |level| level.to_string()),
+                bytes,
+                bytes as f64 / data.len() as f64,
+                data.len() as f64 / 1024.0 / 1024.0 / elapsed.as_secs_f64(),
+            );
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use std::fmt::Display;
+use std::sync::Arc;
+
+use kameo::error::SendError;
+use liberum_core::DaemonError;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::node::identity::IdentityError;
+use crate::node::manager::NodeManagerError;
+use crate::node::retry::PermanentError;
+use crate::node::store::NodeStoreError;
+use crate::swarm_runner::behaviour::pending::TimeoutError;
+use crate::swarm_runner::messages::SwarmRunnerMessage;
+
+///! The module converts the internal errors of the daemon to the errors returned
+///! to the UIs, so they can tell what went wrong without parsing the messages.
+
+/// Classifies an error returned by a node
+pub fn daemon_error(e: anyhow::Error) -> DaemonError {
+    classify(&e)
+}
+
+/// Classifies the error of a download, shared by the requests which joined it
+pub fn download_error(e: Arc<anyhow::Error>) -> DaemonError {
+    classify(&e)
+}
+
+fn classify(e: &anyhow::Error) -> DaemonError {
+    if let Some(e) = e.downcast_ref::<DaemonError>() {
+        return e.clone();
+    }
+
+    if let Some(e) = e.downcast_ref::<PermanentError>() {
+        return match e {
+            PermanentError::NotFound(id) => DaemonError::ObjectNotFound(id.clone()),
+            PermanentError::NoProviders(id) => DaemonError::NoProviders(id.clone()),
+            PermanentError::MessageRejected(_) => DaemonError::Other(e.to_string()),
+            PermanentError::AccessDenied(_) => DaemonError::PermissionDenied(e.to_string()),
+        };
+    }
+
+    if e.is::<IdentityError>() {
+        return DaemonError::InvalidArgument(e.to_string());
+    }
+
+    if e.is::<TimeoutError>() || e.is::<tokio::time::error::Elapsed>() {
+        return DaemonError::Timeout(e.to_string());
+    }
+
+    if e.is::<oneshot::error::RecvError>() || e.is::<mpsc::error::SendError<SwarmRunnerMessage>>() {
+        return DaemonError::SwarmError(e.to_string());
+    }
+
+    let is_vault_error = e
+        .chain()
+        .any(|c| c.is::<tokio_rusqlite::Error>() || c.is::<rusqlite::Error>());
+    if is_vault_error {
+        return DaemonError::VaultError(e.to_string());
+    }
+
+    DaemonError::Other(e.to_string())
+}
+
+/// Classifies an error of a message sent to a node
+pub fn node_error<M>(e: SendError<M, anyhow::Error>) -> DaemonError {
+    match e {
+        SendError::HandlerError(e) => daemon_error(e),
+        e => DaemonError::Other(e.to_string()),
+    }
+}
+
+/// Classifies an error of a message sent to the node manager
+pub fn manager_error<M>(e: SendError<M, NodeManagerError>) -> DaemonError {
+    match e {
+        SendError::HandlerError(NodeManagerError::StoreError(e)) => from_store_error(e),
+        SendError::HandlerError(NodeManagerError::OtherError(e)) => daemon_error(e),
+        SendError::HandlerError(e @ NodeManagerError::UnknownGroup { .. }) => {
+            DaemonError::InvalidArgument(e.to_string())
+        }
+        SendError::HandlerError(e @ NodeManagerError::NotStarted { .. }) => {
+            DaemonError::NodeNotRunning(e.to_string())
+        }
+        e => DaemonError::Other(e.to_string()),
+    }
+}
+
+/// Classifies an error of a message sent to the node store
+pub fn store_error<M>(e: SendError<M, NodeStoreError>) -> DaemonError {
+    match e {
+        SendError::HandlerError(e) => from_store_error(e),
+        e => DaemonError::Other(e.to_string()),
+    }
+}
+
+fn from_store_error(e: NodeStoreError) -> DaemonError {
+    match e {
+        NodeStoreError::NodeDoesNotExist => DaemonError::NodeDoesNotExist(e.to_string()),
+        NodeStoreError::NodeAlreadyExists => DaemonError::NodeAlreadyExist(e.to_string()),
+        NodeStoreError::NodeLocked => DaemonError::NodeLocked(e.to_string()),
+        NodeStoreError::VaultOwnedByOther => DaemonError::PermissionDenied(e.to_string()),
+        NodeStoreError::OtherError { err } => daemon_error(err),
+    }
+}
+
+pub fn invalid_argument(e: impl Display) -> DaemonError {
+    DaemonError::InvalidArgument(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    #[test]
+    fn daemon_error_test() {
+        let e = daemon_error(PermanentError::NoProviders("id".to_string()).into());
+        assert!(matches!(e, DaemonError::NoProviders(id) if id == "id"));
+
+        let e = daemon_error(anyhow!(TimeoutError).context("Failed to get object"));
+        assert!(matches!(e, DaemonError::Timeout(_)));
+
+        let e = daemon_error(DaemonError::InvalidArgument("id".to_string()).into());
+        assert!(matches!(e, DaemonError::InvalidArgument(_)));
+
+        let e = daemon_error(anyhow!("Something else"));
+        assert!(matches!(e, DaemonError::Other(_)));
+    }
+}
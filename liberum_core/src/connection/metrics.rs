@@ -0,0 +1,154 @@
+use std::fmt::Write;
+
+use anyhow::Result;
+use axum::extract::State;
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::Router;
+use kameo::request::MessageSend;
+use liberum_core::types::NodeStatus;
+use tokio::net::TcpListener;
+use tracing::{error, info};
+
+use super::{get_node_store, AppContext};
+use crate::node::manager::GetAll;
+use crate::node::store::ListNodes;
+use crate::node::GetStatus;
+
+///! The module contains the metrics endpoint of the daemon. `GET /metrics` answers
+///! with the numbers of the nodes and the state of the running ones, in the text
+///! format of Prometheus. It's served as long as the daemon runs.
+
+/// Starts serving on the address from the daemon config
+pub async fn start(context: &AppContext) -> Result<()> {
+    let address = context.daemon_config.metrics.bind_address;
+    let listener = TcpListener::bind(address).await?;
+    info!(address = address.to_string(), "Metrics endpoint started");
+    let router = Router::new()
+        .route("/metrics", get(get_metrics))
+        .with_state(context.clone());
+
+    tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, router).await {
+            error!(err = e.to_string(), "Metrics endpoint failed");
+        }
+    });
+    Ok(())
+}
+
+async fn get_metrics(State(context): State<AppContext>) -> Response {
+    let Ok(node_store) = get_node_store(&context).await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    let Ok(nodes) = node_store.ask(ListNodes).send().await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    let Ok(running) = context.node_manager.ask(GetAll).send().await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+
+    let mut statuses = Vec::new();
+    for (name, node) in running {
+        // A node stopping meanwhile is left out
+        if let Ok(status) = node.ask(GetStatus).send().await {
+            statuses.push((name, status));
+        }
+    }
+    statuses.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let headers = [(header::CONTENT_TYPE, "text/plain; version=0.0.4")];
+    (headers, render(nodes.len(), &statuses)).into_response()
+}
+
+fn render(nodes: usize, statuses: &[(String, NodeStatus)]) -> String {
+    let mut text = String::new();
+    let _ = writeln!(text, "# TYPE liberum_nodes gauge");
+    let _ = writeln!(text, "liberum_nodes {nodes}");
+    let _ = writeln!(text, "# TYPE liberum_nodes_running gauge");
+    let _ = writeln!(text, "liberum_nodes_running {}", statuses.len());
+
+    let metrics: [(&str, &str, fn(&NodeStatus) -> u64); 9] = [
+        ("liberum_node_connected_peers", "gauge", |s| {
+            s.connected_peers as u64
+        }),
+        ("liberum_node_routing_table_size", "gauge", |s| {
+            s.routing_table_size as u64
+        }),
+        ("liberum_node_uptime_seconds", "gauge", |s| {
+            s.uptime.as_secs()
+        }),
+        ("liberum_node_sent_bytes_total", "counter", |s| s.bytes_sent),
+        ("liberum_node_received_bytes_total", "counter", |s| {
+            s.bytes_received
+        }),
+        ("liberum_node_served_objects_total", "counter", |s| {
+            s.objects_served
+        }),
+        ("liberum_node_active_uploads", "gauge", |s| {
+            s.uploads.active_uploads as u64
+        }),
+        ("liberum_node_upload_bytes_last_hour", "gauge", |s| {
+            s.uploads.bytes_last_hour
+        }),
+        ("liberum_node_rejected_uploads_total", "counter", |s| {
+            s.uploads.rejected_requests
+        }),
+    ];
+    for (metric, kind, value) in metrics {
+        let _ = writeln!(text, "# TYPE {metric} {kind}");
+        for (name, status) in statuses {
+            let _ = writeln!(text, "{metric}{{node=\"{}\"}} {}", name, value(status));
+        }
+    }
+
+    let metric = "liberum_node_rejected_records_total";
+    let _ = writeln!(text, "# TYPE {metric} counter");
+    for (name, status) in statuses {
+        for (reason, count) in status.rejected_records.by_reason() {
+            let _ = writeln!(
+                text,
+                "{metric}{{node=\"{name}\",reason=\"{reason}\"}} {count}"
+            );
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use liberum_core::types::{RecordRejectStats, UploadStats};
+    use std::time::Duration;
+
+    #[test]
+    fn render_test() {
+        let status = NodeStatus {
+            uptime: Duration::from_secs(60),
+            connected_peers: 3,
+            routing_table_size: 10,
+            buckets: vec![],
+            pending_queries: 0,
+            bytes_sent: 0,
+            bytes_received: 0,
+            objects_served: 0,
+            link_impairment: Default::default(),
+            uploads: UploadStats {
+                active_uploads: 1,
+                ..Default::default()
+            },
+            rejected_records: RecordRejectStats {
+                wrong_key: 2,
+                ..Default::default()
+            },
+        };
+        let text = render(2, &[("node".to_string(), status)]);
+        assert!(text.contains("liberum_nodes 2\n"));
+        assert!(text.contains("liberum_nodes_running 1\n"));
+        assert!(text.contains("liberum_node_connected_peers{node=\"node\"} 3\n"));
+        assert!(text.contains("liberum_node_active_uploads{node=\"node\"} 1\n"));
+        assert!(text.contains(
+            "liberum_node_rejected_records_total{node=\"node\",reason=\"wrong_key\"} 2\n"
+        ));
+    }
+}
@@ -1,52 +1,154 @@
+mod error;
+mod metrics;
+mod notifications;
+mod permission;
+
+use crate::logging;
 use crate::node;
+use crate::node::finish_download;
+use crate::node::identity;
+use crate::node::mailbox::Delivery;
+use crate::node::manager::GetAll;
+use crate::node::manager::GetGroupNodes;
 use crate::node::manager::GetNode;
 use crate::node::manager::IsNodeRunning;
 use crate::node::manager::NodeManager;
+use crate::node::module_host::{ModuleHost, ModuleRegistration};
+use crate::node::store::GetNodeConfig;
+use crate::node::store::GetNodeOwner;
+use crate::node::store::GetNodePeerId;
+use crate::node::store::ImportNode;
+use crate::node::store::IsNodeLocked;
 use crate::node::store::ListNodes;
 use crate::node::store::LoadNode;
 use crate::node::store::NodeStore;
+use crate::node::store::SetKeyPassphrase;
+use crate::node::store::UnlockNode;
+use crate::node::CreateGroup;
 use crate::node::DeleteObject;
 use crate::node::DialPeer;
+use crate::node::DisconnectPeer;
 use crate::node::DownloadFile;
 use crate::node::GetAddresses;
+use crate::node::GetEvents;
+use crate::node::GetHistory;
+use crate::node::GetLatencies;
+use crate::node::GetObject;
+use crate::node::GetObjectInfo;
+use crate::node::GetPeerProfile;
+use crate::node::GetPeerScores;
+use crate::node::GetPopularObjects;
 use crate::node::GetProviders;
 use crate::node::GetPublishedObjects;
+use crate::node::GetRoutingTable;
+use crate::node::GetStatus;
+use crate::node::InviteToGroup;
+use crate::node::JoinGroup;
+use crate::node::ListScheduledTasks;
 use crate::node::Node;
 use crate::node::NodeSnapshot;
+use crate::node::PostToGroup;
 use crate::node::ProvideFile;
 use crate::node::PublishFile;
+use crate::node::PublishFiles;
+use crate::node::PublishTags;
+use crate::node::Query;
+use crate::node::ResetPeerScore;
+use crate::node::RunScheduledTask;
+use crate::node::SearchObjects;
+use crate::node::SearchText;
+use crate::node::SendDirectMessage;
+use crate::node::StopProviding;
+use crate::node::StreamProviders;
+use crate::node::VerifyObject;
+use crate::swarm_runner::messages::ProvidersBatch;
 use anyhow::Result;
+use error::{
+    daemon_error, download_error, invalid_argument, manager_error, node_error, store_error,
+};
 use futures::SinkExt;
 use futures::StreamExt;
 use kameo::actor::ActorRef;
 use kameo::request::MessageSend;
 use liberum_core::codec::AsymmetricMessageCodec;
+use liberum_core::daemon_config::DaemonConfig;
+use liberum_core::handshake;
+use liberum_core::node_config::ModulesConfig;
 use liberum_core::node_config::NodeConfig;
+use liberum_core::node_config::NodeProfile;
+use liberum_core::node_config::WatchDir;
+use liberum_core::owned_file::FileTarget;
+use liberum_core::proto;
+use liberum_core::types::AuditFilter;
+use liberum_core::types::Contact;
+use liberum_core::types::DaemonHealth;
+use liberum_core::types::MessageContent;
+use liberum_core::types::ModuleCall;
 use liberum_core::types::NodeInfo;
+use liberum_core::types::ObjectAccess;
+use liberum_core::types::PeerProfile;
+use liberum_core::types::ScheduledTask;
 use liberum_core::DaemonError;
+use liberum_core::DaemonQueryStats;
 use liberum_core::DaemonRequest;
 use liberum_core::DaemonResponse;
 use liberum_core::DaemonResult;
+use liberum_core::NodeOperation;
+use liberum_core::NodeOperationResult;
 use libp2p::identity::Keypair;
+use libp2p::Multiaddr;
 use libp2p::PeerId;
+use notifications::{
+    next_notification, notification_for, OwnedNotification, NOTIFICATION_CAPACITY,
+};
+use permission::{Permission, RequestFiles};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
 use std::path::PathBuf;
-use tokio::net::UnixListener;
-use tokio_util::codec::Decoder;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::{broadcast, mpsc};
 use tokio_util::codec::Framed;
 use tracing::{debug, info, warn};
 
-type SocketFramed =
-    Framed<tokio::net::UnixStream, AsymmetricMessageCodec<DaemonResult, DaemonRequest>>;
+type SocketFramed = Framed<UnixStream, AsymmetricMessageCodec<DaemonResult, DaemonRequest>>;
 
 #[derive(Clone)]
 pub struct AppContext {
     node_manager: ActorRef<NodeManager>,
+    notifications: broadcast::Sender<OwnedNotification>,
+    module_host: ModuleHost,
+    daemon_config: Arc<DaemonConfig>,
+    started_at: Instant,
+    /// The requests of all the connections being handled now
+    queued_requests: Arc<AtomicUsize>,
 }
 
 impl AppContext {
     pub(super) fn new(node_store: ActorRef<NodeStore>) -> Self {
+        Self::with_config(node_store, DaemonConfig::default())
+    }
+
+    pub(super) fn with_config(
+        node_store: ActorRef<NodeStore>,
+        daemon_config: DaemonConfig,
+    ) -> Self {
+        let (notifications, _) = broadcast::channel(NOTIFICATION_CAPACITY);
+        let module_host = ModuleHost::default();
         AppContext {
-            node_manager: kameo::spawn(NodeManager::new(node_store.clone())),
+            node_manager: kameo::spawn(NodeManager::new(
+                node_store.clone(),
+                module_host.clone(),
+                daemon_config.groups.clone(),
+            )),
+            notifications,
+            module_host,
+            daemon_config: Arc::new(daemon_config),
+            started_at: Instant::now(),
+            queued_requests: Arc::new(AtomicUsize::new(0)),
         }
     }
 }
@@ -57,54 +159,452 @@ pub async fn create_app_context_for_test() -> Result<AppContext, anyhow::Error>
     )))
 }
 
-pub async fn listen(listener: UnixListener) -> Result<()> {
+pub async fn listen(listener: UnixListener, daemon_config: DaemonConfig) -> Result<()> {
     info!("Server listening on {:?}", listener);
     let mut id = 0;
-    let app_context = AppContext::new(kameo::spawn(NodeStore::with_default_nodes_dir().await?));
+    let node_store = match &daemon_config.state_dir {
+        Some(state_dir) => NodeStore::with_custom_nodes_dir(state_dir).await?,
+        None => NodeStore::with_default_nodes_dir().await?,
+    };
+    let app_context = AppContext::with_config(kameo::spawn(node_store), daemon_config);
+    if app_context.daemon_config.metrics.enabled {
+        metrics::start(&app_context).await?;
+    }
+    if app_context.daemon_config.autostart {
+        start_autostart_nodes(&app_context).await;
+    }
     loop {
         let (daemon_socket, _) = listener.accept().await?;
-        let daemon_socket_framed: SocketFramed =
-            AsymmetricMessageCodec::new().framed(daemon_socket);
+        let permission = Permission::of_peer(&daemon_socket, &app_context.daemon_config.roles);
+        debug!(
+            id = id,
+            permission = format!("{permission:?}"),
+            "UI connected"
+        );
         tokio::spawn(handle_connection(
-            daemon_socket_framed,
+            daemon_socket,
             id.clone(),
+            permission,
             app_context.clone(),
         ));
         id = id.wrapping_add(1);
     }
 }
 
+/// Starts the nodes which have autostart enabled in their config.
+/// A node failing to start does not prevent the daemon from running
+async fn start_autostart_nodes(context: &AppContext) {
+    let node_names = match handle_list_nodes(context).await {
+        Ok(DaemonResponse::NodeList(nodes)) => nodes.into_iter().map(|n| n.name),
+        _ => {
+            warn!("Failed to list nodes to autostart");
+            return;
+        }
+    };
+
+    for name in node_names {
+        match handle_get_node_config(name.clone(), context).await {
+            Ok(DaemonResponse::NodeConfig(config)) if config.autostart => {
+                info!(name = name, "Autostarting node");
+                if let Err(e) = handle_start_node(name.clone(), context).await {
+                    warn!(name = name, err = e.to_string(), "Failed to autostart node");
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Many UIs may be connected at the same time, every one is handled by its own task
 async fn handle_connection(
-    mut daemon_socket_framed: SocketFramed,
+    daemon_socket: UnixStream,
     id: u64,
+    mut permission: Permission,
     app_context: AppContext,
 ) -> Result<()> {
+    let mut daemon_socket_framed: SocketFramed = match handshake::accept(daemon_socket).await {
+        Ok(framed) => framed,
+        Err(e) => {
+            warn!(id = id, err = e.to_string(), "UI refused");
+            return Ok(());
+        }
+    };
+    let mut notifications = None;
+    let mut module = None;
+    let mut logs = None;
+    let mut providers = None;
     loop {
         tokio::select! {
             Some(message) = daemon_socket_framed.next() => {
                 debug!("Received: {message:?} at {id}");
                 match message {
+                    Ok(DaemonRequest::Subscribe) => {
+                        notifications = Some(app_context.notifications.subscribe());
+                        daemon_socket_framed.send(Ok(DaemonResponse::Subscribed)).await?;
+                    },
+                    Ok(DaemonRequest::Authenticate { token }) => {
+                        let response = match app_context.daemon_config.roles.role_of_token(&token) {
+                            Some(role) => {
+                                permission = permission.with_role(role);
+                                Ok(DaemonResponse::Authenticated { role })
+                            },
+                            None => Err(DaemonError::PermissionDenied("Unknown token".to_string())),
+                        };
+                        daemon_socket_framed.send(response).await?;
+                    },
+                    Ok(DaemonRequest::ModuleCallResult { call_id, result }) => {
+                        handle_module_call_result(module.as_ref(), call_id, result);
+                    },
+                    Ok(message @ DaemonRequest::TailLogs { follow: true, .. }) => {
+                        let response = match permission.check(&message, &app_context).await {
+                            Ok(_) => follow_logs(message, &mut logs),
+                            Err(e) => Err(e),
+                        };
+                        daemon_socket_framed.send(response).await?;
+                    },
+                    Ok(message @ DaemonRequest::GetProviders { stream: true, .. }) => {
+                        let result = match permission.check(&message, &app_context).await {
+                            Ok(_) => stream_providers(message, &mut providers, &app_context).await,
+                            Err(e) => Err(e),
+                        };
+                        // The responses are sent as the providers are found
+                        if let Err(e) = result {
+                            daemon_socket_framed.send(Err(e)).await?;
+                        }
+                    },
+                    Ok(DaemonRequest::BatchNodes { node_names, operation }) => {
+                        let response = handle_batch(node_names, operation, permission, &app_context).await;
+                        daemon_socket_framed.send(response).await?;
+                    },
+                    Ok(DaemonRequest::BatchGroup { group, operation }) => {
+                        let response = match group_nodes(group, &app_context).await {
+                            Ok(node_names) => handle_batch(node_names, operation, permission, &app_context).await,
+                            Err(e) => Err(e),
+                        };
+                        daemon_socket_framed.send(response).await?;
+                    },
+                    Ok(message @ DaemonRequest::RegisterModule { .. }) => {
+                        let response = match permission.check(&message, &app_context).await {
+                            Ok(_) => register_module(message, &mut module, &app_context),
+                            Err(e) => Err(e),
+                        };
+                        daemon_socket_framed.send(response).await?;
+                    },
                     Ok(message) => {
-                        let response = handle_message(message, &app_context).await;
+                        let response = match permission.check(&message, &app_context).await {
+                            Ok(files) => {
+                                let result = handle_message_of(message, files, permission.owner_uid(), &app_context).await;
+                                visible_nodes(result, permission, &app_context).await
+                            },
+                            Err(e) => Err(e),
+                        };
                         daemon_socket_framed.send(response).await?;
                     },
                     Err(e) => {warn!(err=e.to_string(), "Error receiving message"); break;}
                 };
             },
+            notification = next_notification(&mut notifications, permission) => {
+                daemon_socket_framed.send(Ok(DaemonResponse::Notification(notification))).await?;
+            },
+            call = next_module_call(&mut module) => {
+                daemon_socket_framed.send(Ok(DaemonResponse::ModuleCall(call))).await?;
+            },
+            line = next_log_line(&mut logs) => {
+                daemon_socket_framed.send(Ok(DaemonResponse::LogLines { lines: vec![line] })).await?;
+            },
+            response = next_providers(&mut providers) => {
+                daemon_socket_framed.send(Ok(response)).await?;
+            },
             else => {
                 break;
             }
         }
     }
+    debug!(id = id, "UI disconnected");
+    Ok(())
+}
+
+/// Applies the operation to the nodes concurrently. Every node is checked and
+/// handled like its own request, so e.g. its start is notified
+async fn handle_batch(
+    node_names: Vec<String>,
+    operation: NodeOperation,
+    permission: Permission,
+    context: &AppContext,
+) -> DaemonResult {
+    let node_names = match node_names.is_empty() {
+        true => {
+            let names = get_node_store(context)
+                .await?
+                .ask(ListNodes)
+                .send()
+                .await
+                .map_err(store_error)?;
+            let mut visible = Vec::new();
+            for name in names {
+                if permission.can_see(get_node_owner(&name, context).await?) {
+                    visible.push(name);
+                }
+            }
+            visible
+        }
+        false => node_names,
+    };
+
+    let results = node_names.into_iter().map(|node_name| async move {
+        let request = operation.request(node_name.clone());
+        let result = match permission.check(&request, context).await {
+            Ok(files) => handle_message_of(request, files, permission.owner_uid(), context).await,
+            Err(e) => Err(e),
+        };
+        NodeOperationResult { node_name, result }
+    });
+
+    Ok(DaemonResponse::BatchResults(
+        futures::future::join_all(results).await,
+    ))
+}
+
+/// The nodes of the group from the daemon config. An empty group is refused, the
+/// batch of no names would apply to all the nodes
+async fn group_nodes(group: String, context: &AppContext) -> Result<Vec<String>, DaemonError> {
+    let node_names = context
+        .node_manager
+        .ask(GetGroupNodes {
+            group: group.clone(),
+        })
+        .send()
+        .await
+        .map_err(manager_error)?;
+    if node_names.is_empty() {
+        return Err(invalid_argument(format!("group {group} has no nodes")));
+    }
+    Ok(node_names)
+}
+
+/// Makes the connection an external module, replacing the module registered before
+fn register_module(
+    message: DaemonRequest,
+    module: &mut Option<ModuleRegistration>,
+    context: &AppContext,
+) -> DaemonResult {
+    let DaemonRequest::RegisterModule { name, type_uuids } = message else {
+        unreachable!("only RegisterModule is passed here");
+    };
+
+    // The types of the previous registration may be registered again
+    *module = None;
+    *module = Some(
+        context
+            .module_host
+            .register(name, type_uuids)
+            .map_err(invalid_argument)?,
+    );
+
+    Ok(DaemonResponse::ModuleRegistered)
+}
+
+fn handle_module_call_result(
+    module: Option<&ModuleRegistration>,
+    call_id: u64,
+    result: Result<Option<proto::TypedObject>, String>,
+) {
+    let Some(module) = module else {
+        warn!(
+            call_id = call_id,
+            "Call result from a connection which is not a module"
+        );
+        return;
+    };
+
+    if let Err(e) = module.respond(call_id, result) {
+        warn!(err = e.to_string(), "Failed to pass the module call result");
+    }
+}
+
+/// Waits for the next call to the module. Never returns if the connection is not a module
+async fn next_module_call(module: &mut Option<ModuleRegistration>) -> ModuleCall {
+    let Some(registration) = module else {
+        return std::future::pending().await;
+    };
+
+    match registration.next_call().await {
+        Some(call) => call,
+        // The host keeps the sender as long as the module is registered
+        None => std::future::pending().await,
+    }
+}
+
+/// Makes the connection receive the log lines, replacing the logs followed before
+fn follow_logs(message: DaemonRequest, logs: &mut Option<logging::LogFollower>) -> DaemonResult {
+    let DaemonRequest::TailLogs {
+        node_name, lines, ..
+    } = message
+    else {
+        unreachable!("only TailLogs is passed here");
+    };
+
+    if let Some(node_name) = &node_name {
+        check_node_name(node_name)?;
+    }
+    let (lines, follower) = logging::follow_logs(node_name, lines).map_err(daemon_error)?;
+    *logs = Some(follower);
+
+    Ok(DaemonResponse::LogLines { lines })
+}
+
+/// Waits for the next followed log line. Never returns if the connection does not
+/// follow the logs
+async fn next_log_line(logs: &mut Option<logging::LogFollower>) -> String {
+    let Some(follower) = logs else {
+        return std::future::pending().await;
+    };
+
+    match follower.next().await {
+        Some(line) => line,
+        // The sender lives as long as the daemon
+        None => std::future::pending().await,
+    }
+}
+
+/// The providers of a lookup streamed to the connection
+struct ProvidersStream {
+    receiver: mpsc::Receiver<ProvidersBatch>,
+    seen: HashSet<PeerId>,
+    ids: Vec<String>,
+    stats: Option<DaemonQueryStats>,
+}
+
+/// Makes the connection receive the providers of the object as they are found,
+/// replacing the lookup streamed before
+async fn stream_providers(
+    message: DaemonRequest,
+    providers: &mut Option<ProvidersStream>,
+    context: &AppContext,
+) -> Result<(), DaemonError> {
+    let DaemonRequest::GetProviders { node_name, id, .. } = message else {
+        unreachable!("only GetProviders is passed here");
+    };
+
+    check_object_id(&id)?;
+    let node = get_node(&node_name, context).await?;
+    let receiver = node
+        .ask(StreamProviders { obj_id_str: id })
+        .send()
+        .await
+        .inspect_err(|e| debug!(err = e.to_string(), "Failed to stream file providers"))
+        .map_err(node_error)?;
+
+    *providers = Some(ProvidersStream {
+        receiver,
+        seen: HashSet::new(),
+        ids: Vec::new(),
+        stats: None,
+    });
     Ok(())
 }
 
-/// Used by the core daemon to listen for incoming connections from UI
-/// Only one UI connection is possible at a time
+/// Waits for the next new providers of the streamed lookup. When the lookup
+/// finishes, responds with all the providers found and stops streaming. Never
+/// returns if the connection does not stream a lookup
+async fn next_providers(providers: &mut Option<ProvidersStream>) -> DaemonResponse {
+    let Some(stream) = providers else {
+        return std::future::pending().await;
+    };
+
+    while let Some((peers, stats)) = stream.receiver.recv().await {
+        stream.stats = stats.or(stream.stats.take());
+        let ids: Vec<String> = peers
+            .into_iter()
+            .filter(|p| stream.seen.insert(*p))
+            .map(|p| p.to_base58())
+            .collect();
+        if !ids.is_empty() {
+            stream.ids.extend(ids.iter().cloned());
+            return DaemonResponse::ProvidersFound { ids };
+        }
+    }
+
+    let response = DaemonResponse::Providers {
+        ids: std::mem::take(&mut stream.ids),
+        stats: stream.stats.take(),
+    };
+    *providers = None;
+    response
+}
+
+/// Handles the request from a UI and notifies the subscribed UIs about the change
 pub async fn handle_message(message: DaemonRequest, context: &AppContext) -> DaemonResult {
+    let files = RequestFiles::open(&message, None)?;
+    handle_message_of(message, files, None, context).await
+}
+
+/// Handles the request of the user, who gets the node created by it, with the files
+/// opened by its check. Without the user the node belongs to the user running the
+/// daemon
+async fn handle_message_of(
+    message: DaemonRequest,
+    files: RequestFiles,
+    user: Option<u32>,
+    context: &AppContext,
+) -> DaemonResult {
+    let notification = notification_for(&message);
+    let created_node = message.created_node().map(str::to_string);
+    // Looked up before the request, a deleted node has no owner after it
+    let node_owner = match (&notification, message.node_name()) {
+        (Some(_), Some(name)) => get_node_owner(name, context).await.ok().flatten(),
+        _ => None,
+    };
+    context.queued_requests.fetch_add(1, Ordering::Relaxed);
+    let result = dispatch_message(message, files, user, context).await;
+    context.queued_requests.fetch_sub(1, Ordering::Relaxed);
+
+    // The created node is stored with its owner
+    let owner = match (created_node, user) {
+        (Some(_), Some(uid)) => Some(uid),
+        (Some(_), None) => Some(nix::unistd::geteuid().as_raw()),
+        _ => node_owner,
+    };
+    if let (Ok(_), Some(notification)) = (&result, notification) {
+        // Fails only if nobody is subscribed
+        let _ = context.notifications.send(OwnedNotification {
+            notification,
+            owner,
+        });
+    }
+
+    result
+}
+
+/// Leaves the nodes the connection may not see out of the node list
+async fn visible_nodes(
+    result: DaemonResult,
+    permission: Permission,
+    context: &AppContext,
+) -> DaemonResult {
+    let nodes = match result {
+        Ok(DaemonResponse::NodeList(nodes)) if !permission.is_admin() => nodes,
+        result => return result,
+    };
+
+    let mut visible = Vec::new();
+    for node in nodes {
+        if permission.can_see(get_node_owner(&node.name, context).await?) {
+            visible.push(node);
+        }
+    }
+    Ok(DaemonResponse::NodeList(visible))
+}
+
+async fn dispatch_message(
+    message: DaemonRequest,
+    mut files: RequestFiles,
+    user: Option<u32>,
+    context: &AppContext,
+) -> DaemonResult {
     match message {
         DaemonRequest::NewNode { node_name, id_seed } => {
-            handle_new_node(node_name, id_seed, context).await
+            handle_new_node(node_name, id_seed, user, context).await
         }
         DaemonRequest::StartNode { node_name } => handle_start_node(node_name, context).await,
         DaemonRequest::GetNodeConfig { node_name } => {
@@ -113,18 +613,55 @@ pub async fn handle_message(message: DaemonRequest, context: &AppContext) -> Dae
         DaemonRequest::OverwriteNodeConfig { node_name, new_cfg } => {
             handle_overwrite_node_config(node_name, new_cfg, context).await
         }
+        DaemonRequest::BlockPeer { node_name, peer_id } => {
+            handle_set_peer_blocked(node_name, peer_id, true, context).await
+        }
+        DaemonRequest::UnblockPeer { node_name, peer_id } => {
+            handle_set_peer_blocked(node_name, peer_id, false, context).await
+        }
+        DaemonRequest::AddWatchDir { node_name, dir } => {
+            handle_add_watch_dir(node_name, dir, context).await
+        }
+        DaemonRequest::RemoveWatchDir { node_name, path } => {
+            handle_remove_watch_dir(node_name, path, context).await
+        }
+        DaemonRequest::ListWatchDirs { node_name } => {
+            handle_list_watch_dirs(node_name, context).await
+        }
         DaemonRequest::StopNode { node_name } => handle_stop_node(node_name, context).await,
         DaemonRequest::ListNodes => handle_list_nodes(context).await,
         DaemonRequest::GetNodeDetails { node_name } => {
             handle_get_node_details(&node_name, context).await
         }
-        DaemonRequest::ProvideFile { node_name, path } => {
-            handle_provide_file(&node_name, path, context).await
+        DaemonRequest::GetNodeStatus { node_name } => {
+            handle_get_node_status(node_name, context).await
+        }
+        DaemonRequest::GetPeerScores { node_name } => {
+            handle_get_peer_scores(node_name, context).await
         }
-        DaemonRequest::DownloadFile { node_name, id } => {
-            handle_download_file(node_name, id, context).await
+        DaemonRequest::GetNodeEvents { node_name, since } => {
+            handle_get_node_events(node_name, since, context).await
         }
-        DaemonRequest::GetProviders { node_name, id } => {
+        DaemonRequest::GetRoutingTable { node_name } => {
+            handle_get_routing_table(node_name, context).await
+        }
+        DaemonRequest::ResetPeerScore { node_name, peer_id } => {
+            handle_reset_peer_score(node_name, peer_id, context).await
+        }
+        DaemonRequest::DisconnectPeer { node_name, peer_id } => {
+            handle_disconnect_peer(node_name, peer_id, context).await
+        }
+        DaemonRequest::ProvideFile { node_name, path } => {
+            let file = files.take_read(&path)?;
+            handle_provide_file(&node_name, path, file, context).await
+        }
+        DaemonRequest::DownloadFile {
+            node_name,
+            id,
+            group,
+            force_network,
+        } => handle_download_file(node_name, id, group, force_network, context).await,
+        DaemonRequest::GetProviders { node_name, id, .. } => {
             handle_get_providers(node_name, id, context).await
         }
         DaemonRequest::GetPeerId { node_name } => handle_get_peer_id(node_name, context).await,
@@ -133,9 +670,31 @@ pub async fn handle_message(message: DaemonRequest, context: &AppContext) -> Dae
             peer_id,
             addr,
         } => handle_dial(node_name, peer_id, addr, context).await,
-        DaemonRequest::PublishFile { node_name, path } => {
-            handle_publish_file(node_name, path, context).await
+        DaemonRequest::PublishFile {
+            node_name,
+            path,
+            access,
+            previous,
+        } => {
+            let file = files.take_read(&path)?;
+            handle_publish_file(node_name, path, file, access, previous, context).await
+        }
+        DaemonRequest::PublishFiles {
+            node_name,
+            paths,
+            max_concurrency,
+        } => {
+            let opened = paths
+                .into_iter()
+                .map(|path| files.take_read(&path).map(|file| (path, file)))
+                .collect::<Result<Vec<_>, _>>()?;
+            handle_publish_files(node_name, opened, max_concurrency, context).await
         }
+        DaemonRequest::VerifyObject {
+            node_name,
+            object_id,
+            signer,
+        } => handle_verify_object(node_name, object_id, signer, context).await,
         DaemonRequest::GetPublishedObjects { node_name } => {
             handle_get_published_objects(node_name, context).await
         }
@@ -143,6 +702,193 @@ pub async fn handle_message(message: DaemonRequest, context: &AppContext) -> Dae
             node_name,
             object_id,
         } => handle_delete_object(node_name, object_id, context).await,
+        DaemonRequest::StopProviding {
+            node_name,
+            object_id,
+            keep_in_vault,
+        } => handle_stop_providing(node_name, object_id, keep_in_vault, context).await,
+        DaemonRequest::ExportNodeIdentity {
+            node_name,
+            passphrase,
+        } => handle_export_node_identity(node_name, passphrase, context).await,
+        DaemonRequest::ImportNodeIdentity {
+            node_name,
+            bundle,
+            passphrase,
+        } => handle_import_node_identity(node_name, bundle, passphrase, user, context).await,
+        DaemonRequest::UnlockNode {
+            node_name,
+            passphrase,
+        } => handle_unlock_node(node_name, passphrase, context).await,
+        DaemonRequest::SetNodePassphrase {
+            node_name,
+            passphrase,
+        } => handle_set_node_passphrase(node_name, passphrase, context).await,
+        DaemonRequest::RenameNode {
+            node_name,
+            new_name,
+            force,
+        } => handle_rename_node(node_name, new_name, force, context).await,
+        DaemonRequest::DeleteNode {
+            node_name,
+            force,
+            keep_vault,
+        } => handle_delete_node(node_name, force, keep_vault, context).await,
+        DaemonRequest::CloneNode {
+            source,
+            target,
+            fresh_identity,
+        } => handle_clone_node(source, target, fresh_identity, user, context).await,
+        DaemonRequest::ReloadNodeConfig { node_name, new_cfg } => {
+            handle_reload_node_config(node_name, new_cfg, context).await
+        }
+        DaemonRequest::ListModules { node_name } => handle_list_modules(node_name, context).await,
+        DaemonRequest::SetModuleEnabled {
+            node_name,
+            module,
+            enabled,
+        } => handle_set_module_enabled(node_name, module, enabled, context).await,
+        DaemonRequest::RegisterModule { .. } | DaemonRequest::ModuleCallResult { .. } => Err(
+            invalid_argument("modules can be registered only over the daemon socket"),
+        ),
+        DaemonRequest::Authenticate { .. } => Err(invalid_argument(
+            "only the connections over the daemon socket can authenticate",
+        )),
+        DaemonRequest::BatchNodes { .. } | DaemonRequest::BatchGroup { .. } => Err(
+            invalid_argument("the batches can be sent only over the daemon socket"),
+        ),
+        DaemonRequest::GetDaemonConfig => Ok(DaemonResponse::DaemonConfig(
+            context.daemon_config.redacted(),
+        )),
+        DaemonRequest::Query { node_name, query } => handle_query(node_name, query, context).await,
+        DaemonRequest::ExportVaultSnapshot { node_name, path } => {
+            let target = files.take_target(&path)?;
+            handle_export_vault_snapshot(node_name, target, context).await
+        }
+        DaemonRequest::ImportVaultSnapshot { node_name, path } => {
+            let file = files.take_read(&path)?;
+            handle_import_vault_snapshot(node_name, file, context).await
+        }
+        DaemonRequest::SetLogLevel { target, level } => handle_set_log_level(target, level),
+        DaemonRequest::Subscribe => Ok(DaemonResponse::Subscribed),
+        DaemonRequest::TailLogs {
+            node_name, lines, ..
+        } => handle_tail_logs(node_name, lines),
+        DaemonRequest::SetProfile {
+            node_name,
+            display_name,
+            description,
+            avatar,
+        } => {
+            let profile = NodeProfile {
+                display_name,
+                description,
+                avatar,
+            };
+            handle_set_profile(node_name, profile, context).await
+        }
+        DaemonRequest::GetPeerProfile { node_name, peer_id } => {
+            handle_get_peer_profile(node_name, peer_id, context).await
+        }
+        DaemonRequest::AddContact {
+            node_name,
+            peer_id,
+            alias,
+            address,
+            trust,
+        } => {
+            let contact = Contact {
+                peer_id,
+                alias,
+                address,
+                last_seen: None,
+                trust,
+            };
+            handle_add_contact(node_name, contact, context).await
+        }
+        DaemonRequest::RemoveContact { node_name, peer_id } => {
+            handle_remove_contact(node_name, peer_id, context).await
+        }
+        DaemonRequest::ListContacts { node_name } => handle_list_contacts(node_name, context).await,
+        DaemonRequest::SendMessage {
+            node_name,
+            peer_id,
+            content,
+            encrypt,
+        } => handle_send_message(node_name, peer_id, content, encrypt, context).await,
+        DaemonRequest::GetInbox { node_name } => handle_get_inbox(node_name, context).await,
+        DaemonRequest::CreateGroup {
+            node_name,
+            group_name,
+        } => handle_create_group(node_name, group_name, context).await,
+        DaemonRequest::InviteToGroup {
+            node_name,
+            group_id,
+            peer_id,
+            valid_for_secs,
+        } => handle_invite_to_group(node_name, group_id, peer_id, valid_for_secs, context).await,
+        DaemonRequest::JoinGroup {
+            node_name,
+            invitation,
+        } => handle_join_group(node_name, invitation, context).await,
+        DaemonRequest::PostToGroup {
+            node_name,
+            group_id,
+            content,
+        } => handle_post_to_group(node_name, group_id, content, context).await,
+        DaemonRequest::GetGroupFeed {
+            node_name,
+            group_id,
+        } => handle_get_group_feed(node_name, group_id, context).await,
+        DaemonRequest::RotateNodeKey {
+            node_name,
+            passphrase,
+            force,
+        } => handle_rotate_node_key(node_name, passphrase, force, context).await,
+        DaemonRequest::GetAuditLog { node_name, filter } => {
+            handle_get_audit_log(node_name, filter, context).await
+        }
+        DaemonRequest::GetNodeStatsHistory {
+            node_name,
+            since,
+            until,
+        } => handle_get_node_stats_history(node_name, since, until, context).await,
+        DaemonRequest::GetObjectInfo {
+            node_name,
+            object_id,
+        } => handle_get_object_info(node_name, object_id, context).await,
+        DaemonRequest::GetObject {
+            node_name,
+            object_id,
+        } => handle_get_object(node_name, object_id, context).await,
+        DaemonRequest::Ping => handle_ping(context).await,
+        DaemonRequest::ListScheduledTasks { node_name } => {
+            handle_list_scheduled_tasks(node_name, context).await
+        }
+        DaemonRequest::RunTaskNow { node_name, task } => {
+            handle_run_task_now(node_name, task, context).await
+        }
+        DaemonRequest::GetPopularObjects { node_name, limit } => {
+            handle_get_popular_objects(node_name, limit, context).await
+        }
+        DaemonRequest::TagObject {
+            node_name,
+            id,
+            tags,
+        } => handle_tag_object(node_name, id, tags, context).await,
+        DaemonRequest::SearchObjects {
+            node_name,
+            query,
+            network,
+        } => handle_search_objects(node_name, query, network, context).await,
+        DaemonRequest::SearchText {
+            node_name,
+            query,
+            limit,
+        } => handle_search_text(node_name, query, limit, context).await,
+        DaemonRequest::GetHistory { node_name, id } => {
+            handle_get_history(node_name, id, context).await
+        }
     }
 }
 
@@ -161,7 +907,22 @@ async fn get_node(node_name: &str, context: &AppContext) -> Result<ActorRef<Node
                 "Failed to get node"
             )
         })
-        .map_err(|e| DaemonError::Other(e.to_string()))
+        .map_err(manager_error)
+}
+
+/// Node names are used as the directory names of the nodes
+fn check_node_name(name: &str) -> Result<(), DaemonError> {
+    if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+        return Err(invalid_argument(format!("Invalid node name {name}")));
+    }
+    Ok(())
+}
+
+/// Rejects malformed object IDs before they reach the node
+fn check_object_id(id: &str) -> Result<(), DaemonError> {
+    proto::Hash::try_from(id)
+        .map(|_| ())
+        .map_err(|e| invalid_argument(format!("Invalid object ID {id}: {e}")))
 }
 
 async fn handle_get_peer_id(node_name: String, context: &AppContext) -> DaemonResult {
@@ -172,7 +933,7 @@ async fn handle_get_peer_id(node_name: String, context: &AppContext) -> DaemonRe
         .send()
         .await
         .inspect_err(|e| debug!(err = e.to_string(), "Failed to get peer id"))
-        .map_err(|e| DaemonError::Other(e.to_string()))?;
+        .map_err(node_error)?;
 
     Ok(DaemonResponse::PeerId {
         id: peer_id.to_base58(),
@@ -182,15 +943,22 @@ async fn handle_get_peer_id(node_name: String, context: &AppContext) -> DaemonRe
 async fn handle_new_node(
     name: String,
     id_seed: Option<String>,
+    owner: Option<u32>,
     context: &AppContext,
 ) -> DaemonResult {
     let keypair = match id_seed {
         Some(seed) => liberum_core::node_keypair_from_seed(&seed),
         None => Keypair::generate_ed25519(),
     };
+    let config = NodeConfig {
+        compression: context.daemon_config.vault,
+        ..Default::default()
+    };
     let node_snapshot = NodeSnapshot::builder()
         .name(name)
         .keypair(keypair)
+        .config(config)
+        .owner(owner)
         .build_snapshot()
         // This can't fail
         .unwrap();
@@ -201,7 +969,7 @@ async fn handle_new_node(
         .send()
         .await;
     match resp {
-        Err(e) => Err(DaemonError::Other(e.to_string())),
+        Err(e) => Err(manager_error(e)),
         Ok(_resp) => Ok(DaemonResponse::NodeCreated),
     }
 }
@@ -213,7 +981,7 @@ async fn handle_start_node(name: String, context: &AppContext) -> DaemonResult {
         .send()
         .await
         .inspect_err(|e| debug!(err = e.to_string(), "Failed to handle start node"))
-        .map_err(|e| DaemonError::Other(e.to_string()))?;
+        .map_err(manager_error)?;
 
     debug!(name = name, "Node started!");
 
@@ -227,7 +995,7 @@ async fn handle_get_node_config(name: String, context: &AppContext) -> DaemonRes
         .send()
         .await
         .inspect_err(|e| debug!(err = e.to_string(), "Failed to handle get node config"))
-        .map_err(|e| DaemonError::Other(e.to_string()))?;
+        .map_err(manager_error)?;
 
     debug!(name = name, "Node config got!");
 
@@ -253,101 +1021,282 @@ async fn handle_overwrite_node_config(
                 "Failed to handle overwrite node config"
             )
         })
-        .map_err(|e| DaemonError::Other(e.to_string()))?;
+        .map_err(manager_error)?;
 
     debug!(name = name, "Node config overwritten!");
 
     Ok(DaemonResponse::NodeConfigUpdated)
 }
 
-async fn handle_stop_node(name: String, context: &AppContext) -> DaemonResult {
-    let resp = context
+async fn handle_set_peer_blocked(
+    name: String,
+    peer_id: String,
+    blocked: bool,
+    context: &AppContext,
+) -> DaemonResult {
+    let peer_id = PeerId::from_str(&peer_id).map_err(invalid_argument)?;
+
+    context
         .node_manager
-        .ask(node::manager::StopNode { name })
+        .ask(node::manager::SetPeerBlocked {
+            name: name.clone(),
+            peer_id,
+            blocked,
+        })
         .send()
         .await
-        .map_err(|e| DaemonError::Other(e.to_string()));
-    match resp {
-        Err(e) => Err(DaemonError::Other(e.to_string())),
-        Ok(_nodes) => Ok(DaemonResponse::NodeStopped),
-    }
+        .inspect_err(|e| debug!(err = e.to_string(), "Failed to handle set peer blocked"))
+        .map_err(manager_error)?;
+
+    debug!(
+        name = name,
+        peer_id = peer_id.to_base58(),
+        blocked = blocked,
+        "Peer blocklist updated!"
+    );
+
+    Ok(DaemonResponse::NodeConfigUpdated)
 }
 
-async fn handle_list_nodes(context: &AppContext) -> DaemonResult {
-    let node_store = context
+async fn handle_add_watch_dir(name: String, dir: WatchDir, context: &AppContext) -> DaemonResult {
+    dir.validate().map_err(invalid_argument)?;
+    // The paths are resolved by the daemon, not the UI
+    if !dir.path.is_absolute() || !dir.path.is_dir() {
+        return Err(invalid_argument(format!(
+            "{} is not an absolute path of a directory",
+            dir.path.display()
+        )));
+    }
+
+    context
         .node_manager
-        .ask(node::manager::GetNodeStore)
+        .ask(node::manager::AddWatchDir { name, dir })
         .send()
         .await
-        .map_err(|e| DaemonError::Other(e.to_string()))?;
+        .inspect_err(|e| debug!(err = e.to_string(), "Failed to handle add watch dir"))
+        .map_err(manager_error)?;
 
-    let all_nodes_names = node_store
-        .ask(ListNodes)
+    Ok(DaemonResponse::NodeConfigUpdated)
+}
+
+async fn handle_remove_watch_dir(
+    name: String,
+    path: PathBuf,
+    context: &AppContext,
+) -> DaemonResult {
+    let removed = context
+        .node_manager
+        .ask(node::manager::RemoveWatchDir {
+            name,
+            path: path.clone(),
+        })
         .send()
         .await
-        .map_err(|e| DaemonError::Other(e.to_string()))?;
-
-    let mut node_infos = Vec::new();
+        .inspect_err(|e| debug!(err = e.to_string(), "Failed to handle remove watch dir"))
+        .map_err(manager_error)?;
 
-    for name in all_nodes_names.iter() {
-        let node_info = get_node_details(&name, context)
-            .await
-            .map_err(|e| DaemonError::Other(e.to_string()))?;
-        node_infos.push(node_info);
+    match removed {
+        true => Ok(DaemonResponse::NodeConfigUpdated),
+        false => Err(invalid_argument(format!(
+            "{} is not watched",
+            path.display()
+        ))),
     }
-
-    Ok(DaemonResponse::NodeList(node_infos))
-}
-
-async fn handle_get_node_details(node_name: &str, context: &AppContext) -> DaemonResult {
-    let node_info = get_node_details(node_name, context)
-        .await
-        .map_err(|e| DaemonError::Other(e.to_string()))?;
-    DaemonResult::Ok(DaemonResponse::NodeDetails(node_info))
 }
 
-async fn get_node_details(node_name: &str, context: &AppContext) -> Result<NodeInfo> {
-    let is_running = context
+async fn handle_list_watch_dirs(name: String, context: &AppContext) -> DaemonResult {
+    let config = context
         .node_manager
-        .ask(IsNodeRunning {
-            name: node_name.to_string(),
-        })
+        .ask(node::manager::GetNodeConfig { name })
         .send()
         .await
-        .map_err(|e| DaemonError::Other(e.to_string()))?;
+        .inspect_err(|e| debug!(err = e.to_string(), "Failed to handle list watch dirs"))
+        .map_err(manager_error)?;
 
-    let node_store = context
+    Ok(DaemonResponse::WatchDirs(config.watch_dirs))
+}
+
+async fn handle_list_modules(name: String, context: &AppContext) -> DaemonResult {
+    let config = context
         .node_manager
-        .ask(node::manager::GetNodeStore)
+        .ask(node::manager::GetNodeConfig { name })
+        .send()
+        .await
+        .inspect_err(|e| debug!(err = e.to_string(), "Failed to handle list modules"))
+        .map_err(manager_error)?;
+
+    let mut modules = config.modules.list();
+    modules.extend(context.module_host.list());
+
+    Ok(DaemonResponse::Modules(modules))
+}
+
+async fn handle_set_module_enabled(
+    name: String,
+    module: String,
+    enabled: bool,
+    context: &AppContext,
+) -> DaemonResult {
+    let uuid = ModulesConfig::find(&module)
+        .ok_or_else(|| invalid_argument(format!("unknown module {module}")))?;
+
+    context
+        .node_manager
+        .ask(node::manager::SetModuleEnabled {
+            name: name.clone(),
+            module: uuid,
+            enabled,
+        })
+        .send()
+        .await
+        .inspect_err(|e| debug!(err = e.to_string(), "Failed to handle set module enabled"))
+        .map_err(manager_error)?;
+
+    debug!(
+        name = name,
+        module = module,
+        enabled = enabled,
+        "Module toggled!"
+    );
+
+    Ok(DaemonResponse::NodeConfigUpdated)
+}
+
+async fn handle_stop_node(name: String, context: &AppContext) -> DaemonResult {
+    let resp = context
+        .node_manager
+        .ask(node::manager::StopNode { name })
+        .send()
+        .await;
+    match resp {
+        Err(e) => Err(manager_error(e)),
+        Ok(_nodes) => Ok(DaemonResponse::NodeStopped),
+    }
+}
+
+async fn get_node_store(context: &AppContext) -> Result<ActorRef<NodeStore>, DaemonError> {
+    context
+        .node_manager
+        .ask(node::manager::GetNodeStore)
+        .send()
+        .await
+        .map_err(|e| DaemonError::Other(e.to_string()))
+}
+
+async fn get_node_owner(name: &str, context: &AppContext) -> Result<Option<u32>, DaemonError> {
+    get_node_store(context)
+        .await?
+        .ask(GetNodeOwner {
+            name: name.to_string(),
+        })
+        .send()
+        .await
+        .map_err(store_error)
+}
+
+async fn handle_ping(context: &AppContext) -> DaemonResult {
+    let running_nodes = context
+        .node_manager
+        .ask(GetAll)
         .send()
         .await
         .map_err(|e| DaemonError::Other(e.to_string()))?;
 
-    let node = node_store
-        .ask(LoadNode {
+    Ok(DaemonResponse::Pong(DaemonHealth {
+        version: handshake::VERSION.to_string(),
+        protocol_version: handshake::PROTOCOL_VERSION,
+        uptime: context.started_at.elapsed(),
+        running_nodes: running_nodes.len(),
+        queued_requests: context
+            .queued_requests
+            .load(Ordering::Relaxed)
+            .saturating_sub(1),
+    }))
+}
+
+async fn handle_list_nodes(context: &AppContext) -> DaemonResult {
+    let node_store = get_node_store(context).await?;
+
+    let all_nodes_names = node_store
+        .ask(ListNodes)
+        .send()
+        .await
+        .map_err(store_error)?;
+
+    let mut node_infos = Vec::new();
+
+    for name in all_nodes_names.iter() {
+        let node_info = get_node_details(&name, context)
+            .await
+            .map_err(daemon_error)?;
+        node_infos.push(node_info);
+    }
+
+    Ok(DaemonResponse::NodeList(node_infos))
+}
+
+async fn handle_get_node_details(node_name: &str, context: &AppContext) -> DaemonResult {
+    let node_info = get_node_details(node_name, context)
+        .await
+        .map_err(daemon_error)?;
+    DaemonResult::Ok(DaemonResponse::NodeDetails(node_info))
+}
+
+async fn get_node_details(node_name: &str, context: &AppContext) -> Result<NodeInfo> {
+    let is_running = context
+        .node_manager
+        .ask(IsNodeRunning {
             name: node_name.to_string(),
         })
         .send()
         .await
+        .map_err(manager_error)?;
+
+    let node_store = context
+        .node_manager
+        .ask(node::manager::GetNodeStore)
+        .send()
+        .await
         .map_err(|e| DaemonError::Other(e.to_string()))?;
 
-    let config_ext_addrs = node
-        .config
+    // Loading the whole node would fail for the locked ones
+    let config = node_store
+        .ask(GetNodeConfig {
+            name: node_name.to_string(),
+        })
+        .send()
+        .await
+        .map_err(store_error)?;
+    let peer_id = node_store
+        .ask(GetNodePeerId {
+            name: node_name.to_string(),
+        })
+        .send()
+        .await
+        .map_err(store_error)?;
+    let is_locked = node_store
+        .ask(IsNodeLocked {
+            name: node_name.to_string(),
+        })
+        .send()
+        .await
+        .map_err(store_error)?;
+
+    let config_ext_addrs = config
         .external_addresses
         .into_iter()
         .map(|addr| addr.to_string())
         .collect::<Vec<String>>();
 
-    let running_ext_addrs = match is_running {
+    let (running_ext_addrs, latencies) = match is_running {
         true => {
             let node = get_node(node_name, context).await?;
 
-            node.ask(GetAddresses)
-                .send()
-                .await
-                .map_err(|e| DaemonError::Other(e.to_string()))?
+            let addrs = node.ask(GetAddresses).send().await.map_err(node_error)?;
+            let latencies = node.ask(GetLatencies).send().await.map_err(node_error)?;
+            (addrs, latencies)
         }
-        false => Vec::new(),
+        false => (Vec::new(), HashMap::new()),
     };
 
     let running_ext_addrs = running_ext_addrs
@@ -357,29 +1306,130 @@ async fn get_node_details(node_name: &str, context: &AppContext) -> Result<NodeI
 
     let node_info = NodeInfo {
         name: node_name.to_string(),
-        peer_id: PeerId::from_public_key(&node.keypair.public()).to_string(),
+        peer_id: peer_id.to_string(),
         is_running,
         config_addresses: config_ext_addrs,
         running_addresses: running_ext_addrs,
+        peer_latencies: latencies
+            .into_iter()
+            .map(|(peer_id, latency)| (peer_id.to_base58(), latency))
+            .collect(),
+        is_locked,
     };
 
     Ok(node_info)
 }
 
-async fn handle_provide_file(node_name: &str, path: PathBuf, context: &AppContext) -> DaemonResult {
+async fn handle_get_node_status(node_name: String, context: &AppContext) -> DaemonResult {
+    let node = get_node(&node_name, context).await?;
+
+    let status = node
+        .ask(GetStatus)
+        .send()
+        .await
+        .inspect_err(|e| debug!(err = e.to_string(), "Failed to get node status"))
+        .map_err(node_error)?;
+
+    Ok(DaemonResponse::NodeStatus(status))
+}
+
+async fn handle_get_peer_scores(node_name: String, context: &AppContext) -> DaemonResult {
+    let node = get_node(&node_name, context).await?;
+
+    let scores = node
+        .ask(GetPeerScores)
+        .send()
+        .await
+        .inspect_err(|e| debug!(err = e.to_string(), "Failed to get peer scores"))
+        .map_err(node_error)?;
+
+    Ok(DaemonResponse::PeerScores { scores })
+}
+
+async fn handle_get_node_events(
+    node_name: String,
+    since: Option<u64>,
+    context: &AppContext,
+) -> DaemonResult {
+    let node = get_node(&node_name, context).await?;
+
+    let events = node
+        .ask(GetEvents { since })
+        .send()
+        .await
+        .inspect_err(|e| debug!(err = e.to_string(), "Failed to get node events"))
+        .map_err(node_error)?;
+
+    Ok(DaemonResponse::NodeEvents { events })
+}
+
+async fn handle_get_routing_table(node_name: String, context: &AppContext) -> DaemonResult {
+    let node = get_node(&node_name, context).await?;
+
+    let (buckets, peers) = node
+        .ask(GetRoutingTable)
+        .send()
+        .await
+        .inspect_err(|e| debug!(err = e.to_string(), "Failed to get routing table"))
+        .map_err(node_error)?;
+
+    Ok(DaemonResponse::RoutingTable { buckets, peers })
+}
+
+async fn handle_disconnect_peer(
+    node_name: String,
+    peer_id: String,
+    context: &AppContext,
+) -> DaemonResult {
+    let peer_id = PeerId::from_str(&peer_id).map_err(invalid_argument)?;
+    let node = get_node(&node_name, context).await?;
+
+    node.ask(DisconnectPeer { peer_id })
+        .send()
+        .await
+        .inspect_err(|e| debug!(err = e.to_string(), "Failed to disconnect peer"))
+        .map_err(node_error)?;
+
+    Ok(DaemonResponse::PeerDisconnected)
+}
+
+async fn handle_reset_peer_score(
+    node_name: String,
+    peer_id: String,
+    context: &AppContext,
+) -> DaemonResult {
+    let peer_id = PeerId::from_str(&peer_id).map_err(invalid_argument)?;
+    let node = get_node(&node_name, context).await?;
+
+    node.ask(ResetPeerScore { peer_id })
+        .send()
+        .await
+        .inspect_err(|e| debug!(err = e.to_string(), "Failed to reset peer score"))
+        .map_err(node_error)?;
+
+    Ok(DaemonResponse::PeerScoreReset)
+}
+
+async fn handle_provide_file(
+    node_name: &str,
+    path: PathBuf,
+    file: File,
+    context: &AppContext,
+) -> DaemonResult {
     let node = get_node(&node_name, context).await?;
 
     let resp_id = node
-        .ask(ProvideFile { path })
+        .ask(ProvideFile { path, file })
         .send()
         .await
         .inspect_err(|e| debug!(err = e.to_string(), "Failed to handle provide file"))
-        .map_err(|e| DaemonError::Other(e.to_string()))?;
+        .map_err(node_error)?;
 
     Ok(DaemonResponse::FileProvided { id: resp_id })
 }
 
 async fn handle_get_providers(node_name: String, id: String, context: &AppContext) -> DaemonResult {
+    check_object_id(&id)?;
     let node = get_node(&node_name, context).await?;
 
     let resp = node
@@ -389,7 +1439,7 @@ async fn handle_get_providers(node_name: String, id: String, context: &AppContex
         .send()
         .await
         .inspect_err(|e| debug!(err = e.to_string(), "Failed to get file providers"))
-        .map_err(|e| DaemonError::Other(e.to_string()))?;
+        .map_err(node_error)?;
 
     Ok(DaemonResponse::Providers {
         ids: resp.0.iter().map(|r| r.to_base58()).collect(),
@@ -398,15 +1448,33 @@ async fn handle_get_providers(node_name: String, id: String, context: &AppContex
 }
 
 // TODO! Downloading a file is blocking now, it should be done in background in some way
-async fn handle_download_file(node_name: String, id: String, context: &AppContext) -> DaemonResult {
+async fn handle_download_file(
+    node_name: String,
+    id: String,
+    group: Option<String>,
+    force_network: bool,
+    context: &AppContext,
+) -> DaemonResult {
+    check_object_id(&id)?;
+    if let Some(group) = &group {
+        check_object_id(group)?;
+    }
     let node = get_node(&node_name, context).await?;
 
-    let resp = node
-        .ask(DownloadFile { obj_id_str: id })
+    let download = node
+        .ask(DownloadFile {
+            obj_id_str: id,
+            group,
+            force_network,
+        })
         .send()
         .await
         .inspect_err(|e| debug!(err = e.to_string(), "Failed to handle download file"))
-        .map_err(|e| DaemonError::Other(e.to_string()))?;
+        .map_err(node_error)?;
+    let resp = finish_download(download)
+        .await
+        .inspect_err(|e| debug!(err = e.to_string(), "Failed to download file"))
+        .map_err(download_error)?;
 
     Ok(DaemonResponse::FileDownloaded {
         data: resp.0,
@@ -416,67 +1484,972 @@ async fn handle_download_file(node_name: String, id: String, context: &AppContex
 
 async fn handle_dial(
     node_name: String,
-    peer_id: String,
+    peer_id: Option<String>,
     addr: String,
     context: &AppContext,
 ) -> DaemonResult {
     let node = get_node(&node_name, context).await?;
 
-    node.ask(DialPeer {
-        peer_id: peer_id.clone(),
-        peer_addr: addr.clone(),
-    })
-    .send()
-    .await
-    .inspect_err(|e| debug!(err = e.to_string(), "Failed to handle dial"))
-    .map_err(|e| DaemonError::Other(e.to_string()))?;
+    let peer_id = node
+        .ask(DialPeer {
+            peer_id,
+            peer_addr: addr.clone(),
+        })
+        .send()
+        .await
+        .inspect_err(|e| debug!(err = e.to_string(), "Failed to handle dial"))
+        .map_err(node_error)?;
 
     debug!("Dialed peer: {}", peer_id);
-    Ok(DaemonResponse::Dialed)
+    Ok(DaemonResponse::Dialed {
+        peer_id: peer_id.to_base58(),
+    })
 }
 
 async fn handle_publish_file(
     node_name: String,
     path: PathBuf,
+    file: File,
+    access: ObjectAccess,
+    previous: Option<String>,
     context: &AppContext,
 ) -> DaemonResult {
+    if let ObjectAccess::Group(group) = &access {
+        check_object_id(group)?;
+    }
+    if let Some(previous) = &previous {
+        check_object_id(previous)?;
+    }
     let node = get_node(&node_name, context).await?;
 
     let resp_id = node
-        .ask(PublishFile { path })
+        .ask(PublishFile {
+            path,
+            file,
+            access,
+            previous,
+        })
         .send()
         .await
         .inspect_err(|e| debug!(err = e.to_string(), "Failed to handle publish file"))
-        .map_err(|e| DaemonError::Other(e.to_string()))?;
+        .map_err(node_error)?;
 
     Ok(DaemonResponse::FilePublished { id: resp_id })
 }
 
-async fn handle_get_published_objects(node_name: String, context: &AppContext) -> DaemonResult {
+async fn handle_publish_files(
+    node_name: String,
+    files: Vec<(PathBuf, File)>,
+    max_concurrency: usize,
+    context: &AppContext,
+) -> DaemonResult {
     let node = get_node(&node_name, context).await?;
-    let object_infos = node
-        .ask(GetPublishedObjects)
+
+    let results = node
+        .ask(PublishFiles {
+            files,
+            max_concurrency,
+        })
         .send()
         .await
-        .inspect_err(|e| debug!(err = e.to_string(), "Failed to get published objects list"))
+        .inspect_err(|e| debug!(err = e.to_string(), "Failed to handle publish files"))
         .map_err(|e| DaemonError::Other(e.to_string()))?;
 
-    DaemonResult::Ok(DaemonResponse::PublishedObjectsList { object_infos })
+    Ok(DaemonResponse::FilesPublished { results })
 }
 
-async fn handle_delete_object(
+async fn handle_verify_object(
     node_name: String,
     object_id: String,
+    signer: Option<String>,
     context: &AppContext,
 ) -> DaemonResult {
+    let signer = signer
+        .map(|s| PeerId::from_str(&s))
+        .transpose()
+        .map_err(invalid_argument)?;
+    check_object_id(&object_id)?;
     let node = get_node(&node_name, context).await?;
-    let result = node
-        .ask(DeleteObject {
+
+    let verification = node
+        .ask(VerifyObject {
+            obj_id_str: object_id,
+            signer,
+        })
+        .send()
+        .await
+        .inspect_err(|e| debug!(err = e.to_string(), "Failed to handle verify object"))
+        .map_err(node_error)?;
+
+    Ok(DaemonResponse::ObjectVerified(verification))
+}
+
+async fn handle_get_object_info(
+    node_name: String,
+    object_id: String,
+    context: &AppContext,
+) -> DaemonResult {
+    check_object_id(&object_id)?;
+    let node = get_node(&node_name, context).await?;
+
+    let info = node
+        .ask(GetObjectInfo {
             obj_id_str: object_id,
         })
+        .send()
         .await
-        .inspect_err(|e| debug!(err = e.to_string(), "Failed to delete object"))
-        .map_err(|e| DaemonError::Other(e.to_string()))?;
+        .inspect_err(|e| debug!(err = e.to_string(), "Failed to handle get object info"))
+        .map_err(node_error)?;
 
-    DaemonResult::Ok(result)
+    Ok(DaemonResponse::ObjectInfo(info))
+}
+
+async fn handle_get_object(
+    node_name: String,
+    object_id: String,
+    context: &AppContext,
+) -> DaemonResult {
+    check_object_id(&object_id)?;
+    let node = get_node(&node_name, context).await?;
+
+    let object = node
+        .ask(GetObject {
+            obj_id_str: object_id,
+        })
+        .send()
+        .await
+        .inspect_err(|e| debug!(err = e.to_string(), "Failed to handle get object"))
+        .map_err(node_error)?;
+
+    Ok(DaemonResponse::Object(object))
+}
+
+async fn handle_query(
+    node_name: String,
+    query: proto::TypedObject,
+    context: &AppContext,
+) -> DaemonResult {
+    let node = get_node(&node_name, context).await?;
+    let results = node
+        .ask(Query { query })
+        .send()
+        .await
+        .inspect_err(|e| debug!(err = e.to_string(), "Failed to handle query"))
+        .map_err(node_error)?;
+
+    Ok(DaemonResponse::QueryResults(results))
+}
+
+async fn handle_export_vault_snapshot(
+    node_name: String,
+    target: FileTarget,
+    context: &AppContext,
+) -> DaemonResult {
+    let summary = context
+        .node_manager
+        .ask(node::manager::ExportVaultSnapshot {
+            name: node_name,
+            target,
+        })
+        .send()
+        .await
+        .inspect_err(|e| debug!(err = e.to_string(), "Failed to export vault snapshot"))
+        .map_err(manager_error)?;
+
+    Ok(DaemonResponse::VaultSnapshotExported(summary))
+}
+
+async fn handle_import_vault_snapshot(
+    node_name: String,
+    file: File,
+    context: &AppContext,
+) -> DaemonResult {
+    let summary = context
+        .node_manager
+        .ask(node::manager::ImportVaultSnapshot {
+            name: node_name,
+            file,
+        })
+        .send()
+        .await
+        .inspect_err(|e| debug!(err = e.to_string(), "Failed to import vault snapshot"))
+        .map_err(manager_error)?;
+
+    Ok(DaemonResponse::VaultSnapshotImported(summary))
+}
+
+fn handle_set_log_level(target: String, level: String) -> DaemonResult {
+    let filter = logging::set_log_level(&target, &level).map_err(invalid_argument)?;
+    info!(filter = filter, "Log level changed");
+
+    Ok(DaemonResponse::LogLevelSet { filter })
+}
+
+fn handle_tail_logs(node_name: Option<String>, lines: usize) -> DaemonResult {
+    if let Some(node_name) = &node_name {
+        check_node_name(node_name)?;
+    }
+    let lines = logging::tail_logs(node_name.as_deref(), lines).map_err(daemon_error)?;
+
+    Ok(DaemonResponse::LogLines { lines })
+}
+
+async fn handle_set_profile(
+    node_name: String,
+    profile: NodeProfile,
+    context: &AppContext,
+) -> DaemonResult {
+    if profile.display_name.trim().is_empty() {
+        return Err(invalid_argument("The display name can't be empty"));
+    }
+    if let Some(avatar) = &profile.avatar {
+        check_object_id(avatar)?;
+    }
+
+    context
+        .node_manager
+        .ask(node::manager::SetNodeProfile {
+            name: node_name.clone(),
+            profile,
+        })
+        .send()
+        .await
+        .inspect_err(|e| debug!(err = e.to_string(), "Failed to handle set profile"))
+        .map_err(manager_error)?;
+
+    debug!(name = node_name, "Profile set!");
+
+    Ok(DaemonResponse::NodeConfigUpdated)
+}
+
+async fn handle_get_peer_profile(
+    node_name: String,
+    peer_id: String,
+    context: &AppContext,
+) -> DaemonResult {
+    let peer_id = PeerId::from_str(&peer_id).map_err(invalid_argument)?;
+    let node = get_node(&node_name, context).await?;
+
+    let profile = node
+        .ask(GetPeerProfile { peer_id })
+        .send()
+        .await
+        .inspect_err(|e| debug!(err = e.to_string(), "Failed to get peer profile"))
+        .map_err(node_error)?;
+
+    let profile = profile.map(|p| PeerProfile {
+        peer_id: peer_id.to_base58(),
+        display_name: p.display_name,
+        description: p.description,
+        avatar: p.avatar.map(|a| a.to_string()),
+    });
+
+    Ok(DaemonResponse::PeerProfile(profile))
+}
+
+async fn handle_add_contact(
+    node_name: String,
+    mut contact: Contact,
+    context: &AppContext,
+) -> DaemonResult {
+    check_node_name(&node_name)?;
+    // Stored normalized, so the swarm finds the contact by the peer ID
+    contact.peer_id = PeerId::from_str(&contact.peer_id)
+        .map_err(invalid_argument)?
+        .to_base58();
+    if let Some(address) = &contact.address {
+        Multiaddr::from_str(address).map_err(invalid_argument)?;
+    }
+    if contact.alias.trim().is_empty() {
+        contact.alias = contact.peer_id.clone();
+    }
+
+    context
+        .node_manager
+        .ask(node::manager::AddContact {
+            name: node_name,
+            contact,
+        })
+        .send()
+        .await
+        .inspect_err(|e| debug!(err = e.to_string(), "Failed to add contact"))
+        .map_err(manager_error)?;
+
+    Ok(DaemonResponse::ContactAdded)
+}
+
+async fn handle_remove_contact(
+    node_name: String,
+    peer_id: String,
+    context: &AppContext,
+) -> DaemonResult {
+    check_node_name(&node_name)?;
+    let peer_id = PeerId::from_str(&peer_id).map_err(invalid_argument)?;
+
+    let removed = context
+        .node_manager
+        .ask(node::manager::RemoveContact {
+            name: node_name,
+            peer_id: peer_id.to_base58(),
+        })
+        .send()
+        .await
+        .inspect_err(|e| debug!(err = e.to_string(), "Failed to remove contact"))
+        .map_err(manager_error)?;
+
+    Ok(DaemonResponse::ContactRemoved { removed })
+}
+
+async fn handle_list_contacts(node_name: String, context: &AppContext) -> DaemonResult {
+    check_node_name(&node_name)?;
+
+    let contacts = context
+        .node_manager
+        .ask(node::manager::ListContacts { name: node_name })
+        .send()
+        .await
+        .inspect_err(|e| debug!(err = e.to_string(), "Failed to list contacts"))
+        .map_err(manager_error)?;
+
+    Ok(DaemonResponse::Contacts(contacts))
+}
+
+async fn handle_send_message(
+    node_name: String,
+    peer_id: String,
+    content: MessageContent,
+    encrypt: bool,
+    context: &AppContext,
+) -> DaemonResult {
+    let peer_id = PeerId::from_str(&peer_id).map_err(invalid_argument)?;
+    let node = get_node(&node_name, context).await?;
+
+    let delivery = node
+        .ask(SendDirectMessage {
+            peer_id,
+            content,
+            encrypt,
+        })
+        .send()
+        .await
+        .inspect_err(|e| debug!(err = e.to_string(), "Failed to send message"))
+        .map_err(node_error)?;
+
+    Ok(match delivery {
+        Delivery::Direct(id) => DaemonResponse::MessageSent { id: id.to_string() },
+        Delivery::Stored { id, peers } => DaemonResponse::MessageStored {
+            id: id.to_string(),
+            peers,
+        },
+    })
+}
+
+async fn handle_get_inbox(node_name: String, context: &AppContext) -> DaemonResult {
+    check_node_name(&node_name)?;
+
+    let messages = context
+        .node_manager
+        .ask(node::manager::GetInbox { name: node_name })
+        .send()
+        .await
+        .inspect_err(|e| debug!(err = e.to_string(), "Failed to get inbox"))
+        .map_err(manager_error)?;
+
+    Ok(DaemonResponse::Inbox(messages))
+}
+
+async fn handle_create_group(
+    node_name: String,
+    group_name: String,
+    context: &AppContext,
+) -> DaemonResult {
+    if group_name.trim().is_empty() {
+        return Err(invalid_argument("The group name is empty"));
+    }
+    let node = get_node(&node_name, context).await?;
+
+    let id = node
+        .ask(CreateGroup { name: group_name })
+        .send()
+        .await
+        .inspect_err(|e| debug!(err = e.to_string(), "Failed to create group"))
+        .map_err(node_error)?;
+
+    Ok(DaemonResponse::GroupCreated { id })
+}
+
+async fn handle_invite_to_group(
+    node_name: String,
+    group_id: String,
+    peer_id: String,
+    valid_for_secs: u64,
+    context: &AppContext,
+) -> DaemonResult {
+    check_object_id(&group_id)?;
+    let peer_id = PeerId::from_str(&peer_id).map_err(invalid_argument)?;
+    let node = get_node(&node_name, context).await?;
+
+    let invitation = node
+        .ask(InviteToGroup {
+            group_id,
+            peer_id,
+            valid_for: Duration::from_secs(valid_for_secs),
+        })
+        .send()
+        .await
+        .inspect_err(|e| debug!(err = e.to_string(), "Failed to invite to group"))
+        .map_err(node_error)?;
+
+    Ok(DaemonResponse::GroupInvitation { invitation })
+}
+
+async fn handle_join_group(
+    node_name: String,
+    invitation: String,
+    context: &AppContext,
+) -> DaemonResult {
+    let node = get_node(&node_name, context).await?;
+
+    let id = node
+        .ask(JoinGroup { invitation })
+        .send()
+        .await
+        .inspect_err(|e| debug!(err = e.to_string(), "Failed to join group"))
+        .map_err(node_error)?;
+
+    Ok(DaemonResponse::GroupJoined { id })
+}
+
+async fn handle_post_to_group(
+    node_name: String,
+    group_id: String,
+    content: MessageContent,
+    context: &AppContext,
+) -> DaemonResult {
+    check_object_id(&group_id)?;
+    let node = get_node(&node_name, context).await?;
+
+    let id = node
+        .ask(PostToGroup { group_id, content })
+        .send()
+        .await
+        .inspect_err(|e| debug!(err = e.to_string(), "Failed to post to group"))
+        .map_err(node_error)?;
+
+    Ok(DaemonResponse::GroupPosted { id })
+}
+
+async fn handle_get_group_feed(
+    node_name: String,
+    group_id: String,
+    context: &AppContext,
+) -> DaemonResult {
+    check_node_name(&node_name)?;
+    check_object_id(&group_id)?;
+
+    let posts = context
+        .node_manager
+        .ask(node::manager::GetGroupFeed {
+            name: node_name,
+            group_id,
+        })
+        .send()
+        .await
+        .inspect_err(|e| debug!(err = e.to_string(), "Failed to get group feed"))
+        .map_err(manager_error)?;
+
+    Ok(DaemonResponse::GroupFeed(posts))
+}
+
+async fn handle_get_audit_log(
+    node_name: String,
+    filter: AuditFilter,
+    context: &AppContext,
+) -> DaemonResult {
+    check_node_name(&node_name)?;
+    if let Some(object_id) = &filter.object_id {
+        check_object_id(object_id)?;
+    }
+
+    let entries = context
+        .node_manager
+        .ask(node::manager::GetAuditLog {
+            name: node_name,
+            filter,
+        })
+        .send()
+        .await
+        .inspect_err(|e| debug!(err = e.to_string(), "Failed to get audit log"))
+        .map_err(manager_error)?;
+
+    Ok(DaemonResponse::AuditLog(entries))
+}
+
+async fn handle_get_node_stats_history(
+    node_name: String,
+    since: Option<SystemTime>,
+    until: Option<SystemTime>,
+    context: &AppContext,
+) -> DaemonResult {
+    check_node_name(&node_name)?;
+
+    let samples = context
+        .node_manager
+        .ask(node::manager::GetStatsHistory {
+            name: node_name,
+            since,
+            until,
+        })
+        .send()
+        .await
+        .inspect_err(|e| debug!(err = e.to_string(), "Failed to get stats history"))
+        .map_err(manager_error)?;
+
+    Ok(DaemonResponse::NodeStatsHistory(samples))
+}
+
+async fn handle_list_scheduled_tasks(node_name: String, context: &AppContext) -> DaemonResult {
+    let node = get_node(&node_name, context).await?;
+    let tasks = node
+        .ask(ListScheduledTasks)
+        .send()
+        .await
+        .inspect_err(|e| debug!(err = e.to_string(), "Failed to list scheduled tasks"))
+        .map_err(node_error)?;
+
+    Ok(DaemonResponse::ScheduledTasks(tasks))
+}
+
+async fn handle_run_task_now(
+    node_name: String,
+    task: ScheduledTask,
+    context: &AppContext,
+) -> DaemonResult {
+    let node = get_node(&node_name, context).await?;
+    let finished = node
+        .ask(RunScheduledTask { task })
+        .send()
+        .await
+        .inspect_err(|e| debug!(err = e.to_string(), "Failed to run scheduled task"))
+        .map_err(node_error)?;
+    // Dropped if the node stops before the task finishes
+    let info = finished
+        .await
+        .map_err(|_| DaemonError::Other(format!("Task {task} did not finish")))?;
+
+    Ok(DaemonResponse::TaskRun(info))
+}
+
+async fn handle_get_popular_objects(
+    node_name: String,
+    limit: Option<usize>,
+    context: &AppContext,
+) -> DaemonResult {
+    let node = get_node(&node_name, context).await?;
+    let objects = node
+        .ask(GetPopularObjects { limit })
+        .send()
+        .await
+        .inspect_err(|e| debug!(err = e.to_string(), "Failed to get popular objects"))
+        .map_err(node_error)?;
+
+    Ok(DaemonResponse::PopularObjects(objects))
+}
+
+async fn handle_tag_object(
+    node_name: String,
+    id: String,
+    tags: Vec<String>,
+    context: &AppContext,
+) -> DaemonResult {
+    check_object_id(&id)?;
+    let node = get_node(&node_name, context).await?;
+    let tags_id = node
+        .ask(PublishTags {
+            obj_id_str: id,
+            tags,
+        })
+        .send()
+        .await
+        .inspect_err(|e| debug!(err = e.to_string(), "Failed to publish tags"))
+        .map_err(node_error)?;
+
+    Ok(DaemonResponse::TagsPublished { id: tags_id })
+}
+
+async fn handle_search_objects(
+    node_name: String,
+    query: String,
+    network: bool,
+    context: &AppContext,
+) -> DaemonResult {
+    let node = get_node(&node_name, context).await?;
+    let results = node
+        .ask(SearchObjects { query, network })
+        .send()
+        .await
+        .inspect_err(|e| debug!(err = e.to_string(), "Failed to search objects"))
+        .map_err(node_error)?;
+
+    Ok(DaemonResponse::SearchResults(results))
+}
+
+async fn handle_search_text(
+    node_name: String,
+    query: String,
+    limit: Option<usize>,
+    context: &AppContext,
+) -> DaemonResult {
+    let node = get_node(&node_name, context).await?;
+    let matches = node
+        .ask(SearchText { query, limit })
+        .send()
+        .await
+        .inspect_err(|e| debug!(err = e.to_string(), "Failed to search text"))
+        .map_err(node_error)?;
+
+    Ok(DaemonResponse::TextSearchResults(matches))
+}
+
+async fn handle_get_history(node_name: String, id: String, context: &AppContext) -> DaemonResult {
+    check_object_id(&id)?;
+    let node = get_node(&node_name, context).await?;
+    let revisions = node
+        .ask(GetHistory { obj_id_str: id })
+        .send()
+        .await
+        .inspect_err(|e| debug!(err = e.to_string(), "Failed to get history"))
+        .map_err(node_error)?;
+
+    Ok(DaemonResponse::History { revisions })
+}
+
+async fn handle_get_published_objects(node_name: String, context: &AppContext) -> DaemonResult {
+    let node = get_node(&node_name, context).await?;
+    let object_infos = node
+        .ask(GetPublishedObjects)
+        .send()
+        .await
+        .inspect_err(|e| debug!(err = e.to_string(), "Failed to get published objects list"))
+        .map_err(node_error)?;
+
+    DaemonResult::Ok(DaemonResponse::PublishedObjectsList { object_infos })
+}
+
+async fn handle_delete_object(
+    node_name: String,
+    object_id: String,
+    context: &AppContext,
+) -> DaemonResult {
+    check_object_id(&object_id)?;
+    let node = get_node(&node_name, context).await?;
+    let result = node
+        .ask(DeleteObject {
+            obj_id_str: object_id,
+        })
+        .await
+        .inspect_err(|e| debug!(err = e.to_string(), "Failed to delete object"))
+        .map_err(node_error)?;
+
+    DaemonResult::Ok(result)
+}
+
+async fn handle_stop_providing(
+    node_name: String,
+    object_id: String,
+    keep_in_vault: bool,
+    context: &AppContext,
+) -> DaemonResult {
+    check_object_id(&object_id)?;
+    let node = get_node(&node_name, context).await?;
+    node.ask(StopProviding {
+        obj_id_str: object_id,
+        keep_in_vault,
+    })
+    .await
+    .inspect_err(|e| debug!(err = e.to_string(), "Failed to stop providing"))
+    .map_err(node_error)?;
+
+    Ok(DaemonResponse::StoppedProviding)
+}
+
+async fn handle_export_node_identity(
+    node_name: String,
+    passphrase: String,
+    context: &AppContext,
+) -> DaemonResult {
+    if passphrase.is_empty() {
+        return Err(invalid_argument("The passphrase can't be empty"));
+    }
+
+    let node_store = context
+        .node_manager
+        .ask(node::manager::GetNodeStore)
+        .send()
+        .await
+        .map_err(|e| DaemonError::Other(e.to_string()))?;
+    let node = node_store
+        .ask(LoadNode { name: node_name })
+        .send()
+        .await
+        .inspect_err(|e| debug!(err = e.to_string(), "Failed to load node to export"))
+        .map_err(store_error)?;
+
+    let bundle = identity::encrypt_keypair(&node.keypair, &passphrase).map_err(daemon_error)?;
+
+    Ok(DaemonResponse::NodeIdentityExported { bundle })
+}
+
+async fn handle_import_node_identity(
+    node_name: String,
+    bundle: Vec<u8>,
+    passphrase: String,
+    owner: Option<u32>,
+    context: &AppContext,
+) -> DaemonResult {
+    let keypair = identity::decrypt_keypair(&bundle, &passphrase).map_err(daemon_error)?;
+    let peer_id = PeerId::from_public_key(&keypair.public());
+    let node_snapshot = NodeSnapshot::builder()
+        .name(node_name.clone())
+        .keypair(keypair)
+        .owner(owner)
+        .build_snapshot()
+        // This can't fail
+        .unwrap();
+
+    let node_store = context
+        .node_manager
+        .ask(node::manager::GetNodeStore)
+        .send()
+        .await
+        .map_err(|e| DaemonError::Other(e.to_string()))?;
+    node_store
+        .ask(ImportNode { node_snapshot })
+        .send()
+        .await
+        .inspect_err(|e| debug!(err = e.to_string(), "Failed to import node identity"))
+        .map_err(store_error)?;
+
+    info!(
+        name = node_name,
+        peer_id = peer_id.to_base58(),
+        "Node identity imported"
+    );
+    Ok(DaemonResponse::NodeIdentityImported {
+        peer_id: peer_id.to_base58(),
+    })
+}
+
+async fn handle_unlock_node(
+    node_name: String,
+    passphrase: String,
+    context: &AppContext,
+) -> DaemonResult {
+    let node_store = context
+        .node_manager
+        .ask(node::manager::GetNodeStore)
+        .send()
+        .await
+        .map_err(|e| DaemonError::Other(e.to_string()))?;
+    node_store
+        .ask(UnlockNode {
+            name: node_name.clone(),
+            passphrase,
+        })
+        .send()
+        .await
+        .inspect_err(|e| debug!(err = e.to_string(), "Failed to unlock node"))
+        .map_err(store_error)?;
+
+    debug!(name = node_name, "Node unlocked!");
+    Ok(DaemonResponse::NodeUnlocked)
+}
+
+async fn handle_set_node_passphrase(
+    node_name: String,
+    passphrase: Option<String>,
+    context: &AppContext,
+) -> DaemonResult {
+    if passphrase.as_ref().is_some_and(|p| p.is_empty()) {
+        return Err(invalid_argument("The passphrase can't be empty"));
+    }
+
+    let node_store = context
+        .node_manager
+        .ask(node::manager::GetNodeStore)
+        .send()
+        .await
+        .map_err(|e| DaemonError::Other(e.to_string()))?;
+    node_store
+        .ask(SetKeyPassphrase {
+            name: node_name.clone(),
+            passphrase,
+        })
+        .send()
+        .await
+        .inspect_err(|e| debug!(err = e.to_string(), "Failed to set node passphrase"))
+        .map_err(store_error)?;
+
+    debug!(name = node_name, "Node passphrase updated!");
+    Ok(DaemonResponse::NodeConfigUpdated)
+}
+
+async fn handle_rename_node(
+    name: String,
+    new_name: String,
+    force: bool,
+    context: &AppContext,
+) -> DaemonResult {
+    check_node_name(&new_name)?;
+    context
+        .node_manager
+        .ask(node::manager::RenameNode {
+            name: name.clone(),
+            new_name: new_name.clone(),
+            force,
+        })
+        .send()
+        .await
+        .inspect_err(|e| debug!(err = e.to_string(), "Failed to handle rename node"))
+        .map_err(manager_error)?;
+
+    debug!(name = name, new_name = new_name, "Node renamed!");
+    Ok(DaemonResponse::NodeRenamed)
+}
+
+async fn handle_rotate_node_key(
+    name: String,
+    passphrase: Option<String>,
+    force: bool,
+    context: &AppContext,
+) -> DaemonResult {
+    check_node_name(&name)?;
+    let rotation = context
+        .node_manager
+        .ask(node::manager::RotateNodeKey {
+            name: name.clone(),
+            passphrase,
+            force,
+        })
+        .send()
+        .await
+        .inspect_err(|e| debug!(err = e.to_string(), "Failed to rotate node key"))
+        .map_err(manager_error)?;
+
+    let (old_key, new_key) = rotation.verify().map_err(daemon_error)?;
+    debug!(name = name, "Node key rotated!");
+    Ok(DaemonResponse::NodeKeyRotated {
+        old_peer_id: old_key.to_peer_id().to_base58(),
+        new_peer_id: new_key.to_peer_id().to_base58(),
+    })
+}
+
+async fn handle_delete_node(
+    name: String,
+    force: bool,
+    keep_vault: bool,
+    context: &AppContext,
+) -> DaemonResult {
+    context
+        .node_manager
+        .ask(node::manager::DeleteNode {
+            name: name.clone(),
+            force,
+            keep_vault,
+        })
+        .send()
+        .await
+        .inspect_err(|e| debug!(err = e.to_string(), "Failed to handle delete node"))
+        .map_err(manager_error)?;
+
+    debug!(name = name, "Node deleted!");
+    Ok(DaemonResponse::NodeDeleted)
+}
+
+async fn handle_clone_node(
+    source: String,
+    target: String,
+    fresh_identity: bool,
+    owner: Option<u32>,
+    context: &AppContext,
+) -> DaemonResult {
+    check_node_name(&target)?;
+    let node_store = context
+        .node_manager
+        .ask(node::manager::GetNodeStore)
+        .send()
+        .await
+        .map_err(|e| DaemonError::Other(e.to_string()))?;
+
+    // Only the config is needed for a fresh identity, so also locked nodes can be cloned
+    let (config, keypair) = match fresh_identity {
+        true => {
+            let config = node_store
+                .ask(GetNodeConfig {
+                    name: source.clone(),
+                })
+                .send()
+                .await
+                .map_err(store_error)?;
+            (config, Keypair::generate_ed25519())
+        }
+        false => {
+            let source_node = node_store
+                .ask(LoadNode {
+                    name: source.clone(),
+                })
+                .send()
+                .await
+                .map_err(store_error)?;
+            // The copy would be stored unencrypted
+            if source_node.config.key_protected {
+                return Err(invalid_argument(
+                    "The keypair of a protected node can't be copied, export its identity instead",
+                ));
+            }
+            (source_node.config, source_node.keypair)
+        }
+    };
+
+    let peer_id = PeerId::from_public_key(&keypair.public());
+    let node_snapshot = NodeSnapshot::builder()
+        .name(target.clone())
+        .keypair(keypair)
+        .config(config)
+        .owner(owner)
+        .build_snapshot()
+        // This can't fail
+        .unwrap();
+
+    node_store
+        .ask(ImportNode { node_snapshot })
+        .send()
+        .await
+        .inspect_err(|e| debug!(err = e.to_string(), "Failed to clone node"))
+        .map_err(store_error)?;
+
+    debug!(source = source, target = target, "Node cloned!");
+    Ok(DaemonResponse::NodeCloned {
+        peer_id: peer_id.to_base58(),
+    })
+}
+
+async fn handle_reload_node_config(
+    name: String,
+    new_cfg: Option<NodeConfig>,
+    context: &AppContext,
+) -> DaemonResult {
+    let summary = context
+        .node_manager
+        .ask(node::manager::ReloadNodeConfig {
+            name: name.clone(),
+            new_cfg,
+        })
+        .send()
+        .await
+        .inspect_err(|e| debug!(err = e.to_string(), "Failed to handle reload node config"))
+        .map_err(manager_error)?;
+
+    debug!(name = name, "Node config reloaded!");
+    Ok(DaemonResponse::NodeConfigReloaded(summary))
 }
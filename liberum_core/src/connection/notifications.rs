@@ -0,0 +1,89 @@
+use liberum_core::{DaemonNotification, DaemonRequest};
+use tokio::sync::broadcast;
+
+use super::permission::Permission;
+
+///! The state changes of the nodes are broadcast to all the connections which
+///! subscribed to them, so every UI can refresh when another one changes something.
+
+/// Number of notifications kept for the slow connections before they lag
+pub const NOTIFICATION_CAPACITY: usize = 64;
+
+/// The notification with the owner of its node, only the connections which may
+/// see the node receive it
+#[derive(Debug, Clone)]
+pub struct OwnedNotification {
+    pub notification: DaemonNotification,
+    pub owner: Option<u32>,
+}
+
+/// The notification sent when the request succeeds
+pub fn notification_for(request: &DaemonRequest) -> Option<DaemonNotification> {
+    let notification = match request {
+        DaemonRequest::NewNode { node_name, .. }
+        | DaemonRequest::ImportNodeIdentity { node_name, .. } => DaemonNotification::NodeCreated {
+            node_name: node_name.clone(),
+        },
+        DaemonRequest::StartNode { node_name } => DaemonNotification::NodeStarted {
+            node_name: node_name.clone(),
+        },
+        DaemonRequest::StopNode { node_name } => DaemonNotification::NodeStopped {
+            node_name: node_name.clone(),
+        },
+        DaemonRequest::OverwriteNodeConfig { node_name, .. }
+        | DaemonRequest::BlockPeer { node_name, .. }
+        | DaemonRequest::UnblockPeer { node_name, .. }
+        | DaemonRequest::AddWatchDir { node_name, .. }
+        | DaemonRequest::RemoveWatchDir { node_name, .. }
+        | DaemonRequest::SetNodePassphrase { node_name, .. }
+        | DaemonRequest::ReloadNodeConfig { node_name, .. }
+        | DaemonRequest::SetModuleEnabled { node_name, .. }
+        | DaemonRequest::SetProfile { node_name, .. }
+        | DaemonRequest::RotateNodeKey { node_name, .. } => DaemonNotification::NodeConfigUpdated {
+            node_name: node_name.clone(),
+        },
+        DaemonRequest::UnlockNode { node_name, .. } => DaemonNotification::NodeUnlocked {
+            node_name: node_name.clone(),
+        },
+        DaemonRequest::RenameNode {
+            node_name,
+            new_name,
+            ..
+        } => DaemonNotification::NodeRenamed {
+            node_name: node_name.clone(),
+            new_name: new_name.clone(),
+        },
+        DaemonRequest::DeleteNode { node_name, .. } => DaemonNotification::NodeDeleted {
+            node_name: node_name.clone(),
+        },
+        DaemonRequest::CloneNode { target, .. } => DaemonNotification::NodeCreated {
+            node_name: target.clone(),
+        },
+        _ => return None,
+    };
+
+    Some(notification)
+}
+
+/// Waits for the next notification about a node the connection may see. Never
+/// returns if the connection is not subscribed
+pub async fn next_notification(
+    receiver: &mut Option<broadcast::Receiver<OwnedNotification>>,
+    permission: Permission,
+) -> DaemonNotification {
+    let Some(receiver) = receiver else {
+        return std::future::pending().await;
+    };
+
+    loop {
+        match receiver.recv().await {
+            Ok(owned) if permission.can_see(owned.owner) => return owned.notification,
+            Ok(_) => continue,
+            Err(broadcast::error::RecvError::Lagged(missed)) => {
+                return DaemonNotification::Lagged { missed }
+            }
+            // The sender lives as long as the daemon
+            Err(broadcast::error::RecvError::Closed) => return std::future::pending().await,
+        }
+    }
+}
@@ -0,0 +1,347 @@
+use std::fs::File;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+use liberum_core::daemon_config::{Role, RolesConfig};
+use liberum_core::owned_file::{self, FileTarget};
+use liberum_core::{DaemonError, DaemonRequest};
+use tokio::net::UnixStream;
+use tracing::warn;
+
+use super::{get_node_owner, AppContext};
+
+///! The socket of the daemon is accessible to all the users of the system, every
+///! connection is authenticated with the credentials of its peer. The nodes belong to
+///! the users who created them, and a user can only see and control their own nodes.
+///!
+///! What a connection may do depends on its role from the daemon config: the
+///! observers only look at the nodes, the operators control them and the admins also
+///! control the daemon itself and all the nodes, no matter who owns them. The user
+///! running the daemon and root are always admins, a connection may switch to another
+///! role with a token. The paths sent to the daemon are read and written by it, so
+///! the others may only pass the paths they own. The files read or written by a
+///! request are opened by its check, and the handlers use the opened files, so a
+///! path checked once can't be swapped for another file later.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Permission {
+    /// None if the credentials of the peer could not be read
+    uid: Option<u32>,
+    role: Role,
+}
+
+impl Permission {
+    pub fn of_peer(socket: &UnixStream, roles: &RolesConfig) -> Self {
+        let peer_uid = match socket.peer_cred() {
+            Ok(cred) => cred.uid(),
+            Err(e) => {
+                warn!(err = e.to_string(), "Failed to get the peer credentials");
+                return Permission {
+                    uid: None,
+                    role: Role::Observer,
+                };
+            }
+        };
+
+        Self::of_uid(peer_uid, nix::unistd::geteuid().as_raw(), roles)
+    }
+
+    fn of_uid(peer_uid: u32, daemon_uid: u32, roles: &RolesConfig) -> Self {
+        let role = match peer_uid == daemon_uid || peer_uid == 0 {
+            true => Role::Admin,
+            false => roles.role_of_user(peer_uid),
+        };
+        Permission {
+            uid: Some(peer_uid),
+            role,
+        }
+    }
+
+    /// The permission of the same user with the role of a token
+    pub fn with_role(self, role: Role) -> Self {
+        Permission { role, ..self }
+    }
+
+    pub fn is_admin(&self) -> bool {
+        self.role == Role::Admin
+    }
+
+    /// The owner of the nodes created by the connection, without it the nodes
+    /// belong to the user running the daemon
+    pub fn owner_uid(&self) -> Option<u32> {
+        self.uid
+    }
+
+    /// Whether the connection may see the node with the owner, None if the node
+    /// doesn't exist
+    pub fn can_see(&self, owner: Option<u32>) -> bool {
+        self.is_admin() || (owner.is_some() && owner == self.uid)
+    }
+
+    /// Checks the request against the owner of its node, and the owners of its paths.
+    /// Returns the files of the request, opened for its handler
+    pub async fn check(
+        &self,
+        request: &DaemonRequest,
+        context: &AppContext,
+    ) -> Result<RequestFiles, DaemonError> {
+        if self.is_admin() {
+            return RequestFiles::open(request, None);
+        }
+
+        let owner = match request.node_name() {
+            Some(name) => get_node_owner(name, context).await?,
+            None => None,
+        };
+        self.allows(request, owner)?;
+
+        let paths = request_paths(request);
+        let Some(uid) = self.uid else {
+            return match paths.first() {
+                Some((path, _)) => Err(not_owned(path)),
+                None => Ok(RequestFiles::default()),
+            };
+        };
+        for (path, path_use) in paths {
+            if path_use == PathUse::Refer && !owns_path(uid, path).await {
+                return Err(not_owned(path));
+            }
+        }
+        RequestFiles::open(request, Some(uid))
+    }
+
+    /// Whether the request is allowed for the node with the owner, None if the
+    /// request has no node or it doesn't exist
+    fn allows(&self, request: &DaemonRequest, owner: Option<u32>) -> Result<(), DaemonError> {
+        if !self.role.allows(request) {
+            return Err(DaemonError::PermissionDenied(format!(
+                "The {} role may not do it",
+                self.role
+            )));
+        }
+        match owner {
+            Some(_) if !self.can_see(owner) => Err(DaemonError::PermissionDenied(
+                "The node is owned by another user".to_string(),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The files of the request opened by its check. The handlers take them instead of
+/// opening the paths again
+#[derive(Debug, Default)]
+pub struct RequestFiles(Vec<(PathBuf, RequestFile)>);
+
+#[derive(Debug)]
+enum RequestFile {
+    Read(File),
+    Target(FileTarget),
+}
+
+impl RequestFiles {
+    /// Opens the files read or written by the request. With the UID of the user,
+    /// the files must be owned by the user
+    pub fn open(request: &DaemonRequest, uid: Option<u32>) -> Result<Self, DaemonError> {
+        let mut files = Vec::new();
+        for (path, path_use) in request_paths(request) {
+            let file = match path_use {
+                PathUse::Read => owned_file::open_read(path, uid).map(RequestFile::Read),
+                PathUse::Create => FileTarget::open(path, uid).map(RequestFile::Target),
+                PathUse::Refer => continue,
+            };
+            let file = file.map_err(|e| match e.kind() {
+                std::io::ErrorKind::PermissionDenied => not_owned(path),
+                // A symlink which is not followed
+                _ if e.raw_os_error() == Some(nix::libc::ELOOP) => not_owned(path),
+                _ => {
+                    DaemonError::InvalidArgument(format!("could not open {}: {e}", path.display()))
+                }
+            })?;
+            files.push((path.to_path_buf(), file));
+        }
+
+        Ok(RequestFiles(files))
+    }
+
+    /// The opened file at the path, for reading
+    pub fn take_read(&mut self, path: &Path) -> Result<File, DaemonError> {
+        match self.take(path) {
+            Some(RequestFile::Read(file)) => Ok(file),
+            _ => Err(not_opened(path)),
+        }
+    }
+
+    /// The opened directory of the file at the path, for writing it
+    pub fn take_target(&mut self, path: &Path) -> Result<FileTarget, DaemonError> {
+        match self.take(path) {
+            Some(RequestFile::Target(target)) => Ok(target),
+            _ => Err(not_opened(path)),
+        }
+    }
+
+    fn take(&mut self, path: &Path) -> Option<RequestFile> {
+        let index = self.0.iter().position(|(p, _)| p == path)?;
+        Some(self.0.swap_remove(index).1)
+    }
+}
+
+/// How the daemon uses a path of the request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PathUse {
+    /// The file is read by the handler
+    Read,
+    /// The file is created or replaced by the handler
+    Create,
+    /// The path is kept and opened later, which checks its owner again
+    Refer,
+}
+
+/// The paths read or written by the daemon for the request
+fn request_paths(request: &DaemonRequest) -> Vec<(&Path, PathUse)> {
+    match request {
+        DaemonRequest::ProvideFile { path, .. }
+        | DaemonRequest::PublishFile { path, .. }
+        | DaemonRequest::ImportVaultSnapshot { path, .. } => vec![(path, PathUse::Read)],
+        DaemonRequest::ExportVaultSnapshot { path, .. } => vec![(path, PathUse::Create)],
+        DaemonRequest::PublishFiles { paths, .. } => {
+            paths.iter().map(|p| (p.as_path(), PathUse::Read)).collect()
+        }
+        DaemonRequest::AddWatchDir { dir, .. } => vec![(&dir.path, PathUse::Refer)],
+        DaemonRequest::OverwriteNodeConfig { new_cfg, .. }
+        | DaemonRequest::ReloadNodeConfig {
+            new_cfg: Some(new_cfg),
+            ..
+        } => new_cfg
+            .journal_path
+            .iter()
+            .map(|p| p.as_path())
+            .chain(new_cfg.watch_dirs.iter().map(|d| d.path.as_path()))
+            .map(|p| (p, PathUse::Refer))
+            .collect(),
+        _ => vec![],
+    }
+}
+
+fn not_owned(path: &Path) -> DaemonError {
+    DaemonError::PermissionDenied(format!("{} is not owned by the user", path.display()))
+}
+
+fn not_opened(path: &Path) -> DaemonError {
+    DaemonError::Other(format!("{} was not opened for the request", path.display()))
+}
+
+/// Whether the user owns the path, or its directory if it doesn't exist yet. The
+/// owner is read from the opened file, so the path can't be swapped in between, and
+/// a symlink is not followed, the user may own the link but not its target
+async fn owns_path(uid: u32, path: &Path) -> bool {
+    let file = match open_path(path).await {
+        Ok(file) => Ok(file),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => match path.parent() {
+            Some(dir) => open_path(dir).await,
+            None => return false,
+        },
+        Err(e) => Err(e),
+    };
+    let Ok(file) = file else {
+        return false;
+    };
+    file.metadata()
+        .await
+        .is_ok_and(|metadata| metadata.uid() == uid)
+}
+
+/// Opens the path only to refer to it, neither reading nor writing it
+async fn open_path(path: &Path) -> std::io::Result<tokio::fs::File> {
+    tokio::fs::OpenOptions::new()
+        .read(true)
+        .custom_flags(nix::libc::O_PATH | nix::libc::O_NOFOLLOW | nix::libc::O_CLOEXEC)
+        .open(path)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn permission_test() {
+        let mut roles = RolesConfig::default();
+        roles.users.insert("1002".to_string(), Role::Observer);
+        assert!(Permission::of_uid(1000, 1000, &roles).is_admin());
+        assert!(Permission::of_uid(0, 1000, &roles).is_admin());
+
+        let user = Permission::of_uid(1001, 1000, &roles);
+        let start = DaemonRequest::StartNode {
+            node_name: "node".to_string(),
+        };
+        assert!(user.allows(&start, Some(1001)).is_ok());
+        assert!(user.allows(&start, Some(1000)).is_err());
+        assert!(user
+            .with_role(Role::Admin)
+            .allows(&start, Some(1000))
+            .is_ok());
+        assert!(user
+            .allows(
+                &DaemonRequest::SetLogLevel {
+                    target: "liberum_core".to_string(),
+                    level: "debug".to_string(),
+                },
+                None
+            )
+            .is_err());
+        assert!(!user.can_see(Some(1000)));
+
+        let observer = Permission::of_uid(1002, 1000, &roles);
+        assert!(observer.allows(&DaemonRequest::ListNodes, None).is_ok());
+        assert!(observer.allows(&start, Some(1002)).is_err());
+    }
+
+    #[tokio::test]
+    async fn owns_path_test() {
+        let tmp_dir = tempdir::TempDir::new("liberum_tests").unwrap();
+        let uid = nix::unistd::geteuid().as_raw();
+        let file = tmp_dir.path().join("file");
+        tokio::fs::write(&file, "abc").await.unwrap();
+        assert!(owns_path(uid, &file).await);
+        assert!(owns_path(uid, &tmp_dir.path().join("missing")).await);
+        assert!(!owns_path(uid + 1, &file).await);
+
+        let link = tmp_dir.path().join("link");
+        tokio::fs::symlink(&file, &link).await.unwrap();
+        assert!(!owns_path(uid, &link).await);
+    }
+
+    #[test]
+    fn request_files_test() {
+        let tmp_dir = tempdir::TempDir::new("liberum_tests").unwrap();
+        let uid = nix::unistd::geteuid().as_raw();
+        let file = tmp_dir.path().join("file");
+        std::fs::write(&file, "abc").unwrap();
+        let link = tmp_dir.path().join("link");
+        std::os::unix::fs::symlink(&file, &link).unwrap();
+        let publish = |path: &Path| DaemonRequest::PublishFile {
+            node_name: "node".to_string(),
+            path: path.to_path_buf(),
+            access: Default::default(),
+            previous: None,
+        };
+
+        let mut files = RequestFiles::open(&publish(&file), Some(uid)).unwrap();
+        assert!(files.take_read(&file).is_ok());
+        assert!(files.take_read(&file).is_err());
+        assert!(matches!(
+            RequestFiles::open(&publish(&link), Some(uid)),
+            Err(DaemonError::PermissionDenied(_))
+        ));
+        assert!(RequestFiles::open(&publish(&link), None).is_ok());
+
+        let export = DaemonRequest::ExportVaultSnapshot {
+            node_name: "node".to_string(),
+            path: tmp_dir.path().join("snapshot"),
+        };
+        let mut files = RequestFiles::open(&export, Some(uid)).unwrap();
+        assert!(files.take_target(&tmp_dir.path().join("snapshot")).is_ok());
+        assert!(RequestFiles::open(&export, Some(uid + 1)).is_err());
+    }
+}
@@ -0,0 +1,236 @@
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+use tokio::io::AsyncWriteExt;
+use uuid::Uuid;
+
+///! The module contains the crash-safe writes of the files. The data goes to a
+///! temporary file next to the target, which is synced to the disk and renamed over
+///! the target, then the directory is synced too. After a crash or a power loss the
+///! target has either the old or the new content, never a part of it.
+///!
+///! The temporary files left by an interrupted write are removed with
+///! `remove_orphans` when the daemon starts.
+
+/// The suffix of the temporary files
+pub const TEMP_FILE_SUFFIX: &str = ".liberum-tmp";
+
+/// Replaces the content of the file
+pub async fn write(path: &Path, data: impl AsRef<[u8]>) -> Result<()> {
+    write_with(&Disk, path, data.as_ref()).await
+}
+
+/// Moves the written temporary file over the target once it's on the disk. The
+/// temporary file is removed if it fails
+pub async fn persist(temp_path: &Path, path: &Path) -> Result<()> {
+    persist_with(&Disk, temp_path, path).await
+}
+
+/// A temporary file in the directory of the target, for the data written in parts
+/// and then persisted
+pub fn temp_path(path: &Path) -> PathBuf {
+    path.with_file_name(temp_file_name(path.file_name().unwrap_or_default()))
+}
+
+/// The name of a temporary file for the file with the name
+pub fn temp_file_name(file_name: &OsStr) -> OsString {
+    let file_name = file_name.to_string_lossy();
+    format!(".{file_name}.{}{TEMP_FILE_SUFFIX}", Uuid::new_v4()).into()
+}
+
+/// Removes the temporary files left in the directory by the writes interrupted by a
+/// crash. Returns how many were removed
+pub async fn remove_orphans(dir: &Path) -> Result<usize> {
+    let mut removed = 0;
+    let mut entries = tokio::fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let is_temp = entry
+            .file_name()
+            .to_str()
+            .is_some_and(|name| name.ends_with(TEMP_FILE_SUFFIX));
+        if is_temp && entry.file_type().await?.is_file() {
+            tokio::fs::remove_file(entry.path()).await?;
+            removed += 1;
+        }
+    }
+
+    Ok(removed)
+}
+
+/// The operations of the writes, the tests inject the faults in them
+trait Filesystem {
+    async fn write_synced(&self, path: &Path, data: &[u8]) -> io::Result<()>;
+    async fn sync_file(&self, path: &Path) -> io::Result<()>;
+    async fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+    async fn sync_dir(&self, path: &Path) -> io::Result<()>;
+    async fn remove_file(&self, path: &Path) -> io::Result<()>;
+}
+
+struct Disk;
+
+impl Filesystem for Disk {
+    async fn write_synced(&self, path: &Path, data: &[u8]) -> io::Result<()> {
+        let mut file = tokio::fs::File::create(path).await?;
+        file.write_all(data).await?;
+        file.sync_all().await
+    }
+
+    async fn sync_file(&self, path: &Path) -> io::Result<()> {
+        tokio::fs::File::open(path).await?.sync_all().await
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        tokio::fs::rename(from, to).await
+    }
+
+    async fn sync_dir(&self, path: &Path) -> io::Result<()> {
+        tokio::fs::File::open(path).await?.sync_all().await
+    }
+
+    async fn remove_file(&self, path: &Path) -> io::Result<()> {
+        tokio::fs::remove_file(path).await
+    }
+}
+
+async fn write_with(fs: &impl Filesystem, path: &Path, data: &[u8]) -> Result<()> {
+    let temp_path = temp_path(path);
+    if let Err(e) = fs.write_synced(&temp_path, data).await {
+        let _ = fs.remove_file(&temp_path).await;
+        return Err(anyhow!(e).context(format!("could not write {}", path.display())));
+    }
+
+    rename_synced(fs, &temp_path, path).await
+}
+
+async fn persist_with(fs: &impl Filesystem, temp_path: &Path, path: &Path) -> Result<()> {
+    if let Err(e) = fs.sync_file(temp_path).await {
+        let _ = fs.remove_file(temp_path).await;
+        return Err(anyhow!(e).context(format!("could not write {}", path.display())));
+    }
+
+    rename_synced(fs, temp_path, path).await
+}
+
+/// Renames the synced temporary file over the target. The directory is synced, so
+/// the rename itself survives a crash
+async fn rename_synced(fs: &impl Filesystem, temp_path: &Path, path: &Path) -> Result<()> {
+    if let Err(e) = fs.rename(temp_path, path).await {
+        let _ = fs.remove_file(temp_path).await;
+        return Err(anyhow!(e).context(format!("could not replace {}", path.display())));
+    }
+
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    fs.sync_dir(dir)
+        .await
+        .with_context(|| format!("could not sync {}", dir.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[derive(Clone, Copy, PartialEq, Debug)]
+    enum Step {
+        Write,
+        SyncFile,
+        Rename,
+        SyncDir,
+    }
+
+    /// The disk failing at one step, a failed write leaves a part of the data
+    struct FaultyDisk {
+        fail_at: Step,
+    }
+
+    impl FaultyDisk {
+        fn check(&self, step: Step) -> io::Result<()> {
+            match self.fail_at == step {
+                true => Err(io::Error::other(format!("injected fault at {step:?}"))),
+                false => Ok(()),
+            }
+        }
+    }
+
+    impl Filesystem for FaultyDisk {
+        async fn write_synced(&self, path: &Path, data: &[u8]) -> io::Result<()> {
+            if self.fail_at == Step::Write {
+                tokio::fs::write(path, &data[..data.len() / 2]).await?;
+            }
+            self.check(Step::Write)?;
+            Disk.write_synced(path, data).await
+        }
+
+        async fn sync_file(&self, path: &Path) -> io::Result<()> {
+            self.check(Step::SyncFile)?;
+            Disk.sync_file(path).await
+        }
+
+        async fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+            self.check(Step::Rename)?;
+            Disk.rename(from, to).await
+        }
+
+        async fn sync_dir(&self, path: &Path) -> io::Result<()> {
+            self.check(Step::SyncDir)?;
+            Disk.sync_dir(path).await
+        }
+
+        async fn remove_file(&self, path: &Path) -> io::Result<()> {
+            Disk.remove_file(path).await
+        }
+    }
+
+    fn file_count(dir: &Path) -> usize {
+        std::fs::read_dir(dir).unwrap().count()
+    }
+
+    #[tokio::test]
+    async fn fault_test() {
+        let tmp_dir = TempDir::new("liberum_tests").unwrap();
+        let path = tmp_dir.path().join("file");
+        write(&path, "old").await.unwrap();
+
+        for fail_at in [Step::Write, Step::Rename] {
+            let disk = FaultyDisk { fail_at };
+            assert!(write_with(&disk, &path, b"new content").await.is_err());
+            assert_eq!(std::fs::read_to_string(&path).unwrap(), "old");
+            assert_eq!(file_count(tmp_dir.path()), 1);
+        }
+
+        // The file is already replaced, only its durability is not known
+        let disk = FaultyDisk {
+            fail_at: Step::SyncDir,
+        };
+        assert!(write_with(&disk, &path, b"new content").await.is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "new content");
+
+        let temp_path = temp_path(&path);
+        std::fs::write(&temp_path, "newer").unwrap();
+        let disk = FaultyDisk {
+            fail_at: Step::SyncFile,
+        };
+        assert!(persist_with(&disk, &temp_path, &path).await.is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "new content");
+        assert!(!temp_path.exists());
+    }
+
+    #[tokio::test]
+    async fn remove_orphans_test() {
+        let tmp_dir = TempDir::new("liberum_tests").unwrap();
+        let path = tmp_dir.path().join("file");
+        write(&path, "data").await.unwrap();
+        // Left by a crash in the middle of a write
+        std::fs::write(temp_path(&path), "da").unwrap();
+
+        assert_eq!(remove_orphans(tmp_dir.path()).await.unwrap(), 1);
+        assert_eq!(file_count(tmp_dir.path()), 1);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "data");
+    }
+}
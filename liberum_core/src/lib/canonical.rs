@@ -0,0 +1,181 @@
+use anyhow::{anyhow, Result};
+use cbor4ii::core::dec::Decode;
+use cbor4ii::core::enc::Encode;
+use cbor4ii::core::utils::{BufWriter, SliceReader};
+use cbor4ii::core::Value;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+///! The module contains the canonical serialization of the payloads of the typed
+///! objects. The payloads are encoded with CBOR, which describes itself, so other
+///! implementations can read them without knowing the layout of the Rust structs.
+///!
+///! The encoding is deterministic, following the core deterministic encoding of
+///! RFC 8949: the integers and lengths are the shortest possible, the lengths are
+///! always definite and the keys of the maps are sorted by their encoded bytes. The
+///! same value is always encoded to the same bytes, so it has the same hash in
+///! every implementation.
+///!
+///! The payloads start with the self-described CBOR tag, which tells them apart
+///! from the payloads encoded with bincode before, still found in the vaults.
+
+/// CBOR tag 55799, the magic number of the self-described CBOR
+pub const SELF_DESCRIBED_TAG: [u8; 3] = [0xd9, 0xd9, 0xf7];
+const SELF_DESCRIBED: u64 = 55799;
+
+/// Encodes the value in the canonical form
+pub fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>> {
+    let bytes = cbor4ii::serde::to_vec(Vec::new(), value).map_err(|e| anyhow!("{e:?}"))?;
+    let value = canonicalize(decode_value(&bytes)?)?;
+    encode_value(&Value::Tag(SELF_DESCRIBED, Box::new(value)))
+}
+
+/// Decodes the value encoded with [`encode`]
+pub fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+    let Some(payload) = bytes.strip_prefix(&SELF_DESCRIBED_TAG) else {
+        return Err(anyhow!("Not a canonical payload"));
+    };
+    cbor4ii::serde::from_slice(payload).map_err(|e| anyhow!("{e:?}"))
+}
+
+/// Whether the bytes are a payload in the canonical form, not just tagged
+pub fn is_canonical(bytes: &[u8]) -> bool {
+    if !bytes.starts_with(&SELF_DESCRIBED_TAG) {
+        return false;
+    }
+    decode_value(bytes)
+        .and_then(canonicalize)
+        .and_then(|value| encode_value(&value))
+        .is_ok_and(|canonical| canonical == bytes)
+}
+
+/// The canonical bytes of the whole typed object, hashed for its ID. The object
+/// is an array of the type UUID and the payload, both as byte strings
+pub fn encode_object(uuid: &uuid::Uuid, data: &[u8]) -> Result<Vec<u8>> {
+    let mut bytes = object_prefix(uuid, data.len() as u64);
+    bytes.extend_from_slice(data);
+    Ok(bytes)
+}
+
+/// The bytes of the canonical encoding of the object before its payload, so the
+/// object can be hashed while the payload is received
+pub fn object_prefix(uuid: &uuid::Uuid, data_len: u64) -> Vec<u8> {
+    // An array of 2 items and a byte string of 16 bytes
+    let mut prefix = vec![0x82, 0x50];
+    prefix.extend_from_slice(uuid.as_bytes());
+    // The header of the byte string of the payload, with the shortest length
+    match data_len {
+        0..=23 => prefix.push(0x40 | data_len as u8),
+        24..=0xff => prefix.extend_from_slice(&[0x58, data_len as u8]),
+        0x100..=0xffff => {
+            prefix.push(0x59);
+            prefix.extend_from_slice(&(data_len as u16).to_be_bytes());
+        }
+        0x10000..=0xffff_ffff => {
+            prefix.push(0x5a);
+            prefix.extend_from_slice(&(data_len as u32).to_be_bytes());
+        }
+        _ => {
+            prefix.push(0x5b);
+            prefix.extend_from_slice(&data_len.to_be_bytes());
+        }
+    }
+    prefix
+}
+
+/// Sorts the keys of all the maps in the value, the duplicated keys are an error
+fn canonicalize(value: Value) -> Result<Value> {
+    Ok(match value {
+        Value::Array(items) => {
+            Value::Array(items.into_iter().map(canonicalize).collect::<Result<_>>()?)
+        }
+        Value::Map(entries) => {
+            let mut entries = entries
+                .into_iter()
+                .map(|(key, value)| {
+                    let key = canonicalize(key)?;
+                    Ok((encode_value(&key)?, key, canonicalize(value)?))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            if entries.windows(2).any(|pair| pair[0].0 == pair[1].0) {
+                return Err(anyhow!("Map has a duplicated key"));
+            }
+            Value::Map(entries.into_iter().map(|(_, k, v)| (k, v)).collect())
+        }
+        Value::Tag(tag, value) => Value::Tag(tag, Box::new(canonicalize(*value)?)),
+        value => value,
+    })
+}
+
+fn encode_value(value: &Value) -> Result<Vec<u8>> {
+    let mut writer = BufWriter::new(Vec::new());
+    value.encode(&mut writer).map_err(|e| anyhow!("{e:?}"))?;
+    Ok(writer.into_inner())
+}
+
+fn decode_value(bytes: &[u8]) -> Result<Value> {
+    let mut reader = SliceReader::new(bytes);
+    Value::decode(&mut reader).map_err(|e| anyhow!("{e:?}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use std::collections::HashMap;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Ab {
+        a: u64,
+        b: String,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Ba {
+        b: String,
+        a: u64,
+    }
+
+    #[test]
+    fn canonical_test() {
+        let ab = Ab {
+            a: 1,
+            b: "b".to_string(),
+        };
+        let bytes = encode(&ab).unwrap();
+        assert!(bytes.starts_with(&SELF_DESCRIBED_TAG));
+        assert!(is_canonical(&bytes));
+        assert_eq!(decode::<Ab>(&bytes).unwrap(), ab);
+
+        // The order of the fields doesn't change the bytes
+        let ba = Ba {
+            b: "b".to_string(),
+            a: 1,
+        };
+        assert_eq!(encode(&ba).unwrap(), bytes);
+        assert_eq!(decode::<Ba>(&bytes).unwrap(), ba);
+
+        let map: HashMap<u32, u32> = (0..100).map(|i| (i, i)).collect();
+        let reversed: HashMap<u32, u32> = (0..100).rev().map(|i| (i, i)).collect();
+        assert_eq!(encode(&map).unwrap(), encode(&reversed).unwrap());
+
+        let not_sorted = cbor4ii::serde::to_vec(SELF_DESCRIBED_TAG.to_vec(), &ba).unwrap();
+        assert!(!is_canonical(&not_sorted));
+        assert!(!is_canonical(&bincode::serialize(&ab).unwrap()));
+        assert!(decode::<Ab>(&bincode::serialize(&ab).unwrap()).is_err());
+
+        let uuid = uuid::Uuid::from_u128(7);
+        for len in [0, 23, 24, 255, 256, 70000] {
+            let data = vec![1; len];
+            let value = Value::Array(vec![
+                Value::Bytes(uuid.as_bytes().to_vec()),
+                Value::Bytes(data.clone()),
+            ]);
+            assert_eq!(
+                encode_object(&uuid, &data).unwrap(),
+                encode_value(&value).unwrap()
+            );
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use std::ops::Range;
+
+///! The module splits the files into chunks at the boundaries found by a rolling
+///! hash of their content, so an edit changes only the chunks around it and the
+///! revisions of a file share all the other ones. With chunks of a fixed size, a
+///! single byte inserted at the beginning would change every chunk.
+///!
+///! The boundaries are found with the gear hash, whose top bits depend on the last
+///! 64 bytes. A boundary is where the top 16 bits are zero, so the chunks are about
+///! 64 KiB on average, and it's looked for only between the minimal and the maximal
+///! size of a chunk. The gear table is the same on all the nodes, so the same file
+///! is always split the same way.
+
+pub const MIN_CHUNK_SIZE: usize = 16 * 1024;
+pub const MAX_CHUNK_SIZE: usize = 256 * 1024;
+const BOUNDARY_MASK: u64 = 0xffff << 48;
+
+/// The random numbers for the bytes, from splitmix64 with a fixed seed
+const GEAR: [u64; 256] = gear_table();
+
+const fn gear_table() -> [u64; 256] {
+    let mut table = [0; 256];
+    let mut state: u64 = 0x6c69_6265_7275_6d00;
+    let mut i = 0;
+    while i < table.len() {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+}
+
+/// The ranges of the chunks of the data, in order and covering all of it
+pub fn chunk_ranges(data: &[u8]) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    while start < data.len() {
+        let end = start + chunk_len(&data[start..]);
+        ranges.push(start..end);
+        start = end;
+    }
+    ranges
+}
+
+/// The length of the first chunk of the data
+fn chunk_len(data: &[u8]) -> usize {
+    if data.len() <= MIN_CHUNK_SIZE {
+        return data.len();
+    }
+    let end = data.len().min(MAX_CHUNK_SIZE);
+    let mut hash: u64 = 0;
+    for (i, byte) in data[..end].iter().enumerate() {
+        hash = (hash << 1).wrapping_add(GEAR[*byte as usize]);
+        if i >= MIN_CHUNK_SIZE && hash & BOUNDARY_MASK == 0 {
+            return i + 1;
+        }
+    }
+    end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use std::collections::HashSet;
+
+    #[test]
+    fn chunk_ranges_test() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let data: Vec<u8> = (0..4 * 1024 * 1024).map(|_| rng.gen()).collect();
+        let ranges = chunk_ranges(&data);
+        assert_eq!(ranges.first().unwrap().start, 0);
+        assert_eq!(ranges.last().unwrap().end, data.len());
+        assert!(ranges.windows(2).all(|pair| pair[0].end == pair[1].start));
+        assert!(ranges[..ranges.len() - 1]
+            .iter()
+            .all(|range| (MIN_CHUNK_SIZE..=MAX_CHUNK_SIZE).contains(&range.len())));
+
+        // Only the chunks around the insertion change
+        let mut edited = data.clone();
+        edited.splice(1_000_000..1_000_000, *b"inserted");
+        let chunks = |data: &[u8]| -> HashSet<Vec<u8>> {
+            chunk_ranges(data)
+                .into_iter()
+                .map(|range| data[range].to_vec())
+                .collect()
+        };
+        let (old, new) = (chunks(&data), chunks(&edited));
+        assert!(new.difference(&old).count() <= 2);
+        assert!(chunk_ranges(&[]).is_empty());
+    }
+}
@@ -18,7 +18,7 @@ where
     type Error = std::io::Error;
 
     fn encode(&mut self, item: U, dst: &mut BytesMut) -> Result<(), Self::Error> {
-        let serialized: Vec<u8> = to_allocvec(&item).unwrap();
+        let serialized: Vec<u8> = to_allocvec(&item).map_err(invalid_data)?;
         self.framing_codec.encode(Bytes::from(serialized), dst)
     }
 }
@@ -35,12 +35,17 @@ where
         let result = self.framing_codec.decode(src)?;
 
         match result {
-            Some(data) => Ok(Some(from_bytes::<Self::Item>(&data).unwrap())),
+            // A malformed message fails the connection, not the daemon
+            Some(data) => Ok(Some(from_bytes::<Self::Item>(&data).map_err(invalid_data)?)),
             None => Ok(None),
         }
     }
 }
 
+fn invalid_data(e: postcard::Error) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, e)
+}
+
 impl<U, V> AsymmetricMessageCodec<U, V>
 where
     U: Serialize + DeserializeOwned,
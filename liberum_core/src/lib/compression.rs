@@ -0,0 +1,107 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+///! The module contains the compression of the payloads of the objects, used when
+///! they are transferred and when they are stored in the vault. The data is compressed
+///! only if it gets smaller, so the incompressible payloads, e.g. images or archives,
+///! are kept as they are and cost nothing more than the tag of the algorithm.
+
+/// The data shorter than this is never compressed, the frame of zstd would eat
+/// the savings
+const MIN_COMPRESSED_SIZE: usize = 64;
+
+/// The algorithm the data is compressed with
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Compression {
+    #[default]
+    None,
+    Zstd,
+}
+
+impl Compression {
+    /// The tag of the algorithm, stored with the data
+    pub fn tag(self) -> u8 {
+        match self {
+            Compression::None => 0,
+            Compression::Zstd => 1,
+        }
+    }
+
+    pub fn from_tag(tag: u8) -> Result<Compression> {
+        match tag {
+            0 => Ok(Compression::None),
+            1 => Ok(Compression::Zstd),
+            tag => Err(anyhow!("Unknown compression {tag}")),
+        }
+    }
+}
+
+/// The data with the algorithm it is compressed with
+#[derive(Debug, Clone, PartialEq)]
+pub struct Compressed {
+    pub compression: Compression,
+    pub data: Vec<u8>,
+}
+
+impl Compressed {
+    /// Compresses the data with zstd at the level, unless the level is None or the
+    /// compressed data is not smaller
+    pub fn new(data: Vec<u8>, level: Option<i32>) -> Compressed {
+        let compressed = level
+            .filter(|_| data.len() >= MIN_COMPRESSED_SIZE)
+            .and_then(|level| zstd::bulk::compress(&data, level).ok())
+            .filter(|compressed| compressed.len() < data.len());
+        match compressed {
+            Some(compressed) => Compressed {
+                compression: Compression::Zstd,
+                data: compressed,
+            },
+            None => Compressed {
+                compression: Compression::None,
+                data,
+            },
+        }
+    }
+
+    /// The original data, longer data than the limit is an error, so a small
+    /// malicious frame can't expand to a huge buffer
+    pub fn decompress(self, limit: usize) -> Result<Vec<u8>> {
+        let data = match self.compression {
+            Compression::None => self.data,
+            Compression::Zstd => zstd::bulk::decompress(&self.data, limit)?,
+        };
+        match data.len() <= limit {
+            true => Ok(data),
+            false => Err(anyhow!("Data of {} bytes is over the limit", data.len())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compression_test() {
+        let text = "The quick brown fox jumps over the lazy dog. ".repeat(1000);
+        let compressed = Compressed::new(text.clone().into_bytes(), Some(3));
+        assert_eq!(compressed.compression, Compression::Zstd);
+        assert!(compressed.data.len() * 10 < text.len());
+        assert_eq!(
+            compressed.clone().decompress(text.len()).unwrap(),
+            text.as_bytes()
+        );
+        assert!(compressed.decompress(text.len() - 1).is_err());
+
+        // The random data doesn't get any bigger
+        let random: Vec<u8> = (0..10000).map(|_| rand::random()).collect();
+        let compressed = Compressed::new(random.clone(), Some(3));
+        assert_eq!(compressed.compression, Compression::None);
+        assert_eq!(compressed.data, random);
+
+        let disabled = Compressed::new(text.clone().into_bytes(), None);
+        assert_eq!(disabled.compression, Compression::None);
+        assert!(Compression::from_tag(Compression::Zstd.tag()).is_ok());
+        assert!(Compression::from_tag(7).is_err());
+    }
+}
@@ -0,0 +1,277 @@
+use std::collections::{BTreeMap, HashMap};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use strum_macros::{Display, EnumString};
+
+use crate::node_config::CompressionConfig;
+use crate::DaemonRequest;
+
+///! The module contains the config of the daemon itself, shared by all its nodes.
+///! It's read from `~/.liberum-neto/daemon.toml` when the daemon starts, without the
+///! file the defaults are used. The settings from the file are then overridden by
+///! the environment variables, and those by the flags of the daemon.
+
+/// The socket the clients connect to, unless `SOCKET_PATH_ENV` is set
+pub const DEFAULT_SOCKET_PATH: &str = "/tmp/liberum-core/liberum-core-socket";
+pub const SOCKET_PATH_ENV: &str = "LIBERUM_SOCKET";
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DaemonConfig {
+    /// The directory of the nodes, `~/.liberum-neto` if not set
+    #[serde(default)]
+    pub state_dir: Option<PathBuf>,
+    /// The pid file and the output of the daemon go to the same directory
+    #[serde(default = "default_socket_path")]
+    pub socket_path: PathBuf,
+    /// The permissions of the socket, every connection is still checked against
+    /// the roles
+    #[serde(default = "default_socket_mode")]
+    pub socket_mode: u32,
+    #[serde(default = "default_log_level")]
+    pub log_level: String,
+    #[serde(default)]
+    pub metrics: MetricsConfig,
+    /// The compression of the vaults of the new nodes
+    #[serde(default)]
+    pub vault: CompressionConfig,
+    /// Start the nodes with `autostart` in their configs when the daemon starts
+    #[serde(default = "default_autostart")]
+    pub autostart: bool,
+    #[serde(default)]
+    pub roles: RolesConfig,
+    /// The named groups of nodes managed together, e.g. `testnet = ["a", "b"]`
+    #[serde(default)]
+    pub groups: BTreeMap<String, Vec<String>>,
+}
+
+/// The HTTP endpoint with the metrics of the daemon in the Prometheus text format
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct MetricsConfig {
+    pub enabled: bool,
+    pub bind_address: SocketAddr,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bind_address: SocketAddr::from(([127, 0, 0, 1], 9464)),
+        }
+    }
+}
+
+/// The roles of the connections to the daemon. The user running the daemon and root
+/// are always admins, the other users get the role of their UID from `users`, or the
+/// default one. A connection may switch to the role of a token with `Authenticate`
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct RolesConfig {
+    #[serde(default)]
+    pub default_role: Role,
+    /// The roles by the UIDs of the users, e.g. `"1001" = "observer"`
+    #[serde(default)]
+    pub users: HashMap<String, Role>,
+    /// The roles by the SHA-256 hashes of the tokens in hex, e.g. the output of
+    /// `echo -n <token> | sha256sum`, so the config doesn't reveal the tokens
+    #[serde(default)]
+    pub tokens: HashMap<String, Role>,
+}
+
+#[derive(
+    Debug,
+    Serialize,
+    Deserialize,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Display,
+    EnumString,
+)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase", ascii_case_insensitive)]
+pub enum Role {
+    /// Only looks at the nodes, with the read-only requests
+    Observer,
+    /// Controls the nodes, but not the daemon
+    #[default]
+    Operator,
+    /// Controls the daemon and all the nodes, no matter who owns them
+    Admin,
+}
+
+fn default_socket_path() -> PathBuf {
+    PathBuf::from(DEFAULT_SOCKET_PATH)
+}
+
+fn default_socket_mode() -> u32 {
+    0o666
+}
+
+fn default_log_level() -> String {
+    "debug".to_string()
+}
+
+fn default_autostart() -> bool {
+    true
+}
+
+impl Default for DaemonConfig {
+    fn default() -> Self {
+        Self {
+            state_dir: None,
+            socket_path: default_socket_path(),
+            socket_mode: default_socket_mode(),
+            log_level: default_log_level(),
+            metrics: MetricsConfig::default(),
+            vault: CompressionConfig::default(),
+            autostart: default_autostart(),
+            roles: RolesConfig::default(),
+            groups: BTreeMap::new(),
+        }
+    }
+}
+
+impl DaemonConfig {
+    pub const FILE_NAME: &'static str = "daemon.toml";
+
+    pub fn default_path() -> Result<PathBuf> {
+        let home_dir_path = homedir::my_home()?.ok_or(anyhow!("no home directory"))?;
+        Ok(home_dir_path.join(".liberum-neto").join(Self::FILE_NAME))
+    }
+
+    /// Loads the config from the file, the default one if the file doesn't exist.
+    /// Read before the logging is set up, so the errors are only returned
+    pub fn load(path: &Path) -> Result<DaemonConfig> {
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(DaemonConfig::default())
+            }
+            Err(e) => return Err(anyhow!(e).context("could not read daemon config")),
+        };
+
+        toml::from_str(&content).map_err(|e| anyhow!(e).context("could not parse daemon config"))
+    }
+
+    /// The config as sent to the clients, without the tokens
+    pub fn redacted(&self) -> DaemonConfig {
+        let mut config = self.clone();
+        config.roles.tokens.clear();
+        config
+    }
+}
+
+/// The socket the clients connect to
+pub fn client_socket_path() -> PathBuf {
+    std::env::var_os(SOCKET_PATH_ENV)
+        .map(PathBuf::from)
+        .unwrap_or_else(default_socket_path)
+}
+
+impl Role {
+    pub fn allows(&self, request: &DaemonRequest) -> bool {
+        match self {
+            Role::Admin => true,
+            Role::Operator => !request.is_admin_only(),
+            Role::Observer => request.is_read_only() && !request.is_admin_only(),
+        }
+    }
+}
+
+impl RolesConfig {
+    pub fn role_of_user(&self, uid: u32) -> Role {
+        self.users
+            .get(&uid.to_string())
+            .copied()
+            .unwrap_or(self.default_role)
+    }
+
+    /// The role of the token, all the hashes are compared in constant time so the
+    /// timing doesn't tell how close a guess is
+    pub fn role_of_token(&self, token: &str) -> Option<Role> {
+        let hash = hex::encode(Sha256::digest(token.as_bytes()));
+        self.tokens.iter().fold(None, |found, (token_hash, role)| {
+            let matches =
+                constant_time_eq(token_hash.to_ascii_lowercase().as_bytes(), hash.as_bytes());
+            found.or(matches.then_some(*role))
+        })
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn load_test() {
+        let config: DaemonConfig = toml::from_str(
+            r#"
+            log_level = "info"
+            autostart = false
+
+            [metrics]
+            enabled = true
+            bind_address = "0.0.0.0:9464"
+
+            [groups]
+            testnet = ["a", "b"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.log_level, "info");
+        assert_eq!(config.groups["testnet"], ["a", "b"]);
+        assert!(!config.autostart && config.metrics.enabled);
+        assert_eq!(config.socket_path, PathBuf::from(DEFAULT_SOCKET_PATH));
+        assert_eq!(
+            toml::from_str::<DaemonConfig>("").unwrap(),
+            DaemonConfig::default()
+        );
+    }
+
+    #[test]
+    fn roles_test() {
+        let config: DaemonConfig = toml::from_str(
+            r#"
+            [roles]
+            default_role = "observer"
+            users = { "1001" = "operator" }
+            # The hash of "secret"
+            tokens = { "2bb80d537b1da3e38bd30361aa855686bde0eacd7162fef6a25fe97bf527a25b" = "admin" }
+            "#,
+        )
+        .unwrap();
+        assert!(config.redacted().roles.tokens.is_empty());
+        let roles = config.roles;
+        assert_eq!(roles.role_of_user(1001), Role::Operator);
+        assert_eq!(roles.role_of_user(1002), Role::Observer);
+        assert_eq!(roles.role_of_token("secret"), Some(Role::Admin));
+        assert_eq!(roles.role_of_token("guess"), None);
+
+        let details = DaemonRequest::GetNodeDetails {
+            node_name: "node".to_string(),
+        };
+        let delete = DaemonRequest::DeleteObject {
+            node_name: "node".to_string(),
+            object_id: "id".to_string(),
+        };
+        assert!(Role::Observer.allows(&DaemonRequest::ListNodes));
+        assert!(Role::Observer.allows(&details));
+        assert!(!Role::Observer.allows(&delete));
+        assert!(Role::Operator.allows(&delete));
+        assert!(!Role::Operator.allows(&DaemonRequest::SetLogLevel {
+            target: "liberum_core".to_string(),
+            level: "debug".to_string(),
+        }));
+    }
+}
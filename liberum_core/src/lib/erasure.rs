@@ -0,0 +1,112 @@
+use anyhow::{bail, Result};
+use reed_solomon_erasure::galois_8::ReedSolomon;
+
+use crate::proto::MAX_OBJECT_SIZE;
+
+///! The module contains the Reed-Solomon erasure coding of the chunks of a file.
+///! The chunks are coded in groups of `data_shards` of them, every group gets
+///! `parity_shards` parity chunks, and any `data_shards` of the chunks of a group
+///! are enough to get the others back. The chunks of a group are padded with zeros
+///! to the longest one, the last group is filled up with the empty chunks, which
+///! are never published.
+
+/// Whether Reed-Solomon can code the groups with the numbers of the chunks
+pub fn is_valid(data_shards: usize, parity_shards: usize) -> bool {
+    data_shards > 0 && parity_shards > 0 && data_shards + parity_shards <= 256
+}
+
+/// The parity chunks of the group of at most `data_shards` chunks
+pub fn parity_shards(
+    chunks: &[&[u8]],
+    data_shards: usize,
+    parity_shards: usize,
+) -> Result<Vec<Vec<u8>>> {
+    if chunks.is_empty() || chunks.len() > data_shards {
+        bail!("A group has between 1 and {data_shards} chunks");
+    }
+    let size = chunks.iter().map(|chunk| chunk.len()).max().unwrap_or(0);
+    let coder = ReedSolomon::new(data_shards, parity_shards)?;
+    let mut shards: Vec<Vec<u8>> = (0..data_shards)
+        .map(|i| padded(chunks.get(i).copied().unwrap_or_default(), size))
+        .chain((0..parity_shards).map(|_| vec![0; size]))
+        .collect();
+    coder.encode(&mut shards)?;
+
+    Ok(shards.split_off(data_shards))
+}
+
+/// The chunks of the group with the missing ones made from the others and the
+/// parity chunks. The sizes are of all the chunks of the group, all the shards
+/// together can't be larger than an object
+pub fn reconstruct(
+    chunks: Vec<Option<Vec<u8>>>,
+    chunk_sizes: &[u64],
+    parity: Vec<Option<Vec<u8>>>,
+    data_shards: usize,
+) -> Result<Vec<Vec<u8>>> {
+    if chunks.len() != chunk_sizes.len() || chunks.len() > data_shards {
+        bail!("The sizes don't match the chunks of the group");
+    }
+    let size = chunk_sizes.iter().max().copied().unwrap_or(0);
+    let shard_count = (data_shards + parity.len()).max(1) as u64;
+    if size > MAX_OBJECT_SIZE / shard_count {
+        bail!("The chunks of the group are too large");
+    }
+    let size = size as usize;
+    let coder = ReedSolomon::new(data_shards, parity.len())?;
+    let count = chunks.len();
+    let mut shards: Vec<Option<Vec<u8>>> = chunks
+        .into_iter()
+        .map(|chunk| chunk.map(|chunk| padded(&chunk, size)))
+        .chain((count..data_shards).map(|_| Some(vec![0; size])))
+        .chain(
+            parity
+                .into_iter()
+                .map(|shard| shard.filter(|s| s.len() == size)),
+        )
+        .collect();
+    coder.reconstruct_data(&mut shards)?;
+
+    Ok(shards
+        .into_iter()
+        .zip(chunk_sizes)
+        .map(|(shard, chunk_size)| {
+            let mut chunk = shard.expect("All the data shards to be reconstructed");
+            chunk.truncate(*chunk_size as usize);
+            chunk
+        })
+        .collect())
+}
+
+fn padded(chunk: &[u8], size: usize) -> Vec<u8> {
+    let mut shard = chunk.to_vec();
+    shard.resize(size, 0);
+    shard
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reconstruct_test() {
+        let chunks: Vec<&[u8]> = vec![&b"first chunk"[..], b"second", b"the third chunk"];
+        let sizes: Vec<u64> = chunks.iter().map(|chunk| chunk.len() as u64).collect();
+        // The group has room for one more chunk
+        let parity = parity_shards(&chunks, 4, 2).unwrap();
+        assert_eq!(parity.len(), 2);
+
+        let received = vec![None, Some(chunks[1].to_vec()), None];
+        let parity: Vec<Option<Vec<u8>>> = parity.into_iter().map(Some).collect();
+        let reconstructed = reconstruct(received, &sizes, parity.clone(), 4).unwrap();
+        assert_eq!(reconstructed, chunks);
+
+        // Three chunks are missing, with only two parity chunks
+        let missing = vec![None, None, None];
+        assert!(reconstruct(missing, &sizes, parity.clone(), 4).is_err());
+        // The sizes come from the manifest, which may lie about them
+        let huge = vec![u64::MAX; 3];
+        assert!(reconstruct(vec![None, None, None], &huge, parity, 4).is_err());
+        assert!(!is_valid(200, 57));
+    }
+}
@@ -0,0 +1,191 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+use bytes::Bytes;
+use futures::{SinkExt, StreamExt};
+use postcard::{from_bytes, to_allocvec};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tokio::net::UnixStream;
+use tokio_util::codec::{Framed, FramedParts, LengthDelimitedCodec};
+use tracing::warn;
+
+use crate::codec::AsymmetricMessageCodec;
+
+///! The module contains the handshake opening every connection to the daemon. The
+///! requests and the responses are encoded by the positions of their variants, so a
+///! client built from other sources than the daemon may read them wrong. Before
+///! the first request both sides send their versions. A different protocol version
+///! closes the connection, a different daemon version only warns, the protocol is
+///! still the same.
+///!
+///! The hello itself is never changed, every version of the daemon and the clients
+///! can read it.
+
+/// The layout of the requests and the responses. Raised whenever they change in a
+/// way the older clients can't read, new variants at the end don't change it.
+/// Version 3 changed the node status, its rejected records and the routing table
+/// with its buckets
+pub const PROTOCOL_VERSION: u32 = 3;
+
+/// The version of the daemon and the clients built from the same sources
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Marks the hello, the clients older than the handshake send a request instead
+const MAGIC: [u8; 4] = *b"LBNT";
+
+/// How long the daemon waits for the hello of a new connection
+const HELLO_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The first message of both sides of the connection
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Hello {
+    magic: [u8; 4],
+    pub protocol_version: u32,
+    pub version: String,
+}
+
+impl Hello {
+    pub fn ours() -> Self {
+        Hello {
+            magic: MAGIC,
+            protocol_version: PROTOCOL_VERSION,
+            version: VERSION.to_string(),
+        }
+    }
+}
+
+/// Opens the connection of a client. Returns the hello of the daemon, the
+/// connection fails if the daemon speaks another protocol
+pub async fn open<U, V>(
+    socket: UnixStream,
+) -> Result<(Framed<UnixStream, AsymmetricMessageCodec<U, V>>, Hello)>
+where
+    U: Serialize + DeserializeOwned,
+    V: Serialize + DeserializeOwned,
+{
+    let mut framed = Framed::new(socket, LengthDelimitedCodec::new());
+    send_hello(&mut framed).await?;
+    let daemon = receive_hello(&mut framed).await?.ok_or(anyhow!(
+        "The daemon closed the connection, it may be too old"
+    ))?;
+
+    if daemon.protocol_version != PROTOCOL_VERSION {
+        bail!(
+            "The daemon {} speaks protocol {}, this client {} speaks protocol {}",
+            daemon.version,
+            daemon.protocol_version,
+            VERSION,
+            PROTOCOL_VERSION
+        );
+    }
+    if daemon.version != VERSION {
+        warn!(
+            daemon = daemon.version,
+            client = VERSION,
+            "The daemon has another version than the client"
+        );
+    }
+
+    Ok((with_codec(framed), daemon))
+}
+
+/// Accepts the connection of a client on the daemon side. It fails if the client
+/// sent no hello or speaks another protocol
+pub async fn accept<U, V>(
+    socket: UnixStream,
+) -> Result<Framed<UnixStream, AsymmetricMessageCodec<U, V>>>
+where
+    U: Serialize + DeserializeOwned,
+    V: Serialize + DeserializeOwned,
+{
+    let mut framed = Framed::new(socket, LengthDelimitedCodec::new());
+    let client = tokio::time::timeout(HELLO_TIMEOUT, receive_hello(&mut framed))
+        .await
+        .map_err(|_| anyhow!("The client sent no hello"))??
+        .ok_or(anyhow!("The client closed the connection"))?;
+
+    // The client compares the versions too and tells its user
+    send_hello(&mut framed).await?;
+    if client.protocol_version != PROTOCOL_VERSION {
+        bail!(
+            "The client {} speaks protocol {}, the daemon speaks protocol {}",
+            client.version,
+            client.protocol_version,
+            PROTOCOL_VERSION
+        );
+    }
+    if client.version != VERSION {
+        warn!(
+            daemon = VERSION,
+            client = client.version,
+            "The client has another version than the daemon"
+        );
+    }
+
+    Ok(with_codec(framed))
+}
+
+async fn send_hello(framed: &mut Framed<UnixStream, LengthDelimitedCodec>) -> Result<()> {
+    let hello = to_allocvec(&Hello::ours())?;
+    framed.send(Bytes::from(hello)).await?;
+    Ok(())
+}
+
+/// The hello of the other side, None if it closed the connection
+async fn receive_hello(
+    framed: &mut Framed<UnixStream, LengthDelimitedCodec>,
+) -> Result<Option<Hello>> {
+    let Some(frame) = framed.next().await else {
+        return Ok(None);
+    };
+    let hello = from_bytes::<Hello>(&frame?)
+        .ok()
+        .filter(|h| h.magic == MAGIC);
+    match hello {
+        Some(hello) => Ok(Some(hello)),
+        None => bail!("The other side sent no hello, it may be older than the handshake"),
+    }
+}
+
+/// Switches the connection to the requests and the responses, with what was already
+/// received or not yet sent
+fn with_codec<U, V>(
+    framed: Framed<UnixStream, LengthDelimitedCodec>,
+) -> Framed<UnixStream, AsymmetricMessageCodec<U, V>>
+where
+    U: Serialize + DeserializeOwned,
+    V: Serialize + DeserializeOwned,
+{
+    let parts = framed.into_parts();
+    let mut new_parts = FramedParts::new(parts.io, AsymmetricMessageCodec::new());
+    new_parts.read_buf = parts.read_buf;
+    new_parts.write_buf = parts.write_buf;
+    Framed::from_parts(new_parts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DaemonRequest, DaemonResult};
+
+    #[tokio::test]
+    async fn handshake_test() {
+        let (client, daemon) = UnixStream::pair().unwrap();
+        let daemon = tokio::spawn(accept::<DaemonResult, DaemonRequest>(daemon));
+        let (_, hello) = open::<DaemonRequest, DaemonResult>(client).await.unwrap();
+        assert_eq!(hello, Hello::ours());
+        assert!(daemon.await.unwrap().is_ok());
+
+        // A client older than the handshake starts with a request
+        let (mut client, daemon) = UnixStream::pair().unwrap();
+        let daemon = tokio::spawn(accept::<DaemonResult, DaemonRequest>(daemon));
+        let mut request = AsymmetricMessageCodec::<DaemonRequest, DaemonResult>::new();
+        let mut buf = bytes::BytesMut::new();
+        tokio_util::codec::Encoder::encode(&mut request, DaemonRequest::ListNodes, &mut buf)
+            .unwrap();
+        tokio::io::AsyncWriteExt::write_all(&mut client, &buf)
+            .await
+            .unwrap();
+        assert!(daemon.await.unwrap().is_err());
+    }
+}
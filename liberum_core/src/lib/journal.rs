@@ -0,0 +1,341 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Read, Write};
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::owned_file::FileTarget;
+
+///! The module contains the journal of a node, the record of the events of its swarm
+///! and of the messages sent to it. The journal is written only if the node config
+///! sets a path for it, and is read offline to reconstruct the timeline of the
+///! queries and the connections, mainly to debug the tests with many nodes.
+///!
+///! The entries are serialized with bincode, each prefixed with its length as
+///! a big endian u32. Every start of the swarm appends to the same journal.
+
+/// An entry of the journal
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct JournalEntry {
+    pub at: SystemTime,
+    pub event: JournalEvent,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum JournalEvent {
+    /// The swarm of the node started
+    Started { peer_id: String },
+    /// A message from the node to the swarm, only its kind is recorded
+    Message { kind: String },
+    ConnectionEstablished {
+        connection_id: String,
+        peer_id: String,
+        address: String,
+        outbound: bool,
+    },
+    ConnectionClosed {
+        connection_id: String,
+        peer_id: String,
+        cause: Option<String>,
+    },
+    OutgoingConnectionError {
+        peer_id: Option<String>,
+        error: String,
+    },
+    /// A step of a Kademlia query started by the node. `elapsed` is the time since
+    /// the query started
+    QueryProgressed {
+        id: String,
+        kind: String,
+        step: usize,
+        last: bool,
+        error: Option<String>,
+        elapsed: Option<Duration>,
+    },
+    /// Any other event of the swarm, as formatted for debugging
+    Other { description: String },
+}
+
+impl fmt::Display for JournalEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JournalEvent::Started { peer_id } => write!(f, "swarm started as {peer_id}"),
+            JournalEvent::Message { kind } => write!(f, "message {kind}"),
+            JournalEvent::ConnectionEstablished {
+                connection_id,
+                peer_id,
+                address,
+                outbound,
+            } => {
+                let direction = if *outbound { "to" } else { "from" };
+                write!(
+                    f,
+                    "connection {connection_id} established {direction} {peer_id} at {address}"
+                )
+            }
+            JournalEvent::ConnectionClosed {
+                connection_id,
+                peer_id,
+                cause,
+            } => {
+                write!(f, "connection {connection_id} to {peer_id} closed")?;
+                match cause {
+                    Some(cause) => write!(f, ": {cause}"),
+                    None => Ok(()),
+                }
+            }
+            JournalEvent::OutgoingConnectionError { peer_id, error } => {
+                let peer_id = peer_id.as_deref().unwrap_or("unknown peer");
+                write!(f, "failed to connect to {peer_id}: {error}")
+            }
+            JournalEvent::QueryProgressed {
+                id,
+                kind,
+                step,
+                last,
+                error,
+                ..
+            } => {
+                write!(f, "query {id} ({kind}) step {step}")?;
+                if *last {
+                    write!(f, ", finished")?;
+                }
+                match error {
+                    Some(error) => write!(f, ", failed: {error}"),
+                    None => Ok(()),
+                }
+            }
+            JournalEvent::Other { description } => write!(f, "{description}"),
+        }
+    }
+}
+
+/// Appends the entries to the journal file. Every entry is flushed at once, so the
+/// journal is complete up to the last event even if the daemon crashes
+pub struct JournalWriter {
+    file: BufWriter<File>,
+}
+
+impl JournalWriter {
+    /// Opens the journal for appending, it's created if it doesn't exist. With the
+    /// owner of the node, the opened file must belong to the owner, the path could be
+    /// swapped since it was checked
+    pub fn open(path: &Path, owner: Option<u32>) -> Result<Self> {
+        let file = match owner {
+            Some(_) => FileTarget::open(path, owner)?.open_append()?,
+            None => OpenOptions::new().create(true).append(true).open(path)?,
+        };
+        Ok(JournalWriter {
+            file: BufWriter::new(file),
+        })
+    }
+
+    pub fn write(&mut self, event: JournalEvent) -> Result<()> {
+        let entry = JournalEntry {
+            at: SystemTime::now(),
+            event,
+        };
+        let data = bincode::serialize(&entry)?;
+        let len = u32::try_from(data.len())?;
+        self.file.write_all(&len.to_be_bytes())?;
+        self.file.write_all(&data)?;
+        self.file.flush()?;
+        Ok(())
+    }
+}
+
+/// Reads all the entries of the journal file. An entry cut short, written when
+/// the daemon was killed, ends the journal
+pub fn read_journal(path: &Path) -> Result<Vec<JournalEntry>> {
+    let mut data = Vec::new();
+    File::open(path)?.read_to_end(&mut data)?;
+    parse_journal(&data)
+}
+
+fn parse_journal(mut data: &[u8]) -> Result<Vec<JournalEntry>> {
+    let mut entries = Vec::new();
+    while data.len() >= 4 {
+        let len = u32::from_be_bytes(data[..4].try_into()?) as usize;
+        let Some(frame) = data.get(4..4 + len) else {
+            break;
+        };
+        let entry = bincode::deserialize(frame)
+            .map_err(|e| anyhow!("Invalid journal entry {}: {e}", entries.len()))?;
+        entries.push(entry);
+        data = &data[4 + len..];
+    }
+
+    Ok(entries)
+}
+
+/// A Kademlia query reconstructed from the journal
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuerySpan {
+    pub id: String,
+    pub kind: String,
+    pub started_at: SystemTime,
+    /// None if the journal ends before the last step of the query
+    pub finished_at: Option<SystemTime>,
+    pub steps: usize,
+    /// The error of the last failed step
+    pub error: Option<String>,
+}
+
+/// A connection reconstructed from the journal
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConnectionSpan {
+    pub connection_id: String,
+    pub peer_id: String,
+    pub address: String,
+    pub outbound: bool,
+    pub opened_at: SystemTime,
+    /// None if the connection was still open when the journal ends
+    pub closed_at: Option<SystemTime>,
+    pub cause: Option<String>,
+}
+
+/// Reconstructs the queries from their steps, ordered by the start. The IDs of the
+/// queries are unique only within one run of the swarm
+pub fn queries(entries: &[JournalEntry]) -> Vec<QuerySpan> {
+    let mut finished = Vec::new();
+    let mut running: HashMap<String, QuerySpan> = HashMap::new();
+    for entry in entries {
+        match &entry.event {
+            JournalEvent::Started { .. } => finished.extend(running.drain().map(|(_, q)| q)),
+            JournalEvent::QueryProgressed {
+                id,
+                kind,
+                last,
+                error,
+                elapsed,
+                ..
+            } => {
+                let query = running.entry(id.clone()).or_insert_with(|| QuerySpan {
+                    id: id.clone(),
+                    kind: kind.clone(),
+                    started_at: elapsed
+                        .and_then(|elapsed| entry.at.checked_sub(elapsed))
+                        .unwrap_or(entry.at),
+                    finished_at: None,
+                    steps: 0,
+                    error: None,
+                });
+                query.steps += 1;
+                if error.is_some() {
+                    query.error = error.clone();
+                }
+                if *last {
+                    let mut query = running
+                        .remove(id)
+                        .expect("To be present, as it was inserted");
+                    query.finished_at = Some(entry.at);
+                    finished.push(query);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    finished.extend(running.into_values());
+    finished.sort_by_key(|q| q.started_at);
+    finished
+}
+
+/// Reconstructs the connections, ordered by the time they were opened
+pub fn connections(entries: &[JournalEntry]) -> Vec<ConnectionSpan> {
+    let mut closed = Vec::new();
+    let mut open: HashMap<String, ConnectionSpan> = HashMap::new();
+    for entry in entries {
+        match &entry.event {
+            // The connections of the previous run were closed when it ended
+            JournalEvent::Started { .. } => closed.extend(open.drain().map(|(_, c)| c)),
+            JournalEvent::ConnectionEstablished {
+                connection_id,
+                peer_id,
+                address,
+                outbound,
+            } => {
+                let connection = ConnectionSpan {
+                    connection_id: connection_id.clone(),
+                    peer_id: peer_id.clone(),
+                    address: address.clone(),
+                    outbound: *outbound,
+                    opened_at: entry.at,
+                    closed_at: None,
+                    cause: None,
+                };
+                open.insert(connection_id.clone(), connection);
+            }
+            JournalEvent::ConnectionClosed {
+                connection_id,
+                cause,
+                ..
+            } => {
+                if let Some(mut connection) = open.remove(connection_id) {
+                    connection.closed_at = Some(entry.at);
+                    connection.cause = cause.clone();
+                    closed.push(connection);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    closed.extend(open.into_values());
+    closed.sort_by_key(|c| c.opened_at);
+    closed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    fn step(id: &str, step: usize, last: bool, elapsed_secs: u64) -> JournalEvent {
+        JournalEvent::QueryProgressed {
+            id: id.to_string(),
+            kind: "GetProviders".to_string(),
+            step,
+            last,
+            error: None,
+            elapsed: Some(Duration::from_secs(elapsed_secs)),
+        }
+    }
+
+    #[test]
+    fn journal_test() {
+        let tmp_dir = TempDir::new("liberum_journal_test").unwrap();
+        let path = tmp_dir.path().join("journal.bin");
+        let mut writer = JournalWriter::open(&path, None).unwrap();
+        writer.write(step("1", 1, false, 1)).unwrap();
+        writer
+            .write(JournalEvent::Message {
+                kind: "Dial".to_string(),
+            })
+            .unwrap();
+        writer.write(step("1", 2, true, 2)).unwrap();
+        writer.write(step("2", 1, false, 0)).unwrap();
+        drop(writer);
+
+        // A cut entry is ignored
+        let mut data = std::fs::read(&path).unwrap();
+        data.extend_from_slice(&100u32.to_be_bytes());
+        data.push(0);
+        std::fs::write(&path, data).unwrap();
+
+        let entries = read_journal(&path).unwrap();
+        assert_eq!(entries.len(), 4);
+
+        let queries = queries(&entries);
+        assert_eq!(queries.len(), 2);
+        assert_eq!(queries[0].id, "1");
+        assert_eq!(queries[0].steps, 2);
+        assert_eq!(queries[0].finished_at, Some(entries[2].at));
+        assert_eq!(queries[1].id, "2");
+        assert_eq!(queries[1].finished_at, None);
+    }
+}
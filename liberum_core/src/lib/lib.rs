@@ -1,30 +1,46 @@
+pub mod atomic_file;
+pub mod canonical;
+pub mod chunker;
 pub mod codec;
+pub mod compression;
+pub mod daemon_config;
+pub mod erasure;
+pub mod handshake;
+pub mod journal;
 pub mod node_config;
+pub mod owned_file;
 pub mod parser;
 pub mod proto;
+pub mod render;
 pub mod types;
 
+use daemon_config::{DaemonConfig, Role};
 use libp2p::futures::StreamExt;
-use node_config::NodeConfig;
+use node_config::{NodeConfig, WatchDir};
 use proto::*;
 use std::{
     path::{Path, PathBuf},
-    time::Duration,
+    time::{Duration, SystemTime},
 };
 use tokio::fs::File;
 use tokio::net::UnixStream;
 use tokio::sync::mpsc;
 use tokio_util::io::ReaderStream;
 use tracing::{debug, error};
-use types::{NodeInfo, TypedObjectInfo};
+use types::{
+    AuditEntry, AuditFilter, BucketInfo, ConfigReloadSummary, Contact, DaemonHealth, GroupPost,
+    InboxMessage, MessageContent, ModuleCall, ModuleInfo, NodeEvent, NodeInfo, NodeStatsSample,
+    NodeStatus, ObjectAccess, ObjectInfo, ObjectPopularity, ObjectVerification, PeerInfo,
+    PeerProfile, PeerScore, PublishFileResult, QueryResults, ScheduledTask, ScheduledTaskInfo,
+    SearchResult, TextMatch, TrustLevel, TypedObjectInfo, VaultSnapshotSummary,
+};
 
 use anyhow::Result;
-use codec::AsymmetricMessageCodec;
 use futures::prelude::*;
-use tokio_util::codec::Decoder;
 
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
+use uuid::Uuid;
 
 /// Messages that can be sent from the UI to the daemon
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -43,6 +59,30 @@ pub enum DaemonRequest {
         node_name: String,
         new_cfg: NodeConfig,
     },
+    /// Adds the peer to the blocklist of the node. Works also for running nodes
+    BlockPeer {
+        node_name: String,
+        peer_id: String,
+    },
+    /// Removes the peer from the blocklist of the node. Works also for running nodes
+    UnblockPeer {
+        node_name: String,
+        peer_id: String,
+    },
+    /// Adds the directory to the watch list of the node or replaces the one with the
+    /// same path. Works also for running nodes
+    AddWatchDir {
+        node_name: String,
+        dir: WatchDir,
+    },
+    /// Stops watching the directory, the files published from it stay published
+    RemoveWatchDir {
+        node_name: String,
+        path: PathBuf,
+    },
+    ListWatchDirs {
+        node_name: String,
+    },
     StopNode {
         node_name: String,
     },
@@ -50,29 +90,75 @@ pub enum DaemonRequest {
     GetNodeDetails {
         node_name: String,
     },
+    GetNodeStatus {
+        node_name: String,
+    },
+    GetPeerScores {
+        node_name: String,
+    },
+    /// Gets the events of the running node newer than the event with the `since` ID
+    GetNodeEvents {
+        node_name: String,
+        since: Option<u64>,
+    },
+    /// Lists the buckets of the Kademlia routing table, the peers in them and the
+    /// connected peers
+    GetRoutingTable {
+        node_name: String,
+    },
+    DisconnectPeer {
+        node_name: String,
+        peer_id: String,
+    },
+    /// Forgets the misbehaviours of the peer recorded by the running node, which
+    /// lifts its ban
+    ResetPeerScore {
+        node_name: String,
+        peer_id: String,
+    },
     ProvideFile {
         node_name: String,
         path: PathBuf,
     },
+    /// Downloads the file, the objects of a group with the membership in the group.
+    /// The file already in the vault is not downloaded, unless `force_network`
     DownloadFile {
         node_name: String,
         id: String,
+        group: Option<String>,
+        #[serde(default)]
+        force_network: bool,
     },
+    /// Finds the providers of the object. With `stream` the providers are sent as
+    /// soon as they are found, as `ProvidersFound` responses, before the final
+    /// `Providers` response with all of them
     GetProviders {
         node_name: String,
         id: String,
+        stream: bool,
     },
     GetPeerId {
         node_name: String,
     },
+    /// Dials the peer at the address. The peer ID may be at the end of the address
+    /// as `/p2p/<peer id>`, without any the ID of the peer is learned when connected
     Dial {
         node_name: String,
-        peer_id: String,
+        peer_id: Option<String>,
         addr: String,
     },
+    /// Publishes the file, as a new revision of `previous` if given
     PublishFile {
         node_name: String,
         path: PathBuf,
+        access: ObjectAccess,
+        previous: Option<String>,
+    },
+    /// Publishes the files, at most `max_concurrency` of them at the same time
+    PublishFiles {
+        node_name: String,
+        paths: Vec<PathBuf>,
+        max_concurrency: usize,
     },
     GetPublishedObjects {
         node_name: String,
@@ -81,16 +167,572 @@ pub enum DaemonRequest {
         node_name: String,
         object_id: String,
     },
+    /// Checks the integrity of the locally stored object and the number of its providers.
+    /// The signatures are verified with the key of the node and of the optional signer
+    VerifyObject {
+        node_name: String,
+        object_id: String,
+        signer: Option<String>,
+    },
+    /// Stops announcing the node as a provider of the object. The object is
+    /// removed from the vault, unless `keep_in_vault` is set
+    StopProviding {
+        node_name: String,
+        object_id: String,
+        keep_in_vault: bool,
+    },
+    /// Gets the keypair of the node encrypted with the passphrase, as a backup
+    /// which can be imported by `ImportNodeIdentity`
+    ExportNodeIdentity {
+        node_name: String,
+        passphrase: String,
+    },
+    /// Creates a new node with the keypair from the exported identity bundle
+    ImportNodeIdentity {
+        node_name: String,
+        bundle: Vec<u8>,
+        passphrase: String,
+    },
+    /// Decrypts the keypair of a protected node, so it can be started.
+    /// The node stays unlocked until the daemon stops
+    UnlockNode {
+        node_name: String,
+        passphrase: String,
+    },
+    /// Encrypts the keypair of the node on disk with the passphrase, or stores
+    /// it unencrypted if the passphrase is `None`
+    SetNodePassphrase {
+        node_name: String,
+        passphrase: Option<String>,
+    },
+    /// After the `Subscribed` response the connection also receives
+    /// the `Notification` responses with the state changes of the nodes
+    Subscribe,
+    /// Renames the node. Fails for a running node, unless `force` is set,
+    /// then the node is stopped first
+    RenameNode {
+        node_name: String,
+        new_name: String,
+        force: bool,
+    },
+    /// Deletes the node with its vault, unless `keep_vault` is set. Fails for
+    /// a running node, unless `force` is set, then the node is stopped first
+    DeleteNode {
+        node_name: String,
+        force: bool,
+        keep_vault: bool,
+    },
+    /// Creates a new node with the configuration of the source node. The keypair
+    /// is copied too unless `fresh_identity` is set, which is rarely what you want,
+    /// two nodes with the same peer ID can't be in the network at the same time
+    CloneNode {
+        source: String,
+        target: String,
+        fresh_identity: bool,
+    },
+    /// Applies the bootstrap nodes and the listen addresses from the config to the
+    /// running node. The config is saved first, also when the node is not running,
+    /// without it the stored one is reloaded
+    ReloadNodeConfig {
+        node_name: String,
+        new_cfg: Option<NodeConfig>,
+    },
+    /// Lists the modules of the node, the object types it handles
+    ListModules {
+        node_name: String,
+    },
+    /// Enables or disables the module, given by its name or UUID. Works also for
+    /// running nodes
+    SetModuleEnabled {
+        node_name: String,
+        module: String,
+        enabled: bool,
+    },
+    /// Registers the connection as an external module handling the object types.
+    /// The objects of these types are then sent to the connection as `ModuleCall`
+    /// responses, until it is closed
+    RegisterModule {
+        name: String,
+        type_uuids: Vec<Uuid>,
+    },
+    /// The answer of an external module to a `ModuleCall`. The daemon does not
+    /// respond to it
+    ModuleCallResult {
+        call_id: u64,
+        result: Result<Option<TypedObject>, String>,
+    },
+    /// Sends the query to the closest peers of its hash and merges the IDs of
+    /// the matching objects. The type of the query must be handled by an external
+    /// module on the asked peers
+    Query {
+        node_name: String,
+        query: TypedObject,
+    },
+    /// Packs the vault of the node into a snapshot archive at the path, which
+    /// is resolved by the daemon
+    ExportVaultSnapshot {
+        node_name: String,
+        path: PathBuf,
+    },
+    /// Merges the snapshot archive into the vault of the node, skipping the
+    /// objects and the fragments it already has
+    ImportVaultSnapshot {
+        node_name: String,
+        path: PathBuf,
+    },
+    /// Sets the log level of the target, e.g. `liberum_core::swarm_runner`, while
+    /// the daemon runs
+    SetLogLevel {
+        target: String,
+        level: String,
+    },
+    /// Returns up to `lines` recent log lines of the node, or of the whole daemon
+    /// if `node_name` is `None`. With `follow` the connection then also receives
+    /// the lines logged afterwards, as more `LogLines` responses
+    TailLogs {
+        node_name: Option<String>,
+        follow: bool,
+        lines: usize,
+    },
+    /// Sets the profile of the node, published in the DHT at once if the node runs
+    /// or when it starts. `avatar` is the ID of the object with the avatar image
+    SetProfile {
+        node_name: String,
+        display_name: String,
+        description: String,
+        avatar: Option<String>,
+    },
+    /// Finds the profile of the peer in the DHT using the running node
+    GetPeerProfile {
+        node_name: String,
+        peer_id: String,
+    },
+    /// Adds the peer to the contacts of the node or replaces the contact. The node
+    /// dials the contacts with an address when it starts
+    AddContact {
+        node_name: String,
+        peer_id: String,
+        alias: String,
+        address: Option<String>,
+        trust: TrustLevel,
+    },
+    RemoveContact {
+        node_name: String,
+        peer_id: String,
+    },
+    ListContacts {
+        node_name: String,
+    },
+    /// Sends the message directly to the peer using the running node. With `encrypt`
+    /// only the peer can read the content. If the peer is offline, the message is
+    /// encrypted and left at its closest peers, which it fetches it from when it starts
+    SendMessage {
+        node_name: String,
+        peer_id: String,
+        content: MessageContent,
+        encrypt: bool,
+    },
+    /// Returns the messages received by the node
+    GetInbox {
+        node_name: String,
+    },
+    /// Creates a group owned by the running node and subscribes to its feed
+    CreateGroup {
+        node_name: String,
+        group_name: String,
+    },
+    /// Issues the access token for the peer, valid for `valid_for_secs`. Only the
+    /// owner of the group can invite. Returns the invitation to be passed to the peer
+    InviteToGroup {
+        node_name: String,
+        group_id: String,
+        peer_id: String,
+        valid_for_secs: u64,
+    },
+    /// Joins the group using the invitation issued for this node
+    JoinGroup {
+        node_name: String,
+        invitation: String,
+    },
+    /// Publishes the post in the feed of the group the running node is a member of
+    PostToGroup {
+        node_name: String,
+        group_id: String,
+        content: MessageContent,
+    },
+    /// Returns the posts of the group received by the node
+    GetGroupFeed {
+        node_name: String,
+        group_id: String,
+    },
+    /// Replaces the keypair of the node with a new one, which changes its peer ID.
+    /// The rotation is signed by both keys and published when the node starts, so
+    /// peers can follow the old peer ID to the new one. The passphrase is required
+    /// for a protected node. Fails for a running node, unless `force` is set, then
+    /// the node is stopped first
+    RotateNodeKey {
+        node_name: String,
+        passphrase: Option<String>,
+        force: bool,
+    },
+    /// Returns the inbound requests served or rejected by the node, the oldest first
+    GetAuditLog {
+        node_name: String,
+        filter: AuditFilter,
+    },
+    /// Lists the maintenance tasks of the running node, with their last results
+    ListScheduledTasks {
+        node_name: String,
+    },
+    /// Runs the task of the running node now and responds when it finishes
+    RunTaskNow {
+        node_name: String,
+        task: ScheduledTask,
+    },
+    /// Lists the objects most often fetched from the node, limited to the given number
+    GetPopularObjects {
+        node_name: String,
+        limit: Option<usize>,
+    },
+    /// Publishes the tags of the object, for it to be found by searching
+    TagObject {
+        node_name: String,
+        id: String,
+        tags: Vec<String>,
+    },
+    /// Searches the objects with the tags in the query, in the index of the node
+    /// and, with `network`, of the closest peers of the tags
+    SearchObjects {
+        node_name: String,
+        query: String,
+        network: bool,
+    },
+    /// Searches the text of the plain text files stored by the node
+    SearchText {
+        node_name: String,
+        query: String,
+        limit: Option<usize>,
+    },
+    /// Lists the revisions of the object known to the node, the latest one first
+    GetHistory {
+        node_name: String,
+        id: String,
+    },
+    /// Switches the connection to the role of the token from the daemon config
+    Authenticate {
+        token: String,
+    },
+    /// The config the daemon runs with, after the overrides, without the tokens
+    GetDaemonConfig,
+    /// Returns the samples of the stats of the node taken in the range, the oldest
+    /// first. The node may be stopped
+    GetNodeStatsHistory {
+        node_name: String,
+        since: Option<SystemTime>,
+        until: Option<SystemTime>,
+    },
+    /// Describes the object stored or published by the node, with the peer it came
+    /// from and when. The types of the nested objects are decoded, e.g. the file in
+    /// a signed object. The object missing in the vault is looked up in the network
+    GetObjectInfo {
+        node_name: String,
+        object_id: String,
+    },
+    /// The object stored or published by the node, looked up in the network if it's
+    /// missing in the vault
+    GetObject {
+        node_name: String,
+        object_id: String,
+    },
+    /// Checks the daemon is alive and responsive
+    Ping,
+    /// Applies the operation to the nodes at once, or to all the nodes the
+    /// connection sees if there are no names. Every node is handled like its own
+    /// request, the results are per node
+    BatchNodes {
+        node_names: Vec<String>,
+        operation: NodeOperation,
+    },
+    /// Applies the operation to the nodes of the group from the daemon config, like
+    /// a batch of them
+    BatchGroup {
+        group: String,
+        operation: NodeOperation,
+    },
+}
+
+/// An operation applied to many nodes by a batch request
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum NodeOperation {
+    Start,
+    Stop,
+    GetDetails,
+    /// Applies the stored config to the running node
+    ReloadConfig,
+}
+
+impl NodeOperation {
+    /// The request of the operation for a single node
+    pub fn request(&self, node_name: String) -> DaemonRequest {
+        match self {
+            NodeOperation::Start => DaemonRequest::StartNode { node_name },
+            NodeOperation::Stop => DaemonRequest::StopNode { node_name },
+            NodeOperation::GetDetails => DaemonRequest::GetNodeDetails { node_name },
+            NodeOperation::ReloadConfig => DaemonRequest::ReloadNodeConfig {
+                node_name,
+                new_cfg: None,
+            },
+        }
+    }
+}
+
+impl DaemonRequest {
+    /// Requests which don't change the state of the daemon, allowed also for the
+    /// observers
+    pub fn is_read_only(&self) -> bool {
+        match self {
+            DaemonRequest::GetNodeConfig { .. }
+            | DaemonRequest::ListNodes
+            | DaemonRequest::GetNodeDetails { .. }
+            | DaemonRequest::GetNodeStatus { .. }
+            | DaemonRequest::GetPeerScores { .. }
+            | DaemonRequest::GetNodeEvents { .. }
+            | DaemonRequest::GetRoutingTable { .. }
+            | DaemonRequest::GetProviders { .. }
+            | DaemonRequest::GetPeerId { .. }
+            | DaemonRequest::GetPublishedObjects { .. }
+            | DaemonRequest::VerifyObject { .. }
+            | DaemonRequest::Subscribe
+            | DaemonRequest::TailLogs { .. }
+            | DaemonRequest::GetPeerProfile { .. }
+            | DaemonRequest::ListContacts { .. }
+            | DaemonRequest::ListWatchDirs { .. }
+            | DaemonRequest::GetInbox { .. }
+            | DaemonRequest::GetGroupFeed { .. }
+            | DaemonRequest::GetAuditLog { .. }
+            | DaemonRequest::ListScheduledTasks { .. }
+            | DaemonRequest::GetPopularObjects { .. }
+            | DaemonRequest::SearchObjects { .. }
+            | DaemonRequest::SearchText { .. }
+            | DaemonRequest::GetHistory { .. }
+            | DaemonRequest::ListModules { .. }
+            | DaemonRequest::Query { .. }
+            | DaemonRequest::Authenticate { .. }
+            | DaemonRequest::GetDaemonConfig
+            | DaemonRequest::GetNodeStatsHistory { .. }
+            | DaemonRequest::GetObjectInfo { .. }
+            | DaemonRequest::GetObject { .. }
+            | DaemonRequest::Ping => true,
+            DaemonRequest::BatchNodes { operation, .. }
+            | DaemonRequest::BatchGroup { operation, .. } => {
+                operation.request(String::new()).is_read_only()
+            }
+            DaemonRequest::NewNode { .. }
+            | DaemonRequest::StartNode { .. }
+            | DaemonRequest::OverwriteNodeConfig { .. }
+            | DaemonRequest::BlockPeer { .. }
+            | DaemonRequest::UnblockPeer { .. }
+            | DaemonRequest::AddWatchDir { .. }
+            | DaemonRequest::RemoveWatchDir { .. }
+            | DaemonRequest::StopNode { .. }
+            | DaemonRequest::DisconnectPeer { .. }
+            | DaemonRequest::ResetPeerScore { .. }
+            | DaemonRequest::ProvideFile { .. }
+            | DaemonRequest::DownloadFile { .. }
+            | DaemonRequest::Dial { .. }
+            | DaemonRequest::PublishFile { .. }
+            | DaemonRequest::PublishFiles { .. }
+            | DaemonRequest::DeleteObject { .. }
+            | DaemonRequest::StopProviding { .. }
+            | DaemonRequest::ExportNodeIdentity { .. }
+            | DaemonRequest::ImportNodeIdentity { .. }
+            | DaemonRequest::UnlockNode { .. }
+            | DaemonRequest::SetNodePassphrase { .. }
+            | DaemonRequest::RenameNode { .. }
+            | DaemonRequest::DeleteNode { .. }
+            | DaemonRequest::CloneNode { .. }
+            | DaemonRequest::ReloadNodeConfig { .. }
+            | DaemonRequest::SetModuleEnabled { .. }
+            | DaemonRequest::RegisterModule { .. }
+            | DaemonRequest::ModuleCallResult { .. }
+            | DaemonRequest::ExportVaultSnapshot { .. }
+            | DaemonRequest::ImportVaultSnapshot { .. }
+            | DaemonRequest::SetLogLevel { .. }
+            | DaemonRequest::SetProfile { .. }
+            | DaemonRequest::AddContact { .. }
+            | DaemonRequest::RemoveContact { .. }
+            | DaemonRequest::SendMessage { .. }
+            | DaemonRequest::CreateGroup { .. }
+            | DaemonRequest::InviteToGroup { .. }
+            | DaemonRequest::JoinGroup { .. }
+            | DaemonRequest::PostToGroup { .. }
+            | DaemonRequest::RotateNodeKey { .. }
+            | DaemonRequest::RunTaskNow { .. }
+            | DaemonRequest::TagObject { .. } => false,
+        }
+    }
+
+    /// Requests which control the whole daemon or override its protections, allowed
+    /// only for the admins
+    pub fn is_admin_only(&self) -> bool {
+        matches!(
+            self,
+            DaemonRequest::SetLogLevel { .. }
+                | DaemonRequest::RegisterModule { .. }
+                | DaemonRequest::ResetPeerScore { .. }
+                | DaemonRequest::TailLogs {
+                    node_name: None,
+                    ..
+                }
+        )
+    }
+
+    /// The node the request is about, the source node when cloning
+    pub fn node_name(&self) -> Option<&str> {
+        match self {
+            DaemonRequest::NewNode { node_name, .. }
+            | DaemonRequest::StartNode { node_name, .. }
+            | DaemonRequest::GetNodeConfig { node_name, .. }
+            | DaemonRequest::OverwriteNodeConfig { node_name, .. }
+            | DaemonRequest::BlockPeer { node_name, .. }
+            | DaemonRequest::UnblockPeer { node_name, .. }
+            | DaemonRequest::AddWatchDir { node_name, .. }
+            | DaemonRequest::RemoveWatchDir { node_name, .. }
+            | DaemonRequest::ListWatchDirs { node_name, .. }
+            | DaemonRequest::StopNode { node_name, .. }
+            | DaemonRequest::GetNodeDetails { node_name, .. }
+            | DaemonRequest::GetNodeStatus { node_name, .. }
+            | DaemonRequest::GetPeerScores { node_name, .. }
+            | DaemonRequest::GetNodeEvents { node_name, .. }
+            | DaemonRequest::GetRoutingTable { node_name, .. }
+            | DaemonRequest::DisconnectPeer { node_name, .. }
+            | DaemonRequest::ResetPeerScore { node_name, .. }
+            | DaemonRequest::ProvideFile { node_name, .. }
+            | DaemonRequest::DownloadFile { node_name, .. }
+            | DaemonRequest::GetProviders { node_name, .. }
+            | DaemonRequest::GetPeerId { node_name, .. }
+            | DaemonRequest::Dial { node_name, .. }
+            | DaemonRequest::PublishFile { node_name, .. }
+            | DaemonRequest::PublishFiles { node_name, .. }
+            | DaemonRequest::GetPublishedObjects { node_name, .. }
+            | DaemonRequest::DeleteObject { node_name, .. }
+            | DaemonRequest::VerifyObject { node_name, .. }
+            | DaemonRequest::StopProviding { node_name, .. }
+            | DaemonRequest::ExportNodeIdentity { node_name, .. }
+            | DaemonRequest::ImportNodeIdentity { node_name, .. }
+            | DaemonRequest::UnlockNode { node_name, .. }
+            | DaemonRequest::SetNodePassphrase { node_name, .. }
+            | DaemonRequest::RenameNode { node_name, .. }
+            | DaemonRequest::DeleteNode { node_name, .. }
+            | DaemonRequest::ReloadNodeConfig { node_name, .. }
+            | DaemonRequest::ListModules { node_name, .. }
+            | DaemonRequest::SetModuleEnabled { node_name, .. }
+            | DaemonRequest::Query { node_name, .. }
+            | DaemonRequest::ExportVaultSnapshot { node_name, .. }
+            | DaemonRequest::ImportVaultSnapshot { node_name, .. }
+            | DaemonRequest::SetProfile { node_name, .. }
+            | DaemonRequest::GetPeerProfile { node_name, .. }
+            | DaemonRequest::AddContact { node_name, .. }
+            | DaemonRequest::RemoveContact { node_name, .. }
+            | DaemonRequest::ListContacts { node_name, .. }
+            | DaemonRequest::SendMessage { node_name, .. }
+            | DaemonRequest::GetInbox { node_name, .. }
+            | DaemonRequest::CreateGroup { node_name, .. }
+            | DaemonRequest::InviteToGroup { node_name, .. }
+            | DaemonRequest::JoinGroup { node_name, .. }
+            | DaemonRequest::PostToGroup { node_name, .. }
+            | DaemonRequest::GetGroupFeed { node_name, .. }
+            | DaemonRequest::RotateNodeKey { node_name, .. }
+            | DaemonRequest::GetAuditLog { node_name, .. }
+            | DaemonRequest::ListScheduledTasks { node_name, .. }
+            | DaemonRequest::RunTaskNow { node_name, .. }
+            | DaemonRequest::GetPopularObjects { node_name, .. }
+            | DaemonRequest::TagObject { node_name, .. }
+            | DaemonRequest::SearchObjects { node_name, .. }
+            | DaemonRequest::SearchText { node_name, .. }
+            | DaemonRequest::GetHistory { node_name, .. }
+            | DaemonRequest::GetNodeStatsHistory { node_name, .. }
+            | DaemonRequest::GetObjectInfo { node_name, .. }
+            | DaemonRequest::GetObject { node_name, .. } => Some(node_name),
+            DaemonRequest::CloneNode { source, .. } => Some(source),
+            DaemonRequest::TailLogs { node_name, .. } => node_name.as_deref(),
+            DaemonRequest::ListNodes
+            | DaemonRequest::Subscribe
+            | DaemonRequest::RegisterModule { .. }
+            | DaemonRequest::ModuleCallResult { .. }
+            | DaemonRequest::SetLogLevel { .. }
+            | DaemonRequest::Authenticate { .. }
+            | DaemonRequest::GetDaemonConfig
+            | DaemonRequest::Ping
+            | DaemonRequest::BatchNodes { .. }
+            | DaemonRequest::BatchGroup { .. } => None,
+        }
+    }
+
+    /// The node the request creates if it succeeds
+    pub fn created_node(&self) -> Option<&str> {
+        match self {
+            DaemonRequest::NewNode { node_name, .. }
+            | DaemonRequest::ImportNodeIdentity { node_name, .. } => Some(node_name),
+            DaemonRequest::CloneNode { target, .. } => Some(target),
+            _ => None,
+        }
+    }
+}
+
+/// State changes of the nodes, sent to all the subscribed connections
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum DaemonNotification {
+    NodeCreated {
+        node_name: String,
+    },
+    NodeStarted {
+        node_name: String,
+    },
+    NodeStopped {
+        node_name: String,
+    },
+    NodeConfigUpdated {
+        node_name: String,
+    },
+    NodeUnlocked {
+        node_name: String,
+    },
+    /// The connection was too slow and missed some notifications,
+    /// the state should be fetched again
+    Lagged {
+        missed: u64,
+    },
+    NodeRenamed {
+        node_name: String,
+        new_name: String,
+    },
+    NodeDeleted {
+        node_name: String,
+    },
 }
 
 /// Messages that are sent from the daemon as a reponse
 /// An enum of enums - categorizes the responses
 pub type DaemonResult = Result<DaemonResponse, DaemonError>;
 
+/// The result of the operation of a batch request on one of its nodes
 #[derive(Serialize, Deserialize, Debug)]
+pub struct NodeOperationResult {
+    pub node_name: String,
+    pub result: DaemonResult,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct DaemonQueryStats {
     pub query_duration: Duration,
     pub total_requests: u32,
+    /// Number of attempts made before the query succeeded or gave up
+    #[serde(default)]
+    pub attempts: u32,
+    /// The object was found in the vault, the network was not asked
+    #[serde(default)]
+    pub from_cache: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -102,6 +744,19 @@ pub enum DaemonResponse {
     NodeStopped,
     NodeList(Vec<NodeInfo>),
     NodeDetails(NodeInfo),
+    NodeStatus(NodeStatus),
+    PeerScores {
+        scores: Vec<PeerScore>,
+    },
+    RoutingTable {
+        buckets: Vec<BucketInfo>,
+        peers: Vec<PeerInfo>,
+    },
+    NodeEvents {
+        events: Vec<NodeEvent>,
+    },
+    PeerDisconnected,
+    PeerScoreReset,
     FileProvided {
         id: String,
     },
@@ -116,10 +771,15 @@ pub enum DaemonResponse {
     PeerId {
         id: String,
     },
-    Dialed,
+    Dialed {
+        peer_id: String,
+    },
     FilePublished {
         id: String,
     },
+    FilesPublished {
+        results: Vec<PublishFileResult>,
+    },
     PublishedObjectsList {
         object_infos: Vec<TypedObjectInfo>,
     },
@@ -128,11 +788,107 @@ pub enum DaemonResponse {
         deleted_count: u32,
         failed_count: u32,
     },
+    StoppedProviding,
+    ObjectVerified(ObjectVerification),
+    NodeIdentityExported {
+        bundle: Vec<u8>,
+    },
+    NodeIdentityImported {
+        peer_id: String,
+    },
+    NodeUnlocked,
+    Subscribed,
+    Notification(DaemonNotification),
+    NodeRenamed,
+    NodeDeleted,
+    NodeCloned {
+        peer_id: String,
+    },
+    NodeConfigReloaded(ConfigReloadSummary),
+    Modules(Vec<ModuleInfo>),
+    ModuleRegistered,
+    ModuleCall(ModuleCall),
+    QueryResults(QueryResults),
+    VaultSnapshotExported(VaultSnapshotSummary),
+    VaultSnapshotImported(VaultSnapshotSummary),
+    /// The log filter in effect after the change
+    LogLevelSet {
+        filter: String,
+    },
+    LogLines {
+        lines: Vec<String>,
+    },
+    /// None if the peer has not published a valid profile
+    PeerProfile(Option<PeerProfile>),
+    ContactAdded,
+    /// `removed` is false if the node had no such contact
+    ContactRemoved {
+        removed: bool,
+    },
+    Contacts(Vec<Contact>),
+    WatchDirs(Vec<WatchDir>),
+    /// The message was delivered to the peer
+    MessageSent {
+        id: String,
+    },
+    Inbox(Vec<InboxMessage>),
+    /// The peer could not be reached, the message was left for it at `peers` of
+    /// its closest peers
+    MessageStored {
+        id: String,
+        peers: usize,
+    },
+    GroupCreated {
+        id: String,
+    },
+    /// The invitation to be passed to the invited peer, which joins with it
+    GroupInvitation {
+        invitation: String,
+    },
+    GroupJoined {
+        id: String,
+    },
+    GroupPosted {
+        id: String,
+    },
+    GroupFeed(Vec<GroupPost>),
+    NodeKeyRotated {
+        old_peer_id: String,
+        new_peer_id: String,
+    },
+    AuditLog(Vec<AuditEntry>),
+    ScheduledTasks(Vec<ScheduledTaskInfo>),
+    /// The state of the task after the run asked for finished
+    TaskRun(ScheduledTaskInfo),
+    /// The providers found since the previous response of a streamed provider lookup
+    ProvidersFound {
+        ids: Vec<String>,
+    },
+    PopularObjects(Vec<ObjectPopularity>),
+    TagsPublished {
+        id: String,
+    },
+    SearchResults(Vec<SearchResult>),
+    TextSearchResults(Vec<TextMatch>),
+    History {
+        revisions: Vec<String>,
+    },
+    Authenticated {
+        role: Role,
+    },
+    DaemonConfig(DaemonConfig),
+    NodeStatsHistory(Vec<NodeStatsSample>),
+    ObjectInfo(ObjectInfo),
+    Object(TypedObject),
+    Pong(DaemonHealth),
+    BatchResults(Vec<NodeOperationResult>),
 }
 
 /// Errors that can be returned by the daemon
-/// An enum of enums - categorizes the errors, just like responses
-#[derive(Serialize, Deserialize, Debug, Error)]
+/// An enum of enums - categorizes the errors, just like responses.
+/// The codec identifies the variants by their position, so new variants
+/// must be added at the end to keep the older UIs working
+#[derive(Serialize, Deserialize, Debug, Clone, Error)]
 pub enum DaemonError {
     #[error("Node already exist: {0}")]
     NodeAlreadyExist(String),
@@ -140,6 +896,24 @@ pub enum DaemonError {
     NodeDoesNotExist(String),
     #[error("Other error: {0}")]
     Other(String),
+    #[error("Object not found: {0}")]
+    ObjectNotFound(String),
+    #[error("No providers found for object: {0}")]
+    NoProviders(String),
+    #[error("Timed out: {0}")]
+    Timeout(String),
+    #[error("Vault error: {0}")]
+    VaultError(String),
+    #[error("Swarm error: {0}")]
+    SwarmError(String),
+    #[error("Invalid argument: {0}")]
+    InvalidArgument(String),
+    #[error("Node is locked: {0}")]
+    NodeLocked(String),
+    #[error("Permission denied: {0}")]
+    PermissionDenied(String),
+    #[error("Node is not running: {0}")]
+    NodeNotRunning(String),
 }
 
 /// Function for a CLI or other UI to connecto to the client daemon
@@ -149,9 +923,7 @@ pub async fn connect(
     socket_path: PathBuf,
 ) -> Result<(mpsc::Sender<DaemonRequest>, mpsc::Receiver<DaemonResult>)> {
     let socket = UnixStream::connect(&socket_path).await?;
-    let encoder: AsymmetricMessageCodec<DaemonRequest, DaemonResult> =
-        AsymmetricMessageCodec::new();
-    let mut daemon_socket = encoder.framed(socket);
+    let (mut daemon_socket, _) = handshake::open::<DaemonRequest, DaemonResult>(socket).await?;
     let (daemon_sender, mut daemon_receiver) = mpsc::channel::<DaemonRequest>(16);
     let (ui_sender, ui_receiver) = mpsc::channel::<DaemonResult>(16);
 
@@ -189,6 +961,100 @@ pub async fn connect(
     Ok((daemon_sender, ui_receiver))
 }
 
+/// Opens a separate connection to the daemon which receives only the notifications
+/// about the state changes of the nodes
+pub async fn subscribe(socket_path: PathBuf) -> Result<mpsc::Receiver<DaemonNotification>> {
+    let socket = UnixStream::connect(&socket_path).await?;
+    let (mut daemon_socket, _) = handshake::open::<DaemonRequest, DaemonResult>(socket).await?;
+    let (ui_sender, ui_receiver) = mpsc::channel::<DaemonNotification>(16);
+
+    daemon_socket.send(DaemonRequest::Subscribe).await?;
+    match daemon_socket.next().await {
+        Some(Ok(Ok(DaemonResponse::Subscribed))) => {}
+        Some(Ok(Err(e))) => return Err(e.into()),
+        _ => return Err(anyhow::anyhow!("Failed to subscribe to the daemon")),
+    }
+
+    tokio::spawn(async move {
+        while let Some(message) = daemon_socket.next().await {
+            let notification = match message {
+                Ok(Ok(DaemonResponse::Notification(notification))) => notification,
+                Ok(resp) => {
+                    debug!(resp = format!("{resp:?}"), "Unexpected message from daemon");
+                    continue;
+                }
+                Err(e) => {
+                    error!(err = e.to_string(), "Error receiving message");
+                    break;
+                }
+            };
+
+            if ui_sender.send(notification).await.is_err() {
+                debug!("Notification receiver dropped");
+                break;
+            }
+        }
+    });
+
+    Ok(ui_receiver)
+}
+
+/// Opens a separate connection to the daemon which receives the recent log lines
+/// of the node, or of the whole daemon, and then the lines logged afterwards
+pub async fn follow_logs(
+    socket_path: PathBuf,
+    node_name: Option<String>,
+    lines: usize,
+) -> Result<mpsc::Receiver<String>> {
+    let socket = UnixStream::connect(&socket_path).await?;
+    let (mut daemon_socket, _) = handshake::open::<DaemonRequest, DaemonResult>(socket).await?;
+    let (ui_sender, ui_receiver) = mpsc::channel::<String>(64);
+
+    daemon_socket
+        .send(DaemonRequest::TailLogs {
+            node_name,
+            follow: true,
+            lines,
+        })
+        .await?;
+    let recent_lines = match daemon_socket.next().await {
+        Some(Ok(Ok(DaemonResponse::LogLines { lines }))) => lines,
+        Some(Ok(Err(e))) => return Err(e.into()),
+        _ => return Err(anyhow::anyhow!("Failed to follow the logs of the daemon")),
+    };
+
+    tokio::spawn(async move {
+        for line in recent_lines {
+            if ui_sender.send(line).await.is_err() {
+                return;
+            }
+        }
+
+        while let Some(message) = daemon_socket.next().await {
+            let lines = match message {
+                Ok(Ok(DaemonResponse::LogLines { lines })) => lines,
+                Ok(resp) => {
+                    debug!(resp = format!("{resp:?}"), "Unexpected message from daemon");
+                    continue;
+                }
+                Err(e) => {
+                    error!(err = e.to_string(), "Error receiving message");
+                    break;
+                }
+            };
+
+            for line in lines {
+                if ui_sender.send(line).await.is_err() {
+                    debug!("Log receiver dropped");
+                    return;
+                }
+            }
+        }
+    });
+
+    Ok(ui_receiver)
+}
+
 pub async fn get_file_id(path: &Path) -> Result<libp2p::kad::RecordKey> {
     let file = File::open(path).await?;
     let mut stream = ReaderStream::new(file);
@@ -200,10 +1066,19 @@ pub async fn get_file_id(path: &Path) -> Result<libp2p::kad::RecordKey> {
     let k = libp2p::kad::RecordKey::from(k.to_vec());
     Ok(k)
 }
+/// Accepts the bare blake3 digests and the IDs in the multihash encoding. The blake3
+/// ones have the same key in both encodings, the other ones are keyed by the whole
+/// multihash, so they never collide with the older IDs
 pub fn str_to_file_id(s: &str) -> Result<libp2p::kad::RecordKey> {
     let k: Vec<u8> = bs58::decode::<Vec<u8>>(s.into()).into_vec()?;
-    let k = libp2p::kad::RecordKey::from(k);
-    Ok(k)
+    if k.len() == 32 {
+        return Ok(libp2p::kad::RecordKey::from(k));
+    }
+    let k = match parse_multihash(&k)? {
+        (HashAlgorithm::Blake3, multihash) => multihash.digest().to_vec(),
+        _ => k,
+    };
+    Ok(libp2p::kad::RecordKey::from(k))
 }
 
 pub fn file_id_to_str(id: libp2p::kad::RecordKey) -> String {
@@ -1,21 +1,697 @@
-use std::{path::Path, str::FromStr};
+use std::{
+    collections::{BTreeMap, HashMap},
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use libp2p::{Multiaddr, PeerId};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use strum_macros::{Display, EnumString};
 use tracing::error;
+use uuid::Uuid;
+
+use crate::atomic_file;
+use crate::proto::{PlainFileObject, RotationObject, SignedObject};
+use crate::types::{ModuleInfo, ScheduledTask};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct NodeConfig {
     pub bootstrap_nodes: Vec<BootstrapNode>,
+    /// Hostnames with dnsaddr TXT records, resolved to more bootstrap nodes when
+    /// the node starts, so the bootstrap nodes can change without editing the config
+    #[serde(default)]
+    pub bootstrap_dns_seeds: Vec<String>,
     pub external_addresses: Vec<Multiaddr>,
+    /// Peers that the node refuses to talk to
+    #[serde(
+        default,
+        serialize_with = "serialize_peer_ids",
+        deserialize_with = "deserialize_peer_ids"
+    )]
+    pub blocked_peers: Vec<PeerId>,
+    /// If not empty, the node talks only to the peers from this list
+    #[serde(
+        default,
+        serialize_with = "serialize_peer_ids",
+        deserialize_with = "deserialize_peer_ids"
+    )]
+    pub allowed_peers: Vec<PeerId>,
+    #[serde(default)]
+    pub connection_limits: ConnectionLimits,
+    #[serde(default)]
+    pub replication: ReplicationConfig,
+    /// How long the provider records received from other peers are valid
+    #[serde(default = "default_provider_ttl_secs")]
+    pub provider_ttl_secs: u64,
+    /// How often the node republishes the provider records of the objects it provides.
+    /// Should be shorter than the provider TTL, so the records never expire
+    #[serde(default = "default_republish_interval_secs")]
+    pub republish_interval_secs: u64,
+    /// The most provider announcements started in a second, the others wait. The
+    /// republished records are also spread over the republish interval
+    #[serde(default = "default_max_provides_per_sec")]
+    pub max_provides_per_sec: u32,
+    /// Start the node when the daemon starts
+    #[serde(default)]
+    pub autostart: bool,
+    /// Number of providers asked for an object at the same time when downloading
+    #[serde(default = "default_download_parallelism")]
+    pub download_parallelism: usize,
+    #[serde(default)]
+    pub retry: RetryConfig,
+    #[serde(default)]
+    pub restart: RestartConfig,
+    /// The keypair of the node is encrypted with a passphrase and the node must be
+    /// unlocked before starting. Managed by the node store, overwriting it has no effect
+    #[serde(default)]
+    pub key_protected: bool,
+    #[serde(default)]
+    pub modules: ModulesConfig,
+    /// Published in the DHT whenever the node starts
+    #[serde(default)]
+    pub profile: Option<NodeProfile>,
+    /// The rotations of the keypair of the node, the oldest first. Published in the
+    /// DHT whenever the node starts, so peers can follow the old peer IDs to the
+    /// current one. Managed by the node store, overwriting it has no effect
+    #[serde(default)]
+    pub key_rotations: Vec<RotationObject>,
+    /// If set, the swarm events and the messages to the swarm are recorded to this
+    /// file, to be replayed with `liberum_cli replay`. Takes effect when the node starts
+    #[serde(default)]
+    pub journal_path: Option<PathBuf>,
+    #[serde(default)]
+    pub transport: TransportKind,
+    /// The transports the node listens on besides the main one. Take effect when
+    /// the node starts
+    #[serde(default)]
+    pub transports: Vec<ListenTransport>,
+    /// The pre-shared key of the private network of the node, 64 hex digits. Only
+    /// the nodes with the same key can connect to it. The private connections use
+    /// TCP instead of QUIC and WebRTC is not used. Takes effect when the node starts
+    #[serde(default)]
+    pub network_key: Option<String>,
+    /// Artificial latency and losses on the links to the given peers. Applied only
+    /// by the nodes of the test runner, ignored by the daemon
+    #[serde(default)]
+    pub link_conditions: Vec<LinkCondition>,
+    #[serde(default)]
+    pub compression: CompressionConfig,
+    #[serde(default)]
+    pub gateway: GatewayConfig,
+    /// The directories whose new and changed files are published automatically
+    #[serde(default)]
+    pub watch_dirs: Vec<WatchDir>,
+    /// How long a watched file must stay unchanged before it is published, so a
+    /// file being written is published once, when it's complete
+    #[serde(default = "default_watch_debounce_ms")]
+    pub watch_debounce_ms: u64,
+    #[serde(default)]
+    pub schedule: ScheduleConfig,
+    #[serde(default)]
+    pub address_policy: AddressPolicy,
+    #[serde(default)]
+    pub upload_limits: UploadLimits,
+    #[serde(default)]
+    pub cache: CacheConfig,
+    #[serde(default)]
+    pub text_index: TextIndexConfig,
+    #[serde(default)]
+    pub chunking: ChunkingConfig,
+    #[serde(default)]
+    pub peer_exchange: PeerExchangeConfig,
+    #[serde(default)]
+    pub record_objects: RecordObjectsConfig,
+}
+
+/// The transport of the swarm of the node
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+pub enum TransportKind {
+    #[default]
+    Quic,
+    /// Reaches only the nodes of the same process, used by the simulations. The
+    /// addresses are `/memory/<port>`
+    Memory,
+}
+
+/// An additional transport of the swarm of the node
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum ListenTransport {
+    /// WebRTC direct, which the browsers can connect to without a certificate signed
+    /// by an authority. Listens on the UDP port, 0 picks a free one. The certificate
+    /// is generated once and kept in the directory of the node, so the addresses of
+    /// the node stay the same
+    WebRtc { port: u16 },
+}
+
+/// The defaults follow the Kademlia spec, records live for 48 hours and are
+/// republished every 22 hours
+fn default_provider_ttl_secs() -> u64 {
+    48 * 60 * 60
+}
+
+fn default_republish_interval_secs() -> u64 {
+    22 * 60 * 60
+}
+
+fn default_max_provides_per_sec() -> u32 {
+    10
+}
+
+fn default_download_parallelism() -> usize {
+    3
+}
+
+fn default_watch_debounce_ms() -> u64 {
+    2000
+}
+
+/// Configuration of the replication of the objects published by the node
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ReplicationConfig {
+    /// Minimal number of providers of a published object. If less peers provide
+    /// the object, it is sent again to the closest peers
+    pub factor: usize,
+    /// How often the number of providers is checked
+    pub check_interval_secs: u64,
+    /// Minimal number of providers of the popular objects, so the peers asking for
+    /// them are not all served by the same few providers
+    #[serde(default = "default_popular_factor")]
+    pub popular_factor: usize,
+    /// How many times this node must have served an object for it to be popular
+    #[serde(default = "default_popular_fetch_count")]
+    pub popular_fetch_count: u64,
+}
+
+impl Default for ReplicationConfig {
+    fn default() -> Self {
+        Self {
+            factor: 5,
+            check_interval_secs: 600,
+            popular_factor: default_popular_factor(),
+            popular_fetch_count: default_popular_fetch_count(),
+        }
+    }
+}
+
+impl ReplicationConfig {
+    /// The number of providers the object should have, served the number of times
+    pub fn factor_for(&self, fetch_count: u64) -> usize {
+        match fetch_count >= self.popular_fetch_count {
+            true => self.factor.max(self.popular_factor),
+            false => self.factor,
+        }
+    }
+}
+
+fn default_popular_factor() -> usize {
+    10
+}
+
+fn default_popular_fetch_count() -> u64 {
+    20
+}
+
+/// The objects the node stores for other peers, as opposed to the published ones
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+pub struct CacheConfig {
+    /// The stored size of the objects above which the least requested ones are
+    /// removed by the cache GC task, 0 is no limit
+    pub max_stored_bytes: u64,
+}
+
+/// Retry policy of the queries to the network which failed with a transient error
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct RetryConfig {
+    /// Number of attempts including the first one
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled for every next one
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff_ms: 500,
+            max_backoff_ms: 8000,
+        }
+    }
+}
+
+/// When the daemon restarts the node which stopped without being asked to
+#[derive(
+    Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, Display, EnumString,
+)]
+#[strum(serialize_all = "kebab-case", ascii_case_insensitive)]
+pub enum RestartPolicy {
+    Never,
+    /// Only after a crash, e.g. when its swarm failed
+    #[default]
+    OnFailure,
+    Always,
+}
+
+/// Supervision of the running node, the crashed node is restarted with a backoff
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct RestartConfig {
+    #[serde(default)]
+    pub policy: RestartPolicy,
+    /// Delay before the first restart, doubled for every next one in the window
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+    /// The most restarts in the window, after them the node stays stopped
+    pub max_restarts: u32,
+    pub window_secs: u64,
+}
+
+impl Default for RestartConfig {
+    fn default() -> Self {
+        Self {
+            policy: RestartPolicy::default(),
+            initial_backoff_ms: 1000,
+            max_backoff_ms: 60_000,
+            max_restarts: 5,
+            window_secs: 600,
+        }
+    }
+}
+
+/// Limits of serving the objects to other peers, the requests over them are
+/// rejected with the time after which they can be sent again. 0 is no limit
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct UploadLimits {
+    /// The bytes served to a single peer in the last hour
+    pub bytes_per_peer_per_hour: u64,
+    /// The objects being sent to the peers at the same time
+    pub max_concurrent_uploads: usize,
+}
+
+impl Default for UploadLimits {
+    fn default() -> Self {
+        Self {
+            bytes_per_peer_per_hour: 1024 * 1024 * 1024,
+            max_concurrent_uploads: 32,
+        }
+    }
+}
+
+/// The full-text index of the plain text files in the vault, searched only by the
+/// operator of the node. Turning it off removes what was indexed
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct TextIndexConfig {
+    pub enabled: bool,
+    /// The bigger files are not indexed
+    pub max_indexed_bytes: u64,
+}
+
+impl Default for TextIndexConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_indexed_bytes: 1024 * 1024,
+        }
+    }
+}
+
+/// The big files are published in chunks, so their next revisions publish only the
+/// changed chunks and the peers having the previous revision download only those.
+/// The nodes of the older versions can't download the files published in chunks
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct ChunkingConfig {
+    pub enabled: bool,
+    /// The smaller files are published whole
+    pub min_file_size: u64,
+    /// Publishes the parity chunks too, so the file can be downloaded even when
+    /// some of its chunks are lost
+    #[serde(default)]
+    pub erasure_coding: Option<ErasureCodingConfig>,
+}
+
+/// Every `data_shards` chunks get `parity_shards` parity chunks, so any
+/// `parity_shards` of them may be lost. The parity costs `parity_shards /
+/// data_shards` of the size of the file
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct ErasureCodingConfig {
+    pub data_shards: u8,
+    pub parity_shards: u8,
+}
+
+impl Default for ChunkingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            min_file_size: 4 * 1024 * 1024,
+            erasure_coding: None,
+        }
+    }
+}
+
+/// The peer exchange, the connected peers share samples of the peers they are
+/// connected to, so the routing table fills up even with few bootstrap nodes. The
+/// exchange runs with the `peer-exchange` scheduled task
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct PeerExchangeConfig {
+    /// Whether the node asks for the peers and answers the requests for them
+    pub enabled: bool,
+    /// The most peers sent in a response and taken from a response
+    pub max_peers: usize,
+    /// A peer asking again sooner is rejected
+    pub min_request_interval_secs: u64,
+}
+
+impl Default for PeerExchangeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_peers: 16,
+            min_request_interval_secs: 60,
+        }
+    }
+}
+
+/// The small public objects are also put in the DHT as Kademlia records, so they
+/// can be fetched with a record query instead of a connection to their provider.
+/// The larger ones are only sent by the providers
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct RecordObjectsConfig {
+    /// Whether the published objects are put as records and the downloads look for them
+    pub enabled: bool,
+    /// The most bytes of an encoded object put as a record
+    pub max_size: usize,
+    /// How many peers must store the record for the put to succeed
+    pub quorum: usize,
+}
+
+impl Default for RecordObjectsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_size: 1024,
+            quorum: 1,
+        }
+    }
+}
+
+/// The HTTP gateway serving the published files to the browsers, at
+/// `http://<bind_address>/object/<id>`. Takes effect when the node starts
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct GatewayConfig {
+    pub enabled: bool,
+    pub bind_address: SocketAddr,
+    /// Whether the files not in the vault are downloaded from the network. Anyone
+    /// reaching the gateway can then make the node download, and get the files the
+    /// providers let the node download
+    #[serde(default)]
+    pub fetch_from_network: bool,
+    /// The most downloads from the network the gateway starts in a minute
+    #[serde(default = "default_gateway_fetches_per_min")]
+    pub max_fetches_per_min: u32,
+}
+
+fn default_gateway_fetches_per_min() -> u32 {
+    10
+}
+
+impl Default for GatewayConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bind_address: SocketAddr::from(([127, 0, 0, 1], 8080)),
+            fetch_from_network: false,
+            max_fetches_per_min: default_gateway_fetches_per_min(),
+        }
+    }
+}
+
+/// How often the scheduler of the node runs its tasks, in seconds. The tasks not
+/// listed run with their default intervals, 0 runs the task only when asked to
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct ScheduleConfig {
+    pub intervals: BTreeMap<ScheduledTask, u64>,
+}
+
+/// Which addresses the node listens on, dials first and tells the other peers about
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct AddressPolicy {
+    /// The IP versions of the default listen addresses, used when the config has
+    /// no external addresses
+    #[serde(default)]
+    pub stack: IpStack,
+    /// The addresses of a peer are dialed in this order of preference, the first
+    /// preference decides and the next ones break the ties
+    #[serde(default)]
+    pub prefer: Vec<AddressPreference>,
+    /// Keep the loopback and link-local addresses in the routing table and in the
+    /// addresses announced to the peers, for the networks on a single machine
+    #[serde(default)]
+    pub allow_unroutable: bool,
+}
+
+#[derive(
+    Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, Display, EnumString,
+)]
+#[strum(serialize_all = "lowercase", ascii_case_insensitive)]
+pub enum IpStack {
+    #[default]
+    Dual,
+    Ipv4,
+    Ipv6,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Display, EnumString)]
+#[strum(serialize_all = "lowercase", ascii_case_insensitive)]
+pub enum AddressPreference {
+    Ipv6,
+    Ipv4,
+    /// The addresses in the private ranges, e.g. the peers in the same LAN
+    Private,
+    Public,
+    Quic,
+}
+
+impl IpStack {
+    pub fn allows_ipv4(&self) -> bool {
+        *self != IpStack::Ipv6
+    }
+
+    pub fn allows_ipv6(&self) -> bool {
+        *self != IpStack::Ipv4
+    }
+}
+
+/// A directory watched by the node, its files are published when they are created
+/// or changed, also in the subdirectories
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct WatchDir {
+    pub path: PathBuf,
+    /// Glob patterns of the files which are not published, matched against the
+    /// name of the file and its path relative to the directory, e.g. `*.tmp`
+    #[serde(default)]
+    pub ignore: Vec<String>,
+    /// Unpublish the previous version of a changed file once the new one is published
+    #[serde(default)]
+    pub unpublish_old: bool,
+}
+
+impl WatchDir {
+    /// Checks the ignore patterns, returns the first invalid one
+    pub fn validate(&self) -> Result<()> {
+        for pattern in &self.ignore {
+            glob::Pattern::new(pattern)
+                .map_err(|e| anyhow!("invalid ignore pattern {pattern}: {e}"))?;
+        }
+        Ok(())
+    }
+
+    /// Whether the file is in the directory and not ignored
+    pub fn should_publish(&self, file: &Path) -> bool {
+        let Ok(relative) = file.strip_prefix(&self.path) else {
+            return false;
+        };
+        let name = file.file_name().map(Path::new).unwrap_or(relative);
+        !self
+            .ignore
+            .iter()
+            .filter_map(|pattern| glob::Pattern::new(pattern).ok())
+            .any(|pattern| pattern.matches_path(name) || pattern.matches_path(relative))
+    }
+}
+
+/// Compression of the objects sent to the peers and stored in the vault
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct CompressionConfig {
+    pub enabled: bool,
+    /// The zstd level, from 1 (fastest) to 22 (smallest)
+    pub level: i32,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            level: 3,
+        }
+    }
+}
+
+impl CompressionConfig {
+    pub fn disabled() -> Self {
+        Self {
+            enabled: false,
+            ..Self::default()
+        }
+    }
+
+    /// The level to compress with, None if the compression is disabled
+    pub fn level(&self) -> Option<i32> {
+        self.enabled.then_some(self.level)
+    }
+}
+
+/// The human readable identity of the node, shown to other peers
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct NodeProfile {
+    pub display_name: String,
+    #[serde(default)]
+    pub description: String,
+    /// ID of the object with the avatar image
+    #[serde(default)]
+    pub avatar: Option<String>,
+}
+
+/// Limits enforced by the connection manager of the swarm
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ConnectionLimits {
+    /// Maximum number of connections established by other peers
+    pub max_inbound: usize,
+    /// Maximum number of connections established by this node
+    pub max_outbound: usize,
+    /// Connections to peers which were not active for this long are closed,
+    /// unless the peer is kept alive (bootstrap nodes, peers transferring objects)
+    pub idle_timeout_secs: u64,
+}
+
+impl Default for ConnectionLimits {
+    fn default() -> Self {
+        Self {
+            max_inbound: 64,
+            max_outbound: 64,
+            idle_timeout_secs: 60,
+        }
+    }
+}
+
+/// The object types which can be turned off per node, with the names used by the UIs.
+/// The other object types (queries, results) are always handled
+pub const KNOWN_MODULES: [(&str, Uuid); 2] = [
+    ("PlainFileObject", PlainFileObject::UUID),
+    ("SignedObject", SignedObject::UUID),
+];
+
+/// The modules handling the objects received from the network
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ModulesConfig {
+    /// UUIDs of the enabled modules, the objects of the disabled ones are rejected.
+    /// All the known modules are enabled if it's missing
+    #[serde(default = "default_enabled_modules")]
+    pub enabled: Vec<Uuid>,
+    /// Settings of the modules as JSON, by the module UUID
+    #[serde(default)]
+    pub settings: HashMap<Uuid, String>,
+}
+
+fn default_enabled_modules() -> Vec<Uuid> {
+    KNOWN_MODULES.iter().map(|(_, uuid)| *uuid).collect()
+}
+
+impl Default for ModulesConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_enabled_modules(),
+            settings: HashMap::new(),
+        }
+    }
+}
+
+impl ModulesConfig {
+    /// Finds the known module by its name or UUID
+    pub fn find(name_or_uuid: &str) -> Option<Uuid> {
+        KNOWN_MODULES
+            .iter()
+            .find(|(name, uuid)| {
+                name.eq_ignore_ascii_case(name_or_uuid) || uuid.to_string() == name_or_uuid
+            })
+            .map(|(_, uuid)| *uuid)
+    }
+
+    pub fn is_enabled(&self, type_uuid: &Uuid) -> bool {
+        let is_module = KNOWN_MODULES.iter().any(|(_, uuid)| uuid == type_uuid);
+        !is_module || self.enabled.contains(type_uuid)
+    }
+
+    pub fn set_enabled(&mut self, uuid: Uuid, enabled: bool) {
+        self.enabled.retain(|u| *u != uuid);
+        if enabled {
+            self.enabled.push(uuid);
+        }
+    }
+
+    pub fn list(&self) -> Vec<ModuleInfo> {
+        KNOWN_MODULES
+            .iter()
+            .map(|(name, uuid)| ModuleInfo {
+                name: name.to_string(),
+                uuid: *uuid,
+                enabled: self.enabled.contains(uuid),
+                settings: self.settings.get(uuid).cloned(),
+                external: false,
+            })
+            .collect()
+    }
 }
 
 impl Default for NodeConfig {
     fn default() -> Self {
         Self {
             bootstrap_nodes: vec![],
+            bootstrap_dns_seeds: vec![],
             external_addresses: vec![],
+            blocked_peers: vec![],
+            allowed_peers: vec![],
+            connection_limits: ConnectionLimits::default(),
+            replication: ReplicationConfig::default(),
+            provider_ttl_secs: default_provider_ttl_secs(),
+            republish_interval_secs: default_republish_interval_secs(),
+            max_provides_per_sec: default_max_provides_per_sec(),
+            autostart: false,
+            download_parallelism: default_download_parallelism(),
+            retry: RetryConfig::default(),
+            restart: RestartConfig::default(),
+            key_protected: false,
+            modules: ModulesConfig::default(),
+            profile: None,
+            key_rotations: vec![],
+            journal_path: None,
+            transport: TransportKind::Quic,
+            transports: vec![],
+            network_key: None,
+            link_conditions: Vec::new(),
+            compression: CompressionConfig::default(),
+            gateway: GatewayConfig::default(),
+            watch_dirs: vec![],
+            watch_debounce_ms: default_watch_debounce_ms(),
+            schedule: ScheduleConfig::default(),
+            address_policy: AddressPolicy::default(),
+            upload_limits: UploadLimits::default(),
+            cache: CacheConfig::default(),
+            text_index: TextIndexConfig::default(),
+            chunking: ChunkingConfig::default(),
+            peer_exchange: PeerExchangeConfig::default(),
+            record_objects: RecordObjectsConfig::default(),
         }
     }
 }
@@ -25,12 +701,79 @@ impl NodeConfig {
         Self {
             bootstrap_nodes,
             external_addresses,
+            ..Default::default()
+        }
+    }
+
+    /// The pre-shared key of the private network of the node, if it's in one
+    pub fn network_key(&self) -> Result<Option<[u8; 32]>> {
+        self.network_key
+            .as_deref()
+            .map(parse_network_key)
+            .transpose()
+    }
+
+    /// Checks the peer against the blocklist and the allowlist
+    pub fn is_peer_allowed(&self, peer_id: &PeerId) -> bool {
+        if self.blocked_peers.contains(peer_id) {
+            return false;
+        }
+
+        self.allowed_peers.is_empty() || self.allowed_peers.contains(peer_id)
+    }
+
+    pub fn block_peer(&mut self, peer_id: PeerId) {
+        if !self.blocked_peers.contains(&peer_id) {
+            self.blocked_peers.push(peer_id);
+        }
+    }
+
+    pub fn unblock_peer(&mut self, peer_id: &PeerId) {
+        self.blocked_peers.retain(|p| p != peer_id);
+    }
+
+    /// How often the task runs, None if only when asked to. The replication runs
+    /// every `replication.check_interval_secs` unless the schedule says otherwise
+    pub fn task_interval(&self, task: ScheduledTask) -> Option<Duration> {
+        let default_secs = match task {
+            // The swarm republishes the provider records by itself
+            ScheduledTask::Reprovide => 0,
+            ScheduledTask::Replication => self.replication.check_interval_secs,
+            ScheduledTask::MailboxFetch => 15 * 60,
+            ScheduledTask::BootstrapHealth => 60,
+            ScheduledTask::CacheGc if self.cache.max_stored_bytes > 0 => 60 * 60,
+            ScheduledTask::CacheGc => 0,
+            ScheduledTask::PeerExchange if self.peer_exchange.enabled => 5 * 60,
+            ScheduledTask::PeerExchange => 0,
+            ScheduledTask::StatsSample => 5 * 60,
+        };
+        let secs = self
+            .schedule
+            .intervals
+            .get(&task)
+            .copied()
+            .unwrap_or(default_secs);
+        (secs > 0).then(|| Duration::from_secs(secs))
+    }
+
+    /// Adds the directory to the watch list, replacing the one with the same path
+    pub fn add_watch_dir(&mut self, dir: WatchDir) {
+        match self.watch_dirs.iter_mut().find(|d| d.path == dir.path) {
+            Some(existing) => *existing = dir,
+            None => self.watch_dirs.push(dir),
         }
     }
 
+    /// Returns false if the directory was not watched
+    pub fn remove_watch_dir(&mut self, path: &Path) -> bool {
+        let len = self.watch_dirs.len();
+        self.watch_dirs.retain(|d| d.path != path);
+        self.watch_dirs.len() != len
+    }
+
     pub async fn save(&self, path: &Path) -> Result<()> {
         let content = serde_json::to_string(&self)?;
-        tokio::fs::write(path, content)
+        atomic_file::write(path, content)
             .await
             .inspect_err(|e| error!(err = e.to_string(), "could not write node config"))?;
 
@@ -49,7 +792,24 @@ impl NodeConfig {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+/// Conditions injected on the link to a peer, see `NodeConfig::link_conditions`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct LinkCondition {
+    #[serde(
+        serialize_with = "serialize_peer_id",
+        deserialize_with = "deserialize_peer_id"
+    )]
+    pub peer_id: PeerId,
+    /// Added to the first message of every stream
+    pub latency_millis: u64,
+    /// The latency is varied by up to this much either way
+    pub jitter_millis: u64,
+    /// The share of the streams which are lost, from 0 to 1
+    #[serde(deserialize_with = "deserialize_drop_rate")]
+    pub drop_rate: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct BootstrapNode {
     #[serde(
         serialize_with = "serialize_peer_id",
@@ -72,6 +832,19 @@ impl BootstrapNode {
     }
 }
 
+/// Decodes the pre-shared key of a private network from its hex digits
+pub fn parse_network_key(key: &str) -> Result<[u8; 32]> {
+    let bytes = hex::decode(key.trim()).map_err(|e| anyhow!("invalid network key: {e}"))?;
+    bytes
+        .try_into()
+        .map_err(|_| anyhow!("the network key must have 64 hex digits"))
+}
+
+/// A random pre-shared key for a new private network
+pub fn generate_network_key() -> String {
+    hex::encode(rand::random::<[u8; 32]>())
+}
+
 fn serialize_peer_id<S>(peer_id: &PeerId, serializer: S) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
@@ -87,3 +860,195 @@ where
     PeerId::from_str(&peer_id_base58)
         .map_err(|e| serde::de::Error::custom(format!("could not deserialize PeerId: {}", e)))
 }
+
+fn deserialize_drop_rate<'de, D>(deserializer: D) -> Result<f64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let drop_rate = f64::deserialize(deserializer)?;
+    if !(0.0..=1.0).contains(&drop_rate) {
+        return Err(serde::de::Error::custom(format!(
+            "drop rate must be from 0 to 1, got {drop_rate}"
+        )));
+    }
+    Ok(drop_rate)
+}
+
+fn serialize_peer_ids<S>(peer_ids: &Vec<PeerId>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.collect_seq(peer_ids.iter().map(|p| p.to_base58()))
+}
+
+fn deserialize_peer_ids<'de, D>(deserializer: D) -> Result<Vec<PeerId>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let peer_ids_base58 = Vec::<String>::deserialize(deserializer)?;
+    peer_ids_base58
+        .iter()
+        .map(|p| {
+            PeerId::from_str(p).map_err(|e| {
+                serde::de::Error::custom(format!("could not deserialize PeerId: {}", e))
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn peer_filter_test() {
+        let mut config = NodeConfig::default();
+        let peer_a = PeerId::random();
+        let peer_b = PeerId::random();
+
+        assert!(config.is_peer_allowed(&peer_a));
+
+        config.block_peer(peer_a);
+        assert!(!config.is_peer_allowed(&peer_a));
+        assert!(config.is_peer_allowed(&peer_b));
+
+        config.unblock_peer(&peer_a);
+        config.allowed_peers.push(peer_b);
+        assert!(!config.is_peer_allowed(&peer_a));
+        assert!(config.is_peer_allowed(&peer_b));
+    }
+
+    #[test]
+    fn missing_peer_lists_test() {
+        let config: NodeConfig =
+            serde_json::from_str(r#"{"bootstrap_nodes":[],"external_addresses":[]}"#).unwrap();
+
+        assert!(config.blocked_peers.is_empty());
+        assert!(config.allowed_peers.is_empty());
+        assert!(config.bootstrap_dns_seeds.is_empty());
+        assert_eq!(config.modules, ModulesConfig::default());
+
+        let modules: ModulesConfig = serde_json::from_str(r#"{"settings":{}}"#).unwrap();
+        assert_eq!(modules, ModulesConfig::default());
+    }
+
+    #[test]
+    fn modules_test() {
+        let mut modules = ModulesConfig::default();
+        let signed = ModulesConfig::find("signedobject").unwrap();
+        assert_eq!(signed, SignedObject::UUID);
+        assert_eq!(
+            ModulesConfig::find(&PlainFileObject::UUID.to_string()),
+            Some(PlainFileObject::UUID)
+        );
+        assert_eq!(ModulesConfig::find("unknown"), None);
+
+        modules.set_enabled(signed, false);
+        assert!(!modules.is_enabled(&signed));
+        assert!(modules.is_enabled(&PlainFileObject::UUID));
+        // Not a module, always handled
+        assert!(modules.is_enabled(&crate::proto::QueryObject::UUID));
+
+        modules.set_enabled(signed, true);
+        modules.set_enabled(signed, true);
+        assert_eq!(modules.enabled.len(), KNOWN_MODULES.len());
+    }
+
+    #[test]
+    fn task_interval_test() {
+        let mut config = NodeConfig::default();
+        assert_eq!(config.task_interval(ScheduledTask::Reprovide), None);
+        assert_eq!(
+            config.task_interval(ScheduledTask::Replication),
+            Some(Duration::from_secs(config.replication.check_interval_secs))
+        );
+
+        config
+            .schedule
+            .intervals
+            .insert(ScheduledTask::Reprovide, 3600);
+        config
+            .schedule
+            .intervals
+            .insert(ScheduledTask::BootstrapHealth, 0);
+        assert_eq!(
+            config.task_interval(ScheduledTask::Reprovide),
+            Some(Duration::from_secs(3600))
+        );
+        assert_eq!(config.task_interval(ScheduledTask::BootstrapHealth), None);
+
+        let json = serde_json::to_string(&config.schedule).unwrap();
+        assert_eq!(
+            json,
+            r#"{"intervals":{"reprovide":3600,"bootstrap-health":0}}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<ScheduleConfig>(&json).unwrap(),
+            config.schedule
+        );
+        assert_eq!(
+            "mailbox-fetch".parse::<ScheduledTask>().unwrap(),
+            ScheduledTask::MailboxFetch
+        );
+    }
+
+    #[test]
+    fn network_key_test() {
+        let key = generate_network_key();
+        assert_eq!(key.len(), 64);
+        let config = NodeConfig {
+            network_key: Some(key.clone()),
+            ..Default::default()
+        };
+        assert_eq!(hex::encode(config.network_key().unwrap().unwrap()), key);
+        assert_eq!(NodeConfig::default().network_key().unwrap(), None);
+        assert!(parse_network_key("abcd").is_err());
+        assert!(parse_network_key(&"zz".repeat(32)).is_err());
+    }
+
+    #[test]
+    fn watch_dir_test() {
+        let dir = WatchDir {
+            path: PathBuf::from("/home/user/shared"),
+            ignore: vec!["*.tmp".to_string(), "drafts/*".to_string()],
+            unpublish_old: false,
+        };
+        assert!(dir.validate().is_ok());
+        assert!(dir.should_publish(Path::new("/home/user/shared/notes.txt")));
+        assert!(dir.should_publish(Path::new("/home/user/shared/photos/cat.jpg")));
+        assert!(!dir.should_publish(Path::new("/home/user/shared/photos/cat.tmp")));
+        assert!(!dir.should_publish(Path::new("/home/user/shared/drafts/post.md")));
+        assert!(!dir.should_publish(Path::new("/home/user/other/notes.txt")));
+
+        let invalid = WatchDir {
+            ignore: vec!["[".to_string()],
+            ..dir.clone()
+        };
+        assert!(invalid.validate().is_err());
+
+        let mut config = NodeConfig::default();
+        config.add_watch_dir(dir.clone());
+        config.add_watch_dir(WatchDir {
+            unpublish_old: true,
+            ..dir.clone()
+        });
+        assert_eq!(config.watch_dirs.len(), 1);
+        assert!(config.watch_dirs[0].unpublish_old);
+        assert!(config.remove_watch_dir(&dir.path));
+        assert!(!config.remove_watch_dir(&dir.path));
+    }
+
+    #[test]
+    fn link_condition_drop_rate_test() {
+        let condition = |drop_rate: &str| {
+            serde_json::from_str::<LinkCondition>(&format!(
+                r#"{{"peer_id":"{}","latency_millis":0,"jitter_millis":0,"drop_rate":{drop_rate}}}"#,
+                PeerId::random().to_base58()
+            ))
+        };
+        assert_eq!(condition("0.5").unwrap().drop_rate, 0.5);
+        assert!(condition("1").is_ok());
+        assert!(condition("-0.1").is_err());
+        assert!(condition("1.5").is_err());
+    }
+}
@@ -0,0 +1,219 @@
+use std::ffi::{OsStr, OsString};
+use std::fs::{File, Metadata, OpenOptions};
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd};
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt};
+use std::path::Path;
+
+use nix::fcntl::{openat, renameat, OFlag};
+use nix::sys::stat::Mode;
+use nix::unistd::{unlinkat, UnlinkatFlags};
+
+use crate::atomic_file;
+
+///! The module opens the files at the paths given to the daemon by its users. The
+///! owner is checked on the opened file, which is then read or written through the
+///! same handle, so the path can't be swapped for another file after the check.
+///! With the user, symlinks are not followed, the user may own the link but not its
+///! target.
+///!
+///! A file which may not exist yet is created through the handle of its directory,
+///! which must be owned by the user, so the daemon never writes into the
+///! directories of the others. Without the user, e.g. for the admins, nothing is
+///! checked.
+
+/// The permissions of the files created by the daemon
+const CREATED_FILE_MODE: u32 = 0o644;
+
+/// Opens the file for reading. With the UID of the user, the file must be owned by
+/// the user
+pub fn open_read(path: &Path, uid: Option<u32>) -> io::Result<File> {
+    let mut options = OpenOptions::new();
+    options.read(true);
+    if uid.is_some() {
+        // Opening a FIFO of the user would block until someone writes to it
+        options.custom_flags(nix::libc::O_NOFOLLOW | nix::libc::O_NONBLOCK);
+    }
+    let file = options.open(path)?;
+    let metadata = file.metadata()?;
+    if !metadata.is_file() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not a file", path.display()),
+        ));
+    }
+    check_owner(&metadata, uid, path)?;
+
+    Ok(file)
+}
+
+/// A file which may not exist yet, with its directory opened. The file is created
+/// and replaced only through the handle of the directory
+#[derive(Debug)]
+pub struct FileTarget {
+    dir: File,
+    file_name: OsString,
+    uid: Option<u32>,
+}
+
+impl FileTarget {
+    /// Opens the directory of the file. With the UID of the user, the directory must
+    /// be owned by the user
+    pub fn open(path: &Path, uid: Option<u32>) -> io::Result<Self> {
+        let file_name = path.file_name().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} has no file name", path.display()),
+            )
+        })?;
+        let dir_path = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+
+        let mut flags = nix::libc::O_DIRECTORY;
+        if uid.is_some() {
+            flags |= nix::libc::O_NOFOLLOW;
+        }
+        let dir = OpenOptions::new()
+            .read(true)
+            .custom_flags(flags)
+            .open(dir_path)?;
+        check_owner(&dir.metadata()?, uid, dir_path)?;
+
+        Ok(FileTarget {
+            dir,
+            file_name: file_name.to_owned(),
+            uid,
+        })
+    }
+
+    /// Opens the file for appending, it's created if it doesn't exist. The file must
+    /// be owned by the user, or be the one created by the daemon
+    pub fn open_append(&self) -> io::Result<File> {
+        let file = self.open_at(
+            &self.file_name,
+            OFlag::O_WRONLY | OFlag::O_APPEND | OFlag::O_CREAT,
+        )?;
+        let metadata = file.metadata()?;
+        if !metadata.is_file() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{:?} is not a file", self.file_name),
+            ));
+        }
+        // A file of the daemon with another link elsewhere is not the one it created
+        let created_by_daemon =
+            metadata.uid() == nix::unistd::geteuid().as_raw() && metadata.nlink() == 1;
+        if !created_by_daemon {
+            check_owner(&metadata, self.uid, Path::new(&self.file_name))?;
+        }
+
+        Ok(file)
+    }
+
+    /// Creates a new temporary file in the directory, for the data moved over the
+    /// file with `persist`. Returns the file and its name
+    pub fn create_temp(&self) -> io::Result<(File, OsString)> {
+        let temp_name = atomic_file::temp_file_name(&self.file_name);
+        let file = self.open_at(&temp_name, OFlag::O_WRONLY | OFlag::O_CREAT | OFlag::O_EXCL)?;
+
+        Ok((file, temp_name))
+    }
+
+    /// Moves the synced temporary file over the file. The directory is synced, so
+    /// the rename itself survives a crash
+    pub fn persist(&self, temp_name: &OsStr) -> io::Result<()> {
+        let dir = Some(self.dir.as_raw_fd());
+        renameat(dir, temp_name, dir, self.file_name.as_os_str())?;
+        self.dir.sync_all()
+    }
+
+    /// Removes the file with the name from the directory
+    pub fn remove(&self, name: &OsStr) -> io::Result<()> {
+        unlinkat(Some(self.dir.as_raw_fd()), name, UnlinkatFlags::NoRemoveDir)?;
+        Ok(())
+    }
+
+    fn open_at(&self, name: &OsStr, flags: OFlag) -> io::Result<File> {
+        let fd = openat(
+            Some(self.dir.as_raw_fd()),
+            name,
+            flags | OFlag::O_NOFOLLOW | OFlag::O_CLOEXEC,
+            Mode::from_bits_truncate(CREATED_FILE_MODE),
+        )?;
+        // SAFETY: The descriptor was just opened and nothing else owns it
+        Ok(unsafe { File::from_raw_fd(fd) })
+    }
+}
+
+fn check_owner(metadata: &Metadata, uid: Option<u32>, path: &Path) -> io::Result<()> {
+    match uid {
+        Some(uid) if metadata.uid() != uid => Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("{} is not owned by the user", path.display()),
+        )),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn open_read_test() {
+        let tmp_dir = TempDir::new("liberum_tests").unwrap();
+        let uid = nix::unistd::geteuid().as_raw();
+        let file = tmp_dir.path().join("file");
+        std::fs::write(&file, "abc").unwrap();
+
+        let mut content = String::new();
+        open_read(&file, Some(uid))
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content, "abc");
+        assert!(open_read(&file, Some(uid + 1)).is_err());
+        assert!(open_read(tmp_dir.path(), Some(uid)).is_err());
+
+        let link = tmp_dir.path().join("link");
+        std::os::unix::fs::symlink(&file, &link).unwrap();
+        assert!(open_read(&link, Some(uid)).is_err());
+        assert!(open_read(&link, None).is_ok());
+    }
+
+    #[test]
+    fn file_target_test() {
+        let tmp_dir = TempDir::new("liberum_tests").unwrap();
+        let uid = nix::unistd::geteuid().as_raw();
+        let path = tmp_dir.path().join("file");
+        assert!(FileTarget::open(&path, Some(uid + 1)).is_err());
+
+        let target = FileTarget::open(&path, Some(uid)).unwrap();
+        let (mut temp, temp_name) = target.create_temp().unwrap();
+        temp.write_all(b"abc").unwrap();
+        temp.sync_all().unwrap();
+        target.persist(&temp_name).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "abc");
+        assert_eq!(std::fs::read_dir(tmp_dir.path()).unwrap().count(), 1);
+
+        target.open_append().unwrap().write_all(b"def").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "abcdef");
+
+        // A symlink in place of the file is neither followed nor replaced
+        let link_dir = tmp_dir.path().join("link");
+        std::os::unix::fs::symlink(tmp_dir.path(), &link_dir).unwrap();
+        assert!(FileTarget::open(&link_dir.join("file"), Some(uid)).is_err());
+        let other = tmp_dir.path().join("other");
+        std::fs::write(&other, "other").unwrap();
+        std::os::unix::fs::symlink(&other, tmp_dir.path().join("journal")).unwrap();
+        let target = FileTarget::open(&tmp_dir.path().join("journal"), Some(uid)).unwrap();
+        assert!(target.open_append().is_err());
+        assert_eq!(std::fs::read_to_string(&other).unwrap(), "other");
+    }
+}
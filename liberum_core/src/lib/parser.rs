@@ -1,10 +1,16 @@
 use crate::proto::*;
+use crate::types::ObjectLayer;
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use strum_macros::Display;
 use tracing::debug;
 use uuid::Uuid;
 
+/// The objects nested deeper are not described, as a crafted object may nest them
+/// without an end
+const MAX_DESCRIBED_LAYERS: usize = 16;
+
 #[derive(Serialize, Deserialize, Debug, Display)]
 pub enum ObjectEnum {
     Group(GroupObject),
@@ -16,6 +22,13 @@ pub enum ObjectEnum {
     SimpleIDQuery(SimpleIDQuery),
     Query(QueryObject),
     Result(ResultObject),
+    QueryResult(QueryResultObject),
+    Profile(ProfileObject),
+    Tag(TagObject),
+    TagQuery(TagQuery),
+    Version(VersionObject),
+    Chunk(ChunkObject),
+    Manifest(ManifestObject),
 }
 impl UUIDTyped for ObjectEnum {
     // TODO couldn't we do this better? Is it possible to force a member of an enum to implement a trait??
@@ -30,6 +43,13 @@ impl UUIDTyped for ObjectEnum {
             ObjectEnum::SimpleIDQuery(simple_idquery) => simple_idquery.get_type_uuid(),
             ObjectEnum::Query(query_object) => query_object.get_type_uuid(),
             ObjectEnum::Result(result_object) => result_object.get_type_uuid(),
+            ObjectEnum::QueryResult(query_result) => query_result.get_type_uuid(),
+            ObjectEnum::Profile(profile) => profile.get_type_uuid(),
+            ObjectEnum::Tag(tag) => tag.get_type_uuid(),
+            ObjectEnum::TagQuery(query) => query.get_type_uuid(),
+            ObjectEnum::Version(version) => version.get_type_uuid(),
+            ObjectEnum::Chunk(chunk) => chunk.get_type_uuid(),
+            ObjectEnum::Manifest(manifest) => manifest.get_type_uuid(),
         }
     }
 }
@@ -38,7 +58,8 @@ pub async fn parse_typed(object: TypedObject) -> Result<ObjectEnum> {
     match object.uuid {
         GroupObject::UUID => {
             debug!("Parser: Group object: {:?}", object);
-            todo!()
+            let group = TypedObject::try_from_typed(&object)?;
+            Ok(ObjectEnum::Group(group))
         }
         SignedObject::UUID => {
             debug!("Parser: Signed object: {:?}", object);
@@ -75,9 +96,347 @@ pub async fn parse_typed(object: TypedObject) -> Result<ObjectEnum> {
             let obj = TypedObject::try_from_typed(&object)?;
             Ok(ObjectEnum::DeleteObject(obj))
         }
+        QueryResultObject::UUID => {
+            debug!("Parser: Got Query Result object: {:?}", object);
+            let obj = TypedObject::try_from_typed(&object)?;
+            Ok(ObjectEnum::QueryResult(obj))
+        }
+        ProfileObject::UUID => {
+            debug!("Parser: Got Profile object: {:?}", object);
+            let obj = TypedObject::try_from_typed(&object)?;
+            Ok(ObjectEnum::Profile(obj))
+        }
+        TagObject::UUID => {
+            debug!("Parser: Got Tag object: {:?}", object);
+            let obj = TypedObject::try_from_typed(&object)?;
+            Ok(ObjectEnum::Tag(obj))
+        }
+        TagQuery::UUID => {
+            debug!("Parser: Got Tag Query object: {:?}", object);
+            let obj = TypedObject::try_from_typed(&object)?;
+            Ok(ObjectEnum::TagQuery(obj))
+        }
+        VersionObject::UUID => {
+            debug!("Parser: Got Version object: {:?}", object);
+            let obj = TypedObject::try_from_typed(&object)?;
+            Ok(ObjectEnum::Version(obj))
+        }
+        ChunkObject::UUID => {
+            debug!("Parser: Got Chunk object: {:?}", object);
+            let obj = TypedObject::try_from_typed(&object)?;
+            Ok(ObjectEnum::Chunk(obj))
+        }
+        ManifestObject::UUID => {
+            debug!("Parser: Got Manifest object: {:?}", object);
+            let obj = TypedObject::try_from_typed(&object)?;
+            Ok(ObjectEnum::Manifest(obj))
+        }
         _ => {
             debug!("Parser: Unknown object: {:?}", object);
             Ok(ObjectEnum::Empty(EmptyObject {}))
         }
     }
 }
+
+/// Whether the object type is parsed by `parse_typed`, the other types are unknown
+/// to the daemon and may be handled by the external modules
+pub fn is_known_type(uuid: &Uuid) -> bool {
+    [
+        GroupObject::UUID,
+        SignedObject::UUID,
+        PlainFileObject::UUID,
+        EmptyObject::UUID,
+        SimpleIDQuery::UUID,
+        QueryObject::UUID,
+        ResultObject::UUID,
+        DeleteObjectQuery::UUID,
+        QueryResultObject::UUID,
+        ProfileObject::UUID,
+        TagObject::UUID,
+        TagQuery::UUID,
+        VersionObject::UUID,
+        ChunkObject::UUID,
+        ManifestObject::UUID,
+    ]
+    .contains(uuid)
+}
+
+/// The human readable name of the object type, None if the type is unknown
+pub fn type_name(uuid: &Uuid) -> Option<&'static str> {
+    let name = match *uuid {
+        GroupObject::UUID => "group object",
+        GroupPostObject::UUID => "group post",
+        SignedObject::UUID => "signed",
+        PlainFileObject::UUID => "plain file",
+        EmptyObject::UUID => "empty",
+        SimpleIDQuery::UUID => "simple ID query",
+        QueryObject::UUID => "query",
+        ResultObject::UUID => "result",
+        DeleteObjectQuery::UUID => "delete query",
+        QueryResultObject::UUID => "query result",
+        ProfileObject::UUID => "profile",
+        TagObject::UUID => "tags",
+        TagQuery::UUID => "tag query",
+        VersionObject::UUID => "version",
+        ChunkObject::UUID => "chunk",
+        ManifestObject::UUID => "manifest",
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// Walks the type cascade of the object, e.g. signed → group object → signed →
+/// group post, decoding the fields of the known types. The walk ends at an object
+/// which doesn't wrap another one, or which can't be decoded
+pub fn describe(object: &TypedObject) -> Vec<ObjectLayer> {
+    let mut layers = Vec::new();
+    let mut next = Some(object.clone());
+    while let Some(object) = next.take() {
+        if layers.len() == MAX_DESCRIBED_LAYERS {
+            break;
+        }
+        let (properties, created_at, inner) = match decode_layer(&object) {
+            Ok(decoded) => decoded,
+            Err(e) => {
+                debug!(err = e.to_string(), "Describer: Undecodable object");
+                (vec![], None, None)
+            }
+        };
+        layers.push(ObjectLayer {
+            type_id: object.uuid,
+            type_name: type_name(&object.uuid).map(str::to_string),
+            size: object.data.len() as u64,
+            properties,
+            created_at,
+        });
+        next = inner;
+    }
+    layers
+}
+
+type DecodedLayer = (
+    Vec<(String, String)>,
+    Option<SystemTime>,
+    Option<TypedObject>,
+);
+
+/// The fields of the object, when it was made and the object it wraps
+fn decode_layer(object: &TypedObject) -> Result<DecodedLayer> {
+    let field = |name: &str, value: String| (name.to_string(), value);
+
+    let decoded = match object.uuid {
+        SignedObject::UUID => {
+            let signed: SignedObject = TypedObject::try_from_typed(object)?;
+            let fields = vec![field(
+                "signature_size",
+                signed.signature.bytes.len().to_string(),
+            )];
+            (fields, None, Some(signed.object))
+        }
+        GroupObject::UUID => {
+            let group: GroupObject = TypedObject::try_from_typed(object)?;
+            let fields = vec![field("group", group.group.to_string())];
+            (fields, None, Some(group.object.into()))
+        }
+        GroupPostObject::UUID => {
+            let post: GroupPostObject = TypedObject::try_from_typed(object)?;
+            let author: libp2p::identity::PublicKey = post.author_key.try_into()?;
+            let fields = vec![
+                field("author", author.to_peer_id().to_base58()),
+                field("member", post.access_token.is_some().to_string()),
+            ];
+            let posted_at = UNIX_EPOCH + Duration::from_secs(post.posted_at);
+            (fields, Some(posted_at), None)
+        }
+        PlainFileObject::UUID => {
+            let file: PlainFileObject = TypedObject::try_from_typed(object)?;
+            let fields = vec![
+                field("name", file.name),
+                field("content_size", file.content.len().to_string()),
+            ];
+            (fields, None, None)
+        }
+        ChunkObject::UUID => {
+            let chunk: ChunkObject = TypedObject::try_from_typed(object)?;
+            let fields = vec![field("content_size", chunk.content.len().to_string())];
+            (fields, None, None)
+        }
+        ManifestObject::UUID => {
+            let manifest: ManifestObject = TypedObject::try_from_typed(object)?;
+            let mut fields = vec![
+                field("name", manifest.name),
+                field("content_size", manifest.size.to_string()),
+                field("chunks", manifest.chunks.len().to_string()),
+            ];
+            if let Some(coding) = manifest.coding {
+                fields.push(field(
+                    "erasure_coding",
+                    format!("{}+{}", coding.data_shards, coding.parity_shards),
+                ));
+            }
+            (fields, None, None)
+        }
+        VersionObject::UUID => {
+            let version: VersionObject = TypedObject::try_from_typed(object)?;
+            let fields = vec![
+                field("object", version.object.to_string()),
+                field("previous", version.previous.to_string()),
+            ];
+            (fields, None, None)
+        }
+        TagObject::UUID => {
+            let tag: TagObject = TypedObject::try_from_typed(object)?;
+            let fields = vec![
+                field("object", tag.object.to_string()),
+                field("tags", tag.tags.join(", ")),
+            ];
+            (fields, None, None)
+        }
+        ProfileObject::UUID => {
+            let profile: ProfileObject = TypedObject::try_from_typed(object)?;
+            let fields = vec![
+                field("peer_id", profile.peer_id()?.to_base58()),
+                field("display_name", profile.display_name),
+            ];
+            (fields, None, None)
+        }
+        _ => (vec![], None, None),
+    };
+    Ok(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use libp2p::kad::RecordKey;
+    use proptest::prelude::*;
+
+    /// The types parsed by `parse_typed` and any other
+    fn type_uuid() -> impl Strategy<Value = Uuid> {
+        prop_oneof![
+            Just(GroupObject::UUID),
+            Just(SignedObject::UUID),
+            Just(PlainFileObject::UUID),
+            Just(SimpleIDQuery::UUID),
+            Just(QueryObject::UUID),
+            Just(ResultObject::UUID),
+            Just(DeleteObjectQuery::UUID),
+            Just(QueryResultObject::UUID),
+            Just(ProfileObject::UUID),
+            Just(TagObject::UUID),
+            Just(TagQuery::UUID),
+            Just(VersionObject::UUID),
+            Just(ChunkObject::UUID),
+            Just(ManifestObject::UUID),
+            any::<u128>().prop_map(Uuid::from_u128),
+        ]
+    }
+
+    /// Objects with random data, wrapped in a few levels of signed objects
+    fn typed_object() -> impl Strategy<Value = TypedObject> {
+        let leaf = (type_uuid(), prop::collection::vec(any::<u8>(), 0..256))
+            .prop_map(|(uuid, data)| TypedObject { uuid, data });
+        leaf.prop_recursive(4, 16, 1, |inner| {
+            (inner, prop::collection::vec(any::<u8>(), 0..80)).prop_map(|(object, bytes)| {
+                SignedObject {
+                    object,
+                    signature: Signature { bytes },
+                }
+                .into()
+            })
+        })
+    }
+
+    fn valid_bytes(name: String, content: Vec<u8>) -> Vec<u8> {
+        let file: TypedObject = PlainFileObject { name, content }.into();
+        let signed: TypedObject = SignedObject {
+            object: file,
+            signature: Signature { bytes: vec![1; 64] },
+        }
+        .into();
+        signed.try_into().unwrap()
+    }
+
+    proptest! {
+        #[test]
+        fn parse_arbitrary_objects(object in typed_object()) {
+            // Unwraps the signed objects like the handlers of the requests do
+            let mut object = object;
+            while let Ok(ObjectEnum::Signed(signed)) = block_on(parse_typed(object.clone())) {
+                object = signed.object;
+            }
+        }
+
+        #[test]
+        fn describe_arbitrary_objects(object in typed_object()) {
+            prop_assert!(!describe(&object).is_empty());
+        }
+
+        #[test]
+        fn parse_wrong_types(uuid in type_uuid(), name in ".{0,16}", content: Vec<u8>) {
+            let data = bincode::serialize(&PlainFileObject { name, content }).unwrap();
+            let _ = block_on(parse_typed(TypedObject { uuid, data }));
+        }
+
+        #[test]
+        fn decode_truncated_objects(name in ".{0,16}", content: Vec<u8>, cut: prop::sample::Index) {
+            let bytes = valid_bytes(name, content);
+            let truncated = bytes[..cut.index(bytes.len())].to_vec();
+            prop_assert!(TypedObject::try_from(&truncated).is_err());
+
+            let mut trailing = bytes.clone();
+            trailing.push(0);
+            prop_assert!(TypedObject::try_from(&trailing).is_err());
+            prop_assert!(TypedObject::try_from(&bytes).is_ok());
+        }
+
+        #[test]
+        fn decode_arbitrary_records(key: Vec<u8>, value: Vec<u8>) {
+            let key = RecordKey::new(&key);
+            prop_assert!(ProfileObject::from_record(&key, &value).is_err());
+            prop_assert!(RotationObject::from_record(&key, &value).is_err());
+            let public_key = SerializablePublicKey { key: value };
+            let _: Result<libp2p::identity::PublicKey> = public_key.try_into();
+        }
+    }
+
+    #[test]
+    fn describe_test() {
+        let file: TypedObject = PlainFileObject {
+            name: "file".to_string(),
+            content: vec![1; 10],
+        }
+        .into();
+        let signed: TypedObject = SignedObject {
+            object: file,
+            signature: Signature { bytes: vec![1; 64] },
+        }
+        .into();
+
+        let layers = describe(&signed);
+        assert_eq!(layers.len(), 2);
+        assert_eq!(layers[0].type_name.as_deref(), Some("signed"));
+        assert_eq!(layers[1].type_id, PlainFileObject::UUID);
+        assert!(layers[1]
+            .properties
+            .contains(&("name".to_string(), "file".to_string())));
+
+        let unknown = TypedObject {
+            uuid: Uuid::from_u128(1),
+            data: vec![1, 2, 3],
+        };
+        let layers = describe(&unknown);
+        assert_eq!(layers.len(), 1);
+        assert_eq!(layers[0].type_name, None);
+        assert_eq!(layers[0].size, 3);
+    }
+
+    #[test]
+    fn huge_length_rejected() {
+        // A UUID followed by the length of the data far beyond the limit
+        let mut bytes = bincode::serialize(&PlainFileObject::UUID).unwrap();
+        bytes.extend_from_slice(&u64::MAX.to_le_bytes());
+        assert!(TypedObject::try_from(&bytes).is_err());
+    }
+}
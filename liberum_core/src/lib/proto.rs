@@ -1,12 +1,35 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use std::{fmt::Display, path::Path};
 
 use anyhow::bail;
 use anyhow::{anyhow, Error, Result};
-use libp2p::identity::PublicKey;
+use bincode::Options;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use curve25519_dalek::edwards::CompressedEdwardsY;
+use libp2p::identity::{Keypair, PublicKey};
 use libp2p::kad::RecordKey;
+use libp2p::PeerId;
+use rand::RngCore;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, Bytes};
+use sha2::{Digest, Sha512};
+use tokio::io::AsyncReadExt;
+
+use crate::canonical;
+use crate::types::{MessageContent, SignatureStatus};
 use uuid::{uuid, Uuid};
 
+/// The ID of an object, the blake3 digest of the object in its canonical encoding,
+/// or of its bincode encoding for the legacy objects.
+///
+/// The digest is stored, sent and shown without the algorithm, as it was before the
+/// IDs described themselves. The IDs are also accepted in the multihash encoding,
+/// the multicodec code of the algorithm and the length of the digest as varints
+/// followed by the digest. The IDs hashed with other algorithms are only valid in
+/// the multihash encoding, so the existing IDs stay the same when the algorithm
+/// changes.
 #[derive(Serialize, Deserialize, Debug, Hash, PartialEq, Clone, Eq)]
 pub struct Hash {
     pub bytes: [u8; 32],
@@ -15,11 +38,70 @@ pub type UserId = Hash;
 pub type GroupId = Hash;
 pub type ObjectId = Hash;
 pub type Content = Vec<u8>;
+pub type Multihash = libp2p::multihash::Multihash<64>;
+
+/// The algorithms of the hashes in the multihash encoding
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    Blake3,
+    Sha2_256,
+}
+
+impl HashAlgorithm {
+    /// The multicodec code of the algorithm
+    pub const fn code(self) -> u64 {
+        match self {
+            HashAlgorithm::Blake3 => 0x1e,
+            HashAlgorithm::Sha2_256 => 0x12,
+        }
+    }
+
+    pub fn from_code(code: u64) -> Option<HashAlgorithm> {
+        [HashAlgorithm::Blake3, HashAlgorithm::Sha2_256]
+            .into_iter()
+            .find(|algorithm| algorithm.code() == code)
+    }
+
+    /// Hashes the data, the result is in the multihash encoding
+    pub fn hash(self, data: &[u8]) -> Multihash {
+        let digest: [u8; 32] = match self {
+            HashAlgorithm::Blake3 => *blake3::hash(data).as_bytes(),
+            HashAlgorithm::Sha2_256 => sha2::Sha256::digest(data).into(),
+        };
+        Multihash::wrap(self.code(), &digest).expect("Digest to fit in the multihash")
+    }
+}
+
+/// Parses the ID in the multihash encoding, only the known algorithms are accepted
+pub fn parse_multihash(bytes: &[u8]) -> Result<(HashAlgorithm, Multihash)> {
+    let multihash = Multihash::from_bytes(bytes)?;
+    let algorithm = HashAlgorithm::from_code(multihash.code())
+        .ok_or(anyhow!("Unknown hash algorithm {:#x}", multihash.code()))?;
+    if multihash.size() != 32 {
+        bail!("Hash {algorithm:?} has 32 bytes, got {}", multihash.size());
+    }
+    Ok((algorithm, multihash))
+}
 
 pub trait UUIDTyped {
     fn get_type_uuid(&self) -> Uuid;
 }
 
+/// The largest object decoded from bytes. A length prefix claiming more comes from
+/// a broken or malicious peer, and is rejected before anything is allocated
+pub const MAX_OBJECT_SIZE: u64 = 256 * 1024 * 1024;
+
+/// Decodes the bytes received from other peers. Unlike `bincode::deserialize`, the
+/// size of the object is limited and trailing bytes are an error
+pub fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .with_limit(MAX_OBJECT_SIZE)
+        .reject_trailing_bytes()
+        .deserialize(bytes)
+        .map_err(|e| anyhow!(e))
+}
+
 impl<T> From<T> for TypedObject
 where
     T: UUIDTyped + Serialize,
@@ -27,17 +109,65 @@ where
     fn from(value: T) -> Self {
         TypedObject {
             uuid: value.get_type_uuid(),
-            data: bincode::serialize(&value).unwrap(),
+            data: canonical::encode(&value).unwrap(),
         }
     }
 }
 
 impl TypedObject {
+    /// Decodes the payload. The payloads stored before the canonical encoding was
+    /// introduced are bincode, they are still read
     pub fn try_from_typed<T>(value: &TypedObject) -> Result<T>
     where
-        T: for<'de> Deserialize<'de>,
+        T: DeserializeOwned,
     {
-        bincode::deserialize::<T>(&value.data).map_err(|e| anyhow!(e))
+        if value.data.starts_with(&canonical::SELF_DESCRIBED_TAG) {
+            if let Ok(decoded) = canonical::decode(&value.data) {
+                return Ok(decoded);
+            }
+        }
+        decode(&value.data)
+    }
+
+    /// Whether the payload is in the canonical encoding, not a legacy bincode one
+    pub fn is_canonical(&self) -> bool {
+        canonical::is_canonical(&self.data)
+    }
+
+    /// Builds the object from JSON like `{"uuid": "<type UUID>", "data": <any JSON>}`.
+    /// The data is kept as JSON text, for the types handled by the external modules
+    pub fn from_json(json: &str) -> Result<TypedObject> {
+        #[derive(Deserialize)]
+        struct JsonObject {
+            uuid: Uuid,
+            data: serde_json::Value,
+        }
+
+        let object: JsonObject = serde_json::from_str(json)?;
+        Ok(TypedObject {
+            uuid: object.uuid,
+            data: serde_json::to_vec(&object.data)?,
+        })
+    }
+
+    /// The DHT key of the small object stored as a record
+    pub fn record_key(obj_id: &Hash) -> RecordKey {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(Self::RECORD_KEY_PREFIX);
+        hasher.update(&obj_id.bytes);
+        RecordKey::new(hasher.finalize().as_bytes())
+    }
+
+    /// Decodes the value of the DHT record. Fails unless the object is stored under
+    /// the key of its own ID
+    pub fn from_record(key: &RecordKey, value: &[u8]) -> Result<TypedObject> {
+        let object = TypedObject::try_from(&value.to_vec())?;
+        let obj_id = Hash::try_from(&object)?;
+        if Self::record_key(&obj_id) != *key {
+            bail!("The object {obj_id} is stored under a wrong key");
+        }
+
+        Ok(object)
     }
 }
 
@@ -57,16 +187,20 @@ impl TryFrom<Vec<u8>> for Hash {
 impl TryFrom<&[u8]> for Hash {
     type Error = Error;
 
+    /// Accepts the bare digest and the multihash encoding of a blake3 hash
     fn try_from(bytes: &[u8]) -> Result<Self> {
-        if bytes.len() != 32 {
-            return Err(anyhow!(
-                "Hash has 32 bytes, tried to convert from {} bytes",
-                bytes.len()
-            ));
+        if bytes.len() == 32 {
+            return Ok(Hash {
+                bytes: bytes.try_into()?,
+            });
         }
-        Ok(Hash {
-            bytes: bytes[..32].try_into()?,
-        })
+        let (algorithm, multihash) = parse_multihash(bytes).map_err(|e| {
+            e.context(format!(
+                "Hash has 32 bytes or is a multihash, tried to convert from {} bytes",
+                bytes.len()
+            ))
+        })?;
+        Hash::from_multihash(algorithm, &multihash)
     }
 }
 impl TryFrom<&[u8; 32]> for Hash {
@@ -87,10 +221,14 @@ impl TryFrom<&[u8; 32]> for Hash {
 impl TryFrom<&TypedObject> for Hash {
     type Error = Error;
 
+    /// The objects with a canonical payload are hashed in the canonical encoding, the
+    /// legacy ones as before, so their IDs stay the same
     fn try_from(value: &TypedObject) -> Result<Self> {
-        blake3::hash(bincode::serialize(value)?.as_slice())
-            .as_bytes()
-            .try_into()
+        let bytes = match value.is_canonical() {
+            true => canonical::encode_object(&value.uuid, &value.data)?,
+            false => bincode::serialize(value)?,
+        };
+        blake3::hash(bytes.as_slice()).as_bytes().try_into()
     }
 }
 
@@ -100,13 +238,51 @@ impl TryFrom<&str> for Hash {
         bs58::decode(value).into_vec()?.as_slice().try_into()
     }
 }
+impl Hash {
+    pub fn blake3(data: &[u8]) -> Hash {
+        Hash {
+            bytes: *blake3::hash(data).as_bytes(),
+        }
+    }
+
+    /// The ID in the multihash encoding
+    pub fn to_multihash(&self) -> Multihash {
+        Multihash::wrap(HashAlgorithm::Blake3.code(), &self.bytes)
+            .expect("Digest to fit in the multihash")
+    }
+
+    fn from_multihash(algorithm: HashAlgorithm, multihash: &Multihash) -> Result<Hash> {
+        if algorithm != HashAlgorithm::Blake3 {
+            bail!("Object IDs hashed with {algorithm:?} are not supported yet");
+        }
+        Ok(Hash {
+            bytes: multihash.digest().try_into()?,
+        })
+    }
+}
+
 impl Into<libp2p::kad::RecordKey> for Hash {
     fn into(self) -> libp2p::kad::RecordKey {
         RecordKey::new(&self.bytes)
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// The ID of the user of the peer, the hash of its peer ID
+pub fn user_id(peer_id: &PeerId) -> UserId {
+    Hash {
+        bytes: *blake3::hash(&peer_id.to_bytes()).as_bytes(),
+    }
+}
+
+pub fn unix_now() -> UnixTimestamp {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Grants the recipient the membership in the group until the revocation date
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct GroupAccessToken {
     pub binding: GroupAccessBinding,
     pub group_owner_signature: Signature,
@@ -114,34 +290,239 @@ pub struct GroupAccessToken {
 
 pub type UnixTimestamp = u64;
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct GroupAccessBinding {
     pub group: GroupId,
     pub recipient: UserId,
     pub revocation_date: UnixTimestamp,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+impl GroupAccessToken {
+    /// Signs the membership of the recipient in the group. Only the owner of the
+    /// group can issue a valid token
+    pub fn issue(
+        group: &GroupId,
+        recipient: &PeerId,
+        revocation_date: UnixTimestamp,
+        owner: &Keypair,
+    ) -> Result<Self> {
+        let binding = GroupAccessBinding {
+            group: group.clone(),
+            recipient: user_id(recipient),
+            revocation_date,
+        };
+        let signature = Signature {
+            bytes: owner.sign(&bincode::serialize(&binding)?)?,
+        };
+
+        Ok(GroupAccessToken {
+            binding,
+            group_owner_signature: signature,
+        })
+    }
+
+    /// Checks that the token was issued by the owner of the group for the peer and
+    /// is not revoked yet
+    pub fn verify(&self, group: &UserGroup, peer_id: &PeerId) -> Result<()> {
+        if self.binding.group != group.definition.id {
+            bail!("The token is for another group");
+        }
+        if self.binding.recipient != user_id(peer_id) {
+            bail!("The token is not issued for {peer_id}");
+        }
+        if self.binding.revocation_date <= unix_now() {
+            bail!("The token is revoked");
+        }
+
+        let owner_key: PublicKey = group.definition.owner_key.clone().try_into()?;
+        let binding = bincode::serialize(&self.binding)?;
+        if !owner_key.verify(&binding, &self.group_owner_signature.bytes) {
+            bail!("The token is not signed by the owner of the group");
+        }
+
+        Ok(())
+    }
+}
+
+/// The definition of the group signed by its owner
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct UserGroup {
     pub definition: GroupDefinition,
     pub signature: Signature,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct GroupDefinition {
     pub id: GroupId,
+    pub name: String,
     pub owner: UserId,
-    pub parent: GroupId,
-    pub parent_membership_proof: GroupAccessToken,
+    /// The key of the owner, which signs the definition and the access tokens
+    pub owner_key: SerializablePublicKey,
+    /// None for the groups which are not a subgroup of another one
+    pub parent: Option<GroupId>,
+    /// The membership of the owner in the parent group
+    pub parent_membership_proof: Option<GroupAccessToken>,
+}
+
+impl UserGroup {
+    /// Defines a new group owned by the keypair. The ID is derived from the owner,
+    /// the name and the time, so the owner can have many groups with the same name
+    pub fn create(name: String, owner: &Keypair) -> Result<Self> {
+        let owner_id = owner.public().to_peer_id();
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&owner_id.to_bytes());
+        hasher.update(name.as_bytes());
+        hasher.update(&unix_now().to_le_bytes());
+        let mut nonce = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        hasher.update(&nonce);
+
+        let definition = GroupDefinition {
+            id: Hash {
+                bytes: *hasher.finalize().as_bytes(),
+            },
+            name,
+            owner: user_id(&owner_id),
+            owner_key: owner.public().into(),
+            parent: None,
+            parent_membership_proof: None,
+        };
+        let signature = Signature {
+            bytes: owner.sign(&bincode::serialize(&definition)?)?,
+        };
+
+        Ok(UserGroup {
+            definition,
+            signature,
+        })
+    }
+
+    /// Checks that the definition is signed by its owner
+    pub fn verify(&self) -> Result<()> {
+        let owner_key: PublicKey = self.definition.owner_key.clone().try_into()?;
+        if user_id(&owner_key.to_peer_id()) != self.definition.owner {
+            bail!("The key of the group is not the key of its owner");
+        }
+        let definition = bincode::serialize(&self.definition)?;
+        if !owner_key.verify(&definition, &self.signature.bytes) {
+            bail!("The group is not signed by its owner");
+        }
+
+        Ok(())
+    }
+
+    pub fn owner_id(&self) -> Result<PeerId> {
+        let key: PublicKey = self.definition.owner_key.clone().try_into()?;
+        Ok(key.to_peer_id())
+    }
+
+    /// Whether the peer may post in the group with the token
+    pub fn check_member(&self, peer_id: &PeerId, token: Option<&GroupAccessToken>) -> Result<()> {
+        if self.owner_id()? == *peer_id {
+            return Ok(());
+        }
+        match token {
+            Some(token) => token.verify(self, peer_id),
+            None => bail!("{peer_id} is not a member of the group"),
+        }
+    }
+}
+
+/// What a member needs to join the group
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GroupInvitation {
+    pub group: UserGroup,
+    pub token: GroupAccessToken,
+}
+
+impl GroupInvitation {
+    /// The invitation as text, to be passed to the invited user
+    pub fn encode(&self) -> Result<String> {
+        Ok(bs58::encode(bincode::serialize(self)?).into_string())
+    }
+
+    pub fn decode(text: &str) -> Result<Self> {
+        Ok(bincode::deserialize(
+            &bs58::decode(text.trim()).into_vec()?,
+        )?)
+    }
+}
+
+/// Who may get an object from the peers storing it. The policy is sent along with
+/// the object and kept by every peer storing it, which checks it before serving
+/// the object. The peer requesting the object is known from the connection
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub enum AccessPolicy {
+    #[default]
+    Public,
+    /// Only the listed users, like the contacts of the publisher
+    Users(Vec<UserId>),
+    /// Only the members of the group, proven with their access tokens
+    Group(UserGroup),
+}
+
+impl AccessPolicy {
+    /// Whether the peer may get the object, with the token for the group objects
+    pub fn check(
+        &self,
+        peer_id: &PeerId,
+        token: Option<&GroupAccessToken>,
+    ) -> Result<(), ResultErrorCode> {
+        let allowed = match self {
+            AccessPolicy::Public => true,
+            AccessPolicy::Users(users) => users.contains(&user_id(peer_id)),
+            AccessPolicy::Group(group) => group.check_member(peer_id, token).is_ok(),
+        };
+
+        match allowed {
+            true => Ok(()),
+            false => Err(ResultErrorCode::AccessDenied),
+        }
+    }
+
+    pub fn is_public(&self) -> bool {
+        matches!(self, AccessPolicy::Public)
+    }
+}
+
+/// A post in the feed of a group, sent as the signed object of a GroupObject
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GroupPostObject {
+    pub author_key: SerializablePublicKey,
+    pub posted_at: UnixTimestamp,
+    pub content: MessageContent,
+    /// The membership of the author, None if the author owns the group
+    pub access_token: Option<GroupAccessToken>,
+}
+impl GroupPostObject {
+    pub const UUID: Uuid = uuid!("0193e0b4-7d2a-7c61-b8e3-4f1a9c2d6e05");
+}
+impl UUIDTyped for GroupPostObject {
+    fn get_type_uuid(&self) -> Uuid {
+        GroupPostObject::UUID
+    }
 }
 
+/// A post after it was verified by a member of the group
+#[derive(Debug, Clone)]
+pub struct VerifiedPost {
+    pub id: Hash,
+    pub author: PeerId,
+    pub posted_at: SystemTime,
+    pub content: MessageContent,
+}
+
+#[serde_as]
 #[derive(Serialize, Deserialize, Debug, Clone, Hash, PartialEq)]
 pub struct TypedObject {
     pub uuid: Uuid,
+    /// A byte string in the canonical encoding, not an array of integers
+    #[serde_as(as = "Bytes")]
     pub data: Vec<u8>,
 }
 impl TypedObject {
     pub const UUID: Uuid = uuid!("0193a7be-425b-7158-8677-2dfdb28d3b00");
+    const RECORD_KEY_PREFIX: &'static [u8] = b"liberum-object/";
 }
 impl TypedObject {
     pub fn get_uuid(&self) -> Uuid {
@@ -151,7 +532,7 @@ impl TypedObject {
 impl TryFrom<&Vec<u8>> for TypedObject {
     type Error = Error;
     fn try_from(value: &Vec<u8>) -> std::result::Result<Self, Self::Error> {
-        bincode::deserialize::<TypedObject>(value).map_err(|e| anyhow!(e))
+        decode(value)
     }
 }
 impl TryInto<Vec<u8>> for TypedObject {
@@ -161,12 +542,16 @@ impl TryInto<Vec<u8>> for TypedObject {
     }
 }
 
+#[serde_as]
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Signature {
+    #[serde_as(as = "Bytes")]
     pub bytes: Vec<u8>,
 }
+#[serde_as]
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct SerializablePublicKey {
+    #[serde_as(as = "Bytes")]
     pub key: Vec<u8>,
 }
 impl From<libp2p::identity::PublicKey> for SerializablePublicKey {
@@ -179,7 +564,8 @@ impl From<libp2p::identity::PublicKey> for SerializablePublicKey {
 impl TryInto<libp2p::identity::PublicKey> for SerializablePublicKey {
     type Error = Error;
     fn try_into(self) -> Result<libp2p::identity::PublicKey> {
-        PublicKey::try_decode_protobuf(bincode::deserialize(&self.key)?).map_err(|e| anyhow!(e))
+        let key: Vec<u8> = decode(&self.key)?;
+        PublicKey::try_decode_protobuf(&key).map_err(|e| anyhow!(e))
     }
 }
 
@@ -197,6 +583,28 @@ impl UUIDTyped for SignedObject {
     }
 }
 impl SignedObject {
+    /// Checks the signatures of the object and of all the signed objects nested in it,
+    /// starting from the outermost one. A signature is verified if it was made with any of the keys
+    pub fn verify_chain(&self, keys: &[PublicKey]) -> Result<Vec<SignatureStatus>> {
+        let mut statuses = Vec::new();
+        let mut signed = self.clone();
+        loop {
+            let mut verified = false;
+            for key in keys {
+                verified |= signed.verify_ed25519(key.clone())?;
+            }
+            statuses.push(match verified {
+                true => SignatureStatus::Verified,
+                false => SignatureStatus::Unverified,
+            });
+
+            if signed.object.uuid != SignedObject::UUID {
+                return Ok(statuses);
+            }
+            signed = TypedObject::try_from_typed(&signed.object)?;
+        }
+    }
+
     pub fn sign_ed25519(object: TypedObject, keypair: libp2p::identity::Keypair) -> Result<Self> {
         let v: Vec<u8> = object.clone().try_into()?;
         let signature = Signature {
@@ -208,6 +616,18 @@ impl SignedObject {
         let msg: Vec<u8> = self.object.clone().try_into()?;
         Ok(public.verify(msg.as_slice(), &self.signature.bytes.as_slice()))
     }
+
+    /// Checks the signature against the key of the peer, it's unverified if the key
+    /// is not known from the peer ID
+    pub fn verify_peer(&self, peer_id: &PeerId) -> SignatureStatus {
+        let verified = public_key_of(peer_id)
+            .and_then(|key| self.verify_ed25519(key))
+            .unwrap_or(false);
+        match verified {
+            true => SignatureStatus::Verified,
+            false => SignatureStatus::Unverified,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -217,6 +637,51 @@ pub struct GroupObject {
 }
 impl GroupObject {
     pub const UUID: Uuid = uuid!("0193a7c0-1cb7-72e8-97cd-e84c15925233");
+
+    /// Signs the post of the member of the group
+    pub fn post(
+        group: &GroupId,
+        content: MessageContent,
+        access_token: Option<GroupAccessToken>,
+        author: &Keypair,
+    ) -> Result<Self> {
+        let post = GroupPostObject {
+            author_key: author.public().into(),
+            posted_at: unix_now(),
+            content,
+            access_token,
+        };
+
+        Ok(GroupObject {
+            group: group.clone(),
+            object: SignedObject::sign_ed25519(post.into(), author.clone())?,
+        })
+    }
+
+    /// Checks that the object is a post signed by a member of the group
+    pub fn verify_post(&self, group: &UserGroup) -> Result<VerifiedPost> {
+        if self.group != group.definition.id {
+            bail!("The post is for another group");
+        }
+        if self.object.object.uuid != GroupPostObject::UUID {
+            bail!("The object is not a post");
+        }
+        let post: GroupPostObject = TypedObject::try_from_typed(&self.object.object)?;
+
+        let author_key: PublicKey = post.author_key.clone().try_into()?;
+        let author = author_key.to_peer_id();
+        if !self.object.verify_ed25519(author_key)? {
+            bail!("Invalid signature of the post of {author}");
+        }
+        group.check_member(&author, post.access_token.as_ref())?;
+
+        Ok(VerifiedPost {
+            id: Hash::try_from(&self.object.object)?,
+            author,
+            posted_at: UNIX_EPOCH + Duration::from_secs(post.posted_at),
+            content: post.content,
+        })
+    }
 }
 impl UUIDTyped for GroupObject {
     fn get_type_uuid(&self) -> Uuid {
@@ -224,9 +689,12 @@ impl UUIDTyped for GroupObject {
     }
 }
 
+#[serde_as]
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct PlainFileObject {
     pub name: String,
+    /// A byte string in the canonical encoding, not an array of integers
+    #[serde_as(as = "Bytes")]
     pub content: Content,
 }
 impl PlainFileObject {
@@ -239,7 +707,8 @@ impl UUIDTyped for PlainFileObject {
 }
 
 impl PlainFileObject {
-    pub async fn try_from_path(path: &Path) -> Result<Self> {
+    /// Reads the file opened from the path, it's named after the path
+    pub async fn try_from_file(path: &Path, file: std::fs::File) -> Result<Self> {
         let name = {
             let name = path.file_name();
             if let None = name {
@@ -252,13 +721,64 @@ impl PlainFileObject {
             name.unwrap().to_string()
         };
 
-        Ok(PlainFileObject {
-            name,
-            content: tokio::fs::read(path).await?,
-        })
+        let mut content = Vec::new();
+        tokio::fs::File::from_std(file)
+            .read_to_end(&mut content)
+            .await?;
+        Ok(PlainFileObject { name, content })
     }
 }
 
+/// A piece of a file published in chunks. The chunks are not signed, the manifest
+/// of the file has their IDs
+#[serde_as]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ChunkObject {
+    /// A byte string in the canonical encoding, not an array of integers
+    #[serde_as(as = "Bytes")]
+    pub content: Content,
+}
+impl ChunkObject {
+    pub const UUID: Uuid = uuid!("0193f3d8-6a2c-7e41-b958-2d7c0f1e8a63");
+}
+impl UUIDTyped for ChunkObject {
+    fn get_type_uuid(&self) -> Uuid {
+        ChunkObject::UUID
+    }
+}
+
+/// A file published in chunks cut by the chunker, so the revisions of a big file
+/// share the chunks which didn't change and only the new ones are transferred
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ManifestObject {
+    pub name: String,
+    pub size: u64,
+    pub chunks: Vec<ObjectId>,
+    /// The parity chunks the missing chunks can be reconstructed from
+    #[serde(default)]
+    pub coding: Option<ErasureCoding>,
+}
+impl ManifestObject {
+    pub const UUID: Uuid = uuid!("0193f3d8-9b15-7c06-a4e7-5f3a8d2c1b94");
+}
+impl UUIDTyped for ManifestObject {
+    fn get_type_uuid(&self) -> Uuid {
+        ManifestObject::UUID
+    }
+}
+
+/// The Reed-Solomon coding of the chunks of a manifest. The chunks are coded in
+/// groups of `data_shards` of them, in order, every group has its own parity chunks
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ErasureCoding {
+    pub data_shards: u8,
+    pub parity_shards: u8,
+    /// The sizes of the chunks, the parity needs the padding to be removed
+    pub chunk_sizes: Vec<u64>,
+    /// The parity chunks of every group
+    pub parity: Vec<Vec<ObjectId>>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct EmptyObject {}
 impl EmptyObject {
@@ -307,9 +827,57 @@ impl From<SimpleIDQuery> for QueryObject {
 pub struct DeleteObjectQuery {
     pub id: ObjectId,
     pub verification_key_ed25519: SerializablePublicKey,
+    /// The time the query was signed at, a captured query can't be replayed
+    /// once it's older than `MAX_AGE`
+    pub signed_at: UnixTimestamp,
+    /// Signature of the object ID and the signing time made with the key of
+    /// the publisher, proves that the query was sent by the owner of the object
+    pub signature: Signature,
 }
 impl DeleteObjectQuery {
     pub const UUID: Uuid = uuid!("0193b1a3-0b17-73a4-941c-5c79ac9a3780");
+    /// How far the signing time may be from the time of the recipient
+    pub const MAX_AGE: Duration = Duration::from_secs(5 * 60);
+
+    pub fn sign_ed25519(id: ObjectId, keypair: libp2p::identity::Keypair) -> Result<Self> {
+        let signed_at = unix_now();
+        let signature = Signature {
+            bytes: keypair
+                .sign(&Self::signed_bytes(&id, signed_at))
+                .map_err(|e| anyhow!(e))?,
+        };
+        Ok(Self {
+            id,
+            verification_key_ed25519: keypair.public().into(),
+            signed_at,
+            signature,
+        })
+    }
+
+    /// Checks the signature and the age of the query and returns the key that
+    /// signed it
+    pub fn verify_ed25519(&self) -> Result<PublicKey, ResultErrorCode> {
+        let key: PublicKey = self
+            .verification_key_ed25519
+            .clone()
+            .try_into()
+            .map_err(|_| ResultErrorCode::InvalidKey)?;
+
+        let signed_bytes = Self::signed_bytes(&self.id, self.signed_at);
+        if !key.verify(&signed_bytes, &self.signature.bytes) {
+            return Err(ResultErrorCode::InvalidSignature);
+        }
+        if unix_now().abs_diff(self.signed_at) > Self::MAX_AGE.as_secs() {
+            return Err(ResultErrorCode::Expired);
+        }
+        Ok(key)
+    }
+
+    fn signed_bytes(id: &ObjectId, signed_at: UnixTimestamp) -> Vec<u8> {
+        let mut bytes = id.bytes.to_vec();
+        bytes.extend_from_slice(&signed_at.to_le_bytes());
+        bytes
+    }
 }
 impl UUIDTyped for DeleteObjectQuery {
     fn get_type_uuid(&self) -> Uuid {
@@ -317,9 +885,531 @@ impl UUIDTyped for DeleteObjectQuery {
     }
 }
 
+/// The IDs of the objects matching a query, the answer to the queries handled
+/// by the external modules
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct QueryResultObject {
+    pub ids: Vec<ObjectId>,
+}
+impl QueryResultObject {
+    pub const UUID: Uuid = uuid!("0193c2d4-1e6a-7c83-b5f2-8a4d0e9c3b61");
+}
+impl UUIDTyped for QueryResultObject {
+    fn get_type_uuid(&self) -> Uuid {
+        QueryResultObject::UUID
+    }
+}
+
+/// Free-form tags of an object, for the objects to be found by searching. Sent as
+/// the signed object to the closest peers of the hash of every tag, which index it
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TagObject {
+    pub object: ObjectId,
+    pub tags: Vec<String>,
+}
+impl TagObject {
+    pub const UUID: Uuid = uuid!("0193f1a7-5c2e-7d94-a6b3-9e8f4c1d2b70");
+    const TAG_HASH_PREFIX: &'static [u8] = b"liberum-tag/";
+    pub const MAX_TAGS: usize = 32;
+    pub const MAX_TAG_LEN: usize = 64;
+
+    /// Whether the peers index the tags, they don't index too many or too long ones
+    pub fn is_valid(&self) -> bool {
+        (1..=Self::MAX_TAGS).contains(&self.tags.len())
+            && self.tags.iter().all(|tag| {
+                let len = Self::normalize(tag).chars().count();
+                (1..=Self::MAX_TAG_LEN).contains(&len)
+            })
+    }
+
+    /// The tag as it's indexed, trimmed and in lowercase
+    pub fn normalize(tag: &str) -> String {
+        tag.trim().to_lowercase()
+    }
+
+    /// The key the objects with the tag are indexed under
+    pub fn tag_hash(tag: &str) -> Hash {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(Self::TAG_HASH_PREFIX);
+        hasher.update(Self::normalize(tag).as_bytes());
+        Hash {
+            bytes: *hasher.finalize().as_bytes(),
+        }
+    }
+}
+impl UUIDTyped for TagObject {
+    fn get_type_uuid(&self) -> Uuid {
+        TagObject::UUID
+    }
+}
+
+/// Asks for the IDs of the objects with the tag, sent to the closest peers of the
+/// hash of the tag. The answer is a QueryResultObject
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TagQuery {
+    pub tag_hash: Hash,
+}
+impl TagQuery {
+    pub const UUID: Uuid = uuid!("0193f1a7-8d41-7b25-93c6-1f0e7a5d3c84");
+    /// The most objects a peer answers with for one tag
+    pub const MAX_RESULTS: usize = 256;
+}
+impl UUIDTyped for TagQuery {
+    fn get_type_uuid(&self) -> Uuid {
+        TagQuery::UUID
+    }
+}
+
+/// A revision of an object, linking it to the revision it replaces. Published signed
+/// by the publisher, the chain of the linked revisions is the history of the object
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct VersionObject {
+    pub previous: ObjectId,
+    pub object: ObjectId,
+}
+impl VersionObject {
+    pub const UUID: Uuid = uuid!("0193f2c4-1e7b-7a3d-8f52-6c9d0b4e2a17");
+}
+impl UUIDTyped for VersionObject {
+    fn get_type_uuid(&self) -> Uuid {
+        VersionObject::UUID
+    }
+}
+
+/// The human readable identity of a node. Signed by the node and published in the
+/// DHT as a record under the key derived from its peer ID, not as a provided object
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProfileObject {
+    pub display_name: String,
+    pub description: String,
+    /// ID of the object with the avatar image
+    pub avatar: Option<ObjectId>,
+    /// The key of the node, the peer ID of the profile is derived from it
+    pub public_key: SerializablePublicKey,
+    /// When the profile was signed, an older profile never replaces a newer one
+    pub updated_at: UnixTimestamp,
+}
+impl ProfileObject {
+    pub const UUID: Uuid = uuid!("0193d5e2-6f41-7a0c-9d18-3c7b2e5f8a46");
+    const RECORD_KEY_PREFIX: &'static [u8] = b"liberum-profile/";
+
+    /// The DHT key of the profile of the peer
+    pub fn record_key(peer_id: &PeerId) -> RecordKey {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(Self::RECORD_KEY_PREFIX);
+        hasher.update(&peer_id.to_bytes());
+        RecordKey::new(hasher.finalize().as_bytes())
+    }
+
+    pub fn peer_id(&self) -> Result<PeerId> {
+        let key: PublicKey = self.public_key.clone().try_into()?;
+        Ok(key.to_peer_id())
+    }
+
+    /// Signs the profile and encodes it as the value of the DHT record
+    pub fn to_record_value(self, keypair: Keypair) -> Result<Vec<u8>> {
+        let signed: TypedObject = SignedObject::sign_ed25519(self.into(), keypair)?.into();
+        signed.try_into()
+    }
+
+    /// Decodes the value of the DHT record. Fails unless the profile is signed by
+    /// its own key and stored under the key of its peer ID
+    pub fn from_record(key: &RecordKey, value: &[u8]) -> Result<ProfileObject> {
+        let signed = TypedObject::try_from(&value.to_vec())?;
+        if signed.uuid != SignedObject::UUID {
+            bail!("The profile is not signed");
+        }
+        let signed: SignedObject = TypedObject::try_from_typed(&signed)?;
+        if signed.object.uuid != ProfileObject::UUID {
+            bail!("The record is not a profile");
+        }
+        let profile: ProfileObject = TypedObject::try_from_typed(&signed.object)?;
+
+        let public_key: PublicKey = profile.public_key.clone().try_into()?;
+        let peer_id = public_key.to_peer_id();
+        if Self::record_key(&peer_id) != *key {
+            bail!("The profile of {peer_id} is stored under a wrong key");
+        }
+        if !signed.verify_ed25519(public_key)? {
+            bail!("Invalid signature of the profile of {peer_id}");
+        }
+
+        Ok(profile)
+    }
+}
+impl UUIDTyped for ProfileObject {
+    fn get_type_uuid(&self) -> Uuid {
+        ProfileObject::UUID
+    }
+}
+
+/// Links the old key of a node to the new one after its keypair was rotated. Signed
+/// with both keys, so it proves that the owner of the old key moved to the new one.
+/// Published in the DHT as a record under the key derived from the old peer ID
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RotationObject {
+    pub old_key: SerializablePublicKey,
+    pub new_key: SerializablePublicKey,
+    pub rotated_at: UnixTimestamp,
+    pub old_signature: Signature,
+    pub new_signature: Signature,
+}
+impl RotationObject {
+    pub const UUID: Uuid = uuid!("0193e6c1-3b9d-7f42-a5e8-2d7c1b4f9a63");
+    const RECORD_KEY_PREFIX: &'static [u8] = b"liberum-rotation/";
+
+    /// Links the old keypair to the new one
+    pub fn rotate(old: &Keypair, new: &Keypair) -> Result<Self> {
+        let old_key: SerializablePublicKey = old.public().into();
+        let new_key: SerializablePublicKey = new.public().into();
+        let rotated_at = unix_now();
+        let signed = Self::signed_bytes(&old_key, &new_key, rotated_at)?;
+
+        Ok(RotationObject {
+            old_signature: Signature {
+                bytes: old.sign(&signed)?,
+            },
+            new_signature: Signature {
+                bytes: new.sign(&signed)?,
+            },
+            old_key,
+            new_key,
+            rotated_at,
+        })
+    }
+
+    fn signed_bytes(
+        old_key: &SerializablePublicKey,
+        new_key: &SerializablePublicKey,
+        rotated_at: UnixTimestamp,
+    ) -> Result<Vec<u8>> {
+        Ok(bincode::serialize(&(
+            &old_key.key,
+            &new_key.key,
+            rotated_at,
+        ))?)
+    }
+
+    /// Checks both signatures. Returns the old and the new key
+    pub fn verify(&self) -> Result<(PublicKey, PublicKey)> {
+        let old_key: PublicKey = self.old_key.clone().try_into()?;
+        let new_key: PublicKey = self.new_key.clone().try_into()?;
+        if old_key == new_key {
+            bail!("The key is rotated to itself");
+        }
+
+        let signed = Self::signed_bytes(&self.old_key, &self.new_key, self.rotated_at)?;
+        if !old_key.verify(&signed, &self.old_signature.bytes) {
+            bail!("Invalid signature of the old key");
+        }
+        if !new_key.verify(&signed, &self.new_signature.bytes) {
+            bail!("Invalid signature of the new key");
+        }
+
+        Ok((old_key, new_key))
+    }
+
+    /// The DHT key of the rotation of the peer
+    pub fn record_key(peer_id: &PeerId) -> RecordKey {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(Self::RECORD_KEY_PREFIX);
+        hasher.update(&peer_id.to_bytes());
+        RecordKey::new(hasher.finalize().as_bytes())
+    }
+
+    pub fn to_record_value(&self) -> Result<Vec<u8>> {
+        let typed: TypedObject = self.clone().into();
+        typed.try_into()
+    }
+
+    /// Decodes the value of the DHT record. Fails unless the rotation is valid and
+    /// stored under the key of its old peer ID
+    pub fn from_record(key: &RecordKey, value: &[u8]) -> Result<RotationObject> {
+        let typed = TypedObject::try_from(&value.to_vec())?;
+        if typed.uuid != RotationObject::UUID {
+            bail!("The record is not a key rotation");
+        }
+        let rotation: RotationObject = TypedObject::try_from_typed(&typed)?;
+
+        let (old_key, _) = rotation.verify()?;
+        let peer_id = old_key.to_peer_id();
+        if Self::record_key(&peer_id) != *key {
+            bail!("The rotation of {peer_id} is stored under a wrong key");
+        }
+
+        Ok(rotation)
+    }
+}
+impl UUIDTyped for RotationObject {
+    fn get_type_uuid(&self) -> Uuid {
+        RotationObject::UUID
+    }
+}
+
+/// The key followed by the keys it was rotated to, in order. Only the valid rotations
+/// continue the chain. If a key was rotated more than once, which happens only when
+/// it leaked, the earliest rotation is followed
+pub fn rotated_keys(key: &PublicKey, rotations: &[RotationObject]) -> Vec<PublicKey> {
+    let valid: Vec<(UnixTimestamp, PublicKey, PublicKey)> = rotations
+        .iter()
+        .filter_map(|r| r.verify().ok().map(|(old, new)| (r.rotated_at, old, new)))
+        .collect();
+
+    let mut keys = vec![key.clone()];
+    loop {
+        let last = keys.last().unwrap();
+        let next = valid
+            .iter()
+            .filter(|(_, old, _)| old == last)
+            .min_by_key(|(rotated_at, _, _)| *rotated_at)
+            .map(|(_, _, new)| new.clone());
+        match next {
+            // A cycle would never end
+            Some(next) if !keys.contains(&next) => keys.push(next),
+            _ => return keys,
+        }
+    }
+}
+
+/// A message sent directly to one peer. Signed by the sender, the content may be
+/// encrypted for the recipient with a key derived from both their ed25519 keys
+#[serde_as]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DirectMessageObject {
+    pub sender: SerializablePublicKey,
+    /// The peer ID of the recipient, so the message can't be replayed to another peer
+    #[serde_as(as = "Bytes")]
+    pub recipient: Vec<u8>,
+    pub sent_at: UnixTimestamp,
+    pub payload: MessagePayload,
+}
+
+#[serde_as]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum MessagePayload {
+    Plain(MessageContent),
+    /// The content encrypted with chacha20poly1305, with the key agreed between an
+    /// ephemeral x25519 key and the key of the recipient
+    Encrypted {
+        ephemeral_key: [u8; 32],
+        nonce: [u8; 12],
+        #[serde_as(as = "Bytes")]
+        ciphertext: Vec<u8>,
+    },
+}
+
+/// A direct message after it was verified and decrypted by the recipient
+#[derive(Debug, Clone)]
+pub struct OpenedMessage {
+    pub id: Hash,
+    pub sender: PeerId,
+    pub sent_at: SystemTime,
+    pub encrypted: bool,
+    pub content: MessageContent,
+}
+
+impl DirectMessageObject {
+    pub const UUID: Uuid = uuid!("0193d8a1-2c5e-7b34-a1f9-6e0d4c8b7a52");
+    const KEY_CONTEXT: &'static str = "liberum-neto 2024 direct message key";
+
+    /// Signs the message for the recipient, encrypting the content if asked to.
+    /// Returns the signed message and its ID
+    pub fn seal(
+        keypair: &Keypair,
+        recipient: &PeerId,
+        content: MessageContent,
+        encrypt: bool,
+    ) -> Result<(SignedObject, Hash)> {
+        let payload = match encrypt {
+            true => Self::encrypt(recipient, &content)?,
+            false => MessagePayload::Plain(content),
+        };
+        let message = DirectMessageObject {
+            sender: keypair.public().into(),
+            recipient: recipient.to_bytes(),
+            sent_at: unix_now(),
+            payload,
+        };
+
+        let signed = SignedObject::sign_ed25519(message.into(), keypair.clone())?;
+        let id = Hash::try_from(&signed.object)?;
+        Ok((signed, id))
+    }
+
+    /// Verifies that the message was signed by `sender` for the owner of the keypair
+    /// and decrypts it
+    pub fn open(
+        signed: &SignedObject,
+        sender: &PeerId,
+        keypair: &Keypair,
+    ) -> Result<OpenedMessage> {
+        let message = Self::verify(signed)?;
+        if message.sender_id()? != *sender {
+            bail!("The message was not sent by {sender}");
+        }
+        if message.recipient != keypair.public().to_peer_id().to_bytes() {
+            bail!("The message from {sender} is for another peer");
+        }
+
+        let encrypted = matches!(message.payload, MessagePayload::Encrypted { .. });
+        let content = match message.payload {
+            MessagePayload::Plain(content) => content,
+            MessagePayload::Encrypted {
+                ephemeral_key,
+                nonce,
+                ciphertext,
+            } => Self::decrypt(keypair, ephemeral_key, nonce, &ciphertext)?,
+        };
+
+        Ok(OpenedMessage {
+            id: Hash::try_from(&signed.object)?,
+            sender: *sender,
+            sent_at: UNIX_EPOCH + Duration::from_secs(message.sent_at),
+            encrypted,
+            content,
+        })
+    }
+
+    /// Decodes the message and checks that it is signed by its sender. The content
+    /// is not decrypted, so also the peers keeping the message for the recipient can
+    /// verify it
+    pub fn verify(signed: &SignedObject) -> Result<DirectMessageObject> {
+        if signed.object.uuid != DirectMessageObject::UUID {
+            bail!("The object is not a direct message");
+        }
+        let message: DirectMessageObject = TypedObject::try_from_typed(&signed.object)?;
+
+        let sender_key: PublicKey = message.sender.clone().try_into()?;
+        if !signed.verify_ed25519(sender_key)? {
+            bail!("Invalid signature of the message");
+        }
+
+        Ok(message)
+    }
+
+    pub fn sender_id(&self) -> Result<PeerId> {
+        let key: PublicKey = self.sender.clone().try_into()?;
+        Ok(key.to_peer_id())
+    }
+
+    pub fn recipient_id(&self) -> Result<PeerId> {
+        Ok(PeerId::from_bytes(&self.recipient)?)
+    }
+
+    fn encrypt(recipient: &PeerId, content: &MessageContent) -> Result<MessagePayload> {
+        let recipient_key = x25519_public(&public_key_of(recipient)?)?;
+        let ephemeral = x25519_dalek::StaticSecret::random_from_rng(rand::rngs::OsRng);
+        let shared = ephemeral.diffie_hellman(&recipient_key);
+
+        let mut nonce = [0u8; 12];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        let ciphertext = Self::cipher(shared.as_bytes())
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                bincode::serialize(content)?.as_slice(),
+            )
+            .map_err(|e| anyhow!("could not encrypt message: {e}"))?;
+
+        Ok(MessagePayload::Encrypted {
+            ephemeral_key: x25519_dalek::PublicKey::from(&ephemeral).to_bytes(),
+            nonce,
+            ciphertext,
+        })
+    }
+
+    fn decrypt(
+        keypair: &Keypair,
+        ephemeral_key: [u8; 32],
+        nonce: [u8; 12],
+        ciphertext: &[u8],
+    ) -> Result<MessageContent> {
+        let shared =
+            x25519_secret(keypair)?.diffie_hellman(&x25519_dalek::PublicKey::from(ephemeral_key));
+        let plaintext = Self::cipher(shared.as_bytes())
+            .decrypt(Nonce::from_slice(&nonce), ciphertext)
+            .map_err(|_| anyhow!("could not decrypt message"))?;
+
+        decode(&plaintext)
+    }
+
+    fn cipher(shared_secret: &[u8; 32]) -> ChaCha20Poly1305 {
+        let key = blake3::derive_key(Self::KEY_CONTEXT, shared_secret);
+        ChaCha20Poly1305::new(Key::from_slice(&key))
+    }
+}
+impl UUIDTyped for DirectMessageObject {
+    fn get_type_uuid(&self) -> Uuid {
+        DirectMessageObject::UUID
+    }
+}
+
+/// The key of the peer, which is inlined in the peer IDs of ed25519 keys
+fn public_key_of(peer_id: &PeerId) -> Result<PublicKey> {
+    const IDENTITY_MULTIHASH_CODE: u64 = 0;
+
+    let multihash = peer_id.as_ref();
+    if multihash.code() != IDENTITY_MULTIHASH_CODE {
+        bail!("The key of {peer_id} is not known from its ID");
+    }
+    Ok(PublicKey::try_decode_protobuf(multihash.digest())?)
+}
+
+/// The x25519 key of the peer, the birational map of its ed25519 key
+fn x25519_public(key: &PublicKey) -> Result<x25519_dalek::PublicKey> {
+    let ed25519 = key.clone().try_into_ed25519()?;
+    let point = CompressedEdwardsY(ed25519.to_bytes())
+        .decompress()
+        .ok_or(anyhow!("Invalid ed25519 key"))?;
+    Ok(x25519_dalek::PublicKey::from(
+        point.to_montgomery().to_bytes(),
+    ))
+}
+
+/// The x25519 secret matching `x25519_public` of the ed25519 key, the scalar
+/// derived from the seed the same way as for signing
+fn x25519_secret(keypair: &Keypair) -> Result<x25519_dalek::StaticSecret> {
+    let ed25519 = keypair.clone().try_into_ed25519()?;
+    let hash = Sha512::digest(ed25519.secret().as_ref());
+    let mut scalar = [0u8; 32];
+    scalar.copy_from_slice(&hash[..32]);
+    Ok(x25519_dalek::StaticSecret::from(scalar))
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ResultObject {
-    pub result: Result<(), ()>,
+    pub result: Result<(), ResultErrorCode>,
+}
+
+/// The reason of a failure reported in a ResultObject
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum ResultErrorCode {
+    /// No specific reason
+    Other,
+    /// The object is not stored by the peer
+    NotFound,
+    /// The object is not signed, so the owner can't be verified
+    NotSigned,
+    /// The public key in the query could not be decoded
+    InvalidKey,
+    /// The signature of the query does not match the key in it
+    InvalidSignature,
+    /// The key in the query does not belong to the publisher of the object
+    NotOwner,
+    /// The message could not be verified or decrypted by the recipient
+    InvalidMessage,
+    /// The access policy of the object does not allow the peer to get it
+    AccessDenied,
+    /// The peer was served its quota or the provider is serving too many peers,
+    /// the request can be sent again after the given time
+    QuotaExceeded { retry_after_secs: u64 },
+    /// The query was signed too long ago or too far in the future
+    Expired,
+}
+
+impl Display for ResultErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
 }
 impl ResultObject {
     pub const UUID: Uuid = uuid!("0193a7c0-be9b-72fa-b216-fb91814cba4f");
@@ -329,3 +1419,315 @@ impl UUIDTyped for ResultObject {
         ResultObject::UUID
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libp2p::identity::Keypair;
+
+    #[test]
+    fn verify_chain_test() {
+        let publisher = Keypair::generate_ed25519();
+        let cosigner = Keypair::generate_ed25519();
+        let object: TypedObject = EmptyObject {}.into();
+        let inner: TypedObject = SignedObject::sign_ed25519(object, publisher.clone())
+            .unwrap()
+            .into();
+        let outer = SignedObject::sign_ed25519(inner, cosigner.clone()).unwrap();
+
+        let statuses = outer.verify_chain(&[publisher.public()]).unwrap();
+        assert_eq!(
+            statuses,
+            vec![SignatureStatus::Unverified, SignatureStatus::Verified]
+        );
+
+        let statuses = outer
+            .verify_chain(&[publisher.public(), cosigner.public()])
+            .unwrap();
+        assert_eq!(
+            statuses,
+            vec![SignatureStatus::Verified, SignatureStatus::Verified]
+        );
+    }
+
+    #[test]
+    fn legacy_payload_test() {
+        let file = PlainFileObject {
+            name: "file".to_string(),
+            content: vec![200; 100],
+        };
+        let object: TypedObject = file.clone().into();
+        assert!(object.is_canonical());
+        let decoded: PlainFileObject = TypedObject::try_from_typed(&object).unwrap();
+        assert_eq!(decoded.content, file.content);
+
+        // An object stored in a vault before the canonical encoding
+        let legacy = TypedObject {
+            uuid: PlainFileObject::UUID,
+            data: bincode::serialize(&file).unwrap(),
+        };
+        assert!(!legacy.is_canonical());
+        let decoded: PlainFileObject = TypedObject::try_from_typed(&legacy).unwrap();
+        assert_eq!(decoded.name, file.name);
+        let legacy_id = blake3::hash(&bincode::serialize(&legacy).unwrap());
+        assert_eq!(
+            Hash::try_from(&legacy).unwrap().bytes,
+            *legacy_id.as_bytes()
+        );
+        assert_ne!(
+            Hash::try_from(&legacy).unwrap(),
+            Hash::try_from(&object).unwrap()
+        );
+    }
+
+    #[test]
+    fn multihash_test() {
+        let hash = Hash::blake3(b"object");
+        let multihash = hash.to_multihash();
+        assert_eq!(multihash, HashAlgorithm::Blake3.hash(b"object"));
+        assert_eq!(&multihash.to_bytes()[..2], &[0x1e, 32]);
+
+        // Both encodings give the same ID and the same key
+        let bare = hash.to_string();
+        let encoded = bs58::encode(multihash.to_bytes()).into_string();
+        assert_eq!(Hash::try_from(bare.as_str()).unwrap(), hash);
+        assert_eq!(Hash::try_from(encoded.as_str()).unwrap(), hash);
+        assert_eq!(
+            crate::str_to_file_id(&bare).unwrap(),
+            crate::str_to_file_id(&encoded).unwrap()
+        );
+
+        let sha2 = HashAlgorithm::Sha2_256.hash(b"object");
+        assert_eq!(&sha2.to_bytes()[..2], &[0x12, 32]);
+        assert!(Hash::try_from(sha2.to_bytes().as_slice()).is_err());
+        let sha2_id = bs58::encode(sha2.to_bytes()).into_string();
+        assert_eq!(
+            crate::str_to_file_id(&sha2_id).unwrap().to_vec(),
+            sha2.to_bytes()
+        );
+
+        let unknown = Multihash::wrap(0x99, &[0; 32]).unwrap().to_bytes();
+        assert!(parse_multihash(&unknown).is_err());
+        assert!(Hash::try_from(&[0u8; 31][..]).is_err());
+    }
+
+    #[test]
+    fn profile_record_test() {
+        let keypair = Keypair::generate_ed25519();
+        let peer_id = keypair.public().to_peer_id();
+        let profile = ProfileObject {
+            display_name: "Alice".to_string(),
+            description: String::new(),
+            avatar: None,
+            public_key: keypair.public().into(),
+            updated_at: 1,
+        };
+        let key = ProfileObject::record_key(&peer_id);
+
+        let value = profile.clone().to_record_value(keypair).unwrap();
+        let decoded = ProfileObject::from_record(&key, &value).unwrap();
+        assert_eq!(decoded.display_name, "Alice");
+        assert_eq!(decoded.peer_id().unwrap(), peer_id);
+
+        // Stored under the key of another peer
+        let other_key = ProfileObject::record_key(&PeerId::random());
+        assert!(ProfileObject::from_record(&other_key, &value).is_err());
+
+        // Signed by another key than the one in the profile
+        let forged = profile
+            .to_record_value(Keypair::generate_ed25519())
+            .unwrap();
+        assert!(ProfileObject::from_record(&key, &forged).is_err());
+    }
+
+    #[test]
+    fn object_record_test() {
+        let object = TypedObject {
+            uuid: PlainFileObject::UUID,
+            data: vec![1, 2, 3],
+        };
+        let obj_id = Hash::try_from(&object).unwrap();
+        let value: Vec<u8> = object.clone().try_into().unwrap();
+
+        let key = TypedObject::record_key(&obj_id);
+        assert_eq!(TypedObject::from_record(&key, &value).unwrap(), object);
+        let other_key = TypedObject::record_key(&Hash { bytes: [0; 32] });
+        assert!(TypedObject::from_record(&other_key, &value).is_err());
+    }
+
+    #[test]
+    fn rotation_test() {
+        let first = Keypair::generate_ed25519();
+        let second = Keypair::generate_ed25519();
+        let third = Keypair::generate_ed25519();
+        let first_rotation = RotationObject::rotate(&first, &second).unwrap();
+        let second_rotation = RotationObject::rotate(&second, &third).unwrap();
+
+        let key = RotationObject::record_key(&first.public().to_peer_id());
+        let value = first_rotation.to_record_value().unwrap();
+        assert!(RotationObject::from_record(&key, &value).is_ok());
+        let wrong_key = RotationObject::record_key(&second.public().to_peer_id());
+        assert!(RotationObject::from_record(&wrong_key, &value).is_err());
+
+        let keys = rotated_keys(
+            &first.public(),
+            &[second_rotation.clone(), first_rotation.clone()],
+        );
+        assert_eq!(keys, vec![first.public(), second.public(), third.public()]);
+
+        // A rotation signed by one of the keys only does not continue the chain
+        let mut forged = RotationObject::rotate(&second, &Keypair::generate_ed25519()).unwrap();
+        forged.old_signature = first_rotation.old_signature.clone();
+        assert!(forged.verify().is_err());
+        let keys = rotated_keys(&second.public(), &[forged]);
+        assert_eq!(keys, vec![second.public()]);
+    }
+
+    #[test]
+    fn group_post_test() {
+        let owner = Keypair::generate_ed25519();
+        let member = Keypair::generate_ed25519();
+        let stranger = Keypair::generate_ed25519();
+        let group = UserGroup::create("Friends".to_string(), &owner).unwrap();
+        group.verify().unwrap();
+        let group_id = group.definition.id.clone();
+
+        let token = GroupAccessToken::issue(
+            &group_id,
+            &member.public().to_peer_id(),
+            unix_now() + 60,
+            &owner,
+        )
+        .unwrap();
+        let content = MessageContent::Text("Hi".to_string());
+
+        let owner_post = GroupObject::post(&group_id, content.clone(), None, &owner).unwrap();
+        assert!(owner_post.verify_post(&group).is_ok());
+        let member_post =
+            GroupObject::post(&group_id, content.clone(), Some(token.clone()), &member).unwrap();
+        let verified = member_post.verify_post(&group).unwrap();
+        assert_eq!(verified.author, member.public().to_peer_id());
+
+        // The token of another member does not work, as does a token issued by a member
+        let stolen = GroupObject::post(&group_id, content.clone(), Some(token), &stranger).unwrap();
+        assert!(stolen.verify_post(&group).is_err());
+        let forged_token = GroupAccessToken::issue(
+            &group_id,
+            &stranger.public().to_peer_id(),
+            unix_now() + 60,
+            &member,
+        )
+        .unwrap();
+        let forged = GroupObject::post(&group_id, content, Some(forged_token), &stranger).unwrap();
+        assert!(forged.verify_post(&group).is_err());
+    }
+
+    #[test]
+    fn access_policy_test() {
+        let owner = Keypair::generate_ed25519();
+        let member = Keypair::generate_ed25519().public().to_peer_id();
+        let stranger = Keypair::generate_ed25519().public().to_peer_id();
+        let group = UserGroup::create("Friends".to_string(), &owner).unwrap();
+        let token = GroupAccessToken::issue(&group.definition.id, &member, unix_now() + 60, &owner)
+            .unwrap();
+
+        assert!(AccessPolicy::Public.check(&stranger, None).is_ok());
+
+        let users = AccessPolicy::Users(vec![user_id(&member)]);
+        assert!(users.check(&member, None).is_ok());
+        assert_eq!(
+            users.check(&stranger, None),
+            Err(ResultErrorCode::AccessDenied)
+        );
+
+        let group = AccessPolicy::Group(group);
+        assert!(group.check(&member, Some(&token)).is_ok());
+        assert!(group.check(&owner.public().to_peer_id(), None).is_ok());
+        assert!(group.check(&member, None).is_err());
+        assert!(group.check(&stranger, Some(&token)).is_err());
+    }
+
+    #[test]
+    fn direct_message_test() {
+        let sender = Keypair::generate_ed25519();
+        let recipient = Keypair::generate_ed25519();
+        let sender_id = sender.public().to_peer_id();
+        let recipient_id = recipient.public().to_peer_id();
+        let content = MessageContent::Text("Hello".to_string());
+
+        for encrypt in [false, true] {
+            let (signed, id) =
+                DirectMessageObject::seal(&sender, &recipient_id, content.clone(), encrypt)
+                    .unwrap();
+            let opened = DirectMessageObject::open(&signed, &sender_id, &recipient).unwrap();
+            assert_eq!(opened.id, id);
+            assert_eq!(opened.content, content);
+            assert_eq!(opened.encrypted, encrypt);
+
+            // Only the recipient can open it and only as sent by the sender
+            assert!(DirectMessageObject::open(&signed, &sender_id, &sender).is_err());
+            assert!(DirectMessageObject::open(&signed, &recipient_id, &recipient).is_err());
+        }
+    }
+
+    #[test]
+    fn signed_file_size_test() {
+        let mut content = vec![0u8; 10_000];
+        rand::thread_rng().fill_bytes(&mut content);
+        let file = PlainFileObject {
+            name: "file".to_string(),
+            content: content.clone(),
+        };
+        let keypair = Keypair::generate_ed25519();
+        let signed = SignedObject::sign_ed25519(file.into(), keypair.clone()).unwrap();
+        assert!(signed.verify_ed25519(keypair.public()).unwrap());
+
+        // The bytes are not encoded as arrays of integers at any level
+        let payload = TypedObject::from(signed);
+        assert!(payload.data.len() < content.len() + 256);
+    }
+
+    #[test]
+    fn tag_hash_test() {
+        assert_eq!(TagObject::normalize("  Rust "), "rust");
+        assert_eq!(TagObject::tag_hash("Rust "), TagObject::tag_hash("rust"));
+        assert_ne!(TagObject::tag_hash("rust"), TagObject::tag_hash("rusty"));
+
+        let tags = |tags: Vec<String>| TagObject {
+            object: Hash { bytes: [1; 32] },
+            tags,
+        };
+        assert!(tags(vec!["rust".to_string()]).is_valid());
+        assert!(!tags(vec![]).is_valid());
+        assert!(!tags(vec![" ".to_string()]).is_valid());
+        assert!(!tags(vec!["a".repeat(TagObject::MAX_TAG_LEN + 1)]).is_valid());
+    }
+
+    #[test]
+    fn delete_object_query_test() {
+        let keypair = Keypair::generate_ed25519();
+        let query =
+            DeleteObjectQuery::sign_ed25519(Hash { bytes: [1; 32] }, keypair.clone()).unwrap();
+        assert!(query.verify_ed25519().is_ok());
+
+        // The signing time is covered by the signature
+        let mut forged = query.clone();
+        forged.signed_at += 1;
+        assert!(matches!(
+            forged.verify_ed25519(),
+            Err(ResultErrorCode::InvalidSignature)
+        ));
+
+        let mut stale = query.clone();
+        stale.signed_at -= DeleteObjectQuery::MAX_AGE.as_secs() + 1;
+        stale.signature = Signature {
+            bytes: keypair
+                .sign(&DeleteObjectQuery::signed_bytes(&stale.id, stale.signed_at))
+                .unwrap(),
+        };
+        assert!(matches!(
+            stale.verify_ed25519(),
+            Err(ResultErrorCode::Expired)
+        ));
+    }
+}
@@ -0,0 +1,169 @@
+use std::fmt::Write;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde_json::{json, Map, Value};
+use strum_macros::{Display, EnumString};
+
+use crate::parser;
+use crate::proto::{ChunkObject, GroupObject, PlainFileObject, SignedObject, TypedObject};
+use crate::types::ObjectLayer;
+
+///! The module renders any typed object for the humans. The object is shown as the
+///! tree of the objects nested in it, e.g. a signed envelope with the file inside,
+///! with the fields decoded from every one of them, or as JSON of the same tree.
+///! The types unknown to the daemon are shown with their type IDs and sizes only.
+
+/// The longest content shown in the tree, the longer ones are cut
+const MAX_TEXT_CONTENT: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Display, EnumString)]
+#[strum(serialize_all = "lowercase", ascii_case_insensitive)]
+pub enum RenderFormat {
+    /// The indented tree of the nested objects, followed by the text content
+    #[default]
+    Tree,
+    Json,
+}
+
+/// Renders the object with the ID in the format
+pub fn render(id: &str, object: &TypedObject, format: RenderFormat) -> Result<String> {
+    let layers = parser::describe(object);
+    let content = content(object);
+
+    match format {
+        RenderFormat::Tree => Ok(render_tree(id, &layers, content.as_deref())),
+        RenderFormat::Json => {
+            let mut json = json!({ "id": id });
+            if let Some(inner) = layers_json(&layers) {
+                json["object"] = inner;
+            }
+            json["content"] = json!(content.map(|c| String::from_utf8_lossy(&c).into_owned()));
+            Ok(serde_json::to_string_pretty(&json)?)
+        }
+    }
+}
+
+fn render_tree(id: &str, layers: &[ObjectLayer], content: Option<&[u8]>) -> String {
+    let mut text = String::new();
+    let _ = writeln!(text, "{id}");
+    for (depth, layer) in layers.iter().enumerate() {
+        let indent = "   ".repeat(depth);
+        let type_name = layer.type_name.as_deref().unwrap_or("unknown");
+        let _ = writeln!(
+            text,
+            "{indent}└─ {type_name} ({}), {} bytes",
+            layer.type_id, layer.size
+        );
+        for (name, value) in &layer.properties {
+            let _ = writeln!(text, "{indent}   {name}: {value}");
+        }
+        if let Some(created_at) = layer.created_at {
+            let created_at: DateTime<Utc> = created_at.into();
+            let _ = writeln!(text, "{indent}   created: {}", created_at.to_rfc3339());
+        }
+    }
+
+    let Some(content) = content else {
+        return text;
+    };
+    let _ = writeln!(text);
+    match std::str::from_utf8(content) {
+        Ok(content) => match content.char_indices().nth(MAX_TEXT_CONTENT) {
+            Some((cut, _)) => {
+                let _ = writeln!(text, "{}", &content[..cut]);
+                let _ = writeln!(text, "[{} more bytes]", content.len() - cut);
+            }
+            None => text.push_str(content),
+        },
+        Err(_) => {
+            let _ = writeln!(text, "[{} bytes of binary content]", content.len());
+        }
+    }
+    text
+}
+
+/// The layers as the JSON objects, every one with the next one nested in it
+fn layers_json(layers: &[ObjectLayer]) -> Option<Value> {
+    let (layer, inner) = layers.split_first()?;
+    let fields: Map<String, Value> = layer
+        .properties
+        .iter()
+        .map(|(name, value)| (name.clone(), json!(value)))
+        .collect();
+    let created_at = layer
+        .created_at
+        .map(|at| DateTime::<Utc>::from(at).to_rfc3339());
+
+    let mut json = json!({
+        "type": layer.type_name,
+        "type_id": layer.type_id,
+        "size": layer.size,
+        "fields": fields,
+        "created": created_at,
+    });
+    if let Some(inner) = layers_json(inner) {
+        json["inner"] = inner;
+    }
+    Some(json)
+}
+
+/// The content of the file or the chunk wrapped in the object, if it's one
+fn content(object: &TypedObject) -> Option<Vec<u8>> {
+    let mut object = object.clone();
+    loop {
+        object = match object.uuid {
+            SignedObject::UUID => {
+                TypedObject::try_from_typed::<SignedObject>(&object)
+                    .ok()?
+                    .object
+            }
+            GroupObject::UUID => {
+                TypedObject::try_from_typed::<GroupObject>(&object)
+                    .ok()?
+                    .object
+                    .object
+            }
+            PlainFileObject::UUID => {
+                let file: PlainFileObject = TypedObject::try_from_typed(&object).ok()?;
+                return Some(file.content);
+            }
+            ChunkObject::UUID => {
+                let chunk: ChunkObject = TypedObject::try_from_typed(&object).ok()?;
+                return Some(chunk.content);
+            }
+            _ => return None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::Signature;
+
+    #[test]
+    fn render_test() {
+        let file: TypedObject = PlainFileObject {
+            name: "notes.txt".to_string(),
+            content: b"hello".to_vec(),
+        }
+        .into();
+        let signed: TypedObject = SignedObject {
+            object: file,
+            signature: Signature { bytes: vec![1; 64] },
+        }
+        .into();
+
+        let tree = render("id", &signed, RenderFormat::Tree).unwrap();
+        assert!(tree.contains("└─ signed"));
+        assert!(tree.contains("      name: notes.txt"));
+        assert!(tree.ends_with("\nhello"));
+
+        let json: Value =
+            serde_json::from_str(&render("id", &signed, RenderFormat::Json).unwrap()).unwrap();
+        assert_eq!(json["object"]["type"], "signed");
+        assert_eq!(json["object"]["inner"]["fields"]["name"], "notes.txt");
+        assert_eq!(json["content"], "hello");
+    }
+}
@@ -1,6 +1,84 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+use strum_macros::{Display, EnumString};
 use uuid::Uuid;
 
+use crate::proto::TypedObject;
+
+/// Changes applied to the running node when its config was reloaded
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ConfigReloadSummary {
+    pub added_bootstrap_nodes: Vec<String>,
+    pub removed_bootstrap_nodes: Vec<String>,
+    pub added_listen_addresses: Vec<String>,
+    pub removed_listen_addresses: Vec<String>,
+    /// The changes which could not be applied, the others are applied anyway
+    pub errors: Vec<String>,
+}
+
+/// Number of the objects and the fragments written to or merged from a vault snapshot
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct VaultSnapshotSummary {
+    pub typed_objects: usize,
+    pub published_objects: usize,
+    pub fragments: usize,
+    /// Entries already present in the vault, only counted on import
+    pub skipped: usize,
+}
+
+/// The profile of a peer, resolved from the DHT and verified
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PeerProfile {
+    pub peer_id: String,
+    pub display_name: String,
+    pub description: String,
+    /// ID of the object with the avatar image
+    pub avatar: Option<String>,
+}
+
+/// The merged answers of the peers to a query sent to the network
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct QueryResults {
+    /// IDs of the matching objects, without duplicates
+    pub ids: Vec<String>,
+    pub peers_asked: usize,
+    pub peers_answered: usize,
+}
+
+/// A module of the node handling one object type
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ModuleInfo {
+    pub name: String,
+    pub uuid: Uuid,
+    pub enabled: bool,
+    /// The settings as JSON, if there are any
+    pub settings: Option<String>,
+    /// The module is run by an external process registered over the daemon socket
+    #[serde(default)]
+    pub external: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum ModuleCallKind {
+    /// Another peer sent the object to be stored. Answering with no object accepts it,
+    /// the daemon stores and provides it then
+    Store,
+    /// Another peer sent a query of the type. The answer is sent back to the peer,
+    /// no object means it was not found
+    Query,
+}
+
+/// An object of a type handled by an external module, forwarded to its process
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ModuleCall {
+    pub call_id: u64,
+    pub kind: ModuleCallKind,
+    pub node_name: String,
+    pub object: TypedObject,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct NodeInfo {
     pub name: String,
@@ -8,6 +86,12 @@ pub struct NodeInfo {
     pub is_running: bool,
     pub config_addresses: Vec<String>,
     pub running_addresses: Vec<String>,
+    /// Average round trip times of the connected peers, empty if the node is not running
+    #[serde(default)]
+    pub peer_latencies: HashMap<String, Duration>,
+    /// The keypair of the node is encrypted and was not unlocked yet
+    #[serde(default)]
+    pub is_locked: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -15,3 +99,478 @@ pub struct TypedObjectInfo {
     pub id: String,
     pub type_id: Uuid,
 }
+
+/// Result of publishing one of many files, the ID of the object or the error
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PublishFileResult {
+    pub path: PathBuf,
+    pub result: Result<String, String>,
+}
+
+/// Result of checking one signature of a chain of nested signed objects
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum SignatureStatus {
+    /// The signature was made with one of the known keys
+    Verified,
+    /// The signature does not match any of the known keys, it is either
+    /// invalid or made by an unknown signer
+    Unverified,
+}
+
+/// Health summary of an object, used to debug failing downloads
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ObjectVerification {
+    pub id: String,
+    pub stored_locally: bool,
+    pub published: bool,
+    /// Whether the hash of the locally stored object matches its ID,
+    /// None if the object is not stored locally
+    pub hash_matches: Option<bool>,
+    pub type_id: Option<Uuid>,
+    /// Statuses of the signatures, starting from the outermost signed object
+    pub signatures: Vec<SignatureStatus>,
+    pub providers: usize,
+}
+
+impl ObjectVerification {
+    pub fn is_healthy(&self) -> bool {
+        self.hash_matches != Some(false)
+            && self
+                .signatures
+                .iter()
+                .all(|s| *s == SignatureStatus::Verified)
+            && self.providers > 0
+    }
+}
+
+/// How the object got to the vault of the node
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Display, EnumString)]
+#[strum(serialize_all = "lowercase", ascii_case_insensitive)]
+pub enum ProvenanceKind {
+    /// Published by the node itself
+    Published,
+    /// Sent by a peer which published it, to be stored and provided
+    Stored,
+    /// Downloaded by the node, or reconstructed from the downloaded chunks
+    Downloaded,
+}
+
+/// Where a stored object came from, recorded when it was stored for the first time
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ObjectProvenance {
+    /// The peer which sent the object, None if the node made it itself
+    pub source_peer: Option<String>,
+    pub received_at: SystemTime,
+    pub kind: ProvenanceKind,
+    /// The outermost signature checked against the key of the source peer, None
+    /// if the object is not signed or has no source peer
+    pub signature: Option<SignatureStatus>,
+}
+
+/// One of the objects nested in an object, e.g. the file inside a signed object
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ObjectLayer {
+    pub type_id: Uuid,
+    /// None for the types unknown to the daemon
+    pub type_name: Option<String>,
+    /// The size of the encoded payload
+    pub size: u64,
+    /// The fields decoded from the payload, e.g. the name of a file
+    pub properties: Vec<(String, String)>,
+    /// When the object was made, for the types which record it
+    pub created_at: Option<SystemTime>,
+}
+
+/// An object stored by the node, or found in the network
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ObjectInfo {
+    pub id: String,
+    pub type_id: Uuid,
+    pub size: u64,
+    pub published: bool,
+    /// None for the objects stored before their provenance was recorded
+    pub provenance: Option<ObjectProvenance>,
+    /// False if the object was downloaded only to be described
+    #[serde(default)]
+    pub stored_locally: bool,
+    /// The nested objects, the outermost one first
+    #[serde(default)]
+    pub layers: Vec<ObjectLayer>,
+    /// The peer which made the outermost signature, if it's made with a known key
+    #[serde(default)]
+    pub signer: Option<String>,
+    /// When the object was made, from its content or from its publication by the node
+    #[serde(default)]
+    pub created_at: Option<SystemTime>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NodeStatus {
+    pub uptime: Duration,
+    pub connected_peers: usize,
+    pub routing_table_size: usize,
+    pub buckets: Vec<BucketInfo>,
+    pub pending_queries: usize,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    /// The objects served to other peers since the node started
+    #[serde(default)]
+    pub objects_served: u64,
+    pub link_impairment: LinkImpairmentStats,
+    #[serde(default)]
+    pub uploads: UploadStats,
+    #[serde(default)]
+    pub rejected_records: RecordRejectStats,
+}
+
+/// The state of the daemon, cheap to get, for checking it's alive and responsive
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DaemonHealth {
+    pub version: String,
+    pub protocol_version: u32,
+    pub uptime: Duration,
+    pub running_nodes: usize,
+    /// The requests of all the connections being handled, besides the ping
+    pub queued_requests: usize,
+}
+
+/// The serving of the objects to other peers, limited by the upload limits of the config
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct UploadStats {
+    /// The objects being sent now
+    pub active_uploads: usize,
+    /// The bytes served to all the peers in the last hour
+    pub bytes_last_hour: u64,
+    /// The requests rejected since the node started, as over a limit
+    pub rejected_requests: u64,
+}
+
+/// The records and provider records put by other peers which the node didn't store,
+/// by the reasons, since the node started
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct RecordRejectStats {
+    /// Put by a blocked or banned peer
+    pub blocked_peer: u64,
+    /// Put by a peer over its rate limit
+    pub rate_limited: u64,
+    pub too_large: u64,
+    /// Not a record used by the network
+    pub malformed: u64,
+    /// Stored under another key than the one derived from the value
+    pub wrong_key: u64,
+    /// Not signed by the owner of the record
+    pub invalid_signature: u64,
+    /// Older than the record stored under the same key
+    pub outdated: u64,
+}
+
+impl RecordRejectStats {
+    /// The counts with the names of their reasons
+    pub fn by_reason(&self) -> [(&'static str, u64); 7] {
+        [
+            ("blocked_peer", self.blocked_peer),
+            ("rate_limited", self.rate_limited),
+            ("too_large", self.too_large),
+            ("malformed", self.malformed),
+            ("wrong_key", self.wrong_key),
+            ("invalid_signature", self.invalid_signature),
+            ("outdated", self.outdated),
+        ]
+    }
+
+    pub fn total(&self) -> u64 {
+        self.by_reason().iter().map(|(_, count)| count).sum()
+    }
+}
+
+/// The effect of the link conditions injected by the test runner since the node
+/// started, all zeros outside of the tests
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct LinkImpairmentStats {
+    /// Streams whose first message was delayed
+    pub delayed_streams: u64,
+    /// Streams which were lost
+    pub dropped_streams: u64,
+    /// The sum of the delays
+    pub injected_delay: Duration,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum NodeEventKind {
+    Connection,
+    Publish,
+    Download,
+    Error,
+    Message,
+    /// The daemon restarted the node, or gave up restarting it
+    Restart,
+}
+
+/// Something that happened in a running node, for showing in the UIs
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NodeEvent {
+    /// Increasing ID of the event, used to get only the new events
+    pub id: u64,
+    pub timestamp: SystemTime,
+    pub kind: NodeEventKind,
+    pub message: String,
+}
+
+/// How much the user trusts a contact
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default, Display, EnumString,
+)]
+#[strum(serialize_all = "lowercase", ascii_case_insensitive)]
+pub enum TrustLevel {
+    /// Not known who the peer is, it doesn't get the objects shared with the contacts
+    Unknown,
+    #[default]
+    Known,
+    Trusted,
+}
+
+/// A peer known to the user, kept in the vault of the node
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Contact {
+    pub peer_id: String,
+    pub alias: String,
+    /// The last address the node dialed the peer at, dialed again when the node starts
+    pub address: Option<String>,
+    pub last_seen: Option<SystemTime>,
+    pub trust: TrustLevel,
+}
+
+/// The content of a direct message between peers
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum MessageContent {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+/// A direct message received by the node, kept in the vault
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct InboxMessage {
+    /// Hash of the signed message
+    pub id: String,
+    pub sender: String,
+    /// The time claimed by the sender
+    pub sent_at: SystemTime,
+    pub received_at: SystemTime,
+    /// Whether the message was encrypted for this node on the way
+    pub encrypted: bool,
+    pub content: MessageContent,
+}
+
+/// A post in the feed of a group the node is a member of, kept in the vault
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GroupPost {
+    /// Hash of the signed post
+    pub id: String,
+    pub group: String,
+    pub author: String,
+    /// The time claimed by the author
+    pub posted_at: SystemTime,
+    pub content: MessageContent,
+}
+
+/// A maintenance task the scheduler of the node runs periodically
+#[derive(
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    PartialOrd,
+    Ord,
+    Display,
+    EnumString,
+)]
+#[serde(rename_all = "kebab-case")]
+#[strum(serialize_all = "kebab-case", ascii_case_insensitive)]
+pub enum ScheduledTask {
+    /// Announces again that the node provides its objects, e.g. after it was offline
+    Reprovide,
+    /// Sends the published objects with too few providers to more peers
+    Replication,
+    /// Fetches the messages left for the node at its closest peers
+    MailboxFetch,
+    /// Bootstraps the node again when it has no peers
+    BootstrapHealth,
+    /// Removes the least requested objects stored for other peers when the vault
+    /// stores more than the cache limit
+    CacheGc,
+    /// Asks a few connected peers for the peers they are connected to
+    PeerExchange,
+    /// Records the stats of the node in the history kept in its vault
+    StatsSample,
+}
+
+impl ScheduledTask {
+    pub const ALL: [ScheduledTask; 7] = [
+        ScheduledTask::Reprovide,
+        ScheduledTask::Replication,
+        ScheduledTask::MailboxFetch,
+        ScheduledTask::BootstrapHealth,
+        ScheduledTask::CacheGc,
+        ScheduledTask::PeerExchange,
+        ScheduledTask::StatsSample,
+    ];
+}
+
+/// How often an object in the vault of the node was served to other peers
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ObjectPopularity {
+    pub id: String,
+    pub fetch_count: u64,
+    pub last_served: SystemTime,
+    /// Published by the node, never removed by the cache GC
+    pub published: bool,
+}
+
+/// A plain text file in the vault matching a full-text search
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TextMatch {
+    pub id: String,
+    /// The name of the file
+    pub name: String,
+    /// The part of the text around the match, with the matched words in brackets
+    pub snippet: String,
+}
+
+/// An object matching a search, the objects matching more of the tags go first
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SearchResult {
+    pub id: String,
+    /// The tags of the search the object has
+    pub matched_tags: Vec<String>,
+    /// Found in the index of the node, not only by the peers
+    pub local: bool,
+}
+
+/// The state of a scheduled task of the running node
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ScheduledTaskInfo {
+    pub task: ScheduledTask,
+    /// None if the task runs only when asked to
+    pub interval: Option<Duration>,
+    pub next_run: Option<SystemTime>,
+    pub running: bool,
+    pub runs: u64,
+    pub last_run: Option<SystemTime>,
+    pub last_duration: Option<Duration>,
+    /// The error of the last run, None if it succeeded
+    pub last_error: Option<String>,
+}
+
+/// The kind of an inbound request recorded in the audit log
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Display, EnumString)]
+#[strum(serialize_all = "lowercase", ascii_case_insensitive)]
+pub enum AuditRequestKind {
+    /// A peer asked for an object stored by the node
+    Get,
+    /// A peer sent an object for the node to store and provide
+    Store,
+    /// The publisher of an object asked the node to delete it
+    Delete,
+}
+
+/// An inbound request served or rejected by the node, kept in the vault
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AuditEntry {
+    pub peer_id: String,
+    pub object_id: String,
+    pub kind: AuditRequestKind,
+    /// The reason of the rejection, None if the request was served
+    pub rejection: Option<String>,
+    pub at: SystemTime,
+}
+
+/// Selects the entries of the audit log, every set field must match
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct AuditFilter {
+    pub peer_id: Option<String>,
+    pub object_id: Option<String>,
+    pub kind: Option<AuditRequestKind>,
+    pub since: Option<SystemTime>,
+    pub rejected_only: bool,
+    /// Only the newest entries, up to the limit
+    pub limit: Option<usize>,
+}
+
+impl AuditFilter {
+    /// Whether the entry matches the filter, the limit is not checked
+    pub fn matches(&self, entry: &AuditEntry) -> bool {
+        self.peer_id.as_ref().map_or(true, |p| *p == entry.peer_id)
+            && self
+                .object_id
+                .as_ref()
+                .map_or(true, |o| *o == entry.object_id)
+            && self.kind.map_or(true, |k| k == entry.kind)
+            && self.since.map_or(true, |s| entry.at >= s)
+            && (!self.rejected_only || entry.rejection.is_some())
+    }
+}
+
+/// The activity of a node at a time, sampled periodically while the node runs and
+/// kept in its vault
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct NodeStatsSample {
+    pub at: SystemTime,
+    pub connected_peers: usize,
+    /// Since the node started
+    pub bytes_sent: u64,
+    /// Since the node started
+    pub bytes_received: u64,
+    /// Since the node started
+    pub objects_served: u64,
+}
+
+/// Who may get a published object, resolved by the node to its access policy
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub enum ObjectAccess {
+    #[default]
+    Public,
+    /// The contacts of the node at the time of publishing, except the ones with
+    /// the unknown trust level
+    Contacts,
+    /// The members of the group with the ID
+    Group(String),
+}
+
+/// A peer the node is connected to or knows from its routing table
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PeerInfo {
+    pub peer_id: String,
+    pub addresses: Vec<String>,
+    pub connected: bool,
+    pub in_routing_table: bool,
+    /// The index of the bucket of the routing table the peer is in
+    pub bucket: Option<u32>,
+    pub latency: Option<Duration>,
+    /// The newest version of the object sender protocol supported by the peer,
+    /// known only for the connected peers
+    pub object_sender_version: Option<u8>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BucketInfo {
+    pub index: u32,
+    pub num_entries: usize,
+    /// The peers of the bucket the node is connected to
+    #[serde(default)]
+    pub num_connected: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct PeerScore {
+    pub peer_id: String,
+    pub failed_integrity_checks: u32,
+    pub timeouts: u32,
+    pub protocol_violations: u32,
+    /// The last misbehaviour, the counts expire some time after it
+    #[serde(default)]
+    pub last_reported_at: Option<SystemTime>,
+}
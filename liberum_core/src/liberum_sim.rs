@@ -0,0 +1,56 @@
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+use sim::scenario::Scenario;
+use tracing::level_filters::LevelFilter;
+
+pub mod connection;
+pub mod logging;
+pub mod node;
+pub mod sim;
+pub mod swarm_runner;
+pub mod vault;
+
+///! Runs a simulation of the network described by a scenario file and prints the
+///! results of its steps. Exits with an error if any step failed.
+///!
+///! Usage: liberum_sim <scenario.json>
+
+#[tokio::main]
+async fn run(path: PathBuf) -> Result<()> {
+    let json = tokio::fs::read_to_string(&path).await?;
+    let scenario = Scenario::from_json(&json)?;
+    let reports = sim::run_scenario(&scenario).await?;
+
+    let mut failed = 0;
+    for report in &reports {
+        let result = match &report.error {
+            Some(e) => {
+                failed += 1;
+                format!("FAILED: {e}")
+            }
+            None => "ok".to_string(),
+        };
+        println!(
+            "{:>3} {:>9.3}s {} {result}",
+            report.step,
+            report.duration.as_secs_f64(),
+            report.description
+        );
+    }
+
+    if failed > 0 {
+        return Err(anyhow!("{failed} of {} steps failed", reports.len()));
+    }
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    logging::setup("liberum_sim", LevelFilter::WARN, None);
+
+    let args: Vec<String> = std::env::args().collect();
+    let Some(path) = args.get(1) else {
+        return Err(anyhow!("Usage: liberum_sim <scenario.json>"));
+    };
+    run(PathBuf::from(path))
+}
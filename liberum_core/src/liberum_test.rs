@@ -1,12 +1,22 @@
 use core::error;
 use std::{
-    collections::HashMap, fs::File, io::Write, iter::zip, panic, path::PathBuf, str::FromStr,
-    sync::Arc, time::Duration, usize,
+    collections::HashMap,
+    fs::File,
+    io::Write,
+    iter::zip,
+    panic,
+    path::PathBuf,
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, Instant},
+    usize,
 };
 
 use connection::AppContext;
+use liberum_core::node_config::LinkCondition;
+use liberum_core::types::{LinkImpairmentStats, ObjectAccess};
 use liberum_core::{node_config::NodeConfig, DaemonError, DaemonRequest, DaemonResponse};
-use libp2p::Multiaddr;
+use libp2p::{Multiaddr, PeerId};
 use node::store::NodeStore;
 use tokio::{sync::RwLock, time::sleep};
 use tonic::{
@@ -20,14 +30,19 @@ use crate::test_protocol::test_scenario::node_definition::NodeDefinitionLevel;
 use crate::test_protocol::test_scenario::test_part_scenario::Part::Simple;
 
 use test_protocol::{
-    action_resoult::{Details, DialNodeResult, GetObjectResult, PublishObjectResult},
+    action_resoult::{
+        crash_restart_result, CrashRestartResult, Details, DialNodeResult, GetObjectResult,
+        LinkStats, PublishObjectResult, StartNodeResult, StopNodeResult,
+    },
+    assertion::Check,
     callable_nodes::CallableNode,
     identity_server_client::IdentityServerClient,
-    Action, ActionResoult, DaemonQueryStats, Identity, NodeInstance, NodesCreated, TestPartResult,
-    TestScenario,
+    Action, ActionResoult, Assertion, AssertionResult, DaemonQueryStats, Identity, NodeInstance,
+    NodesCreated, TestPartResult, TestScenario,
 };
-use tracing::{error, info};
+use tracing::{error, info, warn};
 pub mod connection;
+pub mod logging;
 pub mod node;
 pub mod swarm_runner;
 pub mod test_runner;
@@ -37,6 +52,12 @@ pub mod test_protocol {
     tonic::include_proto!("test_protocol");
 }
 
+/// How long a restarted node may take to connect to a peer, if the action does
+/// not say
+const DEFAULT_RECOVERY_TIMEOUT: Duration = Duration::from_secs(30);
+const RECOVERY_POLL_INTERVAL: Duration = Duration::from_millis(100);
+const ASSERTION_RETRY_INTERVAL: Duration = Duration::from_millis(500);
+
 struct TestContext {
     scenario: TestScenario,
     callable_nodes: HashMap<u64, CallableNode>,
@@ -85,8 +106,20 @@ pub(crate) async fn run_test(
         .into_inner();
 
     let new_nodes = handle_create_nodes(&test_scenario, app_context.clone()).await;
+    let mut peer_ids: HashMap<u64, String> = new_nodes
+        .nodes
+        .iter()
+        .map(|n| (n.node_id, n.node_hash.clone()))
+        .collect();
     sleep(Duration::from_secs(1)).await;
     let diallable_nodes = client.test_ready(new_nodes).await?.into_inner();
+    peer_ids.extend(
+        diallable_nodes
+            .nodes
+            .iter()
+            .map(|n| (n.node_id, n.node_hash.clone())),
+    );
+    apply_link_conditions(&test_scenario, &peer_ids, &app_context).await?;
 
     let mut test_context = TestContext {
         scenario: test_scenario,
@@ -143,15 +176,117 @@ pub(crate) async fn run_test(
             }
         }
 
+        let assertions: Vec<Assertion> = ctx
+            .read()
+            .await
+            .scenario
+            .assertions
+            .iter()
+            .filter(|a| a.part_id == descriptor.part_id)
+            .cloned()
+            .collect();
+        let mut assertion_tasks = Vec::new();
+        for assertion in assertions {
+            assertion_tasks.push(tokio::spawn(evaluate_assertion(assertion, ctx.clone())));
+        }
+        let mut assertion_results = Vec::new();
+        for handle in assertion_tasks {
+            assertion_results.push(handle.await?);
+        }
+
         result_tx
             .send(TestPartResult {
                 resoults: action_result,
+                assertions: assertion_results,
             })
             .await?;
     }
     Ok(())
 }
 
+/// Checks the assertion until it holds or its time is up
+async fn evaluate_assertion(
+    assertion: Assertion,
+    ctx: Arc<tokio::sync::RwLock<TestContext>>,
+) -> AssertionResult {
+    let started_at = Instant::now();
+    let ctx = ctx.read().await;
+    let within = match &assertion.check {
+        Some(Check::ObjectRetrievable(check)) => check.within_millis,
+        Some(Check::ProviderCount(check)) => check.within_millis,
+        None => 0,
+    };
+    let within = Duration::from_millis(within);
+
+    let failure = loop {
+        let failure = check_assertion(&assertion, &ctx).await.err();
+        if failure.is_none() || started_at.elapsed() + ASSERTION_RETRY_INTERVAL > within {
+            break failure;
+        }
+        sleep(ASSERTION_RETRY_INTERVAL).await;
+    };
+    if let Some(failure) = &failure {
+        warn!(
+            assertion_id = assertion.assertion_id,
+            failure, "Assertion failed"
+        );
+    }
+
+    AssertionResult {
+        assertion_id: assertion.assertion_id,
+        passed: failure.is_none(),
+        failure,
+        elapsed_in_nano: started_at.elapsed().as_nanos() as u64,
+    }
+}
+
+/// Checks the assertion once, returns why it does not hold
+async fn check_assertion(assertion: &Assertion, ctx: &TestContext) -> Result<(), String> {
+    let object_id = |hash_id: u64| {
+        ctx.hash_map
+            .get(&hash_id)
+            .cloned()
+            .ok_or(format!("Object {hash_id} is not known"))
+    };
+
+    match &assertion.check {
+        Some(Check::ObjectRetrievable(check)) => {
+            let request = DaemonRequest::DownloadFile {
+                node_name: assertion.node_name.clone(),
+                id: object_id(check.object_hash_id)?,
+                group: None,
+                force_network: false,
+            };
+            daemon_request(request, ctx.app_context.clone())
+                .await
+                .map_err(error_message)?;
+            Ok(())
+        }
+        Some(Check::ProviderCount(check)) => {
+            let request = DaemonRequest::GetProviders {
+                node_name: assertion.node_name.clone(),
+                id: object_id(check.object_hash_id)?,
+                stream: false,
+            };
+            let response = daemon_request(request, ctx.app_context.clone())
+                .await
+                .map_err(error_message)?;
+            let DaemonResponse::Providers { ids, .. } = response else {
+                return Err("Daemon returned wrong response".to_string());
+            };
+            match ids.len() as u64 >= check.at_least {
+                true => Ok(()),
+                false => Err(format!(
+                    "Found {} providers, expected at least {}",
+                    ids.len(),
+                    check.at_least
+                )),
+            }
+        }
+        None => Err("The assertion has no check".to_string()),
+    }
+}
+
 async fn handle_simple_action(
     action: Action,
     _ctx: Arc<tokio::sync::RwLock<TestContext>>,
@@ -162,18 +297,39 @@ async fn handle_simple_action(
         ..Default::default()
     };
     let ctx = _ctx.read().await;
+    let node_name = action.node_name.clone();
+    let impairment_before = link_impairment(&node_name, &ctx.app_context).await;
 
     match action.details {
+        Some(
+            details @ (test_protocol::action::Details::StopNode(_)
+            | test_protocol::action::Details::StartNode(_)
+            | test_protocol::action::Details::CrashRestart(_)),
+        ) => {
+            let churn = handle_churn_action(&details, &node_name, &ctx.app_context).await;
+            result.action_stop_time = chrono::Utc::now().to_rfc3339();
+            match churn {
+                Ok(churn_details) => {
+                    result.is_success = true;
+                    result.details = Some(churn_details);
+                }
+                Err(error) => {
+                    result.error = Some(error_message(error));
+                    result.details = Some(empty_details(&details));
+                }
+            }
+        }
         Some(details) => {
             let request = match &details {
                 test_protocol::action::Details::Dial(dial_node) => DaemonRequest::Dial {
                     node_name: action.node_name,
-                    peer_id: ctx
-                        .callable_nodes
-                        .get(&dial_node.dialed_node_id)
-                        .unwrap()
-                        .node_hash
-                        .to_string(),
+                    peer_id: Some(
+                        ctx.callable_nodes
+                            .get(&dial_node.dialed_node_id)
+                            .unwrap()
+                            .node_hash
+                            .to_string(),
+                    ),
                     addr: ctx
                         .callable_nodes
                         .get(&dial_node.dialed_node_id)
@@ -185,6 +341,8 @@ async fn handle_simple_action(
                     DaemonRequest::PublishFile {
                         node_name: action.node_name,
                         path: PathBuf::from(publish_object.hash.to_string()),
+                        access: ObjectAccess::Public,
+                        previous: None,
                     }
                 }
                 test_protocol::action::Details::GetObject(get_object) => {
@@ -195,6 +353,8 @@ async fn handle_simple_action(
                             .get(&get_object.object_hash_id)
                             .unwrap()
                             .clone(),
+                        group: None,
+                        force_network: get_object.force_network,
                     }
                 }
                 test_protocol::action::Details::DeleteObject(delete_object) => {
@@ -208,6 +368,11 @@ async fn handle_simple_action(
                     }
                 }
                 test_protocol::action::Details::PublishMeta(publish_meta) => todo!(),
+                test_protocol::action::Details::StopNode(_)
+                | test_protocol::action::Details::StartNode(_)
+                | test_protocol::action::Details::CrashRestart(_) => {
+                    unreachable!("Churn actions are handled separately")
+                }
             };
 
             let daemon_request = daemon_request(request, ctx.app_context.clone()).await;
@@ -224,6 +389,7 @@ async fn handle_simple_action(
                                         query_duration_in_nano: stats.query_duration.as_nanos()
                                             as u64,
                                         total_request: stats.total_requests as u64,
+                                        cache_hit: stats.from_cache,
                                     }),
                                 })
                             } else {
@@ -232,7 +398,7 @@ async fn handle_simple_action(
                                 })
                             }
                         }
-                        DaemonResponse::Dialed => {
+                        DaemonResponse::Dialed { .. } => {
                             test_protocol::action_resoult::Details::Dial(DialNodeResult {})
                         }
                         DaemonResponse::FilePublished { id } => {
@@ -251,45 +417,218 @@ async fn handle_simple_action(
                         _ => panic!(),
                     })
                 }
-                Err(error) => match error {
-                    DaemonError::Other(err) => {
-                        result.error = Some(err);
-                        result.details = Some(match &details {
-                            test_protocol::action::Details::DeleteObject(_) => {
-                                Details::DeleteObject(
-                                    test_protocol::action_resoult::DeleteObjectResult {
-                                        ..Default::default()
-                                    },
-                                )
-                            }
-                            test_protocol::action::Details::PublishMeta(_) => Details::PublishMeta(
-                                test_protocol::action_resoult::PublishMetaResult {
-                                    ..Default::default()
-                                },
-                            ),
-                            test_protocol::action::Details::Dial(_) => {
-                                Details::Dial(DialNodeResult {})
-                            }
-                            test_protocol::action::Details::PublishObject(_) => {
-                                Details::PublishObject(PublishObjectResult {
-                                    ..Default::default()
-                                })
-                            }
-                            test_protocol::action::Details::GetObject(_) => {
-                                Details::GetObject(GetObjectResult { stats: None })
-                            }
-                        });
-                    }
-                    _ => panic!(),
-                },
+                Err(error) => {
+                    result.error = Some(error_message(error));
+                    result.details = Some(empty_details(&details));
+                }
             }
         }
         None => {}
     }
 
+    let impairment_after = link_impairment(&node_name, &ctx.app_context).await;
+    if let (Some(before), Some(after)) = (impairment_before, impairment_after) {
+        result.link_stats = Some(LinkStats {
+            delayed_streams: after.delayed_streams.saturating_sub(before.delayed_streams),
+            dropped_streams: after.dropped_streams.saturating_sub(before.dropped_streams),
+            injected_delay_in_nano: after
+                .injected_delay
+                .saturating_sub(before.injected_delay)
+                .as_nanos() as u64,
+        });
+    }
+
     return result;
 }
 
+fn error_message(error: DaemonError) -> String {
+    match error {
+        DaemonError::Other(err) => err,
+        error => error.to_string(),
+    }
+}
+
+/// The details of the result of a failed action
+fn empty_details(details: &test_protocol::action::Details) -> Details {
+    match details {
+        test_protocol::action::Details::DeleteObject(_) => {
+            Details::DeleteObject(test_protocol::action_resoult::DeleteObjectResult {
+                ..Default::default()
+            })
+        }
+        test_protocol::action::Details::PublishMeta(_) => {
+            Details::PublishMeta(test_protocol::action_resoult::PublishMetaResult {
+                ..Default::default()
+            })
+        }
+        test_protocol::action::Details::Dial(_) => Details::Dial(DialNodeResult {}),
+        test_protocol::action::Details::PublishObject(_) => {
+            Details::PublishObject(PublishObjectResult {
+                ..Default::default()
+            })
+        }
+        test_protocol::action::Details::GetObject(_) => {
+            Details::GetObject(GetObjectResult { stats: None })
+        }
+        test_protocol::action::Details::StopNode(_) => Details::StopNode(StopNodeResult {}),
+        test_protocol::action::Details::StartNode(_) => {
+            Details::StartNode(StartNodeResult::default())
+        }
+        test_protocol::action::Details::CrashRestart(_) => {
+            Details::CrashRestart(CrashRestartResult::default())
+        }
+    }
+}
+
+/// Stops and starts the node, measuring how long it takes to reconnect
+async fn handle_churn_action(
+    details: &test_protocol::action::Details,
+    node_name: &str,
+    app_context: &AppContext,
+) -> Result<Details, DaemonError> {
+    match details {
+        test_protocol::action::Details::StopNode(_) => {
+            stop_node(node_name, app_context).await?;
+            Ok(Details::StopNode(StopNodeResult {}))
+        }
+        test_protocol::action::Details::StartNode(start) => {
+            let timeout = recovery_timeout(start.recovery_timeout_millis);
+            let recovery_time = start_node(node_name, timeout, app_context).await?;
+            Ok(Details::StartNode(StartNodeResult {
+                recovery_time_in_nano: recovery_time.map(|t| t.as_nanos() as u64),
+            }))
+        }
+        test_protocol::action::Details::CrashRestart(crash) => {
+            let timeout = recovery_timeout(crash.recovery_timeout_millis);
+            let mut restarts = Vec::new();
+            for i in 0..crash.times {
+                if i > 0 {
+                    sleep(Duration::from_millis(crash.uptime_millis)).await;
+                }
+                let crashed_at = Instant::now();
+                stop_node(node_name, app_context).await?;
+                sleep(Duration::from_millis(crash.downtime_millis)).await;
+                let downtime = crashed_at.elapsed();
+                let recovery_time = start_node(node_name, timeout, app_context).await?;
+                restarts.push(crash_restart_result::Restart {
+                    downtime_in_nano: downtime.as_nanos() as u64,
+                    recovery_time_in_nano: recovery_time.map(|t| t.as_nanos() as u64),
+                });
+            }
+            Ok(Details::CrashRestart(CrashRestartResult { restarts }))
+        }
+        _ => Err(DaemonError::Other("Not a churn action".to_string())),
+    }
+}
+
+fn recovery_timeout(millis: u64) -> Duration {
+    match millis {
+        0 => DEFAULT_RECOVERY_TIMEOUT,
+        millis => Duration::from_millis(millis),
+    }
+}
+
+async fn stop_node(node_name: &str, app_context: &AppContext) -> Result<(), DaemonError> {
+    let request = DaemonRequest::StopNode {
+        node_name: node_name.to_string(),
+    };
+    daemon_request(request, app_context.clone()).await?;
+    Ok(())
+}
+
+/// Starts the node and returns the time until it connected to a peer, None if it
+/// did not within the timeout
+async fn start_node(
+    node_name: &str,
+    timeout: Duration,
+    app_context: &AppContext,
+) -> Result<Option<Duration>, DaemonError> {
+    let started_at = Instant::now();
+    let request = DaemonRequest::StartNode {
+        node_name: node_name.to_string(),
+    };
+    daemon_request(request, app_context.clone()).await?;
+
+    while started_at.elapsed() < timeout {
+        let request = DaemonRequest::GetNodeStatus {
+            node_name: node_name.to_string(),
+        };
+        if let DaemonResponse::NodeStatus(status) =
+            daemon_request(request, app_context.clone()).await?
+        {
+            if status.connected_peers > 0 {
+                return Ok(Some(started_at.elapsed()));
+            }
+        }
+        sleep(RECOVERY_POLL_INTERVAL).await;
+    }
+    Ok(None)
+}
+
+/// The effect of the link conditions on the node so far, None if it is not running.
+/// The actions run at the same time on the node are counted together
+async fn link_impairment(node_name: &str, app_context: &AppContext) -> Option<LinkImpairmentStats> {
+    let request = DaemonRequest::GetNodeStatus {
+        node_name: node_name.to_string(),
+    };
+    match daemon_request(request, app_context.clone()).await {
+        Ok(DaemonResponse::NodeStatus(status)) => Some(status.link_impairment),
+        _ => None,
+    }
+}
+
+/// Sets the link conditions of the scenario on the nodes of this host. A link is
+/// impaired on both of its ends, each one delays what it sends to the other
+async fn apply_link_conditions(
+    test_scenario: &TestScenario,
+    peer_ids: &HashMap<u64, String>,
+    app_context: &AppContext,
+) -> Result<(), Box<dyn error::Error>> {
+    let mut conditions: HashMap<u64, Vec<LinkCondition>> = HashMap::new();
+    for link in &test_scenario.links {
+        let ends = [
+            (link.from_node_id, link.to_node_id),
+            (link.to_node_id, link.from_node_id),
+        ];
+        for (node_id, peer_node_id) in ends {
+            let Some(peer_id) = peer_ids.get(&peer_node_id) else {
+                warn!(
+                    node_id = peer_node_id,
+                    "Peer ID of the node is unknown, link condition skipped"
+                );
+                continue;
+            };
+            conditions.entry(node_id).or_default().push(LinkCondition {
+                peer_id: PeerId::from_str(peer_id)?,
+                latency_millis: link.latency_millis,
+                jitter_millis: link.jitter_millis,
+                drop_rate: link.drop_rate,
+            });
+        }
+    }
+
+    for node in &test_scenario.nodes {
+        let Some(link_conditions) = conditions.remove(&node.node_id) else {
+            continue;
+        };
+        let request = DaemonRequest::GetNodeConfig {
+            node_name: node.name.clone(),
+        };
+        let DaemonResponse::NodeConfig(mut config) =
+            daemon_request(request, app_context.clone()).await?
+        else {
+            return Err("Daemon returned wrong response".into());
+        };
+        config.link_conditions = link_conditions;
+        let request = DaemonRequest::ReloadNodeConfig {
+            node_name: node.name.clone(),
+            new_cfg: Some(config),
+        };
+        daemon_request(request, app_context.clone()).await?;
+    }
+    Ok(())
+}
+
 async fn daemon_request(
     request: DaemonRequest,
     app_context: AppContext,
@@ -371,6 +710,7 @@ async fn handle_create_nodes(
                         external_addresses: vec![
                             Multiaddr::from_str("/ip4/0.0.0.0/udp/0/quic-v1").unwrap()
                         ],
+                        ..Default::default()
                     },
                 },
             ));
@@ -463,6 +803,7 @@ fn setup_logging() {
 
 fn main() -> Result<(), ()> {
     setup_logging();
+    swarm_runner::impairment::enable_test_mode();
 
     let args: Vec<String> = std::env::args().collect();
 
@@ -0,0 +1,420 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt::{self, Write as _};
+use std::io::Write as _;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use anyhow::{anyhow, Result};
+use tokio::sync::broadcast;
+use tracing::field::{Field, Visit};
+use tracing::level_filters::LevelFilter;
+use tracing::span::{Attributes, Id};
+use tracing::{info_span, Event, Span, Subscriber};
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{reload, EnvFilter, Layer, Registry};
+
+///! The module sets up the logging of the daemon. Everything is logged to stderr, the
+///! events inside the span of a node are also written to the rotated log files in the
+///! directory of the node. The recent lines are kept in memory, so they can be tailed
+///! over the socket. The log levels can be changed while the daemon runs.
+
+/// Name of the span the events of a node are logged in
+pub const NODE_SPAN_NAME: &str = "node";
+/// Directory of the log files, inside the node directory
+pub const NODE_LOG_DIR_NAME: &str = "logs";
+/// Number of the daily log files kept for each node
+const NODE_LOG_FILES_KEPT: usize = 7;
+/// Number of the recent log lines kept in memory
+pub const LOG_BUFFER_CAPACITY: usize = 1000;
+/// Number of the lines a follower may fall behind before it misses some
+const LOG_FOLLOW_CAPACITY: usize = 256;
+
+static LOG_CONTROL: OnceLock<LogControl> = OnceLock::new();
+
+/// Changes the filter of the installed subscriber
+struct LogControl {
+    handle: reload::Handle<EnvFilter, Registry>,
+    /// Levels by target, the filter is built from them
+    levels: Mutex<BTreeMap<String, LevelFilter>>,
+    buffer: Arc<LogBuffer>,
+}
+
+/// Installs the subscriber logging to stderr and, if the nodes directory is given,
+/// to the log files of the nodes
+pub fn setup(target: &str, level: LevelFilter, nodes_dir: Option<PathBuf>) {
+    let levels = BTreeMap::from([(target.to_string(), level)]);
+    let filter = EnvFilter::new(filter_directives(&levels));
+    let (filter, handle) = reload::Layer::new(filter);
+    let buffer = Arc::new(LogBuffer::new(LOG_BUFFER_CAPACITY));
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_line_number(true)
+                .with_target(true)
+                .compact()
+                .with_file(true),
+        )
+        .with(nodes_dir.map(NodeLogLayer::new))
+        .with(LogBufferLayer(buffer.clone()))
+        .init();
+
+    let _ = LOG_CONTROL.set(LogControl {
+        handle,
+        levels: Mutex::new(levels),
+        buffer,
+    });
+}
+
+/// Sets the level of the target, e.g. `liberum_core::swarm_runner`, without
+/// restarting the daemon. Returns the resulting filter
+pub fn set_log_level(target: &str, level: &str) -> Result<String> {
+    let control = LOG_CONTROL
+        .get()
+        .ok_or(anyhow!("log levels can't be changed in this process"))?;
+    let level = LevelFilter::from_str(level)?;
+
+    let mut levels = control.levels.lock().unwrap();
+    let mut new_levels = levels.clone();
+    new_levels.insert(target.to_string(), level);
+    let directives = filter_directives(&new_levels);
+    let filter = EnvFilter::try_new(&directives)?;
+
+    control.handle.reload(filter)?;
+    *levels = new_levels;
+
+    Ok(directives)
+}
+
+/// Returns up to `lines` most recent log lines of the node, or of the whole daemon
+pub fn tail_logs(node: Option<&str>, lines: usize) -> Result<Vec<String>> {
+    Ok(log_buffer()?.tail(node, lines))
+}
+
+/// Like `tail_logs`, also returns the follower receiving the lines logged afterwards
+pub fn follow_logs(node: Option<String>, lines: usize) -> Result<(Vec<String>, LogFollower)> {
+    Ok(log_buffer()?.follow(node, lines))
+}
+
+fn log_buffer() -> Result<&'static LogBuffer> {
+    let control = LOG_CONTROL
+        .get()
+        .ok_or(anyhow!("logs are not kept in this process"))?;
+    Ok(&control.buffer)
+}
+
+/// The span marking the events of the node, so they are also written to its log files
+pub fn node_span(name: &str) -> Span {
+    info_span!(NODE_SPAN_NAME, node = name)
+}
+
+fn filter_directives(levels: &BTreeMap<String, LevelFilter>) -> String {
+    levels
+        .iter()
+        .map(|(target, level)| format!("{target}={level}"))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// A line logged by the daemon, `node` is set for the lines logged inside a node span
+#[derive(Clone, Debug)]
+struct LogLine {
+    node: Option<String>,
+    line: String,
+}
+
+impl LogLine {
+    fn matches(&self, node: Option<&str>) -> bool {
+        node.is_none() || self.node.as_deref() == node
+    }
+
+    /// The line as shown to the user. Lines of the nodes are prefixed with the node
+    /// name when the logs of the whole daemon are shown
+    fn text(&self, node: Option<&str>) -> String {
+        match (&self.node, node) {
+            (Some(line_node), None) => format!("[{line_node}] {}", self.line),
+            _ => self.line.clone(),
+        }
+    }
+}
+
+/// The ring buffer of the recent log lines
+struct LogBuffer {
+    lines: Mutex<VecDeque<LogLine>>,
+    capacity: usize,
+    sender: broadcast::Sender<LogLine>,
+}
+
+impl LogBuffer {
+    fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(LOG_FOLLOW_CAPACITY);
+        LogBuffer {
+            lines: Mutex::new(VecDeque::with_capacity(capacity)),
+            capacity,
+            sender,
+        }
+    }
+
+    fn push(&self, line: LogLine) {
+        let mut lines = self.lines.lock().unwrap();
+        if lines.len() == self.capacity {
+            lines.pop_front();
+        }
+        lines.push_back(line.clone());
+        // Fails only if nobody follows the logs
+        let _ = self.sender.send(line);
+    }
+
+    fn tail(&self, node: Option<&str>, count: usize) -> Vec<String> {
+        let lines = self.lines.lock().unwrap();
+        Self::tail_of(&lines, node, count)
+    }
+
+    fn follow(&self, node: Option<String>, count: usize) -> (Vec<String>, LogFollower) {
+        // Subscribed under the lock, so no line is missed or repeated
+        let lines = self.lines.lock().unwrap();
+        let follower = LogFollower {
+            receiver: self.sender.subscribe(),
+            node: node.clone(),
+        };
+
+        (Self::tail_of(&lines, node.as_deref(), count), follower)
+    }
+
+    fn tail_of(lines: &VecDeque<LogLine>, node: Option<&str>, count: usize) -> Vec<String> {
+        let mut tail = lines
+            .iter()
+            .rev()
+            .filter(|line| line.matches(node))
+            .take(count)
+            .map(|line| line.text(node))
+            .collect::<Vec<_>>();
+        tail.reverse();
+        tail
+    }
+}
+
+/// Receives the lines logged after it was created
+pub struct LogFollower {
+    receiver: broadcast::Receiver<LogLine>,
+    node: Option<String>,
+}
+
+impl LogFollower {
+    /// Waits for the next line of the followed node, or of the whole daemon
+    pub async fn next(&mut self) -> Option<String> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(line) if line.matches(self.node.as_deref()) => {
+                    return Some(line.text(self.node.as_deref()))
+                }
+                Ok(_) => {}
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    return Some(format!("... {missed} log lines skipped"))
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+struct LogBufferLayer(Arc<LogBuffer>);
+
+impl<S> Layer<S> for LogBufferLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        self.0.push(LogLine {
+            node: event_node(event, &ctx),
+            line: format_event(event),
+        });
+    }
+}
+
+/// Name of the node the event was logged in, if any
+fn event_node<S>(event: &Event<'_>, ctx: &Context<'_, S>) -> Option<String>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    ctx.event_scope(event)?
+        .from_root()
+        .find_map(|span| span.extensions().get::<NodeName>().map(|n| n.0.clone()))
+}
+
+fn format_event(event: &Event<'_>) -> String {
+    let mut fields = FieldsVisitor::default();
+    event.record(&mut fields);
+    let metadata = event.metadata();
+    format!(
+        "{} {} {}: {}{}",
+        chrono::Local::now().to_rfc3339(),
+        metadata.level(),
+        metadata.target(),
+        fields.message,
+        fields.rest
+    )
+}
+
+/// Writes the events inside a node span to the log files of the node
+struct NodeLogLayer {
+    nodes_dir: PathBuf,
+    files: Mutex<HashMap<String, RollingFileAppender>>,
+}
+
+/// Stored in the extensions of a node span
+struct NodeName(String);
+
+impl NodeLogLayer {
+    fn new(nodes_dir: PathBuf) -> Self {
+        NodeLogLayer {
+            nodes_dir,
+            files: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn write(&self, node: &str, line: &str) -> Result<()> {
+        let mut files = self.files.lock().unwrap();
+        if !files.contains_key(node) {
+            let appender = RollingFileAppender::builder()
+                .rotation(Rotation::DAILY)
+                .filename_prefix(NODE_SPAN_NAME)
+                .filename_suffix("log")
+                .max_log_files(NODE_LOG_FILES_KEPT)
+                .build(self.nodes_dir.join(node).join(NODE_LOG_DIR_NAME))?;
+            files.insert(node.to_string(), appender);
+        }
+
+        let file = files
+            .get_mut(node)
+            .expect("To be present, as it was inserted");
+        file.write_all(line.as_bytes())?;
+
+        Ok(())
+    }
+}
+
+impl<S> Layer<S> for NodeLogLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if attrs.metadata().name() != NODE_SPAN_NAME {
+            return;
+        }
+
+        let mut fields = FieldsVisitor::default();
+        attrs.record(&mut fields);
+        if let (Some(node), Some(span)) = (fields.node, ctx.span(id)) {
+            span.extensions_mut().insert(NodeName(node));
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let Some(node) = event_node(event, &ctx) else {
+            return;
+        };
+        let line = format!("{}\n", format_event(event));
+
+        // There is nowhere to log the failure to, the event is still on stderr
+        let _ = self.write(&node, &line);
+    }
+}
+
+#[derive(Default)]
+struct FieldsVisitor {
+    message: String,
+    rest: String,
+    node: Option<String>,
+}
+
+impl Visit for FieldsVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "node" => self.node = Some(value.to_string()),
+            _ => self.record_debug(field, &value),
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        match field.name() {
+            "message" => self.message = format!("{value:?}"),
+            name => {
+                let _ = write!(self.rest, " {name}={value:?}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+    use tracing::info;
+
+    use super::*;
+
+    #[test]
+    fn node_log_file_test() {
+        let tmp_dir = TempDir::new("liberum_tests").unwrap();
+        let subscriber =
+            tracing_subscriber::registry().with(NodeLogLayer::new(tmp_dir.path().to_path_buf()));
+
+        tracing::subscriber::with_default(subscriber, || {
+            info!("Outside of any node");
+            let _span = node_span("node1").entered();
+            info!(peer = "peer1", "Inside the node");
+        });
+
+        let log_dir = tmp_dir.path().join("node1").join(NODE_LOG_DIR_NAME);
+        let log_file = std::fs::read_dir(log_dir)
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap()
+            .path();
+        let logs = std::fs::read_to_string(log_file).unwrap();
+        assert!(logs.contains("Inside the node peer=\"peer1\""));
+        assert!(!logs.contains("Outside of any node"));
+    }
+
+    #[test]
+    fn log_buffer_test() {
+        let buffer = Arc::new(LogBuffer::new(3));
+        let subscriber = tracing_subscriber::registry().with(LogBufferLayer(buffer.clone()));
+
+        tracing::subscriber::with_default(subscriber, || {
+            info!("First");
+            let _span = node_span("node1").entered();
+            info!("Second");
+            info!("Third");
+            info!("Fourth");
+        });
+
+        let node_lines = buffer.tail(Some("node1"), 2);
+        assert_eq!(node_lines.len(), 2);
+        assert!(node_lines[0].ends_with("Third"));
+        assert!(node_lines[1].ends_with("Fourth"));
+
+        let daemon_lines = buffer.tail(None, 10);
+        assert_eq!(daemon_lines.len(), 3);
+        assert!(daemon_lines[0].starts_with("[node1] "));
+        assert!(buffer.tail(Some("node2"), 10).is_empty());
+    }
+
+    #[test]
+    fn filter_directives_test() {
+        let levels = BTreeMap::from([
+            ("liberum_core".to_string(), LevelFilter::DEBUG),
+            ("liberum_core::vault".to_string(), LevelFilter::WARN),
+        ]);
+        assert_eq!(
+            filter_directives(&levels),
+            "liberum_core=debug,liberum_core::vault=warn"
+        );
+    }
+}
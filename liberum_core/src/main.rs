@@ -1,22 +1,83 @@
 pub mod connection;
+pub mod logging;
 pub mod node;
 pub mod swarm_runner;
 pub mod vault;
 
 use anyhow::{anyhow, Result};
+use clap::Parser;
 use connection::listen;
 use daemonize::*;
+use liberum_core::daemon_config::{DaemonConfig, SOCKET_PATH_ENV};
+use node::store::NodeStore;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
 use std::{fs::Permissions, io, os::unix::fs::PermissionsExt, path::Path};
 use tokio::net::UnixListener;
-use tracing::{debug, error};
+use tracing::level_filters::LevelFilter;
+use tracing::{debug, error, info};
+
+/// The core daemon of Liberum-Neto. The settings from the config file are overridden
+/// by the environment variables, and those by the flags
+#[derive(Parser)]
+struct Args {
+    /// Run in the background
+    #[arg(long)]
+    daemon: bool,
+    /// The config file, `~/.liberum-neto/daemon.toml` by default
+    #[arg(long, env = "LIBERUM_CONFIG")]
+    config: Option<PathBuf>,
+    /// The directory of the nodes
+    #[arg(long, env = "LIBERUM_STATE_DIR")]
+    state_dir: Option<PathBuf>,
+    #[arg(long, env = SOCKET_PATH_ENV)]
+    socket: Option<PathBuf>,
+    #[arg(long, env = "LIBERUM_LOG_LEVEL")]
+    log_level: Option<String>,
+    /// Serve the metrics on the address
+    #[arg(long, env = "LIBERUM_METRICS_ADDRESS")]
+    metrics_address: Option<SocketAddr>,
+    /// Don't start the nodes with autostart in their configs
+    #[arg(long, env = "LIBERUM_NO_AUTOSTART")]
+    no_autostart: bool,
+}
+
+impl Args {
+    fn daemon_config(&self) -> Result<DaemonConfig> {
+        let path = match &self.config {
+            Some(path) => path.clone(),
+            None => DaemonConfig::default_path()?,
+        };
+        let mut config = DaemonConfig::load(&path)?;
+
+        if let Some(state_dir) = &self.state_dir {
+            config.state_dir = Some(state_dir.clone());
+        }
+        if let Some(socket) = &self.socket {
+            config.socket_path = socket.clone();
+        }
+        if let Some(log_level) = &self.log_level {
+            config.log_level = log_level.clone();
+        }
+        if let Some(address) = self.metrics_address {
+            config.metrics.enabled = true;
+            config.metrics.bind_address = address;
+        }
+        if self.no_autostart {
+            config.autostart = false;
+        }
+        Ok(config)
+    }
+}
 
 /// The main function of the core daemon
 #[tokio::main]
-async fn run(path: &Path) -> Result<()> {
-    let socket = path.join("liberum-core-socket");
-    let listener = UnixListener::bind(&socket)
+async fn run(config: DaemonConfig) -> Result<()> {
+    let socket = &config.socket_path;
+    let listener = UnixListener::bind(socket)
         .inspect_err(|e| error!(err = e.to_string(), "Failed to bind the socket"))?;
-    tokio::fs::set_permissions(&socket, Permissions::from_mode(0o666))
+    tokio::fs::set_permissions(socket, Permissions::from_mode(config.socket_mode))
         .await
         .inspect_err(|e| {
             error!(
@@ -25,20 +86,20 @@ async fn run(path: &Path) -> Result<()> {
             )
         })?;
 
-    listen(listener).await?;
+    listen(listener, config).await?;
     Ok(())
 }
 
-/// Helper function to setup logging
-fn setup_logging() {
-    tracing_subscriber::fmt()
-        .with_max_level(tracing::Level::DEBUG)
-        .with_line_number(true)
-        .with_target(true)
-        .compact()
-        .with_file(true)
-        .with_env_filter("liberum_core=debug")
-        .init();
+/// Helper function to setup logging. The nodes also log to the files in their directories
+fn setup_logging(config: &DaemonConfig) -> Result<()> {
+    let level = LevelFilter::from_str(&config.log_level)
+        .map_err(|_| anyhow!("Invalid log level {}", config.log_level))?;
+    let nodes_dir = match &config.state_dir {
+        Some(state_dir) => Some(state_dir.clone()),
+        None => NodeStore::default_nodes_dir().ok(),
+    };
+    logging::setup("liberum_core", level, nodes_dir);
+    Ok(())
 }
 
 fn start_daemon(path: &Path) -> Result<()> {
@@ -65,24 +126,34 @@ fn start_daemon(path: &Path) -> Result<()> {
 }
 
 fn main() -> Result<()> {
-    setup_logging();
-    let path = Path::new("/tmp/liberum-core/");
-    match std::fs::remove_dir_all(path) {
+    let args = Args::parse();
+    let config = args.daemon_config()?;
+    setup_logging(&config)?;
+    info!(
+        socket = config.socket_path.display().to_string(),
+        "Daemon config loaded"
+    );
+
+    let path = config
+        .socket_path
+        .parent()
+        .ok_or(anyhow!("The socket path has no directory"))?;
+    // The socket left by a daemon which did not stop cleanly
+    match std::fs::remove_file(&config.socket_path) {
         Err(e) => {
             if e.kind() != io::ErrorKind::NotFound {
-                error!(err = e.to_string(), "Failed to remove the directory");
+                error!(err = e.to_string(), "Failed to remove the socket");
                 return Err(anyhow!(e));
             }
         }
         _ => {}
     }
-    std::fs::create_dir(path)?;
-    let args: Vec<String> = std::env::args().collect();
-    if args.len() > 1 && args[1] == "--daemon" {
+    std::fs::create_dir_all(path)?;
+    if args.daemon {
         start_daemon(path)?;
     }
 
-    match run(&path) {
+    match run(config) {
         Ok(_) => Ok(()),
         Err(e) => {
             error!(err = e.to_string(), "Error running the core daemon");
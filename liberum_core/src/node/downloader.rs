@@ -0,0 +1,845 @@
+use anyhow::{anyhow, bail, Result};
+use futures::stream::{self, FuturesUnordered, StreamExt};
+use kameo::actor::ActorRef;
+use kameo::request::MessageSend;
+use liberum_core::erasure;
+use liberum_core::node_config::RetryConfig;
+use liberum_core::parser::{self, ObjectEnum};
+use liberum_core::proto::{
+    self, AccessPolicy, ChunkObject, GroupAccessToken, ManifestObject, PlainFileObject,
+    ResultErrorCode, ResultObject, TypedObject,
+};
+use liberum_core::types::ProvenanceKind;
+use liberum_core::DaemonQueryStats;
+use libp2p::PeerId;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, oneshot};
+use tracing::{debug, warn};
+
+use crate::swarm_runner::commands;
+use crate::swarm_runner::messages::{ProvidersBatch, SwarmRunnerMessage};
+use crate::swarm_runner::reputation::Misbehaviour;
+use crate::vault::{
+    LoadObject, LoadPublishedObject, StoreAccessPolicy, StoreReceivedObject, Vault,
+};
+
+use super::retry::{self, PermanentError, TransientError};
+
+///! The module contains the downloading logic of a node. Many providers are asked
+///! for the object at the same time and the first valid response wins, the requests
+///! to the other providers are dropped. The download starts as soon as the first
+///! provider is found, the providers found later are asked if the first ones fail.
+///!
+///! The files published in chunks are downloaded as their manifests first. Only the
+///! chunks missing in the vault are downloaded then, so the peers having the previous
+///! revision of a file download only the changed chunks. The downloaded chunks are
+///! kept in the vault for the next revisions, and so are the files downloaded whole
+///! and the manifests, as they were received, signed. A file in the vault is found
+///! inside its signed objects and put together from its chunks there, so it's not
+///! downloaded again.
+///!
+///! When some chunks of an erasure-coded file can't be downloaded, the parity chunks
+///! of their groups are downloaded and the missing chunks are reconstructed. The
+///! reconstructed chunks are checked against their IDs before they are stored.
+///!
+///! The small public objects are also looked for as Kademlia records while their
+///! providers are searched for, whichever comes first wins.
+
+/// Capacity of the channel of the providers found by a query. A provider query
+/// sends a batch per step, so it's rarely full
+pub const PROVIDERS_CHANNEL_CAPACITY: usize = 64;
+/// The chunks of a file downloaded at the same time
+const CHUNK_DOWNLOADS: usize = 4;
+
+/// Starts the query for the providers of the object. The providers are received
+/// as soon as they are found, the channel is closed when the query finishes
+pub async fn find_providers(
+    swarm_sender: &mpsc::Sender<SwarmRunnerMessage>,
+    obj_id: &proto::Hash,
+) -> Result<mpsc::Receiver<ProvidersBatch>> {
+    let (providers_sender, providers_receiver) = mpsc::channel(PROVIDERS_CHANNEL_CAPACITY);
+    swarm_sender
+        .send(
+            commands::GetProviders {
+                obj_id: obj_id.clone(),
+                providers_sender,
+            }
+            .into(),
+        )
+        .await?;
+    Ok(providers_receiver)
+}
+
+/// Waits for the provider query to finish. Returns all the providers found, without
+/// duplicates, and the last stats of the query
+pub async fn collect_providers(
+    mut providers: mpsc::Receiver<ProvidersBatch>,
+) -> (Vec<PeerId>, Option<DaemonQueryStats>) {
+    let mut found = Vec::new();
+    let mut seen = HashSet::new();
+    let mut stats = None;
+    while let Some((peers, batch_stats)) = providers.recv().await {
+        found.extend(peers.into_iter().filter(|p| seen.insert(*p)));
+        stats = batch_stats.or(stats);
+    }
+    (found, stats)
+}
+
+#[derive(Clone)]
+pub struct Downloader {
+    pub name: String,
+    pub swarm_sender: mpsc::Sender<SwarmRunnerMessage>,
+    pub vault_ref: ActorRef<Vault>,
+}
+
+/// A downloaded file, whole or the manifest of its chunks
+pub enum FileObject {
+    Plain(PlainFileObject),
+    Chunked(ManifestObject),
+}
+
+/// An object found in the vault by `local_file`
+pub enum LocalFile {
+    Found(PlainFileObject),
+    /// Published in chunks, some of which are not in the vault
+    Incomplete,
+    /// The object is not a file
+    NotFile,
+}
+
+/// The object as published by the node, or as stored if it's not published by the
+/// node
+pub async fn local_object(
+    vault_ref: &ActorRef<Vault>,
+    obj_id: &proto::Hash,
+) -> Result<Option<TypedObject>> {
+    let published = vault_ref
+        .ask(LoadPublishedObject {
+            hash: obj_id.clone(),
+        })
+        .send()
+        .await?;
+    Ok(match published {
+        Some(object) => Some(object),
+        None => match vault_ref
+            .ask(LoadObject {
+                hash: obj_id.clone(),
+            })
+            .send()
+            .await?
+        {
+            Some(ObjectEnum::Typed(object)) => Some(object),
+            _ => None,
+        },
+    })
+}
+
+/// The file with the ID in the vault, found inside the signed objects. The file
+/// published in chunks is put together from the chunks in the vault. None if
+/// there is no object with the ID
+pub async fn local_file(
+    vault_ref: &ActorRef<Vault>,
+    obj_id: &proto::Hash,
+) -> Result<Option<LocalFile>> {
+    let Some(object) = local_object(vault_ref, obj_id).await? else {
+        return Ok(None);
+    };
+    let file = match unwrap_signed(object).await {
+        Ok(ObjectEnum::PlainFile(file)) => LocalFile::Found(file),
+        Ok(ObjectEnum::Manifest(manifest)) => match local_content(vault_ref, &manifest).await? {
+            Some(content) => LocalFile::Found(PlainFileObject {
+                name: manifest.name,
+                content,
+            }),
+            None => LocalFile::Incomplete,
+        },
+        _ => LocalFile::NotFile,
+    };
+    Ok(Some(file))
+}
+
+/// The file from the vault like it was downloaded, with the stats of the download
+/// started at the given time. None if the whole file is not in the vault
+pub async fn cached_file(
+    vault_ref: &ActorRef<Vault>,
+    obj_id: &proto::Hash,
+    started_at: Instant,
+) -> Result<Option<(PlainFileObject, DaemonQueryStats)>> {
+    let Some(LocalFile::Found(file)) = local_file(vault_ref, obj_id).await? else {
+        return Ok(None);
+    };
+    let stats = DaemonQueryStats {
+        query_duration: started_at.elapsed(),
+        total_requests: 0,
+        attempts: 0,
+        from_cache: true,
+    };
+    Ok(Some((file, stats)))
+}
+
+/// The content of the file of the manifest, None if some of its chunks are not in
+/// the vault
+async fn local_content(
+    vault_ref: &ActorRef<Vault>,
+    manifest: &ManifestObject,
+) -> Result<Option<Vec<u8>>> {
+    let mut content = Vec::new();
+    for chunk_id in &manifest.chunks {
+        let Some(chunk) = local_chunk(vault_ref, chunk_id).await? else {
+            return Ok(None);
+        };
+        content.extend(chunk.content);
+    }
+    Ok((content.len() as u64 == manifest.size).then_some(content))
+}
+
+/// The chunk published or stored by the node, if it's in the vault
+async fn local_chunk(
+    vault_ref: &ActorRef<Vault>,
+    chunk_id: &proto::Hash,
+) -> Result<Option<ChunkObject>> {
+    Ok(local_object(vault_ref, chunk_id)
+        .await?
+        .filter(|object| object.uuid == ChunkObject::UUID)
+        .and_then(|object| TypedObject::try_from_typed(&object).ok()))
+}
+
+impl Downloader {
+    /// Finds the providers of the object and asks up to `parallelism` of them at
+    /// once, starting with the first provider found. The providers of every batch are
+    /// asked from the fastest, by the given latencies. When a provider fails, the
+    /// next one is asked. Timeouts and protocol violations are reported to the
+    /// reputation system by the swarm, the objects with wrong hashes are reported
+    /// here. The access token is sent to the providers of group objects. Returns
+    /// the file and the stats of the provider query so far
+    pub async fn download_file(
+        &self,
+        obj_id: &proto::Hash,
+        parallelism: usize,
+        access_token: Option<&GroupAccessToken>,
+        latencies: &HashMap<PeerId, Duration>,
+    ) -> Result<(FileObject, Option<DaemonQueryStats>)> {
+        let (typed, peer, stats) = self
+            .download_typed(obj_id, parallelism, access_token, latencies)
+            .await?;
+        let file = match unwrap_signed(typed.clone()).await? {
+            ObjectEnum::PlainFile(file) => FileObject::Plain(file),
+            ObjectEnum::Manifest(manifest) => FileObject::Chunked(manifest),
+            _ => return Err(anyhow!("Received object was not a file!")),
+        };
+        self.keep_file(obj_id, typed, peer).await;
+        Ok((file, stats))
+    }
+
+    /// Downloads the file, retrying the transient failures, and puts it together if
+    /// it's published in chunks. Returns the file and the stats of the provider query
+    pub async fn fetch_file(
+        &self,
+        obj_id: &proto::Hash,
+        retry_config: &RetryConfig,
+        parallelism: usize,
+        access_token: Option<&GroupAccessToken>,
+        latencies: &HashMap<PeerId, Duration>,
+    ) -> Result<(PlainFileObject, Option<DaemonQueryStats>)> {
+        let (result, attempts) = retry::retry(retry_config, || {
+            self.download_file(obj_id, parallelism, access_token, latencies)
+        })
+        .await;
+        let (file, stats) = match result? {
+            (FileObject::Plain(file), stats) => (file, stats),
+            (FileObject::Chunked(manifest), stats) => {
+                debug!(
+                    node = self.name,
+                    obj_id = obj_id.to_string(),
+                    "Downloading {} chunks",
+                    manifest.chunks.len()
+                );
+                let file = self
+                    .download_chunks(manifest, retry_config, parallelism, access_token, latencies)
+                    .await?;
+                (file, stats)
+            }
+        };
+        debug!(
+            node = self.name,
+            obj_id = obj_id.to_string(),
+            "Downloaded file"
+        );
+
+        let stats = stats.map(|stats| DaemonQueryStats { attempts, ..stats });
+        Ok((file, stats))
+    }
+
+    /// Stores the downloaded file or manifest as it was received, signed, so it's
+    /// found in the vault the next time. The policy it was published with is not
+    /// known, so the kept file is not served to anyone else
+    async fn keep_file(&self, obj_id: &proto::Hash, typed: TypedObject, peer: PeerId) {
+        if proto::Hash::try_from(&typed).ok().as_ref() != Some(obj_id) {
+            return;
+        }
+        let kept = self
+            .vault_ref
+            .ask(LoadObject {
+                hash: obj_id.clone(),
+            })
+            .send()
+            .await;
+        if matches!(kept, Ok(Some(_))) {
+            return;
+        }
+
+        let private = self
+            .vault_ref
+            .ask(StoreAccessPolicy {
+                hash: obj_id.clone(),
+                policy: AccessPolicy::Users(vec![]),
+            })
+            .send()
+            .await
+            .map_err(|e| e.to_string());
+        let stored = match private {
+            Ok(()) => self
+                .vault_ref
+                .ask(StoreReceivedObject {
+                    hash: obj_id.clone(),
+                    object: typed,
+                    kind: ProvenanceKind::Downloaded,
+                    source: Some(peer),
+                })
+                .send()
+                .await
+                .map_err(|e| e.to_string()),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = stored {
+            warn!(
+                obj_id = obj_id.to_string(),
+                err = e,
+                "Could not keep the downloaded file"
+            );
+        }
+    }
+
+    /// Puts the file of the manifest together. The chunks in the vault are not
+    /// downloaded, the downloaded ones are stored there. The chunks which can't be
+    /// downloaded are reconstructed if the manifest has the erasure coding
+    pub async fn download_chunks(
+        &self,
+        manifest: ManifestObject,
+        retry_config: &RetryConfig,
+        parallelism: usize,
+        access_token: Option<&GroupAccessToken>,
+        latencies: &HashMap<PeerId, Duration>,
+    ) -> Result<PlainFileObject> {
+        let results: Vec<Result<Vec<u8>>> = self
+            .fetch_chunks(
+                &manifest.chunks,
+                retry_config,
+                parallelism,
+                access_token,
+                latencies,
+            )
+            .await;
+        let chunks = self
+            .reconstruct_chunks(
+                &manifest,
+                results,
+                retry_config,
+                parallelism,
+                access_token,
+                latencies,
+            )
+            .await?;
+        let content = chunks.concat();
+        if content.len() as u64 != manifest.size {
+            bail!(
+                "The chunks of {} have {} bytes instead of {}",
+                manifest.name,
+                content.len(),
+                manifest.size
+            );
+        }
+
+        Ok(PlainFileObject {
+            name: manifest.name,
+            content,
+        })
+    }
+
+    /// Replaces the chunks which could not be downloaded with the ones reconstructed
+    /// from the other chunks of their groups and the parity chunks. Fails on the
+    /// first missing chunk without the erasure coding
+    async fn reconstruct_chunks(
+        &self,
+        manifest: &ManifestObject,
+        results: Vec<Result<Vec<u8>>>,
+        retry_config: &RetryConfig,
+        parallelism: usize,
+        access_token: Option<&GroupAccessToken>,
+        latencies: &HashMap<PeerId, Duration>,
+    ) -> Result<Vec<Vec<u8>>> {
+        let Some(coding) = &manifest.coding else {
+            return results.into_iter().collect();
+        };
+        let chunk_ids = &manifest.chunks;
+        let data_shards = coding.data_shards as usize;
+        if data_shards == 0
+            || coding.chunk_sizes.len() != chunk_ids.len()
+            || coding.parity.len() != chunk_ids.len().div_ceil(data_shards)
+        {
+            bail!("The erasure coding of the manifest doesn't match its chunks");
+        }
+
+        let mut results = results.into_iter();
+        let mut chunks = Vec::with_capacity(chunk_ids.len());
+        let groups = chunk_ids
+            .chunks(data_shards)
+            .zip(coding.chunk_sizes.chunks(data_shards))
+            .zip(&coding.parity);
+        for ((ids, sizes), parity_ids) in groups {
+            let group: Vec<Result<Vec<u8>>> = results.by_ref().take(ids.len()).collect();
+            let missing: Vec<bool> = group.iter().map(Result::is_err).collect();
+            if !missing.contains(&true) {
+                chunks.extend(group.into_iter().flatten());
+                continue;
+            }
+            debug!(
+                node = self.name,
+                "Reconstructing {} chunks from {} parity chunks",
+                missing.iter().filter(|missing| **missing).count(),
+                parity_ids.len()
+            );
+
+            let parity: Vec<Option<Vec<u8>>> = self
+                .fetch_chunks(
+                    parity_ids,
+                    retry_config,
+                    parallelism,
+                    access_token,
+                    latencies,
+                )
+                .await
+                .into_iter()
+                .map(Result::ok)
+                .collect();
+            let received = group.into_iter().map(Result::ok).collect();
+            let group = erasure::reconstruct(received, sizes, parity, data_shards)
+                .map_err(|e| anyhow!("Could not reconstruct the missing chunks: {e}"))?;
+
+            for ((chunk_id, content), _) in ids
+                .iter()
+                .zip(&group)
+                .zip(missing)
+                .filter(|(_, missing)| *missing)
+            {
+                let chunk = ChunkObject {
+                    content: content.clone(),
+                };
+                let typed: TypedObject = chunk.clone().into();
+                if &proto::Hash::try_from(&typed)? != chunk_id {
+                    bail!("The reconstructed chunk {chunk_id} has a wrong hash");
+                }
+                self.vault_ref
+                    .ask(StoreReceivedObject {
+                        hash: chunk_id.clone(),
+                        object: typed,
+                        kind: ProvenanceKind::Downloaded,
+                        source: None,
+                    })
+                    .send()
+                    .await?;
+            }
+            chunks.extend(group);
+        }
+        Ok(chunks)
+    }
+
+    /// Fetches the chunks in order, a few at a time
+    async fn fetch_chunks(
+        &self,
+        chunk_ids: &[proto::Hash],
+        retry_config: &RetryConfig,
+        parallelism: usize,
+        access_token: Option<&GroupAccessToken>,
+        latencies: &HashMap<PeerId, Duration>,
+    ) -> Vec<Result<Vec<u8>>> {
+        stream::iter(chunk_ids)
+            .map(|chunk_id| {
+                self.fetch_chunk(chunk_id, retry_config, parallelism, access_token, latencies)
+            })
+            .buffered(CHUNK_DOWNLOADS)
+            .collect()
+            .await
+    }
+
+    async fn fetch_chunk(
+        &self,
+        chunk_id: &proto::Hash,
+        retry_config: &RetryConfig,
+        parallelism: usize,
+        access_token: Option<&GroupAccessToken>,
+        latencies: &HashMap<PeerId, Duration>,
+    ) -> Result<Vec<u8>> {
+        if let Some(chunk) = local_chunk(&self.vault_ref, chunk_id).await? {
+            return Ok(chunk.content);
+        }
+        let (result, _) = retry::retry(retry_config, || {
+            self.download_object(chunk_id, parallelism, access_token, latencies)
+        })
+        .await;
+        let (object, peer, _) = result?;
+        let ObjectEnum::Chunk(chunk) = object else {
+            bail!("Object {chunk_id} is not a chunk");
+        };
+
+        self.vault_ref
+            .ask(StoreReceivedObject {
+                hash: chunk_id.clone(),
+                object: chunk.clone().into(),
+                kind: ProvenanceKind::Downloaded,
+                source: Some(peer),
+            })
+            .send()
+            .await?;
+        Ok(chunk.content)
+    }
+
+    /// Like `download_file`, but for any object. Returns the object inside the
+    /// signed ones, with the peer it was downloaded from
+    async fn download_object(
+        &self,
+        obj_id: &proto::Hash,
+        parallelism: usize,
+        access_token: Option<&GroupAccessToken>,
+        latencies: &HashMap<PeerId, Duration>,
+    ) -> Result<(ObjectEnum, PeerId, Option<DaemonQueryStats>)> {
+        let (object, peer, stats) = self
+            .download_typed(obj_id, parallelism, access_token, latencies)
+            .await?;
+        Ok((unwrap_signed(object).await?, peer, stats))
+    }
+
+    /// Downloads the object as it was published, with the signed objects it may be
+    /// wrapped in, and the peer it was downloaded from
+    pub async fn download_typed(
+        &self,
+        obj_id: &proto::Hash,
+        parallelism: usize,
+        access_token: Option<&GroupAccessToken>,
+        latencies: &HashMap<PeerId, Duration>,
+    ) -> Result<(TypedObject, PeerId, Option<DaemonQueryStats>)> {
+        let mut providers = find_providers(&self.swarm_sender, obj_id).await?;
+        // The objects of groups are never stored as records
+        let (record_sender, mut record) = oneshot::channel();
+        let mut waiting_record = access_token.is_none();
+        if waiting_record {
+            self.swarm_sender
+                .send(
+                    commands::GetObjectRecord {
+                        obj_id: obj_id.clone(),
+                        response_sender: record_sender,
+                    }
+                    .into(),
+                )
+                .await?;
+        }
+        let mut searching = true;
+        let mut stats = None;
+        let mut seen = HashSet::new();
+        let mut queue = VecDeque::new();
+        let mut in_flight = FuturesUnordered::new();
+
+        let mut failed = 0;
+        let mut not_found = 0;
+        let mut denied = 0;
+        loop {
+            while in_flight.len() < parallelism.max(1) {
+                let Some(peer) = queue.pop_front() else {
+                    break;
+                };
+                in_flight.push(self.download_from(obj_id, peer, access_token));
+            }
+            if in_flight.is_empty() && !searching && !waiting_record {
+                break;
+            }
+
+            tokio::select! {
+                found = &mut record, if waiting_record => {
+                    waiting_record = false;
+                    if let Ok(Ok(Some((object, peer)))) = found {
+                        debug!(
+                            node = self.name,
+                            from = peer.to_base58(),
+                            "Got object from its record"
+                        );
+                        return Ok((object, peer, stats));
+                    }
+                },
+                batch = providers.recv(), if searching => match batch {
+                    Some((peers, batch_stats)) => {
+                        let mut peers: Vec<PeerId> =
+                            peers.into_iter().filter(|p| seen.insert(*p)).collect();
+                        // The ones which were never pinged are asked last
+                        peers.sort_by_key(|p| latencies.get(p).copied().unwrap_or(Duration::MAX));
+                        queue.extend(peers);
+                        stats = batch_stats.or(stats);
+                    }
+                    None => searching = false,
+                },
+                Some((peer, result)) = in_flight.next(), if !in_flight.is_empty() => match result {
+                    Ok(object) => {
+                        debug!(
+                            node = self.name,
+                            from = peer.to_base58(),
+                            failed_providers = failed,
+                            "Downloaded object"
+                        );
+                        // Dropping the receiver stops the provider query
+                        return Ok((object, peer, stats));
+                    }
+                    Err(e) => {
+                        debug!(
+                            node = self.name,
+                            from = peer.to_base58(),
+                            err = e.to_string(),
+                            "Failed to download object"
+                        );
+                        failed += 1;
+                        match e.downcast_ref() {
+                            Some(PermanentError::NotFound(_)) => not_found += 1,
+                            Some(PermanentError::AccessDenied(_)) => denied += 1,
+                            _ => (),
+                        }
+                    }
+                },
+            }
+        }
+
+        // No providers found is a permanent error, so it is not retried
+        if seen.is_empty() {
+            return Err(PermanentError::NoProviders(obj_id.to_string()).into());
+        }
+        // Asking again makes sense only if some of the providers may still have the object
+        if failed > 0 && not_found == failed {
+            return Err(PermanentError::NotFound(obj_id.to_string()).into());
+        }
+        if failed > 0 && denied > 0 && not_found + denied == failed {
+            return Err(PermanentError::AccessDenied(obj_id.to_string()).into());
+        }
+        Err(TransientError::ProvidersFailed(failed).into())
+    }
+
+    async fn download_from(
+        &self,
+        obj_id: &proto::Hash,
+        peer: PeerId,
+        access_token: Option<&GroupAccessToken>,
+    ) -> (PeerId, Result<TypedObject>) {
+        (
+            peer,
+            self.try_download_from(obj_id, peer, access_token).await,
+        )
+    }
+
+    async fn try_download_from(
+        &self,
+        obj_id: &proto::Hash,
+        peer: PeerId,
+        access_token: Option<&GroupAccessToken>,
+    ) -> Result<TypedObject> {
+        debug!(
+            node = self.name,
+            peer_id = peer.to_base58(),
+            obj_id = obj_id.to_string(),
+            "Trying to download from peer"
+        );
+
+        let (obj_sender, obj_receiver) = oneshot::channel();
+        self.swarm_sender
+            .send(
+                commands::GetObject {
+                    obj_id: obj_id.clone(),
+                    peer_id: peer,
+                    access_token: access_token.cloned(),
+                    response_sender: obj_sender,
+                }
+                .into(),
+            )
+            .await?;
+        let obj = obj_receiver.await??;
+
+        if obj.uuid == ResultObject::UUID {
+            if let Ok(ObjectEnum::Result(ResultObject { result: Err(code) })) =
+                parser::parse_typed(obj).await
+            {
+                match code {
+                    ResultErrorCode::NotFound => {
+                        return Err(PermanentError::NotFound(obj_id.to_string()).into())
+                    }
+                    ResultErrorCode::AccessDenied => {
+                        return Err(PermanentError::AccessDenied(obj_id.to_string()).into())
+                    }
+                    ResultErrorCode::QuotaExceeded { retry_after_secs } => {
+                        return Err(anyhow!(
+                            "Peer is over its upload limits, retry after {retry_after_secs}s"
+                        ))
+                    }
+                    _ => (),
+                }
+                return Err(anyhow!("Peer responded with error {code:?}"));
+            }
+            return Err(anyhow!("Peer responded with unexpected result"));
+        }
+
+        let calculated_obj_id = proto::Hash::try_from(&obj)?;
+        if obj_id != &calculated_obj_id {
+            self.swarm_sender
+                .send(
+                    commands::ReportPeer {
+                        peer_id: peer,
+                        misbehaviour: Misbehaviour::FailedIntegrity,
+                    }
+                    .into(),
+                )
+                .await?;
+            return Err(anyhow!(
+                "Received wrong file! {calculated_obj_id} != {obj_id}"
+            ));
+        }
+
+        Ok(obj)
+    }
+}
+
+/// Extracts the object from the signed objects it may be wrapped in
+async fn unwrap_signed(obj: TypedObject) -> Result<ObjectEnum> {
+    let mut typed = obj;
+    loop {
+        typed = match parser::parse_typed(typed).await? {
+            ObjectEnum::Signed(signed) => signed.object,
+            object => return Ok(object),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::sync::{Arc, Mutex};
+
+    use liberum_core::node_config::ChunkingConfig;
+    use libp2p::identity::Keypair;
+    use tempdir::TempDir;
+
+    use super::*;
+    use crate::node::publisher::Publisher;
+
+    /// The objects sent to the network by their IDs
+    type Network = Arc<Mutex<HashMap<proto::Hash, TypedObject>>>;
+
+    /// Answers the messages like a swarm connected to a single peer, which keeps
+    /// the objects sent to it and provides them
+    fn fake_swarm(network: Network) -> mpsc::Sender<SwarmRunnerMessage> {
+        let (sender, mut receiver) = mpsc::channel(16);
+        let peer = PeerId::random();
+        tokio::spawn(async move {
+            while let Some(message) = receiver.recv().await {
+                match message {
+                    SwarmRunnerMessage::GetClosestPeers(commands::GetClosestPeers {
+                        response_sender,
+                        ..
+                    }) => {
+                        let _ = response_sender.send(vec![peer]);
+                    }
+                    SwarmRunnerMessage::SendObject(commands::SendObject {
+                        object,
+                        obj_id,
+                        response_sender,
+                        ..
+                    }) => {
+                        network.lock().unwrap().insert(obj_id, object);
+                        let _ = response_sender.send(Ok(ResultObject { result: Ok(()) }));
+                    }
+                    SwarmRunnerMessage::GetProviders(commands::GetProviders {
+                        obj_id,
+                        providers_sender,
+                    }) => {
+                        if network.lock().unwrap().contains_key(&obj_id) {
+                            let _ = providers_sender.send((vec![peer], None)).await;
+                        }
+                    }
+                    SwarmRunnerMessage::GetObjectRecord(commands::GetObjectRecord {
+                        response_sender,
+                        ..
+                    }) => {
+                        let _ = response_sender.send(Ok(None));
+                    }
+                    SwarmRunnerMessage::GetObject(commands::GetObject {
+                        obj_id,
+                        response_sender,
+                        ..
+                    }) => {
+                        let object = network.lock().unwrap().get(&obj_id).cloned();
+                        let _ = response_sender.send(object.ok_or(anyhow!("Not found")));
+                    }
+                    _ => (),
+                }
+            }
+        });
+        sender
+    }
+
+    #[tokio::test]
+    async fn cached_published_file_test() {
+        let tmp_dir = TempDir::new("liberum_tests").unwrap();
+        let path = tmp_dir.path().join("file.txt");
+        let content: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        std::fs::write(&path, &content).unwrap();
+
+        for chunked in [false, true] {
+            let network = Network::default();
+            let publisher = Publisher {
+                name: "publisher".to_string(),
+                keypair: Keypair::generate_ed25519(),
+                swarm_sender: fake_swarm(network.clone()),
+                vault_ref: kameo::spawn(Vault::new_in_memory().await.unwrap()),
+                chunking: ChunkingConfig {
+                    enabled: chunked,
+                    min_file_size: 0,
+                    erasure_coding: None,
+                },
+            };
+            let obj_id = publisher
+                .publish_file(
+                    &path,
+                    File::open(&path).unwrap(),
+                    AccessPolicy::Public,
+                    None,
+                )
+                .await
+                .unwrap();
+            let obj_id = proto::Hash::try_from(obj_id.as_str()).unwrap();
+
+            let downloader = Downloader {
+                name: "downloader".to_string(),
+                swarm_sender: fake_swarm(network.clone()),
+                vault_ref: kameo::spawn(Vault::new_in_memory().await.unwrap()),
+            };
+            let (file, _) = downloader
+                .fetch_file(&obj_id, &RetryConfig::default(), 1, None, &HashMap::new())
+                .await
+                .unwrap();
+            assert_eq!(file.content, content);
+
+            // The second download is from the vault, even with the network gone
+            network.lock().unwrap().clear();
+            let (file, stats) = cached_file(&downloader.vault_ref, &obj_id, Instant::now())
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(file.content, content);
+            assert!(stats.from_cache);
+        }
+    }
+}
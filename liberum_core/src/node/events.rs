@@ -0,0 +1,87 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use liberum_core::types::{NodeEvent, NodeEventKind};
+
+///! The module contains the log of the events of a node, like connections,
+///! publishes, downloads and errors. The log is shared by the node and its swarm
+///! and is polled by the UIs to show what the node is doing.
+
+/// Number of the events kept in the log, the oldest ones are dropped
+const EVENT_LOG_CAPACITY: usize = 1000;
+
+pub type SharedEventLog = Arc<Mutex<EventLog>>;
+
+pub struct EventLog {
+    next_id: u64,
+    events: VecDeque<NodeEvent>,
+}
+
+impl EventLog {
+    pub fn new_shared() -> SharedEventLog {
+        // The IDs start from the current time, so they keep increasing when the node
+        // is restarted and the UIs asking for the newer events don't miss any
+        let first_id = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+
+        Arc::new(Mutex::new(EventLog {
+            next_id: first_id,
+            events: VecDeque::new(),
+        }))
+    }
+
+    pub fn push(&mut self, kind: NodeEventKind, message: String) {
+        if self.events.len() >= EVENT_LOG_CAPACITY {
+            self.events.pop_front();
+        }
+
+        self.events.push_back(NodeEvent {
+            id: self.next_id,
+            timestamp: SystemTime::now(),
+            kind,
+            message,
+        });
+        self.next_id += 1;
+    }
+
+    /// Events with IDs greater than `since`, or all of them if `since` is None
+    pub fn since(&self, since: Option<u64>) -> Vec<NodeEvent> {
+        self.events
+            .iter()
+            .filter(|e| since.map_or(true, |since| e.id > since))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Adds the event to the shared log. A poisoned lock only means another thread
+/// panicked while logging, the log itself is still usable
+pub fn record(log: &SharedEventLog, kind: NodeEventKind, message: String) {
+    let mut log = log.lock().unwrap_or_else(|e| e.into_inner());
+    log.push(kind, message);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn event_log_since_test() {
+        let log = EventLog::new_shared();
+        for i in 0..EVENT_LOG_CAPACITY + 5 {
+            record(&log, NodeEventKind::Connection, i.to_string());
+        }
+
+        let log = log.lock().unwrap();
+        let all = log.since(None);
+        assert_eq!(all.len(), EVENT_LOG_CAPACITY);
+        assert_eq!(all.first().unwrap().message, "5");
+
+        let last = all.last().unwrap().id;
+        assert_eq!(log.since(Some(last - 2)).len(), 2);
+        assert!(log.since(Some(last)).is_empty());
+    }
+}
@@ -0,0 +1,345 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+use axum::extract::{Path, State};
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::Router;
+use kameo::actor::{ActorRef, WeakActorRef};
+use kameo::request::MessageSend;
+use liberum_core::node_config::GatewayConfig;
+use liberum_core::proto::{self, AccessPolicy, PlainFileObject};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+use tracing::{debug, error, info};
+
+use super::downloader::{self, LocalFile};
+use super::{finish_download, DownloadFile, Node};
+use crate::vault::{LoadAccessPolicy, Vault};
+
+///! The module contains the HTTP gateway of the node, which lets the ordinary
+///! browsers get the files published on the network. `GET /object/<id>` answers
+///! with the content of the file with the ID, from the vault of the node if it's
+///! there and public. The signed files and the ones published in chunks are served
+///! like the plain ones. If the config allows it, the files not in the vault, or
+///! with some of their chunks missing there, are downloaded from the providers like
+///! by the `download` command, a limited number a minute.
+///!
+///! The type of the content is guessed from the name of the file. Anyone can
+///! publish a file, so the content is sandboxed and never sniffed by the browser,
+///! a published page can't run scripts with the origin of the gateway.
+
+const FETCH_WINDOW: Duration = Duration::from_secs(60);
+
+#[derive(Clone)]
+struct GatewayState {
+    node_ref: WeakActorRef<Node>,
+    vault_ref: ActorRef<Vault>,
+    config: GatewayConfig,
+    /// The start of the current window and the downloads started in it
+    fetches: Arc<Mutex<(Instant, u32)>>,
+}
+
+impl GatewayState {
+    /// Counts the download from the network, false if it's not allowed now
+    fn allow_fetch(&self) -> bool {
+        if !self.config.fetch_from_network {
+            return false;
+        }
+        let mut fetches = self.fetches.lock().expect("Not to be poisoned");
+        let (start, count) = &mut *fetches;
+        if start.elapsed() >= FETCH_WINDOW {
+            *start = Instant::now();
+            *count = 0;
+        }
+        if *count >= self.config.max_fetches_per_min {
+            return false;
+        }
+        *count += 1;
+        true
+    }
+}
+
+/// The file with the ID in the vault
+enum StoredFile {
+    Public(PlainFileObject),
+    /// In the vault, but not public
+    Hidden,
+    /// Public, but not a file
+    Unsupported,
+    Missing,
+}
+
+/// Starts serving on the address from the config, until the task is aborted
+pub async fn start(
+    config: &GatewayConfig,
+    node_ref: WeakActorRef<Node>,
+    vault_ref: ActorRef<Vault>,
+) -> Result<JoinHandle<()>> {
+    let listener = TcpListener::bind(config.bind_address).await?;
+    info!(address = config.bind_address.to_string(), "Gateway started");
+    let router = Router::new()
+        .route("/object/:id", get(get_object))
+        .with_state(GatewayState {
+            node_ref,
+            vault_ref,
+            config: *config,
+            fetches: Arc::new(Mutex::new((Instant::now(), 0))),
+        });
+
+    Ok(tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, router).await {
+            error!(err = e.to_string(), "Gateway failed");
+        }
+    }))
+}
+
+async fn get_object(State(state): State<GatewayState>, Path(id): Path<String>) -> Response {
+    let Ok(object_id) = proto::Hash::try_from(id.as_str()) else {
+        return (StatusCode::BAD_REQUEST, "Invalid object ID").into_response();
+    };
+
+    let file = match load_file(&state.vault_ref, object_id).await {
+        StoredFile::Public(file) => Some(file),
+        StoredFile::Hidden => None,
+        StoredFile::Unsupported => {
+            return (StatusCode::UNSUPPORTED_MEDIA_TYPE, "Object is not a file").into_response()
+        }
+        StoredFile::Missing if state.allow_fetch() => download_file(&state.node_ref, id).await,
+        StoredFile::Missing => None,
+    };
+    match file {
+        Some(file) => file_response(file),
+        None => (StatusCode::NOT_FOUND, "File not found").into_response(),
+    }
+}
+
+/// The file from the vault, only the public files are served, not the other objects
+async fn load_file(vault_ref: &ActorRef<Vault>, object_id: proto::Hash) -> StoredFile {
+    let file = match downloader::local_file(vault_ref, &object_id).await {
+        Ok(Some(file)) => file,
+        Ok(None) => return StoredFile::Missing,
+        Err(e) => {
+            debug!(
+                obj_id = object_id.to_string(),
+                err = e.to_string(),
+                "Gateway could not load"
+            );
+            return StoredFile::Hidden;
+        }
+    };
+
+    let policy = vault_ref
+        .ask(LoadAccessPolicy { hash: object_id })
+        .send()
+        .await;
+    if !matches!(policy, Ok(AccessPolicy::Public)) {
+        return StoredFile::Hidden;
+    }
+    match file {
+        LocalFile::Found(file) => StoredFile::Public(file),
+        // The chunks not in the vault are downloaded
+        LocalFile::Incomplete => StoredFile::Missing,
+        LocalFile::NotFile => StoredFile::Unsupported,
+    }
+}
+
+async fn download_file(node_ref: &WeakActorRef<Node>, id: String) -> Option<PlainFileObject> {
+    let node_ref = node_ref.upgrade()?;
+    let download = node_ref
+        .ask(DownloadFile {
+            obj_id_str: id.clone(),
+            group: None,
+            // The vault was checked, its files which are not public are not served
+            force_network: true,
+        })
+        .send()
+        .await;
+    let result = match download {
+        Ok(download) => finish_download(download).await,
+        Err(e) => Err(Arc::new(anyhow!(e.to_string()))),
+    };
+    match result {
+        Ok((file, _)) => Some(file),
+        Err(e) => {
+            debug!(
+                obj_id = id,
+                err = e.to_string(),
+                "Gateway could not download"
+            );
+            None
+        }
+    }
+}
+
+fn file_response(file: PlainFileObject) -> Response {
+    let headers = [
+        (header::CONTENT_TYPE, content_type(&file.name)),
+        (header::CONTENT_SECURITY_POLICY, "sandbox"),
+        (header::X_CONTENT_TYPE_OPTIONS, "nosniff"),
+    ];
+    (headers, file.content).into_response()
+}
+
+/// The media type of the file by the extension of its name
+fn content_type(name: &str) -> &'static str {
+    let extension = name
+        .rsplit_once('.')
+        .map(|(_, extension)| extension.to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("html" | "htm") => "text/html; charset=utf-8",
+        Some("css") => "text/css; charset=utf-8",
+        Some("js") => "text/javascript; charset=utf-8",
+        Some("json") => "application/json",
+        Some("txt") => "text/plain; charset=utf-8",
+        Some("md") => "text/markdown; charset=utf-8",
+        Some("csv") => "text/csv; charset=utf-8",
+        Some("xml") => "application/xml",
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("svg") => "image/svg+xml",
+        Some("ico") => "image/x-icon",
+        Some("pdf") => "application/pdf",
+        Some("mp3") => "audio/mpeg",
+        Some("ogg") => "audio/ogg",
+        Some("mp4") => "video/mp4",
+        Some("webm") => "video/webm",
+        Some("wasm") => "application/wasm",
+        Some("zip") => "application/zip",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::fs::File;
+
+    use liberum_core::node_config::ChunkingConfig;
+    use liberum_core::proto::{ChunkObject, ResultObject, TypedObject};
+    use libp2p::identity::Keypair;
+    use libp2p::PeerId;
+    use tempdir::TempDir;
+    use tokio::sync::mpsc;
+
+    use super::*;
+    use crate::node::publisher::Publisher;
+    use crate::swarm_runner::commands;
+    use crate::swarm_runner::messages::SwarmRunnerMessage;
+
+    #[test]
+    fn content_type_test() {
+        assert_eq!(content_type("index.HTML"), "text/html; charset=utf-8");
+        assert_eq!(content_type("photo.tar.jpeg"), "image/jpeg");
+        assert_eq!(content_type("README"), "application/octet-stream");
+        assert_eq!(content_type("archive.unknown"), "application/octet-stream");
+
+        let response = file_response(PlainFileObject {
+            name: "notes.txt".to_string(),
+            content: b"notes".to_vec(),
+        });
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "text/plain; charset=utf-8"
+        );
+        assert_eq!(
+            response.headers()[header::CONTENT_SECURITY_POLICY],
+            "sandbox"
+        );
+    }
+
+    /// Publishes the file to a single peer, which accepts everything. Returns the
+    /// ID of the file and the objects sent to the peer
+    async fn publish(
+        vault_ref: ActorRef<Vault>,
+        path: &std::path::Path,
+        chunked: bool,
+    ) -> (proto::Hash, HashMap<proto::Hash, TypedObject>) {
+        let (swarm_sender, mut swarm_receiver) = mpsc::channel(16);
+        let network = tokio::spawn(async move {
+            let mut sent = HashMap::new();
+            while let Some(message) = swarm_receiver.recv().await {
+                match message {
+                    SwarmRunnerMessage::GetClosestPeers(commands::GetClosestPeers {
+                        response_sender,
+                        ..
+                    }) => {
+                        let _ = response_sender.send(vec![PeerId::random()]);
+                    }
+                    SwarmRunnerMessage::SendObject(commands::SendObject {
+                        object,
+                        obj_id,
+                        response_sender,
+                        ..
+                    }) => {
+                        sent.insert(obj_id, object);
+                        let _ = response_sender.send(Ok(ResultObject { result: Ok(()) }));
+                    }
+                    _ => (),
+                }
+            }
+            sent
+        });
+        let publisher = Publisher {
+            name: "publisher".to_string(),
+            keypair: Keypair::generate_ed25519(),
+            swarm_sender,
+            vault_ref,
+            chunking: ChunkingConfig {
+                enabled: chunked,
+                min_file_size: 0,
+                erasure_coding: None,
+            },
+        };
+        let obj_id = publisher
+            .publish_file(path, File::open(path).unwrap(), AccessPolicy::Public, None)
+            .await
+            .unwrap();
+        drop(publisher);
+
+        let obj_id = proto::Hash::try_from(obj_id.as_str()).unwrap();
+        (obj_id, network.await.unwrap())
+    }
+
+    #[tokio::test]
+    async fn load_published_file_test() {
+        let tmp_dir = TempDir::new("liberum_tests").unwrap();
+        let path = tmp_dir.path().join("index.html");
+        let content: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+        std::fs::write(&path, &content).unwrap();
+
+        for chunked in [false, true] {
+            let vault_ref = kameo::spawn(Vault::new_in_memory().await.unwrap());
+            let (obj_id, sent) = publish(vault_ref.clone(), &path, chunked).await;
+            match load_file(&vault_ref, obj_id).await {
+                StoredFile::Public(file) => {
+                    assert_eq!(file.name, "index.html");
+                    assert_eq!(file.content, content);
+                }
+                _ => panic!("The published file is not served"),
+            }
+
+            // The chunks are public too, but they are not files
+            let chunk_id = sent
+                .iter()
+                .find(|(_, object)| object.uuid == ChunkObject::UUID)
+                .map(|(id, _)| id.clone());
+            assert_eq!(chunk_id.is_some(), chunked);
+            if let Some(chunk_id) = chunk_id {
+                assert!(matches!(
+                    load_file(&vault_ref, chunk_id).await,
+                    StoredFile::Unsupported
+                ));
+            }
+            assert!(matches!(
+                load_file(&vault_ref, proto::Hash { bytes: [1; 32] }).await,
+                StoredFile::Missing
+            ));
+        }
+    }
+}
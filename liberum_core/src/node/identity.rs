@@ -0,0 +1,102 @@
+use anyhow::{anyhow, Result};
+use argon2::Argon2;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use libp2p::identity::Keypair;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+///! The module contains the encryption of the node keypairs with a passphrase.
+///! The key is derived from the passphrase with argon2 and the keypair is
+///! encrypted with chacha20poly1305, so a wrong passphrase is always detected.
+
+const BUNDLE_VERSION: u8 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+#[derive(Error, Debug)]
+pub enum IdentityError {
+    #[error("wrong passphrase or corrupted identity")]
+    WrongPassphrase,
+    #[error("unsupported identity version {0}")]
+    UnsupportedVersion(u8),
+}
+
+/// The encrypted keypair in the postcard encoding, as written to the disk. The
+/// plaintext of the ciphertext is the keypair in the protobuf encoding
+#[derive(Serialize, Deserialize, Debug)]
+struct EncryptedKeypair {
+    version: u8,
+    salt: [u8; SALT_LEN],
+    nonce: [u8; NONCE_LEN],
+    ciphertext: Vec<u8>,
+}
+
+pub fn encrypt_keypair(keypair: &Keypair, passphrase: &str) -> Result<Vec<u8>> {
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce = [0u8; NONCE_LEN];
+    rand::thread_rng().fill_bytes(&mut salt);
+    rand::thread_rng().fill_bytes(&mut nonce);
+
+    let cipher = cipher(passphrase, &salt)?;
+    let key_bytes = keypair.to_protobuf_encoding()?;
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), key_bytes.as_slice())
+        .map_err(|e| anyhow!("could not encrypt keypair: {e}"))?;
+
+    let encrypted = EncryptedKeypair {
+        version: BUNDLE_VERSION,
+        salt,
+        nonce,
+        ciphertext,
+    };
+    Ok(postcard::to_allocvec(&encrypted)?)
+}
+
+pub fn decrypt_keypair(bytes: &[u8], passphrase: &str) -> Result<Keypair> {
+    let encrypted: EncryptedKeypair =
+        postcard::from_bytes(bytes).map_err(|_| IdentityError::WrongPassphrase)?;
+    if encrypted.version != BUNDLE_VERSION {
+        return Err(IdentityError::UnsupportedVersion(encrypted.version).into());
+    }
+
+    let cipher = cipher(passphrase, &encrypted.salt)?;
+    let key_bytes = cipher
+        .decrypt(
+            Nonce::from_slice(&encrypted.nonce),
+            encrypted.ciphertext.as_slice(),
+        )
+        .map_err(|_| IdentityError::WrongPassphrase)?;
+
+    Ok(Keypair::from_protobuf_encoding(&key_bytes)?)
+}
+
+fn cipher(passphrase: &str, salt: &[u8]) -> Result<ChaCha20Poly1305> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| anyhow!("could not derive key from passphrase: {e}"))?;
+
+    Ok(ChaCha20Poly1305::new(Key::from_slice(&key)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encrypt_decrypt_test() {
+        let keypair = Keypair::generate_ed25519();
+        let encrypted = encrypt_keypair(&keypair, "passphrase").unwrap();
+
+        let decrypted = decrypt_keypair(&encrypted, "passphrase").unwrap();
+        assert_eq!(decrypted.public(), keypair.public());
+
+        let err = decrypt_keypair(&encrypted, "wrong").unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<IdentityError>(),
+            Some(IdentityError::WrongPassphrase)
+        ));
+    }
+}
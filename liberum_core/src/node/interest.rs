@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::oneshot;
+
+///! The module coalesces the concurrent fetches of the same key, e.g. the downloads
+///! of an object asked for by a few UIs at once. The first caller starts the fetch,
+///! the ones coming while it's in flight wait for its result instead of fetching
+///! the key again. Every waiter gets its own copy of the result. The fetch is
+///! forgotten when its `Fetch` is dropped without a result, e.g. when the task
+///! fetching the key panics, so the waiters get an error instead of hanging.
+
+/// The callers waiting for the fetches in flight, by their keys
+pub struct PendingInterests<K, T> {
+    waiting: Arc<Mutex<HashMap<K, Vec<oneshot::Sender<T>>>>>,
+}
+
+impl<K, T> Clone for PendingInterests<K, T> {
+    fn clone(&self) -> Self {
+        PendingInterests {
+            waiting: self.waiting.clone(),
+        }
+    }
+}
+
+impl<K, T> Default for PendingInterests<K, T> {
+    fn default() -> Self {
+        PendingInterests {
+            waiting: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+}
+
+impl<K: Hash + Eq, T: Clone> PendingInterests<K, T> {
+    /// Waits for the fetch of the key, None if it's not in flight
+    pub fn attach(&self, key: &K) -> Option<oneshot::Receiver<T>> {
+        let mut waiting = self.waiting.lock().expect("Not to be poisoned");
+        let waiters = waiting.get_mut(key)?;
+        let (send, recv) = oneshot::channel();
+        waiters.push(send);
+        Some(recv)
+    }
+
+    /// Marks the fetch of the key as in flight, it's completed with the returned `Fetch`
+    pub fn start(&self, key: K) -> (oneshot::Receiver<T>, Fetch<K, T>)
+    where
+        K: Clone,
+    {
+        let (send, recv) = oneshot::channel();
+        self.waiting
+            .lock()
+            .expect("Not to be poisoned")
+            .entry(key.clone())
+            .or_default()
+            .push(send);
+        let fetch = Fetch {
+            interests: self.clone(),
+            key: Some(key),
+        };
+        (recv, fetch)
+    }
+
+    /// Removes the waiters of the key, to send them the result or to drop them
+    fn take(&self, key: &K) -> Vec<oneshot::Sender<T>> {
+        self.waiting
+            .lock()
+            .expect("Not to be poisoned")
+            .remove(key)
+            .unwrap_or_default()
+    }
+}
+
+/// The fetch of a key in flight. The waiters are dropped if it's dropped before
+/// it's completed
+pub struct Fetch<K: Hash + Eq, T: Clone> {
+    interests: PendingInterests<K, T>,
+    key: Option<K>,
+}
+
+impl<K: Hash + Eq, T: Clone> Fetch<K, T> {
+    /// Sends the result of the fetch to everyone waiting for it
+    pub fn complete(mut self, result: T) {
+        if let Some(key) = self.key.take() {
+            for waiter in self.interests.take(&key) {
+                let _ = waiter.send(result.clone());
+            }
+        }
+    }
+}
+
+impl<K: Hash + Eq, T: Clone> Drop for Fetch<K, T> {
+    fn drop(&mut self) {
+        if let Some(key) = self.key.take() {
+            self.interests.take(&key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn coalesce_test() {
+        let interests: PendingInterests<&str, u32> = PendingInterests::default();
+        let in_flight = || interests.waiting.lock().unwrap().len();
+        assert!(interests.attach(&"a").is_none());
+
+        let (first, fetch) = interests.start("a");
+        let second = interests.attach(&"a").unwrap();
+        assert_eq!(in_flight(), 1);
+
+        fetch.complete(7);
+        assert_eq!(first.await.unwrap(), 7);
+        assert_eq!(second.await.unwrap(), 7);
+        assert_eq!(in_flight(), 0);
+        assert!(interests.attach(&"a").is_none());
+
+        // A fetch which panics doesn't leave its waiters hanging
+        let (first, fetch) = interests.start("b");
+        let task = tokio::spawn(async move {
+            let _fetch = fetch;
+            panic!("fetch failed");
+        });
+        assert!(task.await.is_err());
+        assert!(first.await.is_err());
+        assert_eq!(in_flight(), 0);
+    }
+}
@@ -0,0 +1,379 @@
+use std::collections::HashSet;
+use std::time::{Duration, SystemTime};
+
+use anyhow::{anyhow, Result};
+use kameo::{actor::ActorRef, request::MessageSend};
+use liberum_core::proto::{self, DirectMessageObject};
+use liberum_core::types::{InboxMessage, MessageContent, NodeEventKind};
+use libp2p::{identity::Keypair, PeerId};
+use tokio::sync::{mpsc, oneshot};
+use tracing::{debug, warn};
+
+use crate::swarm_runner::behaviour::mailbox::{
+    MailboxRequest, MailboxResponse, MAILBOX_MAX_ENTRIES,
+};
+use crate::swarm_runner::commands;
+use crate::swarm_runner::messages::SwarmRunnerMessage;
+use crate::vault::{StoreMessage, Vault};
+
+use super::events::{self, SharedEventLog};
+use super::retry;
+
+///! The module contains the store-and-forward of the direct messages. A message for
+///! a peer which can't be reached is encrypted for it and kept by the peers closest
+///! to its peer ID. When the node starts, it fetches the messages kept for it by its
+///! own closest peers page by page, stores them in the inbox and asks the peers to
+///! delete them.
+
+/// Time for the swarm to bootstrap before the mailboxes are fetched
+pub const MAILBOX_FETCH_DELAY: Duration = Duration::from_secs(10);
+
+/// How the message got to the recipient
+#[derive(Debug)]
+pub enum Delivery {
+    Direct(proto::Hash),
+    /// The recipient could not be reached, the message waits at the peers
+    Stored {
+        id: proto::Hash,
+        peers: usize,
+    },
+}
+
+#[derive(Clone)]
+pub struct Mailbox {
+    pub name: String,
+    pub keypair: Keypair,
+    pub swarm_sender: mpsc::Sender<SwarmRunnerMessage>,
+    pub vault_ref: ActorRef<Vault>,
+    pub events: SharedEventLog,
+}
+
+impl Mailbox {
+    /// Sends the message to the peer, or leaves it at the closest peers of the
+    /// recipient if it can't be reached
+    pub async fn send(
+        &self,
+        peer_id: PeerId,
+        content: MessageContent,
+        encrypt: bool,
+    ) -> Result<Delivery> {
+        let (send, recv) = oneshot::channel();
+        self.swarm_sender
+            .send(
+                commands::SendMessage {
+                    peer_id,
+                    content: content.clone(),
+                    encrypt,
+                    response_sender: send,
+                }
+                .into(),
+            )
+            .await?;
+
+        match recv.await? {
+            Ok(id) => Ok(Delivery::Direct(id)),
+            Err(e) if retry::is_transient(&e) => {
+                debug!(
+                    node = self.name,
+                    peer = peer_id.to_base58(),
+                    err = e.to_string(),
+                    "Peer unreachable, storing the message at its closest peers"
+                );
+                self.store_at_closest_peers(peer_id, content).await
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Encrypts the message for the recipient and sends it to the closest peers of
+    /// its ID. Fails if no peer kept it
+    async fn store_at_closest_peers(
+        &self,
+        recipient: PeerId,
+        content: MessageContent,
+    ) -> Result<Delivery> {
+        // Always encrypted, the peers keeping it must not read it
+        let (message, id) = DirectMessageObject::seal(&self.keypair, &recipient, content, true)?;
+
+        let mut peers = 0;
+        for peer in self.closest_peers(recipient).await? {
+            if peer == recipient {
+                continue;
+            }
+            let request = MailboxRequest::Store {
+                message: message.clone(),
+            };
+            match self.request(peer, request).await {
+                Ok(MailboxResponse::Stored) => peers += 1,
+                Ok(response) => debug!(
+                    node = self.name,
+                    peer = peer.to_base58(),
+                    response = format!("{response:?}"),
+                    "Peer did not keep the message"
+                ),
+                Err(e) => debug!(
+                    node = self.name,
+                    peer = peer.to_base58(),
+                    err = e.to_string(),
+                    "Could not leave the message at the peer"
+                ),
+            }
+        }
+
+        if peers == 0 {
+            return Err(anyhow!(
+                "Peer {recipient} is unreachable and no peer kept the message"
+            ));
+        }
+        Ok(Delivery::Stored { id, peers })
+    }
+
+    /// Fetches the messages kept for this node, stores them in the inbox and
+    /// deletes them at the peers. Returns the number of the new messages
+    pub async fn fetch(&self) -> Result<usize> {
+        let own_id = self.keypair.public().to_peer_id();
+        let mut received = HashSet::new();
+
+        for peer in self.closest_peers(own_id).await? {
+            let ids = self.fetch_from(peer, &mut received).await?;
+
+            // The messages are deleted only after they are safe in the inbox
+            if !ids.is_empty() {
+                if let Err(e) = self.request(peer, MailboxRequest::Delete { ids }).await {
+                    debug!(
+                        node = self.name,
+                        peer = peer.to_base58(),
+                        err = e.to_string(),
+                        "Could not delete the fetched messages"
+                    );
+                }
+            }
+        }
+
+        if !received.is_empty() {
+            events::record(
+                &self.events,
+                NodeEventKind::Message,
+                format!("{} messages received while offline", received.len()),
+            );
+        }
+        Ok(received.len())
+    }
+
+    /// Fetches the pages of the mailbox kept by the peer and stores the messages not
+    /// received yet in the inbox. Returns the IDs of the stored messages to delete.
+    /// The peer is asked for at most as many pages as it may keep messages
+    async fn fetch_from(
+        &self,
+        peer: PeerId,
+        received: &mut HashSet<String>,
+    ) -> Result<Vec<String>> {
+        let mut ids = Vec::new();
+        let mut after = None;
+        for _ in 0..MAILBOX_MAX_ENTRIES {
+            let request = MailboxRequest::Fetch {
+                after: after.clone(),
+            };
+            let (messages, more) = match self.request(peer, request).await {
+                Ok(MailboxResponse::Messages { messages, more }) => (messages, more),
+                Ok(_) => break,
+                Err(e) => {
+                    debug!(
+                        node = self.name,
+                        peer = peer.to_base58(),
+                        err = e.to_string(),
+                        "Could not fetch the mailbox"
+                    );
+                    break;
+                }
+            };
+            let last = match messages.last() {
+                Some(signed) => Some(proto::Hash::try_from(&signed.object)?.to_string()),
+                None => None,
+            };
+
+            for signed in messages {
+                let opened = DirectMessageObject::verify(&signed)
+                    .and_then(|m| m.sender_id())
+                    .and_then(|sender| DirectMessageObject::open(&signed, &sender, &self.keypair));
+                let opened = match opened {
+                    Ok(opened) => opened,
+                    Err(e) => {
+                        warn!(
+                            node = self.name,
+                            peer = peer.to_base58(),
+                            err = e.to_string(),
+                            "Invalid message in the mailbox"
+                        );
+                        continue;
+                    }
+                };
+
+                let id = opened.id.to_string();
+                ids.push(id.clone());
+                if !received.insert(id.clone()) {
+                    continue;
+                }
+                let message = InboxMessage {
+                    id,
+                    sender: opened.sender.to_base58(),
+                    sent_at: opened.sent_at,
+                    received_at: SystemTime::now(),
+                    encrypted: opened.encrypted,
+                    content: opened.content,
+                };
+                self.vault_ref.ask(StoreMessage { message }).send().await?;
+            }
+
+            // A page which doesn't move on would be asked for again and again
+            if !more || last.is_none() || last == after {
+                break;
+            }
+            after = last;
+        }
+        Ok(ids)
+    }
+
+    /// Waits for the swarm to bootstrap and fetches the mailbox
+    pub async fn fetch_after(self, delay: Duration) {
+        tokio::time::sleep(delay).await;
+        match self.fetch().await {
+            Ok(count) => debug!(node = self.name, count = count, "Mailbox fetched"),
+            Err(e) => warn!(
+                node = self.name,
+                err = e.to_string(),
+                "Could not fetch the mailbox"
+            ),
+        }
+    }
+
+    async fn closest_peers(&self, peer_id: PeerId) -> Result<Vec<PeerId>> {
+        let (send, recv) = oneshot::channel();
+        self.swarm_sender
+            .send(
+                commands::GetClosestPeersOfPeer {
+                    peer_id,
+                    response_sender: send,
+                }
+                .into(),
+            )
+            .await?;
+
+        let peers = recv.await?;
+        Ok(peers
+            .into_iter()
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect())
+    }
+
+    async fn request(&self, peer_id: PeerId, request: MailboxRequest) -> Result<MailboxResponse> {
+        let (send, recv) = oneshot::channel();
+        self.swarm_sender
+            .send(
+                commands::SendMailboxRequest {
+                    peer_id,
+                    request,
+                    response_sender: send,
+                }
+                .into(),
+            )
+            .await?;
+
+        recv.await?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::node::events::EventLog;
+    use crate::vault::LoadMessages;
+
+    #[tokio::test]
+    async fn fetch_pages_test() {
+        let keypair = Keypair::generate_ed25519();
+        let sender = Keypair::generate_ed25519();
+        let own_id = keypair.public().to_peer_id();
+        let messages: Vec<_> = (0..5)
+            .map(|i| {
+                let content = MessageContent::Text(format!("Message {i}"));
+                DirectMessageObject::seal(&sender, &own_id, content, true)
+                    .unwrap()
+                    .0
+            })
+            .collect();
+
+        // A peer keeping the messages, which sends them two at a time
+        let deleted = Arc::new(Mutex::new(Vec::new()));
+        let (swarm_sender, mut swarm_receiver) = mpsc::channel(16);
+        let peer_deleted = deleted.clone();
+        tokio::spawn(async move {
+            let peer = PeerId::random();
+            while let Some(message) = swarm_receiver.recv().await {
+                match message {
+                    SwarmRunnerMessage::GetClosestPeersOfPeer(
+                        commands::GetClosestPeersOfPeer {
+                            response_sender, ..
+                        },
+                    ) => {
+                        let _ = response_sender.send(vec![peer]);
+                    }
+                    SwarmRunnerMessage::SendMailboxRequest(commands::SendMailboxRequest {
+                        request,
+                        response_sender,
+                        ..
+                    }) => {
+                        let response = match request {
+                            MailboxRequest::Fetch { after } => {
+                                let start = after.map_or(0, |after| {
+                                    messages
+                                        .iter()
+                                        .position(|m| {
+                                            proto::Hash::try_from(&m.object).unwrap().to_string()
+                                                == after
+                                        })
+                                        .unwrap()
+                                        + 1
+                                });
+                                let end = (start + 2).min(messages.len());
+                                MailboxResponse::Messages {
+                                    messages: messages[start..end].to_vec(),
+                                    more: end < messages.len(),
+                                }
+                            }
+                            MailboxRequest::Delete { ids } => {
+                                let count = ids.len();
+                                peer_deleted.lock().unwrap().extend(ids);
+                                MailboxResponse::Deleted(count)
+                            }
+                            MailboxRequest::Store { .. } => MailboxResponse::Stored,
+                        };
+                        let _ = response_sender.send(Ok(response));
+                    }
+                    _ => (),
+                }
+            }
+        });
+
+        let vault_ref = kameo::spawn(Vault::new_in_memory().await.unwrap());
+        let mailbox = Mailbox {
+            name: "node".to_string(),
+            keypair,
+            swarm_sender,
+            vault_ref: vault_ref.clone(),
+            events: EventLog::new_shared(),
+        };
+        assert_eq!(mailbox.fetch().await.unwrap(), 5);
+
+        let inbox = vault_ref.ask(LoadMessages).send().await.unwrap();
+        assert_eq!(inbox.len(), 5);
+        assert_eq!(
+            inbox[4].content,
+            MessageContent::Text("Message 4".to_string())
+        );
+        assert_eq!(deleted.lock().unwrap().len(), 5);
+    }
+}
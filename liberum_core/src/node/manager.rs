@@ -1,8 +1,16 @@
 use super::{
+    events::{self, EventLog, SharedEventLog},
+    module_host::ModuleHost,
     store::{GetNodeVault, ListNodes, NodeStore, NodeStoreError, StoreNode},
-    GetSnapshot, Node, NodeSnapshot,
+    supervisor::{Exit, Restart, Supervisor},
+    GetSnapshot, GetVaultRef, Node, NodeSnapshot,
 };
 use crate::node::store::LoadNode;
+use crate::vault::snapshot::{ExportSnapshot, ImportSnapshot};
+use crate::vault::{
+    DeleteContact, LoadAuditLog, LoadContacts, LoadGroupPosts, LoadMessages, LoadStatsSamples,
+    StoreContact, Vault,
+};
 use anyhow::anyhow;
 use anyhow::{Error, Result};
 use kameo::{
@@ -12,13 +20,23 @@ use kameo::{
     request::MessageSend,
     spawn, Actor,
 };
-use liberum_core::node_config::NodeConfig;
+use liberum_core::node_config::{NodeConfig, NodeProfile, WatchDir};
+use liberum_core::owned_file::FileTarget;
+use liberum_core::proto::RotationObject;
+use liberum_core::types::{
+    AuditEntry, AuditFilter, ConfigReloadSummary, Contact, GroupPost, InboxMessage, NodeEventKind,
+    NodeStatsSample, VaultSnapshotSummary,
+};
+use libp2p::PeerId;
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     fmt::{Debug, Display},
+    path::PathBuf,
+    time::{Instant, SystemTime},
 };
 use thiserror::Error;
-use tracing::{debug, error};
+use tracing::{debug, error, info, warn};
+use uuid::Uuid;
 
 type NodeRefs = HashMap<String, ActorRef<Node>>;
 
@@ -26,7 +44,13 @@ type NodeRefs = HashMap<String, ActorRef<Node>>;
 pub struct NodeManager {
     nodes: NodeRefs,
     store: ActorRef<NodeStore>,
+    module_host: ModuleHost,
     actor_ref: Option<ActorRef<NodeManager>>,
+    supervisor: Supervisor,
+    /// The event logs of the nodes, kept when the nodes are restarted
+    events: HashMap<String, SharedEventLog>,
+    /// The named groups of nodes from the daemon config
+    groups: BTreeMap<String, Vec<String>>,
 }
 
 #[derive(Error, Debug)]
@@ -39,6 +63,8 @@ pub enum NodeManagerError {
     AlreadyStopped { name: String },
     #[error("node {name} is not started")]
     NotStarted { name: String },
+    #[error("group {name} is not in the daemon config")]
+    UnknownGroup { name: String },
     #[error("node store error: {0}")]
     StoreError(NodeStoreError),
     #[error("other node manager error: {0}")]
@@ -52,6 +78,15 @@ impl NodeManager {
         Ok(self.store.clone())
     }
 
+    /// The nodes of the group, to be started, stopped or reloaded together
+    #[message]
+    pub fn get_group_nodes(&self, group: String) -> Result<Vec<String>, NodeManagerError> {
+        self.groups
+            .get(&group)
+            .cloned()
+            .ok_or(NodeManagerError::UnknownGroup { name: group })
+    }
+
     #[message]
     pub fn is_node_running(&self, name: String) -> bool {
         self.nodes.contains_key(&name)
@@ -62,11 +97,7 @@ impl NodeManager {
         &mut self,
         node_snapshot: NodeSnapshot,
     ) -> Result<(), NodeManagerError> {
-        self.store
-            .ask(StoreNode { node_snapshot })
-            .send()
-            .await
-            .map_err(|e| NodeManagerError::OtherError(e.into()))?;
+        self.store.ask(StoreNode { node_snapshot }).send().await?;
 
         Ok(())
     }
@@ -102,6 +133,8 @@ impl NodeManager {
             .from_snapshot(&node_snapshot)
             .manager_ref(self_ref)
             .vault_ref(spawn(node_vault))
+            .module_host(self.module_host.clone())
+            .events(self.event_log(&name))
             .build()
             .map_err(|e| NodeManagerError::OtherError(e))?;
 
@@ -178,36 +211,524 @@ impl NodeManager {
         Ok(())
     }
 
+    /// Adds the peer to or removes it from the blocklist. If the node is running
+    /// the change is applied immediately, without restarting it
+    #[message]
+    pub async fn set_peer_blocked(
+        &self,
+        name: String,
+        peer_id: PeerId,
+        blocked: bool,
+    ) -> Result<(), NodeManagerError> {
+        if self.is_node_running(name.clone()) {
+            let node_ref = self.get_node_ref(&name)?;
+            node_ref
+                .ask(super::SetPeerBlocked { peer_id, blocked })
+                .send()
+                .await
+                .map_err(|e| NodeManagerError::OtherError(anyhow!(e.to_string())))?;
+            return self.save_node(node_ref).await;
+        }
+
+        let mut new_cfg = self
+            .store
+            .ask(super::store::GetNodeConfig { name: name.clone() })
+            .send()
+            .await?;
+
+        match blocked {
+            true => new_cfg.block_peer(peer_id),
+            false => new_cfg.unblock_peer(&peer_id),
+        }
+
+        self.store
+            .ask(super::store::OverwriteNodeConfig { name, new_cfg })
+            .send()
+            .await?;
+
+        Ok(())
+    }
+
+    /// Enables or disables the module of the node. If the node is running the
+    /// change is applied immediately, without restarting it
+    #[message]
+    pub async fn set_module_enabled(
+        &self,
+        name: String,
+        module: Uuid,
+        enabled: bool,
+    ) -> Result<(), NodeManagerError> {
+        let mut new_cfg = self.get_node_config(name.clone()).await?;
+        new_cfg.modules.set_enabled(module, enabled);
+
+        if self.is_node_running(name.clone()) {
+            let node_ref = self.get_node_ref(&name)?;
+            node_ref
+                .ask(super::ReloadConfig { config: new_cfg })
+                .send()
+                .await
+                .map_err(|e| NodeManagerError::OtherError(anyhow!(e.to_string())))?;
+            return self.save_node(node_ref).await;
+        }
+
+        self.store
+            .ask(super::store::OverwriteNodeConfig { name, new_cfg })
+            .send()
+            .await?;
+
+        Ok(())
+    }
+
+    /// Adds the directory to the watch list of the node. A running node starts
+    /// watching it at once
+    #[message]
+    pub async fn add_watch_dir(&self, name: String, dir: WatchDir) -> Result<(), NodeManagerError> {
+        let mut new_cfg = self.get_node_config(name.clone()).await?;
+        new_cfg.add_watch_dir(dir);
+        self.apply_config(name, new_cfg).await
+    }
+
+    /// Removes the directory from the watch list of the node, returns false if it
+    /// was not watched
+    #[message]
+    pub async fn remove_watch_dir(
+        &self,
+        name: String,
+        path: PathBuf,
+    ) -> Result<bool, NodeManagerError> {
+        let mut new_cfg = self.get_node_config(name.clone()).await?;
+        if !new_cfg.remove_watch_dir(&path) {
+            return Ok(false);
+        }
+        self.apply_config(name, new_cfg).await?;
+        Ok(true)
+    }
+
+    /// Sets the profile of the node. A running node publishes it at once, a stopped
+    /// one when it starts
+    #[message]
+    pub async fn set_node_profile(
+        &self,
+        name: String,
+        profile: NodeProfile,
+    ) -> Result<(), NodeManagerError> {
+        if self.is_node_running(name.clone()) {
+            let node_ref = self.get_node_ref(&name)?;
+            node_ref
+                .ask(super::SetProfile { profile })
+                .send()
+                .await
+                .map_err(|e| NodeManagerError::OtherError(anyhow!(e.to_string())))?;
+            return self.save_node(node_ref).await;
+        }
+
+        let mut new_cfg = self.get_node_config(name.clone()).await?;
+        new_cfg.profile = Some(profile);
+        self.store
+            .ask(super::store::OverwriteNodeConfig { name, new_cfg })
+            .send()
+            .await?;
+
+        Ok(())
+    }
+
     #[message]
-    pub async fn stop_node(&self, name: String) -> Result<(), NodeManagerError> {
+    pub async fn stop_node(&mut self, name: String) -> Result<(), NodeManagerError> {
         let node_ref = self.get_node_ref(&name)?;
         self.save_node(node_ref.clone()).await?;
+        self.supervisor.stopping(&name);
 
         node_ref
             .stop_gracefully()
             .await
             .map_err(|e| NodeManagerError::OtherError(e.into()))?;
+        // Renaming or deleting the node needs its vault closed
+        node_ref.wait_for_stop().await;
 
         Ok(())
     }
 
+    /// Applies the config to the running node without restarting it. The new config
+    /// is saved first, also when the node is not running. If there is none the stored
+    /// config is reloaded
+    #[message]
+    pub async fn reload_node_config(
+        &self,
+        name: String,
+        new_cfg: Option<NodeConfig>,
+    ) -> Result<ConfigReloadSummary, NodeManagerError> {
+        let config = match new_cfg {
+            Some(new_cfg) => {
+                self.store
+                    .ask(super::store::OverwriteNodeConfig {
+                        name: name.clone(),
+                        new_cfg: new_cfg.clone(),
+                    })
+                    .send()
+                    .await?;
+                new_cfg
+            }
+            None => {
+                self.store
+                    .ask(super::store::GetNodeConfig { name: name.clone() })
+                    .send()
+                    .await?
+            }
+        };
+
+        let summary = self
+            .get_node_ref(&name)?
+            .ask(super::ReloadConfig { config })
+            .send()
+            .await
+            .map_err(|e| NodeManagerError::OtherError(anyhow!(e.to_string())))?;
+
+        Ok(summary)
+    }
+
+    /// Renames the stopped node. A running node is stopped first if `force` is set
+    #[message]
+    pub async fn rename_node(
+        &mut self,
+        name: String,
+        new_name: String,
+        force: bool,
+    ) -> Result<(), NodeManagerError> {
+        self.ensure_stopped(&name, force).await?;
+        self.store
+            .ask(super::store::RenameNode {
+                name: name.clone(),
+                new_name: new_name.clone(),
+            })
+            .send()
+            .await?;
+
+        self.supervisor.forget(&name);
+        if let Some(log) = self.events.remove(&name) {
+            self.events.insert(new_name, log);
+        }
+
+        Ok(())
+    }
+
+    /// Rotates the keypair of the stopped node. A running node is stopped first if
+    /// `force` is set. The rotation is published when the node starts again
+    #[message]
+    pub async fn rotate_node_key(
+        &mut self,
+        name: String,
+        passphrase: Option<String>,
+        force: bool,
+    ) -> Result<RotationObject, NodeManagerError> {
+        self.ensure_stopped(&name, force).await?;
+        let rotation = self
+            .store
+            .ask(super::store::RotateKey { name, passphrase })
+            .send()
+            .await?;
+
+        Ok(rotation)
+    }
+
+    /// Deletes the stopped node. A running node is stopped first if `force` is set
+    #[message]
+    pub async fn delete_node(
+        &mut self,
+        name: String,
+        force: bool,
+        keep_vault: bool,
+    ) -> Result<(), NodeManagerError> {
+        self.ensure_stopped(&name, force).await?;
+        self.store
+            .ask(super::store::DeleteNode {
+                name: name.clone(),
+                keep_vault,
+            })
+            .send()
+            .await?;
+
+        self.supervisor.forget(&name);
+        self.events.remove(&name);
+
+        Ok(())
+    }
+
+    /// Packs the vault of the node into the archive. The node may be running
+    #[message]
+    pub async fn export_vault_snapshot(
+        &self,
+        name: String,
+        target: FileTarget,
+    ) -> Result<VaultSnapshotSummary, NodeManagerError> {
+        self.get_node_vault(&name)
+            .await?
+            .ask(ExportSnapshot { target })
+            .send()
+            .await
+            .map_err(vault_error)
+    }
+
+    /// Merges the archive into the vault of the node. The node may be running
+    #[message]
+    pub async fn import_vault_snapshot(
+        &self,
+        name: String,
+        file: std::fs::File,
+    ) -> Result<VaultSnapshotSummary, NodeManagerError> {
+        self.get_node_vault(&name)
+            .await?
+            .ask(ImportSnapshot { file })
+            .send()
+            .await
+            .map_err(vault_error)
+    }
+
+    /// Adds or replaces the contact. The node may be running
+    #[message]
+    pub async fn add_contact(
+        &self,
+        name: String,
+        contact: Contact,
+    ) -> Result<(), NodeManagerError> {
+        self.get_node_vault(&name)
+            .await?
+            .ask(StoreContact { contact })
+            .send()
+            .await
+            .map_err(vault_error)
+    }
+
+    /// Returns false if the node had no such contact
+    #[message]
+    pub async fn remove_contact(
+        &self,
+        name: String,
+        peer_id: String,
+    ) -> Result<bool, NodeManagerError> {
+        self.get_node_vault(&name)
+            .await?
+            .ask(DeleteContact { peer_id })
+            .send()
+            .await
+            .map_err(vault_error)
+    }
+
+    #[message]
+    pub async fn list_contacts(&self, name: String) -> Result<Vec<Contact>, NodeManagerError> {
+        self.get_node_vault(&name)
+            .await?
+            .ask(LoadContacts)
+            .send()
+            .await
+            .map_err(vault_error)
+    }
+
+    /// The messages received by the node. The node may be running
+    #[message]
+    pub async fn get_inbox(&self, name: String) -> Result<Vec<InboxMessage>, NodeManagerError> {
+        self.get_node_vault(&name)
+            .await?
+            .ask(LoadMessages)
+            .send()
+            .await
+            .map_err(vault_error)
+    }
+
+    /// The posts of the group received by the node. The node may be running
+    #[message]
+    pub async fn get_group_feed(
+        &self,
+        name: String,
+        group_id: String,
+    ) -> Result<Vec<GroupPost>, NodeManagerError> {
+        self.get_node_vault(&name)
+            .await?
+            .ask(LoadGroupPosts { group: group_id })
+            .send()
+            .await
+            .map_err(vault_error)
+    }
+
+    /// The inbound requests served or rejected by the node. The node may be running
+    #[message]
+    pub async fn get_audit_log(
+        &self,
+        name: String,
+        filter: AuditFilter,
+    ) -> Result<Vec<AuditEntry>, NodeManagerError> {
+        self.get_node_vault(&name)
+            .await?
+            .ask(LoadAuditLog { filter })
+            .send()
+            .await
+            .map_err(vault_error)
+    }
+
+    /// The samples of the stats recorded while the node was running. The node may
+    /// be running
+    #[message]
+    pub async fn get_stats_history(
+        &self,
+        name: String,
+        since: Option<SystemTime>,
+        until: Option<SystemTime>,
+    ) -> Result<Vec<NodeStatsSample>, NodeManagerError> {
+        self.get_node_vault(&name)
+            .await?
+            .ask(LoadStatsSamples { since, until })
+            .send()
+            .await
+            .map_err(vault_error)
+    }
+
     #[message]
     pub async fn stop_all(&mut self) -> Result<(), NodeManagerError> {
-        for name in self.nodes.keys() {
-            self.stop_node(name.to_string()).await?;
+        let names: Vec<String> = self.nodes.keys().cloned().collect();
+        for name in names {
+            self.stop_node(name).await?;
         }
 
         Ok(())
     }
+
+    /// Called by the node which failed and is stopping, it's restarted by its
+    /// restart policy
+    #[message]
+    pub fn node_failed(&mut self, name: String, reason: String) {
+        warn!(node = name, reason, "Node failed");
+        self.supervisor.failed(&name, reason);
+    }
+
+    /// Starts the node which stopped by itself again, unless it was started
+    /// meanwhile
+    #[message]
+    pub async fn restart_node(&mut self, name: String) -> Result<(), NodeManagerError> {
+        if self.nodes.contains_key(&name) {
+            return Ok(());
+        }
+
+        match self.start_node(name.clone()).await {
+            Ok(_) => {
+                info!(node = name, "Node restarted");
+                let log = self.event_log(&name);
+                events::record(&log, NodeEventKind::Restart, "Node restarted".to_string());
+                Ok(())
+            }
+            Err(e) => {
+                if let Some(log) = self.events.get(&name) {
+                    let message = format!("Failed to restart node: {e}");
+                    events::record(log, NodeEventKind::Error, message);
+                }
+                Err(e)
+            }
+        }
+    }
 }
 
 impl NodeManager {
-    pub fn new(store: ActorRef<NodeStore>) -> Self {
+    pub fn new(
+        store: ActorRef<NodeStore>,
+        module_host: ModuleHost,
+        groups: BTreeMap<String, Vec<String>>,
+    ) -> Self {
         NodeManager {
             nodes: HashMap::new(),
             store,
+            module_host,
             actor_ref: None,
+            supervisor: Supervisor::default(),
+            events: HashMap::new(),
+            groups,
+        }
+    }
+
+    /// The event log of the node, the same one every time the node starts
+    fn event_log(&mut self, name: &str) -> SharedEventLog {
+        self.events
+            .entry(name.to_string())
+            .or_insert_with(EventLog::new_shared)
+            .clone()
+    }
+
+    /// Restarts the stopped node by its restart policy, after the backoff
+    async fn supervise(&mut self, name: String, exit: Exit) {
+        if exit == Exit::Requested {
+            return;
+        }
+        let log = self.event_log(&name);
+        if let Exit::Failed(reason) = &exit {
+            events::record(
+                &log,
+                NodeEventKind::Error,
+                format!("Node crashed: {reason}"),
+            );
+        }
+
+        let config = match self
+            .store
+            .ask(super::store::GetNodeConfig { name: name.clone() })
+            .send()
+            .await
+        {
+            Ok(config) => config.restart,
+            Err(e) => {
+                error!(
+                    node = name,
+                    err = e.to_string(),
+                    "Failed to load restart policy"
+                );
+                return;
+            }
+        };
+
+        match self
+            .supervisor
+            .next_restart(&name, &exit, &config, Instant::now())
+        {
+            Restart::No => {}
+            Restart::After(delay) => {
+                let Some(self_ref) = self.actor_ref.clone() else {
+                    return;
+                };
+                info!(node = name, delay = format!("{delay:?}"), "Restarting node");
+                let message = format!("Restarting node in {delay:?}");
+                events::record(&log, NodeEventKind::Restart, message);
+                tokio::spawn(async move {
+                    tokio::time::sleep(delay).await;
+                    if let Err(e) = self_ref
+                        .ask(RestartNode { name: name.clone() })
+                        .send()
+                        .await
+                    {
+                        error!(node = name, err = e.to_string(), "Failed to restart node");
+                    }
+                });
+            }
+            Restart::GaveUp => {
+                warn!(
+                    node = name,
+                    "Node restarted too many times, leaving it stopped"
+                );
+                let message = format!(
+                    "Node not restarted, it restarted {} times in {} s",
+                    config.max_restarts, config.window_secs
+                );
+                events::record(&log, NodeEventKind::Restart, message);
+            }
+        }
+    }
+
+    async fn ensure_stopped(&mut self, name: &str, force: bool) -> Result<(), NodeManagerError> {
+        if !self.nodes.contains_key(name) {
+            return Ok(());
+        }
+        if !force {
+            return Err(NodeManagerError::NodeStarted {
+                name: name.to_string(),
+            });
         }
+
+        self.stop_node(name.to_string()).await
     }
 
     fn get_node_ref(&self, name: &str) -> Result<ActorRef<Node>, NodeManagerError> {
@@ -219,6 +740,60 @@ impl NodeManager {
         }
     }
 
+    /// The vault of the running node or, if the node is stopped, the vault opened
+    /// from the store
+    async fn get_node_vault(&self, name: &str) -> Result<ActorRef<Vault>, NodeManagerError> {
+        if let Ok(node_ref) = self.get_node_ref(name) {
+            return node_ref
+                .ask(GetVaultRef)
+                .send()
+                .await
+                .map_err(|e| NodeManagerError::OtherError(anyhow!(e.to_string())));
+        }
+
+        // The store would create an empty vault for a node that does not exist
+        self.store
+            .ask(super::store::GetNodeConfig {
+                name: name.to_string(),
+            })
+            .send()
+            .await?;
+        let node_vault = self
+            .store
+            .ask(GetNodeVault {
+                name: name.to_string(),
+            })
+            .send()
+            .await
+            .map_err(|e| NodeManagerError::OtherError(anyhow!(e)))?;
+
+        Ok(spawn(node_vault))
+    }
+
+    /// Reloads the config of the running node, or overwrites the stored one
+    async fn apply_config(
+        &self,
+        name: String,
+        new_cfg: NodeConfig,
+    ) -> Result<(), NodeManagerError> {
+        if self.is_node_running(name.clone()) {
+            let node_ref = self.get_node_ref(&name)?;
+            node_ref
+                .ask(super::ReloadConfig { config: new_cfg })
+                .send()
+                .await
+                .map_err(|e| NodeManagerError::OtherError(anyhow!(e.to_string())))?;
+            return self.save_node(node_ref).await;
+        }
+
+        self.store
+            .ask(super::store::OverwriteNodeConfig { name, new_cfg })
+            .send()
+            .await?;
+
+        Ok(())
+    }
+
     async fn save_node(&self, node_ref: ActorRef<Node>) -> Result<(), NodeManagerError> {
         let snapshot = node_ref
             .ask(super::GetSnapshot)
@@ -262,7 +837,7 @@ impl Actor for NodeManager {
         &mut self,
         _: kameo::actor::WeakActorRef<Self>,
         id: kameo::actor::ActorID,
-        _: kameo::error::ActorStopReason,
+        reason: kameo::error::ActorStopReason,
     ) -> std::result::Result<Option<kameo::error::ActorStopReason>, kameo::error::BoxError> {
         debug!(id = id.to_string(), "node died");
         let name = self
@@ -280,12 +855,27 @@ impl Actor for NodeManager {
         let name = name
             .first()
             .ok_or(anyhow!("there is no such node started"))?;
-        self.nodes.remove(name);
+        let name = name.clone();
+        self.nodes.remove(&name);
+
+        let crash = match reason {
+            kameo::error::ActorStopReason::Normal => None,
+            reason => Some(reason.to_string()),
+        };
+        let exit = self.supervisor.exit(&name, crash);
+        self.supervise(name, exit).await;
 
         Ok(None)
     }
 }
 
+fn vault_error<M>(e: SendError<M, Error>) -> NodeManagerError {
+    match e {
+        SendError::HandlerError(e) => NodeManagerError::OtherError(e),
+        e => NodeManagerError::OtherError(anyhow!(e.to_string())),
+    }
+}
+
 impl From<NodeStoreError> for NodeManagerError {
     fn from(value: NodeStoreError) -> Self {
         NodeManagerError::StoreError(value)
@@ -1,41 +1,114 @@
+pub mod downloader;
+pub mod events;
+pub mod gateway;
+pub mod identity;
+pub mod interest;
+pub mod mailbox;
 pub mod manager;
+pub mod module_host;
+pub mod publisher;
+pub mod query;
+pub mod replicator;
+pub mod retry;
+pub mod scheduler;
+pub mod search;
 pub mod store;
+pub mod supervisor;
+pub mod watcher;
 
+use crate::logging;
 use crate::swarm_runner;
-use crate::vault::{ListTypedObjects, Vault};
+use crate::vault::backend::GroupMembership;
+use crate::vault::{
+    DeletePublishedObject, ListTypedObjects, LoadContacts, LoadGroups, LoadHistory, LoadObject,
+    LoadPopularObjects, LoadProvenance, LoadPublishedObject, SearchText, SetTextIndex, StoreGroup,
+    Vault,
+};
 use anyhow::{anyhow, Result};
+use downloader::Downloader;
+use events::{EventLog, SharedEventLog};
+use futures::{stream, StreamExt};
+use interest::PendingInterests;
 use kameo::mailbox::bounded::BoundedMailbox;
 use kameo::messages;
 use kameo::request::MessageSend;
 use kameo::{actor::ActorRef, message::Message, Actor};
-use liberum_core::node_config::NodeConfig;
-use liberum_core::proto::{self, SignedObject, TypedObject};
-use liberum_core::proto::{PlainFileObject, ResultObject};
+use liberum_core::node_config::{NodeConfig, NodeProfile};
+use liberum_core::proto::PlainFileObject;
+use liberum_core::proto::{self, TypedObject};
+use liberum_core::proto::{
+    AccessPolicy, GroupAccessToken, GroupInvitation, GroupObject, SignedObject, UserGroup,
+};
 use liberum_core::str_to_file_id;
-use liberum_core::types::TypedObjectInfo;
+use liberum_core::types::{
+    BucketInfo, ConfigReloadSummary, MessageContent, NodeEvent, NodeEventKind, NodeStatus,
+    ObjectAccess, ObjectInfo, ObjectPopularity, ObjectVerification, PeerInfo, PeerScore,
+    ProvenanceKind, PublishFileResult, QueryResults, ScheduledTask, ScheduledTaskInfo,
+    SearchResult, TextMatch, TrustLevel, TypedObjectInfo,
+};
 use liberum_core::{parser, DaemonQueryStats, DaemonResponse};
-use libp2p::{identity::Keypair, Multiaddr, PeerId};
-use manager::NodeManager;
-use std::{borrow::Borrow, collections::HashSet, fmt, path::PathBuf, str::FromStr};
-use swarm_runner::messages::SwarmRunnerMessage;
+use libp2p::identity::{Keypair, PublicKey};
+use libp2p::multihash::Multihash;
+use libp2p::{Multiaddr, PeerId};
+use mailbox::{Delivery, Mailbox, MAILBOX_FETCH_DELAY};
+use manager::{NodeFailed, NodeManager};
+use module_host::ModuleHost;
+use publisher::Publisher;
+use query::{QueryCoordinator, QUERY_PARALLELISM};
+use replicator::Replicator;
+use scheduler::{ListTasks, RunNow, Scheduler, SetIntervals, TaskContext};
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use std::{borrow::Borrow, fmt, str::FromStr};
+use swarm_runner::commands;
+use swarm_runner::messages::{ProvidersBatch, SwarmRunnerMessage};
 use tokio::sync::mpsc::Sender;
 use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
 use tokio::time::Duration;
-use tracing::{debug, error, warn};
+use tracing::{debug, error, warn, Instrument};
 
 pub struct Node {
     pub name: String,
     pub keypair: Keypair,
     pub config: NodeConfig,
+    /// The PEM of the certificate of the WebRTC transport, if the node listens on it
+    pub webrtc_certificate: Option<String>,
+    /// The UID of the user who created the node, the files the node opens by itself
+    /// must be owned by the user. None if no owner is recorded
+    pub owner: Option<u32>,
     pub manager_ref: ActorRef<NodeManager>,
     pub vault_ref: ActorRef<Vault>,
+    pub module_host: ModuleHost,
     // These fields are mandatory, but may be set only after spawning the node, so unwrapping them should be safe from
     // all of the methods:
     pub self_actor_ref: Option<ActorRef<Self>>,
     swarm_sender: Option<mpsc::Sender<SwarmRunnerMessage>>,
+    replicator_ref: Option<ActorRef<Replicator>>,
+    scheduler_ref: Option<ActorRef<Scheduler>>,
+    gateway: Option<JoinHandle<()>>,
+    watcher: Option<watcher::FolderWatcher>,
+    events: SharedEventLog,
+    /// The downloads in flight, joined by the requests for the same objects of the
+    /// same group
+    downloads: PendingInterests<(proto::Hash, Option<String>), DownloadResult>,
 }
 
+/// The downloaded file shared by the requests which asked for it at the same time
+pub type DownloadResult = Result<(PlainFileObject, Option<DaemonQueryStats>), Arc<anyhow::Error>>;
+/// A download started with `DownloadFile`
+pub type PendingDownload = oneshot::Receiver<DownloadResult>;
+
 const DIAL_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long the stopping node waits for its swarm to stop
+const SWARM_STOP_TIMEOUT: Duration = Duration::from_secs(10);
+/// Longest chain of key rotations followed when verifying a signer
+const MAX_KEY_ROTATIONS: usize = 16;
+/// The matches of a full-text search without a limit
+const TEXT_SEARCH_LIMIT: usize = 20;
 
 impl Actor for Node {
     type Mailbox = BoundedMailbox<Self>;
@@ -47,7 +120,28 @@ impl Actor for Node {
         // This should always be first thing to set self ref, because some methods executed later will assume that
         // this field is Some -- unwrapping this option
         self.self_actor_ref = Some(actor_ref.clone());
+        self.vault_ref
+            .ask(SetTextIndex {
+                config: self.config.text_index,
+            })
+            .send()
+            .await
+            .map_err(|e| anyhow!(e.to_string()))?;
         self.start_swarm().await?;
+        self.start_replicator();
+        self.start_scheduler();
+        self.start_watcher();
+        if self.config.gateway.enabled {
+            self.gateway = Some(
+                gateway::start(
+                    &self.config.gateway,
+                    actor_ref.downgrade(),
+                    self.vault_ref.clone(),
+                )
+                .await?,
+            );
+        }
+        tokio::spawn(self.mailbox().fetch_after(MAILBOX_FETCH_DELAY));
 
         Ok(())
     }
@@ -57,12 +151,32 @@ impl Actor for Node {
         _: kameo::actor::WeakActorRef<Self>,
         _: kameo::error::ActorStopReason,
     ) -> std::result::Result<(), kameo::error::BoxError> {
-        Ok(self
-            .swarm_sender
-            .as_ref()
-            .unwrap()
-            .send(SwarmRunnerMessage::Kill)
-            .await?)
+        if let Some(scheduler_ref) = self.scheduler_ref.take() {
+            scheduler_ref.kill();
+        }
+        if let Some(replicator_ref) = self.replicator_ref.take() {
+            replicator_ref.kill();
+        }
+        if let Some(gateway) = self.gateway.take() {
+            gateway.abort();
+        }
+        self.watcher = None;
+
+        let swarm_sender = self.swarm_sender.as_ref().unwrap();
+        swarm_sender.send(SwarmRunnerMessage::Kill).await?;
+        // The node stops once its files are closed, so the manager may move or delete
+        // its directory then
+        if tokio::time::timeout(SWARM_STOP_TIMEOUT, swarm_sender.closed())
+            .await
+            .is_err()
+        {
+            warn!(node = self.name, "The swarm did not stop in time");
+        }
+        if self.vault_ref.stop_gracefully().await.is_ok() {
+            self.vault_ref.wait_for_stop().await;
+        }
+
+        Ok(())
     }
 }
 
@@ -71,80 +185,84 @@ impl Node {
     /// Message called by the swarm when it dies. The node should know about
     /// it and shut down.
     #[message]
-    pub async fn swarm_died(&mut self) {
+    pub async fn swarm_died(&mut self, reason: String) {
         debug!(node = self.name, "Swarm died! Killing myself!");
-        if let Err(e) = self
-            .self_actor_ref
-            .as_mut()
-            .unwrap()
-            .stop_gracefully()
-            .await
-        {
-            error!(
-                node = self.name,
-                err = format!("{e:?}"),
-                "Failed to kill node!"
-            );
-            self.self_actor_ref.as_mut().unwrap().kill();
-        }
+        self.record_event(NodeEventKind::Error, format!("Swarm died: {reason}"));
+
+        let name = self.name.clone();
+        let manager_ref = self.manager_ref.clone();
+        let self_ref = self.self_actor_ref.clone().unwrap();
+        // The manager learns about the failure before the node stops, so it knows
+        // the node crashed. It's asked outside of the handler, as it may be waiting
+        // for the node
+        tokio::spawn(async move {
+            if let Err(e) = manager_ref
+                .ask(NodeFailed {
+                    name: name.clone(),
+                    reason,
+                })
+                .send()
+                .await
+            {
+                error!(node = name, err = e.to_string(), "Failed to report failure");
+            }
+            if let Err(e) = self_ref.stop_gracefully().await {
+                error!(node = name, err = format!("{e:?}"), "Failed to kill node!");
+                self_ref.kill();
+            }
+        });
     }
 
     /// Message called on the node from the daemon to get the list of providers
-    /// of an id. Changes the ID from string to libp2p format and just passes it to the swarm.
+    /// of an id. Waits for the whole provider query to finish.
     #[message]
     pub async fn get_providers(
         &mut self,
         obj_id_str: String,
     ) -> Result<(Vec<PeerId>, Option<DaemonQueryStats>)> {
         debug!(node = self.name, "Node got GetProviders");
+        let providers = self.stream_providers(obj_id_str).await?;
+        let (peers, stats) = downloader::collect_providers(providers).await;
+        debug!(node = self.name, "Got providers: {peers:?}");
+        Ok((peers, stats))
+    }
+
+    /// Message called on the node from the daemon to get the providers of an id as
+    /// soon as they are found. The providers may repeat between the batches, the
+    /// channel is closed when the provider query finishes
+    #[message]
+    pub async fn stream_providers(
+        &mut self,
+        obj_id_str: String,
+    ) -> Result<mpsc::Receiver<ProvidersBatch>> {
         let obj_id_kad = str_to_file_id(&obj_id_str)?;
         let obj_id = proto::Hash {
             bytes: obj_id_kad.to_vec().as_slice().try_into()?,
         };
-        let (send, recv) = oneshot::channel();
-
-        self.swarm_sender
-            .as_mut()
-            .unwrap()
-            .send(SwarmRunnerMessage::GetProviders {
-                obj_id: obj_id,
-                response_sender: send,
-            })
-            .await?;
-
-        if let Ok(received) = recv.await {
-            let peers: Vec<PeerId> = received
-                .0
-                .into_iter()
-                .collect::<HashSet<_>>()
-                .into_iter()
-                .collect();
-            let stats = received.1;
-            debug!(node = self.name, "Got providers: {peers:?}");
-            return Ok((peers, stats));
-        }
-
-        Err(anyhow!("Could not get providers"))
+        downloader::find_providers(self.swarm_sender.as_ref().unwrap(), &obj_id).await
     }
 
-    /// Message called on the node from the daemon to provide a file.
-    /// Calculates the ID of the file and passes it to the swarm. Responds with
+    /// Message called on the node from the daemon to provide a file opened from the
+    /// path. Calculates the ID of the file and passes it to the swarm. Responds with
     /// the ID of the file using which it can be found.
     #[message]
-    pub async fn provide_file(&mut self, path: PathBuf) -> Result<String> {
+    pub async fn provide_file(&mut self, path: PathBuf, file: File) -> Result<String> {
         let (resp_send, resp_recv) = oneshot::channel();
 
-        let object: TypedObject = PlainFileObject::try_from_path(&path).await?.into();
+        let object: TypedObject = PlainFileObject::try_from_file(&path, file).await?.into();
         let obj_id = proto::Hash::try_from(&object)?;
 
         self.swarm_sender
             .as_mut()
             .unwrap()
-            .send(SwarmRunnerMessage::ProvideObject {
-                object,
-                obj_id: obj_id.clone(),
-                response_sender: resp_send,
-            })
+            .send(
+                commands::ProvideObject {
+                    object,
+                    obj_id: obj_id.clone(),
+                    response_sender: resp_send,
+                }
+                .into(),
+            )
             .await?;
 
         resp_recv.await??;
@@ -153,159 +271,501 @@ impl Node {
         Ok(obj_id_str)
     }
 
+    /// Starts downloading the file, its result is received with `finish_download`.
+    /// The file already in the vault is not downloaded, unless forced to. The objects
+    /// of a group are downloaded with the membership of the node in the group. The
+    /// download of an object already in flight is joined instead of started again
     #[message]
     pub async fn download_file(
         &mut self,
         obj_id_str: String,
-    ) -> Result<(proto::PlainFileObject, Option<DaemonQueryStats>)> {
+        group: Option<String>,
+        force_network: bool,
+    ) -> Result<PendingDownload> {
         let obj_id = proto::Hash::try_from(obj_id_str.as_str())?;
+        let started_at = Instant::now();
+        if !force_network {
+            if let Some((file, stats)) =
+                downloader::cached_file(&self.vault_ref, &obj_id, started_at).await?
+            {
+                debug!(node = self.name, obj_id = obj_id_str, "File found in vault");
+                let result = Ok((file, Some(stats)));
+                record_download(&self.events, &obj_id_str, &result);
+                let (send, recv) = oneshot::channel();
+                let _ = send.send(result.map_err(Arc::new));
+                return Ok(recv);
+            }
+        }
+        // The objects of a group are downloaded with another membership, so the
+        // downloads of different groups are not joined
+        let key = (obj_id.clone(), group.clone());
+        if let Some(download) = self.downloads.attach(&key) {
+            debug!(node = self.name, obj_id = obj_id_str, "Joined download");
+            return Ok(download);
+        }
 
-        // first get the providers of the file
-        // Maybe getting the providers could be reused from GetProviders node message handler??
-        let (resp_send, resp_recv) = oneshot::channel();
+        let access_token = match group {
+            Some(group) => self.group_membership(&group).await?.token,
+            None => None,
+        };
+        // The fastest providers are asked first
+        let latencies = self.get_latencies().await.unwrap_or_else(|e| {
+            warn!(
+                node = self.name,
+                err = e.to_string(),
+                "Failed to get peer latencies"
+            );
+            HashMap::new()
+        });
+
+        let (download, fetch) = self.downloads.start(key);
+        let downloader = self.downloader();
+        let events = self.events.clone();
+        let retry_config = self.config.retry.clone();
+        let parallelism = self.config.download_parallelism;
+        tokio::spawn(async move {
+            let result = downloader
+                .fetch_file(
+                    &obj_id,
+                    &retry_config,
+                    parallelism,
+                    access_token.as_ref(),
+                    &latencies,
+                )
+                .await;
+            record_download(&events, &obj_id_str, &result);
+            fetch.complete(result.map_err(Arc::new));
+        });
+        Ok(download)
+    }
+
+    #[message]
+    pub async fn list_scheduled_tasks(&self) -> Result<Vec<ScheduledTaskInfo>> {
+        let scheduler_ref = self
+            .scheduler_ref
+            .as_ref()
+            .ok_or(anyhow!("Scheduler is not running"))?;
+        Ok(scheduler_ref.ask(ListTasks).send().await?)
+    }
+
+    /// Starts the task now. The receiver gets the state of the task when it finishes,
+    /// it's not awaited by the node, as the tasks ask the node too
+    #[message]
+    pub async fn run_scheduled_task(
+        &self,
+        task: ScheduledTask,
+    ) -> Result<oneshot::Receiver<ScheduledTaskInfo>> {
+        let scheduler_ref = self
+            .scheduler_ref
+            .as_ref()
+            .ok_or(anyhow!("Scheduler is not running"))?;
+        Ok(scheduler_ref.ask(RunNow { task }).send().await?)
+    }
+
+    #[message]
+    pub fn get_peer_id(&mut self) -> Result<PeerId> {
+        Ok(PeerId::from(self.keypair.public()))
+    }
+
+    #[message]
+    pub async fn get_addresses(&mut self) -> Result<Vec<Multiaddr>> {
+        let (send, recv) = oneshot::channel();
 
         self.swarm_sender
             .as_mut()
             .unwrap()
-            .send(SwarmRunnerMessage::GetProviders {
-                obj_id: obj_id.clone(),
-                response_sender: resp_send,
-            })
+            .send(
+                commands::GetAddresses {
+                    response_sender: send,
+                }
+                .into(),
+            )
+            .await?;
+
+        let addrs = recv.await??;
+        Ok(addrs)
+    }
+
+    #[message]
+    pub async fn get_status(&mut self) -> Result<NodeStatus> {
+        let (send, recv) = oneshot::channel();
+
+        self.swarm_sender
+            .as_mut()
+            .unwrap()
+            .send(
+                commands::GetStatus {
+                    response_sender: send,
+                }
+                .into(),
+            )
+            .await?;
+
+        Ok(recv.await?)
+    }
+
+    #[message]
+    pub async fn get_peer_scores(&mut self) -> Result<Vec<PeerScore>> {
+        let (send, recv) = oneshot::channel();
+
+        self.swarm_sender
+            .as_mut()
+            .unwrap()
+            .send(
+                commands::GetPeerScores {
+                    response_sender: send,
+                }
+                .into(),
+            )
+            .await?;
+
+        Ok(recv.await?)
+    }
+
+    #[message]
+    pub async fn reset_peer_score(&mut self, peer_id: PeerId) -> Result<()> {
+        let (send, recv) = oneshot::channel();
+
+        self.swarm_sender
+            .as_mut()
+            .unwrap()
+            .send(
+                commands::ResetPeerScore {
+                    peer_id,
+                    response_sender: send,
+                }
+                .into(),
+            )
+            .await?;
+
+        recv.await?
+    }
+
+    #[message]
+    pub async fn get_routing_table(&mut self) -> Result<(Vec<BucketInfo>, Vec<PeerInfo>)> {
+        let (send, recv) = oneshot::channel();
+
+        self.swarm_sender
+            .as_mut()
+            .unwrap()
+            .send(
+                commands::GetRoutingTable {
+                    response_sender: send,
+                }
+                .into(),
+            )
+            .await?;
+
+        Ok(recv.await?)
+    }
+
+    /// Average round trip times of the connected peers
+    #[message]
+    pub async fn get_latencies(&mut self) -> Result<HashMap<PeerId, Duration>> {
+        let (send, recv) = oneshot::channel();
+
+        self.swarm_sender
+            .as_mut()
+            .unwrap()
+            .send(
+                commands::GetLatencies {
+                    response_sender: send,
+                }
+                .into(),
+            )
             .await?;
 
-        let resp = resp_recv.await?;
-        let (providers, stats) = resp;
-        if providers.is_empty() {
-            return Err(anyhow!("Could not find provider for file {obj_id_str}.").into());
+        Ok(recv.await?)
+    }
+
+    #[message]
+    pub async fn disconnect_peer(&mut self, peer_id: PeerId) -> Result<()> {
+        let (send, recv) = oneshot::channel();
+
+        self.swarm_sender
+            .as_mut()
+            .unwrap()
+            .send(
+                commands::DisconnectPeer {
+                    peer_id,
+                    response_sender: send,
+                }
+                .into(),
+            )
+            .await?;
+
+        recv.await?
+    }
+
+    #[message]
+    pub async fn set_peer_blocked(&mut self, peer_id: PeerId, blocked: bool) -> Result<()> {
+        match blocked {
+            true => self.config.block_peer(peer_id),
+            false => self.config.unblock_peer(&peer_id),
         }
-        debug!(
-            node = self.name,
-            obj_id = obj_id_str,
-            "Found providers: {providers:?}"
-        );
-        let providers: Vec<PeerId> = providers
-            .into_iter()
-            .collect::<HashSet<_>>()
-            .into_iter()
-            .collect();
-        for peer in &providers {
-            debug!(
-                node = self.name,
-                peer_id = peer.to_base58(),
-                obj_id = obj_id_str,
-                "Trying to download from peer"
-            );
 
-            let (obj_sender, obj_receiver) = oneshot::channel();
-            let result = self
-                .swarm_sender
-                .as_mut()
-                .unwrap()
-                .send(SwarmRunnerMessage::GetObject {
-                    obj_id: obj_id.clone(),
-                    peer_id: peer.clone(),
-                    response_sender: obj_sender,
-                });
+        self.swarm_sender
+            .as_mut()
+            .unwrap()
+            .send(commands::SetPeerBlocked { peer_id, blocked }.into())
+            .await?;
 
-            if let Err(e) = result.await {
-                error!(
-                    node = self.name,
-                    err = e.to_string(),
-                    "Failed to send download file message"
-                );
-                continue;
+        Ok(())
+    }
+
+    /// Applies the changed bootstrap nodes and listen addresses to the running swarm.
+    /// The other settings are saved, but take effect after a restart. A section which
+    /// fails doesn't stop the others, its error is in the summary
+    #[message]
+    pub async fn reload_config(&mut self, config: NodeConfig) -> Result<ConfigReloadSummary> {
+        let mut summary = match self.reload_swarm_config(&config).await {
+            Ok(summary) => summary,
+            Err(e) => ConfigReloadSummary {
+                errors: vec![format!("swarm: {e}")],
+                ..Default::default()
+            },
+        };
+
+        let watch_changed = self.config.watch_dirs != config.watch_dirs
+            || self.config.watch_debounce_ms != config.watch_debounce_ms;
+        if let Some(scheduler_ref) = &self.scheduler_ref {
+            let result = scheduler_ref
+                .ask(SetIntervals {
+                    config: config.clone(),
+                })
+                .send()
+                .await;
+            if let Err(e) = result {
+                summary.errors.push(format!("scheduler: {e}"));
             }
+        }
+        if self.config.text_index != config.text_index {
+            let result = self
+                .vault_ref
+                .ask(SetTextIndex {
+                    config: config.text_index,
+                })
+                .send()
+                .await;
+            if let Err(e) = result {
+                summary.errors.push(format!("text index: {e}"));
+            }
+        }
+        self.config = config;
+        if watch_changed {
+            self.start_watcher();
+        }
 
-            match obj_receiver.await {
-                Err(e) => {
-                    debug!(
-                        node = self.name,
-                        from = format!("{peer}"),
-                        err = e.to_string(),
-                        "Failed to download file"
-                    );
-                    continue;
+        debug!(node = self.name, "Config reloaded: {summary:?}");
+        Ok(summary)
+    }
+
+    async fn reload_swarm_config(&mut self, config: &NodeConfig) -> Result<ConfigReloadSummary> {
+        let (send, recv) = oneshot::channel();
+        self.swarm_sender
+            .as_mut()
+            .unwrap()
+            .send(
+                commands::ReloadConfig {
+                    config: config.clone(),
+                    response_sender: send,
                 }
-                Ok(Err(e)) => {
-                    debug!(
-                        node = self.name,
-                        from = format!("{peer}"),
-                        err = e.to_string(),
-                        "Failed to download file"
-                    );
-                    continue;
+                .into(),
+            )
+            .await?;
+
+        recv.await?
+    }
+
+    /// Publishes the profile in the DHT and keeps it in the config, so it is
+    /// published again when the node starts
+    #[message]
+    pub async fn set_profile(&mut self, profile: NodeProfile) -> Result<()> {
+        let (send, recv) = oneshot::channel();
+
+        self.swarm_sender
+            .as_mut()
+            .unwrap()
+            .send(
+                commands::PublishProfile {
+                    profile: profile.clone(),
+                    response_sender: send,
                 }
+                .into(),
+            )
+            .await?;
 
-                Ok(Ok(obj)) => {
-                    let calculated_obj_id = proto::Hash::try_from(&obj)?;
-                    if obj_id != calculated_obj_id {
-                        debug!(
-                            node = self.name,
-                            from = format!("{peer}"),
-                            data = format!("{:?}", &obj.data),
-                            "Received wrong file! {} != {obj_id_str}",
-                            calculated_obj_id.to_string()
-                        );
-                        continue;
-                    }
+        recv.await??;
+        self.config.profile = Some(profile);
 
-                    let mut typed = Some(obj);
-                    while let Some(obj) = typed.clone() {
-                        typed = match parser::parse_typed(obj).await {
-                            Ok(parser::ObjectEnum::Signed(signed)) => Some(signed.object),
-                            Ok(parser::ObjectEnum::PlainFile(file)) => return Ok((file, stats)),
-                            Err(e) => {
-                                debug!("{e}");
-                                continue;
-                            }
-                            Ok(_) => {
-                                debug!("Received object was not a file!");
-                                continue;
-                            }
-                        }
-                    }
+        Ok(())
+    }
+
+    #[message]
+    pub async fn get_peer_profile(
+        &mut self,
+        peer_id: PeerId,
+    ) -> Result<Option<proto::ProfileObject>> {
+        let (send, recv) = oneshot::channel();
+
+        self.swarm_sender
+            .as_mut()
+            .unwrap()
+            .send(
+                commands::GetProfile {
+                    peer_id,
+                    response_sender: send,
                 }
-            }
+                .into(),
+            )
+            .await?;
+
+        recv.await?
+    }
+
+    /// Sends the message to the peer. If the peer can't be reached, the message is
+    /// left for it at its closest peers
+    #[message]
+    pub async fn send_direct_message(
+        &mut self,
+        peer_id: PeerId,
+        content: MessageContent,
+        encrypt: bool,
+    ) -> Result<Delivery> {
+        self.mailbox().send(peer_id, content, encrypt).await
+    }
+
+    /// Creates a group owned by the node and subscribes to its feed. Returns the
+    /// ID of the group
+    #[message]
+    pub async fn create_group(&mut self, name: String) -> Result<String> {
+        let group = UserGroup::create(name, &self.keypair)?;
+        let id = group.definition.id.to_string();
+        self.join(GroupMembership { group, token: None }).await?;
+
+        Ok(id)
+    }
+
+    /// Issues the access token for the peer and returns the invitation with it.
+    /// Only the groups owned by the node can be shared
+    #[message]
+    pub async fn invite_to_group(
+        &mut self,
+        group_id: String,
+        peer_id: PeerId,
+        valid_for: Duration,
+    ) -> Result<String> {
+        let membership = self.group_membership(&group_id).await?;
+        if membership.token.is_some() {
+            return Err(anyhow!("Only the owner of the group can invite"));
         }
 
-        Err(anyhow!("Could not download file"))
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?;
+        let token = GroupAccessToken::issue(
+            &membership.group.definition.id,
+            &peer_id,
+            (now + valid_for).as_secs(),
+            &self.keypair,
+        )?;
+        GroupInvitation {
+            group: membership.group,
+            token,
+        }
+        .encode()
     }
 
+    /// Joins the group using the invitation issued for this node. Returns the ID
+    /// of the group
     #[message]
-    pub fn get_peer_id(&mut self) -> Result<PeerId> {
-        Ok(PeerId::from(self.keypair.public()))
+    pub async fn join_group(&mut self, invitation: String) -> Result<String> {
+        let invitation =
+            GroupInvitation::decode(&invitation).map_err(|e| anyhow!("Invalid invitation: {e}"))?;
+        invitation.group.verify()?;
+        let own_id = self.keypair.public().to_peer_id();
+        invitation.token.verify(&invitation.group, &own_id)?;
+
+        let id = invitation.group.definition.id.to_string();
+        self.join(GroupMembership {
+            group: invitation.group,
+            token: Some(invitation.token),
+        })
+        .await?;
+
+        Ok(id)
     }
 
+    /// Signs the post and publishes it in the feed of the group. Returns the ID
+    /// of the post
     #[message]
-    pub async fn get_addresses(&mut self) -> Result<Vec<Multiaddr>> {
+    pub async fn post_to_group(
+        &mut self,
+        group_id: String,
+        content: MessageContent,
+    ) -> Result<String> {
+        let membership = self.group_membership(&group_id).await?;
+        let post = GroupObject::post(
+            &membership.group.definition.id,
+            content,
+            membership.token,
+            &self.keypair,
+        )?;
         let (send, recv) = oneshot::channel();
 
         self.swarm_sender
             .as_mut()
             .unwrap()
-            .send(SwarmRunnerMessage::GetAddresses {
-                response_sender: send,
-            })
+            .send(
+                commands::PublishToGroup {
+                    post,
+                    response_sender: send,
+                }
+                .into(),
+            )
+            .await?;
+
+        Ok(recv.await??.to_string())
+    }
+
+    /// Sends the query to the closest peers and merges their answers
+    #[message]
+    pub async fn query(&mut self, query: TypedObject) -> Result<QueryResults> {
+        let results = self
+            .query_coordinator()
+            .query(query, QUERY_PARALLELISM)
             .await?;
+        debug!(
+            node = self.name,
+            matching = results.ids.len(),
+            peers_answered = results.peers_answered,
+            "Query finished"
+        );
 
-        let addrs = recv.await??;
-        Ok(addrs)
+        Ok(results)
     }
 
+    /// Dials the peer at the address. Without the peer ID the one at the end of the
+    /// address is used, or the one of the peer the node connected to is returned
     #[message]
-    pub async fn dial_peer(&mut self, peer_id: String, peer_addr: String) -> Result<()> {
+    pub async fn dial_peer(
+        &mut self,
+        peer_id: Option<String>,
+        peer_addr: String,
+    ) -> Result<PeerId> {
         let (send, recv) = oneshot::channel();
-        let peer_id = PeerId::from_str(&peer_id)?;
+        let peer_id = peer_id.map(|id| PeerId::from_str(&id)).transpose()?;
         let peer_addr = peer_addr.parse::<Multiaddr>()?;
 
         self.swarm_sender
             .as_mut()
             .unwrap()
-            .send(SwarmRunnerMessage::Dial {
-                peer_id,
-                peer_addr,
-                response_sender: send,
-            })
+            .send(
+                commands::Dial {
+                    peer_id,
+                    peer_addr,
+                    response_sender: send,
+                }
+                .into(),
+            )
             .await?;
         return match tokio::time::timeout(DIAL_TIMEOUT, recv).await {
             Ok(o) => o?.map_err(|e| e.into()),
@@ -313,80 +773,115 @@ impl Node {
         };
     }
 
+    /// Publishes the file opened from the path, only the peers allowed by the access
+    /// get it from the peers storing it. With `previous`, the file is a new revision
+    /// of that object
     #[message]
-    pub async fn publish_file(&mut self, path: PathBuf) -> Result<String> {
-        // The file has to be read to the memory to be published. There is no other way without
-        // a new behaviour kademlia could talk to, which would provide streams of data.
-        // (Maybe could be implemented on the existing request_response if it would be generalised more?)
-        let object: TypedObject = PlainFileObject::try_from_path(&path).await?.into();
-        let object: TypedObject = SignedObject::sign_ed25519(object, self.keypair.clone())
-            .unwrap()
-            .into();
-        let obj_id = proto::Hash::try_from(&object)?;
-        let obj_id_str = bs58::encode(&obj_id.bytes).into_string();
+    pub async fn publish_file(
+        &mut self,
+        path: PathBuf,
+        file: File,
+        access: ObjectAccess,
+        previous: Option<String>,
+    ) -> Result<String> {
+        let publish = async {
+            let policy = self.access_policy(access).await?;
+            let previous = previous.as_deref().map(proto::Hash::try_from).transpose()?;
+            self.publisher()
+                .publish_file(&path, file, policy, previous)
+                .await
+        };
+        let result = publish.await;
+        self.record_publish(&path, result.as_ref().map_err(|e| e.to_string()));
+        result
+    }
 
-        let (resp_send, resp_recv) = oneshot::channel();
-        self.swarm_sender
-            .as_mut()
-            .unwrap()
-            .send(SwarmRunnerMessage::GetClosestPeers {
-                obj_id: obj_id.clone(),
-                response_sender: resp_send,
-            })
+    /// Publishes the tags of the object, for the peers to find it by searching
+    #[message]
+    pub async fn publish_tags(&mut self, obj_id_str: String, tags: Vec<String>) -> Result<String> {
+        let obj_id = proto::Hash::try_from(obj_id_str.as_str())?;
+        self.publisher().publish_tags(obj_id, tags).await
+    }
+
+    /// Searches the objects by their tags in the index of the node, and the peers
+    /// too with `network`
+    #[message]
+    pub async fn search_objects(
+        &mut self,
+        query: String,
+        network: bool,
+    ) -> Result<Vec<SearchResult>> {
+        let coordinator = match network {
+            true => Some(self.query_coordinator()),
+            false => None,
+        };
+        search::search(&self.vault_ref, coordinator.as_ref(), &query).await
+    }
+
+    /// The revisions of the object known to the node, the latest one first
+    #[message]
+    pub async fn get_history(&self, obj_id_str: String) -> Result<Vec<String>> {
+        let obj_id = proto::Hash::try_from(obj_id_str.as_str())?;
+        let history = self
+            .vault_ref
+            .ask(LoadHistory { hash: obj_id })
+            .send()
             .await?;
 
-        let peers = resp_recv.await?;
-        if peers.is_empty() {
-            return Err(anyhow!("Could not find provider for file {obj_id_str}.").into());
-        }
-        debug!(
-            node = self.name,
-            "Found {} closest nodes for publishing",
-            peers.len()
-        );
-        let peers: Vec<PeerId> = peers
-            .into_iter()
-            .collect::<HashSet<_>>()
-            .into_iter()
-            .collect();
-        let kad_k_parameter: i32 = 20;
-        let mut successes = 0;
-        for peer in &peers {
-            let (send, recv) = oneshot::channel();
-            self.swarm_sender
-                .as_mut()
-                .unwrap()
-                .send(SwarmRunnerMessage::SendObject {
-                    object: object.clone(),
-                    obj_id: obj_id.clone(),
-                    peer_id: peer.clone(),
-                    response_sender: send,
-                })
-                .await?;
+        Ok(history.iter().map(|hash| hash.to_string()).collect())
+    }
 
-            if let Ok(obj) = recv.await {
-                match obj {
-                    Ok(ResultObject { result: Ok(_) }) => {
-                        successes += 1;
-                        if successes >= kad_k_parameter {
-                            break;
-                        }
-                    }
-                    _ => {
-                        continue;
-                    }
+    /// Searches the text of the plain text files stored by the node
+    #[message]
+    pub async fn search_text(&self, query: String, limit: Option<usize>) -> Result<Vec<TextMatch>> {
+        Ok(self
+            .vault_ref
+            .ask(SearchText {
+                query,
+                limit: limit.unwrap_or(TEXT_SEARCH_LIMIT),
+            })
+            .send()
+            .await?)
+    }
+
+    /// Publishes many files opened from their paths, at most `max_concurrency` at the
+    /// same time. A failure of one file does not stop publishing the others
+    #[message]
+    pub async fn publish_files(
+        &mut self,
+        files: Vec<(PathBuf, File)>,
+        max_concurrency: usize,
+    ) -> Vec<PublishFileResult> {
+        let publisher = self.publisher();
+
+        let results: Vec<PublishFileResult> = stream::iter(files)
+            .map(|(path, file)| {
+                let publisher = publisher.clone();
+                async move {
+                    let result = publisher
+                        .publish_file(&path, file, AccessPolicy::Public, None)
+                        .await
+                        .map_err(|e| e.to_string());
+                    PublishFileResult { path, result }
                 }
-            }
-        }
-        if successes >= 1 {
-            debug!(
-                node = self.name,
-                obj_id = obj_id_str,
-                "Published object to {successes} other nodes"
-            );
-            return Ok(obj_id_str);
+            })
+            .buffer_unordered(max_concurrency.max(1))
+            .collect()
+            .await;
+
+        for result in &results {
+            self.record_publish(&result.path, result.result.as_ref());
         }
-        Err(anyhow!("Could not publish file"))
+        results
+    }
+
+    /// Gets the events of the node newer than the event with the `since` ID
+    #[message]
+    pub async fn get_events(&mut self, since: Option<u64>) -> Vec<NodeEvent> {
+        self.events
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .since(since)
     }
 
     #[message]
@@ -399,54 +894,243 @@ impl Node {
             .swarm_sender
             .as_mut()
             .unwrap()
-            .send(SwarmRunnerMessage::ProvideObject {
-                object,
-                obj_id: obj_id,
-                response_sender: resp_send,
-            })
+            .send(
+                commands::ProvideObject {
+                    object,
+                    obj_id: obj_id,
+                    response_sender: resp_send,
+                }
+                .into(),
+            )
             .await?;
 
         Ok(obj_id_str)
     }
 
+    /// Message called on the node from the daemon to stop providing an object.
+    /// Other nodes storing the object are not affected
+    #[message]
+    pub async fn stop_providing(&mut self, obj_id_str: String, keep_in_vault: bool) -> Result<()> {
+        let obj_id = proto::Hash::try_from(obj_id_str.as_str())?;
+        let (send, recv) = oneshot::channel();
+
+        self.swarm_sender
+            .as_mut()
+            .unwrap()
+            .send(
+                commands::StopProviding {
+                    obj_id,
+                    keep_in_vault,
+                    response_sender: send,
+                }
+                .into(),
+            )
+            .await?;
+
+        recv.await?
+    }
+
     #[message]
     pub async fn get_published_objects(&mut self) -> Result<Vec<TypedObjectInfo>> {
         Ok(self.vault_ref.ask(ListTypedObjects).send().await?)
     }
 
+    /// The objects most often fetched from this node by other peers
     #[message]
-    pub async fn delete_object(&mut self, obj_id_str: String) -> Result<DaemonResponse> {
+    pub async fn get_popular_objects(&self, limit: Option<usize>) -> Result<Vec<ObjectPopularity>> {
+        Ok(self
+            .vault_ref
+            .ask(LoadPopularObjects { limit })
+            .send()
+            .await?)
+    }
+
+    #[message]
+    pub fn get_vault_ref(&self) -> ActorRef<Vault> {
+        self.vault_ref.clone()
+    }
+
+    /// Describes the object stored or published by the node, with where it came
+    /// from. The object missing in the vault is downloaded to be described, but it's
+    /// not stored. The signer is looked for among the keys of this node and of the
+    /// peer the object came from
+    #[message]
+    pub async fn get_object_info(&mut self, obj_id_str: String) -> Result<ObjectInfo> {
         let obj_id = proto::Hash::try_from(obj_id_str.as_str())?;
-        let (send, recv) = oneshot::channel();
 
-        error!("Get providers");
-        self.swarm_sender
-            .as_mut()
-            .unwrap()
-            .send(SwarmRunnerMessage::GetProviders {
-                obj_id: obj_id.clone(),
-                response_sender: send,
+        let published = self
+            .vault_ref
+            .ask(LoadPublishedObject {
+                hash: obj_id.clone(),
             })
+            .send()
             .await?;
+        let stored = match self
+            .vault_ref
+            .ask(LoadObject {
+                hash: obj_id.clone(),
+            })
+            .send()
+            .await?
+        {
+            Some(parser::ObjectEnum::Typed(object)) => Some(object),
+            _ => None,
+        };
+        let is_published = published.is_some();
+        let (object, provenance, source) = match stored.or(published) {
+            Some(object) => {
+                let provenance = self
+                    .vault_ref
+                    .ask(LoadProvenance {
+                        hash: obj_id.clone(),
+                    })
+                    .send()
+                    .await?;
+                let source = provenance
+                    .as_ref()
+                    .and_then(|p| p.source_peer.as_deref())
+                    .and_then(|peer| PeerId::from_str(peer).ok());
+                (Some(object), provenance, source)
+            }
+            None => (None, None, None),
+        };
+        let stored_locally = object.is_some();
+        let (object, source) = match object {
+            Some(object) => (object, source),
+            None => {
+                let (object, peer) = self.download_typed(&obj_id).await?;
+                (object, Some(peer))
+            }
+        };
 
-        let rec = recv.await;
-        if let Err(e) = rec {
-            debug!(
-                node = self.name,
-                err = format!("{e}"),
-                "Failed to get providers"
-            );
-            return Err(anyhow!("Failed to get providers").context(e));
+        let mut keys = self.own_keys();
+        keys.extend(source.as_ref().and_then(public_key_of));
+        let signer = outermost_signed(&object).and_then(|signed| {
+            keys.into_iter()
+                .find(|key| signed.verify_ed25519(key.clone()).unwrap_or(false))
+                .map(|key| key.to_peer_id().to_base58())
+        });
+        let layers = parser::describe(&object);
+        let published_at = provenance
+            .as_ref()
+            .filter(|p| p.kind == ProvenanceKind::Published)
+            .map(|p| p.received_at);
+        let created_at = layers.iter().find_map(|l| l.created_at).or(published_at);
+
+        Ok(ObjectInfo {
+            id: obj_id_str,
+            type_id: object.uuid,
+            size: object.data.len() as u64,
+            published: is_published,
+            provenance,
+            stored_locally,
+            layers,
+            signer,
+            created_at,
+        })
+    }
+
+    /// The object from the vault, or downloaded from the network without storing it
+    #[message]
+    pub async fn get_object(&mut self, obj_id_str: String) -> Result<TypedObject> {
+        let obj_id = proto::Hash::try_from(obj_id_str.as_str())?;
+        if let Some(object) = downloader::local_object(&self.vault_ref, &obj_id).await? {
+            return Ok(object);
         }
-        let rec = rec.unwrap();
-        error!("Fouind providers");
-        let _stats = rec.1;
-        let providers: Vec<PeerId> = rec
-            .0
-            .into_iter()
-            .collect::<HashSet<_>>()
-            .into_iter()
-            .collect();
+        Ok(self.download_typed(&obj_id).await?.0)
+    }
+
+    /// Downloads the object as published, with the peer it came from, without
+    /// storing it
+    async fn download_typed(&mut self, obj_id: &proto::Hash) -> Result<(TypedObject, PeerId)> {
+        let latencies = self.get_latencies().await.unwrap_or_default();
+        let downloader = self.downloader();
+        let parallelism = self.config.download_parallelism;
+        let (result, _) = retry::retry(&self.config.retry, || {
+            downloader.download_typed(obj_id, parallelism, None, &latencies)
+        })
+        .await;
+        let (object, peer, _) = result?;
+        Ok((object, peer))
+    }
+
+    /// Checks the locally stored object and its availability in the network.
+    /// Signatures are verified with the keys of this node and of the signer, if given,
+    /// including the keys they were rotated from or to
+    #[message]
+    pub async fn verify_object(
+        &mut self,
+        obj_id_str: String,
+        signer: Option<PeerId>,
+    ) -> Result<ObjectVerification> {
+        let obj_id = proto::Hash::try_from(obj_id_str.as_str())?;
+
+        let published = self
+            .vault_ref
+            .ask(LoadPublishedObject {
+                hash: obj_id.clone(),
+            })
+            .send()
+            .await?;
+        let stored = match self
+            .vault_ref
+            .ask(LoadObject {
+                hash: obj_id.clone(),
+            })
+            .send()
+            .await?
+        {
+            Some(parser::ObjectEnum::Typed(object)) => Some(object),
+            _ => None,
+        };
+
+        let mut keys = self.own_keys();
+        if let Some(signer) = signer {
+            let signer_key = public_key_of(&signer).ok_or(anyhow!(
+                "The key of the signer can't be extracted from its peer ID"
+            ))?;
+            keys.extend(self.rotated_keys(signer_key).await);
+        }
+
+        let mut verification = ObjectVerification {
+            id: obj_id_str.clone(),
+            stored_locally: stored.is_some(),
+            published: published.is_some(),
+            hash_matches: None,
+            type_id: None,
+            signatures: vec![],
+            providers: 0,
+        };
+
+        if let Some(object) = stored.or(published) {
+            verification.hash_matches = Some(proto::Hash::try_from(&object)? == obj_id);
+            verification.type_id = Some(object.uuid);
+            if let Ok(parser::ObjectEnum::Signed(signed)) = parser::parse_typed(object).await {
+                verification.signatures = signed.verify_chain(&keys)?;
+            }
+        }
+
+        verification.providers = match self.get_providers(obj_id_str).await {
+            Ok((providers, _)) => providers.len(),
+            Err(e) => {
+                warn!(
+                    node = self.name,
+                    err = e.to_string(),
+                    "Failed to get providers of verified object"
+                );
+                0
+            }
+        };
+
+        Ok(verification)
+    }
+
+    #[message]
+    pub async fn delete_object(&mut self, obj_id_str: String) -> Result<DaemonResponse> {
+        let obj_id = proto::Hash::try_from(obj_id_str.as_str())?;
+        let providers =
+            downloader::find_providers(self.swarm_sender.as_ref().unwrap(), &obj_id).await?;
+        let (providers, _stats) = downloader::collect_providers(providers).await;
         let mut deleted_count: u32 = 0;
         let mut failed_count: u32 = 0;
         let mut deleted_myself = false;
@@ -461,10 +1145,14 @@ impl Node {
                 self.swarm_sender
                     .as_mut()
                     .unwrap()
-                    .send(SwarmRunnerMessage::StopProviding {
-                        obj_id: obj_id.clone(),
-                        response_sender: send,
-                    })
+                    .send(
+                        commands::StopProviding {
+                            obj_id: obj_id.clone(),
+                            keep_in_vault: false,
+                            response_sender: send,
+                        }
+                        .into(),
+                    )
                     .await?;
                 let resp = recv.await;
                 if let Err(_) = resp {
@@ -480,11 +1168,14 @@ impl Node {
             self.swarm_sender
                 .as_mut()
                 .unwrap()
-                .send(SwarmRunnerMessage::DeleteObject {
-                    obj_id: obj_id.clone(),
-                    peer: peer.clone(),
-                    response_sender: send,
-                })
+                .send(
+                    commands::DeleteObject {
+                        obj_id: obj_id.clone(),
+                        peer: peer.clone(),
+                        response_sender: send,
+                    }
+                    .into(),
+                )
                 .await?;
             let rec = recv.await;
             match rec {
@@ -509,11 +1200,25 @@ impl Node {
                     }
                     Ok(r) => match r.result {
                         Ok(_) => deleted_count += 1,
-                        Err(_) => failed_count += 1,
+                        Err(code) => {
+                            debug!(
+                                node = self.name,
+                                code = code.to_string(),
+                                asked_node = peer.to_base58(),
+                                "Peer refused to delete the object"
+                            );
+                            failed_count += 1;
+                        }
                     },
                 },
             }
         }
+        // The deleted object must not be replicated anymore
+        self.vault_ref
+            .ask(DeletePublishedObject { hash: obj_id })
+            .send()
+            .await?;
+
         Ok(DaemonResponse::ObjectDeleted {
             deleted_myself,
             deleted_count,
@@ -532,13 +1237,243 @@ impl Node {
             swarm_runner::run_swarm(
                 self.self_actor_ref.as_mut().unwrap().clone(),
                 self.vault_ref.clone(),
+                self.events.clone(),
+                self.module_host.clone(),
             )
+            .instrument(logging::node_span(&self.name))
             .await,
         );
         debug!(name = self.name, "Node starts");
 
         Ok(())
     }
+
+    fn start_replicator(&mut self) {
+        let replicator = Replicator::new(
+            self.name.clone(),
+            self.publisher(),
+            self.config.replication.clone(),
+        );
+        self.replicator_ref = Some(kameo::spawn(replicator));
+    }
+
+    fn start_scheduler(&mut self) {
+        let context = TaskContext {
+            swarm_sender: self.swarm_sender.as_ref().unwrap().clone(),
+            replicator_ref: self.replicator_ref.as_ref().unwrap().clone(),
+            vault_ref: self.vault_ref.clone(),
+            mailbox: self.mailbox(),
+            cache: self.config.cache,
+        };
+        let scheduler = Scheduler::new(self.name.clone(), context, &self.config);
+        self.scheduler_ref = Some(kameo::spawn(scheduler));
+    }
+
+    /// Starts watching the directories from the config, replacing the old watcher
+    fn start_watcher(&mut self) {
+        self.watcher = None;
+        if self.config.watch_dirs.is_empty() {
+            return;
+        }
+
+        let result = watcher::start(
+            self.name.clone(),
+            self.config.watch_dirs.clone(),
+            self.owner,
+            Duration::from_millis(self.config.watch_debounce_ms),
+            self.self_actor_ref.as_ref().unwrap().downgrade(),
+        );
+        match result {
+            Ok(watcher) => self.watcher = Some(watcher),
+            Err(e) => error!(
+                node = self.name,
+                err = e.to_string(),
+                "Failed to start watching directories"
+            ),
+        }
+    }
+
+    fn record_event(&self, kind: NodeEventKind, message: String) {
+        events::record(&self.events, kind, message);
+    }
+
+    fn record_publish<E: fmt::Display>(&self, path: &Path, result: Result<&String, E>) {
+        match result {
+            Ok(id) => self.record_event(
+                NodeEventKind::Publish,
+                format!("Published {} as {id}", path.display()),
+            ),
+            Err(e) => self.record_event(
+                NodeEventKind::Error,
+                format!("Failed to publish {}: {e}", path.display()),
+            ),
+        }
+    }
+
+    fn downloader(&self) -> Downloader {
+        Downloader {
+            name: self.name.clone(),
+            swarm_sender: self.swarm_sender.as_ref().unwrap().clone(),
+            vault_ref: self.vault_ref.clone(),
+        }
+    }
+
+    fn query_coordinator(&self) -> QueryCoordinator {
+        QueryCoordinator {
+            name: self.name.clone(),
+            swarm_sender: self.swarm_sender.as_ref().unwrap().clone(),
+            module_host: self.module_host.clone(),
+        }
+    }
+
+    fn mailbox(&self) -> Mailbox {
+        Mailbox {
+            name: self.name.clone(),
+            keypair: self.keypair.clone(),
+            swarm_sender: self.swarm_sender.as_ref().unwrap().clone(),
+            vault_ref: self.vault_ref.clone(),
+            events: self.events.clone(),
+        }
+    }
+
+    fn publisher(&self) -> Publisher {
+        Publisher {
+            name: self.name.clone(),
+            keypair: self.keypair.clone(),
+            swarm_sender: self.swarm_sender.as_ref().unwrap().clone(),
+            vault_ref: self.vault_ref.clone(),
+            chunking: self.config.chunking,
+        }
+    }
+
+    /// The current key of the node and the keys it was rotated from
+    fn own_keys(&self) -> Vec<PublicKey> {
+        let mut keys = vec![self.keypair.public()];
+        for rotation in &self.config.key_rotations {
+            if let Ok((old_key, _)) = rotation.verify() {
+                keys.push(old_key);
+            }
+        }
+        keys
+    }
+
+    /// The key followed by the keys it was rotated to, found in the DHT
+    async fn rotated_keys(&self, key: PublicKey) -> Vec<PublicKey> {
+        let mut rotations = Vec::new();
+        let mut peer_id = key.to_peer_id();
+        while rotations.len() < MAX_KEY_ROTATIONS {
+            let (send, recv) = oneshot::channel();
+            let sent = self
+                .swarm_sender
+                .as_ref()
+                .unwrap()
+                .send(
+                    commands::GetRotation {
+                        peer_id,
+                        response_sender: send,
+                    }
+                    .into(),
+                )
+                .await;
+            if sent.is_err() {
+                break;
+            }
+
+            let rotation = match recv.await {
+                Ok(Ok(Some(rotation))) => rotation,
+                Ok(Err(e)) => {
+                    warn!(
+                        node = self.name,
+                        err = e.to_string(),
+                        "Failed to find key rotation"
+                    );
+                    break;
+                }
+                _ => break,
+            };
+            let Ok((_, new_key)) = rotation.verify() else {
+                break;
+            };
+            peer_id = new_key.to_peer_id();
+            rotations.push(rotation);
+        }
+
+        proto::rotated_keys(&key, &rotations)
+    }
+
+    /// Stores the group in the vault and subscribes to its feed
+    async fn join(&mut self, membership: GroupMembership) -> Result<()> {
+        let group = membership.group.clone();
+        self.vault_ref.ask(StoreGroup { membership }).send().await?;
+        let (send, recv) = oneshot::channel();
+
+        self.swarm_sender
+            .as_mut()
+            .unwrap()
+            .send(
+                commands::SubscribeGroup {
+                    group,
+                    response_sender: send,
+                }
+                .into(),
+            )
+            .await?;
+
+        recv.await?
+    }
+
+    /// Resolves the access to the policy sent with the object. The contacts are
+    /// listed at the time of publishing, the contacts added later can't get the object.
+    /// The contacts of unknown trust don't get it either
+    async fn access_policy(&self, access: ObjectAccess) -> Result<AccessPolicy> {
+        Ok(match access {
+            ObjectAccess::Public => AccessPolicy::Public,
+            ObjectAccess::Contacts => {
+                let contacts = self.vault_ref.ask(LoadContacts).send().await?;
+                let users = contacts
+                    .iter()
+                    .filter(|c| c.trust != TrustLevel::Unknown)
+                    .map(|c| Ok(proto::user_id(&PeerId::from_str(&c.peer_id)?)))
+                    .collect::<Result<_>>()?;
+                AccessPolicy::Users(users)
+            }
+            ObjectAccess::Group(group_id) => {
+                AccessPolicy::Group(self.group_membership(&group_id).await?.group)
+            }
+        })
+    }
+
+    async fn group_membership(&self, group_id: &str) -> Result<GroupMembership> {
+        self.vault_ref
+            .ask(LoadGroups)
+            .send()
+            .await?
+            .into_iter()
+            .find(|m| m.id() == group_id)
+            .ok_or(anyhow!("The node is not a member of the group {group_id}"))
+    }
+}
+
+/// The outermost signed object, also the one wrapped in a group object
+fn outermost_signed(object: &TypedObject) -> Option<SignedObject> {
+    match object.uuid {
+        SignedObject::UUID => TypedObject::try_from_typed(object).ok(),
+        GroupObject::UUID => TypedObject::try_from_typed::<GroupObject>(object)
+            .ok()
+            .map(|group| group.object),
+        _ => None,
+    }
+}
+
+/// Ed25519 public keys are short enough to be inlined in the peer ID
+/// using the identity hash, other keys can't be extracted
+fn public_key_of(peer_id: &PeerId) -> Option<PublicKey> {
+    let multihash: &Multihash<64> = peer_id.as_ref();
+    if multihash.code() != 0 {
+        return None;
+    }
+
+    PublicKey::try_decode_protobuf(multihash.digest()).ok()
 }
 
 impl fmt::Debug for Node {
@@ -564,12 +1499,41 @@ impl Message<GetSnapshot> for Node {
     }
 }
 
+/// Waits for the download started with `DownloadFile`
+pub async fn finish_download(download: PendingDownload) -> DownloadResult {
+    download.await.unwrap_or_else(|e| Err(Arc::new(e.into())))
+}
+
+/// Records the outcome of the download in the events of the node
+fn record_download(
+    events: &SharedEventLog,
+    obj_id_str: &str,
+    result: &Result<(PlainFileObject, Option<DaemonQueryStats>)>,
+) {
+    match result {
+        Ok((file, _)) => events::record(
+            events,
+            NodeEventKind::Download,
+            format!("Downloaded {} ({obj_id_str})", file.name),
+        ),
+        Err(e) => events::record(
+            events,
+            NodeEventKind::Error,
+            format!("Failed to download {obj_id_str}: {e}"),
+        ),
+    }
+}
+
 pub struct NodeBuilder {
     name: Option<String>,
     keypair: Option<Keypair>,
     config: Option<NodeConfig>,
+    webrtc_certificate: Option<String>,
+    owner: Option<u32>,
     manager_ref: Option<ActorRef<NodeManager>>,
     vault_ref: Option<ActorRef<Vault>>,
+    module_host: Option<ModuleHost>,
+    events: Option<SharedEventLog>,
     self_actor_ref: Option<ActorRef<Node>>,
     swarm_sender: Option<Sender<SwarmRunnerMessage>>,
 }
@@ -580,8 +1544,12 @@ impl Default for NodeBuilder {
             name: None,
             keypair: None,
             config: None,
+            webrtc_certificate: None,
+            owner: None,
             manager_ref: None,
             vault_ref: None,
+            module_host: None,
+            events: None,
             self_actor_ref: None,
             swarm_sender: None,
         }
@@ -604,6 +1572,16 @@ impl NodeBuilder {
         self
     }
 
+    pub fn webrtc_certificate(mut self, webrtc_certificate: Option<String>) -> Self {
+        self.webrtc_certificate = webrtc_certificate;
+        self
+    }
+
+    pub fn owner(mut self, owner: Option<u32>) -> Self {
+        self.owner = owner;
+        self
+    }
+
     pub fn manager_ref(mut self, manager_ref: ActorRef<NodeManager>) -> Self {
         self.manager_ref = Some(manager_ref);
         self
@@ -614,10 +1592,24 @@ impl NodeBuilder {
         self
     }
 
+    /// Without it the node handles only the object types known to the daemon
+    pub fn module_host(mut self, module_host: ModuleHost) -> Self {
+        self.module_host = Some(module_host);
+        self
+    }
+
+    /// Without it the node starts with an empty event log
+    pub fn events(mut self, events: SharedEventLog) -> Self {
+        self.events = Some(events);
+        self
+    }
+
     pub fn from_snapshot(mut self, snapshot: &NodeSnapshot) -> Self {
         self.name = Some(snapshot.name.clone());
         self.keypair = Some(snapshot.keypair.clone());
         self.config = Some(snapshot.config.clone());
+        self.webrtc_certificate = snapshot.webrtc_certificate.clone();
+        self.owner = snapshot.owner;
         self
     }
 
@@ -626,12 +1618,21 @@ impl NodeBuilder {
             name: self.name.ok_or(anyhow!("node name is required"))?,
             keypair: self.keypair.ok_or(anyhow!("keypair is required"))?,
             config: self.config.ok_or(anyhow!("config is required"))?,
+            webrtc_certificate: self.webrtc_certificate,
+            owner: self.owner,
             manager_ref: self
                 .manager_ref
                 .ok_or(anyhow!("node manager ref is required"))?,
             vault_ref: self.vault_ref.ok_or(anyhow!("vault ref is required"))?,
+            module_host: self.module_host.unwrap_or_default(),
             self_actor_ref: self.self_actor_ref,
             swarm_sender: self.swarm_sender,
+            replicator_ref: None,
+            scheduler_ref: None,
+            gateway: None,
+            watcher: None,
+            events: self.events.unwrap_or_else(EventLog::new_shared),
+            downloads: PendingInterests::default(),
         };
 
         Ok(node)
@@ -642,6 +1643,8 @@ impl NodeBuilder {
             name: self.name.ok_or(anyhow!("node name is required"))?,
             keypair: self.keypair.ok_or(anyhow!("keypair is required"))?,
             config: self.config.unwrap_or(NodeConfig::default()),
+            webrtc_certificate: self.webrtc_certificate,
+            owner: self.owner,
         };
 
         Ok(snapshot)
@@ -652,6 +1655,8 @@ pub struct NodeSnapshot {
     pub name: String,
     pub keypair: Keypair,
     pub config: NodeConfig,
+    pub webrtc_certificate: Option<String>,
+    pub owner: Option<u32>,
 }
 
 impl NodeSnapshot {
@@ -666,6 +1671,8 @@ impl From<&Node> for NodeSnapshot {
             name: value.name.clone(),
             keypair: value.keypair.clone(),
             config: value.config.clone(),
+            webrtc_certificate: value.webrtc_certificate.clone(),
+            owner: value.owner,
         }
     }
 }
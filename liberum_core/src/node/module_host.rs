@@ -0,0 +1,259 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+use liberum_core::parser;
+use liberum_core::proto::TypedObject;
+use liberum_core::types::{ModuleCall, ModuleCallKind, ModuleInfo};
+use tokio::sync::{mpsc, oneshot};
+use tracing::debug;
+use uuid::Uuid;
+
+///! The module host lets external processes handle the object types unknown to the
+///! daemon. A process registers a module over the daemon socket, declaring the type
+///! UUIDs it handles. The objects of these types are forwarded to it as `ModuleCall`
+///! responses on the same connection and it answers with `ModuleCallResult` requests.
+///! The module is unregistered when its connection is closed.
+
+/// How long the daemon waits for an external module to answer a call
+pub const MODULE_CALL_TIMEOUT: Duration = Duration::from_secs(10);
+/// Number of calls waiting to be sent to a module, the next ones fail
+const MODULE_CALL_CAPACITY: usize = 16;
+
+pub type ModuleCallResult = std::result::Result<Option<TypedObject>, String>;
+
+/// The registry of the external modules, shared by the connections and the nodes
+#[derive(Clone, Default, Debug)]
+pub struct ModuleHost {
+    state: Arc<Mutex<HostState>>,
+}
+
+#[derive(Default, Debug)]
+struct HostState {
+    modules: HashMap<u64, ExternalModule>,
+    pending: HashMap<u64, PendingCall>,
+    next_module_id: u64,
+    next_call_id: u64,
+}
+
+#[derive(Debug)]
+struct ExternalModule {
+    name: String,
+    type_uuids: Vec<Uuid>,
+    calls: mpsc::Sender<ModuleCall>,
+}
+
+#[derive(Debug)]
+struct PendingCall {
+    module_id: u64,
+    sender: oneshot::Sender<ModuleCallResult>,
+}
+
+/// A registered module, unregistered when dropped
+pub struct ModuleRegistration {
+    host: ModuleHost,
+    module_id: u64,
+    calls: mpsc::Receiver<ModuleCall>,
+}
+
+impl ModuleHost {
+    /// Registers the module. The types must not be handled by the daemon itself
+    /// nor by another module
+    pub fn register(&self, name: String, type_uuids: Vec<Uuid>) -> Result<ModuleRegistration> {
+        if type_uuids.is_empty() {
+            bail!("module {name} does not handle any type");
+        }
+
+        let mut state = self.state.lock().unwrap();
+        for uuid in &type_uuids {
+            if parser::is_known_type(uuid) {
+                bail!("type {uuid} is handled by the daemon");
+            }
+            if let Some((_, module)) = state.module_for(uuid) {
+                bail!("type {uuid} is already handled by module {}", module.name);
+            }
+        }
+
+        let (calls, receiver) = mpsc::channel(MODULE_CALL_CAPACITY);
+        let module_id = state.next_module_id;
+        state.next_module_id += 1;
+        debug!(
+            module = name,
+            module_id = module_id,
+            types = format!("{type_uuids:?}"),
+            "External module registered"
+        );
+        state.modules.insert(
+            module_id,
+            ExternalModule {
+                name,
+                type_uuids,
+                calls,
+            },
+        );
+
+        Ok(ModuleRegistration {
+            host: self.clone(),
+            module_id,
+            calls: receiver,
+        })
+    }
+
+    /// Removes the module, its unanswered calls fail
+    fn unregister(&self, module_id: u64) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(module) = state.modules.remove(&module_id) {
+            debug!(module = module.name, "External module unregistered");
+        }
+        state.pending.retain(|_, call| call.module_id != module_id);
+    }
+
+    pub fn handles(&self, type_uuid: &Uuid) -> bool {
+        self.state.lock().unwrap().module_for(type_uuid).is_some()
+    }
+
+    pub fn list(&self) -> Vec<ModuleInfo> {
+        let state = self.state.lock().unwrap();
+        state
+            .modules
+            .values()
+            .flat_map(|module| {
+                module.type_uuids.iter().map(|uuid| ModuleInfo {
+                    name: module.name.clone(),
+                    uuid: *uuid,
+                    enabled: true,
+                    settings: None,
+                    external: true,
+                })
+            })
+            .collect()
+    }
+
+    /// Forwards the object to the module handling its type and waits for the answer
+    pub async fn call(
+        &self,
+        kind: ModuleCallKind,
+        node_name: &str,
+        object: TypedObject,
+    ) -> Result<Option<TypedObject>> {
+        let (call_id, calls, receiver) = {
+            let mut state = self.state.lock().unwrap();
+            let (module_id, module) = state
+                .module_for(&object.uuid)
+                .ok_or_else(|| anyhow!("no module handles type {}", object.uuid))?;
+            let calls = module.calls.clone();
+
+            let call_id = state.next_call_id;
+            state.next_call_id += 1;
+            let (sender, receiver) = oneshot::channel();
+            state
+                .pending
+                .insert(call_id, PendingCall { module_id, sender });
+            (call_id, calls, receiver)
+        };
+
+        let call = ModuleCall {
+            call_id,
+            kind,
+            node_name: node_name.to_string(),
+            object,
+        };
+        if let Err(e) = calls.try_send(call) {
+            self.state.lock().unwrap().pending.remove(&call_id);
+            bail!("module is not accepting calls: {e}");
+        }
+
+        let result = match tokio::time::timeout(MODULE_CALL_TIMEOUT, receiver).await {
+            Ok(result) => result?,
+            Err(e) => {
+                self.state.lock().unwrap().pending.remove(&call_id);
+                return Err(e.into());
+            }
+        };
+
+        result.map_err(|e| anyhow!("module failed to handle the object: {e}"))
+    }
+
+    /// Passes the answer of the module to the waiting call
+    fn respond(&self, module_id: u64, call_id: u64, result: ModuleCallResult) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        match state.pending.get(&call_id) {
+            Some(call) if call.module_id == module_id => {}
+            _ => bail!("no pending call {call_id} of the module"),
+        }
+
+        let call = state
+            .pending
+            .remove(&call_id)
+            .expect("To be present, as it was checked earlier");
+        // The caller may have given up already
+        let _ = call.sender.send(result);
+
+        Ok(())
+    }
+}
+
+impl HostState {
+    fn module_for(&self, type_uuid: &Uuid) -> Option<(u64, &ExternalModule)> {
+        self.modules
+            .iter()
+            .find(|(_, module)| module.type_uuids.contains(type_uuid))
+            .map(|(id, module)| (*id, module))
+    }
+}
+
+impl ModuleRegistration {
+    /// Waits for the next call to the module
+    pub async fn next_call(&mut self) -> Option<ModuleCall> {
+        self.calls.recv().await
+    }
+
+    /// Answers the call. A module may answer only its own calls
+    pub fn respond(&self, call_id: u64, result: ModuleCallResult) -> Result<()> {
+        self.host.respond(self.module_id, call_id, result)
+    }
+}
+
+impl Drop for ModuleRegistration {
+    fn drop(&mut self) {
+        self.host.unregister(self.module_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use liberum_core::proto::PlainFileObject;
+
+    #[tokio::test]
+    async fn module_call_test() {
+        let host = ModuleHost::default();
+        let type_uuid = Uuid::new_v4();
+        assert!(host
+            .register("files".to_string(), vec![PlainFileObject::UUID])
+            .is_err());
+
+        let mut registration = host.register("test".to_string(), vec![type_uuid]).unwrap();
+        assert!(host.handles(&type_uuid));
+        assert!(host.register("other".to_string(), vec![type_uuid]).is_err());
+
+        let object = TypedObject {
+            uuid: type_uuid,
+            data: vec![1, 2, 3],
+        };
+        let module = async {
+            let call = registration.next_call().await.unwrap();
+            assert_eq!(call.kind, ModuleCallKind::Query);
+            assert!(registration.respond(call.call_id + 1, Ok(None)).is_err());
+            registration
+                .respond(call.call_id, Ok(Some(call.object)))
+                .unwrap();
+        };
+        let (result, _) = tokio::join!(host.call(ModuleCallKind::Query, "node", object), module);
+        assert_eq!(result.unwrap().unwrap().data, vec![1, 2, 3]);
+
+        drop(registration);
+        assert!(!host.handles(&type_uuid));
+    }
+}
@@ -0,0 +1,393 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Result};
+use futures::{stream, StreamExt, TryStreamExt};
+use kameo::{actor::ActorRef, request::MessageSend};
+use liberum_core::node_config::ChunkingConfig;
+use liberum_core::proto::{
+    self, AccessPolicy, ChunkObject, ErasureCoding, ManifestObject, PlainFileObject, ResultObject,
+    SignedObject, TagObject, TypedObject, VersionObject,
+};
+use liberum_core::{chunker, erasure};
+use libp2p::{identity::Keypair, PeerId};
+use tokio::sync::{mpsc, oneshot};
+use tracing::debug;
+
+use crate::swarm_runner::commands;
+use crate::swarm_runner::messages::SwarmRunnerMessage;
+use crate::vault::{
+    LoadPublishedObject, StoreAccessPolicy, StorePublishedObject, StoreTags, Vault,
+};
+
+///! The module contains the publishing logic of a node. It does not borrow the
+///! node, so many objects can be published at the same time.
+///!
+///! The tags of an object are sent to the closest peers of the hash of every tag,
+///! not of the ID of the tag object, as the searches for the tag ask those peers.
+///! The tags are not replicated like the published objects yet.
+///!
+///! A file published as a new revision of another object is followed by a version
+///! object linking the two, published like the file with the same access policy.
+///!
+///! The big files are published in chunks with a signed manifest listing them. The
+///! chunks the node already published, e.g. with the previous revision of the file,
+///! are not published again, so a new revision costs only the changed chunks.
+///!
+///! With the erasure coding in the config, the parity chunks of every group of the
+///! chunks are published too and listed in the manifest, so the file survives losing
+///! some of its chunks from the network.
+
+#[derive(Clone)]
+pub struct Publisher {
+    pub name: String,
+    pub keypair: Keypair,
+    pub swarm_sender: mpsc::Sender<SwarmRunnerMessage>,
+    pub vault_ref: ActorRef<Vault>,
+    pub chunking: ChunkingConfig,
+}
+
+/// The chunks of a file published at the same time
+const CHUNK_UPLOADS: usize = 4;
+
+impl Publisher {
+    /// Signs the file opened from the path and sends it with its access policy to the
+    /// closest peers of its ID, linked to the previous revision if given. Responds
+    /// with the ID of the published object
+    pub async fn publish_file(
+        &self,
+        path: &Path,
+        file: File,
+        policy: AccessPolicy,
+        previous: Option<proto::Hash>,
+    ) -> Result<String> {
+        // The file has to be read to the memory to be published. There is no other way without
+        // a new behaviour kademlia could talk to, which would provide streams of data.
+        // (Maybe could be implemented on the existing request_response if it would be generalised more?)
+        let file = PlainFileObject::try_from_file(path, file).await?;
+        let obj_id = match self.chunking {
+            ChunkingConfig {
+                enabled: true,
+                min_file_size,
+                ..
+            } if file.content.len() as u64 >= min_file_size => {
+                self.publish_chunked(file, &policy).await?
+            }
+            _ => {
+                let object: TypedObject =
+                    SignedObject::sign_ed25519(file.into(), self.keypair.clone())
+                        .unwrap()
+                        .into();
+                self.publish_object(object, &policy).await?
+            }
+        };
+        let obj_id_str = obj_id.to_string();
+
+        if let Some(previous) = previous {
+            let version: TypedObject = VersionObject {
+                previous,
+                object: obj_id,
+            }
+            .into();
+            let version = SignedObject::sign_ed25519(version, self.keypair.clone())?.into();
+            self.publish_object(version, &policy).await.map_err(|e| {
+                anyhow!(
+                    "Published {obj_id_str}, but could not link it to the previous revision: {e}"
+                )
+            })?;
+        }
+        Ok(obj_id_str)
+    }
+
+    /// Publishes the chunks of the file the node didn't publish yet and the signed
+    /// manifest of the file. Responds with the ID of the manifest
+    async fn publish_chunked(
+        &self,
+        file: PlainFileObject,
+        policy: &AccessPolicy,
+    ) -> Result<proto::Hash> {
+        let ranges = chunker::chunk_ranges(&file.content);
+        let chunks = ranges
+            .iter()
+            .map(|range| chunk_object(file.content[range.clone()].to_vec()))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut parity_chunks = Vec::new();
+        let coding = match self.chunking.erasure_coding {
+            Some(config) => {
+                let (data_shards, parity_shards) =
+                    (config.data_shards as usize, config.parity_shards as usize);
+                if !erasure::is_valid(data_shards, parity_shards) {
+                    bail!(
+                        "Can't erasure-code the chunks with {data_shards} data and {parity_shards} parity chunks"
+                    );
+                }
+                for group in ranges.chunks(data_shards) {
+                    let contents: Vec<&[u8]> = group
+                        .iter()
+                        .map(|range| &file.content[range.clone()])
+                        .collect();
+                    let parity = erasure::parity_shards(&contents, data_shards, parity_shards)?
+                        .into_iter()
+                        .map(chunk_object)
+                        .collect::<Result<Vec<_>>>()?;
+                    parity_chunks.push(parity);
+                }
+                Some(ErasureCoding {
+                    data_shards: config.data_shards,
+                    parity_shards: config.parity_shards,
+                    chunk_sizes: ranges.iter().map(|range| range.len() as u64).collect(),
+                    parity: parity_chunks
+                        .iter()
+                        .map(|group| group.iter().map(|(id, _)| id.clone()).collect())
+                        .collect(),
+                })
+            }
+            None => None,
+        };
+
+        let mut new_chunks = Vec::new();
+        let mut seen = HashSet::new();
+        let mut total = 0;
+        for (chunk_id, chunk) in chunks.iter().chain(parity_chunks.iter().flatten()) {
+            total += 1;
+            let published = self
+                .vault_ref
+                .ask(LoadPublishedObject {
+                    hash: chunk_id.clone(),
+                })
+                .send()
+                .await?
+                .is_some();
+            if !published && seen.insert(chunk_id.clone()) {
+                new_chunks.push(chunk.clone());
+            }
+        }
+        let chunk_ids: Vec<proto::Hash> = chunks.into_iter().map(|(id, _)| id).collect();
+
+        let new = new_chunks.len();
+        stream::iter(new_chunks)
+            .map(|chunk| self.publish_object(chunk, policy))
+            .buffer_unordered(CHUNK_UPLOADS)
+            .try_collect::<Vec<_>>()
+            .await?;
+        debug!(
+            node = self.name,
+            name = file.name,
+            "Published {new} new of {total} chunks"
+        );
+
+        let manifest: TypedObject = ManifestObject {
+            name: file.name,
+            size: file.content.len() as u64,
+            chunks: chunk_ids,
+            coding,
+        }
+        .into();
+        let manifest = SignedObject::sign_ed25519(manifest, self.keypair.clone())?.into();
+        self.publish_object(manifest, policy).await
+    }
+
+    /// Sends the signed object with its access policy to the closest peers of its
+    /// ID and remembers it for the replicator. Responds with the ID of the object
+    async fn publish_object(
+        &self,
+        object: TypedObject,
+        policy: &AccessPolicy,
+    ) -> Result<proto::Hash> {
+        let obj_id = proto::Hash::try_from(&object)?;
+
+        let kad_k_parameter = 20;
+        let successes = self
+            .send_object_to_closest_peers(
+                &object,
+                &obj_id,
+                policy,
+                &HashSet::new(),
+                kad_k_parameter,
+            )
+            .await?;
+        if successes >= 1 {
+            debug!(
+                node = self.name,
+                obj_id = obj_id.to_string(),
+                "Published object to {successes} other nodes"
+            );
+            // Remembered for the replicator to keep the object alive in the network
+            self.vault_ref
+                .ask(StorePublishedObject {
+                    hash: obj_id.clone(),
+                    object,
+                })
+                .send()
+                .await?;
+            self.vault_ref
+                .ask(StoreAccessPolicy {
+                    hash: obj_id.clone(),
+                    policy: policy.clone(),
+                })
+                .send()
+                .await?;
+            return Ok(obj_id);
+        }
+        Err(anyhow!("Could not publish object"))
+    }
+
+    /// Signs the tags of the object and sends them to the closest peers of every
+    /// tag. The tags are indexed by this node too. Responds with the ID of the tag
+    /// object
+    pub async fn publish_tags(&self, object: proto::Hash, tags: Vec<String>) -> Result<String> {
+        let mut tags: Vec<String> = tags.iter().map(|tag| TagObject::normalize(tag)).collect();
+        tags.sort();
+        tags.dedup();
+        let tag_object = TagObject { object, tags };
+        if !tag_object.is_valid() {
+            return Err(anyhow!(
+                "Between 1 and {} tags of at most {} characters are allowed",
+                TagObject::MAX_TAGS,
+                TagObject::MAX_TAG_LEN
+            ));
+        }
+        self.vault_ref
+            .ask(StoreTags {
+                hash: tag_object.object.clone(),
+                tags: tag_object.tags.clone(),
+            })
+            .send()
+            .await?;
+
+        let tags = tag_object.tags.clone();
+        let object: TypedObject =
+            SignedObject::sign_ed25519(tag_object.into(), self.keypair.clone())?.into();
+        let obj_id = proto::Hash::try_from(&object)?;
+
+        let kad_k_parameter = 20;
+        let mut published = 0;
+        for tag in &tags {
+            let result = self
+                .send_object_near(
+                    &object,
+                    &obj_id,
+                    &TagObject::tag_hash(tag),
+                    &AccessPolicy::Public,
+                    &HashSet::new(),
+                    kad_k_parameter,
+                )
+                .await;
+            match result {
+                Ok(successes) if successes > 0 => published += 1,
+                Ok(_) => debug!(node = self.name, tag = tag, "No peer indexed the tag"),
+                Err(e) => debug!(
+                    node = self.name,
+                    tag = tag,
+                    err = e.to_string(),
+                    "Failed to publish the tag"
+                ),
+            }
+        }
+        if published == 0 {
+            return Err(anyhow!("Could not publish tags"));
+        }
+
+        debug!(
+            node = self.name,
+            obj_id = obj_id.to_string(),
+            "Published {published} of {} tags",
+            tags.len()
+        );
+        Ok(obj_id.to_string())
+    }
+
+    /// Sends the object to the closest peers of its ID, skipping the given peers.
+    /// Stops after `limit` peers accepted the object. Returns the number of peers
+    /// that accepted it
+    pub async fn send_object_to_closest_peers(
+        &self,
+        object: &TypedObject,
+        obj_id: &proto::Hash,
+        policy: &AccessPolicy,
+        skip: &HashSet<PeerId>,
+        limit: usize,
+    ) -> Result<usize> {
+        self.send_object_near(object, obj_id, obj_id, policy, skip, limit)
+            .await
+    }
+
+    /// Like `send_object_to_closest_peers`, but to the closest peers of the target
+    /// key instead of the ID of the object
+    async fn send_object_near(
+        &self,
+        object: &TypedObject,
+        obj_id: &proto::Hash,
+        target: &proto::Hash,
+        policy: &AccessPolicy,
+        skip: &HashSet<PeerId>,
+        limit: usize,
+    ) -> Result<usize> {
+        let (resp_send, resp_recv) = oneshot::channel();
+        self.swarm_sender
+            .send(
+                commands::GetClosestPeers {
+                    obj_id: target.clone(),
+                    response_sender: resp_send,
+                }
+                .into(),
+            )
+            .await?;
+
+        let peers = resp_recv.await?;
+        if peers.is_empty() {
+            return Err(anyhow!("Could not find provider for file {obj_id}."));
+        }
+        debug!(
+            node = self.name,
+            "Found {} closest nodes for publishing",
+            peers.len()
+        );
+        let peers: Vec<PeerId> = peers
+            .into_iter()
+            .filter(|p| !skip.contains(p))
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        let mut successes = 0;
+        for peer in &peers {
+            let (send, recv) = oneshot::channel();
+            self.swarm_sender
+                .send(
+                    commands::SendObject {
+                        object: object.clone(),
+                        obj_id: obj_id.clone(),
+                        policy: policy.clone(),
+                        peer_id: peer.clone(),
+                        response_sender: send,
+                    }
+                    .into(),
+                )
+                .await?;
+
+            if let Ok(obj) = recv.await {
+                match obj {
+                    Ok(ResultObject { result: Ok(_) }) => {
+                        successes += 1;
+                        if successes >= limit {
+                            break;
+                        }
+                    }
+                    _ => {
+                        continue;
+                    }
+                }
+            }
+        }
+
+        Ok(successes)
+    }
+}
+
+/// The chunk with its ID
+fn chunk_object(content: Vec<u8>) -> Result<(proto::Hash, TypedObject)> {
+    let chunk: TypedObject = ChunkObject { content }.into();
+    Ok((proto::Hash::try_from(&chunk)?, chunk))
+}
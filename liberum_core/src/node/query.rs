@@ -0,0 +1,173 @@
+use std::collections::BTreeSet;
+
+use anyhow::{anyhow, Result};
+use futures::stream::{FuturesUnordered, StreamExt};
+use liberum_core::parser::{self, ObjectEnum};
+use liberum_core::proto::{self, ResultErrorCode, ResultObject, TypedObject};
+use liberum_core::types::{ModuleCallKind, QueryResults};
+use libp2p::PeerId;
+use tokio::sync::{mpsc, oneshot};
+use tracing::debug;
+
+use crate::swarm_runner::commands;
+use crate::swarm_runner::messages::SwarmRunnerMessage;
+
+use super::module_host::ModuleHost;
+
+///! The module contains the querying of the network with the queries of the types
+///! handled by the external modules. The query is sent to the closest peers of its
+///! hash, every peer answers with the IDs of the matching objects it knows about.
+
+/// Number of peers asked at the same time
+pub const QUERY_PARALLELISM: usize = 3;
+
+#[derive(Clone)]
+pub struct QueryCoordinator {
+    pub name: String,
+    pub swarm_sender: mpsc::Sender<SwarmRunnerMessage>,
+    pub module_host: ModuleHost,
+}
+
+impl QueryCoordinator {
+    /// Asks up to `parallelism` peers at once and merges their answers. The local
+    /// module is asked too, if it handles the type of the query
+    pub async fn query(&self, query: TypedObject, parallelism: usize) -> Result<QueryResults> {
+        let query_id = proto::Hash::try_from(&query)?;
+        self.query_near(query, &query_id, parallelism).await
+    }
+
+    /// Like `query`, but asks the closest peers of the target key instead of the
+    /// hash of the query, e.g. of the tag the peers index the objects under
+    pub async fn query_near(
+        &self,
+        query: TypedObject,
+        target: &proto::Hash,
+        parallelism: usize,
+    ) -> Result<QueryResults> {
+        let (resp_send, resp_recv) = oneshot::channel();
+        self.swarm_sender
+            .send(
+                commands::GetClosestPeers {
+                    obj_id: target.clone(),
+                    response_sender: resp_send,
+                }
+                .into(),
+            )
+            .await?;
+        let peers = resp_recv.await?;
+
+        let mut ids = BTreeSet::new();
+        let mut peers_answered = 0;
+        if self.module_host.handles(&query.uuid) {
+            let answer = self
+                .module_host
+                .call(ModuleCallKind::Query, &self.name, query.clone())
+                .await;
+            let local_ids = match answer {
+                Ok(Some(answer)) => matching_ids(answer).await,
+                Ok(None) => Ok(Vec::new()),
+                Err(e) => Err(e),
+            };
+            match local_ids {
+                Ok(local_ids) => ids.extend(local_ids),
+                Err(e) => debug!(
+                    node = self.name,
+                    err = e.to_string(),
+                    "Local module failed to answer query"
+                ),
+            }
+        }
+
+        let mut peers_left = peers.iter();
+        let mut in_flight = FuturesUnordered::new();
+        for peer in peers_left.by_ref().take(parallelism.max(1)) {
+            in_flight.push(self.query_peer(&query, *peer));
+        }
+
+        while let Some((peer, result)) = in_flight.next().await {
+            match result {
+                Ok(peer_ids) => {
+                    peers_answered += 1;
+                    ids.extend(peer_ids);
+                }
+                Err(e) => debug!(
+                    node = self.name,
+                    peer_id = peer.to_base58(),
+                    err = e.to_string(),
+                    "Peer failed to answer query"
+                ),
+            }
+            if let Some(next) = peers_left.next() {
+                in_flight.push(self.query_peer(&query, *next));
+            }
+        }
+
+        Ok(QueryResults {
+            ids: ids.into_iter().collect(),
+            peers_asked: peers.len(),
+            peers_answered,
+        })
+    }
+
+    async fn query_peer(&self, query: &TypedObject, peer: PeerId) -> (PeerId, Result<Vec<String>>) {
+        (peer, self.try_query_peer(query, peer).await)
+    }
+
+    async fn try_query_peer(&self, query: &TypedObject, peer: PeerId) -> Result<Vec<String>> {
+        let (resp_send, resp_recv) = oneshot::channel();
+        self.swarm_sender
+            .send(
+                commands::QueryPeer {
+                    query: query.clone(),
+                    peer_id: peer,
+                    response_sender: resp_send,
+                }
+                .into(),
+            )
+            .await?;
+
+        matching_ids(resp_recv.await??).await
+    }
+}
+
+/// The IDs from the answer to a query. Not found is a valid answer, with no IDs
+async fn matching_ids(answer: TypedObject) -> Result<Vec<String>> {
+    match parser::parse_typed(answer).await? {
+        ObjectEnum::QueryResult(result) => Ok(result.ids.iter().map(|id| id.to_string()).collect()),
+        ObjectEnum::Result(ResultObject {
+            result: Err(ResultErrorCode::NotFound),
+        }) => Ok(Vec::new()),
+        ObjectEnum::Result(ResultObject { result: Err(code) }) => {
+            Err(anyhow!("Peer responded with error {code:?}"))
+        }
+        _ => Err(anyhow!("Peer responded with unexpected object")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use liberum_core::proto::QueryResultObject;
+
+    #[tokio::test]
+    async fn matching_ids_test() {
+        let id = proto::Hash { bytes: [1; 32] };
+        let answer: TypedObject = QueryResultObject {
+            ids: vec![id.clone()],
+        }
+        .into();
+        assert_eq!(matching_ids(answer).await.unwrap(), vec![id.to_string()]);
+
+        let not_found: TypedObject = ResultObject {
+            result: Err(ResultErrorCode::NotFound),
+        }
+        .into();
+        assert!(matching_ids(not_found).await.unwrap().is_empty());
+
+        let failed: TypedObject = ResultObject {
+            result: Err(ResultErrorCode::Other),
+        }
+        .into();
+        assert!(matching_ids(failed).await.is_err());
+    }
+}
@@ -0,0 +1,233 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{anyhow, Result};
+use kameo::{mailbox::bounded::BoundedMailbox, messages, request::MessageSend, Actor};
+use liberum_core::node_config::ReplicationConfig;
+use liberum_core::proto;
+use libp2p::PeerId;
+use tracing::{debug, warn};
+
+use crate::vault::{
+    ListPublishedObjects, LoadAccessPolicy, LoadPopularObjects, LoadPublishedObject,
+};
+
+use super::downloader;
+use super::publisher::Publisher;
+
+///! The module contains the replicator of the objects published by a node.
+///! Peers that stored a published object may go offline at any time, so the
+///! replicator counts the providers of every published object and sends the object
+///! again to the closest peers when there are too few of them. The checks are run
+///! periodically by the scheduler of the node.
+///!
+///! The objects often fetched from the node are checked first and get more
+///! providers, so the load of serving them is spread over more peers.
+///!
+///! The replicator talks to the swarm with its own publisher, not through the node,
+///! so the node keeps answering the daemon while the objects are replicated.
+
+pub struct Replicator {
+    node_name: String,
+    publisher: Publisher,
+    config: ReplicationConfig,
+}
+
+impl Actor for Replicator {
+    type Mailbox = BoundedMailbox<Self>;
+}
+
+#[messages]
+impl Replicator {
+    /// Checks the number of providers of every published object and replicates
+    /// the objects with not enough providers
+    #[message]
+    pub async fn check_replication(&mut self) -> Result<()> {
+        let vault_ref = &self.publisher.vault_ref;
+        let mut published = vault_ref.ask(ListPublishedObjects).send().await?;
+        let fetch_counts: HashMap<String, u64> = vault_ref
+            .ask(LoadPopularObjects { limit: None })
+            .send()
+            .await?
+            .into_iter()
+            .map(|popularity| (popularity.id, popularity.fetch_count))
+            .collect();
+        let fetch_count = |obj_id: &proto::Hash| {
+            fetch_counts
+                .get(&obj_id.to_string())
+                .copied()
+                .unwrap_or_default()
+        };
+        published.sort_by_key(|obj_id| std::cmp::Reverse(fetch_count(obj_id)));
+        debug!(
+            node = self.node_name,
+            count = published.len(),
+            "Checking replication of published objects"
+        );
+
+        for obj_id in published {
+            let factor = self.config.factor_for(fetch_count(&obj_id));
+            if let Err(e) = self.replicate(obj_id.clone(), factor).await {
+                warn!(
+                    node = self.node_name,
+                    obj_id = obj_id.to_string(),
+                    err = e.to_string(),
+                    "Failed to replicate object"
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Replicator {
+    pub fn new(node_name: String, publisher: Publisher, config: ReplicationConfig) -> Self {
+        Replicator {
+            node_name,
+            publisher,
+            config,
+        }
+    }
+
+    /// Sends the object to the closest peers which don't provide it yet, until it
+    /// has `factor` providers. Returns the number of the peers it was sent to
+    async fn replicate(&self, obj_id: proto::Hash, factor: usize) -> Result<usize> {
+        let providers = downloader::find_providers(&self.publisher.swarm_sender, &obj_id).await?;
+        let (providers, _) = downloader::collect_providers(providers).await;
+        if providers.len() >= factor {
+            return Ok(0);
+        }
+
+        let vault_ref = &self.publisher.vault_ref;
+        let object = vault_ref
+            .ask(LoadPublishedObject {
+                hash: obj_id.clone(),
+            })
+            .send()
+            .await?
+            .ok_or(anyhow!("Published object is missing in the vault"))?;
+        let policy = vault_ref
+            .ask(LoadAccessPolicy {
+                hash: obj_id.clone(),
+            })
+            .send()
+            .await?;
+
+        let missing = factor - providers.len();
+        let mut skip: HashSet<PeerId> = providers.into_iter().collect();
+        skip.insert(self.publisher.keypair.public().to_peer_id());
+        let replicated = self
+            .publisher
+            .send_object_to_closest_peers(&object, &obj_id, &policy, &skip, missing)
+            .await?;
+
+        debug!(
+            node = self.node_name,
+            obj_id = obj_id.to_string(),
+            missing = missing,
+            replicated = replicated,
+            "Replicated object"
+        );
+
+        Ok(replicated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use liberum_core::node_config::ChunkingConfig;
+    use liberum_core::proto::{PlainFileObject, ResultObject, TypedObject};
+    use libp2p::identity::Keypair;
+    use tokio::sync::mpsc;
+
+    use super::*;
+    use crate::swarm_runner::commands;
+    use crate::swarm_runner::messages::SwarmRunnerMessage;
+    use crate::vault::{StorePublishedObject, Vault};
+
+    /// A network in which the object is provided by `providers`, the closest peers
+    /// are the providers and `others`. Records the peers the object is sent to
+    fn network(
+        providers: Vec<PeerId>,
+        others: Vec<PeerId>,
+        sent: Arc<Mutex<Vec<PeerId>>>,
+    ) -> mpsc::Sender<SwarmRunnerMessage> {
+        let (sender, mut receiver) = mpsc::channel(16);
+        tokio::spawn(async move {
+            while let Some(message) = receiver.recv().await {
+                match message {
+                    SwarmRunnerMessage::GetProviders(commands::GetProviders {
+                        providers_sender,
+                        ..
+                    }) => {
+                        let _ = providers_sender.send((providers.clone(), None)).await;
+                    }
+                    SwarmRunnerMessage::GetClosestPeers(commands::GetClosestPeers {
+                        response_sender,
+                        ..
+                    }) => {
+                        let closest = providers.iter().chain(&others).copied().collect();
+                        let _ = response_sender.send(closest);
+                    }
+                    SwarmRunnerMessage::SendObject(commands::SendObject {
+                        peer_id,
+                        response_sender,
+                        ..
+                    }) => {
+                        sent.lock().unwrap().push(peer_id);
+                        let _ = response_sender.send(Ok(ResultObject { result: Ok(()) }));
+                    }
+                    _ => (),
+                }
+            }
+        });
+        sender
+    }
+
+    #[tokio::test]
+    async fn replicate_test() {
+        let vault_ref = kameo::spawn(Vault::new_in_memory().await.unwrap());
+        let object: TypedObject = PlainFileObject {
+            name: "file".to_string(),
+            content: b"content".to_vec(),
+        }
+        .into();
+        let obj_id = proto::Hash::try_from(&object).unwrap();
+        vault_ref
+            .ask(StorePublishedObject {
+                hash: obj_id.clone(),
+                object,
+            })
+            .send()
+            .await
+            .unwrap();
+
+        let providers: Vec<PeerId> = (0..2).map(|_| PeerId::random()).collect();
+        let others = (0..5).map(|_| PeerId::random()).collect();
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let replicator = Replicator::new(
+            "replicator".to_string(),
+            Publisher {
+                name: "replicator".to_string(),
+                keypair: Keypair::generate_ed25519(),
+                swarm_sender: network(providers.clone(), others, sent.clone()),
+                vault_ref,
+                chunking: ChunkingConfig::default(),
+            },
+            ReplicationConfig::default(),
+        );
+
+        // With enough providers, the object is not sent anywhere
+        assert_eq!(replicator.replicate(obj_id.clone(), 2).await.unwrap(), 0);
+        assert!(sent.lock().unwrap().is_empty());
+
+        // Below the factor, only the missing copies are sent, to the peers which
+        // don't provide the object yet
+        assert_eq!(replicator.replicate(obj_id, 5).await.unwrap(), 3);
+        let sent = sent.lock().unwrap();
+        assert_eq!(sent.len(), 3);
+        assert!(sent.iter().all(|peer_id| !providers.contains(peer_id)));
+    }
+}
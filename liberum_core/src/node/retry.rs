@@ -0,0 +1,157 @@
+use std::future::Future;
+use std::time::Duration;
+
+use anyhow::Result;
+use liberum_core::node_config::RetryConfig;
+use libp2p::request_response::OutboundFailure;
+use rand::Rng;
+use thiserror::Error;
+use tokio::time::error::Elapsed;
+use tracing::debug;
+
+use crate::swarm_runner::behaviour::pending::TimeoutError;
+
+///! The module contains the retry policy of the swarm queries run by a node.
+///! Transient errors like timeouts and closed connections are retried with an
+///! exponential backoff, all the other errors, like a missing object, fail right away.
+
+/// Errors which won't go away when the query is repeated
+#[derive(Error, Debug)]
+pub enum PermanentError {
+    #[error("Could not find provider for object {0}")]
+    NoProviders(String),
+    #[error("Object {0} not found")]
+    NotFound(String),
+    #[error("The peer rejected the message: {0}")]
+    MessageRejected(String),
+    #[error("Access to object {0} denied")]
+    AccessDenied(String),
+}
+
+/// Errors which may go away when the query is repeated, besides the timeouts and
+/// the connection failures
+#[derive(Error, Debug)]
+pub enum TransientError {
+    #[error("Could not download object, all {0} providers failed")]
+    ProvidersFailed(usize),
+}
+
+/// Only the timeouts and the failed connections are retried, an error which is
+/// not known to be transient is permanent
+pub fn is_transient(e: &anyhow::Error) -> bool {
+    if e.is::<TransientError>() || e.is::<TimeoutError>() || e.is::<Elapsed>() {
+        return true;
+    }
+    if let Some(failure) = e.downcast_ref::<OutboundFailure>() {
+        return matches!(
+            failure,
+            OutboundFailure::Timeout
+                | OutboundFailure::DialFailure
+                | OutboundFailure::ConnectionClosed
+                | OutboundFailure::Io(_)
+        );
+    }
+
+    e.chain()
+        .filter_map(|cause| cause.downcast_ref::<std::io::Error>())
+        .any(|io_error| {
+            matches!(
+                io_error.kind(),
+                std::io::ErrorKind::TimedOut
+                    | std::io::ErrorKind::ConnectionRefused
+                    | std::io::ErrorKind::ConnectionReset
+                    | std::io::ErrorKind::ConnectionAborted
+                    | std::io::ErrorKind::NotConnected
+                    | std::io::ErrorKind::BrokenPipe
+            )
+        })
+}
+
+/// Delay before the given retry, starting from 1. The delay doubles with every
+/// retry up to the maximum, then a random jitter of up to half of it is subtracted,
+/// so many nodes retrying at once don't hit the network at the same moment
+pub fn backoff(config: &RetryConfig, retry: u32) -> Duration {
+    let exp = config
+        .initial_backoff_ms
+        .saturating_mul(2u64.saturating_pow(retry.saturating_sub(1)));
+    let capped = exp.min(config.max_backoff_ms);
+    let jitter = rand::thread_rng().gen_range(0..=capped / 2);
+
+    Duration::from_millis(capped - jitter)
+}
+
+/// Runs the operation until it succeeds, fails with a permanent error or runs out
+/// of attempts. Returns the result of the last attempt and the number of attempts
+pub async fn retry<T, F, Fut>(config: &RetryConfig, mut operation: F) -> (Result<T>, u32)
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let max_attempts = config.max_attempts.max(1);
+    let mut attempt = 1;
+
+    loop {
+        let result = operation().await;
+        match result {
+            Err(e) if attempt < max_attempts && is_transient(&e) => {
+                let delay = backoff(config, attempt);
+                debug!(
+                    attempt = attempt,
+                    delay_ms = delay.as_millis() as u64,
+                    err = e.to_string(),
+                    "Transient error, retrying"
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            result => return (result, attempt),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    fn config() -> RetryConfig {
+        RetryConfig {
+            max_attempts: 3,
+            initial_backoff_ms: 1,
+            max_backoff_ms: 4,
+        }
+    }
+
+    #[tokio::test]
+    async fn retry_transient_and_permanent_test() {
+        let mut calls = 0;
+        let (result, attempts) = retry(&config(), || {
+            calls += 1;
+            async { Err::<(), _>(anyhow!("Outbound failure").context(OutboundFailure::Timeout)) }
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(attempts, 3);
+        assert_eq!(calls, 3);
+
+        let (result, attempts) = retry(&config(), || async {
+            Err::<(), _>(PermanentError::NotFound("id".to_string()).into())
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
+
+        // Not known to be transient
+        let (_, attempts) = retry(&config(), || async { Err::<(), _>(anyhow!("Unknown")) }).await;
+        assert_eq!(attempts, 1);
+        let (_, attempts) = retry(&config(), || async {
+            Err::<(), _>(anyhow!(TimeoutError).context("Failed to get object"))
+        })
+        .await;
+        assert_eq!(attempts, 3);
+
+        for retry in 1..10 {
+            assert!(backoff(&config(), retry) <= Duration::from_millis(4));
+        }
+    }
+}
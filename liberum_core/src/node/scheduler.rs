@@ -0,0 +1,430 @@
+use std::collections::BTreeMap;
+use std::time::{Duration, SystemTime};
+
+use anyhow::{anyhow, Result};
+use kameo::{
+    actor::{ActorRef, WeakActorRef},
+    error::SendError,
+    mailbox::bounded::BoundedMailbox,
+    messages,
+    request::MessageSend,
+    Actor,
+};
+use liberum_core::node_config::{CacheConfig, NodeConfig};
+use liberum_core::types::{NodeStatsSample, ScheduledTask, ScheduledTaskInfo};
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::Instant;
+use tracing::{debug, error, warn};
+
+use super::mailbox::Mailbox;
+use super::replicator::{CheckReplication, Replicator};
+use crate::swarm_runner::commands;
+use crate::swarm_runner::messages::SwarmRunnerMessage;
+use crate::vault::{DeleteStatsSamples, SelectForEviction, StoreStatsSample, Vault};
+
+///! The module contains the scheduler of the maintenance tasks of a node, like the
+///! replication checks or fetching the mailbox. Every task runs with the interval
+///! from the config of the node, or only when the operator asks for it with the
+///! interval 0. The tasks run outside of the scheduler, so it can answer how they
+///! are doing while they run, and a task is never started again before it finishes.
+///!
+///! The cache GC task removes the least requested objects the node stores for other
+///! peers once they take more than the limit from the config. The objects published
+///! by the node are never removed.
+///!
+///! The stats task records the activity of the node in its vault, for the graphs of
+///! the history. The samples older than the retention are removed by the same task.
+
+/// How often the scheduler checks which tasks are due
+const TICK_INTERVAL: Duration = Duration::from_secs(1);
+/// How long the samples of the stats are kept
+const STATS_RETENTION: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// What the tasks need from the node, cloned for every run
+#[derive(Clone)]
+pub struct TaskContext {
+    pub swarm_sender: mpsc::Sender<SwarmRunnerMessage>,
+    pub replicator_ref: ActorRef<Replicator>,
+    pub vault_ref: ActorRef<Vault>,
+    pub mailbox: Mailbox,
+    pub cache: CacheConfig,
+}
+
+pub struct Scheduler {
+    node_name: String,
+    context: TaskContext,
+    tasks: BTreeMap<ScheduledTask, TaskState>,
+    self_ref: Option<WeakActorRef<Self>>,
+}
+
+#[derive(Default)]
+struct TaskState {
+    interval: Option<Duration>,
+    next_run: Option<Instant>,
+    running: bool,
+    runs: u64,
+    last_run: Option<SystemTime>,
+    last_duration: Option<Duration>,
+    last_error: Option<String>,
+    /// Waiting for the running task to finish
+    waiters: Vec<oneshot::Sender<ScheduledTaskInfo>>,
+}
+
+impl Actor for Scheduler {
+    type Mailbox = BoundedMailbox<Self>;
+
+    async fn on_start(
+        &mut self,
+        actor_ref: ActorRef<Self>,
+    ) -> std::result::Result<(), kameo::error::BoxError> {
+        let weak_ref = actor_ref.downgrade();
+        self.self_ref = Some(weak_ref.clone());
+        tokio::spawn(Self::run_timer(weak_ref));
+
+        Ok(())
+    }
+}
+
+#[messages]
+impl Scheduler {
+    /// Starts the tasks which are due
+    #[message]
+    pub fn tick(&mut self) {
+        let now = Instant::now();
+        let due: Vec<ScheduledTask> = self
+            .tasks
+            .iter()
+            .filter(|(_, state)| !state.running && state.next_run.is_some_and(|at| at <= now))
+            .map(|(task, _)| *task)
+            .collect();
+        for task in due {
+            self.start(task);
+        }
+    }
+
+    /// Applies the intervals from the config, the next runs are counted from now
+    #[message]
+    pub fn set_intervals(&mut self, config: NodeConfig) {
+        self.context.cache = config.cache;
+        for task in ScheduledTask::ALL {
+            let state = self.tasks.entry(task).or_default();
+            state.interval = config.task_interval(task);
+            state.next_run = state.interval.map(|interval| Instant::now() + interval);
+        }
+    }
+
+    #[message]
+    pub fn list_tasks(&self) -> Vec<ScheduledTaskInfo> {
+        self.tasks
+            .iter()
+            .map(|(task, state)| state.info(*task))
+            .collect()
+    }
+
+    /// Starts the task now, the receiver gets its state when it finishes. If the
+    /// task is already running, the receiver waits for that run
+    #[message]
+    pub fn run_now(&mut self, task: ScheduledTask) -> Result<oneshot::Receiver<ScheduledTaskInfo>> {
+        let (send, recv) = oneshot::channel();
+        let state = self
+            .tasks
+            .get_mut(&task)
+            .ok_or(anyhow!("Unknown task {task}"))?;
+        state.waiters.push(send);
+        if !state.running {
+            self.start(task);
+        }
+        Ok(recv)
+    }
+
+    #[message]
+    pub fn task_finished(
+        &mut self,
+        task: ScheduledTask,
+        started: SystemTime,
+        duration: Duration,
+        error: Option<String>,
+    ) {
+        let Some(state) = self.tasks.get_mut(&task) else {
+            return;
+        };
+        state.running = false;
+        state.runs += 1;
+        state.last_run = Some(started);
+        state.last_duration = Some(duration);
+        state.last_error = error;
+        state.next_run = state.interval.map(|interval| Instant::now() + interval);
+
+        let info = state.info(task);
+        for waiter in state.waiters.drain(..) {
+            let _ = waiter.send(info.clone());
+        }
+    }
+}
+
+impl Scheduler {
+    pub fn new(node_name: String, context: TaskContext, config: &NodeConfig) -> Self {
+        let mut scheduler = Scheduler {
+            node_name,
+            context,
+            tasks: BTreeMap::new(),
+            self_ref: None,
+        };
+        scheduler.set_intervals(config.clone());
+        scheduler
+    }
+
+    fn start(&mut self, task: ScheduledTask) {
+        let Some(state) = self.tasks.get_mut(&task) else {
+            return;
+        };
+        state.running = true;
+
+        let node_name = self.node_name.clone();
+        let context = self.context.clone();
+        let self_ref = self.self_ref.clone();
+        tokio::spawn(async move {
+            debug!(node = node_name, task = task.to_string(), "Running task");
+            let started = SystemTime::now();
+            let start = Instant::now();
+            let result = context.run(task).await;
+            if let Err(e) = &result {
+                warn!(
+                    node = node_name,
+                    task = task.to_string(),
+                    err = e.to_string(),
+                    "Scheduled task failed"
+                );
+            }
+
+            let Some(scheduler_ref) = self_ref.and_then(|r| r.upgrade()) else {
+                return;
+            };
+            let _ = scheduler_ref
+                .ask(TaskFinished {
+                    task,
+                    started,
+                    duration: start.elapsed(),
+                    error: result.err().map(|e| e.to_string()),
+                })
+                .send()
+                .await;
+        });
+    }
+
+    /// Checks the tasks every tick, until the scheduler stops
+    async fn run_timer(weak_ref: WeakActorRef<Self>) {
+        let mut interval = tokio::time::interval(TICK_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            let Some(actor_ref) = weak_ref.upgrade() else {
+                break;
+            };
+
+            match actor_ref.ask(Tick).send().await {
+                Ok(()) => {}
+                Err(SendError::ActorNotRunning(_)) | Err(SendError::ActorStopped) => break,
+                Err(e) => error!(err = e.to_string(), "Scheduler tick failed"),
+            }
+        }
+    }
+}
+
+impl TaskState {
+    fn info(&self, task: ScheduledTask) -> ScheduledTaskInfo {
+        let now = Instant::now();
+        ScheduledTaskInfo {
+            task,
+            interval: self.interval,
+            next_run: self
+                .next_run
+                .filter(|_| !self.running)
+                .map(|at| SystemTime::now() + at.saturating_duration_since(now)),
+            running: self.running,
+            runs: self.runs,
+            last_run: self.last_run,
+            last_duration: self.last_duration,
+            last_error: self.last_error.clone(),
+        }
+    }
+}
+
+impl TaskContext {
+    async fn run(&self, task: ScheduledTask) -> Result<()> {
+        match task {
+            ScheduledTask::Reprovide => self.reprovide().await,
+            ScheduledTask::Replication => {
+                Ok(self.replicator_ref.ask(CheckReplication).send().await?)
+            }
+            ScheduledTask::MailboxFetch => self.mailbox.fetch().await.map(|_| ()),
+            ScheduledTask::BootstrapHealth => self.check_bootstrap().await,
+            ScheduledTask::CacheGc => self.collect_cache().await,
+            ScheduledTask::PeerExchange => self.exchange_peers().await,
+            ScheduledTask::StatsSample => self.sample_stats().await,
+        }
+    }
+
+    async fn reprovide(&self) -> Result<()> {
+        let (send, recv) = oneshot::channel();
+        self.swarm_sender
+            .send(
+                commands::Reprovide {
+                    response_sender: send,
+                }
+                .into(),
+            )
+            .await?;
+
+        let results = recv.await?;
+        let total = results.len();
+        let mut failed = 0;
+        for result in results {
+            if !matches!(result.await, Ok(Ok(()))) {
+                failed += 1;
+            }
+        }
+
+        match failed {
+            0 => Ok(()),
+            failed => Err(anyhow!("{failed} of {total} objects could not be provided")),
+        }
+    }
+
+    /// Bootstraps again if the node lost all its peers, e.g. after the network
+    /// was down for a while
+    async fn check_bootstrap(&self) -> Result<()> {
+        let (send, recv) = oneshot::channel();
+        self.swarm_sender
+            .send(
+                commands::GetStatus {
+                    response_sender: send,
+                }
+                .into(),
+            )
+            .await?;
+        let status = recv.await?;
+        if status.connected_peers > 0 && status.routing_table_size > 0 {
+            return Ok(());
+        }
+
+        let (send, recv) = oneshot::channel();
+        self.swarm_sender
+            .send(
+                commands::Bootstrap {
+                    response_sender: send,
+                }
+                .into(),
+            )
+            .await?;
+        recv.await?
+    }
+
+    async fn exchange_peers(&self) -> Result<()> {
+        let (send, recv) = oneshot::channel();
+        self.swarm_sender
+            .send(
+                commands::ExchangePeers {
+                    response_sender: send,
+                }
+                .into(),
+            )
+            .await?;
+        recv.await?.map(|_| ())
+    }
+
+    async fn sample_stats(&self) -> Result<()> {
+        let (send, recv) = oneshot::channel();
+        self.swarm_sender
+            .send(
+                commands::GetStatus {
+                    response_sender: send,
+                }
+                .into(),
+            )
+            .await?;
+        let status = recv.await?;
+        let now = SystemTime::now();
+        let sample = NodeStatsSample {
+            at: now,
+            connected_peers: status.connected_peers,
+            bytes_sent: status.bytes_sent,
+            bytes_received: status.bytes_received,
+            objects_served: status.objects_served,
+        };
+        self.vault_ref
+            .ask(StoreStatsSample { sample })
+            .send()
+            .await?;
+
+        self.vault_ref
+            .ask(DeleteStatsSamples {
+                before: now - STATS_RETENTION,
+            })
+            .send()
+            .await?;
+        Ok(())
+    }
+
+    /// Stops providing and deletes the least requested stored objects until they
+    /// fit in the cache limit
+    async fn collect_cache(&self) -> Result<()> {
+        if self.cache.max_stored_bytes == 0 {
+            return Ok(());
+        }
+        let evicted = self
+            .vault_ref
+            .ask(SelectForEviction {
+                max_bytes: self.cache.max_stored_bytes,
+            })
+            .send()
+            .await?;
+
+        let total = evicted.len();
+        let mut failed = 0;
+        for obj_id in evicted {
+            let (send, recv) = oneshot::channel();
+            self.swarm_sender
+                .send(
+                    commands::StopProviding {
+                        obj_id,
+                        keep_in_vault: false,
+                        response_sender: send,
+                    }
+                    .into(),
+                )
+                .await?;
+            if !matches!(recv.await, Ok(Ok(()))) {
+                failed += 1;
+            }
+        }
+
+        match failed {
+            0 => Ok(()),
+            failed => Err(anyhow!("{failed} of {total} objects could not be evicted")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn task_info_test() {
+        let mut state = TaskState {
+            interval: Some(Duration::from_secs(60)),
+            next_run: Some(Instant::now() + Duration::from_secs(60)),
+            ..Default::default()
+        };
+        let info = state.info(ScheduledTask::Replication);
+        assert_eq!(info.task, ScheduledTask::Replication);
+        assert!(info.next_run.unwrap() > SystemTime::now());
+        assert_eq!(info.runs, 0);
+
+        // A running task has no next run until it finishes
+        state.running = true;
+        assert_eq!(state.info(ScheduledTask::Replication).next_run, None);
+    }
+}
@@ -0,0 +1,134 @@
+use std::collections::{BTreeSet, HashMap};
+
+use anyhow::{anyhow, Result};
+use kameo::{actor::ActorRef, request::MessageSend};
+use liberum_core::proto::{TagObject, TagQuery, TypedObject};
+use liberum_core::types::SearchResult;
+use tracing::debug;
+
+use super::query::{QueryCoordinator, QUERY_PARALLELISM};
+use crate::vault::{FindTagged, Vault};
+
+///! The module contains the searching of the objects by their tags. Every tag of the
+///! search is looked up in the index of the node, and with the network search also
+///! asked from the closest peers of the hash of the tag, which index the tags sent
+///! to them. The objects matching more of the tags are ranked higher, the ones the
+///! node indexed itself go first among the equal ones.
+
+/// The tags of the search, separated by whitespace or commas
+pub fn parse_query(query: &str) -> Vec<String> {
+    let mut tags: Vec<String> = query
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .map(TagObject::normalize)
+        .filter(|tag| !tag.is_empty())
+        .collect();
+    tags.sort();
+    tags.dedup();
+    tags
+}
+
+#[derive(Default)]
+struct Matches {
+    tags: BTreeSet<String>,
+    local: bool,
+}
+
+/// Searches the index of the node, and the peers if the coordinator is given
+pub async fn search(
+    vault_ref: &ActorRef<Vault>,
+    network: Option<&QueryCoordinator>,
+    query: &str,
+) -> Result<Vec<SearchResult>> {
+    let tags = parse_query(query);
+    if tags.is_empty() {
+        return Err(anyhow!("The search has no tags"));
+    }
+
+    let mut matches: HashMap<String, Matches> = HashMap::new();
+    for tag in &tags {
+        let tag_hash = TagObject::tag_hash(tag);
+        let local = vault_ref
+            .ask(FindTagged {
+                tag_hash: tag_hash.clone(),
+            })
+            .send()
+            .await?;
+        for (id, _) in local {
+            let matched = matches.entry(id.to_string()).or_default();
+            matched.tags.insert(tag.clone());
+            matched.local = true;
+        }
+
+        let Some(coordinator) = network else {
+            continue;
+        };
+        let query: TypedObject = TagQuery {
+            tag_hash: tag_hash.clone(),
+        }
+        .into();
+        match coordinator
+            .query_near(query, &tag_hash, QUERY_PARALLELISM)
+            .await
+        {
+            Ok(results) => {
+                for id in results.ids {
+                    matches.entry(id).or_default().tags.insert(tag.clone());
+                }
+            }
+            Err(e) => debug!(
+                node = coordinator.name,
+                tag = tag,
+                err = e.to_string(),
+                "Failed to search the network for tag"
+            ),
+        }
+    }
+
+    Ok(rank(matches))
+}
+
+fn rank(matches: HashMap<String, Matches>) -> Vec<SearchResult> {
+    let mut results: Vec<SearchResult> = matches
+        .into_iter()
+        .map(|(id, matched)| SearchResult {
+            id,
+            matched_tags: matched.tags.into_iter().collect(),
+            local: matched.local,
+        })
+        .collect();
+    results.sort_by(|a, b| {
+        b.matched_tags
+            .len()
+            .cmp(&a.matched_tags.len())
+            .then(b.local.cmp(&a.local))
+            .then(a.id.cmp(&b.id))
+    });
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_query_test() {
+        assert_eq!(parse_query(" Rust,p2p  rust "), vec!["p2p", "rust"]);
+        assert!(parse_query(" , ").is_empty());
+    }
+
+    #[test]
+    fn rank_test() {
+        let matched = |tags: &[&str], local| Matches {
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            local,
+        };
+        let matches = HashMap::from([
+            ("a".to_string(), matched(&["rust"], false)),
+            ("b".to_string(), matched(&["rust", "p2p"], false)),
+            ("c".to_string(), matched(&["p2p"], true)),
+        ]);
+
+        let ids: Vec<String> = rank(matches).into_iter().map(|r| r.id).collect();
+        assert_eq!(ids, vec!["b", "c", "a"]);
+    }
+}
@@ -1,14 +1,23 @@
+mod database;
+
 use anyhow::{anyhow, Context, Result};
 use kameo::{messages, Actor};
-use liberum_core::node_config::NodeConfig;
-use libp2p::identity::Keypair;
+use liberum_core::atomic_file;
+use liberum_core::node_config::{CompressionConfig, NodeConfig};
+use liberum_core::proto::RotationObject;
+use libp2p::identity::{Keypair, PublicKey};
+use libp2p::PeerId;
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use thiserror::Error;
-use tracing::{debug, error};
+use tracing::{debug, error, info, warn};
 
+use crate::swarm_runner::webrtc;
 use crate::vault::Vault;
 
+use super::identity;
 use super::NodeSnapshot;
+use database::{NodeDatabase, NodeNotFound, NodeRecord};
 
 pub struct UpdateNodeConfig {
     pub name: String,
@@ -19,12 +28,21 @@ pub struct UpdateNodeConfig {
 #[derive(Debug, Actor)]
 pub struct NodeStore {
     store_dir_path: PathBuf,
+    db: NodeDatabase,
+    /// Decrypted keypairs of the protected nodes, kept until the daemon stops
+    unlocked_keys: HashMap<String, Keypair>,
 }
 
 #[derive(Error, Debug)]
 pub enum NodeStoreError {
     #[error("node does not exist")]
     NodeDoesNotExist,
+    #[error("node already exists")]
+    NodeAlreadyExists,
+    #[error("node is locked, it must be unlocked with the passphrase first")]
+    NodeLocked,
+    #[error("the vault kept under the node name belongs to another user")]
+    VaultOwnedByOther,
     #[error("other error: {err}")]
     OtherError {
         #[from]
@@ -32,26 +50,27 @@ pub enum NodeStoreError {
     },
 }
 
+/// The error of the database, a node missing from it is the node not existing
+fn db_error(e: anyhow::Error) -> NodeStoreError {
+    match e.is::<NodeNotFound>() {
+        true => NodeStoreError::NodeDoesNotExist,
+        false => e.into(),
+    }
+}
+
 #[messages]
 impl NodeStore {
     #[message]
     pub async fn load_node(&self, name: String) -> Result<NodeSnapshot, NodeStoreError> {
-        let node_dir_path = self.resolve_node_dir_path(&name);
-        debug!(
-            name = name,
-            path = node_dir_path.display().to_string(),
-            "loading node"
-        );
+        debug!(name = name, "loading node");
 
-        if !node_dir_path.exists() {
-            debug!(
-                path = node_dir_path.display().to_string(),
-                name = name,
-                "node does not exist"
-            );
-
-            return Err(NodeStoreError::NodeDoesNotExist);
-        }
+        let node = self.load_record(&name).await.inspect_err(|_| {
+            debug!(name = name, "node does not exist");
+        })?;
+        let node_dir_path = self
+            .ensure_node_dir_path(&name)
+            .await
+            .context("could not ensure node dir path")?;
 
         if !node_dir_path.is_dir() {
             error!(
@@ -62,21 +81,30 @@ impl NodeStore {
             return Err(anyhow!("node_dir_path is not a directory").into());
         }
 
-        let config_path = node_dir_path.join(Self::NODE_CONFIG_FILE_NAME);
-        let config = NodeConfig::load(&config_path)
-            .await
-            .context("failed to load node config")?;
-        let key_path = node_dir_path.join(Self::NODE_KEY_FILE_NAME);
-        let key_bytes = tokio::fs::read(key_path)
-            .await
-            .inspect_err(|e| error!(err = e.to_string(), "could not read node keypair bytes"))
-            .context("could not read node keypair bytes")?;
-        let keypair = Keypair::from_protobuf_encoding(&key_bytes)
-            .context("could not read keypair from protobuf encoded bytes")?;
+        let config = node.config;
+        let keypair = match config.key_protected {
+            true => self
+                .unlocked_keys
+                .get(&name)
+                .cloned()
+                .ok_or(NodeStoreError::NodeLocked)?,
+            false => Keypair::from_protobuf_encoding(&node.keypair)
+                .context("could not read keypair from protobuf encoded bytes")?,
+        };
+        let webrtc_certificate = match webrtc::is_enabled(&config.transports) {
+            true => Some(
+                webrtc::load_or_generate_certificate(&node_dir_path)
+                    .await
+                    .context("could not load WebRTC certificate")?,
+            ),
+            false => None,
+        };
         let node_snapshot = NodeSnapshot::builder()
             .name(name)
             .keypair(keypair)
             .config(config)
+            .webrtc_certificate(webrtc_certificate)
+            .owner(node.owner)
             .build_snapshot()
             // This can't fail
             .unwrap();
@@ -84,15 +112,22 @@ impl NodeStore {
         Ok(node_snapshot)
     }
 
+    /// Stores the node. A new node gets the owner of the snapshot, it may reuse the
+    /// vault kept under its name only if the vault belonged to the same owner
     #[message]
     pub async fn store_node(&self, node_snapshot: NodeSnapshot) -> Result<(), NodeStoreError> {
+        let name = &node_snapshot.name;
+        let stored = self.db.load(name).await?;
+        if stored.is_none() {
+            self.claim_kept_vault(name, node_snapshot.owner).await?;
+        }
         let node_dir_path = self
-            .ensure_node_dir_path(&node_snapshot.name)
+            .ensure_node_dir_path(name)
             .await
             .context("could not ensure node dir path")?;
 
         debug!(
-            name = node_snapshot.name,
+            name = name,
             path = node_dir_path.display().to_string(),
             "saving node"
         );
@@ -102,89 +137,302 @@ impl NodeStore {
             return Err(anyhow!("node dir path is not a directory").into());
         }
 
-        let config: NodeConfig = (&node_snapshot).into();
-        let config_path = node_dir_path.join(Self::NODE_CONFIG_FILE_NAME);
-        let key_bytes = node_snapshot
-            .keypair
-            .to_protobuf_encoding()
-            .inspect_err(|e| error!(err = e.to_string(), "could not convert keypair to bytes"))
-            .context("could not convert keypair to bytes")?;
-        let key_path = node_dir_path.join(Self::NODE_KEY_FILE_NAME);
+        // The stored config is the source of truth, the one from a snapshot may be
+        // outdated. The keypair changes only when it is rotated by the store, the
+        // encrypted one is written only when the passphrase is set
+        let mut config: NodeConfig = (&node_snapshot).into();
+        match &stored {
+            Some(stored) if stored.config.key_protected => {
+                config.key_protected = true;
+                self.db.save_config(name, &config).await.map_err(db_error)?;
+            }
+            _ => {
+                config.key_protected = false;
+                let key_bytes = Self::encode_plain_key(&node_snapshot.keypair)?;
+                self.db.save(name, &config, key_bytes, None).await?;
+            }
+        }
+        if let (None, Some(owner)) = (stored, node_snapshot.owner) {
+            self.db.set_owner(name, owner).await.map_err(db_error)?;
+        }
 
-        tokio::fs::write(key_path, key_bytes)
+        Ok(())
+    }
+
+    /// Stores a node created from the imported key material. Unlike `StoreNode` it
+    /// never overwrites the keypair of an existing node
+    #[message]
+    pub async fn import_node(&self, node_snapshot: NodeSnapshot) -> Result<(), NodeStoreError> {
+        if self.db.exists(&node_snapshot.name).await? {
+            return Err(NodeStoreError::NodeAlreadyExists);
+        }
+
+        self.store_node(node_snapshot).await
+    }
+
+    #[message]
+    pub async fn get_node_config(&self, name: String) -> Result<NodeConfig, NodeStoreError> {
+        debug!(name = name, "Getting current node config");
+        Ok(self.load_record(&name).await?.config)
+    }
+
+    #[message]
+    pub async fn overwrite_node_config(
+        &self,
+        name: String,
+        mut new_cfg: NodeConfig,
+    ) -> Result<(), NodeStoreError> {
+        let stored = self.load_record(&name).await?.config;
+        new_cfg.key_protected = stored.key_protected;
+        new_cfg.key_rotations = stored.key_rotations;
+        self.db
+            .save_config(&name, &new_cfg)
             .await
-            .inspect_err(|e| error!(err = e.to_string(), "could not write node keypair"))
-            .context("could not write node keypair")?;
+            .map_err(db_error)?;
 
-        config.save(&config_path).await?;
+        Ok(())
+    }
+
+    /// Decrypts the keypair of a protected node, so it can be started
+    #[message]
+    pub async fn unlock_node(
+        &mut self,
+        name: String,
+        passphrase: String,
+    ) -> Result<(), NodeStoreError> {
+        let node = self.load_record(&name).await?;
+        if !node.config.key_protected {
+            debug!(name = name, "node is not protected, nothing to unlock");
+            return Ok(());
+        }
+
+        let keypair = identity::decrypt_keypair(&node.keypair, &passphrase)?;
+        self.unlocked_keys.insert(name, keypair);
 
         Ok(())
     }
 
+    /// Encrypts the keypair of the node with the passphrase, or stores it in
+    /// plaintext again if there is no passphrase. The node must not be locked
     #[message]
-    pub async fn get_node_config(&self, name: String) -> Result<NodeConfig, NodeStoreError> {
-        if !self.node_exists(&name) {
-            return Err(NodeStoreError::NodeDoesNotExist);
+    pub async fn set_key_passphrase(
+        &mut self,
+        name: String,
+        passphrase: Option<String>,
+    ) -> Result<(), NodeStoreError> {
+        let node_snapshot = self.load_node(name.clone()).await?;
+        let mut config = node_snapshot.config.clone();
+        let keypair = node_snapshot.keypair;
+
+        match passphrase {
+            Some(passphrase) => {
+                let key_bytes = identity::encrypt_keypair(&keypair, &passphrase)?;
+                let public_key = keypair.public().encode_protobuf();
+                config.key_protected = true;
+                self.db
+                    .save(&name, &config, key_bytes, Some(public_key))
+                    .await?;
+                self.unlocked_keys.insert(name, keypair);
+            }
+            None => {
+                let key_bytes = Self::encode_plain_key(&keypair)?;
+                config.key_protected = false;
+                self.db.save(&name, &config, key_bytes, None).await?;
+                self.unlocked_keys.remove(&name);
+            }
         }
 
-        let node_conf_path = self.resolve_node_config_path(&name);
-        debug!(
-            path = node_conf_path.display().to_string(),
-            "Getting current node config"
-        );
+        Ok(())
+    }
 
-        let config = NodeConfig::load(&node_conf_path)
-            .await
-            .map_err(|err| NodeStoreError::OtherError { err })?;
+    /// Replaces the keypair of the node with a new one and appends the rotation linking
+    /// them to the history in the config. A protected node must be unlocked and its
+    /// passphrase given, the new keypair is encrypted with it
+    #[message]
+    pub async fn rotate_key(
+        &mut self,
+        name: String,
+        passphrase: Option<String>,
+    ) -> Result<RotationObject, NodeStoreError> {
+        let node_snapshot = self.load_node(name.clone()).await?;
+        let mut config = node_snapshot.config.clone();
+        let old_keypair = node_snapshot.keypair;
+        let new_keypair = Keypair::generate_ed25519();
+        let rotation = RotationObject::rotate(&old_keypair, &new_keypair)?;
+
+        let (key_bytes, public_key) = match config.key_protected {
+            true => {
+                let passphrase =
+                    passphrase.ok_or(anyhow!("the passphrase of a protected node is required"))?;
+                // The passphrase must be the current one
+                let stored = self.load_record(&name).await?;
+                identity::decrypt_keypair(&stored.keypair, &passphrase)?;
+
+                (
+                    identity::encrypt_keypair(&new_keypair, &passphrase)?,
+                    Some(new_keypair.public().encode_protobuf()),
+                )
+            }
+            false => (Self::encode_plain_key(&new_keypair)?, None),
+        };
+
+        // The new keypair is stored together with the rotation, or not at all
+        config.key_rotations.push(rotation.clone());
+        self.db.save(&name, &config, key_bytes, public_key).await?;
+        if config.key_protected {
+            self.unlocked_keys.insert(name, new_keypair);
+        }
 
-        Ok(config)
+        Ok(rotation)
     }
 
+    /// Gets the peer ID of the node, which is known also when the node is locked
     #[message]
-    pub async fn overwrite_node_config(
-        &self,
+    pub async fn get_node_peer_id(&self, name: String) -> Result<PeerId, NodeStoreError> {
+        let node = self.load_record(&name).await?;
+        let public_key = match node.config.key_protected {
+            false => Keypair::from_protobuf_encoding(&node.keypair)
+                .context("could not read keypair from protobuf encoded bytes")?
+                .public(),
+            true => {
+                let public_key_bytes = node
+                    .public_key
+                    .ok_or(anyhow!("the public key of a protected node is missing"))?;
+                PublicKey::try_decode_protobuf(&public_key_bytes)
+                    .context("could not read public key from protobuf encoded bytes")?
+            }
+        };
+
+        Ok(PeerId::from_public_key(&public_key))
+    }
+
+    /// Checks if the node is protected and its keypair was not decrypted yet
+    #[message]
+    pub async fn is_node_locked(&self, name: String) -> Result<bool, NodeStoreError> {
+        let node = self.load_record(&name).await?;
+        Ok(node.config.key_protected && !self.unlocked_keys.contains_key(&name))
+    }
+
+    /// Renames the node and moves its directory, together with the vault, to the
+    /// new name
+    #[message]
+    pub async fn rename_node(
+        &mut self,
         name: String,
-        new_cfg: NodeConfig,
+        new_name: String,
     ) -> Result<(), NodeStoreError> {
-        if !self.node_exists(&name) {
+        if !self.db.exists(&name).await? {
             return Err(NodeStoreError::NodeDoesNotExist);
         }
+        let node_dir_path = self.resolve_node_dir_path(&name);
+        let new_node_dir_path = self.resolve_node_dir_path(&new_name);
+        if self.db.exists(&new_name).await? || new_node_dir_path.exists() {
+            return Err(NodeStoreError::NodeAlreadyExists);
+        }
 
-        let node_conf_path = self.resolve_node_config_path(&name);
+        debug!(name = name, new_name = new_name, "renaming node");
+        let moved = node_dir_path.exists();
+        if moved {
+            tokio::fs::rename(&node_dir_path, &new_node_dir_path)
+                .await
+                .context("could not rename node dir")?;
+        }
+        if let Err(e) = self.db.rename(&name, &new_name).await {
+            // The directory stays with the node
+            if moved {
+                let _ = tokio::fs::rename(&new_node_dir_path, &node_dir_path).await;
+            }
+            return Err(db_error(e));
+        }
 
-        new_cfg
-            .save(&node_conf_path)
-            .await
-            .map_err(|err| NodeStoreError::OtherError { err })?;
+        if let Some(keypair) = self.unlocked_keys.remove(&name) {
+            self.unlocked_keys.insert(new_name, keypair);
+        }
 
         Ok(())
     }
 
+    /// Removes the node and its directory. If `keep_vault` is set only the config and
+    /// the keys are removed, the vault is reused by a new node created with the same
+    /// name by the same owner
     #[message]
-    pub async fn list_nodes(&self) -> Result<Vec<String>, NodeStoreError> {
-        let mut names = Vec::new();
-        let mut dir = tokio::fs::read_dir(&self.store_dir_path).await.unwrap();
-        while let Some(dir) = dir.next_entry().await.unwrap() {
-            if dir.path().is_dir() {
-                if let Some(name) = dir.file_name().to_str() {
-                    names.push(name.to_string());
-                }
-            }
+    pub async fn delete_node(
+        &mut self,
+        name: String,
+        keep_vault: bool,
+    ) -> Result<(), NodeStoreError> {
+        let Some(node) = self.db.load(&name).await? else {
+            return Err(NodeStoreError::NodeDoesNotExist);
+        };
+
+        let node_dir_path = self.resolve_node_dir_path(&name);
+        debug!(
+            name = name,
+            path = node_dir_path.display().to_string(),
+            keep_vault = keep_vault,
+            "deleting node"
+        );
+
+        // The owner goes away with the record, the kept vault remembers it
+        if keep_vault && node_dir_path.exists() {
+            let owner = node
+                .owner
+                .unwrap_or_else(|| nix::unistd::geteuid().as_raw());
+            atomic_file::write(
+                &node_dir_path.join(Self::KEPT_VAULT_OWNER_FILE_NAME),
+                owner.to_string(),
+            )
+            .await
+            .context("could not record the owner of the kept vault")?;
         }
+        self.db.delete(&name).await?;
+        if !keep_vault && node_dir_path.exists() {
+            tokio::fs::remove_dir_all(node_dir_path)
+                .await
+                .context("could not remove node dir")?;
+        }
+
+        self.unlocked_keys.remove(&name);
 
-        Ok(names)
+        Ok(())
+    }
+
+    #[message]
+    pub async fn list_nodes(&self) -> Result<Vec<String>, NodeStoreError> {
+        Ok(self.db.names().await?)
     }
 
     #[message]
     pub async fn get_node_vault(&self, name: String) -> Result<Vault> {
-        Vault::new_on_disk(&self.resolve_node_dir_path(&name)).await
+        // The node being created has no config yet
+        let compression = match self.db.load(&name).await? {
+            Some(node) => node.config.compression,
+            None => CompressionConfig::default(),
+        };
+        Vault::new_on_disk_with_compression(&self.resolve_node_dir_path(&name), compression).await
+    }
+
+    /// The UID of the user owning the node, None if the node doesn't exist. The
+    /// nodes without a recorded owner belong to the user running the daemon
+    #[message]
+    pub async fn get_node_owner(&self, name: String) -> Result<Option<u32>, NodeStoreError> {
+        let node = self.db.load(&name).await?;
+        Ok(node.map(|node| {
+            node.owner
+                .unwrap_or_else(|| nix::unistd::geteuid().as_raw())
+        }))
     }
 }
 
 impl NodeStore {
     const DEFAULT_NODES_DIRECTORY_NAME: &'static str = ".liberum-neto";
-    const NODE_CONFIG_FILE_NAME: &'static str = "config.json";
-    const NODE_KEY_FILE_NAME: &'static str = "keypair";
+    // The files of a node stored by the older versions, moved into the database
+    const LEGACY_CONFIG_FILE_NAME: &'static str = "config.json";
+    const LEGACY_KEY_FILE_NAME: &'static str = "keypair";
+    const LEGACY_PUBLIC_KEY_FILE_NAME: &'static str = "public_key";
+    const LEGACY_OWNER_FILE_NAME: &'static str = "owner";
+    // The owner of the vault kept by a deleted node
+    const KEPT_VAULT_OWNER_FILE_NAME: &'static str = "kept_vault_owner";
 
     pub async fn new(store_dir_path: &Path) -> Result<Self> {
         NodeStore::ensure_store_dir_path(store_dir_path)
@@ -196,9 +444,24 @@ impl NodeStore {
                     "failed to ensure store dir"
                 )
             })?;
-        Ok(NodeStore {
+        let db = NodeDatabase::open(store_dir_path)
+            .await
+            .inspect_err(|e| error!(err = e.to_string(), "failed to open node database"))?;
+        let node_store = NodeStore {
             store_dir_path: store_dir_path.to_path_buf(),
-        })
+            db,
+            unlocked_keys: HashMap::new(),
+        };
+        node_store
+            .migrate_node_dirs()
+            .await
+            .inspect_err(|e| error!(err = e.to_string(), "failed to migrate nodes"))?;
+        node_store
+            .remove_orphan_files()
+            .await
+            .inspect_err(|e| error!(err = e.to_string(), "failed to remove orphan files"))?;
+
+        Ok(node_store)
     }
 
     pub async fn with_default_nodes_dir() -> Result<Self> {
@@ -222,9 +485,159 @@ impl NodeStore {
             .inspect_err(|e| error!(err = e.to_string(), "could not create a node store"))
     }
 
-    fn node_exists(&self, name: &str) -> bool {
-        let node_dir_path = self.resolve_node_dir_path(&name);
-        node_dir_path.exists()
+    async fn load_record(&self, name: &str) -> Result<NodeRecord, NodeStoreError> {
+        self.db
+            .load(name)
+            .await?
+            .ok_or(NodeStoreError::NodeDoesNotExist)
+    }
+
+    fn encode_plain_key(keypair: &Keypair) -> Result<Vec<u8>> {
+        keypair
+            .to_protobuf_encoding()
+            .inspect_err(|e| error!(err = e.to_string(), "could not convert keypair to bytes"))
+            .context("could not convert keypair to bytes")
+    }
+
+    /// Moves the nodes stored in the files of their directories by the older versions
+    /// into the database, all in one transaction. The files are removed once the
+    /// database has them, the vaults stay in the directories
+    async fn migrate_node_dirs(&self) -> Result<()> {
+        let mut nodes = Vec::new();
+        let mut dir = tokio::fs::read_dir(&self.store_dir_path).await?;
+        while let Some(entry) = dir.next_entry().await? {
+            let Some(name) = entry.file_name().to_str().map(str::to_string) else {
+                continue;
+            };
+            match Self::read_node_dir(&entry.path()).await {
+                Ok(Some(node)) => nodes.push((name, node)),
+                Ok(None) => {}
+                Err(e) => warn!(
+                    name = name,
+                    err = e.to_string(),
+                    "could not read node files"
+                ),
+            }
+        }
+        if nodes.is_empty() {
+            return Ok(());
+        }
+
+        let count = nodes.len();
+        let migrated = self.db.insert_all(nodes).await?;
+        for name in &migrated {
+            let node_dir_path = self.resolve_node_dir_path(name);
+            for file_name in [
+                Self::LEGACY_CONFIG_FILE_NAME,
+                Self::LEGACY_KEY_FILE_NAME,
+                Self::LEGACY_PUBLIC_KEY_FILE_NAME,
+                Self::LEGACY_OWNER_FILE_NAME,
+            ] {
+                match tokio::fs::remove_file(node_dir_path.join(file_name)).await {
+                    Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                        return Err(anyhow!(e).context("could not remove migrated node file"))
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        info!(count = migrated.len(), "migrated nodes into the database");
+        if migrated.len() < count {
+            warn!(
+                count = count - migrated.len(),
+                "nodes with the names already in the database were not migrated"
+            );
+        }
+        Ok(())
+    }
+
+    /// Removes the temporary files of the writes interrupted by a crash from the
+    /// directories of the nodes
+    async fn remove_orphan_files(&self) -> Result<()> {
+        let mut removed = atomic_file::remove_orphans(&self.store_dir_path).await?;
+        for name in self.db.names().await? {
+            let node_dir_path = self.resolve_node_dir_path(&name);
+            if node_dir_path.is_dir() {
+                removed += atomic_file::remove_orphans(&node_dir_path).await?;
+            }
+        }
+        if removed > 0 {
+            info!(count = removed, "removed orphan temporary files");
+        }
+
+        Ok(())
+    }
+
+    /// The node stored in the files of the directory, None if there is none
+    async fn read_node_dir(node_dir_path: &Path) -> Result<Option<NodeRecord>> {
+        let config_path = node_dir_path.join(Self::LEGACY_CONFIG_FILE_NAME);
+        let key_path = node_dir_path.join(Self::LEGACY_KEY_FILE_NAME);
+        // A directory without the config may be the vault left by a deleted node
+        if !config_path.is_file() || !key_path.is_file() {
+            return Ok(None);
+        }
+
+        let config = NodeConfig::load(&config_path).await?;
+        let keypair = tokio::fs::read(key_path).await?;
+        let public_key =
+            match tokio::fs::read(node_dir_path.join(Self::LEGACY_PUBLIC_KEY_FILE_NAME)).await {
+                Ok(public_key) => Some(public_key),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+                Err(e) => return Err(e.into()),
+            };
+        let owner =
+            match tokio::fs::read_to_string(node_dir_path.join(Self::LEGACY_OWNER_FILE_NAME)).await
+            {
+                Ok(uid) => Some(uid.trim().parse().context("could not parse node owner")?),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+                Err(e) => return Err(e.into()),
+            };
+
+        Ok(Some(NodeRecord {
+            config,
+            keypair,
+            public_key,
+            owner,
+        }))
+    }
+
+    /// Checks that the vault left under the name of a new node, if any, belongs to
+    /// the creator of the node, who takes it over. A vault kept without its owner
+    /// recorded, by the older versions, belongs to the user running the daemon.
+    /// Without the creator the node is created by the daemon itself
+    async fn claim_kept_vault(
+        &self,
+        name: &str,
+        creator: Option<u32>,
+    ) -> Result<(), NodeStoreError> {
+        let node_dir_path = self.resolve_node_dir_path(name);
+        let marker_path = node_dir_path.join(Self::KEPT_VAULT_OWNER_FILE_NAME);
+        let owner = match tokio::fs::read_to_string(&marker_path).await {
+            Ok(owner) => owner
+                .trim()
+                .parse::<u32>()
+                .context("could not parse the owner of the kept vault")?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let kept = match tokio::fs::read_dir(&node_dir_path).await {
+                    Ok(mut entries) => entries.next_entry().await.ok().flatten().is_some(),
+                    Err(_) => false,
+                };
+                if !kept {
+                    return Ok(());
+                }
+                nix::unistd::geteuid().as_raw()
+            }
+            Err(e) => return Err(anyhow!(e).into()),
+        };
+
+        if creator.is_some_and(|creator| creator != owner) {
+            return Err(NodeStoreError::VaultOwnedByOther);
+        }
+        match tokio::fs::remove_file(&marker_path).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(anyhow!(e).into()),
+            _ => Ok(()),
+        }
     }
 
     async fn ensure_node_dir_path(&self, name: &str) -> Result<PathBuf> {
@@ -247,18 +660,17 @@ impl NodeStore {
         self.store_dir_path.join(name)
     }
 
-    fn resolve_node_config_path(&self, name: &str) -> PathBuf {
-        let node_dir_path = self.resolve_node_dir_path(name);
-
-        node_dir_path.join(Self::NODE_CONFIG_FILE_NAME)
-    }
-
     async fn ensure_store_dir_path(path: &Path) -> Result<()> {
         debug!(path = path.display().to_string(), "ensuring store dir");
         tokio::fs::create_dir_all(path).await?;
         Ok(())
     }
 
+    /// The directory of the nodes, used when no other is given
+    pub fn default_nodes_dir() -> Result<PathBuf> {
+        Self::resolve_store_dir_path(None)
+    }
+
     fn resolve_store_dir_path(path_override: Option<&Path>) -> Result<PathBuf> {
         let home_dir_path = homedir::my_home()?.ok_or(anyhow!("no home directory"))?;
         let store_dir_name =
@@ -269,6 +681,7 @@ impl NodeStore {
 
 #[cfg(test)]
 mod tests {
+    use kameo::error::SendError;
     use kameo::request::MessageSend;
     use libp2p::identity::Keypair;
     use tempdir::TempDir;
@@ -306,6 +719,257 @@ mod tests {
         assert_eq!(got_node_name, "test_node");
     }
 
+    #[tokio::test]
+    async fn protected_key_test() {
+        let tmp_dir = TempDir::new("liberum_tests").unwrap();
+        let node_store = NodeStore::with_custom_nodes_dir(tmp_dir.path())
+            .await
+            .unwrap();
+        let node_store = kameo::spawn(node_store);
+        let keypair = Keypair::generate_ed25519();
+        let node_snapshot = NodeSnapshot::builder()
+            .name("test_node".to_string())
+            .keypair(keypair.clone())
+            .build_snapshot()
+            .unwrap();
+        let name = "test_node".to_string();
+
+        node_store
+            .ask(StoreNode { node_snapshot })
+            .send()
+            .await
+            .unwrap();
+        node_store
+            .ask(SetKeyPassphrase {
+                name: name.clone(),
+                passphrase: Some("passphrase".to_string()),
+            })
+            .send()
+            .await
+            .unwrap();
+
+        // A new store, like after the daemon restarts
+        let node_store = NodeStore::with_custom_nodes_dir(tmp_dir.path())
+            .await
+            .unwrap();
+        let node_store = kameo::spawn(node_store);
+        let locked = node_store.ask(LoadNode { name: name.clone() }).send().await;
+        assert!(matches!(
+            locked,
+            Err(SendError::HandlerError(NodeStoreError::NodeLocked))
+        ));
+        let peer_id = node_store
+            .ask(GetNodePeerId { name: name.clone() })
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(peer_id, PeerId::from_public_key(&keypair.public()));
+
+        let wrong = node_store
+            .ask(UnlockNode {
+                name: name.clone(),
+                passphrase: "wrong".to_string(),
+            })
+            .send()
+            .await;
+        assert!(wrong.is_err());
+        node_store
+            .ask(UnlockNode {
+                name: name.clone(),
+                passphrase: "passphrase".to_string(),
+            })
+            .send()
+            .await
+            .unwrap();
+
+        let loaded = node_store.ask(LoadNode { name }).send().await.unwrap();
+        assert_eq!(loaded.keypair.public(), keypair.public());
+    }
+
+    #[tokio::test]
+    async fn rename_delete_test() {
+        let tmp_dir = TempDir::new("liberum_tests").unwrap();
+        let node_store = NodeStore::with_custom_nodes_dir(tmp_dir.path())
+            .await
+            .unwrap();
+        let node_store = kameo::spawn(node_store);
+        let node_snapshot = NodeSnapshot::builder()
+            .name("test_node".to_string())
+            .keypair(Keypair::generate_ed25519())
+            .build_snapshot()
+            .unwrap();
+
+        node_store
+            .ask(StoreNode { node_snapshot })
+            .send()
+            .await
+            .unwrap();
+        node_store
+            .ask(RenameNode {
+                name: "test_node".to_string(),
+                new_name: "renamed_node".to_string(),
+            })
+            .send()
+            .await
+            .unwrap();
+        let names = node_store.ask(ListNodes).send().await.unwrap();
+        assert_eq!(names, vec!["renamed_node".to_string()]);
+
+        node_store
+            .ask(DeleteNode {
+                name: "renamed_node".to_string(),
+                keep_vault: true,
+            })
+            .send()
+            .await
+            .unwrap();
+        let names = node_store.ask(ListNodes).send().await.unwrap();
+        assert!(names.is_empty());
+        assert!(tmp_dir.path().join("renamed_node").exists());
+    }
+
+    #[tokio::test]
+    async fn kept_vault_test() {
+        let tmp_dir = TempDir::new("liberum_tests").unwrap();
+        let node_store = NodeStore::with_custom_nodes_dir(tmp_dir.path())
+            .await
+            .unwrap();
+        let node_store = kameo::spawn(node_store);
+        let name = "test_node".to_string();
+        let snapshot = |owner: u32| {
+            NodeSnapshot::builder()
+                .name(name.clone())
+                .keypair(Keypair::generate_ed25519())
+                .owner(Some(owner))
+                .build_snapshot()
+                .unwrap()
+        };
+
+        node_store
+            .ask(StoreNode {
+                node_snapshot: snapshot(1001),
+            })
+            .send()
+            .await
+            .unwrap();
+        node_store
+            .ask(DeleteNode {
+                name: name.clone(),
+                keep_vault: true,
+            })
+            .send()
+            .await
+            .unwrap();
+
+        let taken = node_store
+            .ask(StoreNode {
+                node_snapshot: snapshot(1002),
+            })
+            .send()
+            .await;
+        assert!(matches!(
+            taken,
+            Err(SendError::HandlerError(NodeStoreError::VaultOwnedByOther))
+        ));
+        assert!(node_store.ask(ListNodes).send().await.unwrap().is_empty());
+
+        node_store
+            .ask(StoreNode {
+                node_snapshot: snapshot(1001),
+            })
+            .send()
+            .await
+            .unwrap();
+        let owner = node_store.ask(GetNodeOwner { name }).send().await.unwrap();
+        assert_eq!(owner, Some(1001));
+        assert!(!tmp_dir
+            .path()
+            .join("test_node")
+            .join(NodeStore::KEPT_VAULT_OWNER_FILE_NAME)
+            .exists());
+    }
+
+    #[tokio::test]
+    async fn rotate_key_test() {
+        let tmp_dir = TempDir::new("liberum_tests").unwrap();
+        let node_store = NodeStore::with_custom_nodes_dir(tmp_dir.path())
+            .await
+            .unwrap();
+        let node_store = kameo::spawn(node_store);
+        let keypair = Keypair::generate_ed25519();
+        let node_snapshot = NodeSnapshot::builder()
+            .name("test_node".to_string())
+            .keypair(keypair.clone())
+            .build_snapshot()
+            .unwrap();
+        let name = "test_node".to_string();
+
+        node_store
+            .ask(StoreNode { node_snapshot })
+            .send()
+            .await
+            .unwrap();
+        let rotation = node_store
+            .ask(RotateKey {
+                name: name.clone(),
+                passphrase: None,
+            })
+            .send()
+            .await
+            .unwrap();
+
+        let loaded = node_store.ask(LoadNode { name }).send().await.unwrap();
+        let (old_key, new_key) = rotation.verify().unwrap();
+        assert_eq!(old_key, keypair.public());
+        assert_eq!(new_key, loaded.keypair.public());
+        assert_eq!(loaded.config.key_rotations.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn migration_test() {
+        let tmp_dir = TempDir::new("liberum_tests").unwrap();
+        let keypair = Keypair::generate_ed25519();
+        let node_dir_path = tmp_dir.path().join("test_node");
+        tokio::fs::create_dir(&node_dir_path).await.unwrap();
+        NodeConfig::default()
+            .save(&node_dir_path.join(NodeStore::LEGACY_CONFIG_FILE_NAME))
+            .await
+            .unwrap();
+        tokio::fs::write(
+            node_dir_path.join(NodeStore::LEGACY_KEY_FILE_NAME),
+            keypair.to_protobuf_encoding().unwrap(),
+        )
+        .await
+        .unwrap();
+        tokio::fs::write(
+            node_dir_path.join(NodeStore::LEGACY_OWNER_FILE_NAME),
+            "1001",
+        )
+        .await
+        .unwrap();
+        // The vault left by a deleted node is not a node
+        tokio::fs::create_dir(tmp_dir.path().join("vault_only"))
+            .await
+            .unwrap();
+
+        let node_store = NodeStore::with_custom_nodes_dir(tmp_dir.path())
+            .await
+            .unwrap();
+        let node_store = kameo::spawn(node_store);
+        let names = node_store.ask(ListNodes).send().await.unwrap();
+        assert_eq!(names, vec!["test_node".to_string()]);
+        let name = "test_node".to_string();
+        let loaded = node_store
+            .ask(LoadNode { name: name.clone() })
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(loaded.keypair.public(), keypair.public());
+        let owner = node_store.ask(GetNodeOwner { name }).send().await.unwrap();
+        assert_eq!(owner, Some(1001));
+        assert!(!node_dir_path.join(NodeStore::LEGACY_KEY_FILE_NAME).exists());
+    }
+
     #[tokio::test]
     #[should_panic]
     async fn test_not_directory() {
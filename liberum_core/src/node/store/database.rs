@@ -0,0 +1,246 @@
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use liberum_core::node_config::NodeConfig;
+use rusqlite::OptionalExtension;
+use thiserror::Error;
+use tokio_rusqlite::Connection;
+
+///! The module contains the database of the node store. Every node is a single row
+///! with its config, its keypair, encrypted if the node is protected, and its owner,
+///! so a change of the node is written at once, e.g. a rotated keypair never ends up
+///! stored with the old config. The vault, the logs and the certificates of a node
+///! stay in its directory.
+
+/// A node as stored in the database
+#[derive(Debug, Clone)]
+pub struct NodeRecord {
+    pub config: NodeConfig,
+    /// The protobuf encoding of the keypair, or the encrypted one if the node is
+    /// protected
+    pub keypair: Vec<u8>,
+    /// Only stored for the protected nodes, their peer ID is known while locked
+    pub public_key: Option<Vec<u8>>,
+    /// None for the nodes of the user running the daemon
+    pub owner: Option<u32>,
+}
+
+/// An update of a node which is not in the database
+#[derive(Error, Debug)]
+#[error("node {0} is not in the database")]
+pub struct NodeNotFound(pub String);
+
+#[derive(Debug)]
+pub struct NodeDatabase {
+    db: Connection,
+}
+
+const CREATE_NODE_TABLE_QUERY: &str = "
+    CREATE TABLE IF NOT EXISTS node (
+        name TEXT NOT NULL PRIMARY KEY,
+        config TEXT NOT NULL,
+        keypair BLOB NOT NULL,
+        public_key BLOB,
+        owner INTEGER
+    )
+";
+const SELECT_NODE_QUERY: &str =
+    "SELECT config, keypair, public_key, owner FROM node WHERE name = ?1";
+const SELECT_NODE_NAMES_QUERY: &str = "SELECT name FROM node ORDER BY name";
+const SELECT_NODE_EXISTS_QUERY: &str = "SELECT EXISTS (SELECT 1 FROM node WHERE name = ?1)";
+const INSERT_NODE_QUERY: &str = "
+    INSERT INTO node (name, config, keypair, public_key, owner) VALUES (?1, ?2, ?3, ?4, ?5)
+    ON CONFLICT (name) DO NOTHING
+";
+const UPSERT_NODE_QUERY: &str = "
+    INSERT INTO node (name, config, keypair, public_key) VALUES (?1, ?2, ?3, ?4)
+    ON CONFLICT (name) DO UPDATE SET
+        config = excluded.config,
+        keypair = excluded.keypair,
+        public_key = excluded.public_key
+";
+const UPDATE_NODE_CONFIG_QUERY: &str = "UPDATE node SET config = ?2 WHERE name = ?1";
+const UPDATE_NODE_NAME_QUERY: &str = "UPDATE node SET name = ?2 WHERE name = ?1";
+const UPDATE_NODE_OWNER_QUERY: &str = "UPDATE node SET owner = ?2 WHERE name = ?1";
+const DELETE_NODE_QUERY: &str = "DELETE FROM node WHERE name = ?1";
+
+impl NodeDatabase {
+    pub const DATABASE_NAME: &'static str = "nodes.db3";
+    const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+    pub async fn open(store_dir_path: &Path) -> Result<NodeDatabase> {
+        let db = Connection::open(store_dir_path.join(Self::DATABASE_NAME)).await?;
+        db.call(|conn| {
+            conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| {
+                row.get::<_, String>(0)
+            })?;
+            conn.busy_timeout(Self::BUSY_TIMEOUT)?;
+            conn.execute(CREATE_NODE_TABLE_QUERY, ())?;
+
+            Ok(())
+        })
+        .await
+        .map_err(|e| anyhow!(e))?;
+
+        Ok(NodeDatabase { db })
+    }
+
+    pub async fn exists(&self, name: &str) -> Result<bool> {
+        let name = name.to_string();
+        self.db
+            .call(move |conn| {
+                Ok(conn.query_row(SELECT_NODE_EXISTS_QUERY, [name], |row| row.get(0))?)
+            })
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+
+    pub async fn names(&self) -> Result<Vec<String>> {
+        self.db
+            .call(|conn| {
+                let mut stmt = conn.prepare(SELECT_NODE_NAMES_QUERY)?;
+                let names = stmt
+                    .query_map((), |row| row.get(0))?
+                    .collect::<Result<Vec<String>, _>>()?;
+
+                Ok(names)
+            })
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+
+    pub async fn load(&self, name: &str) -> Result<Option<NodeRecord>> {
+        let name = name.to_string();
+        let row = self
+            .db
+            .call(move |conn| {
+                Ok(conn
+                    .query_row(SELECT_NODE_QUERY, [name], |row| {
+                        Ok((
+                            row.get::<_, String>(0)?,
+                            row.get::<_, Vec<u8>>(1)?,
+                            row.get::<_, Option<Vec<u8>>>(2)?,
+                            row.get::<_, Option<u32>>(3)?,
+                        ))
+                    })
+                    .optional()?)
+            })
+            .await
+            .map_err(|e| anyhow!(e))?;
+
+        let Some((config, keypair, public_key, owner)) = row else {
+            return Ok(None);
+        };
+        Ok(Some(NodeRecord {
+            config: serde_json::from_str(&config)?,
+            keypair,
+            public_key,
+            owner,
+        }))
+    }
+
+    /// Stores the config and the keys of the node, the owner of an existing node is
+    /// kept
+    pub async fn save(
+        &self,
+        name: &str,
+        config: &NodeConfig,
+        keypair: Vec<u8>,
+        public_key: Option<Vec<u8>>,
+    ) -> Result<()> {
+        let name = name.to_string();
+        let config = serde_json::to_string(config)?;
+        self.db
+            .call(move |conn| {
+                conn.execute(UPSERT_NODE_QUERY, (name, config, keypair, public_key))?;
+                Ok(())
+            })
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+
+    /// Replaces the config of the node, fails with `NodeNotFound` if it doesn't exist
+    pub async fn save_config(&self, name: &str, config: &NodeConfig) -> Result<()> {
+        let name = name.to_string();
+        let config = serde_json::to_string(config)?;
+        self.update(UPDATE_NODE_CONFIG_QUERY, name, config).await
+    }
+
+    /// Inserts the nodes in a single transaction, the ones with the names already
+    /// taken are left out. Returns the names of the inserted nodes
+    pub async fn insert_all(&self, nodes: Vec<(String, NodeRecord)>) -> Result<Vec<String>> {
+        let rows = nodes
+            .into_iter()
+            .map(|(name, node)| {
+                let config = serde_json::to_string(&node.config)?;
+                Ok((name, config, node.keypair, node.public_key, node.owner))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        self.db
+            .call(|conn| {
+                let tx = conn.transaction()?;
+                let mut inserted = Vec::new();
+                for (name, config, keypair, public_key, owner) in rows {
+                    if tx.execute(
+                        INSERT_NODE_QUERY,
+                        (&name, config, keypair, public_key, owner),
+                    )? > 0
+                    {
+                        inserted.push(name);
+                    }
+                }
+                tx.commit()?;
+
+                Ok(inserted)
+            })
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+
+    pub async fn rename(&self, name: &str, new_name: &str) -> Result<()> {
+        self.update(
+            UPDATE_NODE_NAME_QUERY,
+            name.to_string(),
+            new_name.to_string(),
+        )
+        .await
+    }
+
+    pub async fn set_owner(&self, name: &str, uid: u32) -> Result<()> {
+        self.update(UPDATE_NODE_OWNER_QUERY, name.to_string(), uid)
+            .await
+    }
+
+    pub async fn delete(&self, name: &str) -> Result<()> {
+        let name = name.to_string();
+        self.db
+            .call(move |conn| {
+                conn.execute(DELETE_NODE_QUERY, [name])?;
+                Ok(())
+            })
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+
+    /// Runs an update of a single node, fails with `NodeNotFound` if the node
+    /// doesn't exist
+    async fn update<T>(&self, query: &'static str, name: String, value: T) -> Result<()>
+    where
+        T: rusqlite::ToSql + Send + 'static,
+    {
+        let updated = self
+            .db
+            .call({
+                let name = name.clone();
+                move |conn| Ok(conn.execute(query, (name, value))?)
+            })
+            .await
+            .map_err(|e| anyhow!(e))?;
+        match updated {
+            0 => Err(NodeNotFound(name).into()),
+            _ => Ok(()),
+        }
+    }
+}
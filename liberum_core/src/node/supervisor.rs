@@ -0,0 +1,155 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+
+use liberum_core::node_config::{RestartConfig, RestartPolicy};
+
+///! The module decides which of the stopped nodes the node manager restarts. A node
+///! stopped on request is never restarted. The others are restarted by their restart
+///! policies, the sooner ones with the shorter delays. A node restarted too many
+///! times in the window is left stopped, so a node failing at start doesn't loop.
+
+/// Why the node stopped
+#[derive(Debug, Clone, PartialEq)]
+pub enum Exit {
+    /// It was asked to stop
+    Requested,
+    /// It stopped by itself without a failure
+    Clean,
+    /// It crashed for the reason
+    Failed(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Restart {
+    /// Stopped on request or the policy doesn't restart it
+    No,
+    /// Restarted after the delay
+    After(Duration),
+    /// Restarted too many times in the window
+    GaveUp,
+}
+
+#[derive(Debug, Default)]
+pub struct Supervisor {
+    /// The nodes being stopped on request
+    stopping: HashSet<String>,
+    /// The failures reported by the nodes before they stopped
+    failures: HashMap<String, String>,
+    /// When the nodes were restarted within their windows, the oldest first
+    restarts: HashMap<String, VecDeque<Instant>>,
+}
+
+impl Supervisor {
+    /// Marks the node as stopped on request
+    pub fn stopping(&mut self, name: &str) {
+        self.stopping.insert(name.to_string());
+    }
+
+    /// Records the failure of the node, which is stopping because of it
+    pub fn failed(&mut self, name: &str, reason: String) {
+        self.failures.insert(name.to_string(), reason);
+    }
+
+    /// Why the node stopped, the actor crashed if there is a reason for it
+    pub fn exit(&mut self, name: &str, crash: Option<String>) -> Exit {
+        let failure = self.failures.remove(name);
+        if self.stopping.remove(name) {
+            return Exit::Requested;
+        }
+        match failure.or(crash) {
+            Some(reason) => Exit::Failed(reason),
+            None => Exit::Clean,
+        }
+    }
+
+    /// Whether and when the stopped node is restarted
+    pub fn next_restart(
+        &mut self,
+        name: &str,
+        exit: &Exit,
+        config: &RestartConfig,
+        now: Instant,
+    ) -> Restart {
+        let restarted = match (exit, config.policy) {
+            (Exit::Requested, _) | (_, RestartPolicy::Never) => false,
+            (Exit::Clean, RestartPolicy::OnFailure) => false,
+            _ => true,
+        };
+        if !restarted {
+            return Restart::No;
+        }
+
+        let window = Duration::from_secs(config.window_secs);
+        let restarts = self.restarts.entry(name.to_string()).or_default();
+        while restarts.front().is_some_and(|at| *at + window <= now) {
+            restarts.pop_front();
+        }
+        if restarts.len() >= config.max_restarts as usize {
+            return Restart::GaveUp;
+        }
+
+        let backoff = config
+            .initial_backoff_ms
+            .saturating_mul(1u64 << restarts.len().min(32))
+            .min(config.max_backoff_ms);
+        restarts.push_back(now);
+        Restart::After(Duration::from_millis(backoff))
+    }
+
+    /// Forgets the node, e.g. when it's deleted or renamed
+    pub fn forget(&mut self, name: &str) {
+        self.stopping.remove(name);
+        self.failures.remove(name);
+        self.restarts.remove(name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restart_test() {
+        let config = RestartConfig {
+            policy: RestartPolicy::OnFailure,
+            initial_backoff_ms: 100,
+            max_backoff_ms: 300,
+            max_restarts: 3,
+            window_secs: 60,
+        };
+        let mut supervisor = Supervisor::default();
+        let start = Instant::now();
+
+        supervisor.stopping("a");
+        let exit = supervisor.exit("a", Some("panicked".to_string()));
+        assert_eq!(exit, Exit::Requested);
+        assert_eq!(
+            supervisor.next_restart("a", &exit, &config, start),
+            Restart::No
+        );
+        assert_eq!(supervisor.exit("a", None), Exit::Clean);
+
+        supervisor.failed("a", "swarm died".to_string());
+        let exit = supervisor.exit("a", None);
+        assert_eq!(exit, Exit::Failed("swarm died".to_string()));
+        let delays: Vec<Restart> = (0..4)
+            .map(|_| supervisor.next_restart("a", &exit, &config, start))
+            .collect();
+        assert_eq!(
+            delays,
+            [
+                Restart::After(Duration::from_millis(100)),
+                Restart::After(Duration::from_millis(200)),
+                Restart::After(Duration::from_millis(300)),
+                Restart::GaveUp,
+            ]
+        );
+
+        // The restarts out of the window don't count
+        let later = start + Duration::from_secs(60);
+        assert_eq!(
+            supervisor.next_restart("a", &exit, &config, later),
+            Restart::After(Duration::from_millis(100))
+        );
+    }
+}
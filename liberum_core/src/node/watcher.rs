@@ -0,0 +1,262 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::Result;
+use kameo::actor::WeakActorRef;
+use kameo::request::MessageSend;
+use liberum_core::node_config::WatchDir;
+use liberum_core::owned_file;
+use liberum_core::types::ObjectAccess;
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use tracing::{debug, warn};
+
+use super::{DeleteObject, Node, PublishFile};
+
+///! The module contains the watcher of the directories from the config of the node,
+///! which publishes their files when they are created or changed. The events of a
+///! file are debounced, it's published once it hasn't changed for a while, so a
+///! file being copied is not published in parts.
+///!
+///! The files already in the directories are published when the watching starts, as
+///! they could change while the node was stopped. Publishing a file again is cheap
+///! if it didn't change, it has the same ID. The IDs of the published files are kept
+///! only in memory, so the old version of a file changed while the node was stopped
+///! is not unpublished.
+
+/// How often the changed files are checked for being ready to publish
+const CHECK_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Watches the directories until it's dropped
+pub struct FolderWatcher {
+    _watcher: RecommendedWatcher,
+    task: JoinHandle<()>,
+}
+
+impl Drop for FolderWatcher {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Starts watching the directories. The directories which can't be watched, e.g.
+/// removed ones, are skipped. With the owner of the node, only the files owned by
+/// the owner are published
+pub fn start(
+    node_name: String,
+    dirs: Vec<WatchDir>,
+    owner: Option<u32>,
+    debounce: Duration,
+    node_ref: WeakActorRef<Node>,
+) -> Result<FolderWatcher> {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        match event {
+            Ok(event) if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) => {
+                for path in event.paths {
+                    // Fails only when the watcher is being dropped
+                    let _ = sender.send(path);
+                }
+            }
+            Ok(_) => {}
+            Err(e) => warn!(err = e.to_string(), "Failed to watch directory"),
+        }
+    })?;
+
+    for dir in &dirs {
+        if let Err(e) = watcher.watch(&dir.path, RecursiveMode::Recursive) {
+            warn!(
+                node = node_name,
+                path = dir.path.display().to_string(),
+                err = e.to_string(),
+                "Could not watch directory"
+            );
+        }
+    }
+
+    let publisher = WatchPublisher {
+        node_name,
+        dirs,
+        owner,
+        node_ref,
+        published: HashMap::new(),
+    };
+    let task = tokio::spawn(publisher.run(receiver, debounce));
+
+    Ok(FolderWatcher {
+        _watcher: watcher,
+        task,
+    })
+}
+
+struct WatchPublisher {
+    node_name: String,
+    dirs: Vec<WatchDir>,
+    owner: Option<u32>,
+    node_ref: WeakActorRef<Node>,
+    /// The ID each file was last published as
+    published: HashMap<PathBuf, String>,
+}
+
+impl WatchPublisher {
+    async fn run(mut self, mut changes: mpsc::UnboundedReceiver<PathBuf>, debounce: Duration) {
+        let paths: Vec<PathBuf> = self.dirs.iter().map(|dir| dir.path.clone()).collect();
+        let existing: Vec<PathBuf> = tokio::task::spawn_blocking(move || {
+            paths.iter().flat_map(|path| files_in(path)).collect()
+        })
+        .await
+        .unwrap_or_else(|_| vec![]);
+
+        // The time each file is published at, unless it changes before
+        let now = Instant::now();
+        let mut pending: HashMap<PathBuf, Instant> =
+            existing.into_iter().map(|path| (path, now)).collect();
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+
+        loop {
+            tokio::select! {
+                change = changes.recv() => match change {
+                    Some(path) => {
+                        pending.insert(path, Instant::now() + debounce);
+                    }
+                    None => return,
+                },
+                _ = interval.tick() => {
+                    let now = Instant::now();
+                    let ready: Vec<PathBuf> = pending
+                        .iter()
+                        .filter(|(_, publish_at)| **publish_at <= now)
+                        .map(|(path, _)| path.clone())
+                        .collect();
+                    for path in ready {
+                        pending.remove(&path);
+                        if !self.publish(path).await {
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Publishes the file unless it's ignored, returns false if the node is gone
+    async fn publish(&mut self, path: PathBuf) -> bool {
+        let Some(dir) = self.dirs.iter().find(|dir| dir.should_publish(&path)) else {
+            return true;
+        };
+        // Removed since it changed, or a directory
+        if !path.is_file() {
+            return true;
+        }
+        let unpublish_old = dir.unpublish_old;
+        // A symlink in the directory could point at a file of someone else
+        let file = match owned_file::open_read(&path, self.owner) {
+            Ok(file) => file,
+            Err(e) => {
+                warn!(
+                    node = self.node_name,
+                    path = path.display().to_string(),
+                    err = e.to_string(),
+                    "Could not open watched file"
+                );
+                return true;
+            }
+        };
+        let Some(node_ref) = self.node_ref.upgrade() else {
+            return false;
+        };
+
+        let result = node_ref
+            .ask(PublishFile {
+                path: path.clone(),
+                file,
+                access: ObjectAccess::Public,
+                previous: None,
+            })
+            .send()
+            .await;
+        let id = match result {
+            Ok(id) => id,
+            Err(e) => {
+                warn!(
+                    node = self.node_name,
+                    path = path.display().to_string(),
+                    err = e.to_string(),
+                    "Failed to publish watched file"
+                );
+                return true;
+            }
+        };
+        debug!(
+            node = self.node_name,
+            path = path.display().to_string(),
+            obj_id = id,
+            "Published watched file"
+        );
+
+        let old = self.published.insert(path, id.clone());
+        if let Some(old) = old.filter(|old| unpublish_old && *old != id) {
+            if let Err(e) = node_ref
+                .ask(DeleteObject {
+                    obj_id_str: old.clone(),
+                })
+                .send()
+                .await
+            {
+                warn!(
+                    node = self.node_name,
+                    obj_id = old,
+                    err = e.to_string(),
+                    "Failed to unpublish old version of watched file"
+                );
+            }
+        }
+
+        true
+    }
+}
+
+/// The files in the directory and its subdirectories, without the symlinks
+fn files_in(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return vec![];
+    };
+
+    entries
+        .flatten()
+        .flat_map(|entry| {
+            let path = entry.path();
+            match entry.file_type() {
+                Ok(file_type) if file_type.is_dir() => files_in(&path),
+                Ok(file_type) if file_type.is_file() => vec![path],
+                _ => vec![],
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    #[test]
+    fn files_in_test() {
+        let tmp_dir = TempDir::new("liberum_tests").unwrap();
+        let nested = tmp_dir.path().join("nested");
+        std::fs::create_dir(&nested).unwrap();
+        std::fs::write(tmp_dir.path().join("a.txt"), "a").unwrap();
+        std::fs::write(nested.join("b.txt"), "b").unwrap();
+
+        let mut files = files_in(tmp_dir.path());
+        files.sort();
+        assert_eq!(
+            files,
+            vec![tmp_dir.path().join("a.txt"), nested.join("b.txt")]
+        );
+        assert!(files_in(&tmp_dir.path().join("missing")).is_empty());
+    }
+}
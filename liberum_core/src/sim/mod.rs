@@ -0,0 +1,294 @@
+pub mod scenario;
+
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+use liberum_core::node_config::{NodeConfig, TransportKind};
+use liberum_core::types::ObjectAccess;
+use liberum_core::{DaemonRequest, DaemonResponse};
+use libp2p::Multiaddr;
+use scenario::{Scenario, Step};
+use tempdir::TempDir;
+use tracing::{debug, warn};
+
+use crate::connection::{handle_message, AppContext};
+use crate::node::store::NodeStore;
+
+///! The module runs the simulations of the network. All the nodes of a simulation
+///! run in one process and talk over the in-process transport, so dozens of them
+///! start in a moment and nothing outside of the process affects the results.
+///! The nodes are driven with the same requests as the ones sent by the UIs, one
+///! step after another, and have the same peer IDs in every run.
+
+/// The ports of the in-process transport are shared by the whole process, every
+/// node gets its own so the simulations may run at the same time
+static NEXT_MEMORY_PORT: AtomicU64 = AtomicU64::new(1);
+
+/// The result of a step of the scenario
+#[derive(Debug, Clone)]
+pub struct StepReport {
+    pub step: usize,
+    pub description: String,
+    pub duration: Duration,
+    pub error: Option<String>,
+}
+
+/// A file published in the simulation
+struct SimFile {
+    id: String,
+    content: Vec<u8>,
+}
+
+pub struct Simulation {
+    context: AppContext,
+    nodes: Vec<String>,
+    files: HashMap<String, SimFile>,
+    /// The node store and the published files, removed with the simulation
+    dir: TempDir,
+}
+
+/// Starts the nodes of the scenario, runs its steps and stops the nodes
+pub async fn run_scenario(scenario: &Scenario) -> Result<Vec<StepReport>> {
+    scenario.validate()?;
+    let mut simulation = Simulation::start(scenario).await?;
+    let reports = simulation.run(&scenario.steps).await;
+    simulation.stop().await;
+    Ok(reports)
+}
+
+impl Simulation {
+    /// Creates and starts the nodes and connects them according to the topology
+    pub async fn start(scenario: &Scenario) -> Result<Self> {
+        let dir = TempDir::new("liberum_sim")?;
+        let node_store = NodeStore::with_custom_nodes_dir(dir.path()).await?;
+        let mut simulation = Simulation {
+            context: AppContext::new(kameo::spawn(node_store)),
+            nodes: Vec::new(),
+            files: HashMap::new(),
+            dir,
+        };
+
+        let mut addresses = Vec::new();
+        for i in 0..scenario.nodes {
+            addresses.push(simulation.start_node(i).await?);
+        }
+
+        for (from, to) in scenario.topology.edges(scenario.nodes) {
+            let (peer_id, addr) = &addresses[to];
+            simulation
+                .request(DaemonRequest::Dial {
+                    node_name: simulation.nodes[from].clone(),
+                    peer_id: Some(peer_id.clone()),
+                    addr: addr.to_string(),
+                })
+                .await
+                .map_err(|e| e.context(format!("Node {from} could not dial node {to}")))?;
+        }
+
+        Ok(simulation)
+    }
+
+    /// Starts the node listening on its own port of the in-process transport.
+    /// Returns its peer ID and address
+    async fn start_node(&mut self, i: usize) -> Result<(String, Multiaddr)> {
+        let node_name = format!("sim-{i}");
+        let port = NEXT_MEMORY_PORT.fetch_add(1, Ordering::Relaxed);
+        let addr = Multiaddr::from_str(&format!("/memory/{port}"))?;
+
+        self.request(DaemonRequest::NewNode {
+            node_name: node_name.clone(),
+            id_seed: Some(node_name.clone()),
+        })
+        .await?;
+        self.request(DaemonRequest::OverwriteNodeConfig {
+            node_name: node_name.clone(),
+            new_cfg: NodeConfig {
+                external_addresses: vec![addr.clone()],
+                transport: TransportKind::Memory,
+                ..Default::default()
+            },
+        })
+        .await?;
+        self.request(DaemonRequest::StartNode {
+            node_name: node_name.clone(),
+        })
+        .await?;
+        let DaemonResponse::PeerId { id } = self
+            .request(DaemonRequest::GetPeerId {
+                node_name: node_name.clone(),
+            })
+            .await?
+        else {
+            return Err(anyhow!("Daemon returned wrong response"));
+        };
+
+        debug!(node = node_name, peer_id = id, "Simulated node started");
+        self.nodes.push(node_name);
+        Ok((id, addr))
+    }
+
+    /// Runs the steps one after another. A failed step does not stop the scenario,
+    /// the later steps show how the network copes with it
+    pub async fn run(&mut self, steps: &[Step]) -> Vec<StepReport> {
+        let mut reports = Vec::new();
+        for (i, step) in steps.iter().enumerate() {
+            let started = Instant::now();
+            let result = self.run_step(step).await;
+            let report = StepReport {
+                step: i,
+                description: format!("{step:?}"),
+                duration: started.elapsed(),
+                error: result.err().map(|e| format!("{e:#}")),
+            };
+            if let Some(e) = &report.error {
+                warn!(step = i, err = e, "Simulation step failed");
+            }
+            reports.push(report);
+        }
+
+        reports
+    }
+
+    async fn run_step(&mut self, step: &Step) -> Result<()> {
+        match step {
+            Step::Publish { node, file, size } => self.publish(*node, file, *size).await,
+            Step::Download { node, file } => self.download(*node, file).await,
+            Step::Providers { node, file, min } => self.check_providers(*node, file, *min).await,
+            Step::Stop { node } => {
+                self.request(DaemonRequest::StopNode {
+                    node_name: self.nodes[*node].clone(),
+                })
+                .await?;
+                Ok(())
+            }
+            Step::Wait { millis } => {
+                tokio::time::sleep(Duration::from_millis(*millis)).await;
+                Ok(())
+            }
+        }
+    }
+
+    async fn publish(&mut self, node: usize, file: &str, size: usize) -> Result<()> {
+        let content = file_content(file, size);
+        let path = self.dir.path().join(file);
+        tokio::fs::write(&path, &content).await?;
+
+        let response = self
+            .request(DaemonRequest::PublishFile {
+                node_name: self.nodes[node].clone(),
+                path,
+                access: ObjectAccess::Public,
+                previous: None,
+            })
+            .await?;
+        let DaemonResponse::FilePublished { id } = response else {
+            return Err(anyhow!("Daemon returned wrong response"));
+        };
+
+        self.files.insert(file.to_string(), SimFile { id, content });
+        Ok(())
+    }
+
+    async fn download(&self, node: usize, file: &str) -> Result<()> {
+        let sim_file = self.file(file)?;
+        let response = self
+            .request(DaemonRequest::DownloadFile {
+                node_name: self.nodes[node].clone(),
+                id: sim_file.id.clone(),
+                group: None,
+                force_network: false,
+            })
+            .await?;
+        let DaemonResponse::FileDownloaded { data, .. } = response else {
+            return Err(anyhow!("Daemon returned wrong response"));
+        };
+
+        if data.content != sim_file.content {
+            return Err(anyhow!("Downloaded file {file} has wrong content"));
+        }
+        Ok(())
+    }
+
+    async fn check_providers(&self, node: usize, file: &str, min: usize) -> Result<()> {
+        let response = self
+            .request(DaemonRequest::GetProviders {
+                node_name: self.nodes[node].clone(),
+                id: self.file(file)?.id.clone(),
+                stream: false,
+            })
+            .await?;
+        let DaemonResponse::Providers { ids, .. } = response else {
+            return Err(anyhow!("Daemon returned wrong response"));
+        };
+
+        if ids.len() < min {
+            return Err(anyhow!(
+                "Found {} providers of file {file}, expected at least {min}",
+                ids.len()
+            ));
+        }
+        Ok(())
+    }
+
+    fn file(&self, file: &str) -> Result<&SimFile> {
+        self.files
+            .get(file)
+            .ok_or(anyhow!("File {file} was not published"))
+    }
+
+    /// Stops all the nodes which are still running
+    pub async fn stop(self) {
+        for node_name in &self.nodes {
+            self.request(DaemonRequest::StopNode {
+                node_name: node_name.clone(),
+            })
+            .await
+            .inspect_err(|e| debug!(node = node_name, err = e.to_string(), "Not stopped"))
+            .ok();
+        }
+    }
+
+    async fn request(&self, request: DaemonRequest) -> Result<DaemonResponse> {
+        Ok(handle_message(request, &self.context).await?)
+    }
+}
+
+/// The content of the file is derived from its name
+fn file_content(name: &str, size: usize) -> Vec<u8> {
+    let mut content = vec![0; size];
+    blake3::Hasher::new()
+        .update(name.as_bytes())
+        .finalize_xof()
+        .fill(&mut content);
+    content
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn simulation_test() {
+        let scenario = Scenario::from_json(
+            r#"{
+                "nodes": 3,
+                "topology": "line",
+                "steps": [
+                    { "publish": { "node": 0, "file": "a", "size": 1024 } },
+                    { "download": { "node": 2, "file": "a" } },
+                    { "providers": { "node": 1, "file": "a", "min": 1 } }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let reports = run_scenario(&scenario).await.unwrap();
+        assert_eq!(reports.len(), 3);
+        for report in reports {
+            assert_eq!(report.error, None, "{}", report.description);
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use std::collections::HashSet;
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+///! The module contains the description of a simulation, read from JSON, e.g.
+///!
+///! ```json
+///! {
+///!     "nodes": 10,
+///!     "topology": "ring",
+///!     "steps": [
+///!         { "publish": { "node": 0, "file": "a", "size": 4096 } },
+///!         { "wait": { "millis": 500 } },
+///!         { "download": { "node": 5, "file": "a" } }
+///!     ]
+///! }
+///! ```
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Scenario {
+    /// Number of the nodes, named `sim-0`, `sim-1`...
+    pub nodes: usize,
+    pub topology: Topology,
+    pub steps: Vec<Step>,
+}
+
+/// Which nodes dial which when the simulation starts
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum Topology {
+    /// Every node dials the next one
+    Line,
+    /// Like the line, and the last node dials the first one
+    Ring,
+    /// Every node dials the first one
+    Star,
+    /// Every node dials all the nodes after it
+    Full,
+    /// The first node of every pair dials the second one
+    Edges(Vec<(usize, usize)>),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum Step {
+    /// Publishes the file with the content generated from its name, so the same
+    /// scenario always publishes the same objects
+    Publish {
+        node: usize,
+        file: String,
+        size: usize,
+    },
+    /// Downloads the file published before and checks its content
+    Download {
+        node: usize,
+        file: String,
+    },
+    /// Checks that at least `min` providers of the file published before are found
+    Providers {
+        node: usize,
+        file: String,
+        min: usize,
+    },
+    Stop {
+        node: usize,
+    },
+    /// Gives the network time, e.g. to replicate the published objects
+    Wait {
+        millis: u64,
+    },
+}
+
+impl Topology {
+    /// The pairs of the dialing and the dialed node
+    pub fn edges(&self, nodes: usize) -> Vec<(usize, usize)> {
+        match self {
+            Topology::Line => (1..nodes).map(|i| (i - 1, i)).collect(),
+            // Two nodes would dial each other twice
+            Topology::Ring if nodes > 2 => (0..nodes).map(|i| (i, (i + 1) % nodes)).collect(),
+            Topology::Ring => Topology::Line.edges(nodes),
+            Topology::Star => (1..nodes).map(|i| (i, 0)).collect(),
+            Topology::Full => (0..nodes)
+                .flat_map(|i| (i + 1..nodes).map(move |j| (i, j)))
+                .collect(),
+            Topology::Edges(edges) => edges.clone(),
+        }
+    }
+}
+
+impl Step {
+    /// The node the step is run on
+    fn node(&self) -> Option<usize> {
+        match self {
+            Step::Publish { node, .. }
+            | Step::Download { node, .. }
+            | Step::Providers { node, .. }
+            | Step::Stop { node } => Some(*node),
+            Step::Wait { .. } => None,
+        }
+    }
+}
+
+impl Scenario {
+    pub fn from_json(json: &str) -> Result<Self> {
+        let scenario: Scenario = serde_json::from_str(json)?;
+        scenario.validate()?;
+        Ok(scenario)
+    }
+
+    /// Checks that the scenario refers only to its nodes and to the files it
+    /// publishes, before any node is started
+    pub fn validate(&self) -> Result<()> {
+        let check_node = |node: usize| match node < self.nodes {
+            true => Ok(()),
+            false => Err(anyhow!("Node {node} is not in the scenario")),
+        };
+
+        for (from, to) in self.topology.edges(self.nodes) {
+            check_node(from)?;
+            check_node(to)?;
+        }
+
+        let mut published = HashSet::new();
+        for (i, step) in self.steps.iter().enumerate() {
+            if let Some(node) = step.node() {
+                check_node(node).map_err(|e| e.context(format!("Step {i}")))?;
+            }
+            match step {
+                Step::Publish { file, .. } => {
+                    published.insert(file);
+                }
+                Step::Download { file, .. } | Step::Providers { file, .. } => {
+                    if !published.contains(file) {
+                        return Err(anyhow!("Step {i}: file {file} is not published before"));
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scenario_test() {
+        assert_eq!(Topology::Line.edges(3), vec![(0, 1), (1, 2)]);
+        assert_eq!(Topology::Ring.edges(3), vec![(0, 1), (1, 2), (2, 0)]);
+        assert_eq!(Topology::Ring.edges(2), vec![(0, 1)]);
+        assert_eq!(Topology::Star.edges(3), vec![(1, 0), (2, 0)]);
+        assert_eq!(Topology::Full.edges(3), vec![(0, 1), (0, 2), (1, 2)]);
+
+        let scenario = Scenario::from_json(
+            r#"{
+                "nodes": 2,
+                "topology": { "edges": [[0, 1]] },
+                "steps": [
+                    { "publish": { "node": 0, "file": "a", "size": 16 } },
+                    { "wait": { "millis": 10 } },
+                    { "download": { "node": 1, "file": "a" } }
+                ]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(scenario.steps.len(), 3);
+
+        let not_published = r#"{
+            "nodes": 2,
+            "topology": "line",
+            "steps": [{ "download": { "node": 1, "file": "a" } }]
+        }"#;
+        assert!(Scenario::from_json(not_published).is_err());
+        let unknown_node = r#"{ "nodes": 2, "topology": { "edges": [[0, 2]] }, "steps": [] }"#;
+        assert!(Scenario::from_json(unknown_node).is_err());
+    }
+}
@@ -0,0 +1,324 @@
+use std::collections::HashSet;
+use std::net::IpAddr;
+
+use anyhow::{anyhow, bail, Result};
+use liberum_core::node_config::{AddressPreference, IpStack};
+use libp2p::multiaddr::Protocol;
+use libp2p::swarm::dial_opts::{DialOpts, PeerCondition};
+use libp2p::swarm::DialError;
+use libp2p::{Multiaddr, PeerId};
+use tokio::sync::oneshot;
+use tracing::debug;
+
+use crate::swarm_runner::SwarmContext;
+
+///! The module applies the address policy from the config of the node. The policy
+///! picks the IP versions of the default listen addresses, the order the addresses
+///! of a peer are dialed in, and which addresses are kept in the routing table and
+///! announced as the external addresses of the node, which Kademlia puts into the
+///! provider records published to the DHT.
+///!
+///! The loopback and link-local addresses can't be reached from other machines, so
+///! they are dropped unless the policy allows them, e.g. for the networks run on a
+///! single machine. The addresses without an IP, like the memory ones, are kept.
+
+/// The IP of the address, None for the addresses without one
+fn ip_of(addr: &Multiaddr) -> Option<IpAddr> {
+    addr.iter().find_map(|protocol| match protocol {
+        Protocol::Ip4(ip) => Some(IpAddr::V4(ip)),
+        Protocol::Ip6(ip) => Some(IpAddr::V6(ip)),
+        _ => None,
+    })
+}
+
+/// Whether the address can be reached from other machines
+pub(crate) fn is_routable(addr: &Multiaddr) -> bool {
+    match ip_of(addr) {
+        Some(IpAddr::V4(ip)) => !(ip.is_loopback() || ip.is_link_local() || ip.is_unspecified()),
+        // fe80::/10 is link-local
+        Some(IpAddr::V6(ip)) => {
+            !(ip.is_loopback() || ip.is_unspecified() || ip.segments()[0] & 0xffc0 == 0xfe80)
+        }
+        None => true,
+    }
+}
+
+/// Whether the address is in the private ranges, fc00::/7 for IPv6
+pub(crate) fn is_private(addr: &Multiaddr) -> bool {
+    match ip_of(addr) {
+        Some(IpAddr::V4(ip)) => ip.is_private(),
+        Some(IpAddr::V6(ip)) => ip.segments()[0] & 0xfe00 == 0xfc00,
+        None => false,
+    }
+}
+
+fn matches(preference: AddressPreference, addr: &Multiaddr) -> bool {
+    match preference {
+        AddressPreference::Ipv6 => matches!(ip_of(addr), Some(IpAddr::V6(_))),
+        AddressPreference::Ipv4 => matches!(ip_of(addr), Some(IpAddr::V4(_))),
+        AddressPreference::Private => is_private(addr),
+        AddressPreference::Public => {
+            ip_of(addr).is_some() && is_routable(addr) && !is_private(addr)
+        }
+        AddressPreference::Quic => addr
+            .iter()
+            .any(|protocol| matches!(protocol, Protocol::QuicV1 | Protocol::Quic)),
+    }
+}
+
+/// The addresses without the duplicates, in the order of the preferences. The
+/// addresses equal for all the preferences keep their order
+pub(crate) fn sort_addresses(
+    prefer: &[AddressPreference],
+    addrs: impl IntoIterator<Item = Multiaddr>,
+) -> Vec<Multiaddr> {
+    let mut seen = HashSet::new();
+    let mut addrs: Vec<Multiaddr> = addrs
+        .into_iter()
+        .filter(|addr| seen.insert(addr.clone()))
+        .collect();
+    // false goes first, so the matching addresses do
+    addrs.sort_by_cached_key(|addr| {
+        prefer
+            .iter()
+            .map(|preference| !matches(*preference, addr))
+            .collect::<Vec<_>>()
+    });
+    addrs
+}
+
+/// Whether the node listens on the address by default with the IP versions
+pub(crate) fn is_in_stack(stack: IpStack, addr: &Multiaddr) -> bool {
+    match ip_of(addr) {
+        Some(IpAddr::V4(_)) => stack.allows_ipv4(),
+        Some(IpAddr::V6(_)) => stack.allows_ipv6(),
+        None => true,
+    }
+}
+
+/// The address without the peer ID at the end, as the external addresses are
+fn without_peer_id(mut addr: Multiaddr) -> Multiaddr {
+    if let Some(Protocol::P2p(_)) = addr.iter().last() {
+        addr.pop();
+    }
+    addr
+}
+
+/// The peer to dial at the address, given or taken from `/p2p/<peer id>` at the end
+/// of the address. None if the peer is not known, the dial learns it
+pub(crate) fn split_peer_id(
+    peer_id: Option<PeerId>,
+    addr: Multiaddr,
+) -> Result<(Option<PeerId>, Multiaddr)> {
+    let embedded = match addr.iter().last() {
+        Some(Protocol::P2p(embedded)) => Some(embedded),
+        _ => None,
+    };
+    match (peer_id, embedded) {
+        (Some(peer_id), Some(embedded)) if peer_id != embedded => {
+            bail!("The address {addr} is of another peer than {peer_id}")
+        }
+        (peer_id, embedded) => Ok((peer_id.or(embedded), without_peer_id(addr))),
+    }
+}
+
+/// Methods on SwarmContext for the address policy
+impl SwarmContext {
+    /// Whether the address may be kept in the routing table and announced to the peers
+    pub(crate) fn is_announceable(&self, addr: &Multiaddr) -> bool {
+        self.node_snapshot.config.address_policy.allow_unroutable || is_routable(addr)
+    }
+
+    /// Adds the address of the peer to the routing table, unless the policy drops it
+    pub(crate) fn add_peer_address(&mut self, peer_id: &PeerId, addr: Multiaddr) {
+        if !self.is_announceable(&addr) {
+            debug!(
+                node = self.node_snapshot.name,
+                peer_id = peer_id.to_base58(),
+                address = addr.to_string(),
+                "Not adding unroutable address"
+            );
+            return;
+        }
+        self.swarm
+            .behaviour_mut()
+            .kademlia
+            .add_address(peer_id, addr);
+    }
+
+    /// Announces the address the node listens on as its external address, which
+    /// Kademlia publishes with the provider records
+    pub(crate) fn announce_listen_address(&mut self, addr: Multiaddr) {
+        let addr = without_peer_id(addr);
+        let is_announced = self.swarm.external_addresses().any(|a| *a == addr);
+        if self.is_announceable(&addr) && !is_announced {
+            self.swarm.add_external_address(addr);
+        }
+    }
+
+    pub(crate) fn withdraw_listen_address(&mut self, addr: Multiaddr) {
+        self.swarm.remove_external_address(&without_peer_id(addr));
+    }
+
+    /// Dials the peer with the given addresses and the ones from the routing table,
+    /// in the order of the preferences of the policy
+    pub(crate) fn dial_peer(
+        &mut self,
+        peer_id: PeerId,
+        addresses: Vec<Multiaddr>,
+    ) -> Result<(), DialError> {
+        let mut known = vec![];
+        if let Some(bucket) = self.swarm.behaviour_mut().kademlia.kbucket(peer_id) {
+            for entry in bucket.iter() {
+                if *entry.node.key.preimage() == peer_id {
+                    known.extend(entry.node.value.iter().cloned());
+                }
+            }
+        }
+        let addresses = sort_addresses(
+            &self.node_snapshot.config.address_policy.prefer,
+            addresses.into_iter().chain(known),
+        );
+
+        // The addresses from the behaviours are dialed after the given ones
+        let opts = DialOpts::peer_id(peer_id)
+            .addresses(addresses)
+            .extend_addresses_through_behaviour()
+            .build();
+        self.swarm.dial(opts)
+    }
+}
+
+/// Methods on SwarmContext for dialing the peers
+impl SwarmContext {
+    /// Dials the peer at the address, the peer is sent back once connected
+    pub(crate) fn dial(
+        &mut self,
+        peer_id: Option<PeerId>,
+        peer_addr: Multiaddr,
+        response_sender: oneshot::Sender<Result<PeerId>>,
+    ) {
+        let (peer_id, peer_addr) = match split_peer_id(peer_id, peer_addr) {
+            Ok(target) => target,
+            Err(e) => {
+                let _ = response_sender.send(Err(e));
+                return;
+            }
+        };
+        let dial_opts = match peer_id {
+            Some(peer_id) => DialOpts::peer_id(peer_id)
+                .addresses(vec![peer_addr.clone()])
+                .condition(PeerCondition::Always)
+                .build(),
+            None => DialOpts::from(peer_addr.clone()),
+        };
+        let connection_id = dial_opts.connection_id();
+
+        if self
+            .behaviour
+            .pending_inner_dial
+            .contains_key(&connection_id)
+        {
+            debug!("Already dialing {peer_addr}");
+            return;
+        }
+        match self.swarm.dial(dial_opts) {
+            Ok(()) => {
+                self.behaviour
+                    .pending_inner_dial
+                    .insert(connection_id, response_sender);
+            }
+            Err(err) => {
+                let _ = response_sender.send(Err(anyhow!(err)));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(s: &str) -> Multiaddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn routable_test() {
+        assert!(!is_routable(&addr("/ip4/127.0.0.1/udp/1/quic-v1")));
+        assert!(!is_routable(&addr("/ip4/169.254.0.1/udp/1/quic-v1")));
+        assert!(!is_routable(&addr("/ip6/::1/udp/1/quic-v1")));
+        assert!(!is_routable(&addr("/ip6/fe80::1/udp/1/quic-v1")));
+        assert!(is_routable(&addr("/ip4/192.168.1.2/udp/1/quic-v1")));
+        assert!(is_routable(&addr("/ip6/2001:db8::1/udp/1/quic-v1")));
+        assert!(is_routable(&addr("/memory/5")));
+        assert!(is_private(&addr("/ip6/fd00::1/udp/1/quic-v1")));
+        assert!(!is_private(&addr("/ip4/8.8.8.8/udp/1/quic-v1")));
+    }
+
+    #[test]
+    fn sort_addresses_test() {
+        let public4 = addr("/ip4/8.8.8.8/udp/1/quic-v1");
+        let private4 = addr("/ip4/10.0.0.2/udp/1/quic-v1");
+        let public6 = addr("/ip6/2001:db8::1/udp/1/quic-v1");
+        let webrtc6 = addr("/ip6/2001:db8::1/udp/2/webrtc-direct");
+        let all = vec![
+            public4.clone(),
+            webrtc6.clone(),
+            private4.clone(),
+            public6.clone(),
+            public4.clone(),
+        ];
+
+        assert_eq!(
+            sort_addresses(&[], all.clone()),
+            vec![
+                public4.clone(),
+                webrtc6.clone(),
+                private4.clone(),
+                public6.clone()
+            ]
+        );
+        assert_eq!(
+            sort_addresses(
+                &[AddressPreference::Ipv6, AddressPreference::Quic],
+                all.clone()
+            ),
+            vec![public6, webrtc6, public4.clone(), private4.clone()]
+        );
+        assert_eq!(
+            sort_addresses(&[AddressPreference::Private], all)[0],
+            private4
+        );
+
+        assert!(!is_in_stack(IpStack::Ipv6, &public4));
+        assert!(is_in_stack(IpStack::Dual, &public4));
+        assert_eq!(
+            without_peer_id(addr(
+                "/ip4/8.8.8.8/udp/1/quic-v1/p2p/12D3KooWQYhTNQdmr3ArTeUHRYzFg94BKyTkoWBDWez9kSCVe2Xo"
+            )),
+            public4
+        );
+    }
+
+    #[test]
+    fn split_peer_id_test() {
+        let peer_id = PeerId::random();
+        let quic = addr("/ip4/8.8.8.8/udp/1/quic-v1");
+        let with_peer_id = quic.clone().with(Protocol::P2p(peer_id));
+
+        assert_eq!(
+            split_peer_id(None, quic.clone()).unwrap(),
+            (None, quic.clone())
+        );
+        assert_eq!(
+            split_peer_id(None, with_peer_id.clone()).unwrap(),
+            (Some(peer_id), quic.clone())
+        );
+        assert_eq!(
+            split_peer_id(Some(peer_id), with_peer_id.clone()).unwrap(),
+            (Some(peer_id), quic)
+        );
+        assert!(split_peer_id(Some(PeerId::random()), with_peer_id).is_err());
+    }
+}
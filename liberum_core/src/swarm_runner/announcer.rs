@@ -0,0 +1,248 @@
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+use liberum_core::proto::{self, ResultErrorCode};
+use libp2p::kad;
+use libp2p::request_response::ResponseChannel;
+use tokio::sync::oneshot;
+use tracing::warn;
+
+use super::behaviour::object_sender::ObjectResponse;
+use super::SwarmContext;
+
+///! The module schedules the provider announcements of the node. Publishing many
+///! objects at once would start as many Kademlia queries at once, so the
+///! announcements wait in a queue and at most `max_provides_per_sec` of them start
+///! every second. A key announced within the republish window is not announced
+///! again, its provider records are still valid.
+///!
+///! The records are also republished here instead of by Kademlia, which republishes
+///! all of them at once. A key is announced again a republish window after its last
+///! announcement, but only as many keys a second as needed to go through all of
+///! them once in the window. A burst of published objects is spread evenly over the
+///! window after its first republish.
+
+/// How often the queued announcements start
+pub const ANNOUNCE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Waits for a key to be announced
+pub enum ProvideWaiter {
+    /// A caller in the node
+    Inner(oneshot::Sender<Result<()>>),
+    /// A peer which asked the node to store the object with the ID
+    Outer(proto::Hash, ResponseChannel<ObjectResponse>),
+}
+
+pub struct Announcer {
+    max_per_tick: usize,
+    window: Duration,
+    /// The keys waiting for the announcement, the oldest first
+    queue: VecDeque<proto::Hash>,
+    /// The waiters of the queued keys
+    queued: HashMap<proto::Hash, Vec<ProvideWaiter>>,
+    /// When the provided keys were last announced, None if not yet
+    announced: HashMap<proto::Hash, Option<Instant>>,
+    /// The announcements which may still start in this tick
+    budget: usize,
+}
+
+impl Announcer {
+    pub fn new(max_per_sec: u32, window: Duration) -> Self {
+        let max_per_tick = (max_per_sec as usize).max(1);
+        Announcer {
+            max_per_tick,
+            window,
+            queue: VecDeque::new(),
+            queued: HashMap::new(),
+            announced: HashMap::new(),
+            budget: max_per_tick,
+        }
+    }
+
+    /// Queues the announcement of the key. If it was announced within the window
+    /// the waiter is returned back, the key is provided already
+    pub fn announce(
+        &mut self,
+        obj_id: proto::Hash,
+        waiter: Option<ProvideWaiter>,
+        now: Instant,
+    ) -> Option<ProvideWaiter> {
+        let fresh = matches!(
+            self.announced.get(&obj_id),
+            Some(Some(at)) if now < *at + self.window
+        );
+        if fresh && !self.queued.contains_key(&obj_id) {
+            return waiter;
+        }
+        self.reannounce(obj_id, waiter);
+        None
+    }
+
+    /// Queues the announcement of the key even if it's still fresh
+    pub fn reannounce(&mut self, obj_id: proto::Hash, waiter: Option<ProvideWaiter>) {
+        self.announced.entry(obj_id.clone()).or_insert(None);
+        let waiters = match self.queued.entry(obj_id) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                self.queue.push_back(entry.key().clone());
+                entry.insert(Vec::new())
+            }
+        };
+        waiters.extend(waiter);
+    }
+
+    /// Stops announcing the key. Returns the waiters of its queued announcement
+    pub fn forget(&mut self, obj_id: &proto::Hash) -> Vec<ProvideWaiter> {
+        self.announced.remove(obj_id);
+        self.queue.retain(|id| id != obj_id);
+        self.queued.remove(obj_id).unwrap_or_default()
+    }
+
+    /// Starts the next tick, the budget is refilled and the keys due for the
+    /// republish are queued, the longest waiting first
+    pub fn tick(&mut self, now: Instant) {
+        self.budget = self.max_per_tick;
+
+        let per_tick = self.announced.len() as f64 * ANNOUNCE_INTERVAL.as_secs_f64()
+            / self.window.as_secs_f64();
+        let mut due: Vec<(Instant, proto::Hash)> = self
+            .announced
+            .iter()
+            .filter(|(id, _)| !self.queued.contains_key(id))
+            .filter_map(|(id, at)| at.filter(|at| *at + self.window <= now).map(|at| (at, id)))
+            .map(|(at, id)| (at, id.clone()))
+            .collect();
+        due.sort_by_key(|(at, _)| *at);
+        for (_, obj_id) in due.into_iter().take(per_tick.ceil() as usize) {
+            self.reannounce(obj_id, None);
+        }
+    }
+
+    /// The keys to announce now with their waiters, as many as the budget allows
+    pub fn take(&mut self, now: Instant) -> Vec<(proto::Hash, Vec<ProvideWaiter>)> {
+        let mut taken = Vec::new();
+        while self.budget > 0 {
+            let Some(obj_id) = self.queue.pop_front() else {
+                break;
+            };
+            let waiters = self.queued.remove(&obj_id).unwrap_or_default();
+            self.announced.insert(obj_id.clone(), Some(now));
+            self.budget -= 1;
+            taken.push((obj_id, waiters));
+        }
+        taken
+    }
+}
+
+/// Methods on SwarmContext for announcing the provided keys
+impl SwarmContext {
+    /// Queues the announcement of the key and starts the ones allowed now
+    pub(crate) fn announce(&mut self, obj_id: proto::Hash, waiter: ProvideWaiter) {
+        if let Some(waiter) = self
+            .announcer
+            .announce(obj_id, Some(waiter), Instant::now())
+        {
+            self.respond_provided(waiter, Ok(()));
+        }
+        self.start_announcements();
+    }
+
+    /// Starts the announcements allowed now
+    pub(crate) fn start_announcements(&mut self) {
+        for (obj_id, waiters) in self.announcer.take(Instant::now()) {
+            let result = self
+                .swarm
+                .behaviour_mut()
+                .kademlia
+                .start_providing(kad::RecordKey::new(&obj_id.bytes));
+            match result {
+                Ok(query_id) => {
+                    self.behaviour
+                        .pending_start_providing
+                        .insert(query_id, waiters);
+                }
+                Err(e) => {
+                    let err = e.to_string();
+                    warn!(obj_id = obj_id.to_string(), err, "Could not provide object");
+                    for waiter in waiters {
+                        self.respond_provided(waiter, Err(anyhow!(err.clone())));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Answers the waiter with the result of the announcement
+    pub(crate) fn respond_provided(&mut self, waiter: ProvideWaiter, result: Result<()>) {
+        match waiter {
+            ProvideWaiter::Inner(sender) => {
+                let _ = sender.send(result);
+            }
+            ProvideWaiter::Outer(object_id, response_channel) => {
+                let result = result.map_err(|_| ResultErrorCode::Other);
+                let _ = self.swarm.behaviour_mut().object_sender.send_response(
+                    response_channel,
+                    ObjectResponse {
+                        object: proto::ResultObject { result }.into(),
+                        object_id,
+                    },
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn waiter() -> (ProvideWaiter, oneshot::Receiver<Result<()>>) {
+        let (send, recv) = oneshot::channel();
+        (ProvideWaiter::Inner(send), recv)
+    }
+
+    #[test]
+    fn announcer_test() {
+        let window = Duration::from_secs(10);
+        let mut announcer = Announcer::new(2, window);
+        let start = Instant::now();
+        let ids: Vec<proto::Hash> = (0..4u8).map(|i| proto::Hash { bytes: [i; 32] }).collect();
+
+        for id in &ids {
+            assert!(announcer.announce(id.clone(), None, start).is_none());
+        }
+        // Queued twice, announced once
+        let (second, _) = waiter();
+        assert!(announcer
+            .announce(ids[0].clone(), Some(second), start)
+            .is_none());
+        let taken = announcer.take(start);
+        assert_eq!(taken.len(), 2);
+        assert_eq!(taken[0].1.len(), 1);
+        assert!(announcer.take(start).is_empty());
+
+        announcer.tick(start + ANNOUNCE_INTERVAL);
+        assert_eq!(announcer.take(start + ANNOUNCE_INTERVAL).len(), 2);
+        assert!(announcer.queue.is_empty());
+
+        // Still fresh, the waiter is answered at once
+        let (again, _) = waiter();
+        assert!(announcer
+            .announce(ids[0].clone(), Some(again), start + ANNOUNCE_INTERVAL)
+            .is_some());
+
+        // 4 keys in the 10 second window are republished one a second
+        let later = start + window + ANNOUNCE_INTERVAL;
+        announcer.tick(later);
+        let taken = announcer.take(later);
+        assert_eq!(taken.len(), 1);
+        assert!(ids[..2].contains(&taken[0].0));
+
+        announcer.forget(&ids[1]);
+        announcer.forget(&ids[0]);
+        announcer.tick(later + ANNOUNCE_INTERVAL);
+        assert_eq!(announcer.take(later + ANNOUNCE_INTERVAL).len(), 1);
+    }
+}
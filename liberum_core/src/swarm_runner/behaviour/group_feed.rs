@@ -0,0 +1,206 @@
+use anyhow::{anyhow, Result};
+use kameo::request::MessageSend;
+use liberum_core::parser::{self, ObjectEnum};
+use liberum_core::proto::{self, GroupId, GroupObject, TypedObject, UserGroup};
+use liberum_core::types::{GroupPost, NodeEventKind};
+use libp2p::gossipsub::{self, IdentTopic, MessageAcceptance, MessageId, PublishError};
+use libp2p::PeerId;
+use tokio::sync::oneshot;
+use tracing::{debug, error, warn};
+
+use crate::node::events;
+use crate::swarm_runner::reputation::Misbehaviour;
+use crate::swarm_runner::SwarmContext;
+use crate::vault::{LoadGroups, StoreGroupPost};
+
+///! The module contains the feeds of the groups. Every group has a gossipsub topic
+///! derived from its ID, the members subscribe to it and publish their posts as
+///! signed GroupObjects. A post is forwarded only after it is verified to be signed
+///! by a member of the group, and every verified post is stored in the vault.
+///!
+///! Gossipsub keeps no history, members which are offline when a post is published
+///! don't receive it.
+
+/// The topic of the feed of the group
+pub fn group_topic(group: &GroupId) -> IdentTopic {
+    IdentTopic::new(format!("liberum-group/{group}"))
+}
+
+/// Methods on SwarmContext for the group feeds
+impl SwarmContext {
+    /// Subscribes to the feeds of the groups stored in the vault
+    pub(crate) async fn subscribe_groups(&mut self) {
+        let groups = match self.vault_ref.ask(LoadGroups).send().await {
+            Ok(groups) => groups,
+            Err(e) => {
+                warn!(err = e.to_string(), "Could not load the groups");
+                return;
+            }
+        };
+
+        for membership in groups {
+            self.subscribe_group(membership.group)
+                .inspect_err(|e| warn!(err = e.to_string(), "Could not subscribe to group"))
+                .ok();
+        }
+    }
+
+    /// Subscribes to the feed of the group. The owner of the group is dialed, if its
+    /// address is known, so the node has at least one peer in the feed
+    pub(crate) fn subscribe_group(&mut self, group: UserGroup) -> Result<()> {
+        let topic = group_topic(&group.definition.id);
+        self.swarm.behaviour_mut().gossipsub.subscribe(&topic)?;
+
+        let owner = group.owner_id()?;
+        if owner != *self.swarm.local_peer_id() && !self.swarm.is_connected(&owner) {
+            self.dial_peer(owner, vec![])
+                .inspect_err(|e| debug!(err = e.to_string(), "Could not dial group owner"))
+                .ok();
+        }
+
+        self.behaviour.groups.insert(topic.hash(), group);
+        Ok(())
+    }
+
+    /// Publishes the post in the feed of its group and stores it in the feed of
+    /// this node. Responds with the ID of the post. Having no other members online
+    /// is not an error, the post is kept in the feed of this node only
+    pub(crate) async fn publish_to_group(
+        &mut self,
+        post: GroupObject,
+        response_sender: oneshot::Sender<Result<proto::Hash>>,
+    ) {
+        let _ = response_sender.send(self.try_publish_to_group(post).await);
+    }
+
+    async fn try_publish_to_group(&mut self, post: GroupObject) -> Result<proto::Hash> {
+        let topic = group_topic(&post.group);
+        let group = self
+            .behaviour
+            .groups
+            .get(&topic.hash())
+            .ok_or(anyhow!("The node is not a member of the group"))?;
+        let verified = post.verify_post(group)?;
+        let id = verified.id.clone();
+
+        let data = bincode::serialize(&TypedObject::from(post.clone()))?;
+        self.stats.bytes_sent += data.len() as u64;
+        match self.swarm.behaviour_mut().gossipsub.publish(topic, data) {
+            Ok(_) | Err(PublishError::InsufficientPeers) => (),
+            Err(e) => return Err(anyhow!(e)),
+        }
+
+        self.store_group_post(&post.group, verified).await?;
+        Ok(id)
+    }
+
+    pub(crate) async fn handle_gossipsub(&mut self, event: gossipsub::Event) {
+        match event {
+            gossipsub::Event::Message {
+                propagation_source,
+                message_id,
+                message,
+            } => {
+                self.stats.bytes_received += message.data.len() as u64;
+                self.handle_group_post(propagation_source, message_id, message)
+                    .await;
+            }
+            e => debug!(
+                node = self.node_snapshot.name,
+                "Received gossipsub event! {e:?}"
+            ),
+        }
+    }
+
+    /// Verifies the post received in the feed. Only the valid posts are stored and
+    /// forwarded to the other members
+    async fn handle_group_post(
+        &mut self,
+        source: PeerId,
+        message_id: MessageId,
+        message: gossipsub::Message,
+    ) {
+        let verified = match self.verify_group_post(&message).await {
+            Ok(verified) => verified,
+            Err(e) => {
+                debug!(
+                    node = self.node_snapshot.name,
+                    peer = source.to_base58(),
+                    err = e.to_string(),
+                    "Rejected group post"
+                );
+                self.report_message(&message_id, &source, MessageAcceptance::Reject);
+                self.report_peer(&source, Misbehaviour::ProtocolViolation)
+                    .await;
+                return;
+            }
+        };
+
+        let (group, post) = verified;
+        let acceptance = match self.store_group_post(&group, post).await {
+            Ok(()) => MessageAcceptance::Accept,
+            Err(_) => MessageAcceptance::Ignore,
+        };
+        self.report_message(&message_id, &source, acceptance);
+    }
+
+    async fn verify_group_post(
+        &self,
+        message: &gossipsub::Message,
+    ) -> Result<(GroupId, proto::VerifiedPost)> {
+        let group = self
+            .behaviour
+            .groups
+            .get(&message.topic)
+            .ok_or(anyhow!("Not subscribed to the topic"))?;
+        let typed: TypedObject = proto::decode(&message.data)?;
+        let ObjectEnum::Group(post) = parser::parse_typed(typed).await? else {
+            return Err(anyhow!("The message is not a group object"));
+        };
+        let verified = post.verify_post(group)?;
+        Ok((post.group, verified))
+    }
+
+    fn report_message(
+        &mut self,
+        message_id: &MessageId,
+        source: &PeerId,
+        acceptance: MessageAcceptance,
+    ) {
+        self.swarm
+            .behaviour_mut()
+            .gossipsub
+            .report_message_validation_result(message_id, source, acceptance)
+            .inspect_err(|e| debug!(err = e.to_string(), "Could not report group post"))
+            .ok();
+    }
+
+    async fn store_group_post(&mut self, group: &GroupId, post: proto::VerifiedPost) -> Result<()> {
+        let own = post.author == *self.swarm.local_peer_id();
+        let author = post.author.to_base58();
+        let post = GroupPost {
+            id: post.id.to_string(),
+            group: group.to_string(),
+            author: author.clone(),
+            posted_at: post.posted_at,
+            content: post.content,
+        };
+        if let Err(e) = self.vault_ref.ask(StoreGroupPost { post }).send().await {
+            error!(
+                node = self.node_snapshot.name,
+                err = e.to_string(),
+                "Failed to store group post"
+            );
+            return Err(anyhow!(e.to_string()));
+        }
+
+        if !own {
+            events::record(
+                &self.events,
+                NodeEventKind::Message,
+                format!("Group post received from {author}"),
+            );
+        }
+        Ok(())
+    }
+}
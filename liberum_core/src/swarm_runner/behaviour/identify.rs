@@ -0,0 +1,54 @@
+use libp2p::identify;
+use tracing::debug;
+
+use super::transfer::TRANSFER_PROTOCOL;
+use super::wire::WireVersion;
+use crate::swarm_runner::{webrtc, SwarmContext};
+
+///! The module contains the handler of the identify behaviour. The peers announce
+///! the protocols they support, the newest version of the object sender protocol
+///! of every connected peer is kept to be shown with the peer, and the peers
+///! supporting the transfer protocol are downloaded from with it.
+///!
+///! The WebRTC addresses of the peers are added to the routing table, as they carry
+///! the hashes of the certificates, which can't be learned in any other way.
+
+/// Sent to the peers in the identify info
+pub const IDENTIFY_PROTOCOL_VERSION: &str = "/liberum/1.0.0";
+
+/// Methods on SwarmContext for the identify behaviour
+impl SwarmContext {
+    pub(crate) fn handle_identify(&mut self, event: identify::Event) {
+        let identify::Event::Received { peer_id, info, .. } = event else {
+            return;
+        };
+
+        for addr in info
+            .listen_addrs
+            .iter()
+            .filter(|a| webrtc::is_webrtc_address(a))
+        {
+            self.add_peer_address(&peer_id, addr.clone());
+        }
+
+        match info.protocols.contains(&TRANSFER_PROTOCOL) {
+            true => self.behaviour.transfer_peers.insert(peer_id),
+            false => self.behaviour.transfer_peers.remove(&peer_id),
+        };
+
+        match WireVersion::newest_of(&info.protocols) {
+            Some(version) => {
+                debug!(
+                    node = self.node_snapshot.name,
+                    peer_id = peer_id.to_base58(),
+                    version = format!("{version:?}"),
+                    "Peer identified"
+                );
+                self.behaviour.wire_versions.insert(peer_id, version);
+            }
+            None => {
+                self.behaviour.wire_versions.remove(&peer_id);
+            }
+        }
+    }
+}
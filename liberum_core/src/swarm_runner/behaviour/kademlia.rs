@@ -1,19 +1,21 @@
 use crate::{
-    swarm_runner::{object_sender, SwarmContext},
-    vault::{LoadObject, StoreObject},
+    swarm_runner::{messages::ProvidersBatch, SwarmContext},
+    vault::{DeleteTypedObject, LoadObject, StoreReceivedObject},
 };
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use kameo::request::MessageSend;
-use liberum_core::{parser::ObjectEnum, proto, DaemonQueryStats};
+use liberum_core::{parser::ObjectEnum, proto, types::ProvenanceKind, DaemonQueryStats};
 use libp2p::{
     kad::{
         store::RecordStore, AddProviderError, AddProviderOk, Event, GetClosestPeersResult,
-        GetProvidersError, GetProvidersOk, InboundRequest, ProgressStep, ProviderRecord, QueryId,
-        QueryResult, QueryStats, RecordKey,
+        GetProvidersError, GetProvidersOk, InboundRequest, KBucketKey, ProgressStep,
+        ProviderRecord, QueryId, QueryResult, QueryStats, RecordKey,
     },
     PeerId,
 };
 
+use std::time::Instant;
+use tokio::sync::{mpsc, oneshot};
 use tracing::{debug, error, info, warn};
 
 ///! The module contains methods to handle Kademlia events
@@ -37,6 +39,17 @@ impl SwarmContext {
             Event::InboundRequest { request } => {
                 self.handle_inound_request(request);
             }
+            // Blocked peers must not stay in the routing table
+            Event::RoutingUpdated { peer, .. } => {
+                if !self.is_peer_allowed(&peer) {
+                    debug!(
+                        node = self.node_snapshot.name,
+                        peer_id = peer.to_base58(),
+                        "Removing blocked peer from the routing table"
+                    );
+                    self.swarm.behaviour_mut().kademlia.remove_peer(&peer);
+                }
+            }
             _ => {}
         }
     }
@@ -65,6 +78,27 @@ impl SwarmContext {
                 self.handle_outbound_query_progressed_get_providers(id, result, stats, step)
                     .await;
             }
+            // Triggered when a record, a profile, a key rotation or a small object, is found
+            QueryResult::GetRecord(result) => {
+                if self.behaviour.pending_inner_get_rotation.contains_key(&id) {
+                    self.handle_outbound_query_progressed_get_rotation(id, result, step);
+                } else if self
+                    .behaviour
+                    .pending_inner_get_object_record
+                    .contains_key(&id)
+                {
+                    self.handle_outbound_query_progressed_get_object_record(id, result);
+                } else {
+                    self.handle_outbound_query_progressed_get_record(id, result);
+                }
+            }
+            QueryResult::PutRecord(result) => {
+                debug!(
+                    node = self.node_snapshot.name,
+                    result = format!("{result:?}"),
+                    "Record put"
+                );
+            }
             _ => {}
         }
     }
@@ -80,6 +114,10 @@ impl SwarmContext {
                 num_closer_peers,
                 num_provider_peers,
             } => self.handle_inbound_request_get_provider(num_closer_peers, num_provider_peers),
+            // Triggered when another peer puts a record, as the records are filtered
+            InboundRequest::PutRecord { source, record, .. } => {
+                self.handle_inbound_request_put_record(source, record)
+            }
             _ => {}
         }
     }
@@ -114,26 +152,16 @@ impl SwarmContext {
             );
         }
 
-        // Respond to the caller of StartProviding
-        if let Some(sender) = self.behaviour.pending_inner_start_providing.remove(&id) {
-            match result {
-                Ok(_) => {
-                    let _ = sender.send(Ok(()));
-                }
-                Err(e) => {
-                    let _ = sender.send(Err(e.into()));
-                }
-            }
-        } else if let Some((object_id, response_channel)) =
-            self.behaviour.pending_outer_start_providing.remove(&id)
-        {
-            let _ = self.swarm.behaviour_mut().object_sender.send_response(
-                response_channel,
-                object_sender::ObjectResponse {
-                    object: proto::ResultObject { result: Ok(()) }.into(),
-                    object_id,
-                },
-            );
+        // Respond to the callers of StartProviding and the peers which asked to
+        // store the object, both learn when the object could not be provided
+        let waiters = self
+            .behaviour
+            .pending_start_providing
+            .remove(&id)
+            .unwrap_or_default();
+        for waiter in waiters {
+            let result = result.as_ref().map(|_| ()).map_err(|e| e.clone().into());
+            self.respond_provided(waiter, result);
         }
     }
 
@@ -196,53 +224,51 @@ impl SwarmContext {
             Some(DaemonQueryStats {
                 query_duration: d,
                 total_requests: _stats.num_requests(),
+                attempts: 1,
+                from_cache: false,
             })
         } else {
             None
         };
 
+        // The providers are sent as soon as they are found, the sender is dropped
+        // when the query finishes, which ends the stream of the receiver
         match result {
             Ok(GetProvidersOk::FoundProviders { key: _, providers }) => {
-                debug!(
-                    "get providers pending:{}, last step?: {}",
-                    _stats.num_pending(),
-                    _step.last
-                );
                 debug!(
                     node = self.node_snapshot.name,
+                    pending = _stats.num_pending(),
+                    last = _step.last,
                     "some providers found {}",
                     providers.len()
                 );
-                if providers.len() == 0 {
+                let Some(sender) = self.behaviour.pending_inner_get_providers.remove(&id) else {
                     return;
-                }
-                if let Some(sender) = self.behaviour.pending_inner_get_providers.remove(&id) {
-                    let mut nodes = sender.0;
+                };
 
-                    nodes.append(&mut providers.into_iter().collect());
-                    if !_step.last {
-                        self.behaviour
-                            .pending_inner_get_providers
-                            .insert(id, (nodes, sender.1));
+                let batch = (providers.into_iter().collect(), query_stats);
+                match sender.try_send(batch) {
+                    Ok(()) => (),
+                    Err(mpsc::error::TrySendError::Full(_)) => {
+                        warn!(
+                            node = self.node_snapshot.name,
+                            qid = format!("{id}"),
+                            "Providers receiver is full, dropping providers"
+                        );
+                    }
+                    // Nobody waits for more providers
+                    Err(mpsc::error::TrySendError::Closed(_)) => {
+                        self.finish_query(&id);
                         return;
                     }
+                }
 
-                    let _ = sender.1.send((nodes, query_stats)).inspect_err(|e| {
-                        debug!(
-                            node = self.node_snapshot.name,
-                            qid = format!("{id}"),
-                            err = format!("{e:?}"),
-                            "Channel closed"
-                        )
-                    });
-                    // Stop the query after we got *some* providers. TODO: Leave the decission when to stop to someone requesting the query
-                    // like the node actor. This works for now.
-                    self.swarm
-                        .behaviour_mut()
-                        .kademlia
-                        .query_mut(&id)
-                        .unwrap()
-                        .finish();
+                if _step.last {
+                    self.finish_query(&id);
+                } else {
+                    self.behaviour
+                        .pending_inner_get_providers
+                        .insert(id, sender);
                 }
             }
             Ok(GetProvidersOk::FinishedWithNoAdditionalRecord { closest_peers: _ }) => {
@@ -250,15 +276,7 @@ impl SwarmContext {
                     node = self.node_snapshot.name,
                     "Get providers didn't find any new records"
                 );
-                if let Some(sender) = self.behaviour.pending_inner_get_providers.remove(&id) {
-                    let _ = sender.1.send((sender.0, query_stats)).inspect_err(|e| {
-                        debug!(
-                            qid = format!("{id}"),
-                            err = format!("{e:?}"),
-                            "Channel closed"
-                        )
-                    });
-                }
+                self.behaviour.pending_inner_get_providers.remove(&id);
             }
             Err(e) => {
                 error!(
@@ -267,6 +285,7 @@ impl SwarmContext {
                     err = format!("{e:?}"),
                     "Failed to get providers"
                 );
+                self.behaviour.pending_inner_get_providers.remove(&id);
             }
         }
     }
@@ -276,6 +295,8 @@ impl SwarmContext {
 impl SwarmContext {
     fn handle_inbound_request_add_provider(&mut self, record: Option<ProviderRecord>) {
         match record {
+            // Counted and logged by the validator
+            Some(record) if !self.accept_provider_record(&record) => (),
             Some(record) => {
                 self.swarm
                     .behaviour_mut()
@@ -283,6 +304,9 @@ impl SwarmContext {
                     .store_mut()
                     .add_provider(record.clone())
                     .ok(); // TODO What if the providers amount is exceeded? How to ensure only the closest one are kept?
+                self.behaviour
+                    .foreign_provider_keys
+                    .insert(record.key.clone());
                 info!(
                     node = self.node_snapshot.name,
                     provider = record.provider.to_base58(),
@@ -314,6 +338,72 @@ impl SwarmContext {
     }
 }
 
+/// Methods on SwarmContext for starting the Kademlia queries, their results come
+/// with the QueryProgressed events
+impl SwarmContext {
+    /// Finds the providers of the object, sent in batches as they are found
+    pub(crate) fn get_providers(
+        &mut self,
+        obj_id: proto::Hash,
+        providers_sender: mpsc::Sender<ProvidersBatch>,
+    ) {
+        self.print_neighbours();
+        let query_id = self
+            .swarm
+            .behaviour_mut()
+            .kademlia
+            .get_providers(RecordKey::new(&obj_id.bytes));
+        self.behaviour
+            .pending_inner_get_providers
+            .insert(query_id, providers_sender);
+    }
+
+    /// Finds the `k` peers closest to the key, an object ID or a peer ID
+    pub(crate) fn get_closest_peers<K>(
+        &mut self,
+        key: K,
+        response_sender: oneshot::Sender<Vec<PeerId>>,
+    ) where
+        K: Into<KBucketKey<K>> + Into<Vec<u8>> + Clone,
+    {
+        self.print_neighbours();
+        let query_id = self.swarm.behaviour_mut().kademlia.get_closest_peers(key);
+        self.behaviour
+            .pending_inner_get_closest_peers
+            .insert(query_id, (Vec::new(), response_sender));
+    }
+
+    /// Stops announcing the object and removes its record. The object is deleted
+    /// from the vault unless `keep_in_vault` is set
+    pub(crate) async fn stop_providing(
+        &mut self,
+        obj_id: proto::Hash,
+        keep_in_vault: bool,
+        response_sender: oneshot::Sender<Result<()>>,
+    ) {
+        self.swarm
+            .behaviour_mut()
+            .kademlia
+            .stop_providing(&RecordKey::from(obj_id.bytes.to_vec()));
+        self.remove_object_record(&obj_id);
+        self.behaviour.providing.remove(&obj_id);
+        for waiter in self.announcer.forget(&obj_id) {
+            self.respond_provided(waiter, Err(anyhow!("Stopped providing")));
+        }
+        if keep_in_vault {
+            let _ = response_sender.send(Ok(()));
+            return;
+        }
+        let result = self
+            .vault_ref
+            .ask(DeleteTypedObject { hash: obj_id })
+            .await
+            .map(|_| ())
+            .map_err(|_| anyhow!("Failed to remove from vault"));
+        let _ = response_sender.send(result);
+    }
+}
+
 /// Utility related to the Kademlia behaviour
 impl SwarmContext {
     pub async fn get_object_from_vault(
@@ -338,13 +428,22 @@ impl SwarmContext {
         }
     }
 
-    pub async fn put_object_into_vault(&mut self, obj: proto::TypedObject) -> Result<()> {
+    /// Stores the object with its provenance, the source is None for the objects
+    /// published by the node
+    pub async fn put_object_into_vault(
+        &mut self,
+        obj: proto::TypedObject,
+        kind: ProvenanceKind,
+        source: Option<PeerId>,
+    ) -> Result<()> {
         let obj_id: proto::Hash = proto::Hash::try_from(&obj).unwrap();
 
         self.vault_ref
-            .ask(StoreObject {
+            .ask(StoreReceivedObject {
                 hash: obj_id,
-                object: ObjectEnum::Typed(obj),
+                object: obj,
+                kind,
+                source,
             })
             .send()
             .await?;
@@ -352,6 +451,32 @@ impl SwarmContext {
         Ok(())
     }
 
+    /// Removes the provider records of other peers that were not republished
+    /// before their TTL passed, so they are no longer returned to the queriers
+    pub(crate) fn remove_expired_providers(&mut self) {
+        let now = Instant::now();
+        let store = self.swarm.behaviour_mut().kademlia.store_mut();
+        let mut removed = 0;
+
+        self.behaviour.foreign_provider_keys.retain(|key| {
+            let providers = store.providers(key);
+            for record in providers.iter().filter(|r| r.is_expired(now)) {
+                store.remove_provider(key, &record.provider);
+                removed += 1;
+            }
+
+            providers.iter().any(|r| !r.is_expired(now))
+        });
+
+        if removed > 0 {
+            debug!(
+                node = self.node_snapshot.name,
+                removed = removed,
+                "Removed expired provider records"
+            );
+        }
+    }
+
     pub(crate) fn print_providers(&mut self, obj_id_kad: &RecordKey) {
         debug!(
             node = self.node_snapshot.name,
@@ -0,0 +1,414 @@
+use std::time::{Duration, SystemTime};
+
+use anyhow::{anyhow, Result};
+use kameo::request::MessageSend;
+use liberum_core::proto::{
+    self, DirectMessageObject, MessagePayload, ResultErrorCode, SignedObject,
+};
+use libp2p::kad::{KBucketKey, K_VALUE};
+use libp2p::request_response::{self, OutboundRequestId, ResponseChannel};
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+use tokio::sync::oneshot;
+use tracing::{debug, error, warn};
+
+use crate::swarm_runner::SwarmContext;
+use crate::vault::backend::MailboxEntry;
+use crate::vault::{
+    DeleteExpiredMailboxEntries, DeleteMailboxEntries, LoadMailboxEntries, MailboxSize,
+    StoreMailboxEntry,
+};
+
+///! The module contains the request_response behaviour of the mailboxes. A message
+///! for a peer which can't be reached is kept by the peers closest to its peer ID.
+///! The recipient fetches the messages from them when it starts and then asks them
+///! to delete the received ones. Only the messages encrypted for the recipient are
+///! kept, and only the recipient itself can fetch or delete them.
+///!
+///! A peer keeps the messages only for the recipients it's one of the closest peers
+///! of, as far as its routing table knows, so it can't be made to keep the mailboxes
+///! of the whole network. Every sender gets a part of the mailbox of a recipient and
+///! all the mailboxes together are limited in size. The messages are fetched in
+///! pages, so a full mailbox doesn't make a response too big.
+
+/// How many messages are kept for one recipient
+pub const MAILBOX_MAX_ENTRIES: usize = 100;
+/// How many messages of one sender are kept for one recipient, so a single sender
+/// can't fill the mailbox
+const MAILBOX_MAX_ENTRIES_PER_SENDER: usize = 10;
+/// The size of the messages kept for all the recipients together
+const MAILBOX_MAX_BYTES: u64 = 64 * 1024 * 1024;
+/// The most messages in a page of a fetch, the first one is sent even if it's bigger
+const MAILBOX_PAGE_ENTRIES: usize = 20;
+const MAILBOX_PAGE_BYTES: usize = 1024 * 1024;
+/// The messages are kept by the closest peers of the recipient, as many as the
+/// queries of the sender and the recipient find
+const MAILBOX_CLOSEST_PEERS: usize = K_VALUE.get();
+/// How long the messages are kept when the recipient does not fetch them
+const MAILBOX_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+#[derive(Serialize, Deserialize, Debug)]
+pub enum MailboxRequest {
+    /// Keep the message until its recipient fetches it. Sent by the sender of the message
+    Store { message: SignedObject },
+    /// Return a page of the messages kept for the requesting peer, the ones after
+    /// the message with the ID or from the first one
+    Fetch { after: Option<String> },
+    /// Delete the messages of the requesting peer with the IDs
+    Delete { ids: Vec<String> },
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub enum MailboxResponse {
+    Stored,
+    /// The page of the messages, `more` if there are more after it
+    Messages {
+        messages: Vec<SignedObject>,
+        more: bool,
+    },
+    Deleted(usize),
+    Rejected(ResultErrorCode),
+}
+
+impl SwarmContext {
+    pub(crate) async fn handle_mailbox(
+        &mut self,
+        event: request_response::Event<MailboxRequest, MailboxResponse>,
+    ) {
+        match event {
+            request_response::Event::Message { message, peer } => match message {
+                request_response::Message::Request {
+                    request, channel, ..
+                } => self.handle_mailbox_request(peer, request, channel).await,
+                request_response::Message::Response {
+                    request_id,
+                    response,
+                } => self.handle_mailbox_response(request_id, response),
+            },
+            request_response::Event::OutboundFailure {
+                peer,
+                request_id,
+                error,
+            } => {
+                debug!(
+                    node = self.node_snapshot.name,
+                    peer = peer.to_base58(),
+                    err = error.to_string(),
+                    "Mailbox request failed"
+                );
+                self.behaviour
+                    .pending_inner_mailbox
+                    .respond(&request_id, Err(anyhow!("Outbound failure").context(error)));
+            }
+            e => debug!(
+                node = self.node_snapshot.name,
+                "Received mailbox event! {e:?}"
+            ),
+        }
+    }
+
+    pub(crate) fn send_mailbox_request(
+        &mut self,
+        peer_id: PeerId,
+        request: MailboxRequest,
+        response_sender: oneshot::Sender<Result<MailboxResponse>>,
+    ) {
+        let request_id = self
+            .swarm
+            .behaviour_mut()
+            .mailbox
+            .send_request(&peer_id, request);
+        self.behaviour
+            .pending_inner_mailbox
+            .insert(request_id, response_sender);
+    }
+
+    fn handle_mailbox_response(
+        &mut self,
+        request_id: OutboundRequestId,
+        response: MailboxResponse,
+    ) {
+        self.behaviour
+            .pending_inner_mailbox
+            .respond(&request_id, Ok(response));
+    }
+
+    async fn handle_mailbox_request(
+        &mut self,
+        peer: PeerId,
+        request: MailboxRequest,
+        channel: ResponseChannel<MailboxResponse>,
+    ) {
+        let recipient = peer.to_base58();
+        let response = match request {
+            MailboxRequest::Store { message } => self.keep_message(&peer, message).await,
+            MailboxRequest::Fetch { after } => self.fetch_mailbox(recipient, after).await,
+            MailboxRequest::Delete { ids } => {
+                let deleted = self
+                    .vault_ref
+                    .ask(DeleteMailboxEntries { recipient, ids })
+                    .send()
+                    .await;
+                deleted
+                    .map(MailboxResponse::Deleted)
+                    .map_err(|e| anyhow!(e))
+            }
+        };
+
+        let response = response.unwrap_or_else(|e| {
+            error!(
+                node = self.node_snapshot.name,
+                err = e.to_string(),
+                "Failed to handle mailbox request"
+            );
+            MailboxResponse::Rejected(ResultErrorCode::Other)
+        });
+        if self
+            .swarm
+            .behaviour_mut()
+            .mailbox
+            .send_response(channel, response)
+            .is_err()
+        {
+            debug!(
+                node = self.node_snapshot.name,
+                peer = peer.to_base58(),
+                "Could not respond to mailbox request"
+            );
+        }
+    }
+
+    /// Keeps the message if it was signed by the peer and encrypted for its recipient,
+    /// this node is close to the recipient and the message fits in the quotas
+    async fn keep_message(
+        &mut self,
+        peer: &PeerId,
+        signed: SignedObject,
+    ) -> Result<MailboxResponse> {
+        let message = match DirectMessageObject::verify(&signed) {
+            Ok(message) => message,
+            Err(e) => {
+                debug!(
+                    node = self.node_snapshot.name,
+                    err = e.to_string(),
+                    "Rejected message to keep"
+                );
+                return Ok(MailboxResponse::Rejected(ResultErrorCode::InvalidMessage));
+            }
+        };
+        let encrypted = matches!(message.payload, MessagePayload::Encrypted { .. });
+        let recipient = message.recipient_id();
+        let (true, Ok(recipient), Ok(sender)) = (encrypted, recipient, message.sender_id()) else {
+            return Ok(MailboxResponse::Rejected(ResultErrorCode::InvalidMessage));
+        };
+        if sender != *peer {
+            return Ok(MailboxResponse::Rejected(ResultErrorCode::InvalidMessage));
+        }
+
+        if !self.is_close_to(&recipient) {
+            debug!(
+                node = self.node_snapshot.name,
+                recipient = recipient.to_base58(),
+                "Not keeping the message of a distant peer"
+            );
+            return Ok(MailboxResponse::Rejected(ResultErrorCode::Other));
+        }
+
+        let recipient = recipient.to_base58();
+        let sender = sender.to_base58();
+        let id = proto::Hash::try_from(&signed.object)?.to_string();
+        let message = bincode::serialize(&signed)?;
+        self.delete_expired_mailbox_entries().await?;
+        let entries = self
+            .vault_ref
+            .ask(LoadMailboxEntries {
+                recipient: recipient.clone(),
+            })
+            .send()
+            .await?;
+        if entries.iter().any(|entry| entry.id == id) {
+            return Ok(MailboxResponse::Stored);
+        }
+        let kept_bytes = self.vault_ref.ask(MailboxSize).send().await?;
+        if let Err(code) = check_quota(&entries, &sender, message.len(), kept_bytes) {
+            warn!(
+                node = self.node_snapshot.name,
+                recipient = recipient,
+                sender = sender,
+                "Mailbox quota exceeded"
+            );
+            return Ok(MailboxResponse::Rejected(code));
+        }
+
+        let entry = MailboxEntry {
+            id,
+            recipient,
+            sender,
+            stored_at: SystemTime::now(),
+            message,
+        };
+        self.vault_ref
+            .ask(StoreMailboxEntry { entry })
+            .send()
+            .await?;
+
+        Ok(MailboxResponse::Stored)
+    }
+
+    async fn fetch_mailbox(
+        &mut self,
+        recipient: String,
+        after: Option<String>,
+    ) -> Result<MailboxResponse> {
+        self.delete_expired_mailbox_entries().await?;
+        let entries = self
+            .vault_ref
+            .ask(LoadMailboxEntries { recipient })
+            .send()
+            .await?;
+
+        let (page, more) = page(&entries, after.as_deref());
+        let messages = page
+            .iter()
+            .filter_map(|entry| bincode::deserialize(&entry.message).ok())
+            .collect();
+        Ok(MailboxResponse::Messages { messages, more })
+    }
+
+    /// Whether fewer than `MAILBOX_CLOSEST_PEERS` peers in the routing table are
+    /// closer to the peer than this node, the peer itself not counted
+    fn is_close_to(&mut self, peer: &PeerId) -> bool {
+        let target = KBucketKey::from(*peer);
+        let own_distance = target.distance(&KBucketKey::from(*self.swarm.local_peer_id()));
+        let closer = self
+            .swarm
+            .behaviour_mut()
+            .kademlia
+            .get_closest_local_peers(&target)
+            .take_while(|key| target.distance(key) < own_distance)
+            .filter(|key| key.preimage() != peer)
+            .take(MAILBOX_CLOSEST_PEERS)
+            .count();
+        closer < MAILBOX_CLOSEST_PEERS
+    }
+
+    async fn delete_expired_mailbox_entries(&mut self) -> Result<()> {
+        let before = SystemTime::now() - MAILBOX_TTL;
+        self.vault_ref
+            .ask(DeleteExpiredMailboxEntries { before })
+            .send()
+            .await?;
+        Ok(())
+    }
+}
+
+/// Checks that the message of the sender with the size fits in the mailbox of the
+/// recipient with the entries and in the size of all the kept messages. The
+/// rejected sender may retry when its oldest message in the mailbox expires
+fn check_quota(
+    entries: &[MailboxEntry],
+    sender: &str,
+    size: usize,
+    kept_bytes: u64,
+) -> std::result::Result<(), ResultErrorCode> {
+    let of_sender: Vec<&MailboxEntry> = entries.iter().filter(|e| e.sender == sender).collect();
+    let full = if of_sender.len() >= MAILBOX_MAX_ENTRIES_PER_SENDER {
+        of_sender.into_iter().map(|e| e.stored_at).min()
+    } else if entries.len() >= MAILBOX_MAX_ENTRIES {
+        entries.iter().map(|e| e.stored_at).min()
+    } else if kept_bytes + size as u64 > MAILBOX_MAX_BYTES {
+        Some(SystemTime::now())
+    } else {
+        return Ok(());
+    };
+
+    let retry_after_secs = full
+        .and_then(|stored_at| {
+            (stored_at + MAILBOX_TTL)
+                .duration_since(SystemTime::now())
+                .ok()
+        })
+        .unwrap_or(MAILBOX_TTL)
+        .as_secs();
+    Err(ResultErrorCode::QuotaExceeded { retry_after_secs })
+}
+
+/// The entries after the one with the ID, or from the first one if it's not kept,
+/// up to the size of a page. True if there are more entries after the page
+fn page<'a>(entries: &'a [MailboxEntry], after: Option<&str>) -> (&'a [MailboxEntry], bool) {
+    let start = after
+        .and_then(|after| entries.iter().position(|e| e.id == after))
+        .map_or(0, |position| position + 1);
+    let rest = &entries[start..];
+
+    let mut end = 0;
+    let mut bytes = 0;
+    for entry in rest.iter().take(MAILBOX_PAGE_ENTRIES) {
+        bytes += entry.message.len();
+        if end > 0 && bytes > MAILBOX_PAGE_BYTES {
+            break;
+        }
+        end += 1;
+    }
+    (&rest[..end], end < rest.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(id: usize, sender: &str, size: usize) -> MailboxEntry {
+        MailboxEntry {
+            id: id.to_string(),
+            recipient: "recipient".to_string(),
+            sender: sender.to_string(),
+            stored_at: SystemTime::now(),
+            message: vec![0; size],
+        }
+    }
+
+    #[test]
+    fn check_quota_test() {
+        let entries: Vec<MailboxEntry> = (0..MAILBOX_MAX_ENTRIES_PER_SENDER)
+            .map(|i| entry(i, "spammer", 10))
+            .collect();
+        assert!(check_quota(&entries, "friend", 10, 0).is_ok());
+        assert!(matches!(
+            check_quota(&entries, "spammer", 10, 0),
+            Err(ResultErrorCode::QuotaExceeded { retry_after_secs }) if retry_after_secs > 0
+        ));
+        assert!(check_quota(&entries, "friend", 10, MAILBOX_MAX_BYTES - 10).is_ok());
+        assert!(check_quota(&entries, "friend", 11, MAILBOX_MAX_BYTES - 10).is_err());
+
+        let entries: Vec<MailboxEntry> = (0..MAILBOX_MAX_ENTRIES)
+            .map(|i| entry(i, &i.to_string(), 10))
+            .collect();
+        assert!(check_quota(&entries, "friend", 10, 0).is_err());
+    }
+
+    #[test]
+    fn page_test() {
+        let entries: Vec<MailboxEntry> = (0..MAILBOX_PAGE_ENTRIES + 5)
+            .map(|i| entry(i, "sender", 10))
+            .collect();
+        let (first, more) = page(&entries, None);
+        assert_eq!(first.len(), MAILBOX_PAGE_ENTRIES);
+        assert!(more);
+        let (second, more) = page(&entries, Some(&first.last().unwrap().id));
+        assert_eq!(second.len(), 5);
+        assert_eq!(second[0].id, MAILBOX_PAGE_ENTRIES.to_string());
+        assert!(!more);
+        assert_eq!(
+            page(&entries, Some("unknown")).0.len(),
+            MAILBOX_PAGE_ENTRIES
+        );
+
+        // The messages bigger than a page are sent one by one
+        let entries: Vec<MailboxEntry> = (0..3)
+            .map(|i| entry(i, "sender", MAILBOX_PAGE_BYTES))
+            .collect();
+        let (first, more) = page(&entries, None);
+        assert_eq!(first.len(), 1);
+        assert!(more);
+    }
+}
@@ -0,0 +1,186 @@
+use std::time::SystemTime;
+
+use anyhow::{anyhow, Result};
+use kameo::request::MessageSend;
+use liberum_core::proto::{self, DirectMessageObject, ResultErrorCode, ResultObject, SignedObject};
+use liberum_core::types::{InboxMessage, MessageContent, NodeEventKind};
+use libp2p::request_response::{self, OutboundRequestId, ResponseChannel};
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+use tokio::sync::oneshot;
+use tracing::{debug, error, warn};
+
+use crate::node::events;
+use crate::node::retry::PermanentError;
+use crate::swarm_runner::reputation::Misbehaviour;
+use crate::swarm_runner::SwarmContext;
+use crate::vault::StoreMessage;
+
+///! The module contains the request_response behaviour for the direct messages
+///! between peers. The recipient verifies and decrypts the message, stores it in
+///! its inbox and acknowledges the delivery in the response.
+
+/// A request of the messenger protocol, the message signed by the sender
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DirectMessageRequest {
+    pub message: SignedObject,
+}
+
+impl SwarmContext {
+    pub(crate) async fn handle_messenger(
+        &mut self,
+        event: request_response::Event<DirectMessageRequest, ResultObject>,
+    ) {
+        match event {
+            request_response::Event::Message { message, peer } => match message {
+                request_response::Message::Request {
+                    request, channel, ..
+                } => {
+                    self.stats.bytes_received += request.message.object.data.len() as u64;
+                    self.handle_messenger_request(peer, request, channel).await
+                }
+                request_response::Message::Response {
+                    request_id,
+                    response,
+                } => self.handle_messenger_response(request_id, response),
+            },
+            request_response::Event::OutboundFailure {
+                peer,
+                request_id,
+                error,
+            } => {
+                warn!(
+                    node = self.node_snapshot.name,
+                    peer = peer.to_base58(),
+                    err = error.to_string(),
+                    "Could not deliver message"
+                );
+                if let Some((_, sender)) = self
+                    .behaviour
+                    .pending_inner_send_message
+                    .remove(&request_id)
+                {
+                    let _ = sender.send(Err(anyhow!("Outbound failure").context(error)));
+                }
+            }
+            e => debug!(
+                node = self.node_snapshot.name,
+                "Received messenger event! {e:?}"
+            ),
+        }
+    }
+
+    /// Signs the message and sends it to the peer. Responds with the message ID when
+    /// the peer acknowledges the delivery
+    pub(crate) fn send_direct_message(
+        &mut self,
+        peer_id: PeerId,
+        content: MessageContent,
+        encrypt: bool,
+        response_sender: oneshot::Sender<Result<proto::Hash>>,
+    ) {
+        let sealed =
+            DirectMessageObject::seal(&self.node_snapshot.keypair, &peer_id, content, encrypt);
+        let (message, id) = match sealed {
+            Ok(sealed) => sealed,
+            Err(e) => {
+                let _ = response_sender.send(Err(e));
+                return;
+            }
+        };
+
+        self.stats.bytes_sent += message.object.data.len() as u64;
+        let request_id = self
+            .swarm
+            .behaviour_mut()
+            .messenger
+            .send_request(&peer_id, DirectMessageRequest { message });
+        self.behaviour
+            .pending_inner_send_message
+            .insert(request_id, (id, response_sender));
+    }
+
+    async fn handle_messenger_request(
+        &mut self,
+        peer: PeerId,
+        request: DirectMessageRequest,
+        channel: ResponseChannel<ResultObject>,
+    ) {
+        let opened =
+            DirectMessageObject::open(&request.message, &peer, &self.node_snapshot.keypair);
+        let result = match opened {
+            Ok(opened) => {
+                let message = InboxMessage {
+                    id: opened.id.to_string(),
+                    sender: opened.sender.to_base58(),
+                    sent_at: opened.sent_at,
+                    received_at: SystemTime::now(),
+                    encrypted: opened.encrypted,
+                    content: opened.content,
+                };
+                self.store_message(message).await
+            }
+            Err(e) => {
+                debug!(
+                    node = self.node_snapshot.name,
+                    peer = peer.to_base58(),
+                    err = e.to_string(),
+                    "Rejected message"
+                );
+                self.report_peer(&peer, Misbehaviour::ProtocolViolation)
+                    .await;
+                Err(ResultErrorCode::InvalidMessage)
+            }
+        };
+
+        let response = ResultObject { result };
+        if self
+            .swarm
+            .behaviour_mut()
+            .messenger
+            .send_response(channel, response)
+            .is_err()
+        {
+            debug!(
+                node = self.node_snapshot.name,
+                peer = peer.to_base58(),
+                "Could not acknowledge message"
+            );
+        }
+    }
+
+    async fn store_message(&mut self, message: InboxMessage) -> Result<(), ResultErrorCode> {
+        let sender = message.sender.clone();
+        if let Err(e) = self.vault_ref.ask(StoreMessage { message }).send().await {
+            error!(
+                node = self.node_snapshot.name,
+                err = e.to_string(),
+                "Failed to store message"
+            );
+            return Err(ResultErrorCode::Other);
+        }
+
+        events::record(
+            &self.events,
+            NodeEventKind::Message,
+            format!("Message received from {sender}"),
+        );
+        Ok(())
+    }
+
+    fn handle_messenger_response(&mut self, request_id: OutboundRequestId, response: ResultObject) {
+        let Some((id, sender)) = self
+            .behaviour
+            .pending_inner_send_message
+            .remove(&request_id)
+        else {
+            return;
+        };
+
+        let result = response
+            .result
+            .map(|_| id)
+            .map_err(|code| PermanentError::MessageRejected(code.to_string()).into());
+        let _ = sender.send(result);
+    }
+}
@@ -1,21 +1,41 @@
+pub mod group_feed;
+pub mod identify;
 pub mod kademlia;
+pub mod mailbox;
+pub mod messenger;
+pub mod object_record;
 pub mod object_sender;
+pub mod peer_exchange;
+pub mod pending;
+pub mod ping;
+pub mod profile;
+pub mod rotation;
+pub mod transfer;
+pub mod wire;
 use anyhow::Result;
-use liberum_core::{proto::*, DaemonQueryStats};
-use libp2p::request_response::ResponseChannel;
-use std::collections::HashMap;
+use liberum_core::proto::*;
+use std::collections::{HashMap, HashSet};
+use std::time::Instant;
 
 use libp2p::{
-    kad,
-    request_response::{self, OutboundRequestId},
+    gossipsub, kad,
+    request_response::{self, InboundRequestId, OutboundRequestId},
     swarm::{ConnectionId, NetworkBehaviour},
     PeerId,
 };
+use mailbox::{MailboxRequest, MailboxResponse};
+use messenger::DirectMessageRequest;
 use object_sender::*;
-use tokio::sync::oneshot;
+use peer_exchange::{PeerExchangeRequest, PeerExchangeResponse};
+use pending::{Expired, PendingMap, PendingRequests, Sweep, PENDING_TIMEOUT};
+use tokio::sync::{mpsc, oneshot};
+use wire::ObjectSenderCodec;
 
 use liberum_core::proto::{self, TypedObject};
 
+use super::announcer::ProvideWaiter;
+use super::messages::ProvidersBatch;
+use super::upload_quota::UploadPermit;
 use super::SwarmContext;
 
 ///! The module contains the definition of the behaviour of the network
@@ -24,53 +44,121 @@ use super::SwarmContext;
 #[derive(NetworkBehaviour)]
 pub struct LiberumNetoBehavior {
     pub kademlia: kad::Behaviour<kad::store::MemoryStore>,
-    pub object_sender: request_response::cbor::Behaviour<ObjectSendRequest, ObjectResponse>,
+    pub object_sender: request_response::Behaviour<ObjectSenderCodec>,
+    pub ping: libp2p::ping::Behaviour,
+    pub messenger: request_response::cbor::Behaviour<DirectMessageRequest, ResultObject>,
+    pub mailbox: request_response::cbor::Behaviour<MailboxRequest, MailboxResponse>,
+    pub gossipsub: gossipsub::Behaviour,
+    pub identify: libp2p::identify::Behaviour,
+    pub transfer: libp2p_stream::Behaviour,
+    pub peer_exchange: request_response::cbor::Behaviour<PeerExchangeRequest, PeerExchangeResponse>,
+    pub connection_limits: libp2p::connection_limits::Behaviour,
 }
 
 /// Data required to handle events from the behaviours. Mostly
-/// hashmaps to store query IDs and senders for the requests
+/// maps to store query IDs and senders for the requests
 pub struct BehaviourContext {
     /// A hashmap of resources that are provided by the node. Should be replaced with
     /// an implementation of VAULT
     pub providing: HashMap<proto::Hash, TypedObject>, // TODO VAULT sHOULD REPLACE THIS
-    pub pending_inner_start_providing: HashMap<kad::QueryId, oneshot::Sender<Result<()>>>,
-    pub pending_inner_send_object:
-        HashMap<OutboundRequestId, oneshot::Sender<Result<ResultObject>>>,
-    pub pending_inner_get_providers: HashMap<
+    /// The ones waiting for the started provider announcements
+    pub pending_start_providing: PendingMap<kad::QueryId, Vec<ProvideWaiter>>,
+    pub pending_inner_send_object: PendingRequests<OutboundRequestId, ResultObject>,
+    pub pending_inner_get_providers: PendingMap<kad::QueryId, mpsc::Sender<ProvidersBatch>>,
+    pub pending_inner_get_object: PendingRequests<OutboundRequestId, TypedObject>,
+    pub pending_inner_dial: PendingRequests<ConnectionId, PeerId>,
+    pub pending_inner_get_closest_peers:
+        PendingMap<kad::QueryId, (Vec<PeerId>, oneshot::Sender<Vec<PeerId>>)>,
+    pub pending_outer_delete_object: PendingRequests<OutboundRequestId, ResultObject>,
+    pub pending_inner_get_profile: PendingRequests<kad::QueryId, Option<ProfileObject>>,
+    /// The newest valid rotation found so far, sent when the query finishes
+    pub pending_inner_get_rotation: PendingMap<
         kad::QueryId,
         (
-            Vec<PeerId>,
-            oneshot::Sender<(Vec<PeerId>, Option<DaemonQueryStats>)>,
+            Option<RotationObject>,
+            oneshot::Sender<Result<Option<RotationObject>>>,
         ),
     >,
-    pub pending_inner_get_object: HashMap<OutboundRequestId, oneshot::Sender<Result<TypedObject>>>,
-    pub pending_inner_dial: HashMap<ConnectionId, oneshot::Sender<Result<()>>>,
-    pub pending_inner_get_closest_peers:
-        HashMap<kad::QueryId, (Vec<PeerId>, oneshot::Sender<Vec<PeerId>>)>,
-    pub pending_outer_start_providing:
-        HashMap<kad::QueryId, (proto::Hash, ResponseChannel<ObjectResponse>)>,
-    pub pending_outer_delete_object:
-        HashMap<OutboundRequestId, oneshot::Sender<Result<ResultObject>>>,
+    pub pending_inner_get_object_record:
+        PendingRequests<kad::QueryId, Option<(TypedObject, PeerId)>>,
+    /// The IDs of the sent messages, returned when the delivery is acknowledged
+    pub pending_inner_send_message:
+        PendingMap<OutboundRequestId, (proto::Hash, oneshot::Sender<Result<proto::Hash>>)>,
+    pub pending_inner_mailbox: PendingRequests<OutboundRequestId, MailboxResponse>,
+    /// Keys of the provider records received from other peers. The Kademlia
+    /// store can't be iterated, so the keys are needed to remove expired records
+    pub foreign_provider_keys: HashSet<kad::RecordKey>,
+    /// The groups the node is a member of, by the topics of their feeds
+    pub groups: HashMap<gossipsub::TopicHash, UserGroup>,
+    /// The newest object sender versions of the connected peers, from their identify info
+    pub wire_versions: HashMap<PeerId, wire::WireVersion>,
+    /// The connected peers supporting the transfer protocol, from their identify info
+    pub transfer_peers: HashSet<PeerId>,
+    /// The objects being sent with the object sender, until their responses are sent
+    pub uploads: HashMap<InboundRequestId, UploadPermit>,
+    /// When the peers were last sent the sample of the peers, for the rate limit
+    pub peer_exchange_served: HashMap<PeerId, Instant>,
 }
 
 impl BehaviourContext {
     pub fn new() -> Self {
         BehaviourContext {
             providing: HashMap::new(),
-            pending_inner_start_providing: HashMap::new(),
-            pending_outer_start_providing: HashMap::new(),
-            pending_inner_send_object: HashMap::new(),
-            pending_inner_get_providers: HashMap::new(),
-            pending_inner_get_object: HashMap::new(),
-            pending_inner_dial: HashMap::new(),
-            pending_inner_get_closest_peers: HashMap::new(),
-            pending_outer_delete_object: HashMap::new(),
+            pending_start_providing: PendingMap::new(PENDING_TIMEOUT).on_expiry(Expired::Query),
+            pending_inner_send_object: PendingMap::new(PENDING_TIMEOUT)
+                .on_expiry(Expired::Transfer),
+            pending_inner_get_providers: PendingMap::new(PENDING_TIMEOUT).on_expiry(Expired::Query),
+            pending_inner_get_object: PendingMap::new(PENDING_TIMEOUT).on_expiry(Expired::Transfer),
+            pending_inner_dial: PendingMap::new(PENDING_TIMEOUT),
+            pending_inner_get_closest_peers: PendingMap::new(PENDING_TIMEOUT)
+                .on_expiry(Expired::Query),
+            pending_outer_delete_object: PendingMap::new(PENDING_TIMEOUT)
+                .on_expiry(Expired::Transfer),
+            pending_inner_get_profile: PendingMap::new(PENDING_TIMEOUT).on_expiry(Expired::Query),
+            pending_inner_get_rotation: PendingMap::new(PENDING_TIMEOUT).on_expiry(Expired::Query),
+            pending_inner_get_object_record: PendingMap::new(PENDING_TIMEOUT)
+                .on_expiry(Expired::Query),
+            pending_inner_send_message: PendingMap::new(PENDING_TIMEOUT),
+            pending_inner_mailbox: PendingMap::new(PENDING_TIMEOUT),
+            foreign_provider_keys: HashSet::new(),
+            groups: HashMap::new(),
+            wire_versions: HashMap::new(),
+            transfer_peers: HashSet::new(),
+            uploads: HashMap::new(),
+            peer_exchange_served: HashMap::new(),
         }
     }
+
+    /// Every map of the requests waiting for the network, swept for the timeouts.
+    /// A new map is added here to be timed out and counted
+    pub fn pending_maps(&mut self) -> [&mut dyn Sweep; 12] {
+        [
+            &mut self.pending_start_providing,
+            &mut self.pending_inner_send_object,
+            &mut self.pending_inner_get_providers,
+            &mut self.pending_inner_get_object,
+            &mut self.pending_inner_dial,
+            &mut self.pending_inner_get_closest_peers,
+            &mut self.pending_outer_delete_object,
+            &mut self.pending_inner_get_profile,
+            &mut self.pending_inner_get_rotation,
+            &mut self.pending_inner_get_object_record,
+            &mut self.pending_inner_send_message,
+            &mut self.pending_inner_mailbox,
+        ]
+    }
+
+    /// Number of queries and requests still waiting for a response
+    pub fn pending_count(&mut self) -> usize {
+        self.pending_maps().iter().map(|map| map.pending()).sum()
+    }
 }
 
 impl SwarmContext {
     pub(crate) async fn handle_behaviour_event(&mut self, event: LiberumNetoBehaviorEvent) {
+        if let Some(peer) = active_peer(&event) {
+            self.connections.touch(&peer);
+        }
         match event {
             LiberumNetoBehaviorEvent::Kademlia(e) => {
                 self.handle_kademlia(e).await;
@@ -78,6 +166,73 @@ impl SwarmContext {
             LiberumNetoBehaviorEvent::ObjectSender(e) => {
                 self.handle_object_sender(e).await;
             }
+            LiberumNetoBehaviorEvent::Ping(e) => {
+                self.handle_ping(e);
+            }
+            LiberumNetoBehaviorEvent::Messenger(e) => {
+                self.handle_messenger(e).await;
+            }
+            LiberumNetoBehaviorEvent::Mailbox(e) => {
+                self.handle_mailbox(e).await;
+            }
+            LiberumNetoBehaviorEvent::Gossipsub(e) => {
+                self.handle_gossipsub(e).await;
+            }
+            LiberumNetoBehaviorEvent::Identify(e) => {
+                self.handle_identify(e);
+            }
+            // The transfers are handled by their own tasks
+            LiberumNetoBehaviorEvent::Transfer(()) => {}
+            LiberumNetoBehaviorEvent::PeerExchange(e) => {
+                self.handle_peer_exchange(e);
+            }
+            LiberumNetoBehaviorEvent::ConnectionLimits(e) => match e {},
         }
     }
 }
+
+/// The peer the event shows the activity of, which keeps its connections from being
+/// pruned as idle. Ping and identify run periodically on every connection, so their
+/// events are not activity
+fn active_peer(event: &LiberumNetoBehaviorEvent) -> Option<PeerId> {
+    match event {
+        LiberumNetoBehaviorEvent::Kademlia(e) => match e {
+            kad::Event::RoutingUpdated { peer, .. } => Some(*peer),
+            kad::Event::InboundRequest {
+                request: kad::InboundRequest::PutRecord { source, .. },
+            } => Some(*source),
+            kad::Event::InboundRequest {
+                request:
+                    kad::InboundRequest::AddProvider {
+                        record: Some(record),
+                    },
+            } => Some(record.provider),
+            _ => None,
+        },
+        LiberumNetoBehaviorEvent::ObjectSender(e) => request_peer(e),
+        LiberumNetoBehaviorEvent::Messenger(e) => request_peer(e),
+        LiberumNetoBehaviorEvent::Mailbox(e) => request_peer(e),
+        LiberumNetoBehaviorEvent::PeerExchange(e) => request_peer(e),
+        LiberumNetoBehaviorEvent::Gossipsub(e) => match e {
+            gossipsub::Event::Message {
+                propagation_source, ..
+            } => Some(*propagation_source),
+            gossipsub::Event::Subscribed { peer_id, .. }
+            | gossipsub::Event::Unsubscribed { peer_id, .. } => Some(*peer_id),
+            _ => None,
+        },
+        LiberumNetoBehaviorEvent::Ping(_)
+        | LiberumNetoBehaviorEvent::Identify(_)
+        | LiberumNetoBehaviorEvent::Transfer(()) => None,
+        LiberumNetoBehaviorEvent::ConnectionLimits(e) => match *e {},
+    }
+}
+
+/// The peer which sent a request or a response, or was sent a response
+fn request_peer<Req, Resp>(event: &request_response::Event<Req, Resp>) -> Option<PeerId> {
+    match event {
+        request_response::Event::Message { peer, .. }
+        | request_response::Event::ResponseSent { peer, .. } => Some(*peer),
+        _ => None,
+    }
+}
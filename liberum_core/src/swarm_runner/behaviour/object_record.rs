@@ -0,0 +1,142 @@
+use std::num::NonZeroUsize;
+
+use anyhow::{anyhow, Result};
+use kameo::request::MessageSend;
+use liberum_core::proto::{self, AccessPolicy, TypedObject};
+use libp2p::kad::{self, store::RecordStore, GetRecordError, GetRecordOk, QueryId, Record};
+use libp2p::PeerId;
+use tokio::sync::oneshot;
+use tracing::{debug, warn};
+
+use crate::swarm_runner::SwarmContext;
+use crate::vault;
+
+///! The module puts the small public objects in the DHT as Kademlia records, next
+///! to their provider records. A download asks for the record while it looks for the
+///! providers, so a tiny object like a manifest or a tag object is fetched without a
+///! connection to its provider. The objects larger than `record_objects.max_size` and
+///! the ones with an access policy are only sent by their providers.
+
+/// Methods on SwarmContext for the objects stored as records
+impl SwarmContext {
+    /// Puts the object in the DHT if it's public and small enough. The record is
+    /// stored locally at once and republished by Kademlia periodically
+    pub(crate) async fn put_object_record(&mut self, obj_id: &proto::Hash, object: &TypedObject) {
+        let config = self.node_snapshot.config.record_objects;
+        if !config.enabled {
+            return;
+        }
+        let policy = self
+            .vault_ref
+            .ask(vault::LoadAccessPolicy {
+                hash: obj_id.clone(),
+            })
+            .send()
+            .await;
+        if !matches!(policy, Ok(AccessPolicy::Public)) {
+            return;
+        }
+        let value: Vec<u8> = match object.clone().try_into() {
+            Ok(value) => value,
+            Err(_) => return,
+        };
+        if value.len() > config.max_size {
+            return;
+        }
+
+        let quorum = NonZeroUsize::new(config.quorum).map_or(kad::Quorum::One, kad::Quorum::N);
+        let record = Record::new(TypedObject::record_key(obj_id), value);
+        match self
+            .swarm
+            .behaviour_mut()
+            .kademlia
+            .put_record(record, quorum)
+        {
+            Ok(_) => debug!(
+                node = self.node_snapshot.name,
+                obj_id = obj_id.to_string(),
+                "Putting object record"
+            ),
+            Err(e) => warn!(
+                node = self.node_snapshot.name,
+                obj_id = obj_id.to_string(),
+                err = e.to_string(),
+                "Could not put object record"
+            ),
+        }
+    }
+
+    /// Stops republishing the record of the object. The copies of other peers
+    /// expire by themselves
+    pub(crate) fn remove_object_record(&mut self, obj_id: &proto::Hash) {
+        self.swarm
+            .behaviour_mut()
+            .kademlia
+            .remove_record(&TypedObject::record_key(obj_id));
+    }
+
+    /// Finds the record of the object, in the local store first. Responds with the
+    /// object and the peer which had it, None if no peer has a valid record
+    pub(crate) fn get_object_record(
+        &mut self,
+        obj_id: proto::Hash,
+        response_sender: oneshot::Sender<Result<Option<(TypedObject, PeerId)>>>,
+    ) {
+        if !self.node_snapshot.config.record_objects.enabled {
+            let _ = response_sender.send(Ok(None));
+            return;
+        }
+
+        let key = TypedObject::record_key(&obj_id);
+        let local_peer_id = *self.swarm.local_peer_id();
+        let kademlia = &mut self.swarm.behaviour_mut().kademlia;
+        if let Some(record) = kademlia.store_mut().get(&key) {
+            if let Ok(object) = TypedObject::from_record(&key, &record.value) {
+                let _ = response_sender.send(Ok(Some((object, local_peer_id))));
+                return;
+            }
+        }
+
+        let query_id = kademlia.get_record(key);
+        self.behaviour
+            .pending_inner_get_object_record
+            .insert(query_id, response_sender);
+    }
+
+    pub(crate) fn handle_outbound_query_progressed_get_object_record(
+        &mut self,
+        id: QueryId,
+        result: Result<GetRecordOk, GetRecordError>,
+    ) {
+        let response = match result {
+            Ok(GetRecordOk::FoundRecord(peer_record)) => {
+                let record = peer_record.record;
+                match TypedObject::from_record(&record.key, &record.value) {
+                    Ok(object) => {
+                        let peer = peer_record.peer.unwrap_or(*self.swarm.local_peer_id());
+                        Ok(Some((object, peer)))
+                    }
+                    Err(e) => {
+                        // Another peer may still have a valid one
+                        debug!(
+                            node = self.node_snapshot.name,
+                            err = e.to_string(),
+                            "Received invalid object record"
+                        );
+                        return;
+                    }
+                }
+            }
+            Ok(GetRecordOk::FinishedWithNoAdditionalRecord { .. }) => Ok(None),
+            Err(GetRecordError::NotFound { .. }) => Ok(None),
+            Err(e) => Err(anyhow!(e)),
+        };
+
+        if let Some(mut query) = self.swarm.behaviour_mut().kademlia.query_mut(&id) {
+            query.finish();
+        }
+        self.behaviour
+            .pending_inner_get_object_record
+            .respond(&id, response);
+    }
+}
@@ -2,33 +2,59 @@ use anyhow::anyhow;
 use anyhow::Result;
 use liberum_core::parser::{self, ObjectEnum};
 use liberum_core::proto::{
-    self, DeleteObjectQuery, PlainFileObject, QueryObject, ResultObject, SimpleIDQuery,
+    self, AccessPolicy, DeleteObjectQuery, GroupAccessToken, PlainFileObject, QueryObject,
+    QueryResultObject, ResultErrorCode, ResultObject, SimpleIDQuery, TagObject, TagQuery,
     TypedObject, UUIDTyped,
 };
-use libp2p::identity::PublicKey;
+use liberum_core::types::{
+    AuditEntry, AuditRequestKind, ModuleCallKind, ProvenanceKind, SignatureStatus,
+};
 use libp2p::{
-    kad,
-    request_response::{self, InboundRequestId, OutboundRequestId, ResponseChannel},
+    request_response::{
+        self, InboundRequestId, OutboundFailure, OutboundRequestId, ResponseChannel,
+    },
+    PeerId,
 };
 use serde::{Deserialize, Serialize};
-use tokio::sync::oneshot;
-use tracing::{debug, error};
+use std::time::SystemTime;
+use tokio::sync::{mpsc, oneshot};
+use tracing::{debug, error, warn};
 
+use crate::swarm_runner::announcer::ProvideWaiter;
+use crate::swarm_runner::reputation::Misbehaviour;
 use crate::vault;
 
 use super::super::SwarmContext;
+use super::wire;
 
 ///! The module contains the structures and hanlders for the request_response
 ///! behaviour used to share files
 
+/// The answer of an external module to a forwarded request, sent back to the swarm
+/// loop when the module call completes
+pub struct ModuleAnswer {
+    kind: ModuleCallKind,
+    peer: PeerId,
+    id: proto::Hash,
+    request: ObjectSendRequest,
+    response_channel: ResponseChannel<ObjectResponse>,
+    result: Result<Option<TypedObject>>,
+}
+
 /// An enum that represents anything that can be provided in the network.
 /// Should be replaced with an implementation of the OBJECTS
 
 /// A request to the file_share protocol
-#[derive(Serialize, Deserialize, Debug, Hash, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ObjectSendRequest {
     pub object: TypedObject,
     pub object_id: proto::Hash,
+    /// The access policy of the sent object, kept by the peer storing it
+    #[serde(default)]
+    pub policy: AccessPolicy,
+    /// The membership of the requesting peer, needed to get the objects of a group
+    #[serde(default)]
+    pub access_token: Option<GroupAccessToken>,
 }
 
 /// A response from the file_share protocol. Should be replaced with a stream
@@ -45,14 +71,15 @@ impl SwarmContext {
         event: request_response::Event<ObjectSendRequest, ObjectResponse>,
     ) {
         match event {
-            request_response::Event::Message { message, .. } => match message {
+            request_response::Event::Message { message, peer } => match message {
                 request_response::Message::Request {
                     request_id,
                     request,
                     channel,
                     ..
                 } => {
-                    self.handle_object_sender_request(request_id, request, channel)
+                    self.stats.bytes_received += request.object.data.len() as u64;
+                    self.handle_object_sender_request(peer, request_id, request, channel)
                         .await
                 }
 
@@ -60,6 +87,8 @@ impl SwarmContext {
                     request_id,
                     response,
                 } => {
+                    self.stats.bytes_received += response.object.data.len() as u64;
+                    self.connections.end_transfer(&request_id);
                     self.handle_object_sender_response(request_id, response)
                         .await
                 }
@@ -76,16 +105,36 @@ impl SwarmContext {
                     err = format!("{error}"),
                     "Outbound failure"
                 );
+                self.connections.end_transfer(&request_id);
+                if let OutboundFailure::Timeout = error {
+                    self.report_peer(&peer, Misbehaviour::Timeout).await;
+                }
+                let failure = anyhow!("Outbound failure").context(error);
                 if let Some(sender) = self.behaviour.pending_inner_get_object.remove(&request_id) {
-                    let _ = sender.send(Err(anyhow!("Outbound failure").context(error)));
-                } else if let Some(sender) = self
-                    .behaviour
-                    .pending_outer_delete_object
-                    .remove(&request_id)
-                {
-                    let _ = sender.send(Err(anyhow!("Outbound failure").context(error)));
+                    let _ = sender.send(Err(failure));
+                } else {
+                    self.behaviour
+                        .pending_outer_delete_object
+                        .respond(&request_id, Err(failure));
                 }
             }
+            // The upload ends when its response is sent or fails
+            request_response::Event::ResponseSent { request_id, .. } => {
+                self.behaviour.uploads.remove(&request_id);
+            }
+            request_response::Event::InboundFailure {
+                peer,
+                request_id,
+                error,
+            } => {
+                debug!(
+                    node = self.node_snapshot.name,
+                    peer = peer.to_base58(),
+                    err = format!("{error}"),
+                    "Inbound failure"
+                );
+                self.behaviour.uploads.remove(&request_id);
+            }
             e => debug!(
                 node = self.node_snapshot.name,
                 "Received request_response event! {e:?}"
@@ -94,11 +143,196 @@ impl SwarmContext {
     }
 }
 
+/// Methods on SwarmContext for sending the object requests to the peers, the
+/// responses come with the object_sender events
+impl SwarmContext {
+    /// Downloads the object from the peer, in chunks if the peer streams them
+    pub(crate) async fn get_object(
+        &mut self,
+        obj_id: proto::Hash,
+        peer_id: PeerId,
+        access_token: Option<GroupAccessToken>,
+        response_sender: oneshot::Sender<Result<TypedObject>>,
+    ) {
+        debug!(
+            "Sending a get object request for obj_id {} to peer {}",
+            obj_id.to_string(),
+            peer_id.to_base58()
+        );
+        if self.reputation.is_banned(&peer_id) {
+            let _ = response_sender.send(Err(anyhow!("Peer {peer_id} is banned")));
+            return;
+        }
+        if !self.is_peer_allowed(&peer_id) {
+            let _ = response_sender.send(Err(anyhow!("Peer {peer_id} is blocked")));
+            return;
+        }
+        if &peer_id == self.swarm.local_peer_id() {
+            debug!(
+                peer = peer_id.to_base58(),
+                local = self.swarm.local_peer_id().to_base58(),
+                "Local peer requested object"
+            );
+            let result = self
+                .get_object_from_vault(obj_id)
+                .await
+                .ok_or(anyhow!("Object not found"));
+            let _ = response_sender.send(result);
+            return;
+        }
+
+        if self.behaviour.transfer_peers.contains(&peer_id) {
+            // The peer streams the object in chunks, so it may be of any size
+            self.connections.touch(&peer_id);
+            self.transfers
+                .fetch(peer_id, obj_id, access_token, response_sender);
+        } else {
+            let query: TypedObject = SimpleIDQuery { id: obj_id }.into();
+            match self.send_query(peer_id, query, access_token) {
+                Ok(request_id) => self
+                    .behaviour
+                    .pending_inner_get_object
+                    .insert(request_id, response_sender),
+                Err(e) => {
+                    let _ = response_sender.send(Err(e));
+                }
+            }
+        }
+        self.print_neighbours();
+    }
+
+    /// Sends the query to the peer, its answer is passed on as it is, just like a
+    /// downloaded object
+    pub(crate) fn query_peer(
+        &mut self,
+        query: TypedObject,
+        peer_id: PeerId,
+        response_sender: oneshot::Sender<Result<TypedObject>>,
+    ) {
+        if self.reputation.is_banned(&peer_id) || !self.is_peer_allowed(&peer_id) {
+            let _ = response_sender.send(Err(anyhow!("Peer {peer_id} is not allowed")));
+            return;
+        }
+        match self.send_query(peer_id, query, None) {
+            Ok(request_id) => self
+                .behaviour
+                .pending_inner_get_object
+                .insert(request_id, response_sender),
+            Err(e) => {
+                let _ = response_sender.send(Err(e));
+            }
+        }
+    }
+
+    /// Sends the object to the peer to be stored and provided
+    pub(crate) fn send_object(
+        &mut self,
+        object: TypedObject,
+        obj_id: proto::Hash,
+        policy: AccessPolicy,
+        peer_id: PeerId,
+        response_sender: oneshot::Sender<Result<ResultObject>>,
+    ) {
+        debug!("Sending Object {:?}", object);
+        let calculated_obj_id = proto::Hash::try_from(&object).unwrap();
+        if calculated_obj_id != obj_id {
+            debug!(
+                node = self.node_snapshot.name,
+                calculated_obj_id = calculated_obj_id.to_string(),
+                obj_id = obj_id.to_string(),
+                "Object ID does not match the hash of the object"
+            );
+            let _ = response_sender.send(Err(anyhow!(
+                "Object ID does not match the hash of the object"
+            )));
+            return;
+        }
+        if object.data.len() as u64 >= wire::V2_REQUEST_SIZE_MAXIMUM {
+            let _ = response_sender.send(Err(anyhow!("Object is too big to be sent to the peer")));
+            return;
+        }
+
+        let request_id = self.send_object_request(
+            peer_id,
+            ObjectSendRequest {
+                object,
+                object_id: obj_id,
+                policy,
+                access_token: None,
+            },
+        );
+        self.behaviour
+            .pending_inner_send_object
+            .insert(request_id, response_sender);
+    }
+
+    /// Asks the peer to delete the object, the query is signed by the node
+    pub(crate) fn delete_object(
+        &mut self,
+        obj_id: proto::Hash,
+        peer: PeerId,
+        response_sender: oneshot::Sender<Result<ResultObject>>,
+    ) {
+        let request_id =
+            DeleteObjectQuery::sign_ed25519(obj_id, self.node_snapshot.keypair.clone())
+                .and_then(|query| self.send_query(peer, query.into(), None));
+        match request_id {
+            Ok(request_id) => self
+                .behaviour
+                .pending_outer_delete_object
+                .insert(request_id, response_sender),
+            Err(e) => {
+                let _ = response_sender.send(Err(e));
+            }
+        }
+    }
+
+    /// Sends the query object wrapping the query to the peer
+    fn send_query(
+        &mut self,
+        peer_id: PeerId,
+        query: TypedObject,
+        access_token: Option<GroupAccessToken>,
+    ) -> Result<OutboundRequestId> {
+        let object: TypedObject = QueryObject {
+            query_object: query,
+        }
+        .into();
+        let object_id = proto::Hash::try_from(&object)?;
+        Ok(self.send_object_request(
+            peer_id,
+            ObjectSendRequest {
+                object,
+                object_id,
+                policy: AccessPolicy::Public,
+                access_token,
+            },
+        ))
+    }
+
+    /// Sends the request and counts the transfer to the peer
+    fn send_object_request(
+        &mut self,
+        peer_id: PeerId,
+        request: ObjectSendRequest,
+    ) -> OutboundRequestId {
+        self.stats.bytes_sent += request.object.data.len() as u64;
+        let request_id = self
+            .swarm
+            .behaviour_mut()
+            .object_sender
+            .send_request(&peer_id, request);
+        self.connections.begin_transfer(request_id, peer_id);
+        request_id
+    }
+}
+
 /// Methods on SwarmContext for handling file sharing
 impl SwarmContext {
     /// Handle a object_send request depending on the type of the data which ID is requested
     async fn handle_object_sender_request(
         &mut self,
+        peer: PeerId,
         request_id: InboundRequestId,
         request: ObjectSendRequest,
         response_channel: ResponseChannel<ObjectResponse>,
@@ -118,6 +352,8 @@ impl SwarmContext {
                 err = format!("{e}"),
                 "Can't hash received object to verify hash"
             );
+            self.report_peer(&peer, Misbehaviour::ProtocolViolation)
+                .await;
             self.respond_err(&request, response_channel);
             return;
         }
@@ -129,10 +365,12 @@ impl SwarmContext {
                 received_id = request.object_id.to_string(),
                 id = id.to_string(),
                 "File Request ID does not match actual ID!"
-            )
+            );
+            self.report_peer(&peer, Misbehaviour::FailedIntegrity).await;
         }
 
         self.handle_request_typed(
+            peer,
             request.object.clone(),
             id,
             request,
@@ -144,6 +382,7 @@ impl SwarmContext {
 
     async fn handle_request_typed(
         &mut self,
+        peer: PeerId,
         obj: proto::TypedObject,
         id: proto::Hash,
         request: ObjectSendRequest,
@@ -151,7 +390,29 @@ impl SwarmContext {
         mut response_channel: ResponseChannel<ObjectResponse>,
     ) {
         let mut typed: Option<TypedObject> = Some(obj);
+        // Whether the object unwrapped last was signed by the sending peer
+        let mut signed_by_peer = false;
         while let Some(obj) = typed.clone() {
+            if !self.node_snapshot.config.modules.is_enabled(&obj.uuid) {
+                debug!(
+                    node = self.node_snapshot.name,
+                    type_id = obj.uuid.to_string(),
+                    "Rejected object of a disabled module"
+                );
+                self.respond_err(&request, response_channel);
+                return;
+            }
+            if self.module_host.handles(&obj.uuid) {
+                self.call_module(
+                    ModuleCallKind::Store,
+                    peer,
+                    obj,
+                    id,
+                    request,
+                    response_channel,
+                );
+                return;
+            }
             let obj = parser::parse_typed(obj).await;
             if let Err(e) = obj {
                 error!(
@@ -159,6 +420,8 @@ impl SwarmContext {
                     err = format!("{e}"),
                     "Error parsing request object"
                 );
+                self.report_peer(&peer, Misbehaviour::ProtocolViolation)
+                    .await;
                 self.respond_err(&request, response_channel);
                 return;
             }
@@ -166,6 +429,7 @@ impl SwarmContext {
             let resp;
             match obj {
                 parser::ObjectEnum::Signed(obj) => {
+                    signed_by_peer = obj.verify_peer(&peer) == SignatureStatus::Verified;
                     resp = self
                         .handle_request_signed_file(
                             obj,
@@ -179,6 +443,7 @@ impl SwarmContext {
                 parser::ObjectEnum::PlainFile(obj) => {
                     resp = self
                         .handle_request_plain_file(
+                            peer,
                             obj,
                             &id,
                             &request,
@@ -187,9 +452,36 @@ impl SwarmContext {
                         )
                         .await
                 }
+                parser::ObjectEnum::Tag(tag) => {
+                    self.handle_request_tag(
+                        peer,
+                        tag,
+                        signed_by_peer,
+                        &id,
+                        &request,
+                        response_channel,
+                    )
+                    .await;
+                    return;
+                }
+                // Stored as they are, the vault links the revisions of the version objects
+                parser::ObjectEnum::Version(_)
+                | parser::ObjectEnum::Chunk(_)
+                | parser::ObjectEnum::Manifest(_) => {
+                    self.store_and_provide(peer, &id, &request, response_channel)
+                        .await;
+                    return;
+                }
                 parser::ObjectEnum::Query(query) => {
                     resp = self
-                        .handle_request_query(query, &id, &request, &request_id, response_channel)
+                        .handle_request_query(
+                            peer,
+                            query,
+                            &id,
+                            &request,
+                            &request_id,
+                            response_channel,
+                        )
                         .await
                 }
                 _ => {
@@ -244,11 +536,19 @@ impl SwarmContext {
         &mut self,
         request: &ObjectSendRequest,
         response_channel: ResponseChannel<ObjectResponse>,
+    ) {
+        self.respond_err_code(request, response_channel, ResultErrorCode::Other);
+    }
+    fn respond_err_code(
+        &mut self,
+        request: &ObjectSendRequest,
+        response_channel: ResponseChannel<ObjectResponse>,
+        code: ResultErrorCode,
     ) {
         let _ = self.swarm.behaviour_mut().object_sender.send_response(
             response_channel,
             ObjectResponse {
-                object: proto::ResultObject { result: Err(()) }.into(),
+                object: proto::ResultObject { result: Err(code) }.into(),
                 object_id: request.object_id.clone(),
             },
         );
@@ -266,51 +566,223 @@ impl SwarmContext {
             },
         );
     }
+    fn respond_object(
+        &mut self,
+        request: &ObjectSendRequest,
+        response_channel: ResponseChannel<ObjectResponse>,
+        object: TypedObject,
+    ) {
+        self.stats.bytes_sent += object.data.len() as u64;
+        let _ = self.swarm.behaviour_mut().object_sender.send_response(
+            response_channel,
+            ObjectResponse {
+                object,
+                object_id: request.object_id.clone(),
+            },
+        );
+    }
     async fn handle_request_plain_file(
         &mut self,
+        peer: PeerId,
         _obj: PlainFileObject,
         id: &proto::Hash,
         request: &ObjectSendRequest,
         _request_id: &InboundRequestId,
         response_channel: ResponseChannel<ObjectResponse>,
     ) -> Option<(TypedObject, ResponseChannel<ObjectResponse>)> {
-        let r = self.put_object_into_vault(request.object.clone()).await;
-        if let Err(e) = r {
+        self.store_and_provide(peer, id, request, response_channel)
+            .await;
+        None
+    }
+
+    /// Indexes the tags of the object for the searches of the peers and stores the
+    /// tag object like any other. Only the tags signed by the sending peer are indexed
+    async fn handle_request_tag(
+        &mut self,
+        peer: PeerId,
+        tag: TagObject,
+        signed_by_peer: bool,
+        id: &proto::Hash,
+        request: &ObjectSendRequest,
+        response_channel: ResponseChannel<ObjectResponse>,
+    ) {
+        if !signed_by_peer {
+            debug!(
+                node = self.node_snapshot.name,
+                peer = peer.to_base58(),
+                "Rejected tags not signed by the peer"
+            );
+            self.respond_err_code(request, response_channel, ResultErrorCode::NotSigned);
+            return;
+        }
+        if !tag.is_valid() {
+            debug!(
+                node = self.node_snapshot.name,
+                peer = peer.to_base58(),
+                "Rejected invalid tags"
+            );
+            self.respond_err(request, response_channel);
+            return;
+        }
+        let stored = self
+            .vault_ref
+            .ask(vault::StoreTags {
+                hash: tag.object,
+                tags: tag.tags,
+            })
+            .await;
+        if let Err(e) = stored {
             error!(
                 node = self.node_snapshot.name,
-                err = format!("{e}"),
-                "Failed to put object into vault"
+                err = e.to_string(),
+                "Failed to index tags"
             );
             self.respond_err(request, response_channel);
-            return None;
+            return;
         }
 
-        let qid = self
-            .swarm
-            .behaviour_mut()
-            .kademlia
-            .start_providing(kad::RecordKey::from(id.bytes.to_vec()));
+        self.store_and_provide(peer, id, request, response_channel)
+            .await;
+    }
 
-        if let Err(e) = qid {
+    /// Forwards the object to the external module handling its type. The module is
+    /// called in its own task, the answer comes back to the swarm loop as a `ModuleAnswer`
+    fn call_module(
+        &mut self,
+        kind: ModuleCallKind,
+        peer: PeerId,
+        obj: TypedObject,
+        id: proto::Hash,
+        request: ObjectSendRequest,
+        response_channel: ResponseChannel<ObjectResponse>,
+    ) {
+        let module_host = self.module_host.clone();
+        let module_answers = self.module_answers.clone();
+        let node_name = self.node_snapshot.name.clone();
+        tokio::spawn(async move {
+            let result = module_host.call(kind, &node_name, obj).await;
+            let _ = module_answers.send(ModuleAnswer {
+                kind,
+                peer,
+                id,
+                request,
+                response_channel,
+                result,
+            });
+        });
+    }
+
+    /// Handles the answer of an external module. If the module accepts a stored object
+    /// without answering with its own, the object is stored
+    pub(crate) async fn handle_module_answer(&mut self, answer: ModuleAnswer) {
+        let ModuleAnswer {
+            kind,
+            peer,
+            id,
+            request,
+            response_channel,
+            result,
+        } = answer;
+
+        match (kind, result) {
+            (_, Ok(Some(object))) => self.respond_object(&request, response_channel, object),
+            (ModuleCallKind::Store, Ok(None)) => {
+                self.store_and_provide(peer, &id, &request, response_channel)
+                    .await
+            }
+            (_, Ok(None)) => {
+                self.respond_err_code(&request, response_channel, ResultErrorCode::NotFound)
+            }
+            (_, Err(e)) => {
+                debug!(
+                    node = self.node_snapshot.name,
+                    kind = format!("{kind:?}"),
+                    err = format!("{e}"),
+                    "External module failed to answer"
+                );
+                self.respond_err(&request, response_channel);
+            }
+        }
+    }
+
+    /// Stores the received object with its access policy in the vault and starts
+    /// providing it. The response is sent when providing starts
+    async fn store_and_provide(
+        &mut self,
+        peer: PeerId,
+        id: &proto::Hash,
+        request: &ObjectSendRequest,
+        response_channel: ResponseChannel<ObjectResponse>,
+    ) {
+        let r = self
+            .put_object_into_vault(request.object.clone(), ProvenanceKind::Stored, Some(peer))
+            .await;
+        let r = match r {
+            Ok(()) => {
+                self.store_sent_access_policy(peer, id, &request.policy)
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        if let Err(e) = r {
             error!(
                 node = self.node_snapshot.name,
                 err = format!("{e}"),
-                "Failed to start providing"
+                "Failed to put object into vault"
             );
+            self.audit(
+                &peer,
+                AuditRequestKind::Store,
+                id,
+                Some(ResultErrorCode::Other),
+            )
+            .await;
             self.respond_err(request, response_channel);
-            return None;
+            return;
         }
 
-        let qid = qid.expect("To not be err, as it was checked earlier");
-        self.behaviour
-            .pending_outer_start_providing
-            .insert(qid, (request.object_id.clone(), response_channel));
+        self.audit(&peer, AuditRequestKind::Store, id, None).await;
+        self.announce(
+            id.clone(),
+            ProvideWaiter::Outer(request.object_id.clone(), response_channel),
+        );
+    }
 
-        None
+    /// Stores the policy sent with the object. A policy already stored is kept, so
+    /// another peer sending the object again can't make it more public
+    async fn store_sent_access_policy(
+        &self,
+        peer: PeerId,
+        id: &proto::Hash,
+        policy: &AccessPolicy,
+    ) -> Result<()> {
+        let stored = self
+            .vault_ref
+            .ask(vault::LoadAccessPolicy { hash: id.clone() })
+            .await
+            .map_err(|e| anyhow!(e.to_string()))?;
+        if !matches!(stored, AccessPolicy::Public) {
+            debug!(
+                node = self.node_snapshot.name,
+                peer_id = peer.to_base58(),
+                obj_id = id.to_string(),
+                "Keeping the stored access policy of the object"
+            );
+            return Ok(());
+        }
+
+        self.vault_ref
+            .ask(vault::StoreAccessPolicy {
+                hash: id.clone(),
+                policy: policy.clone(),
+            })
+            .await
+            .map_err(|e| anyhow!(e.to_string()))
     }
 
     async fn handle_request_query(
         &mut self,
+        peer: PeerId,
         query: QueryObject,
         id: &proto::Hash,
         request: &ObjectSendRequest,
@@ -318,6 +790,17 @@ impl SwarmContext {
         response_channel: ResponseChannel<ObjectResponse>,
     ) -> Option<(TypedObject, ResponseChannel<ObjectResponse>)> {
         let typed = query.query_object;
+        if self.module_host.handles(&typed.uuid) {
+            self.call_module(
+                ModuleCallKind::Query,
+                peer,
+                typed,
+                id.clone(),
+                request.clone(),
+                response_channel,
+            );
+            return None;
+        }
         while let Some(obj) = Some(typed.clone()) {
             let obj = parser::parse_typed(obj).await;
             if let Err(e) = obj {
@@ -333,11 +816,24 @@ impl SwarmContext {
 
             return match query {
                 parser::ObjectEnum::SimpleIDQuery(query) => {
-                    self.handle_query_simple_id(query, &id, request, &request_id, response_channel)
-                        .await
+                    self.handle_query_simple_id(
+                        peer,
+                        query,
+                        &id,
+                        request,
+                        &request_id,
+                        response_channel,
+                    )
+                    .await
+                }
+                parser::ObjectEnum::TagQuery(query) => {
+                    self.handle_query_tag(query, request, response_channel)
+                        .await;
+                    None
                 }
                 parser::ObjectEnum::DeleteObject(delete_object) => {
                     self.handle_query_delete_object(
+                        peer,
                         delete_object,
                         &id,
                         request,
@@ -354,7 +850,10 @@ impl SwarmContext {
                     let _ = self.swarm.behaviour_mut().object_sender.send_response(
                         response_channel,
                         ObjectResponse {
-                            object: proto::ResultObject { result: Err(()) }.into(),
+                            object: proto::ResultObject {
+                                result: Err(ResultErrorCode::Other),
+                            }
+                            .into(),
                             object_id: request.object_id.clone(),
                         },
                     );
@@ -365,83 +864,174 @@ impl SwarmContext {
         None
     }
 
+    /// Deletes the object if the query is signed by the publisher of the object.
+    /// The object must be a SignedObject, the key that signed the query must be the
+    /// same as the one that signed the object
     async fn handle_query_delete_object(
         &mut self,
+        peer: PeerId,
         delete_object: DeleteObjectQuery,
         _request_full_object_id: &proto::Hash,
         request: &ObjectSendRequest,
         _request_id: &InboundRequestId,
         response_channel: ResponseChannel<ObjectResponse>,
     ) -> Option<(TypedObject, ResponseChannel<ObjectResponse>)> {
-        let obj = self.get_object_from_vault(delete_object.id.clone()).await;
-        if let None = obj {
+        if let Err(code) = self.verify_delete_object_query(&delete_object).await {
             debug!(
                 node = self.node_snapshot.name,
                 obj_id = delete_object.id.to_string(),
-                "Received Delete Object Query for file not in vault"
+                code = code.to_string(),
+                "Rejected Delete Object Query"
             );
+            self.audit(
+                &peer,
+                AuditRequestKind::Delete,
+                &delete_object.id,
+                Some(code),
+            )
+            .await;
+            self.respond_err_code(&request, response_channel, code);
+            return None;
+        }
+
+        self.behaviour.providing.remove(&delete_object.id);
+        self.swarm
+            .behaviour_mut()
+            .kademlia
+            .stop_providing(&delete_object.id.clone().into());
+        for waiter in self.announcer.forget(&delete_object.id) {
+            self.respond_provided(waiter, Err(anyhow!("Object deleted")));
+        }
+        let deleted = self
+            .vault_ref
+            .ask(vault::DeleteTypedObject {
+                hash: delete_object.id.clone(),
+            })
+            .await;
+
+        if let Err(e) = deleted {
+            error!(
+                node = self.node_snapshot.name,
+                obj_id = delete_object.id.to_string(),
+                err = e.to_string(),
+                "Failed to delete object from vault"
+            );
+            let code = ResultErrorCode::Other;
+            self.audit(
+                &peer,
+                AuditRequestKind::Delete,
+                &delete_object.id,
+                Some(code),
+            )
+            .await;
             self.respond_err(&request, response_channel);
             return None;
         }
 
-        let obj = parser::parse_typed(obj.unwrap()).await.unwrap();
-        if let ObjectEnum::Signed(signed) = obj {
-            let key = delete_object.verification_key_ed25519.try_into();
-            if let Err(e) = key {
-                debug!(
+        debug!(
+            node = self.node_snapshot.name,
+            obj_id = delete_object.id.to_string(),
+            "Object deleted on request of its publisher"
+        );
+        self.audit(&peer, AuditRequestKind::Delete, &delete_object.id, None)
+            .await;
+        self.respond_ok(&request, response_channel);
+        None
+    }
+
+    async fn verify_delete_object_query(
+        &mut self,
+        delete_object: &DeleteObjectQuery,
+    ) -> Result<(), ResultErrorCode> {
+        let request_public_key = delete_object.verify_ed25519()?;
+
+        let obj = self
+            .get_object_from_vault(delete_object.id.clone())
+            .await
+            .ok_or(ResultErrorCode::NotFound)?;
+
+        let signed = match parser::parse_typed(obj).await {
+            Ok(ObjectEnum::Signed(signed)) => signed,
+            _ => return Err(ResultErrorCode::NotSigned),
+        };
+
+        match signed.verify_ed25519(request_public_key) {
+            Ok(true) => Ok(()),
+            _ => Err(ResultErrorCode::NotOwner),
+        }
+    }
+
+    /// Answers with the objects indexed under the tag, at most `TagQuery::MAX_RESULTS`
+    async fn handle_query_tag(
+        &mut self,
+        query: TagQuery,
+        request: &ObjectSendRequest,
+        response_channel: ResponseChannel<ObjectResponse>,
+    ) {
+        let tagged = self
+            .vault_ref
+            .ask(vault::FindTagged {
+                tag_hash: query.tag_hash,
+            })
+            .await;
+
+        match tagged {
+            Ok(tagged) => {
+                let answer = QueryResultObject {
+                    ids: tagged
+                        .into_iter()
+                        .take(TagQuery::MAX_RESULTS)
+                        .map(|(id, _)| id)
+                        .collect(),
+                };
+                self.respond_object(request, response_channel, answer.into());
+            }
+            Err(e) => {
+                error!(
                     node = self.node_snapshot.name,
-                    err = format!("{e}"),
-                    "Query Delete object received invalid key"
+                    err = e.to_string(),
+                    "Failed to find tagged objects"
                 );
-                self.respond_err(&request, response_channel);
-                return None;
-            }
-            let request_public_key: PublicKey = key.unwrap();
-
-            let verified = signed.verify_ed25519(request_public_key);
-            if let Ok(verified) = verified {
-                if verified {
-                    self.swarm
-                        .behaviour_mut()
-                        .kademlia
-                        .stop_providing(&request.object_id.clone().into());
-                    self.vault_ref
-                        .ask(vault::DeleteTypedObject {
-                            hash: delete_object.id,
-                        })
-                        .await
-                        .ok();
-                    self.respond_ok(&request, response_channel);
-                    return None;
-                } else {
-                    self.respond_err(&request, response_channel);
-                    return None;
-                }
-            } else {
-                self.respond_err(&request, response_channel);
-                return None;
+                self.respond_err(request, response_channel);
             }
-        } else {
-            self.respond_err(&request, response_channel);
-            return None;
         }
     }
 
+    /// Responds with the object from the vault, if its access policy allows the
+    /// peer to get it
     async fn handle_query_simple_id(
         &mut self,
+        peer: PeerId,
         query: SimpleIDQuery,
         _request_full_object_id: &proto::Hash,
         request: &ObjectSendRequest,
-        _request_id: &InboundRequestId,
+        request_id: &InboundRequestId,
         response_channel: ResponseChannel<ObjectResponse>,
     ) -> Option<(TypedObject, ResponseChannel<ObjectResponse>)> {
+        if let Err(code) = self.check_access(&query.id, &peer, request).await {
+            debug!(
+                node = self.node_snapshot.name,
+                peer = peer.to_base58(),
+                obj_id = query.id.to_string(),
+                code = code.to_string(),
+                "Denied access to object"
+            );
+            self.audit(&peer, AuditRequestKind::Get, &query.id, Some(code))
+                .await;
+            self.respond_err_code(&request, response_channel, code);
+            return None;
+        }
+
         let obj = self.get_object_from_vault(query.id.clone()).await;
         if let None = obj {
             error!(
                 node = self.node_snapshot.name,
                 "Failed to get asked object from vault"
             );
-            self.respond_err(&request, response_channel);
+            let code = ResultErrorCode::NotFound;
+            self.audit(&peer, AuditRequestKind::Get, &query.id, Some(code))
+                .await;
+            self.respond_err_code(&request, response_channel, code);
             return None;
         }
         let obj = obj.expect("To not be err, as it was checked earlier");
@@ -458,6 +1048,25 @@ impl SwarmContext {
         }
         let calculated_obj_id = calculated_obj_id.expect("Not to be err as it was checked earlier");
 
+        let permit = match self.upload_quotas.acquire(&peer, obj.data.len() as u64) {
+            Ok(permit) => permit,
+            Err(code) => {
+                debug!(
+                    node = self.node_snapshot.name,
+                    peer = peer.to_base58(),
+                    obj_id = query.id.to_string(),
+                    code = code.to_string(),
+                    "Rejected request over the upload limits"
+                );
+                self.audit(&peer, AuditRequestKind::Get, &query.id, Some(code))
+                    .await;
+                self.respond_err_code(&request, response_channel, code);
+                return None;
+            }
+        };
+        self.behaviour.uploads.insert(*request_id, permit);
+        self.stats.bytes_sent += obj.data.len() as u64;
+
         if query.id != calculated_obj_id {
             error!(
                 received_obj_id = bs58::encode(&query.id.bytes).into_string(),
@@ -467,6 +1076,9 @@ impl SwarmContext {
             )
         }
 
+        self.audit(&peer, AuditRequestKind::Get, &query.id, None)
+            .await;
+        self.record_served(&query.id).await;
         let _ = self.swarm.behaviour_mut().object_sender.send_response(
             response_channel,
             ObjectResponse {
@@ -478,6 +1090,62 @@ impl SwarmContext {
         None
     }
 
+    /// Appends the inbound request to the audit log in the vault, with the reason
+    /// of the rejection if the request was not served
+    pub(crate) async fn audit(
+        &mut self,
+        peer: &PeerId,
+        kind: AuditRequestKind,
+        obj_id: &proto::Hash,
+        rejection: Option<ResultErrorCode>,
+    ) {
+        let entry = AuditEntry {
+            peer_id: peer.to_base58(),
+            object_id: obj_id.to_string(),
+            kind,
+            rejection: rejection.map(|code| code.to_string()),
+            at: SystemTime::now(),
+        };
+
+        if let Err(e) = self.vault_ref.ask(vault::AppendAuditEntry { entry }).await {
+            warn!(
+                node = self.node_snapshot.name,
+                err = e.to_string(),
+                "Failed to append to the audit log"
+            );
+        }
+    }
+
+    /// Counts the object as fetched for its popularity and in the stats of the node
+    pub(crate) async fn record_served(&mut self, obj_id: &proto::Hash) {
+        self.stats.objects_served += 1;
+        let hash = obj_id.clone();
+        if let Err(e) = self.vault_ref.ask(vault::RecordObjectServed { hash }).await {
+            warn!(
+                node = self.node_snapshot.name,
+                err = e.to_string(),
+                "Failed to record served object"
+            );
+        }
+    }
+
+    async fn check_access(
+        &mut self,
+        obj_id: &proto::Hash,
+        peer: &PeerId,
+        request: &ObjectSendRequest,
+    ) -> Result<(), ResultErrorCode> {
+        let policy = self
+            .vault_ref
+            .ask(vault::LoadAccessPolicy {
+                hash: obj_id.clone(),
+            })
+            .await
+            .map_err(|_| ResultErrorCode::Other)?;
+
+        policy.check(peer, request.access_token.as_ref())
+    }
+
     async fn send_result_object(
         &mut self,
         object: TypedObject,
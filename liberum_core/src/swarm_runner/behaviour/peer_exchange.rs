@@ -0,0 +1,258 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Result};
+use liberum_core::proto::ResultErrorCode;
+use libp2p::multiaddr::Protocol;
+use libp2p::request_response::{self, ResponseChannel};
+use libp2p::{Multiaddr, PeerId};
+use rand::seq::{IteratorRandom, SliceRandom};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::swarm_runner::SwarmContext;
+
+///! The module contains the request_response behaviour of the peer exchange. Every
+///! exchange asks a few random connected peers for a sample of the peers they are
+///! connected to, so the routing table fills up faster than with the Kademlia queries
+///! alone, e.g. when the bootstrap nodes are few or overloaded.
+///!
+///! Only the healthy peers are shared: connected, answering the pings and not banned.
+///! The received peers are checked against the blocklists and the reputation, and
+///! their addresses against the address policy, before they are added to the routing
+///! table. A peer asking more often than the config allows is rejected.
+
+/// How many connected peers are asked in every exchange
+const EXCHANGE_FANOUT: usize = 3;
+/// The most addresses of a peer taken from a response
+const MAX_ADDRESSES_PER_PEER: usize = 8;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PeerExchangeRequest {
+    /// The most peers the requesting peer takes
+    pub max_peers: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub enum PeerExchangeResponse {
+    Peers(Vec<ExchangedPeer>),
+    Rejected(ResultErrorCode),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ExchangedPeer {
+    pub peer_id: PeerId,
+    pub addresses: Vec<Multiaddr>,
+}
+
+/// The addresses of the peer which may be added to the routing table. The ones
+/// with the ID of another peer are dropped, they would put that peer at the
+/// address of this one
+fn exchanged_addresses(peer: ExchangedPeer) -> Vec<Multiaddr> {
+    peer.addresses
+        .into_iter()
+        .filter(|addr| {
+            addr.iter()
+                .all(|protocol| !matches!(protocol, Protocol::P2p(id) if id != peer.peer_id))
+        })
+        .take(MAX_ADDRESSES_PER_PEER)
+        .collect()
+}
+
+/// Methods on SwarmContext for the peer exchange behaviour
+impl SwarmContext {
+    pub(crate) fn handle_peer_exchange(
+        &mut self,
+        event: request_response::Event<PeerExchangeRequest, PeerExchangeResponse>,
+    ) {
+        match event {
+            request_response::Event::Message { message, peer } => match message {
+                request_response::Message::Request {
+                    request, channel, ..
+                } => self.handle_peer_exchange_request(peer, request, channel),
+                request_response::Message::Response { response, .. } => {
+                    self.handle_peer_exchange_response(peer, response)
+                }
+            },
+            request_response::Event::OutboundFailure { peer, error, .. } => debug!(
+                node = self.node_snapshot.name,
+                peer = peer.to_base58(),
+                err = error.to_string(),
+                "Peer exchange request failed"
+            ),
+            e => debug!(
+                node = self.node_snapshot.name,
+                "Received peer exchange event! {e:?}"
+            ),
+        }
+    }
+
+    /// Asks a few random connected peers for their peers. Returns how many were asked
+    pub(crate) fn exchange_peers(&mut self) -> Result<usize> {
+        let config = self.node_snapshot.config.peer_exchange;
+        if !config.enabled {
+            bail!("The peer exchange is disabled");
+        }
+        let peers: Vec<PeerId> = self
+            .swarm
+            .connected_peers()
+            .filter(|peer| !self.reputation.is_banned(peer))
+            .copied()
+            .choose_multiple(&mut rand::thread_rng(), EXCHANGE_FANOUT);
+
+        for peer in &peers {
+            self.swarm.behaviour_mut().peer_exchange.send_request(
+                peer,
+                PeerExchangeRequest {
+                    max_peers: config.max_peers,
+                },
+            );
+        }
+        Ok(peers.len())
+    }
+
+    fn handle_peer_exchange_request(
+        &mut self,
+        peer: PeerId,
+        request: PeerExchangeRequest,
+        channel: ResponseChannel<PeerExchangeResponse>,
+    ) {
+        let config = self.node_snapshot.config.peer_exchange;
+        let min_interval = Duration::from_secs(config.min_request_interval_secs);
+        let served = &mut self.behaviour.peer_exchange_served;
+        served.retain(|_, at| at.elapsed() < min_interval);
+
+        let response = match served.get(&peer) {
+            _ if !config.enabled => PeerExchangeResponse::Rejected(ResultErrorCode::Other),
+            Some(at) => PeerExchangeResponse::Rejected(ResultErrorCode::QuotaExceeded {
+                retry_after_secs: min_interval.saturating_sub(at.elapsed()).as_secs() + 1,
+            }),
+            None => {
+                served.insert(peer, Instant::now());
+                let max_peers = request.max_peers.min(config.max_peers);
+                PeerExchangeResponse::Peers(self.sample_peers(&peer, max_peers))
+            }
+        };
+        if self
+            .swarm
+            .behaviour_mut()
+            .peer_exchange
+            .send_response(channel, response)
+            .is_err()
+        {
+            debug!(
+                node = self.node_snapshot.name,
+                peer = peer.to_base58(),
+                "Could not respond to peer exchange request"
+            );
+        }
+    }
+
+    /// Random healthy connected peers with their addresses from the routing table,
+    /// without the requesting peer
+    fn sample_peers(&mut self, requester: &PeerId, max_peers: usize) -> Vec<ExchangedPeer> {
+        let mut addresses: HashMap<PeerId, Vec<Multiaddr>> = HashMap::new();
+        for bucket in self.swarm.behaviour_mut().kademlia.kbuckets() {
+            for entry in bucket.iter() {
+                addresses.insert(
+                    *entry.node.key.preimage(),
+                    entry.node.value.iter().cloned().collect(),
+                );
+            }
+        }
+
+        let mut peers: Vec<ExchangedPeer> = self
+            .swarm
+            .connected_peers()
+            .filter(|peer| {
+                *peer != requester
+                    && !self.reputation.is_banned(peer)
+                    && self.latencies.get(peer).is_some()
+            })
+            .filter_map(|peer| {
+                let addresses: Vec<Multiaddr> = addresses
+                    .remove(peer)?
+                    .into_iter()
+                    .filter(|addr| self.is_announceable(addr))
+                    .collect();
+                (!addresses.is_empty()).then(|| ExchangedPeer {
+                    peer_id: *peer,
+                    addresses,
+                })
+            })
+            .collect();
+        peers.shuffle(&mut rand::thread_rng());
+        peers.truncate(max_peers);
+        peers
+    }
+
+    fn handle_peer_exchange_response(&mut self, from: PeerId, response: PeerExchangeResponse) {
+        let peers = match response {
+            PeerExchangeResponse::Peers(peers) => peers,
+            PeerExchangeResponse::Rejected(code) => {
+                debug!(
+                    node = self.node_snapshot.name,
+                    peer = from.to_base58(),
+                    code = code.to_string(),
+                    "Peer exchange request rejected"
+                );
+                return;
+            }
+        };
+
+        let local_peer_id = *self.swarm.local_peer_id();
+        let mut added = 0;
+        for peer in peers
+            .into_iter()
+            .take(self.node_snapshot.config.peer_exchange.max_peers)
+        {
+            let peer_id = peer.peer_id;
+            if peer_id == local_peer_id
+                || peer_id == from
+                || self.reputation.is_banned(&peer_id)
+                || !self.is_peer_allowed(&peer_id)
+            {
+                continue;
+            }
+            for addr in exchanged_addresses(peer) {
+                self.add_peer_address(&peer_id, addr);
+            }
+            added += 1;
+        }
+        debug!(
+            node = self.node_snapshot.name,
+            peer = from.to_base58(),
+            "Received {added} peers in the peer exchange"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exchanged_addresses_test() {
+        let peer_id = PeerId::random();
+        let other = PeerId::random();
+        let addr: Multiaddr = "/ip4/8.8.8.8/udp/1/quic-v1".parse().unwrap();
+        let peer = ExchangedPeer {
+            peer_id,
+            addresses: vec![
+                addr.clone(),
+                addr.clone().with(Protocol::P2p(other)),
+                addr.clone().with(Protocol::P2p(peer_id)),
+            ],
+        };
+        assert_eq!(
+            exchanged_addresses(peer),
+            vec![addr.clone(), addr.clone().with(Protocol::P2p(peer_id))]
+        );
+
+        let many = ExchangedPeer {
+            peer_id,
+            addresses: vec![addr; 20],
+        };
+        assert_eq!(exchanged_addresses(many).len(), MAX_ADDRESSES_PER_PEER);
+    }
+}
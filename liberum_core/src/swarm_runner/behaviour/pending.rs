@@ -0,0 +1,283 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+use liberum_core::proto::{self, RotationObject};
+use libp2p::request_response::OutboundRequestId;
+use libp2p::{kad, PeerId};
+use thiserror::Error;
+use tokio::sync::{mpsc, oneshot};
+use tracing::debug;
+
+use super::super::SwarmContext;
+use crate::swarm_runner::announcer::ProvideWaiter;
+
+///! The module contains the maps of the requests waiting for a response from the
+///! network. Every entry has a deadline, the entries which were not answered in
+///! time are failed with a timeout, so the callers never wait forever. Most of the
+///! callers wait for a result, their maps are `PendingRequests`, which answer and
+///! time out the requests by themselves. Every pending request knows how it is
+///! answered when it times out (`Expire`) and every map what the swarm does with
+///! the keys of the expired requests, so the sweep treats all the maps the same.
+
+/// How long a request may wait for a response. Kademlia queries and object sender
+/// requests have their own timeouts, this one is a safety net for the responses that
+/// never come
+pub const PENDING_TIMEOUT: Duration = Duration::from_secs(90);
+/// How often the pending requests are checked for the timeout
+pub const PENDING_SWEEP_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Error, Debug)]
+#[error("Request timed out waiting for the network")]
+pub struct TimeoutError;
+
+/// What is left to do in the swarm for a request which timed out
+pub enum Expired {
+    /// The Kademlia query is stopped
+    Query(kad::QueryId),
+    /// The object sender request stops counting as a transfer to its peer
+    Transfer(OutboundRequestId),
+    /// The waiters of a provider announcement, answered by the swarm
+    ProvideWaiters(Vec<ProvideWaiter>),
+}
+
+/// A pending request, answered by itself when it times out
+pub trait Expire {
+    /// Answers the request with a timeout, or with what was found so far. Returns
+    /// what the swarm still has to do
+    fn expire(self) -> Option<Expired>;
+}
+
+/// A map of pending requests with a deadline for every entry
+pub struct PendingMap<K, V> {
+    entries: HashMap<K, (V, Instant)>,
+    timeout: Duration,
+    /// What the swarm does with the keys of the requests which timed out
+    on_expiry: Option<fn(K) -> Expired>,
+}
+
+impl<K: Hash + Eq + Copy, V> PendingMap<K, V> {
+    pub fn new(timeout: Duration) -> Self {
+        PendingMap {
+            entries: HashMap::new(),
+            timeout,
+            on_expiry: None,
+        }
+    }
+
+    /// Sets what the swarm does with the keys of the requests which timed out, e.g.
+    /// stops their queries
+    pub fn on_expiry(mut self, on_expiry: fn(K) -> Expired) -> Self {
+        self.on_expiry = Some(on_expiry);
+        self
+    }
+
+    /// Inserts the entry with a new deadline
+    pub fn insert(&mut self, key: K, value: V) {
+        self.entries
+            .insert(key, (value, Instant::now() + self.timeout));
+    }
+
+    /// The entry, keeping its deadline
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        self.entries.get_mut(key).map(|(value, _)| value)
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        self.entries.remove(key).map(|(value, _)| value)
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Removes and returns the entries which deadline passed
+    pub fn remove_expired(&mut self, now: Instant) -> Vec<(K, V)> {
+        let expired = self
+            .entries
+            .iter()
+            .filter(|(_, (_, deadline))| *deadline <= now)
+            .map(|(key, _)| *key)
+            .collect::<Vec<K>>();
+
+        expired
+            .into_iter()
+            .filter_map(|key| Some((key, self.remove(&key)?)))
+            .collect()
+    }
+}
+
+/// The requests of the node waiting for the network, answered with results of the type
+pub type PendingRequests<K, T> = PendingMap<K, oneshot::Sender<Result<T>>>;
+
+impl<K: Hash + Eq + Copy, T> PendingMap<K, oneshot::Sender<Result<T>>> {
+    /// Answers the request, false if it's not pending
+    pub fn respond(&mut self, key: &K, result: Result<T>) -> bool {
+        match self.remove(key) {
+            Some(sender) => {
+                let _ = sender.send(result);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// A map of the pending requests swept for the timeouts, whatever its types are
+pub trait Sweep {
+    /// Expires the requests whose deadlines passed. Returns what the swarm still
+    /// has to do
+    fn sweep(&mut self, now: Instant) -> Vec<Expired>;
+
+    fn pending(&self) -> usize;
+}
+
+impl<K: Hash + Eq + Copy, V: Expire> Sweep for PendingMap<K, V> {
+    fn sweep(&mut self, now: Instant) -> Vec<Expired> {
+        let on_expiry = self.on_expiry;
+        self.remove_expired(now)
+            .into_iter()
+            .flat_map(|(key, value)| [value.expire(), on_expiry.map(|f| f(key))])
+            .flatten()
+            .collect()
+    }
+
+    fn pending(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<T> Expire for oneshot::Sender<Result<T>> {
+    fn expire(self) -> Option<Expired> {
+        let _ = self.send(Err(anyhow!(TimeoutError)));
+        None
+    }
+}
+
+/// The results sent so far were already received, dropping the sender ends the stream
+impl<T> Expire for mpsc::Sender<T> {
+    fn expire(self) -> Option<Expired> {
+        None
+    }
+}
+
+/// The closest peers found so far are the answer
+impl Expire for (Vec<PeerId>, oneshot::Sender<Vec<PeerId>>) {
+    fn expire(self) -> Option<Expired> {
+        let _ = self.1.send(self.0);
+        None
+    }
+}
+
+/// The newest rotation found so far is the answer, if there is any
+impl Expire
+    for (
+        Option<RotationObject>,
+        oneshot::Sender<Result<Option<RotationObject>>>,
+    )
+{
+    fn expire(self) -> Option<Expired> {
+        let _ = self.1.send(self.0.map(Some).ok_or(anyhow!(TimeoutError)));
+        None
+    }
+}
+
+impl Expire for (proto::Hash, oneshot::Sender<Result<proto::Hash>>) {
+    fn expire(self) -> Option<Expired> {
+        self.1.expire()
+    }
+}
+
+impl Expire for Vec<ProvideWaiter> {
+    fn expire(self) -> Option<Expired> {
+        Some(Expired::ProvideWaiters(self))
+    }
+}
+
+/// Methods on SwarmContext for failing the requests that timed out
+impl SwarmContext {
+    pub(crate) fn fail_timed_out_requests(&mut self) {
+        let now = Instant::now();
+        let expired: Vec<Expired> = self
+            .behaviour
+            .pending_maps()
+            .into_iter()
+            .flat_map(|map| map.sweep(now))
+            .collect();
+
+        for expired in expired {
+            match expired {
+                Expired::Query(query_id) => {
+                    debug!(
+                        node = self.node_snapshot.name,
+                        qid = format!("{query_id}"),
+                        "Query timed out"
+                    );
+                    self.finish_query(&query_id);
+                }
+                Expired::Transfer(request_id) => self.connections.end_transfer(&request_id),
+                Expired::ProvideWaiters(waiters) => {
+                    for waiter in waiters {
+                        self.respond_provided(waiter, Err(anyhow!(TimeoutError)));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Stops the Kademlia query, if it still runs
+    pub(crate) fn finish_query(&mut self, query_id: &kad::QueryId) {
+        if let Some(mut query) = self.swarm.behaviour_mut().kademlia.query_mut(query_id) {
+            query.finish();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remove_expired_test() {
+        let mut map = PendingMap::new(Duration::from_secs(10));
+        map.insert(1, "a");
+        map.insert(2, "b");
+        assert!(map.remove_expired(Instant::now()).is_empty());
+
+        assert_eq!(map.remove(&2), Some("b"));
+        let expired = map.remove_expired(Instant::now() + Duration::from_secs(11));
+        assert_eq!(expired, vec![(1, "a")]);
+        assert_eq!(map.len(), 0);
+    }
+
+    #[tokio::test]
+    async fn pending_requests_test() {
+        let mut requests: PendingRequests<u32, &str> =
+            PendingRequests::new(Duration::from_secs(10));
+        let (answered, answer) = oneshot::channel();
+        let (expiring, timeout) = oneshot::channel();
+        requests.insert(1, answered);
+        requests.insert(2, expiring);
+
+        assert!(requests.respond(&1, Ok("a")));
+        assert!(!requests.respond(&1, Ok("b")));
+        assert_eq!(answer.await.unwrap().unwrap(), "a");
+
+        let expired = requests.sweep(Instant::now() + Duration::from_secs(11));
+        assert!(expired.is_empty());
+        assert_eq!(requests.pending(), 0);
+        assert!(timeout.await.unwrap().unwrap_err().is::<TimeoutError>());
+
+        // What's left to do with the expired keys is handed to the swarm
+        let mut queries: PendingRequests<u32, &str> = PendingRequests::new(Duration::from_secs(10))
+            .on_expiry(|_| Expired::ProvideWaiters(vec![]));
+        queries.insert(1, oneshot::channel().0);
+        let expired = queries.sweep(Instant::now() + Duration::from_secs(11));
+        assert!(matches!(expired[..], [Expired::ProvideWaiters(_)]));
+    }
+}
@@ -0,0 +1,87 @@
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+
+use libp2p::{ping, PeerId};
+use tracing::debug;
+
+use super::super::SwarmContext;
+
+///! The module contains the handler of the ping behaviour. The connected peers are
+///! pinged periodically and the round trip times are used to tell how healthy the
+///! connection to a peer is and to prefer the fastest providers when downloading.
+
+/// Number of the latest round trip times averaged to get the latency of a peer
+const RTT_WINDOW: usize = 5;
+
+/// Rolling round trip times of the connected peers
+pub struct PeerLatencies {
+    samples: HashMap<PeerId, VecDeque<Duration>>,
+}
+
+impl PeerLatencies {
+    pub fn new() -> Self {
+        PeerLatencies {
+            samples: HashMap::new(),
+        }
+    }
+
+    pub fn record(&mut self, peer_id: PeerId, rtt: Duration) {
+        let samples = self.samples.entry(peer_id).or_default();
+        if samples.len() >= RTT_WINDOW {
+            samples.pop_front();
+        }
+        samples.push_back(rtt);
+    }
+
+    pub fn remove(&mut self, peer_id: &PeerId) {
+        self.samples.remove(peer_id);
+    }
+
+    /// Average of the latest round trip times of the peer
+    pub fn get(&self, peer_id: &PeerId) -> Option<Duration> {
+        let samples = self.samples.get(peer_id)?;
+        let sum: Duration = samples.iter().sum();
+        Some(sum / samples.len() as u32)
+    }
+
+    pub fn all(&self) -> HashMap<PeerId, Duration> {
+        self.samples
+            .keys()
+            .filter_map(|peer_id| Some((*peer_id, self.get(peer_id)?)))
+            .collect()
+    }
+}
+
+impl SwarmContext {
+    pub(crate) fn handle_ping(&mut self, event: ping::Event) {
+        match event.result {
+            Ok(rtt) => self.latencies.record(event.peer, rtt),
+            Err(e) => debug!(
+                node = self.node_snapshot.name,
+                peer_id = event.peer.to_base58(),
+                err = e.to_string(),
+                "Ping failed"
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rolling_latency_test() {
+        let peer = PeerId::random();
+        let mut latencies = PeerLatencies::new();
+        assert_eq!(latencies.get(&peer), None);
+
+        for ms in [100, 10, 10, 10, 10, 10] {
+            latencies.record(peer, Duration::from_millis(ms));
+        }
+        assert_eq!(latencies.get(&peer), Some(Duration::from_millis(10)));
+
+        latencies.remove(&peer);
+        assert!(latencies.all().is_empty());
+    }
+}
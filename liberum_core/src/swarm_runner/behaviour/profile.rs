@@ -0,0 +1,129 @@
+use anyhow::{anyhow, Result};
+use liberum_core::node_config::NodeProfile;
+use liberum_core::proto::{self, ProfileObject};
+use libp2p::kad::{self, store::RecordStore, GetRecordError, GetRecordOk, QueryId, Record};
+use libp2p::PeerId;
+use tokio::sync::oneshot;
+use tracing::{debug, warn};
+
+use crate::swarm_runner::SwarmContext;
+
+///! The module publishes the profile of the node and resolves the profiles of other
+///! peers. Profiles are Kademlia records under the key derived from the peer ID, so
+///! they can be found without knowing any object ID. Only the records with a valid
+///! profile signed by the peer are stored or returned.
+
+/// Methods on SwarmContext for the profiles
+impl SwarmContext {
+    /// Signs the profile with the key of the node and puts it in the DHT. The record
+    /// is stored locally at once and republished by Kademlia periodically
+    pub(crate) fn publish_profile(&mut self, profile: &NodeProfile) -> Result<()> {
+        let avatar = profile
+            .avatar
+            .as_deref()
+            .map(proto::Hash::try_from)
+            .transpose()?;
+        let keypair = self.node_snapshot.keypair.clone();
+        let object = ProfileObject {
+            display_name: profile.display_name.clone(),
+            description: profile.description.clone(),
+            avatar,
+            public_key: keypair.public().into(),
+            updated_at: proto::unix_now(),
+        };
+
+        let key = ProfileObject::record_key(&keypair.public().to_peer_id());
+        let record = Record::new(key, object.to_record_value(keypair)?);
+        self.swarm
+            .behaviour_mut()
+            .kademlia
+            .put_record(record, kad::Quorum::One)?;
+        debug!(node = self.node_snapshot.name, "Publishing profile");
+
+        Ok(())
+    }
+
+    /// Finds the profile of the peer, in the local store first. Responds with None
+    /// if no peer has a valid profile
+    pub(crate) fn get_profile(
+        &mut self,
+        peer_id: PeerId,
+        response_sender: oneshot::Sender<Result<Option<ProfileObject>>>,
+    ) {
+        let key = ProfileObject::record_key(&peer_id);
+        let kademlia = &mut self.swarm.behaviour_mut().kademlia;
+        if let Some(record) = kademlia.store_mut().get(&key) {
+            if let Ok(profile) = ProfileObject::from_record(&key, &record.value) {
+                let _ = response_sender.send(Ok(Some(profile)));
+                return;
+            }
+        }
+
+        let query_id = kademlia.get_record(key);
+        self.behaviour
+            .pending_inner_get_profile
+            .insert(query_id, response_sender);
+    }
+
+    pub(crate) fn handle_outbound_query_progressed_get_record(
+        &mut self,
+        id: QueryId,
+        result: Result<GetRecordOk, GetRecordError>,
+    ) {
+        if !self.behaviour.pending_inner_get_profile.contains_key(&id) {
+            return;
+        }
+
+        let response = match result {
+            Ok(GetRecordOk::FoundRecord(peer_record)) => {
+                let record = peer_record.record;
+                match ProfileObject::from_record(&record.key, &record.value) {
+                    Ok(profile) => Ok(Some(profile)),
+                    Err(e) => {
+                        // Another peer may still have a valid one
+                        debug!(
+                            node = self.node_snapshot.name,
+                            err = e.to_string(),
+                            "Received invalid profile"
+                        );
+                        return;
+                    }
+                }
+            }
+            Ok(GetRecordOk::FinishedWithNoAdditionalRecord { .. }) => Ok(None),
+            Err(GetRecordError::NotFound { .. }) => Ok(None),
+            Err(e) => Err(anyhow!(e)),
+        };
+
+        if let Some(mut query) = self.swarm.behaviour_mut().kademlia.query_mut(&id) {
+            query.finish();
+        }
+        self.behaviour
+            .pending_inner_get_profile
+            .respond(&id, response);
+    }
+
+    /// Stores the record put by another peer if it is a valid profile, key rotation or
+    /// small object. Other records are not used by the network and are dropped
+    pub(crate) fn handle_inbound_request_put_record(
+        &mut self,
+        source: PeerId,
+        record: Option<Record>,
+    ) {
+        let Some(record) = record else {
+            return;
+        };
+        if !self.accept_record(source, &record) {
+            return;
+        }
+
+        let store = self.swarm.behaviour_mut().kademlia.store_mut();
+        if let Err(e) = store.put(record) {
+            warn!(
+                node = self.node_snapshot.name,
+                err = e.to_string(),
+                "Failed to store record of another peer"
+            );
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use anyhow::{anyhow, Result};
+use liberum_core::proto::RotationObject;
+use libp2p::kad::{
+    self, store::RecordStore, GetRecordError, GetRecordOk, ProgressStep, QueryId, Record,
+};
+use libp2p::PeerId;
+use tokio::sync::oneshot;
+use tracing::{debug, warn};
+
+use crate::swarm_runner::SwarmContext;
+
+///! The module publishes the rotations of the keypair of the node and resolves the
+///! rotations of other peers. Like profiles, rotations are Kademlia records, stored
+///! under the key derived from the old peer ID, so a peer knowing only the old ID can
+///! find the new one. A rotation is signed by both keys, it needs no other signature.
+///! Other peers may keep older rotations of the same key, so all the records found
+///! by the query are checked and the newest valid one is the answer.
+
+/// Methods on SwarmContext for the key rotations
+impl SwarmContext {
+    /// Puts the rotations from the config of the node in the DHT
+    pub(crate) fn publish_rotations(&mut self) {
+        for rotation in self.node_snapshot.config.key_rotations.clone() {
+            if let Err(e) = self.publish_rotation(&rotation) {
+                warn!(err = e.to_string(), "Could not publish key rotation");
+            }
+        }
+    }
+
+    fn publish_rotation(&mut self, rotation: &RotationObject) -> Result<()> {
+        let (old_key, _) = rotation.verify()?;
+        let key = RotationObject::record_key(&old_key.to_peer_id());
+        let record = Record::new(key, rotation.to_record_value()?);
+        self.swarm
+            .behaviour_mut()
+            .kademlia
+            .put_record(record, kad::Quorum::One)?;
+
+        Ok(())
+    }
+
+    /// Finds the rotation of the key of the peer, in the local store first. Responds
+    /// with None if the key was not rotated
+    pub(crate) fn get_rotation(
+        &mut self,
+        peer_id: PeerId,
+        response_sender: oneshot::Sender<Result<Option<RotationObject>>>,
+    ) {
+        let key = RotationObject::record_key(&peer_id);
+        let kademlia = &mut self.swarm.behaviour_mut().kademlia;
+        if let Some(record) = kademlia.store_mut().get(&key) {
+            if let Ok(rotation) = RotationObject::from_record(&key, &record.value) {
+                let _ = response_sender.send(Ok(Some(rotation)));
+                return;
+            }
+        }
+
+        let query_id = kademlia.get_record(key);
+        self.behaviour
+            .pending_inner_get_rotation
+            .insert(query_id, (None, response_sender));
+    }
+
+    pub(crate) fn handle_outbound_query_progressed_get_rotation(
+        &mut self,
+        id: QueryId,
+        result: Result<GetRecordOk, GetRecordError>,
+        step: ProgressStep,
+    ) {
+        let Some((found, _)) = self.behaviour.pending_inner_get_rotation.get_mut(&id) else {
+            return;
+        };
+        let error = match result {
+            Ok(GetRecordOk::FoundRecord(peer_record)) => {
+                let record = peer_record.record;
+                match RotationObject::from_record(&record.key, &record.value) {
+                    Ok(rotation) => {
+                        let is_newer = found
+                            .as_ref()
+                            .map_or(true, |newest| rotation.rotated_at > newest.rotated_at);
+                        if is_newer {
+                            *found = Some(rotation);
+                        }
+                    }
+                    // Another peer may still have a valid one
+                    Err(e) => debug!(
+                        node = self.node_snapshot.name,
+                        err = e.to_string(),
+                        "Received invalid key rotation"
+                    ),
+                }
+                None
+            }
+            Ok(GetRecordOk::FinishedWithNoAdditionalRecord { .. }) => None,
+            Err(GetRecordError::NotFound { .. }) => None,
+            Err(e) => Some(anyhow!(e)),
+        };
+        if !step.last && error.is_none() {
+            return;
+        }
+
+        let Some((found, sender)) = self.behaviour.pending_inner_get_rotation.remove(&id) else {
+            return;
+        };
+        if let Some(mut query) = self.swarm.behaviour_mut().kademlia.query_mut(&id) {
+            query.finish();
+        }
+        let response = match (found, error) {
+            (Some(rotation), _) => Ok(Some(rotation)),
+            (None, Some(e)) => Err(e),
+            (None, None) => Ok(None),
+        };
+        let _ = sender.send(response);
+    }
+}
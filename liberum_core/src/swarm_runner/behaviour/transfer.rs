@@ -0,0 +1,813 @@
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+use futures::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, StreamExt};
+use kameo::actor::ActorRef;
+use kameo::request::MessageSend;
+use liberum_core::canonical;
+use liberum_core::compression::{Compressed, Compression};
+use liberum_core::node_config::CompressionConfig;
+use liberum_core::parser::ObjectEnum;
+use liberum_core::proto::{self, GroupAccessToken, ResultErrorCode, ResultObject, TypedObject};
+use liberum_core::types::AuditRequestKind;
+use libp2p::{PeerId, StreamProtocol};
+use libp2p_stream::{Control, IncomingStreams};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc, oneshot, Semaphore};
+use tokio::task::JoinHandle;
+use tracing::debug;
+use uuid::Uuid;
+
+use crate::swarm_runner::reputation::Misbehaviour;
+use crate::swarm_runner::upload_quota::UploadQuotas;
+use crate::swarm_runner::SwarmContext;
+use crate::vault::{self, PartialObject, Vault};
+
+///! The module contains the transfer protocol, which downloads the objects in
+///! chunks over a stream, so unlike with the object sender the size of an object is
+///! not limited by the size of a single message.
+///!
+///! The downloading peer sends the request, the providing peer answers with the
+///! type and the size of the object, or with the error code, and then sends the
+///! payload in length-prefixed chunks. The downloading peer grants the chunks in
+///! batches, the provider never sends more chunks than granted, so a slow peer is
+///! not flooded. The object is hashed while it's received and a transfer which
+///! doesn't match the requested ID is rejected before anything is stored. The
+///! verified object is returned to the caller, which stores it if it keeps it.
+///!
+///! The header also carries the hashes of all the chunks, so every chunk is
+///! verified as soon as it arrives and kept in the vault with the partial object.
+///! When the transfer is interrupted, the next request for the object asks for the
+///! payload from the offset of the first chunk not received yet, and the transfer
+///! resumes there. The partial object is forgotten when the provider announces
+///! different chunks or when the whole object doesn't match its ID.
+///!
+///! The chunks are compressed with zstd if both peers have the compression enabled.
+///! The downloading peer offers the compression in the request and the provider
+///! confirms it in the header. Every chunk is tagged with its compression, as the
+///! chunks which don't get smaller are sent as they are. The chunk hashes are of
+///! the uncompressed chunks, so a resumed transfer may use another compression.
+///!
+///! The peers supporting the protocol are learned from the identify protocol, the
+///! objects are downloaded from the other ones with the object sender. Both count
+///! against the same upload limits of the providing node.
+
+pub const TRANSFER_PROTOCOL: StreamProtocol = StreamProtocol::new("/liberum/transfer/1.0.0");
+/// The size of the chunks of the payload, the last one may be shorter
+const CHUNK_SIZE: usize = 64 * 1024;
+/// The number of chunks the provider may send before more are granted
+const WINDOW: u32 = 16;
+/// The largest request or header, the header of the biggest object has 4096 hashes
+/// of its chunks
+const MAX_CONTROL_FRAME: usize = 256 * 1024;
+/// How long a frame may take to arrive before the transfer is abandoned
+const FRAME_TIMEOUT: Duration = Duration::from_secs(10);
+/// The inbound streams handled at once, the next ones are dropped before their
+/// requests are read
+const MAX_SERVING_TASKS: usize = 64;
+
+#[derive(Serialize, Deserialize, Debug)]
+struct TransferRequest {
+    object_id: proto::Hash,
+    /// The membership of the requesting peer, needed to get the objects of a group
+    access_token: Option<GroupAccessToken>,
+    /// Where the payload starts, at the beginning of a chunk
+    offset: u64,
+    /// The compression the requesting peer accepts
+    compression: Compression,
+}
+
+/// Sent before the payload
+#[derive(Serialize, Deserialize, Debug)]
+struct ObjectHeader {
+    uuid: Uuid,
+    size: u64,
+    /// The hashes of all the chunks of the payload, also the ones before the offset
+    chunk_hashes: Vec<[u8; 32]>,
+    /// The compression the chunks may be sent with
+    compression: Compression,
+}
+
+/// Reported to the swarm when a transfer ends, to count the bytes and to audit
+/// the served requests
+pub(crate) enum TransferEvent {
+    Served {
+        peer: PeerId,
+        object_id: proto::Hash,
+        rejection: Option<ResultErrorCode>,
+        bytes: u64,
+    },
+    Fetched {
+        peer: PeerId,
+        bytes: u64,
+        misbehaviour: Option<Misbehaviour>,
+    },
+}
+
+/// The transfers of the swarm, the inbound ones are served until it's dropped
+pub(crate) struct Transfers {
+    control: Control,
+    vault_ref: ActorRef<Vault>,
+    events: mpsc::Sender<TransferEvent>,
+    server: JoinHandle<()>,
+    compression: CompressionConfig,
+}
+
+impl Drop for Transfers {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+impl Transfers {
+    /// Starts serving the objects from the vault. Returns the events of the transfers
+    pub(crate) fn start(
+        behaviour: &libp2p_stream::Behaviour,
+        vault_ref: ActorRef<Vault>,
+        compression: CompressionConfig,
+        quotas: UploadQuotas,
+    ) -> Result<(Transfers, mpsc::Receiver<TransferEvent>)> {
+        let mut control = behaviour.new_control();
+        let incoming = control.accept(TRANSFER_PROTOCOL)?;
+        let (events, receiver) = mpsc::channel(16);
+        let server = tokio::spawn(serve(
+            incoming,
+            vault_ref.clone(),
+            events.clone(),
+            compression.level(),
+            quotas,
+        ));
+        let transfers = Transfers {
+            control,
+            vault_ref,
+            events,
+            server,
+            compression,
+        };
+        Ok((transfers, receiver))
+    }
+
+    /// Downloads the object from the peer in the background. A rejected request is
+    /// answered with the result object, like by the object sender
+    pub(crate) fn fetch(
+        &self,
+        peer: PeerId,
+        object_id: proto::Hash,
+        access_token: Option<GroupAccessToken>,
+        response_sender: oneshot::Sender<Result<TypedObject>>,
+    ) {
+        let mut control = self.control.clone();
+        let vault_ref = self.vault_ref.clone();
+        let events = self.events.clone();
+        let compression = match self.compression.enabled {
+            true => Compression::Zstd,
+            false => Compression::None,
+        };
+        tokio::spawn(async move {
+            let mut report = FetchReport::default();
+            let request = TransferRequest {
+                object_id,
+                access_token,
+                offset: 0,
+                compression,
+            };
+            let result = match control.open_stream(peer, TRANSFER_PROTOCOL).await {
+                Ok(mut stream) => {
+                    let result =
+                        receive_object(&mut stream, &vault_ref, request, &mut report).await;
+                    let _ = stream.close().await;
+                    result
+                }
+                Err(e) => Err(anyhow!(e)),
+            };
+            let _ = response_sender.send(result);
+            let _ = events
+                .send(TransferEvent::Fetched {
+                    peer,
+                    bytes: report.bytes,
+                    misbehaviour: report.misbehaviour,
+                })
+                .await;
+        });
+    }
+}
+
+#[derive(Default)]
+struct FetchReport {
+    bytes: u64,
+    misbehaviour: Option<Misbehaviour>,
+}
+
+/// Methods on SwarmContext for the transfer protocol
+impl SwarmContext {
+    pub(crate) async fn handle_transfer_event(&mut self, event: TransferEvent) {
+        match event {
+            TransferEvent::Served {
+                peer,
+                object_id,
+                rejection,
+                bytes,
+            } => {
+                self.connections.touch(&peer);
+                self.stats.bytes_sent += bytes;
+                self.audit(&peer, AuditRequestKind::Get, &object_id, rejection)
+                    .await;
+                if rejection.is_none() {
+                    self.record_served(&object_id).await;
+                }
+            }
+            TransferEvent::Fetched {
+                peer,
+                bytes,
+                misbehaviour,
+            } => {
+                self.connections.touch(&peer);
+                self.stats.bytes_received += bytes;
+                if let Some(misbehaviour) = misbehaviour {
+                    self.report_peer(&peer, misbehaviour).await;
+                }
+            }
+        }
+    }
+}
+
+async fn serve(
+    mut incoming: IncomingStreams,
+    vault_ref: ActorRef<Vault>,
+    events: mpsc::Sender<TransferEvent>,
+    level: Option<i32>,
+    quotas: UploadQuotas,
+) {
+    let tasks = Arc::new(Semaphore::new(MAX_SERVING_TASKS));
+    while let Some((peer, mut stream)) = incoming.next().await {
+        let Ok(task) = tasks.clone().try_acquire_owned() else {
+            debug!(
+                peer_id = peer.to_base58(),
+                "Too many transfers, dropped the stream"
+            );
+            continue;
+        };
+        let vault_ref = vault_ref.clone();
+        let events = events.clone();
+        let quotas = quotas.clone();
+        tokio::spawn(async move {
+            let _task = task;
+            let result = send_object(peer, &mut stream, &vault_ref, level, &quotas).await;
+            let _ = stream.close().await;
+            match result {
+                Ok(event) => {
+                    let _ = events.send(event).await;
+                }
+                Err(e) => debug!(
+                    peer_id = peer.to_base58(),
+                    err = format!("{e}"),
+                    "Transfer to peer failed"
+                ),
+            }
+        });
+    }
+}
+
+async fn send_object<S: AsyncRead + AsyncWrite + Unpin>(
+    peer: PeerId,
+    stream: &mut S,
+    vault_ref: &ActorRef<Vault>,
+    level: Option<i32>,
+    quotas: &UploadQuotas,
+) -> Result<TransferEvent> {
+    let request: TransferRequest = read_message(stream).await?;
+    let object = load_object(&peer, &request, vault_ref)
+        .await
+        .and_then(|object| match request.offset {
+            offset if offset > object.data.len() as u64 || offset % CHUNK_SIZE as u64 != 0 => {
+                Err(ResultErrorCode::Other)
+            }
+            _ => Ok(object),
+        })
+        .and_then(|object| {
+            // Held until the whole payload is sent
+            let size = object.data.len() as u64 - request.offset;
+            quotas.acquire(&peer, size).map(|permit| (object, permit))
+        });
+    let (object, _permit) = match object {
+        Ok(object) => object,
+        Err(code) => {
+            write_message(stream, &Err::<ObjectHeader, _>(code)).await?;
+            return Ok(TransferEvent::Served {
+                peer,
+                object_id: request.object_id,
+                rejection: Some(code),
+                bytes: 0,
+            });
+        }
+    };
+
+    let compression = match level {
+        Some(_) => request.compression,
+        None => Compression::None,
+    };
+    let header = ObjectHeader {
+        uuid: object.uuid,
+        size: object.data.len() as u64,
+        chunk_hashes: object
+            .data
+            .chunks(CHUNK_SIZE)
+            .map(|chunk| *blake3::hash(chunk).as_bytes())
+            .collect(),
+        compression,
+    };
+    write_message(stream, &Ok::<_, ResultErrorCode>(header)).await?;
+    let level = level.filter(|_| compression == Compression::Zstd);
+    let mut sent = 0;
+    let mut granted = WINDOW;
+    for chunk in object.data[request.offset as usize..].chunks(CHUNK_SIZE) {
+        while granted == 0 {
+            granted = read_message(stream).await?;
+        }
+        let frame = encode_chunk(chunk, level);
+        write_frame(stream, &frame).await?;
+        sent += frame.len() as u64;
+        granted -= 1;
+    }
+
+    Ok(TransferEvent::Served {
+        peer,
+        object_id: request.object_id,
+        rejection: None,
+        bytes: sent,
+    })
+}
+
+/// The object from the vault, if its access policy allows the peer to get it
+async fn load_object(
+    peer: &PeerId,
+    request: &TransferRequest,
+    vault_ref: &ActorRef<Vault>,
+) -> Result<TypedObject, ResultErrorCode> {
+    let policy = vault_ref
+        .ask(vault::LoadAccessPolicy {
+            hash: request.object_id.clone(),
+        })
+        .send()
+        .await
+        .map_err(|_| ResultErrorCode::Other)?;
+    policy.check(peer, request.access_token.as_ref())?;
+
+    let object = vault_ref
+        .ask(vault::LoadObject {
+            hash: request.object_id.clone(),
+        })
+        .send()
+        .await
+        .map_err(|_| ResultErrorCode::Other)?;
+    match object {
+        Some(ObjectEnum::Typed(object)) => Ok(object),
+        _ => Err(ResultErrorCode::NotFound),
+    }
+}
+
+async fn receive_object<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    vault_ref: &ActorRef<Vault>,
+    mut request: TransferRequest,
+    report: &mut FetchReport,
+) -> Result<TypedObject> {
+    let object_id = request.object_id.clone();
+    let stored = vault_ref
+        .ask(vault::LoadPartialObject {
+            hash: object_id.clone(),
+        })
+        .send()
+        .await
+        .map_err(|e| anyhow!(e.to_string()))?;
+    let kept = stored.as_ref().map_or(0, |(_, chunks)| chunks.len());
+    request.offset = (kept * CHUNK_SIZE) as u64;
+
+    write_message(stream, &request).await?;
+    let header: Result<ObjectHeader, ResultErrorCode> = read_message(stream).await?;
+    let header = match header {
+        Ok(header) => header,
+        Err(code) => return Ok(ResultObject { result: Err(code) }.into()),
+    };
+    if header.size > proto::MAX_OBJECT_SIZE {
+        report.misbehaviour = Some(Misbehaviour::ProtocolViolation);
+        bail!("Object of {} bytes is too big", header.size);
+    }
+
+    if header.chunk_hashes.len() as u64 != header.size.div_ceil(CHUNK_SIZE as u64) {
+        report.misbehaviour = Some(Misbehaviour::ProtocolViolation);
+        bail!("Header does not have the hashes of all the chunks");
+    }
+
+    // The provider can't choose a compression which was not offered
+    let compression = match header.compression == request.compression {
+        true => header.compression,
+        false => Compression::None,
+    };
+    let partial = PartialObject {
+        uuid: header.uuid,
+        size: header.size,
+        chunk_hashes: header.chunk_hashes,
+    };
+    let mut data = Vec::with_capacity(partial.size as usize);
+    match stored {
+        Some((stored, kept_chunks)) if stored == partial => {
+            for chunk in kept_chunks {
+                data.extend_from_slice(&chunk);
+            }
+        }
+        // The payload already comes from the offset, the next request starts over
+        Some(_) if kept > 0 => {
+            delete_partial_object(vault_ref, &object_id).await?;
+            bail!("Object {object_id} changed since its transfer was interrupted");
+        }
+        _ => {
+            vault_ref
+                .ask(vault::StartPartialObject {
+                    hash: object_id.clone(),
+                    partial: partial.clone(),
+                })
+                .send()
+                .await
+                .map_err(|e| anyhow!(e.to_string()))?;
+        }
+    }
+
+    let mut hasher = ObjectHasher::new(&partial.uuid, partial.size)?;
+    hasher.update(&data);
+    let mut index = kept as u64;
+    let mut received = request.offset;
+    let mut granted = 0;
+    while received < partial.size {
+        let frame = read_frame(stream, CHUNK_SIZE + 1).await?;
+        report.bytes += frame.len() as u64;
+        let chunk = decode_chunk(&frame, compression).inspect_err(|_| {
+            report.misbehaviour = Some(Misbehaviour::ProtocolViolation);
+        })?;
+        if chunk.len() as u64 != (partial.size - received).min(CHUNK_SIZE as u64) {
+            report.misbehaviour = Some(Misbehaviour::ProtocolViolation);
+            bail!("Peer sent a chunk of a wrong size");
+        }
+        if !partial.verify_chunk(index, &chunk) {
+            report.misbehaviour = Some(Misbehaviour::FailedIntegrity);
+            bail!("Chunk {index} of {object_id} does not match its hash");
+        }
+        hasher.update(&chunk);
+        received += chunk.len() as u64;
+        data.extend_from_slice(&chunk);
+        vault_ref
+            .ask(vault::StorePartialChunk {
+                hash: object_id.clone(),
+                index,
+                data: chunk,
+            })
+            .send()
+            .await
+            .map_err(|e| anyhow!(e.to_string()))?;
+        index += 1;
+
+        granted += 1;
+        if granted == WINDOW / 2 {
+            write_message(stream, &granted).await?;
+            granted = 0;
+        }
+    }
+
+    if !hasher.matches(&object_id) {
+        delete_partial_object(vault_ref, &object_id).await?;
+        report.misbehaviour = Some(Misbehaviour::FailedIntegrity);
+        bail!("Received object does not match {object_id}");
+    }
+
+    delete_partial_object(vault_ref, &object_id).await?;
+
+    Ok(TypedObject {
+        uuid: partial.uuid,
+        data,
+    })
+}
+
+/// The chunk with the tag of its compression
+fn encode_chunk(chunk: &[u8], level: Option<i32>) -> Vec<u8> {
+    let compressed = Compressed::new(chunk.to_vec(), level);
+    let mut frame = Vec::with_capacity(compressed.data.len() + 1);
+    frame.push(compressed.compression.tag());
+    frame.extend_from_slice(&compressed.data);
+    frame
+}
+
+/// The uncompressed chunk, only the negotiated compression is accepted
+fn decode_chunk(frame: &[u8], negotiated: Compression) -> Result<Vec<u8>> {
+    let Some((&tag, data)) = frame.split_first() else {
+        bail!("Peer sent an empty chunk");
+    };
+    let compression = Compression::from_tag(tag)?;
+    if compression != Compression::None && compression != negotiated {
+        bail!("Peer sent a chunk compressed with {compression:?}, which was not negotiated");
+    }
+    Compressed {
+        compression,
+        data: data.to_vec(),
+    }
+    .decompress(CHUNK_SIZE)
+}
+
+async fn delete_partial_object(vault_ref: &ActorRef<Vault>, object_id: &proto::Hash) -> Result<()> {
+    vault_ref
+        .ask(vault::DeletePartialObject {
+            hash: object_id.clone(),
+        })
+        .send()
+        .await
+        .map_err(|e| anyhow!(e.to_string()))
+}
+
+/// Hashes the object while its payload is received. Both encodings are hashed, as
+/// it's not known if the payload is canonical before all of it arrives
+struct ObjectHasher {
+    canonical: blake3::Hasher,
+    legacy: blake3::Hasher,
+}
+
+impl ObjectHasher {
+    fn new(uuid: &Uuid, size: u64) -> Result<Self> {
+        let mut canonical = blake3::Hasher::new();
+        canonical.update(&canonical::object_prefix(uuid, size));
+        // The bincode encoding of the object without the payload
+        let mut legacy = blake3::Hasher::new();
+        legacy.update(&bincode::serialize(uuid)?);
+        legacy.update(&size.to_le_bytes());
+        Ok(ObjectHasher { canonical, legacy })
+    }
+
+    fn update(&mut self, chunk: &[u8]) {
+        self.canonical.update(chunk);
+        self.legacy.update(chunk);
+    }
+
+    fn matches(&self, object_id: &proto::Hash) -> bool {
+        [&self.canonical, &self.legacy]
+            .iter()
+            .any(|hasher| hasher.finalize().as_bytes() == &object_id.bytes)
+    }
+}
+
+async fn write_message<M: Serialize, T: AsyncWrite + Unpin>(io: &mut T, message: &M) -> Result<()> {
+    Ok(write_frame(io, &bincode::serialize(message)?).await?)
+}
+
+async fn read_message<M: DeserializeOwned, T: AsyncRead + Unpin>(io: &mut T) -> Result<M> {
+    proto::decode(&read_frame(io, MAX_CONTROL_FRAME).await?)
+}
+
+async fn write_frame<T: AsyncWrite + Unpin>(io: &mut T, frame: &[u8]) -> io::Result<()> {
+    io.write_all(&(frame.len() as u32).to_be_bytes()).await?;
+    io.write_all(frame).await?;
+    io.flush().await
+}
+
+/// Reads the frame, the longer ones than the limit are rejected before they are read
+async fn read_frame<T: AsyncRead + Unpin>(io: &mut T, limit: usize) -> io::Result<Vec<u8>> {
+    let read = async {
+        let mut len = [0; 4];
+        io.read_exact(&mut len).await?;
+        let len = u32::from_be_bytes(len) as usize;
+        if len > limit {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Frame of {len} bytes is over the limit of {limit}"),
+            ));
+        }
+        let mut frame = vec![0; len];
+        io.read_exact(&mut frame).await?;
+        Ok(frame)
+    };
+    tokio::time::timeout(FRAME_TIMEOUT, read)
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "Transfer timed out"))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::io::Cursor;
+    use liberum_core::node_config::UploadLimits;
+    use liberum_core::proto::PlainFileObject;
+    use std::pin::Pin;
+    use std::task::{ready, Context, Poll};
+    use tokio_util::compat::TokioAsyncReadCompatExt;
+
+    /// The connection which breaks after the budget of bytes is read
+    struct Cut<T> {
+        inner: T,
+        budget: usize,
+    }
+
+    impl<T: AsyncRead + Unpin> AsyncRead for Cut<T> {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            if self.budget == 0 {
+                return Poll::Ready(Err(io::ErrorKind::ConnectionReset.into()));
+            }
+            let max = buf.len().min(self.budget);
+            let read = ready!(Pin::new(&mut self.inner).poll_read(cx, &mut buf[..max]))?;
+            self.budget -= read;
+            Poll::Ready(Ok(read))
+        }
+    }
+
+    impl<T: AsyncWrite + Unpin> AsyncWrite for Cut<T> {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            Pin::new(&mut self.inner).poll_write(cx, buf)
+        }
+
+        fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.inner).poll_flush(cx)
+        }
+
+        fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.inner).poll_close(cx)
+        }
+    }
+
+    fn quotas() -> UploadQuotas {
+        UploadQuotas::new(UploadLimits::default())
+    }
+
+    #[test]
+    fn object_hasher_test() {
+        let legacy = TypedObject {
+            uuid: PlainFileObject::UUID,
+            data: vec![3; CHUNK_SIZE * 2 + 5],
+        };
+        let canonical: TypedObject = PlainFileObject {
+            name: "file".to_string(),
+            content: vec![3; CHUNK_SIZE * 2 + 5],
+        }
+        .into();
+
+        for object in [legacy, canonical] {
+            let id = proto::Hash::try_from(&object).unwrap();
+            let mut hasher = ObjectHasher::new(&object.uuid, object.data.len() as u64).unwrap();
+            for chunk in object.data.chunks(CHUNK_SIZE) {
+                hasher.update(chunk);
+            }
+            assert!(hasher.matches(&id));
+            assert!(!hasher.matches(&proto::Hash { bytes: [0; 32] }));
+        }
+    }
+
+    #[tokio::test]
+    async fn resume_test() {
+        let object = TypedObject {
+            uuid: PlainFileObject::UUID,
+            data: (0..CHUNK_SIZE * 4 + 100).map(|i| i as u8).collect(),
+        };
+        let object_id = proto::Hash::try_from(&object).unwrap();
+        let provider = kameo::spawn(Vault::new_in_memory().await.unwrap());
+        provider
+            .ask(vault::StoreObject {
+                hash: object_id.clone(),
+                object: ObjectEnum::Typed(object.clone()),
+            })
+            .send()
+            .await
+            .unwrap();
+        let receiver = kameo::spawn(Vault::new_in_memory().await.unwrap());
+        let request = || TransferRequest {
+            object_id: object_id.clone(),
+            access_token: None,
+            offset: 0,
+            compression: Compression::None,
+        };
+
+        // The connection breaks in the middle of the third chunk
+        let (local, remote) = tokio::io::duplex(CHUNK_SIZE * 8);
+        let mut local = Cut {
+            inner: local.compat(),
+            budget: CHUNK_SIZE * 2 + 1000,
+        };
+        let mut remote = remote.compat();
+        let mut report = FetchReport::default();
+        let (received, _) = tokio::join!(
+            receive_object(&mut local, &receiver, request(), &mut report),
+            send_object(PeerId::random(), &mut remote, &provider, None, &quotas()),
+        );
+        assert!(received.is_err());
+        assert!(report.misbehaviour.is_none());
+        let (_, kept) = receiver
+            .ask(vault::LoadPartialObject {
+                hash: object_id.clone(),
+            })
+            .send()
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(kept.len(), 2);
+
+        // Only the rest of the payload is sent again
+        let (local, remote) = tokio::io::duplex(CHUNK_SIZE * 8);
+        let (mut local, mut remote) = (local.compat(), remote.compat());
+        let mut report = FetchReport::default();
+        let (received, served) = tokio::join!(
+            receive_object(&mut local, &receiver, request(), &mut report),
+            send_object(PeerId::random(), &mut remote, &provider, None, &quotas()),
+        );
+        let received = received.unwrap();
+        assert_eq!(received.uuid, object.uuid);
+        assert_eq!(received.data, object.data);
+        // The rest of the payload and the tags of its 3 chunks
+        let resumed = (object.data.len() - CHUNK_SIZE * 2 + 3) as u64;
+        assert_eq!(report.bytes, resumed);
+        let Ok(TransferEvent::Served { bytes, .. }) = served else {
+            panic!("Object not served");
+        };
+        assert_eq!(bytes, resumed);
+        let partial = receiver
+            .ask(vault::LoadPartialObject { hash: object_id })
+            .send()
+            .await
+            .unwrap();
+        assert!(partial.is_none());
+    }
+
+    #[tokio::test]
+    async fn compression_test() {
+        let text = "All work and no play makes Jack a dull boy. ".repeat(CHUNK_SIZE / 10);
+        let random: Vec<u8> = (0..CHUNK_SIZE * 2).map(|_| rand::random()).collect();
+        let provider = kameo::spawn(Vault::new_in_memory().await.unwrap());
+        for data in [text.into_bytes(), random] {
+            let object = TypedObject {
+                uuid: PlainFileObject::UUID,
+                data,
+            };
+            let object_id = proto::Hash::try_from(&object).unwrap();
+            provider
+                .ask(vault::StoreObject {
+                    hash: object_id.clone(),
+                    object: ObjectEnum::Typed(object.clone()),
+                })
+                .send()
+                .await
+                .unwrap();
+            let receiver = kameo::spawn(Vault::new_in_memory().await.unwrap());
+            let request = TransferRequest {
+                object_id,
+                access_token: None,
+                offset: 0,
+                compression: Compression::Zstd,
+            };
+
+            let (local, remote) = tokio::io::duplex(CHUNK_SIZE * 8);
+            let (mut local, mut remote) = (local.compat(), remote.compat());
+            let mut report = FetchReport::default();
+            let (received, served) = tokio::join!(
+                receive_object(&mut local, &receiver, request, &mut report),
+                send_object(PeerId::random(), &mut remote, &provider, Some(3), &quotas()),
+            );
+            assert_eq!(received.unwrap().data, object.data);
+            let Ok(TransferEvent::Served { bytes, .. }) = served else {
+                panic!("Object not served");
+            };
+            assert_eq!(bytes, report.bytes);
+
+            let chunks = object.data.len().div_ceil(CHUNK_SIZE);
+            if object.data.is_ascii() {
+                assert!(report.bytes * 10 < object.data.len() as u64);
+            } else {
+                // Only the tags are added to the incompressible chunks
+                assert_eq!(report.bytes, (object.data.len() + chunks) as u64);
+            }
+        }
+
+        let frame = encode_chunk(&[b'a'; 1000], Some(3));
+        assert!(decode_chunk(&frame, Compression::None).is_err());
+        assert_eq!(
+            decode_chunk(&frame, Compression::Zstd).unwrap(),
+            [b'a'; 1000]
+        );
+        assert!(decode_chunk(&[], Compression::Zstd).is_err());
+    }
+
+    #[tokio::test]
+    async fn frames_test() {
+        let mut io = Cursor::new(Vec::new());
+        write_message(&mut io, &WINDOW).await.unwrap();
+        write_frame(&mut io, &[1; 100]).await.unwrap();
+        io.set_position(0);
+        assert_eq!(read_message::<u32, _>(&mut io).await.unwrap(), WINDOW);
+        assert!(read_frame(&mut io, 99).await.is_err());
+        // The end of the stream in the middle of a frame
+        assert!(read_frame(&mut io, 100).await.is_err());
+    }
+}
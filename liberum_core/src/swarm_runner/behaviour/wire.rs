@@ -0,0 +1,249 @@
+use std::io;
+
+use async_trait::async_trait;
+use futures::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use liberum_core::proto;
+use libp2p::request_response;
+use libp2p::{PeerId, StreamProtocol};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use super::object_sender::{ObjectResponse, ObjectSendRequest};
+
+///! The module contains the wire formats of the object sender protocol. Version 1
+///! encodes the messages with CBOR, which writes every byte of an object as a separate
+///! integer. Version 2 encodes them with bincode, the same way the objects are hashed
+///! and stored, and allows much bigger objects in the responses. The requests stay
+///! small, as a peer reads them without asking for them.
+///!
+///! Bincode is not self-describing, so unlike in version 1 the fields can't be
+///! added to the messages, any change of their layout needs a new version.
+///!
+///! Both versions are offered when a stream is opened and the first one supported by
+///! both peers is negotiated, so the nodes keep talking to the peers which know only
+///! version 1. The versions supported by a peer are also learned from the identify
+///! protocol, to show them to the user.
+
+pub const OBJECT_SENDER_PROTO_V1: StreamProtocol =
+    StreamProtocol::new("/liberum/object-sender/1.0.0");
+pub const OBJECT_SENDER_PROTO_V2: StreamProtocol =
+    StreamProtocol::new("/liberum/object-sender/2.0.0");
+/// The versions supported by the node, the preferred one first
+pub const OBJECT_SENDER_PROTOCOLS: [StreamProtocol; 2] =
+    [OBJECT_SENDER_PROTO_V2, OBJECT_SENDER_PROTO_V1];
+
+/// The limits of version 1, the same as the ones of the old peers
+const V1_REQUEST_SIZE_MAXIMUM: u64 = 1024 * 1024;
+const V1_RESPONSE_SIZE_MAXIMUM: u64 = 10 * 1024 * 1024;
+/// The largest request of version 2, the bigger objects are downloaded by the peers
+/// storing them instead of being sent in a request
+pub const V2_REQUEST_SIZE_MAXIMUM: u64 = 4 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum WireVersion {
+    V1 = 1,
+    V2 = 2,
+}
+
+impl WireVersion {
+    pub fn of(protocol: &StreamProtocol) -> Option<WireVersion> {
+        match protocol.as_ref() {
+            p if p == OBJECT_SENDER_PROTO_V1.as_ref() => Some(WireVersion::V1),
+            p if p == OBJECT_SENDER_PROTO_V2.as_ref() => Some(WireVersion::V2),
+            _ => None,
+        }
+    }
+
+    /// The newest version among the protocols announced by a peer
+    pub fn newest_of<'a>(
+        protocols: impl IntoIterator<Item = &'a StreamProtocol>,
+    ) -> Option<WireVersion> {
+        protocols.into_iter().filter_map(WireVersion::of).max()
+    }
+
+    pub fn encode<M: Serialize>(self, message: &M) -> io::Result<Vec<u8>> {
+        match self {
+            WireVersion::V1 => cbor4ii::serde::to_vec(Vec::new(), message)
+                .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string())),
+            WireVersion::V2 => bincode::serialize(message)
+                .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string())),
+        }
+    }
+
+    pub fn decode<M: DeserializeOwned>(self, bytes: &[u8]) -> io::Result<M> {
+        match self {
+            WireVersion::V1 => cbor4ii::serde::from_slice(bytes)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string())),
+            WireVersion::V2 => proto::decode(bytes)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string())),
+        }
+    }
+
+    fn request_size_maximum(self) -> u64 {
+        match self {
+            WireVersion::V1 => V1_REQUEST_SIZE_MAXIMUM,
+            WireVersion::V2 => V2_REQUEST_SIZE_MAXIMUM,
+        }
+    }
+
+    fn response_size_maximum(self) -> u64 {
+        match self {
+            WireVersion::V1 => V1_RESPONSE_SIZE_MAXIMUM,
+            WireVersion::V2 => proto::MAX_OBJECT_SIZE,
+        }
+    }
+}
+
+/// Encodes the messages of the object sender in the negotiated version
+#[derive(Debug, Clone, Default)]
+pub struct ObjectSenderCodec;
+
+#[async_trait]
+impl request_response::Codec for ObjectSenderCodec {
+    type Protocol = StreamProtocol;
+    type Request = ObjectSendRequest;
+    type Response = ObjectResponse;
+
+    async fn read_request<T>(
+        &mut self,
+        protocol: &StreamProtocol,
+        io: &mut T,
+    ) -> io::Result<Self::Request>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        let version = negotiated(protocol)?;
+        read_message(version, io, version.request_size_maximum()).await
+    }
+
+    async fn read_response<T>(
+        &mut self,
+        protocol: &StreamProtocol,
+        io: &mut T,
+    ) -> io::Result<Self::Response>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        let version = negotiated(protocol)?;
+        read_message(version, io, version.response_size_maximum()).await
+    }
+
+    async fn write_request<T>(
+        &mut self,
+        protocol: &StreamProtocol,
+        io: &mut T,
+        request: Self::Request,
+    ) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        let data = negotiated(protocol)?.encode(&request)?;
+        io.write_all(&data).await
+    }
+
+    async fn write_response<T>(
+        &mut self,
+        protocol: &StreamProtocol,
+        io: &mut T,
+        response: Self::Response,
+    ) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        let data = negotiated(protocol)?.encode(&response)?;
+        io.write_all(&data).await
+    }
+}
+
+/// The version of the peer as shown to the user
+pub fn wire_version(behaviour: &super::BehaviourContext, peer_id: &PeerId) -> Option<u8> {
+    behaviour
+        .wire_versions
+        .get(peer_id)
+        .map(|version| *version as u8)
+}
+
+fn negotiated(protocol: &StreamProtocol) -> io::Result<WireVersion> {
+    WireVersion::of(protocol).ok_or(io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("Unknown object sender protocol {protocol}"),
+    ))
+}
+
+/// Every stream carries one message, read until the peer closes it
+async fn read_message<M, T>(version: WireVersion, io: &mut T, limit: u64) -> io::Result<M>
+where
+    M: DeserializeOwned,
+    T: AsyncRead + Unpin + Send,
+{
+    let mut data = Vec::new();
+    io.take(limit).read_to_end(&mut data).await?;
+    version.decode(&data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use futures::io::Cursor;
+    use liberum_core::proto::{AccessPolicy, Hash, PlainFileObject, TypedObject};
+    use request_response::Codec;
+
+    fn request() -> ObjectSendRequest {
+        let object: TypedObject = PlainFileObject {
+            name: "file".to_string(),
+            content: vec![200; 1000],
+        }
+        .into();
+        ObjectSendRequest {
+            object_id: Hash::try_from(&object).unwrap(),
+            object,
+            policy: AccessPolicy::default(),
+            access_token: None,
+        }
+    }
+
+    #[test]
+    fn wire_versions_test() {
+        let mut codec = ObjectSenderCodec;
+        for protocol in OBJECT_SENDER_PROTOCOLS {
+            let mut io = Cursor::new(Vec::new());
+            block_on(codec.write_request(&protocol, &mut io, request())).unwrap();
+            io.set_position(0);
+            let decoded = block_on(codec.read_request(&protocol, &mut io)).unwrap();
+            assert_eq!(decoded.object_id, request().object_id);
+            assert_eq!(decoded.object.data, request().object.data);
+        }
+
+        // An old peer encodes with the CBOR codec of libp2p
+        let old: Vec<u8> = cbor4ii::serde::to_vec(Vec::new(), &request()).unwrap();
+        let decoded =
+            block_on(codec.read_request(&OBJECT_SENDER_PROTO_V1, &mut Cursor::new(old))).unwrap();
+        assert_eq!(decoded.object_id, request().object_id);
+        // and decodes what the node sends in version 1
+        let sent = WireVersion::V1.encode(&request()).unwrap();
+        let decoded: ObjectSendRequest = cbor4ii::serde::from_slice(&sent).unwrap();
+        assert_eq!(decoded.object.data, request().object.data);
+
+        // Version 2 is much smaller for binary data
+        let v2 = WireVersion::V2.encode(&request()).unwrap();
+        assert!(v2.len() < sent.len());
+        assert!(WireVersion::V1.decode::<ObjectSendRequest>(&v2).is_err());
+
+        let unknown = StreamProtocol::new("/liberum/object-sender/3.0.0");
+        assert!(block_on(codec.read_request(&unknown, &mut Cursor::new(v2))).is_err());
+        assert_eq!(
+            WireVersion::newest_of(&[OBJECT_SENDER_PROTO_V1, unknown.clone()]),
+            Some(WireVersion::V1)
+        );
+        assert_eq!(WireVersion::newest_of(&[unknown]), None);
+
+        // The requests are read only up to their own limit
+        let mut big = request();
+        big.object.data = vec![1; V2_REQUEST_SIZE_MAXIMUM as usize];
+        let big = WireVersion::V2.encode(&big).unwrap();
+        assert!(
+            block_on(codec.read_request(&OBJECT_SENDER_PROTO_V2, &mut Cursor::new(big))).is_err()
+        );
+    }
+}
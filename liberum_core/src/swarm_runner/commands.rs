@@ -0,0 +1,520 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use liberum_core::node_config::{NodeConfig, NodeProfile};
+use liberum_core::proto::{
+    self, AccessPolicy, GroupAccessToken, GroupObject, ProfileObject, ResultObject, RotationObject,
+    TypedObject, UserGroup,
+};
+use liberum_core::types::{
+    BucketInfo, ConfigReloadSummary, MessageContent, NodeStatus, PeerInfo, PeerScore,
+};
+use libp2p::{Multiaddr, PeerId};
+use tokio::sync::{mpsc, oneshot};
+use tracing::debug;
+
+use super::behaviour::mailbox::{MailboxRequest, MailboxResponse};
+use super::messages::{Command, ProvidersBatch, SwarmRunnerError};
+use super::reputation::Misbehaviour;
+use super::SwarmContext;
+
+///! The module contains the commands sent from the Node actor to the SwarmRunner.
+///! Every command is its own type, which runs itself on the SwarmContext. The
+///! commands answering later, when the swarm event comes, leave their senders in
+///! the pending requests of their behaviours.
+
+/// Echo message, just sends the message back, testing purposes
+pub struct Echo {
+    pub message: String,
+    pub response_sender: oneshot::Sender<Result<String, SwarmRunnerError>>,
+}
+
+#[async_trait]
+impl Command for Echo {
+    async fn execute(self, _context: &mut SwarmContext) {
+        debug!(message = self.message, "Received Echo!");
+        let _ = self.response_sender.send(Ok(self.message));
+    }
+}
+
+/// Dial a peer and remember it as a contact, useful for connecting to other
+/// nodes in the network. Without the peer ID the one of the peer at the address
+/// is accepted, it's sent back once connected
+pub struct Dial {
+    pub peer_id: Option<PeerId>,
+    pub peer_addr: Multiaddr,
+    pub response_sender: oneshot::Sender<Result<PeerId>>,
+}
+
+#[async_trait]
+impl Command for Dial {
+    async fn execute(self, context: &mut SwarmContext) {
+        context.dial(self.peer_id, self.peer_addr, self.response_sender)
+    }
+}
+
+// Kademlia
+
+/// Start providing a file in the network. Only the node that sent this message
+/// will be a provider for the file. The fact of providing the file will be
+/// announced to up to `k` network members close to the provided ID.
+pub struct ProvideObject {
+    pub object: TypedObject,
+    pub obj_id: proto::Hash,
+    pub response_sender: oneshot::Sender<Result<()>>,
+}
+
+#[async_trait]
+impl Command for ProvideObject {
+    async fn execute(self, context: &mut SwarmContext) {
+        context
+            .provide_object(self.object, self.obj_id, self.response_sender)
+            .await
+    }
+}
+
+/// Stop providing the object in the network. The object is deleted
+/// from the vault, unless `keep_in_vault` is set
+pub struct StopProviding {
+    pub obj_id: proto::Hash,
+    pub keep_in_vault: bool,
+    pub response_sender: oneshot::Sender<Result<()>>,
+}
+
+#[async_trait]
+impl Command for StopProviding {
+    async fn execute(self, context: &mut SwarmContext) {
+        context
+            .stop_providing(self.obj_id, self.keep_in_vault, self.response_sender)
+            .await
+    }
+}
+
+/// Announce again all the objects the node provides. Responds with a receiver
+/// of the result for every object, ready when its announcement finishes
+pub struct Reprovide {
+    pub response_sender: oneshot::Sender<Vec<oneshot::Receiver<Result<()>>>>,
+}
+
+#[async_trait]
+impl Command for Reprovide {
+    async fn execute(self, context: &mut SwarmContext) {
+        let _ = self.response_sender.send(context.reprovide());
+    }
+}
+
+/// Get up to `k` providers for the given key. The providers are sent as soon
+/// as they are found, the channel is closed when the query finishes. Dropping
+/// the receiver stops the query
+pub struct GetProviders {
+    pub obj_id: proto::Hash,
+    pub providers_sender: mpsc::Sender<ProvidersBatch>,
+}
+
+#[async_trait]
+impl Command for GetProviders {
+    async fn execute(self, context: &mut SwarmContext) {
+        context.get_providers(self.obj_id, self.providers_sender)
+    }
+}
+
+/// Get the `k` closest peers to the given key. The response will contain
+/// up to `k` peers that are closest to the given key.
+pub struct GetClosestPeers {
+    pub obj_id: proto::Hash,
+    pub response_sender: oneshot::Sender<Vec<PeerId>>,
+}
+
+#[async_trait]
+impl Command for GetClosestPeers {
+    async fn execute(self, context: &mut SwarmContext) {
+        context.get_closest_peers(self.obj_id.bytes.to_vec(), self.response_sender)
+    }
+}
+
+/// Get the `k` closest peers to the ID of the peer, which keep its mailbox
+pub struct GetClosestPeersOfPeer {
+    pub peer_id: PeerId,
+    pub response_sender: oneshot::Sender<Vec<PeerId>>,
+}
+
+#[async_trait]
+impl Command for GetClosestPeersOfPeer {
+    async fn execute(self, context: &mut SwarmContext) {
+        context.get_closest_peers(self.peer_id, self.response_sender)
+    }
+}
+
+/// Add the bootstrap nodes to the routing table again and bootstrap it. Ok if
+/// the bootstrap query started, it fails only if there are no known peers
+pub struct Bootstrap {
+    pub response_sender: oneshot::Sender<Result<()>>,
+}
+
+#[async_trait]
+impl Command for Bootstrap {
+    async fn execute(self, context: &mut SwarmContext) {
+        let _ = self.response_sender.send(context.bootstrap());
+    }
+}
+
+/// Get the buckets of the routing table, the peers in them and the connected peers
+pub struct GetRoutingTable {
+    pub response_sender: oneshot::Sender<(Vec<BucketInfo>, Vec<PeerInfo>)>,
+}
+
+#[async_trait]
+impl Command for GetRoutingTable {
+    async fn execute(self, context: &mut SwarmContext) {
+        let _ = self.response_sender.send(context.get_routing_table());
+    }
+}
+
+/// Sign the profile and publish it in the DHT under the key of the node
+pub struct PublishProfile {
+    pub profile: NodeProfile,
+    pub response_sender: oneshot::Sender<Result<()>>,
+}
+
+#[async_trait]
+impl Command for PublishProfile {
+    async fn execute(self, context: &mut SwarmContext) {
+        let _ = self
+            .response_sender
+            .send(context.publish_profile(&self.profile));
+    }
+}
+
+/// Find the profile of the peer in the DHT. None if it was not found
+pub struct GetProfile {
+    pub peer_id: PeerId,
+    pub response_sender: oneshot::Sender<Result<Option<ProfileObject>>>,
+}
+
+#[async_trait]
+impl Command for GetProfile {
+    async fn execute(self, context: &mut SwarmContext) {
+        context.get_profile(self.peer_id, self.response_sender)
+    }
+}
+
+/// Find the record of the small object in the DHT. Responds with the object and
+/// the peer which had it, None if it's not stored as a record
+pub struct GetObjectRecord {
+    pub obj_id: proto::Hash,
+    pub response_sender: oneshot::Sender<Result<Option<(TypedObject, PeerId)>>>,
+}
+
+#[async_trait]
+impl Command for GetObjectRecord {
+    async fn execute(self, context: &mut SwarmContext) {
+        context.get_object_record(self.obj_id, self.response_sender)
+    }
+}
+
+/// Find the rotation of the key of the peer in the DHT. None if it was not rotated
+pub struct GetRotation {
+    pub peer_id: PeerId,
+    pub response_sender: oneshot::Sender<Result<Option<RotationObject>>>,
+}
+
+#[async_trait]
+impl Command for GetRotation {
+    async fn execute(self, context: &mut SwarmContext) {
+        context.get_rotation(self.peer_id, self.response_sender)
+    }
+}
+
+// Object sender
+
+/// Download a file from the given node. This requires first finding a provider
+/// using ``GetProviders`` and then sending a request to the provider.
+/// Ok if the file was downloaded successfully, Err otherwise.
+pub struct GetObject {
+    pub obj_id: proto::Hash,
+    pub peer_id: PeerId,
+    /// Sent to the provider to get the object of a group
+    pub access_token: Option<GroupAccessToken>,
+    pub response_sender: oneshot::Sender<Result<TypedObject>>,
+}
+
+#[async_trait]
+impl Command for GetObject {
+    async fn execute(self, context: &mut SwarmContext) {
+        context
+            .get_object(
+                self.obj_id,
+                self.peer_id,
+                self.access_token,
+                self.response_sender,
+            )
+            .await
+    }
+}
+
+/// Send the query to the peer and return its answer, whatever object it is.
+/// Used for the queries of the types handled by the external modules
+pub struct QueryPeer {
+    pub query: TypedObject,
+    pub peer_id: PeerId,
+    pub response_sender: oneshot::Sender<Result<TypedObject>>,
+}
+
+#[async_trait]
+impl Command for QueryPeer {
+    async fn execute(self, context: &mut SwarmContext) {
+        context.query_peer(self.query, self.peer_id, self.response_sender)
+    }
+}
+
+/// Publish a file in the network. This will ask up to `k` nodes near the
+/// published ID to store the file. The nodes will announce to be providers
+/// of the file in the network, just like in `ProvideFile`.
+/// Ok if the Quorum of One provider was reached, Err otherwise.
+///
+/// The current node will not be a provider of the file as a result. (TODO: Do we want this?)
+pub struct SendObject {
+    pub object: TypedObject,
+    pub obj_id: proto::Hash,
+    pub policy: AccessPolicy,
+    pub peer_id: PeerId,
+    pub response_sender: oneshot::Sender<Result<ResultObject>>,
+}
+
+#[async_trait]
+impl Command for SendObject {
+    async fn execute(self, context: &mut SwarmContext) {
+        context.send_object(
+            self.object,
+            self.obj_id,
+            self.policy,
+            self.peer_id,
+            self.response_sender,
+        )
+    }
+}
+
+pub struct DeleteObject {
+    pub obj_id: proto::Hash,
+    pub peer: PeerId,
+    pub response_sender: oneshot::Sender<Result<ResultObject>>,
+}
+
+#[async_trait]
+impl Command for DeleteObject {
+    async fn execute(self, context: &mut SwarmContext) {
+        context.delete_object(self.obj_id, self.peer, self.response_sender)
+    }
+}
+
+// Messenger, mailbox and group feeds
+
+/// Send the message to the peer. Responds with the message ID once delivered
+pub struct SendMessage {
+    pub peer_id: PeerId,
+    pub content: MessageContent,
+    pub encrypt: bool,
+    pub response_sender: oneshot::Sender<Result<proto::Hash>>,
+}
+
+#[async_trait]
+impl Command for SendMessage {
+    async fn execute(self, context: &mut SwarmContext) {
+        context.send_direct_message(
+            self.peer_id,
+            self.content,
+            self.encrypt,
+            self.response_sender,
+        )
+    }
+}
+
+/// Send the request to the mailbox kept by the peer
+pub struct SendMailboxRequest {
+    pub peer_id: PeerId,
+    pub request: MailboxRequest,
+    pub response_sender: oneshot::Sender<Result<MailboxResponse>>,
+}
+
+#[async_trait]
+impl Command for SendMailboxRequest {
+    async fn execute(self, context: &mut SwarmContext) {
+        context.send_mailbox_request(self.peer_id, self.request, self.response_sender)
+    }
+}
+
+/// Subscribe to the feed of the group the node is a member of
+pub struct SubscribeGroup {
+    pub group: UserGroup,
+    pub response_sender: oneshot::Sender<Result<()>>,
+}
+
+#[async_trait]
+impl Command for SubscribeGroup {
+    async fn execute(self, context: &mut SwarmContext) {
+        let _ = self
+            .response_sender
+            .send(context.subscribe_group(self.group));
+    }
+}
+
+/// Publish the signed post in the feed of its group. Responds with the post ID
+pub struct PublishToGroup {
+    pub post: GroupObject,
+    pub response_sender: oneshot::Sender<Result<proto::Hash>>,
+}
+
+#[async_trait]
+impl Command for PublishToGroup {
+    async fn execute(self, context: &mut SwarmContext) {
+        context
+            .publish_to_group(self.post, self.response_sender)
+            .await
+    }
+}
+
+// Peers and the swarm itself
+
+/// Ask a few connected peers for the peers they are connected to. Responds with
+/// the number of peers asked, the received peers are added to the routing table
+pub struct ExchangePeers {
+    pub response_sender: oneshot::Sender<Result<usize>>,
+}
+
+#[async_trait]
+impl Command for ExchangePeers {
+    async fn execute(self, context: &mut SwarmContext) {
+        let _ = self.response_sender.send(context.exchange_peers());
+    }
+}
+
+/// Record a misbehaviour of a peer noticed outside of the swarm, for example
+/// a downloaded object that does not match the requested ID
+pub struct ReportPeer {
+    pub peer_id: PeerId,
+    pub misbehaviour: Misbehaviour,
+}
+
+#[async_trait]
+impl Command for ReportPeer {
+    async fn execute(self, context: &mut SwarmContext) {
+        context.report_peer(&self.peer_id, self.misbehaviour).await
+    }
+}
+
+/// Get the reputation scores of all the peers that misbehaved
+pub struct GetPeerScores {
+    pub response_sender: oneshot::Sender<Vec<PeerScore>>,
+}
+
+#[async_trait]
+impl Command for GetPeerScores {
+    async fn execute(self, context: &mut SwarmContext) {
+        let _ = self.response_sender.send(context.reputation.scores());
+    }
+}
+
+/// Forget the misbehaviours of the peer, a banned peer is talked to again
+pub struct ResetPeerScore {
+    pub peer_id: PeerId,
+    pub response_sender: oneshot::Sender<Result<()>>,
+}
+
+#[async_trait]
+impl Command for ResetPeerScore {
+    async fn execute(self, context: &mut SwarmContext) {
+        let result = context.reset_peer_score(&self.peer_id).await;
+        let _ = self.response_sender.send(result);
+    }
+}
+
+/// Add the peer to or remove it from the blocklist of the running node.
+/// A newly blocked peer is disconnected and removed from the routing table
+pub struct SetPeerBlocked {
+    pub peer_id: PeerId,
+    pub blocked: bool,
+}
+
+#[async_trait]
+impl Command for SetPeerBlocked {
+    async fn execute(self, context: &mut SwarmContext) {
+        context.set_peer_blocked(self.peer_id, self.blocked)
+    }
+}
+
+/// Close all the connections to the peer
+pub struct DisconnectPeer {
+    pub peer_id: PeerId,
+    pub response_sender: oneshot::Sender<Result<()>>,
+}
+
+#[async_trait]
+impl Command for DisconnectPeer {
+    async fn execute(self, context: &mut SwarmContext) {
+        let peer_id = self.peer_id;
+        let result = context
+            .swarm
+            .disconnect_peer_id(peer_id)
+            .map_err(|_| anyhow!("Peer {peer_id} is not connected"));
+        let _ = self.response_sender.send(result);
+    }
+}
+
+/// Get the average round trip times of the connected peers measured with ping
+pub struct GetLatencies {
+    pub response_sender: oneshot::Sender<HashMap<PeerId, Duration>>,
+}
+
+#[async_trait]
+impl Command for GetLatencies {
+    async fn execute(self, context: &mut SwarmContext) {
+        let _ = self.response_sender.send(context.latencies.all());
+    }
+}
+
+pub struct GetAddresses {
+    pub response_sender: oneshot::Sender<Result<Vec<Multiaddr>>>,
+}
+
+#[async_trait]
+impl Command for GetAddresses {
+    async fn execute(self, context: &mut SwarmContext) {
+        debug!("Getting external addresses");
+        let addrs = context
+            .swarm
+            .listeners()
+            .cloned()
+            .collect::<Vec<Multiaddr>>();
+        let _ = self.response_sender.send(Ok(addrs));
+    }
+}
+
+/// Get the statistics of the running swarm, like uptime, connected peers
+/// and the routing table size
+pub struct GetStatus {
+    pub response_sender: oneshot::Sender<NodeStatus>,
+}
+
+#[async_trait]
+impl Command for GetStatus {
+    async fn execute(self, context: &mut SwarmContext) {
+        let _ = self.response_sender.send(context.get_status());
+    }
+}
+
+/// Apply the changed bootstrap nodes and listen addresses without restarting
+pub struct ReloadConfig {
+    pub config: NodeConfig,
+    pub response_sender: oneshot::Sender<Result<ConfigReloadSummary>>,
+}
+
+#[async_trait]
+impl Command for ReloadConfig {
+    async fn execute(self, context: &mut SwarmContext) {
+        let _ = self
+            .response_sender
+            .send(context.reload_config(self.config));
+    }
+}
@@ -0,0 +1,151 @@
+use anyhow::Result;
+use liberum_core::node_config::{BootstrapNode, NodeConfig};
+use liberum_core::types::ConfigReloadSummary;
+use libp2p::Multiaddr;
+use tracing::{debug, warn};
+
+use super::{addresses, SwarmContext};
+
+///! The module applies a changed config to the running swarm. Only the bootstrap
+///! nodes and the listen addresses are diffed and applied, the blocklists are checked
+///! on every connection anyway, the link conditions are looked up for every new
+///! stream and the upload limits for every request. The address policy and the peer
+///! exchange settings are read whenever they're used, the other settings still
+///! require a restart.
+
+/// The items to add and to remove to get from the old list to the new one
+#[derive(Debug, PartialEq)]
+pub struct Diff<T> {
+    pub added: Vec<T>,
+    pub removed: Vec<T>,
+}
+
+pub fn diff<T: PartialEq + Clone>(old: &[T], new: &[T]) -> Diff<T> {
+    Diff {
+        added: new.iter().filter(|n| !old.contains(n)).cloned().collect(),
+        removed: old.iter().filter(|o| !new.contains(o)).cloned().collect(),
+    }
+}
+
+/// Methods on SwarmContext for reloading the config
+impl SwarmContext {
+    pub(crate) fn reload_config(&mut self, config: NodeConfig) -> Result<ConfigReloadSummary> {
+        let old_listen_addrs = self.listen_addresses(&self.node_snapshot.config);
+        let new_listen_addrs = self.listen_addresses(&config);
+        let listen_diff = diff(&old_listen_addrs, &new_listen_addrs);
+        let bootstrap_diff = diff(
+            &self.node_snapshot.config.bootstrap_nodes,
+            &config.bootstrap_nodes,
+        );
+
+        let mut errors = vec![];
+        let mut listened = vec![];
+        for addr in &listen_diff.added {
+            match self.swarm.listen_on(addr.clone()) {
+                Ok(listener_id) => {
+                    self.listeners.insert(addr.clone(), listener_id);
+                    listened.push(addr.to_string());
+                }
+                Err(e) => errors.push(format!("listen on {addr}: {e}")),
+            }
+        }
+        for addr in &listen_diff.removed {
+            if let Some(listener_id) = self.listeners.remove(addr) {
+                self.swarm.remove_listener(listener_id);
+            }
+        }
+
+        for node in &bootstrap_diff.removed {
+            self.swarm
+                .behaviour_mut()
+                .kademlia
+                .remove_address(&node.id, &node.addr);
+        }
+        for node in &bootstrap_diff.added {
+            self.swarm
+                .behaviour_mut()
+                .kademlia
+                .add_address(&node.id, node.addr.clone());
+        }
+        // The DNS seeds are resolved only when the swarm starts
+        let bootstrap_peers = config
+            .bootstrap_nodes
+            .iter()
+            .chain(&self.dns_bootstrap_nodes)
+            .map(|n| n.id);
+        self.connections.set_bootstrap_peers(bootstrap_peers);
+        self.impairments.set(&config.link_conditions);
+        self.upload_quotas.set_limits(config.upload_limits);
+        if !bootstrap_diff.added.is_empty() {
+            self.swarm
+                .behaviour_mut()
+                .kademlia
+                .bootstrap()
+                .inspect_err(|e| warn!(err = e.to_string(), "Could not bootstrap the swarm"))
+                .ok();
+        }
+
+        debug!(
+            node = self.node_snapshot.name,
+            listen = format!("{listen_diff:?}"),
+            bootstrap = format!("{bootstrap_diff:?}"),
+            "Config reloaded"
+        );
+        self.node_snapshot.config = config;
+
+        Ok(ConfigReloadSummary {
+            added_bootstrap_nodes: to_strings(&bootstrap_diff.added),
+            removed_bootstrap_nodes: to_strings(&bootstrap_diff.removed),
+            added_listen_addresses: listened,
+            removed_listen_addresses: listen_diff.removed.iter().map(|a| a.to_string()).collect(),
+            errors,
+        })
+    }
+
+    /// The swarm listens on the external addresses from the config, or on the
+    /// default addresses of the IP stack if there are none. The duplicates are
+    /// listened on once
+    pub(crate) fn listen_addresses(&self, config: &NodeConfig) -> Vec<Multiaddr> {
+        let defaults = match config.external_addresses.is_empty() {
+            true => &self.default_listen_addresses[..],
+            false => &[],
+        };
+        let stack_addresses = defaults
+            .iter()
+            .chain(&self.transport_listen_addresses)
+            .filter(|addr| addresses::is_in_stack(config.address_policy.stack, addr));
+
+        let mut listen_addresses: Vec<Multiaddr> = vec![];
+        for addr in config.external_addresses.iter().chain(stack_addresses) {
+            if !listen_addresses.contains(addr) {
+                listen_addresses.push(addr.clone());
+            }
+        }
+        listen_addresses
+    }
+}
+
+fn to_strings(nodes: &[BootstrapNode]) -> Vec<String> {
+    nodes
+        .iter()
+        .map(|n| format!("{}@{}", n.id.to_base58(), n.addr))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_test() {
+        let result = diff(&[1, 2, 3], &[2, 3, 4]);
+        assert_eq!(
+            result,
+            Diff {
+                added: vec![4],
+                removed: vec![1],
+            }
+        );
+        assert_eq!(diff::<i32>(&[], &[]).added, Vec::<i32>::new());
+    }
+}
@@ -0,0 +1,268 @@
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+use liberum_core::node_config::ConnectionLimits;
+use libp2p::request_response::OutboundRequestId;
+use libp2p::swarm::ConnectionId;
+use libp2p::PeerId;
+use tracing::debug;
+
+use super::SwarmContext;
+
+///! The module contains the connection manager of the swarm.
+///! It limits the number of inbound and outbound connections and closes the
+///! connections to peers that are idle. Bootstrap nodes and peers that take part
+///! in an object transfer are kept alive. The inbound connections over the limit
+///! are denied by the connection limits behaviour before they are established, so
+///! only the outbound ones are pruned here.
+
+/// How often the connection manager looks for idle connections
+pub const PRUNE_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Inbound,
+    Outbound,
+}
+
+struct PeerConnections {
+    connections: HashMap<ConnectionId, Direction>,
+    last_active: Instant,
+}
+
+/// Bookkeeping of the connections of the swarm
+pub struct ConnectionManager {
+    limits: ConnectionLimits,
+    peers: HashMap<PeerId, PeerConnections>,
+    bootstrap_peers: HashSet<PeerId>,
+    /// Object sender requests waiting for a response and the peers they were sent to
+    transfers: HashMap<OutboundRequestId, PeerId>,
+}
+
+impl ConnectionManager {
+    pub fn new(
+        limits: ConnectionLimits,
+        bootstrap_peers: impl IntoIterator<Item = PeerId>,
+    ) -> Self {
+        ConnectionManager {
+            limits,
+            peers: HashMap::new(),
+            bootstrap_peers: bootstrap_peers.into_iter().collect(),
+            transfers: HashMap::new(),
+        }
+    }
+
+    /// Replaces the protected bootstrap peers, when the config is reloaded
+    pub fn set_bootstrap_peers(&mut self, bootstrap_peers: impl IntoIterator<Item = PeerId>) {
+        self.bootstrap_peers = bootstrap_peers.into_iter().collect();
+    }
+
+    pub fn add_connection(
+        &mut self,
+        peer_id: PeerId,
+        connection_id: ConnectionId,
+        direction: Direction,
+    ) {
+        let peer = self
+            .peers
+            .entry(peer_id)
+            .or_insert_with(|| PeerConnections {
+                connections: HashMap::new(),
+                last_active: Instant::now(),
+            });
+        peer.connections.insert(connection_id, direction);
+        peer.last_active = Instant::now();
+    }
+
+    pub fn remove_connection(&mut self, peer_id: &PeerId, connection_id: &ConnectionId) {
+        if let Some(peer) = self.peers.get_mut(peer_id) {
+            peer.connections.remove(connection_id);
+            if peer.connections.is_empty() {
+                self.peers.remove(peer_id);
+            }
+        }
+    }
+
+    pub fn count(&self, direction: Direction) -> usize {
+        self.peers
+            .values()
+            .flat_map(|p| p.connections.values())
+            .filter(|d| **d == direction)
+            .count()
+    }
+
+    pub fn is_over_limit(&self, direction: Direction) -> bool {
+        let max = match direction {
+            Direction::Inbound => self.limits.max_inbound,
+            Direction::Outbound => self.limits.max_outbound,
+        };
+
+        self.count(direction) > max
+    }
+
+    /// Marks the peer as active, so it is not considered idle
+    pub fn touch(&mut self, peer_id: &PeerId) {
+        if let Some(peer) = self.peers.get_mut(peer_id) {
+            peer.last_active = Instant::now();
+        }
+    }
+
+    pub fn begin_transfer(&mut self, request_id: OutboundRequestId, peer_id: PeerId) {
+        self.transfers.insert(request_id, peer_id);
+        self.touch(&peer_id);
+    }
+
+    pub fn end_transfer(&mut self, request_id: &OutboundRequestId) {
+        if let Some(peer_id) = self.transfers.remove(request_id) {
+            self.touch(&peer_id);
+        }
+    }
+
+    /// Bootstrap nodes and peers with a transfer in progress are never pruned
+    pub fn is_kept_alive(&self, peer_id: &PeerId) -> bool {
+        self.bootstrap_peers.contains(peer_id) || self.transfers.values().any(|p| p == peer_id)
+    }
+
+    /// Chooses a connection to close when the limit for the direction is exceeded.
+    /// Peers outside of the routing table are pruned first, then the ones that were
+    /// inactive for the longest time
+    pub fn prune_candidate(
+        &self,
+        direction: Direction,
+        routing_peers: &HashSet<PeerId>,
+    ) -> Option<(PeerId, ConnectionId)> {
+        self.peers
+            .iter()
+            .filter(|(peer_id, _)| !self.is_kept_alive(peer_id))
+            .flat_map(|(peer_id, peer)| {
+                peer.connections
+                    .iter()
+                    .filter(|(_, d)| **d == direction)
+                    .map(move |(connection_id, _)| (peer_id, peer, connection_id))
+            })
+            .min_by_key(|(peer_id, peer, _)| (routing_peers.contains(peer_id), peer.last_active))
+            .map(|(peer_id, _, connection_id)| (*peer_id, *connection_id))
+    }
+
+    /// Peers that were not active for longer than the idle timeout and are not kept alive
+    pub fn idle_peers(&self, now: Instant) -> Vec<PeerId> {
+        let idle_timeout = Duration::from_secs(self.limits.idle_timeout_secs);
+
+        self.peers
+            .iter()
+            .filter(|(peer_id, _)| !self.is_kept_alive(peer_id))
+            .filter(|(_, peer)| now.duration_since(peer.last_active) > idle_timeout)
+            .map(|(peer_id, _)| *peer_id)
+            .collect()
+    }
+}
+
+/// Methods on SwarmContext for managing the connections
+impl SwarmContext {
+    /// Registers a new connection and closes a connection if the limit is exceeded
+    pub(crate) fn manage_new_connection(
+        &mut self,
+        peer_id: PeerId,
+        connection_id: ConnectionId,
+        direction: Direction,
+    ) {
+        self.connections
+            .add_connection(peer_id, connection_id, direction);
+
+        if !self.connections.is_over_limit(direction) {
+            return;
+        }
+
+        let routing_peers = self.routing_table_peers();
+        let (prune_peer, prune_connection) = self
+            .connections
+            .prune_candidate(direction, &routing_peers)
+            .unwrap_or((peer_id, connection_id));
+
+        debug!(
+            node = self.node_snapshot.name,
+            peer_id = prune_peer.to_base58(),
+            direction = format!("{direction:?}"),
+            "Connection limit exceeded, closing connection"
+        );
+        self.swarm.close_connection(prune_connection);
+    }
+
+    /// Closes the connections to the peers that were idle for too long
+    pub(crate) fn prune_idle_connections(&mut self) {
+        for peer_id in self.connections.idle_peers(Instant::now()) {
+            debug!(
+                node = self.node_snapshot.name,
+                peer_id = peer_id.to_base58(),
+                "Closing idle connection"
+            );
+            let _ = self.swarm.disconnect_peer_id(peer_id);
+        }
+    }
+
+    fn routing_table_peers(&mut self) -> HashSet<PeerId> {
+        self.swarm
+            .behaviour_mut()
+            .kademlia
+            .kbuckets()
+            .flat_map(|b| b.iter().map(|e| *e.node.key.preimage()).collect::<Vec<_>>())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits(max: usize) -> ConnectionLimits {
+        ConnectionLimits {
+            max_inbound: max,
+            max_outbound: max,
+            idle_timeout_secs: 60,
+        }
+    }
+
+    #[test]
+    fn prune_prefers_peers_outside_routing_table_test() {
+        let bootstrap = PeerId::random();
+        let routed = PeerId::random();
+        let other = PeerId::random();
+        let mut manager = ConnectionManager::new(limits(2), vec![bootstrap]);
+
+        manager.add_connection(
+            bootstrap,
+            ConnectionId::new_unchecked(0),
+            Direction::Inbound,
+        );
+        manager.add_connection(other, ConnectionId::new_unchecked(1), Direction::Inbound);
+        manager.add_connection(routed, ConnectionId::new_unchecked(2), Direction::Inbound);
+        assert!(manager.is_over_limit(Direction::Inbound));
+        assert!(!manager.is_over_limit(Direction::Outbound));
+
+        let routing_peers = HashSet::from([routed]);
+        let candidate = manager.prune_candidate(Direction::Inbound, &routing_peers);
+        assert_eq!(candidate, Some((other, ConnectionId::new_unchecked(1))));
+
+        manager.remove_connection(&other, &ConnectionId::new_unchecked(1));
+        let candidate = manager.prune_candidate(Direction::Inbound, &routing_peers);
+        assert_eq!(candidate, Some((routed, ConnectionId::new_unchecked(2))));
+    }
+
+    #[test]
+    fn idle_peers_test() {
+        let bootstrap = PeerId::random();
+        let other = PeerId::random();
+        let mut manager = ConnectionManager::new(limits(8), vec![bootstrap]);
+
+        manager.add_connection(
+            bootstrap,
+            ConnectionId::new_unchecked(0),
+            Direction::Outbound,
+        );
+        manager.add_connection(other, ConnectionId::new_unchecked(1), Direction::Outbound);
+
+        assert!(manager.idle_peers(Instant::now()).is_empty());
+        let later = Instant::now() + Duration::from_secs(61);
+        assert_eq!(manager.idle_peers(later), vec![other]);
+    }
+}
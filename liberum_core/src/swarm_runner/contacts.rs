@@ -0,0 +1,69 @@
+use std::str::FromStr;
+use std::time::SystemTime;
+
+use kameo::request::MessageSend;
+use libp2p::{Multiaddr, PeerId};
+use tracing::{debug, warn};
+
+use crate::swarm_runner::SwarmContext;
+use crate::vault::{LoadContacts, TouchContact};
+
+///! The module keeps the contacts of the node up to date. The contacts are stored in
+///! the vault, the swarm dials them at start and updates the last seen time and the
+///! address of a contact whenever a connection with it is established.
+
+/// Methods on SwarmContext for the contacts
+impl SwarmContext {
+    /// Dials the contacts with a known address, so they are reachable without
+    /// finding them in the DHT first
+    pub(crate) async fn dial_contacts(&mut self) {
+        let contacts = match self.vault_ref.ask(LoadContacts).send().await {
+            Ok(contacts) => contacts,
+            Err(e) => {
+                warn!(err = e.to_string(), "Could not load the contacts");
+                return;
+            }
+        };
+
+        for contact in contacts {
+            let Some(address) = contact.address else {
+                continue;
+            };
+            let (Ok(peer_id), Ok(address)) = (
+                PeerId::from_str(&contact.peer_id),
+                Multiaddr::from_str(&address),
+            ) else {
+                continue;
+            };
+            if !self.is_peer_allowed(&peer_id) || self.reputation.is_banned(&peer_id) {
+                continue;
+            }
+
+            if let Err(e) = self.dial_peer(peer_id, vec![address]) {
+                debug!(
+                    node = self.node_snapshot.name,
+                    peer_id = contact.peer_id,
+                    err = e.to_string(),
+                    "Could not dial contact"
+                );
+            }
+        }
+    }
+
+    /// Records that the peer was seen now. The address is only known to be reachable
+    /// if this node dialed it
+    pub(crate) async fn touch_contact(&mut self, peer_id: PeerId, address: Option<Multiaddr>) {
+        let message = TouchContact {
+            peer_id: peer_id.to_base58(),
+            address: address.map(|a| a.to_string()),
+            seen_at: SystemTime::now(),
+        };
+        if let Err(e) = self.vault_ref.ask(message).send().await {
+            warn!(
+                node = self.node_snapshot.name,
+                err = e.to_string(),
+                "Failed to update contact"
+            );
+        }
+    }
+}
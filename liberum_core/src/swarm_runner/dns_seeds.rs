@@ -0,0 +1,143 @@
+use std::str::FromStr;
+
+use anyhow::{anyhow, Result};
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use hickory_resolver::TokioAsyncResolver;
+use liberum_core::node_config::BootstrapNode;
+use libp2p::multiaddr::Protocol;
+use libp2p::Multiaddr;
+use tracing::{debug, warn};
+
+///! The module resolves the DNS seeds from the node config to bootstrap nodes. A seed
+///! is a hostname with dnsaddr TXT records, e.g. `_dnsaddr.bootstrap.example.org` with
+///! `dnsaddr=/ip4/1.2.3.4/udp/1234/quic-v1/p2p/<peer id>`. The records may point to
+///! other dnsaddr hostnames, e.g. `dnsaddr=/dnsaddr/eu.bootstrap.example.org`.
+
+/// How many dnsaddr hostnames may point to each other before the resolving stops
+const DNSADDR_MAX_DEPTH: usize = 4;
+const DNSADDR_PREFIX: &str = "dnsaddr=";
+
+/// Resolves the seeds to the bootstrap nodes. A seed which can't be resolved is
+/// skipped, so a DNS failure does not prevent the node from starting
+pub async fn resolve_dns_seeds(seeds: &[String]) -> Vec<BootstrapNode> {
+    if seeds.is_empty() {
+        return Vec::new();
+    }
+
+    let resolver = match TokioAsyncResolver::tokio_from_system_conf() {
+        Ok(resolver) => resolver,
+        Err(e) => {
+            warn!(err = e.to_string(), "Could not create the DNS resolver");
+            return Vec::new();
+        }
+    };
+
+    let mut nodes = Vec::new();
+    for seed in seeds {
+        match resolve_dnsaddr(&resolver, seed.clone(), DNSADDR_MAX_DEPTH).await {
+            Ok(seed_nodes) => {
+                debug!(seed = seed, nodes = seed_nodes.len(), "DNS seed resolved");
+                for node in seed_nodes {
+                    if !nodes.contains(&node) {
+                        nodes.push(node);
+                    }
+                }
+            }
+            Err(e) => warn!(
+                seed = seed,
+                err = e.to_string(),
+                "Could not resolve DNS seed"
+            ),
+        }
+    }
+
+    nodes
+}
+
+fn resolve_dnsaddr(
+    resolver: &TokioAsyncResolver,
+    hostname: String,
+    depth: usize,
+) -> BoxFuture<'_, Result<Vec<BootstrapNode>>> {
+    async move {
+        if depth == 0 {
+            return Err(anyhow!("Too many nested dnsaddr records at {hostname}"));
+        }
+
+        let records = resolver.txt_lookup(format!("_dnsaddr.{hostname}")).await?;
+        let mut nodes = Vec::new();
+        for record in records.iter() {
+            let text = record
+                .iter()
+                .map(|data| String::from_utf8_lossy(data))
+                .collect::<String>();
+            let Some(addr) = parse_dnsaddr_record(&text) else {
+                continue;
+            };
+
+            match addr.iter().next() {
+                Some(Protocol::Dnsaddr(nested)) => {
+                    let nested = resolve_dnsaddr(resolver, nested.to_string(), depth - 1).await;
+                    match nested {
+                        Ok(nested) => nodes.extend(nested),
+                        Err(e) => warn!(
+                            addr = addr.to_string(),
+                            err = e.to_string(),
+                            "Could not resolve nested dnsaddr"
+                        ),
+                    }
+                }
+                _ => match to_bootstrap_node(addr.clone()) {
+                    Some(node) => nodes.push(node),
+                    None => warn!(addr = addr.to_string(), "dnsaddr record without a peer ID"),
+                },
+            }
+        }
+
+        Ok(nodes)
+    }
+    .boxed()
+}
+
+/// Parses the address from the TXT record, other records are ignored
+fn parse_dnsaddr_record(text: &str) -> Option<Multiaddr> {
+    let addr = text.strip_prefix(DNSADDR_PREFIX)?;
+    Multiaddr::from_str(addr)
+        .inspect_err(|e| warn!(record = text, err = e.to_string(), "Invalid dnsaddr record"))
+        .ok()
+}
+
+/// Splits the address ending with `/p2p/<peer id>` to the bootstrap node
+fn to_bootstrap_node(mut addr: Multiaddr) -> Option<BootstrapNode> {
+    match addr.pop()? {
+        Protocol::P2p(peer_id) => Some(BootstrapNode::new(peer_id, addr)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libp2p::PeerId;
+
+    use super::*;
+
+    #[test]
+    fn dnsaddr_record_test() {
+        let peer_id = PeerId::random();
+        let record = format!("dnsaddr=/ip4/1.2.3.4/udp/1234/quic-v1/p2p/{peer_id}");
+
+        let addr = parse_dnsaddr_record(&record).unwrap();
+        let node = to_bootstrap_node(addr).unwrap();
+        assert_eq!(node.id, peer_id);
+        assert_eq!(
+            node.addr,
+            Multiaddr::from_str("/ip4/1.2.3.4/udp/1234/quic-v1").unwrap()
+        );
+
+        assert!(parse_dnsaddr_record("v=spf1 -all").is_none());
+        assert!(parse_dnsaddr_record("dnsaddr=not an address").is_none());
+        let without_peer = parse_dnsaddr_record("dnsaddr=/ip4/1.2.3.4/udp/1234/quic-v1").unwrap();
+        assert!(to_bootstrap_node(without_peer).is_none());
+    }
+}
@@ -0,0 +1,234 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+
+use futures::{AsyncRead, AsyncWrite};
+use liberum_core::node_config::LinkCondition;
+use liberum_core::types::LinkImpairmentStats;
+use libp2p::core::muxing::{StreamMuxer, StreamMuxerBox, StreamMuxerEvent, SubstreamBox};
+use libp2p::core::transport::Boxed;
+use libp2p::{PeerId, Transport};
+use rand::Rng;
+use tokio::time::Sleep;
+
+///! The module injects the link conditions from the node config into the connections
+///! of the swarm, so the test scenarios can check the network with slow and lossy
+///! links. The transport of the swarm is wrapped only in the test runner, the daemon
+///! never delays nor drops anything.
+///!
+///! The conditions are applied per stream of a connection. The first message of a
+///! stream is delayed by the latency, a dropped stream swallows what is written to
+///! it and never receives anything, so the request on it times out. Only the
+///! outbound streams are dropped, so a condition set on both ends of a link loses
+///! the requests in both directions with the same rate.
+
+static TEST_MODE: AtomicBool = AtomicBool::new(false);
+
+/// Makes the swarms started later in this process apply the link conditions from
+/// their configs
+pub fn enable_test_mode() {
+    TEST_MODE.store(true, Ordering::Relaxed);
+}
+
+pub(crate) fn is_test_mode() -> bool {
+    TEST_MODE.load(Ordering::Relaxed)
+}
+
+/// The link conditions of a node, shared by its swarm and all of its connections
+#[derive(Clone, Default)]
+pub(crate) struct Impairments {
+    conditions: Arc<RwLock<HashMap<PeerId, LinkCondition>>>,
+    counters: Arc<Counters>,
+}
+
+#[derive(Default)]
+struct Counters {
+    delayed_streams: AtomicU64,
+    dropped_streams: AtomicU64,
+    injected_delay_nanos: AtomicU64,
+}
+
+impl Impairments {
+    pub(crate) fn new(conditions: &[LinkCondition]) -> Self {
+        let impairments = Impairments::default();
+        impairments.set(conditions);
+        impairments
+    }
+
+    /// Replaces the conditions. They apply to the streams opened from now on, also
+    /// on the connections already established
+    pub(crate) fn set(&self, conditions: &[LinkCondition]) {
+        let conditions = conditions.iter().map(|c| (c.peer_id, c.clone())).collect();
+        *self.conditions.write().expect("Not to be poisoned") = conditions;
+    }
+
+    pub(crate) fn stats(&self) -> LinkImpairmentStats {
+        LinkImpairmentStats {
+            delayed_streams: self.counters.delayed_streams.load(Ordering::Relaxed),
+            dropped_streams: self.counters.dropped_streams.load(Ordering::Relaxed),
+            injected_delay: Duration::from_nanos(
+                self.counters.injected_delay_nanos.load(Ordering::Relaxed),
+            ),
+        }
+    }
+
+    /// Wraps the connections of the transport, so the conditions apply to them
+    pub(crate) fn wrap<T, M>(&self, transport: T) -> Boxed<(PeerId, StreamMuxerBox)>
+    where
+        T: Transport<Output = (PeerId, M)> + Send + Unpin + 'static,
+        T::Error: Send + Sync + 'static,
+        T::Dial: Send + 'static,
+        T::ListenerUpgrade: Send + 'static,
+        M: StreamMuxer + Send + 'static,
+        M::Substream: Send + 'static,
+        M::Error: Send + Sync + 'static,
+    {
+        let impairments = self.clone();
+        transport
+            .map(move |(peer_id, muxer), _| {
+                let muxer = ImpairedMuxer {
+                    inner: StreamMuxerBox::new(muxer),
+                    peer_id,
+                    impairments: impairments.clone(),
+                };
+                (peer_id, StreamMuxerBox::new(muxer))
+            })
+            .boxed()
+    }
+
+    /// Decides what happens to a new stream to the peer
+    fn impair(&self, peer_id: &PeerId, inner: SubstreamBox, outbound: bool) -> ImpairedStream {
+        let condition = self
+            .conditions
+            .read()
+            .expect("Not to be poisoned")
+            .get(peer_id)
+            .cloned();
+        let mut stream = ImpairedStream {
+            inner,
+            delay: None,
+            dropped: false,
+        };
+        let Some(condition) = condition else {
+            return stream;
+        };
+
+        let mut rng = rand::thread_rng();
+        if outbound && condition.drop_rate > 0.0 && rng.gen_bool(condition.drop_rate.min(1.0)) {
+            self.counters
+                .dropped_streams
+                .fetch_add(1, Ordering::Relaxed);
+            stream.dropped = true;
+            return stream;
+        }
+
+        let jitter = condition.jitter_millis as i64;
+        let millis = condition.latency_millis as i64 + rng.gen_range(-jitter..=jitter);
+        let delay = Duration::from_millis(millis.max(0) as u64);
+        if !delay.is_zero() {
+            self.counters
+                .delayed_streams
+                .fetch_add(1, Ordering::Relaxed);
+            self.counters
+                .injected_delay_nanos
+                .fetch_add(delay.as_nanos() as u64, Ordering::Relaxed);
+            stream.delay = Some(Box::pin(tokio::time::sleep(delay)));
+        }
+        stream
+    }
+}
+
+struct ImpairedMuxer {
+    inner: StreamMuxerBox,
+    peer_id: PeerId,
+    impairments: Impairments,
+}
+
+impl StreamMuxer for ImpairedMuxer {
+    type Substream = ImpairedStream;
+    type Error = io::Error;
+
+    fn poll_inbound(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Self::Substream, Self::Error>> {
+        let inner = ready!(self.inner.poll_inbound_unpin(cx))?;
+        Poll::Ready(Ok(self.impairments.impair(&self.peer_id, inner, false)))
+    }
+
+    fn poll_outbound(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Self::Substream, Self::Error>> {
+        let inner = ready!(self.inner.poll_outbound_unpin(cx))?;
+        Poll::Ready(Ok(self.impairments.impair(&self.peer_id, inner, true)))
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_close_unpin(cx)
+    }
+
+    fn poll(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<StreamMuxerEvent, Self::Error>> {
+        self.inner.poll_unpin(cx)
+    }
+}
+
+struct ImpairedStream {
+    inner: SubstreamBox,
+    /// Waited for before the first write
+    delay: Option<Pin<Box<Sleep>>>,
+    dropped: bool,
+}
+
+impl AsyncRead for ImpairedStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        if self.dropped {
+            // Nothing ever arrives, the protocol gives up on its own timeout
+            return Poll::Pending;
+        }
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for ImpairedStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        if self.dropped {
+            return Poll::Ready(Ok(buf.len()));
+        }
+        if let Some(delay) = &mut self.delay {
+            ready!(delay.as_mut().poll(cx));
+            self.delay = None;
+        }
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if self.dropped {
+            return Poll::Ready(Ok(()));
+        }
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if self.dropped {
+            return Poll::Ready(Ok(()));
+        }
+        Pin::new(&mut self.inner).poll_close(cx)
+    }
+}
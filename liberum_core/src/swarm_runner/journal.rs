@@ -0,0 +1,134 @@
+use liberum_core::journal::{JournalEvent, JournalWriter};
+use liberum_core::node_config::NodeConfig;
+use libp2p::kad::{self, QueryResult};
+use libp2p::swarm::SwarmEvent;
+use tracing::warn;
+
+use super::behaviour::LiberumNetoBehaviorEvent;
+use super::messages::SwarmRunnerMessage;
+use super::SwarmContext;
+
+///! The module records the events of the swarm and the messages sent to it to the
+///! journal of the node, if the config of the node sets its path. The events are
+///! recorded before they are handled.
+
+/// Opens the journal from the config of the node, if it is set. The node runs
+/// without the journal if it can't be opened or is not owned by the owner of the node
+pub(crate) fn open_journal(
+    node_name: &str,
+    config: &NodeConfig,
+    owner: Option<u32>,
+) -> Option<JournalWriter> {
+    let path = config.journal_path.as_ref()?;
+    JournalWriter::open(path, owner)
+        .inspect_err(|e| {
+            warn!(
+                node = node_name,
+                path = path.display().to_string(),
+                err = e.to_string(),
+                "Could not open the journal"
+            )
+        })
+        .ok()
+}
+
+/// Methods on SwarmContext for recording the journal
+impl SwarmContext {
+    pub(crate) fn record_message(&mut self, message: &SwarmRunnerMessage) {
+        let kind: &'static str = message.into();
+        self.record(|| JournalEvent::Message {
+            kind: kind.to_string(),
+        });
+    }
+
+    pub(crate) fn record_swarm_event(&mut self, event: &SwarmEvent<LiberumNetoBehaviorEvent>) {
+        self.record(|| journal_event(event));
+    }
+
+    pub(crate) fn record(&mut self, event: impl FnOnce() -> JournalEvent) {
+        let Some(journal) = &mut self.journal else {
+            return;
+        };
+
+        if let Err(e) = journal.write(event()) {
+            // Better no journal than one with gaps
+            warn!(
+                node = self.node_snapshot.name,
+                err = e.to_string(),
+                "Could not write to the journal, recording stopped"
+            );
+            self.journal = None;
+        }
+    }
+}
+
+fn journal_event(event: &SwarmEvent<LiberumNetoBehaviorEvent>) -> JournalEvent {
+    match event {
+        SwarmEvent::ConnectionEstablished {
+            peer_id,
+            connection_id,
+            endpoint,
+            ..
+        } => JournalEvent::ConnectionEstablished {
+            connection_id: format!("{connection_id:?}"),
+            peer_id: peer_id.to_base58(),
+            address: endpoint.get_remote_address().to_string(),
+            outbound: endpoint.is_dialer(),
+        },
+        SwarmEvent::ConnectionClosed {
+            peer_id,
+            connection_id,
+            cause,
+            ..
+        } => JournalEvent::ConnectionClosed {
+            connection_id: format!("{connection_id:?}"),
+            peer_id: peer_id.to_base58(),
+            cause: cause.as_ref().map(|c| c.to_string()),
+        },
+        SwarmEvent::OutgoingConnectionError { peer_id, error, .. } => {
+            JournalEvent::OutgoingConnectionError {
+                peer_id: peer_id.map(|p| p.to_base58()),
+                error: error.to_string(),
+            }
+        }
+        SwarmEvent::Behaviour(LiberumNetoBehaviorEvent::Kademlia(
+            kad::Event::OutboundQueryProgressed {
+                id,
+                result,
+                stats,
+                step,
+            },
+        )) => {
+            let (kind, error) = query_result(result);
+            JournalEvent::QueryProgressed {
+                id: format!("{id}"),
+                kind: kind.to_string(),
+                step: step.count.get(),
+                last: step.last,
+                error,
+                elapsed: stats.duration(),
+            }
+        }
+        event => JournalEvent::Other {
+            description: format!("{event:?}"),
+        },
+    }
+}
+
+/// The kind of the query and the error of the step, if it failed
+fn query_result(result: &QueryResult) -> (&'static str, Option<String>) {
+    fn error<T, E: ToString>(result: &Result<T, E>) -> Option<String> {
+        result.as_ref().err().map(|e| e.to_string())
+    }
+
+    match result {
+        QueryResult::Bootstrap(r) => ("Bootstrap", error(r)),
+        QueryResult::GetClosestPeers(r) => ("GetClosestPeers", error(r)),
+        QueryResult::GetProviders(r) => ("GetProviders", error(r)),
+        QueryResult::StartProviding(r) => ("StartProviding", error(r)),
+        QueryResult::RepublishProvider(r) => ("RepublishProvider", error(r)),
+        QueryResult::GetRecord(r) => ("GetRecord", error(r)),
+        QueryResult::PutRecord(r) => ("PutRecord", error(r)),
+        QueryResult::RepublishRecord(r) => ("RepublishRecord", error(r)),
+    }
+}
@@ -0,0 +1,217 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::SystemTime;
+
+use kameo::request::MessageSend;
+use libp2p::{Multiaddr, PeerId};
+use tracing::warn;
+
+use crate::swarm_runner::SwarmContext;
+use crate::vault::{DeleteKnownPeer, KnownPeer, StoreKnownPeer};
+
+///! The module keeps the cache of the peers the node connected to, so a restarted
+///! node gets back to the network without its bootstrap nodes. The addresses the node
+///! dialed successfully are kept in the vault and added to the routing table when the
+///! swarm starts. The cache is bounded, the peers not reached for the longest time are
+///! dropped first, and so are the peers which failed to be dialed too many times in a
+///! row.
+
+/// The most peers kept in the cache
+const MAX_KNOWN_PEERS: usize = 256;
+/// The most addresses kept for a peer
+const MAX_PEER_ADDRESSES: usize = 4;
+/// The failed dials in a row after which the peer is dropped
+const MAX_FAILURES: u32 = 3;
+
+/// A change of the cache to be written to the vault
+#[derive(Debug, Clone, PartialEq)]
+pub enum KnownPeerUpdate {
+    Store(KnownPeer),
+    Delete(String),
+}
+
+/// The peers known to the swarm from the successful dials
+pub struct KnownPeers {
+    peers: HashMap<PeerId, KnownPeer>,
+}
+
+impl KnownPeers {
+    /// Creates the cache from the peers stored in the vault. Entries with invalid
+    /// peer IDs are skipped
+    pub fn from_stored(peers: Vec<KnownPeer>) -> Self {
+        let peers = peers
+            .into_iter()
+            .filter_map(|p| Some((PeerId::from_str(&p.peer_id).ok()?, p)))
+            .collect();
+
+        KnownPeers { peers }
+    }
+
+    /// The peers with their valid addresses
+    pub fn addresses(&self) -> Vec<(PeerId, Vec<Multiaddr>)> {
+        self.peers
+            .iter()
+            .map(|(peer_id, peer)| {
+                let addresses = peer
+                    .addresses
+                    .iter()
+                    .filter_map(|addr| Multiaddr::from_str(addr).ok())
+                    .collect();
+                (*peer_id, addresses)
+            })
+            .collect()
+    }
+
+    /// Records the peer dialed at the address. The peer reached the longest time ago
+    /// is dropped if the cache is full
+    pub fn succeeded(
+        &mut self,
+        peer_id: PeerId,
+        addr: &Multiaddr,
+        now: SystemTime,
+    ) -> Vec<KnownPeerUpdate> {
+        let addr = addr.to_string();
+        let peer = self.peers.entry(peer_id).or_insert_with(|| KnownPeer {
+            peer_id: peer_id.to_base58(),
+            addresses: Vec::new(),
+            last_success: now,
+            failures: 0,
+        });
+        peer.addresses.retain(|a| *a != addr);
+        peer.addresses.insert(0, addr);
+        peer.addresses.truncate(MAX_PEER_ADDRESSES);
+        peer.last_success = now;
+        peer.failures = 0;
+
+        let mut updates = vec![KnownPeerUpdate::Store(peer.clone())];
+        if self.peers.len() > MAX_KNOWN_PEERS {
+            let oldest = self
+                .peers
+                .iter()
+                .filter(|(id, _)| **id != peer_id)
+                .min_by_key(|(_, peer)| peer.last_success)
+                .map(|(id, _)| *id);
+            if let Some(oldest) = oldest {
+                self.peers.remove(&oldest);
+                updates.push(KnownPeerUpdate::Delete(oldest.to_base58()));
+            }
+        }
+        updates
+    }
+
+    /// Records the failed dial of the peer, None if it's not known
+    pub fn failed(&mut self, peer_id: &PeerId) -> Option<KnownPeerUpdate> {
+        let peer = self.peers.get_mut(peer_id)?;
+        peer.failures += 1;
+        if peer.failures < MAX_FAILURES {
+            return Some(KnownPeerUpdate::Store(peer.clone()));
+        }
+
+        self.peers.remove(peer_id);
+        Some(KnownPeerUpdate::Delete(peer_id.to_base58()))
+    }
+}
+
+/// Methods on SwarmContext for the known peers
+impl SwarmContext {
+    /// Adds the addresses of the known peers to the routing table, so the swarm can
+    /// bootstrap from them
+    pub(crate) fn add_known_peers(&mut self) {
+        for (peer_id, addresses) in self.known_peers.addresses() {
+            if !self.is_peer_allowed(&peer_id) || self.reputation.is_banned(&peer_id) {
+                continue;
+            }
+            for addr in addresses {
+                self.add_peer_address(&peer_id, addr);
+            }
+        }
+    }
+
+    /// Records the peer the node dialed at the address
+    pub(crate) async fn known_peer_reached(&mut self, peer_id: PeerId, addr: &Multiaddr) {
+        let updates = self.known_peers.succeeded(peer_id, addr, SystemTime::now());
+        self.update_known_peers(updates).await;
+    }
+
+    /// Records the failed dial of the peer
+    pub(crate) async fn known_peer_failed(&mut self, peer_id: &PeerId) {
+        let updates = self.known_peers.failed(peer_id).into_iter().collect();
+        self.update_known_peers(updates).await;
+    }
+
+    async fn update_known_peers(&self, updates: Vec<KnownPeerUpdate>) {
+        for update in updates {
+            let result = match update {
+                KnownPeerUpdate::Store(peer) => {
+                    self.vault_ref.ask(StoreKnownPeer { peer }).send().await
+                }
+                KnownPeerUpdate::Delete(peer_id) => {
+                    self.vault_ref.ask(DeleteKnownPeer { peer_id }).send().await
+                }
+            };
+            if let Err(e) = result {
+                warn!(
+                    node = self.node_snapshot.name,
+                    err = e.to_string(),
+                    "Failed to update known peer"
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn known_peers_test() {
+        let mut known = KnownPeers::from_stored(vec![KnownPeer {
+            peer_id: "invalid".to_string(),
+            addresses: vec![],
+            last_success: SystemTime::UNIX_EPOCH,
+            failures: 0,
+        }]);
+        assert!(known.addresses().is_empty());
+
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1000);
+        let first = PeerId::random();
+        let quic: Multiaddr = "/ip4/1.2.3.4/udp/1/quic-v1".parse().unwrap();
+        let tcp: Multiaddr = "/ip4/1.2.3.4/tcp/1".parse().unwrap();
+        known.succeeded(first, &quic, start);
+        let updates = known.succeeded(first, &tcp, start);
+        assert_eq!(
+            updates,
+            vec![KnownPeerUpdate::Store(KnownPeer {
+                peer_id: first.to_base58(),
+                addresses: vec![tcp.to_string(), quic.to_string()],
+                last_success: start,
+                failures: 0,
+            })]
+        );
+
+        // The peer reached the longest time ago is dropped from the full cache
+        for i in 1..MAX_KNOWN_PEERS as u64 {
+            let later = start + Duration::from_secs(i);
+            assert_eq!(known.succeeded(PeerId::random(), &quic, later).len(), 1);
+        }
+        let updates = known.succeeded(PeerId::random(), &quic, start + Duration::from_secs(1000));
+        assert_eq!(updates[1], KnownPeerUpdate::Delete(first.to_base58()));
+        assert_eq!(known.addresses().len(), MAX_KNOWN_PEERS);
+
+        let (failing, _) = known.addresses()[0].clone();
+        for _ in 1..MAX_FAILURES {
+            assert!(matches!(
+                known.failed(&failing),
+                Some(KnownPeerUpdate::Store(_))
+            ));
+        }
+        assert_eq!(
+            known.failed(&failing),
+            Some(KnownPeerUpdate::Delete(failing.to_base58()))
+        );
+        assert_eq!(known.failed(&failing), None);
+    }
+}
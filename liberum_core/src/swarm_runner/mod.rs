@@ -1,31 +1,78 @@
+pub mod addresses;
+pub mod announcer;
 pub mod behaviour;
+pub mod commands;
+pub mod config_reload;
+pub mod connection_manager;
+pub mod contacts;
+pub mod dns_seeds;
+pub mod impairment;
+pub mod journal;
+pub mod known_peers;
 pub mod messages;
+pub mod record_validator;
+pub mod reputation;
+pub mod upload_quota;
+pub mod webrtc;
 
+use crate::node::events::{self, SharedEventLog};
+use crate::node::module_host::ModuleHost;
 use crate::node::NodeSnapshot;
 use crate::node::{self, Node};
-use crate::vault::Vault;
+use crate::vault::{LoadKnownPeers, LoadPeerScores, Vault};
+use announcer::Announcer;
 use anyhow::anyhow;
 use anyhow::Result;
+use behaviour::ping::PeerLatencies;
+use behaviour::wire::wire_version;
 use behaviour::*;
-use futures::StreamExt;
+use connection_manager::{ConnectionManager, Direction};
+use futures::{AsyncRead, AsyncWrite, StreamExt};
+use impairment::Impairments;
 use kameo::actor::ActorRef;
 use kameo::request::MessageSend;
-use liberum_core::node_config::BootstrapNode;
+use known_peers::KnownPeers;
+use liberum_core::journal::{JournalEvent, JournalWriter};
+use liberum_core::node_config::{BootstrapNode, TransportKind};
+use liberum_core::proto;
+use liberum_core::types::{BucketInfo, NodeEventKind, NodeStatus, PeerInfo};
+use libp2p::core::muxing::StreamMuxerBox;
+use libp2p::core::transport::{Boxed, ListenerId, MemoryTransport};
+use libp2p::core::upgrade;
+use libp2p::pnet::{PnetConfig, PreSharedKey};
 use libp2p::request_response::ProtocolSupport;
-use libp2p::{identity, kad, Multiaddr, StreamProtocol, SwarmBuilder};
+use libp2p::swarm::DialError;
+use libp2p::{connection_limits, gossipsub, identity, kad, noise, ping, quic, tcp, yamux};
 use libp2p::{kad::store::MemoryStore, request_response, swarm::SwarmEvent, Swarm};
+use libp2p::{Multiaddr, PeerId, StreamProtocol};
+use libp2p::{SwarmBuilder, Transport};
 use messages::*;
+use record_validator::RecordValidator;
+use reputation::PeerReputation;
+use std::collections::HashMap;
 use std::str::FromStr;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
 use tracing::warn;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, Instrument};
+use upload_quota::UploadQuotas;
 const KAD_PROTO_NAME: StreamProtocol = StreamProtocol::new("/liberum/kad/1.0.0");
 //const FILE_SHARE_PROTO_NAME: StreamProtocol = StreamProtocol::new("/liberum/file-share/1.0.0");
-const OBJECT_SENDER_PROTO_NAME: StreamProtocol =
-    StreamProtocol::new("/liberum/object-sender/1.0.0");
+const MESSAGE_PROTO_NAME: StreamProtocol = StreamProtocol::new("/liberum/message/1.0.0");
+const MAILBOX_PROTO_NAME: StreamProtocol = StreamProtocol::new("/liberum/mailbox/1.0.0");
+const PEER_EXCHANGE_PROTO_NAME: StreamProtocol = StreamProtocol::new("/liberum/pex/1.0.0");
 const DEFAULT_MULTIADDR_STR_IP6: &str = "/ip6/::/udp/0/quic-v1";
 const DEFAULT_MULTIADDR_STR_IP4: &str = "/ip4/0.0.0.0/udp/0/quic-v1";
+/// Of the private networks, QUIC can't be used with a pre-shared key
+const DEFAULT_MULTIADDR_STR_TCP_IP6: &str = "/ip6/::/tcp/0";
+const DEFAULT_MULTIADDR_STR_TCP_IP4: &str = "/ip4/0.0.0.0/tcp/0";
+/// Any free port of the in-process transport
+const DEFAULT_MULTIADDR_STR_MEMORY: &str = "/memory/0";
+/// How often the expired provider records received from other peers are removed
+const PROVIDER_EXPIRY_INTERVAL: Duration = Duration::from_secs(60);
+/// Idle connections are closed by the connection manager, the swarm itself
+/// should keep them open as long as possible
+const SWARM_IDLE_CONNECTION_TIMEOUT: Duration = Duration::from_secs(24 * 60 * 60);
 
 ///! Swarm Runner
 ///! This module is responsible for running the libp2p swarm and providing an interface
@@ -42,15 +89,70 @@ struct SwarmContext {
     vault_ref: ActorRef<Vault>,
     node_snapshot: NodeSnapshot,
     behaviour: BehaviourContext,
+    stats: SwarmStats,
+    reputation: PeerReputation,
+    connections: ConnectionManager,
+    latencies: PeerLatencies,
+    events: SharedEventLog,
+    /// The external modules handling the object types unknown to the daemon
+    module_host: ModuleHost,
+    /// Sends the answers of the module calls back to the swarm loop
+    module_answers: mpsc::UnboundedSender<object_sender::ModuleAnswer>,
+    /// Listeners of the addresses from the config, removed when the config is reloaded
+    listeners: HashMap<Multiaddr, ListenerId>,
+    /// Used when the config has no external addresses
+    default_listen_addresses: Vec<Multiaddr>,
+    /// Of the additional transports, listened on besides the other addresses
+    transport_listen_addresses: Vec<Multiaddr>,
+    /// Resolved from the DNS seeds of the config when the swarm started
+    dns_bootstrap_nodes: Vec<BootstrapNode>,
+    /// Records the events and the messages if the config sets the journal path
+    journal: Option<JournalWriter>,
+    /// The link conditions injected into the connections, only by the test runner
+    impairments: Impairments,
+    transfers: transfer::Transfers,
+    upload_quotas: UploadQuotas,
+    /// Rate limits the provider announcements and republishes the provider records
+    announcer: Announcer,
+    /// The peers dialed before, added to the routing table when the swarm starts
+    known_peers: KnownPeers,
+    /// Checks the records other peers put in the store
+    record_validator: RecordValidator,
+}
+
+/// Counters collected while the swarm is running, reported to the node on `GetStatus`.
+/// Bytes are counted as the sizes of object payloads exchanged using the object_sender
+/// protocol, the transport overhead is not included
+struct SwarmStats {
+    started_at: Instant,
+    bytes_sent: u64,
+    bytes_received: u64,
+    objects_served: u64,
+}
+
+impl SwarmStats {
+    fn new() -> Self {
+        SwarmStats {
+            started_at: Instant::now(),
+            bytes_sent: 0,
+            bytes_received: 0,
+            objects_served: 0,
+        }
+    }
 }
 
 /// Prepares the sender to send messages to the swarm
 pub async fn run_swarm(
     node_ref: ActorRef<Node>,
     vault_ref: ActorRef<Vault>,
+    events: SharedEventLog,
+    module_host: ModuleHost,
 ) -> mpsc::Sender<SwarmRunnerMessage> {
     let (sender, receiver) = mpsc::channel::<SwarmRunnerMessage>(16);
-    tokio::spawn(run_swarm_task(node_ref, vault_ref, receiver));
+    // The swarm logs in the span of its node
+    tokio::spawn(
+        run_swarm_task(node_ref, vault_ref, events, module_host, receiver).in_current_span(),
+    );
     sender
 }
 
@@ -58,11 +160,19 @@ pub async fn run_swarm(
 async fn run_swarm_task(
     node_ref: ActorRef<Node>,
     vault_ref: ActorRef<Vault>,
+    events: SharedEventLog,
+    module_host: ModuleHost,
     receiver: mpsc::Receiver<SwarmRunnerMessage>,
 ) {
-    if let Err(e) = run_swarm_main(node_ref.clone(), vault_ref, receiver).await {
+    if let Err(e) = run_swarm_main(node_ref.clone(), vault_ref, events, module_host, receiver).await
+    {
         error!(err = format!("{e:?}"), "Swarm run error");
-        node_ref.ask(node::SwarmDied).send().await.unwrap();
+        let reason = format!("{e:#}");
+        node_ref
+            .ask(node::SwarmDied { reason })
+            .send()
+            .await
+            .unwrap();
     }
 }
 
@@ -70,6 +180,8 @@ async fn run_swarm_task(
 async fn run_swarm_main(
     node_ref: ActorRef<Node>,
     vault_ref: ActorRef<Vault>,
+    events: SharedEventLog,
+    module_host: ModuleHost,
     mut receiver: mpsc::Receiver<SwarmRunnerMessage>,
 ) -> Result<()> {
     // It must be guaranteed not to ever fail. Swarm can't start without this data.
@@ -85,40 +197,70 @@ async fn run_swarm_main(
     // Create a new swarm using the node data
     let keypair = node_snapshot.keypair.clone();
     let id = identity::PeerId::from_public_key(&keypair.public());
+    let provider_ttl = Duration::from_secs(node_snapshot.config.provider_ttl_secs);
+    let republish_interval = Duration::from_secs(node_snapshot.config.republish_interval_secs);
+    let max_inbound = node_snapshot.config.connection_limits.max_inbound;
+    let behaviour = |key: &identity::Keypair| new_behaviour(key, provider_ttl, max_inbound);
+    let announcer = Announcer::new(
+        node_snapshot.config.max_provides_per_sec,
+        republish_interval,
+    );
+    let impairments = Impairments::new(&node_snapshot.config.link_conditions);
+    let impaired = impairment::is_test_mode().then_some(&impairments);
+    if impaired.is_none() && !node_snapshot.config.link_conditions.is_empty() {
+        warn!("The link conditions of the config are applied only in the tests");
+    }
+    let transport_kind = node_snapshot.config.transport;
+    let network_key = node_snapshot.config.network_key()?.map(PreSharedKey::new);
+    // The browsers can't connect with the pre-shared key
+    let webrtc_certificate = match network_key {
+        Some(_) if !node_snapshot.config.transports.is_empty() => {
+            warn!("The additional transports are not used in a private network");
+            None
+        }
+        Some(_) => None,
+        None => node_snapshot.webrtc_certificate.as_deref(),
+    };
     let swarm = SwarmBuilder::with_existing_identity(keypair.clone())
         .with_tokio()
-        .with_quic()
-        .with_behaviour(|key| {
-            let store_conf = kad::store::MemoryStoreConfig::default();
-            let store = MemoryStore::with_config(key.public().to_peer_id(), store_conf);
-
-            let mut conf = kad::Config::new(KAD_PROTO_NAME);
-
-            conf.set_record_filtering(kad::StoreInserts::FilterBoth);
-            let kademlia = kad::Behaviour::with_config(id, store, conf);
-            let obj_sender = request_response::cbor::Behaviour::<
-                object_sender::ObjectSendRequest,
-                object_sender::ObjectResponse,
-            >::new(
-                [(OBJECT_SENDER_PROTO_NAME, ProtocolSupport::Full)],
-                request_response::Config::default().with_request_timeout(Duration::from_secs(10)),
-            );
-            LiberumNetoBehavior {
-                kademlia,
-                object_sender: obj_sender,
-            }
-        })
+        .with_other_transport(|key| {
+            new_transport(
+                key,
+                transport_kind,
+                network_key,
+                webrtc_certificate,
+                impaired,
+            )
+        })?
+        .with_behaviour(behaviour)
         .inspect_err(|e| error!(err = e.to_string(), "could not create behavior"))?
-        .with_swarm_config(|c| c.with_idle_connection_timeout(Duration::from_secs(60)))
+        .with_swarm_config(|c| c.with_idle_connection_timeout(SWARM_IDLE_CONNECTION_TIMEOUT))
         .build();
 
-    let mut context = SwarmContext {
-        _node_actor: node_ref,
-        node_snapshot,
-        vault_ref,
-        swarm: swarm,
-        behaviour: BehaviourContext::new(),
-    };
+    let peer_scores = vault_ref
+        .ask(LoadPeerScores)
+        .send()
+        .await
+        .inspect_err(|e| warn!(err = e.to_string(), "Could not load peer scores"))
+        .unwrap_or_default();
+    let known_peers = vault_ref
+        .ask(LoadKnownPeers)
+        .send()
+        .await
+        .inspect_err(|e| warn!(err = e.to_string(), "Could not load known peers"))
+        .unwrap_or_default();
+
+    let dns_bootstrap_nodes =
+        dns_seeds::resolve_dns_seeds(&node_snapshot.config.bootstrap_dns_seeds).await;
+    let connections = ConnectionManager::new(
+        node_snapshot.config.connection_limits.clone(),
+        node_snapshot
+            .config
+            .bootstrap_nodes
+            .iter()
+            .chain(&dns_bootstrap_nodes)
+            .map(|n| n.id),
+    );
 
     let swarm_default_addr_ip6 =
         Multiaddr::from_str(DEFAULT_MULTIADDR_STR_IP6).inspect_err(|e| {
@@ -137,19 +279,66 @@ async fn run_swarm_main(
             );
         })?;
 
-    let default_addr = vec![swarm_default_addr_ip6, swarm_default_addr_ip4];
+    let default_addr = match node_snapshot.config.transport {
+        TransportKind::Quic if network_key.is_some() => vec![
+            Multiaddr::from_str(DEFAULT_MULTIADDR_STR_TCP_IP6)?,
+            Multiaddr::from_str(DEFAULT_MULTIADDR_STR_TCP_IP4)?,
+        ],
+        TransportKind::Quic => vec![swarm_default_addr_ip6, swarm_default_addr_ip4],
+        TransportKind::Memory => vec![Multiaddr::from_str(DEFAULT_MULTIADDR_STR_MEMORY)?],
+    };
+    // Only the transports the swarm was built with can be listened on
+    let transport_listen_addresses = match webrtc_certificate {
+        Some(_) => webrtc::listen_addresses(&node_snapshot.config.transports),
+        None => vec![],
+    };
+    let journal = journal::open_journal(
+        &node_snapshot.name,
+        &node_snapshot.config,
+        node_snapshot.owner,
+    );
+    let upload_quotas = UploadQuotas::new(node_snapshot.config.upload_limits);
+    let (transfers, mut transfer_events) = transfer::Transfers::start(
+        &swarm.behaviour().transfer,
+        vault_ref.clone(),
+        node_snapshot.config.compression,
+        upload_quotas.clone(),
+    )?;
+    let (module_answers, mut module_answers_receiver) = mpsc::unbounded_channel();
 
-    // Add the external addresses to the swarm
-    if context.node_snapshot.config.external_addresses.is_empty() {
-        for addr in default_addr {
-            //context.swarm.add_external_address(addr.clone());
-            context.swarm.listen_on(addr.clone())?;
-        }
-    } else {
-        for addr in &context.node_snapshot.config.external_addresses {
-            //context.swarm.add_external_address(addr.clone());
-            context.swarm.listen_on(addr.clone())?;
-        }
+    let mut context = SwarmContext {
+        _node_actor: node_ref,
+        node_snapshot,
+        vault_ref,
+        swarm: swarm,
+        behaviour: BehaviourContext::new(),
+        stats: SwarmStats::new(),
+        reputation: PeerReputation::from_scores(peer_scores),
+        connections,
+        latencies: PeerLatencies::new(),
+        events,
+        module_host,
+        module_answers,
+        listeners: HashMap::new(),
+        default_listen_addresses: default_addr,
+        transport_listen_addresses,
+        dns_bootstrap_nodes,
+        journal,
+        impairments,
+        transfers,
+        upload_quotas,
+        announcer,
+        known_peers: KnownPeers::from_stored(known_peers),
+        record_validator: RecordValidator::new(),
+    };
+    context.record(|| JournalEvent::Started {
+        peer_id: id.to_base58(),
+    });
+
+    // Listen on the external addresses, or the default ones if there are none
+    for addr in context.listen_addresses(&context.node_snapshot.config) {
+        let listener_id = context.swarm.listen_on(addr.clone())?;
+        context.listeners.insert(addr, listener_id);
     }
 
     // Set mode to Server EXTREMELY IMPORTANT, otherwise the node won't
@@ -162,28 +351,34 @@ async fn run_swarm_main(
 
     debug!(node_name = context.node_snapshot.name, "Starting a swarm!");
 
-    // Bootstrap using the bootstrap nodes from the node data
-    for node in &context.node_snapshot.config.bootstrap_nodes {
-        context
-            .swarm
-            .behaviour_mut()
-            .kademlia
-            .add_address(&node.id, node.addr.clone());
-        debug!("Bootstrap node: {}", serde_json::to_string(&node)?);
-    }
+    context.add_known_peers();
     context
-        .swarm
-        .behaviour_mut()
-        .kademlia
         .bootstrap()
         .inspect_err(|e| {
             warn!(err = e.to_string(), "Could not bootstrap the swarm");
         })
         .ok();
 
+    context.dial_contacts().await;
+    context.subscribe_groups().await;
+
+    if let Some(profile) = context.node_snapshot.config.profile.clone() {
+        context
+            .publish_profile(&profile)
+            .inspect_err(|e| warn!(err = e.to_string(), "Could not publish the profile"))
+            .ok();
+    }
+    context.publish_rotations();
+
+    let mut prune_interval = tokio::time::interval(connection_manager::PRUNE_INTERVAL);
+    let mut provider_expiry_interval = tokio::time::interval(PROVIDER_EXPIRY_INTERVAL);
+    let mut pending_sweep_interval = tokio::time::interval(pending::PENDING_SWEEP_INTERVAL);
+    let mut announce_interval = tokio::time::interval(announcer::ANNOUNCE_INTERVAL);
+
     loop {
         tokio::select! {
             Some(message) = receiver.recv() => {
+                context.record_message(&message);
                 let should_end = context.handle_swarm_runner_message(message).await?;
 
                 // If the message returns true, then the swarm should end
@@ -193,13 +388,175 @@ async fn run_swarm_main(
                 }
             }
             event = context.swarm.select_next_some() => {
+                context.record_swarm_event(&event);
                 context.handle_swarm_event(event).await?;
             }
+            _ = prune_interval.tick() => {
+                context.prune_idle_connections();
+            }
+            _ = provider_expiry_interval.tick() => {
+                context.remove_expired_providers();
+            }
+            _ = pending_sweep_interval.tick() => {
+                context.fail_timed_out_requests();
+            }
+            _ = announce_interval.tick() => {
+                context.announcer.tick(Instant::now());
+                context.start_announcements();
+            }
+            Some(event) = transfer_events.recv() => {
+                context.handle_transfer_event(event).await;
+            }
+            Some(answer) = module_answers_receiver.recv() => {
+                context.handle_module_answer(answer).await;
+            }
             else => {break Err(anyhow!("Channel to Node closed"));}
         }
     }
 }
 
+/// Creates the behaviour of the swarm, the same for every transport
+fn new_behaviour(
+    key: &identity::Keypair,
+    provider_ttl: Duration,
+    max_inbound: usize,
+) -> Result<LiberumNetoBehavior, Box<dyn std::error::Error + Send + Sync>> {
+    let id = key.public().to_peer_id();
+    let store_conf = kad::store::MemoryStoreConfig::default();
+    let store = MemoryStore::with_config(key.public().to_peer_id(), store_conf);
+
+    let mut conf = kad::Config::new(KAD_PROTO_NAME);
+
+    conf.set_record_filtering(kad::StoreInserts::FilterBoth);
+    conf.set_provider_record_ttl(Some(provider_ttl));
+    // The announcer republishes the provider records, spread over the interval
+    conf.set_provider_publication_interval(None);
+    let kademlia = kad::Behaviour::with_config(id, store, conf);
+    let obj_sender = request_response::Behaviour::with_codec(
+        wire::ObjectSenderCodec,
+        wire::OBJECT_SENDER_PROTOCOLS.map(|p| (p, ProtocolSupport::Full)),
+        request_response::Config::default().with_request_timeout(Duration::from_secs(10)),
+    );
+    let messenger = request_response::cbor::Behaviour::<
+        messenger::DirectMessageRequest,
+        proto::ResultObject,
+    >::new(
+        [(MESSAGE_PROTO_NAME, ProtocolSupport::Full)],
+        request_response::Config::default().with_request_timeout(Duration::from_secs(10)),
+    );
+    let mailbox = request_response::cbor::Behaviour::<
+        mailbox::MailboxRequest,
+        mailbox::MailboxResponse,
+    >::new(
+        [(MAILBOX_PROTO_NAME, ProtocolSupport::Full)],
+        request_response::Config::default().with_request_timeout(Duration::from_secs(10)),
+    );
+    let peer_exchange = request_response::cbor::Behaviour::<
+        peer_exchange::PeerExchangeRequest,
+        peer_exchange::PeerExchangeResponse,
+    >::new(
+        [(PEER_EXCHANGE_PROTO_NAME, ProtocolSupport::Full)],
+        request_response::Config::default().with_request_timeout(Duration::from_secs(10)),
+    );
+    // Posts are forwarded only after they are verified to come from a member
+    let gossipsub_conf = gossipsub::ConfigBuilder::default()
+        .validation_mode(gossipsub::ValidationMode::Strict)
+        .validate_messages()
+        .build()?;
+    let gossipsub = gossipsub::Behaviour::new(
+        gossipsub::MessageAuthenticity::Signed(key.clone()),
+        gossipsub_conf,
+    )?;
+    Ok(LiberumNetoBehavior {
+        kademlia,
+        object_sender: obj_sender,
+        ping: ping::Behaviour::new(ping::Config::new()),
+        messenger,
+        mailbox,
+        gossipsub,
+        // The listen addresses of the additional transports come up after the
+        // first identify exchange
+        identify: libp2p::identify::Behaviour::new(
+            libp2p::identify::Config::new(
+                behaviour::identify::IDENTIFY_PROTOCOL_VERSION.to_string(),
+                key.public(),
+            )
+            .with_push_listen_addr_updates(true),
+        ),
+        transfer: libp2p_stream::Behaviour::new(),
+        peer_exchange,
+        // The inbound connections over the limit are denied before they are
+        // established, the outbound ones are pruned by the connection manager
+        connection_limits: connection_limits::Behaviour::new(
+            connection_limits::ConnectionLimits::default()
+                .with_max_established_incoming(u32::try_from(max_inbound).ok()),
+        ),
+    })
+}
+
+/// The transport of the given kind, with the link conditions injected into its
+/// connections if the impairments are given. With the pre-shared key, only the
+/// peers with the same key can connect, and TCP is used instead of QUIC
+fn new_transport(
+    key: &identity::Keypair,
+    kind: TransportKind,
+    network_key: Option<PreSharedKey>,
+    webrtc_certificate: Option<&str>,
+    impairments: Option<&Impairments>,
+) -> Result<Boxed<(PeerId, StreamMuxerBox)>, Box<dyn std::error::Error + Send + Sync>> {
+    let transport = match (kind, network_key) {
+        (TransportKind::Quic, None) => quic::tokio::Transport::new(quic::Config::new(key))
+            .map(|(peer_id, connection), _| (peer_id, StreamMuxerBox::new(connection)))
+            .boxed(),
+        (TransportKind::Quic, Some(psk)) => {
+            let base = tcp::tokio::Transport::new(tcp::Config::default().nodelay(true));
+            secure(
+                base.and_then(move |socket, _| PnetConfig::new(psk).handshake(socket)),
+                key,
+            )?
+        }
+        (TransportKind::Memory, None) => secure(MemoryTransport::default(), key)?,
+        (TransportKind::Memory, Some(psk)) => secure(
+            MemoryTransport::default()
+                .and_then(move |socket, _| PnetConfig::new(psk).handshake(socket)),
+            key,
+        )?,
+    };
+    let transport = match webrtc_certificate {
+        Some(certificate) => transport
+            .or_transport(webrtc::transport(key, certificate)?)
+            .map(|output, _| output.into_inner())
+            .boxed(),
+        None => transport,
+    };
+    Ok(match impairments {
+        Some(impairments) => impairments.wrap(transport),
+        None => transport,
+    })
+}
+
+/// Authenticates and multiplexes the connections of the transport. Used for the
+/// in-process transport of the simulations, which reaches only the swarms of the
+/// same process, and for the transports of the private networks
+fn secure<T>(
+    transport: T,
+    key: &identity::Keypair,
+) -> Result<Boxed<(PeerId, StreamMuxerBox)>, Box<dyn std::error::Error + Send + Sync>>
+where
+    T: Transport + Send + Unpin + 'static,
+    T::Output: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    T::Error: Send + Sync + 'static,
+    T::Dial: Send + 'static,
+    T::ListenerUpgrade: Send + 'static,
+{
+    Ok(transport
+        .upgrade(upgrade::Version::V1)
+        .authenticate(noise::Config::new(key)?)
+        .multiplex(yamux::Config::default())
+        .map(|(peer_id, muxer), _| (peer_id, StreamMuxerBox::new(muxer)))
+        .boxed())
+}
+
 /// Methods on SwarmContext for handling Swarm Events
 impl SwarmContext {
     async fn handle_swarm_event(
@@ -218,21 +575,54 @@ impl SwarmContext {
             } => {
                 // If it was caused by using the Dial message, then send the response
                 if endpoint.is_dialer() {
-                    if let Some(sender) = self.behaviour.pending_inner_dial.remove(&connection_id) {
-                        let _ = sender.send(Ok(()));
-                    }
+                    self.behaviour
+                        .pending_inner_dial
+                        .respond(&connection_id, Ok(peer_id));
+                }
+
+                if self.reputation.is_banned(&peer_id) {
+                    debug!(
+                        node = self.node_snapshot.name,
+                        peer_id = peer_id.to_base58(),
+                        "Disconnecting banned peer"
+                    );
+                    let _ = self.swarm.disconnect_peer_id(peer_id);
+                    return Ok(());
                 }
 
+                if !self.is_peer_allowed(&peer_id) {
+                    debug!(
+                        node = self.node_snapshot.name,
+                        peer_id = peer_id.to_base58(),
+                        "Disconnecting blocked peer"
+                    );
+                    let _ = self.swarm.disconnect_peer_id(peer_id);
+                    return Ok(());
+                }
+
+                let direction = match endpoint.is_dialer() {
+                    true => Direction::Outbound,
+                    false => Direction::Inbound,
+                };
+                self.manage_new_connection(peer_id, connection_id, direction);
+
                 let addr = endpoint.get_remote_address().clone();
                 info!(
                     peer_id = format!("{peer_id:?}"),
                     address = format!("{addr}"),
                     "New connection"
                 );
-                self.swarm
-                    .behaviour_mut()
-                    .kademlia
-                    .add_address(&peer_id, addr);
+                events::record(
+                    &self.events,
+                    NodeEventKind::Connection,
+                    format!("Connected to {peer_id} at {addr}"),
+                );
+                let contact_addr = endpoint.is_dialer().then(|| addr.clone());
+                if let Some(addr) = &contact_addr {
+                    self.known_peer_reached(peer_id, addr).await;
+                }
+                self.touch_contact(peer_id, contact_addr).await;
+                self.add_peer_address(&peer_id, addr);
                 //self.print_neighbours();
             }
             SwarmEvent::OutgoingConnectionError {
@@ -246,11 +636,37 @@ impl SwarmContext {
                     error = format!("{error}"),
                     "Outgoing connection error"
                 );
-                if let Some(sender) = self.behaviour.pending_inner_dial.remove(&connection_id) {
-                    let _ = sender.send(Err(anyhow!(error)));
+                events::record(
+                    &self.events,
+                    NodeEventKind::Error,
+                    format!("Failed to connect to {peer_id:?}: {error}"),
+                );
+                // Only the peers unreachable at all of their addresses count
+                if let (Some(peer_id), DialError::Transport(_)) = (peer_id, &error) {
+                    self.known_peer_failed(&peer_id).await;
+                }
+                self.behaviour
+                    .pending_inner_dial
+                    .respond(&connection_id, Err(anyhow!(error)));
+            }
+            SwarmEvent::ConnectionClosed {
+                peer_id,
+                connection_id,
+                num_established,
+                ..
+            } => {
+                self.connections.remove_connection(&peer_id, &connection_id);
+                if num_established == 0 {
+                    self.latencies.remove(&peer_id);
+                    self.behaviour.wire_versions.remove(&peer_id);
+                    self.behaviour.transfer_peers.remove(&peer_id);
                 }
+                events::record(
+                    &self.events,
+                    NodeEventKind::Connection,
+                    format!("Connection to {peer_id} closed"),
+                );
             }
-            SwarmEvent::ConnectionClosed { .. } => {}
             SwarmEvent::NewListenAddr {
                 listener_id: _,
                 address,
@@ -261,6 +677,15 @@ impl SwarmContext {
                 };
                 let node = serde_json::to_string(&node)?;
                 info!(node = self.node_snapshot.name, "Listening! <{node}>");
+                self.announce_listen_address(address);
+            }
+            SwarmEvent::ExpiredListenAddr { address, .. } => {
+                debug!(
+                    node = self.node_snapshot.name,
+                    address = address.to_string(),
+                    "Stopped listening"
+                );
+                self.withdraw_listen_address(address);
             }
             _ => debug!(
                 node = self.node_snapshot.name,
@@ -275,6 +700,153 @@ impl SwarmContext {
 
 /// Utility not related to behaviours
 impl SwarmContext {
+    /// Bootstraps using the bootstrap nodes from the node data and the DNS seeds
+    pub(crate) fn bootstrap(&mut self) -> Result<()> {
+        let bootstrap_nodes = self
+            .node_snapshot
+            .config
+            .bootstrap_nodes
+            .iter()
+            .chain(&self.dns_bootstrap_nodes);
+        for node in bootstrap_nodes {
+            self.swarm
+                .behaviour_mut()
+                .kademlia
+                .add_address(&node.id, node.addr.clone());
+            debug!("Bootstrap node: {}", serde_json::to_string(&node)?);
+        }
+        self.swarm.behaviour_mut().kademlia.bootstrap()?;
+        Ok(())
+    }
+
+    /// Checks the peer against the blocklist and the allowlist from the node config
+    pub(crate) fn is_peer_allowed(&self, peer_id: &PeerId) -> bool {
+        self.node_snapshot.config.is_peer_allowed(peer_id)
+    }
+
+    /// Blocks or unblocks the peer, the blocked peer is disconnected and removed
+    /// from the routing table
+    fn set_peer_blocked(&mut self, peer_id: PeerId, blocked: bool) {
+        let config = &mut self.node_snapshot.config;
+        match blocked {
+            true => config.block_peer(peer_id),
+            false => config.unblock_peer(&peer_id),
+        }
+
+        if !self.is_peer_allowed(&peer_id) {
+            debug!(
+                node = self.node_snapshot.name,
+                peer_id = peer_id.to_base58(),
+                "Peer blocked, disconnecting"
+            );
+            self.swarm.behaviour_mut().kademlia.remove_peer(&peer_id);
+            let _ = self.swarm.disconnect_peer_id(peer_id);
+        }
+    }
+
+    fn get_status(&mut self) -> NodeStatus {
+        let buckets = self
+            .get_buckets()
+            .into_iter()
+            .map(|(info, _)| info)
+            .collect::<Vec<BucketInfo>>();
+
+        NodeStatus {
+            uptime: self.stats.started_at.elapsed(),
+            connected_peers: self.swarm.connected_peers().count(),
+            routing_table_size: buckets.iter().map(|b| b.num_entries).sum(),
+            buckets,
+            pending_queries: self.behaviour.pending_count(),
+            bytes_sent: self.stats.bytes_sent,
+            bytes_received: self.stats.bytes_received,
+            objects_served: self.stats.objects_served,
+            link_impairment: self.impairments.stats(),
+            uploads: self.upload_quotas.stats(),
+            rejected_records: self.record_validator.stats(),
+        }
+    }
+
+    /// The non-empty buckets of the routing table with their peers and addresses
+    fn get_buckets(&mut self) -> Vec<(BucketInfo, Vec<(PeerId, Vec<Multiaddr>)>)> {
+        let buckets: Vec<(u32, Vec<(PeerId, Vec<Multiaddr>)>)> = self
+            .swarm
+            .behaviour_mut()
+            .kademlia
+            .kbuckets()
+            .map(|bucket| {
+                let peers = bucket
+                    .iter()
+                    .map(|e| {
+                        (
+                            *e.node.key.preimage(),
+                            e.node.value.iter().cloned().collect(),
+                        )
+                    })
+                    .collect();
+                (bucket.range().0.ilog2().unwrap_or(0), peers)
+            })
+            .collect();
+
+        buckets
+            .into_iter()
+            .map(|(index, peers)| {
+                let info = BucketInfo {
+                    index,
+                    num_entries: peers.len(),
+                    num_connected: peers
+                        .iter()
+                        .filter(|(peer_id, _)| self.swarm.is_connected(peer_id))
+                        .count(),
+                };
+                (info, peers)
+            })
+            .collect()
+    }
+
+    /// The buckets of the routing table and the peers in them or connected
+    fn get_routing_table(&mut self) -> (Vec<BucketInfo>, Vec<PeerInfo>) {
+        let mut buckets = Vec::new();
+        let mut peers: HashMap<PeerId, PeerInfo> = HashMap::new();
+
+        for (bucket, bucket_peers) in self.get_buckets() {
+            for (peer_id, addresses) in bucket_peers {
+                peers.insert(
+                    peer_id,
+                    PeerInfo {
+                        peer_id: peer_id.to_base58(),
+                        addresses: addresses.iter().map(|a| a.to_string()).collect(),
+                        connected: false,
+                        in_routing_table: true,
+                        bucket: Some(bucket.index),
+                        latency: self.latencies.get(&peer_id),
+                        object_sender_version: wire_version(&self.behaviour, &peer_id),
+                    },
+                );
+            }
+            buckets.push(bucket);
+        }
+
+        let connected = self.swarm.connected_peers().cloned().collect::<Vec<_>>();
+        for peer_id in connected {
+            peers
+                .entry(peer_id)
+                .or_insert_with(|| PeerInfo {
+                    peer_id: peer_id.to_base58(),
+                    addresses: vec![],
+                    connected: true,
+                    in_routing_table: false,
+                    bucket: None,
+                    latency: self.latencies.get(&peer_id),
+                    object_sender_version: wire_version(&self.behaviour, &peer_id),
+                })
+                .connected = true;
+        }
+
+        let mut peers = peers.into_values().collect::<Vec<_>>();
+        peers.sort_by(|a, b| a.peer_id.cmp(&b.peer_id));
+        (buckets, peers)
+    }
+
     fn print_neighbours(&mut self) {
         debug!(node = self.node_snapshot.name, "Neighbours:");
         let mut i = 0;
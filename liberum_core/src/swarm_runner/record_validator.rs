@@ -0,0 +1,320 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use liberum_core::proto::{
+    self, ProfileObject, RotationObject, SignedObject, TypedObject, UnixTimestamp,
+};
+use liberum_core::types::RecordRejectStats;
+use libp2p::identity::PublicKey;
+use libp2p::kad::{store::RecordStore, ProviderRecord, Record, RecordKey};
+use libp2p::PeerId;
+use tracing::debug;
+
+use super::SwarmContext;
+
+///! The module validates the records and the provider records other peers put in the
+///! store of the node. Kademlia filters both (`StoreInserts::FilterBoth`), so none is
+///! stored unless it passes the checks here, in order: the peer is allowed and under
+///! its rate limit, the record is small enough, it's stored under the key derived from
+///! its value, the signed ones are signed by their owners and they are not older than
+///! the ones already stored. The rejected records are counted by the reasons, in the
+///! status of the node.
+
+/// The most bytes of a profile or a key rotation record
+const MAX_SIGNED_RECORD_SIZE: usize = 4096;
+/// The records and provider records a peer may put in a window, twice as many as
+/// the announcements of a node with the default `max_provides_per_sec`
+const MAX_PUTS_PER_WINDOW: u32 = 1200;
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+/// The windows of the peers are pruned once more peers than this are tracked
+const MAX_TRACKED_PEERS: usize = 1024;
+
+/// Why a record put by another peer was not stored
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RejectReason {
+    BlockedPeer,
+    RateLimited,
+    TooLarge,
+    Malformed,
+    WrongKey,
+    InvalidSignature,
+    Outdated,
+}
+
+/// The rate limits of the peers putting records and the counts of the rejects
+pub struct RecordValidator {
+    /// The puts of the peers in their current windows, with the starts of the windows
+    puts: HashMap<PeerId, (Instant, u32)>,
+    stats: RecordRejectStats,
+}
+
+impl RecordValidator {
+    pub fn new() -> Self {
+        RecordValidator {
+            puts: HashMap::new(),
+            stats: RecordRejectStats::default(),
+        }
+    }
+
+    /// Counts the put of the peer, false if it's over the rate limit
+    pub fn allow(&mut self, peer_id: PeerId, now: Instant) -> bool {
+        if self.puts.len() > MAX_TRACKED_PEERS {
+            self.puts.retain(|_, (start, _)| now < *start + RATE_WINDOW);
+        }
+        let (start, count) = self.puts.entry(peer_id).or_insert((now, 0));
+        if now >= *start + RATE_WINDOW {
+            *start = now;
+            *count = 0;
+        }
+        *count += 1;
+        *count <= MAX_PUTS_PER_WINDOW
+    }
+
+    pub fn reject(&mut self, reason: RejectReason) {
+        let count = match reason {
+            RejectReason::BlockedPeer => &mut self.stats.blocked_peer,
+            RejectReason::RateLimited => &mut self.stats.rate_limited,
+            RejectReason::TooLarge => &mut self.stats.too_large,
+            RejectReason::Malformed => &mut self.stats.malformed,
+            RejectReason::WrongKey => &mut self.stats.wrong_key,
+            RejectReason::InvalidSignature => &mut self.stats.invalid_signature,
+            RejectReason::Outdated => &mut self.stats.outdated,
+        };
+        *count += 1;
+    }
+
+    pub fn stats(&self) -> RecordRejectStats {
+        self.stats
+    }
+}
+
+/// Checks the record against its key. A record is a small object stored under the
+/// key of its ID, up to `max_object_size` bytes, or a profile or a key rotation
+/// stored under the key of the peer ID of its owner
+pub fn validate_record(record: &Record, max_object_size: usize) -> Result<(), RejectReason> {
+    if record.value.len() > MAX_SIGNED_RECORD_SIZE.max(max_object_size) {
+        return Err(RejectReason::TooLarge);
+    }
+    let typed = TypedObject::try_from(&record.value).map_err(|_| RejectReason::Malformed)?;
+    let obj_id = proto::Hash::try_from(&typed).map_err(|_| RejectReason::Malformed)?;
+    if TypedObject::record_key(&obj_id) == record.key {
+        return match record.value.len() <= max_object_size {
+            true => Ok(()),
+            false => Err(RejectReason::TooLarge),
+        };
+    }
+
+    // Any other object must be stored under the key of its ID
+    if signed_record_key(&typed)? != Some(record.key.clone()) {
+        return Err(RejectReason::WrongKey);
+    }
+    if record.value.len() > MAX_SIGNED_RECORD_SIZE {
+        return Err(RejectReason::TooLarge);
+    }
+    // Stored under the right key, only the signatures can be wrong
+    ProfileObject::from_record(&record.key, &record.value)
+        .map(|_| ())
+        .or_else(|_| RotationObject::from_record(&record.key, &record.value).map(|_| ()))
+        .map_err(|_| RejectReason::InvalidSignature)
+}
+
+/// The key the profile or the key rotation must be stored under, derived from the
+/// key of its owner without verifying the signatures. None for other objects
+fn signed_record_key(typed: &TypedObject) -> Result<Option<RecordKey>, RejectReason> {
+    if typed.uuid == RotationObject::UUID {
+        let rotation: RotationObject =
+            TypedObject::try_from_typed(typed).map_err(|_| RejectReason::Malformed)?;
+        let old_key: PublicKey = rotation
+            .old_key
+            .try_into()
+            .map_err(|_| RejectReason::Malformed)?;
+        return Ok(Some(RotationObject::record_key(&old_key.to_peer_id())));
+    }
+    let Some(profile) = signed_profile(typed) else {
+        return Ok(None);
+    };
+    let profile = profile.map_err(|_| RejectReason::Malformed)?;
+    let peer_id = profile.peer_id().map_err(|_| RejectReason::Malformed)?;
+    Ok(Some(ProfileObject::record_key(&peer_id)))
+}
+
+/// The profile in the signed object, None if the object is not a signed profile
+fn signed_profile(typed: &TypedObject) -> Option<anyhow::Result<ProfileObject>> {
+    if typed.uuid != SignedObject::UUID {
+        return None;
+    }
+    let signed: SignedObject = match TypedObject::try_from_typed(typed) {
+        Ok(signed) => signed,
+        Err(e) => return Some(Err(e)),
+    };
+    if signed.object.uuid != ProfileObject::UUID {
+        return None;
+    }
+    Some(TypedObject::try_from_typed(&signed.object))
+}
+
+/// Checks the valid record against the one stored under the same key. A profile or
+/// a key rotation replaces only an older one, the objects never change under their IDs
+pub fn validate_replacement(record: &Record, stored: &Record) -> Result<(), RejectReason> {
+    if record.value == stored.value {
+        return Ok(());
+    }
+    match (signed_at(record), signed_at(stored)) {
+        (Some(new), Some(old)) if new > old => Ok(()),
+        // The stored one can't be read, it's replaced by the valid record
+        (Some(_), None) => Ok(()),
+        _ => Err(RejectReason::Outdated),
+    }
+}
+
+/// When the profile or the key rotation in the record was signed
+fn signed_at(record: &Record) -> Option<UnixTimestamp> {
+    let typed = TypedObject::try_from(&record.value).ok()?;
+    if typed.uuid == RotationObject::UUID {
+        let rotation: RotationObject = TypedObject::try_from_typed(&typed).ok()?;
+        return Some(rotation.rotated_at);
+    }
+    signed_profile(&typed)?
+        .ok()
+        .map(|profile| profile.updated_at)
+}
+
+/// Methods on SwarmContext for validating the records of other peers
+impl SwarmContext {
+    /// Whether the record put by the peer may be stored
+    pub(crate) fn accept_record(&mut self, source: PeerId, record: &Record) -> bool {
+        let config = self.node_snapshot.config.record_objects;
+        let max_object_size = if config.enabled { config.max_size } else { 0 };
+        let result = self
+            .check_peer(source)
+            .and_then(|_| validate_record(record, max_object_size))
+            .and_then(|_| {
+                let store = self.swarm.behaviour_mut().kademlia.store_mut();
+                match store.get(&record.key) {
+                    Some(stored) => validate_replacement(record, &stored),
+                    None => Ok(()),
+                }
+            });
+        self.record_validation(source, result)
+    }
+
+    /// Whether the provider record put by its provider may be stored. The keys of the
+    /// provided objects are their IDs
+    pub(crate) fn accept_provider_record(&mut self, record: &ProviderRecord) -> bool {
+        let result = self.check_peer(record.provider).and_then(|_| {
+            match proto::Hash::try_from(record.key.as_ref()) {
+                Ok(_) => Ok(()),
+                Err(_) => Err(RejectReason::Malformed),
+            }
+        });
+        self.record_validation(record.provider, result)
+    }
+
+    fn check_peer(&mut self, peer_id: PeerId) -> Result<(), RejectReason> {
+        if !self.is_peer_allowed(&peer_id) || self.reputation.is_banned(&peer_id) {
+            return Err(RejectReason::BlockedPeer);
+        }
+        match self.record_validator.allow(peer_id, Instant::now()) {
+            true => Ok(()),
+            false => Err(RejectReason::RateLimited),
+        }
+    }
+
+    fn record_validation(&mut self, peer_id: PeerId, result: Result<(), RejectReason>) -> bool {
+        let Err(reason) = result else {
+            return true;
+        };
+        self.record_validator.reject(reason);
+        debug!(
+            node = self.node_snapshot.name,
+            peer_id = peer_id.to_base58(),
+            reason = format!("{reason:?}"),
+            "Rejected record put by another peer"
+        );
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libp2p::identity::Keypair;
+
+    #[test]
+    fn validate_record_test() {
+        let object = TypedObject {
+            uuid: proto::PlainFileObject::UUID,
+            data: vec![0; 100],
+        };
+        let obj_id = proto::Hash::try_from(&object).unwrap();
+        let value: Vec<u8> = object.try_into().unwrap();
+        let record = Record::new(TypedObject::record_key(&obj_id), value.clone());
+        assert_eq!(validate_record(&record, 1024), Ok(()));
+        assert_eq!(validate_record(&record, 10), Err(RejectReason::TooLarge));
+        let other = Record::new(RecordKey::new(&[0; 32]), value);
+        assert_eq!(validate_record(&other, 1024), Err(RejectReason::WrongKey));
+
+        let keypair = Keypair::generate_ed25519();
+        let profile = ProfileObject {
+            display_name: "Alice".to_string(),
+            description: String::new(),
+            avatar: None,
+            public_key: keypair.public().into(),
+            updated_at: 10,
+        };
+        let key = ProfileObject::record_key(&keypair.public().to_peer_id());
+        let value = profile.clone().to_record_value(keypair.clone()).unwrap();
+        assert_eq!(validate_record(&Record::new(key.clone(), value), 0), Ok(()));
+        let forged = profile
+            .clone()
+            .to_record_value(Keypair::generate_ed25519())
+            .unwrap();
+        assert_eq!(
+            validate_record(&Record::new(key, forged.clone()), 0),
+            Err(RejectReason::InvalidSignature)
+        );
+        let wrong_key = ProfileObject::record_key(&PeerId::random());
+        assert_eq!(
+            validate_record(&Record::new(wrong_key, forged), 0),
+            Err(RejectReason::WrongKey)
+        );
+
+        // An older profile doesn't replace the stored one
+        let stored = Record::new(key.clone(), value);
+        let older = ProfileObject {
+            updated_at: 9,
+            display_name: "Old".to_string(),
+            ..profile.clone()
+        };
+        let older = Record::new(key.clone(), older.to_record_value(keypair.clone()).unwrap());
+        assert_eq!(validate_record(&older, 0), Ok(()));
+        assert_eq!(
+            validate_replacement(&older, &stored),
+            Err(RejectReason::Outdated)
+        );
+        assert_eq!(validate_replacement(&stored, &stored), Ok(()));
+        let newer = ProfileObject {
+            updated_at: 11,
+            ..profile
+        };
+        let newer = Record::new(key, newer.to_record_value(keypair).unwrap());
+        assert_eq!(validate_replacement(&newer, &stored), Ok(()));
+        assert_eq!(
+            validate_replacement(&stored, &newer),
+            Err(RejectReason::Outdated)
+        );
+    }
+
+    #[test]
+    fn rate_limit_test() {
+        let mut validator = RecordValidator::new();
+        let peer_id = PeerId::random();
+        let start = Instant::now();
+        for _ in 0..MAX_PUTS_PER_WINDOW {
+            assert!(validator.allow(peer_id, start));
+        }
+        assert!(!validator.allow(peer_id, start));
+        assert!(validator.allow(PeerId::random(), start));
+        assert!(validator.allow(peer_id, start + RATE_WINDOW));
+    }
+}
@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+
+use kameo::request::MessageSend;
+use liberum_core::types::PeerScore;
+use libp2p::PeerId;
+use tracing::{debug, error, warn};
+
+use crate::vault::{DeletePeerScore, StorePeerScore};
+
+use super::SwarmContext;
+
+///! The module contains the reputation tracker of the peers the swarm talks to.
+///! Every misbehaviour of a peer is recorded and the peers which misbehave too often
+///! are disconnected and no longer asked for objects.
+///!
+///! The misbehaviours of a peer are forgotten when it didn't misbehave for a day, so
+///! the occasional timeouts don't add up over the months and a banned peer, which is
+///! not asked anything, is given another chance. The admin can forget them earlier.
+
+/// Penalty points added to the peer score for every kind of misbehaviour
+const FAILED_INTEGRITY_PENALTY: u32 = 10;
+const TIMEOUT_PENALTY: u32 = 1;
+const PROTOCOL_VIOLATION_PENALTY: u32 = 5;
+/// Peers reaching this amount of penalty points are banned
+const BAN_THRESHOLD: u32 = 50;
+/// The score of a peer expires after this long without a misbehaviour
+const SCORE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Kinds of misbehaviour that can be recorded for a peer
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Misbehaviour {
+    /// The peer sent an object which hash did not match the expected ID
+    FailedIntegrity,
+    /// The peer did not respond to a request in time
+    Timeout,
+    /// The peer sent a request or a response which could not be understood
+    ProtocolViolation,
+}
+
+/// Scores of the peers known to the swarm
+pub struct PeerReputation {
+    scores: HashMap<PeerId, PeerScore>,
+}
+
+impl PeerReputation {
+    pub fn new() -> Self {
+        PeerReputation {
+            scores: HashMap::new(),
+        }
+    }
+
+    /// Creates the tracker from the scores persisted in the vault. Entries with
+    /// invalid peer IDs are skipped
+    pub fn from_scores(scores: Vec<PeerScore>) -> Self {
+        let scores = scores
+            .into_iter()
+            .filter_map(|s| Some((s.peer_id.parse::<PeerId>().ok()?, s)))
+            .collect();
+
+        PeerReputation { scores }
+    }
+
+    /// Records a misbehaviour of a peer and returns the updated score
+    pub fn record(&mut self, peer_id: &PeerId, misbehaviour: Misbehaviour) -> PeerScore {
+        self.record_at(peer_id, misbehaviour, SystemTime::now())
+    }
+
+    fn record_at(
+        &mut self,
+        peer_id: &PeerId,
+        misbehaviour: Misbehaviour,
+        now: SystemTime,
+    ) -> PeerScore {
+        let score = self.scores.entry(*peer_id).or_default();
+        if Self::is_expired(score, now) {
+            *score = PeerScore {
+                peer_id: peer_id.to_base58(),
+                ..Default::default()
+            };
+        }
+
+        match misbehaviour {
+            Misbehaviour::FailedIntegrity => score.failed_integrity_checks += 1,
+            Misbehaviour::Timeout => score.timeouts += 1,
+            Misbehaviour::ProtocolViolation => score.protocol_violations += 1,
+        }
+        score.last_reported_at = Some(now);
+
+        score.clone()
+    }
+
+    /// Forgets the misbehaviours of the peer. Returns false if there were none
+    pub fn reset(&mut self, peer_id: &PeerId) -> bool {
+        self.scores.remove(peer_id).is_some()
+    }
+
+    pub fn is_banned(&self, peer_id: &PeerId) -> bool {
+        self.is_banned_at(peer_id, SystemTime::now())
+    }
+
+    fn is_banned_at(&self, peer_id: &PeerId, now: SystemTime) -> bool {
+        self.scores
+            .get(peer_id)
+            .is_some_and(|s| !Self::is_expired(s, now) && Self::is_score_banned(s))
+    }
+
+    /// The scores which did not expire yet
+    pub fn scores(&self) -> Vec<PeerScore> {
+        let now = SystemTime::now();
+        self.scores
+            .values()
+            .filter(|s| !Self::is_expired(s, now))
+            .cloned()
+            .collect()
+    }
+
+    /// The scores persisted before the time of the last misbehaviour was recorded
+    /// are expired
+    fn is_expired(score: &PeerScore, now: SystemTime) -> bool {
+        score
+            .last_reported_at
+            .and_then(|at| now.duration_since(at).ok())
+            .map_or(true, |elapsed| elapsed >= SCORE_TTL)
+    }
+
+    pub fn penalty(score: &PeerScore) -> u32 {
+        score.failed_integrity_checks * FAILED_INTEGRITY_PENALTY
+            + score.timeouts * TIMEOUT_PENALTY
+            + score.protocol_violations * PROTOCOL_VIOLATION_PENALTY
+    }
+
+    pub fn is_score_banned(score: &PeerScore) -> bool {
+        Self::penalty(score) >= BAN_THRESHOLD
+    }
+}
+
+/// Methods on SwarmContext for reporting peers
+impl SwarmContext {
+    /// Records the misbehaviour, persists the new score in the vault and disconnects
+    /// the peer if it exceeded the ban threshold
+    pub(crate) async fn report_peer(&mut self, peer_id: &PeerId, misbehaviour: Misbehaviour) {
+        let was_banned = self.reputation.is_banned(peer_id);
+        let score = self.reputation.record(peer_id, misbehaviour);
+        debug!(
+            node = self.node_snapshot.name,
+            peer_id = peer_id.to_base58(),
+            misbehaviour = format!("{misbehaviour:?}"),
+            penalty = PeerReputation::penalty(&score),
+            "Peer reported"
+        );
+
+        if let Err(e) = self.vault_ref.ask(StorePeerScore { score }).send().await {
+            error!(
+                node = self.node_snapshot.name,
+                err = e.to_string(),
+                "Failed to persist peer score"
+            );
+        }
+
+        if !was_banned && self.reputation.is_banned(peer_id) {
+            warn!(
+                node = self.node_snapshot.name,
+                peer_id = peer_id.to_base58(),
+                "Peer exceeded the misbehaviour threshold, disconnecting"
+            );
+            self.swarm.behaviour_mut().kademlia.remove_peer(peer_id);
+            let _ = self.swarm.disconnect_peer_id(*peer_id);
+        }
+    }
+
+    /// Forgets the misbehaviours of the peer, in the vault too, so a banned peer is
+    /// talked to again
+    pub(crate) async fn reset_peer_score(&mut self, peer_id: &PeerId) -> anyhow::Result<()> {
+        if self.reputation.reset(peer_id) {
+            debug!(
+                node = self.node_snapshot.name,
+                peer_id = peer_id.to_base58(),
+                "Peer score reset"
+            );
+        }
+        self.vault_ref
+            .ask(DeletePeerScore {
+                peer_id: peer_id.to_base58(),
+            })
+            .send()
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ban_after_threshold_test() {
+        let mut reputation = PeerReputation::new();
+        let peer_id = PeerId::random();
+
+        for _ in 0..4 {
+            reputation.record(&peer_id, Misbehaviour::FailedIntegrity);
+        }
+        assert!(!reputation.is_banned(&peer_id));
+
+        reputation.record(&peer_id, Misbehaviour::FailedIntegrity);
+        assert!(reputation.is_banned(&peer_id));
+    }
+
+    #[test]
+    fn score_expiry_test() {
+        let mut reputation = PeerReputation::new();
+        let peer_id = PeerId::random();
+        let start = SystemTime::now();
+
+        for _ in 0..5 {
+            reputation.record_at(&peer_id, Misbehaviour::FailedIntegrity, start);
+        }
+        assert!(reputation.is_banned_at(&peer_id, start + SCORE_TTL / 2));
+        assert!(!reputation.is_banned_at(&peer_id, start + SCORE_TTL));
+
+        // The expired misbehaviours don't count with the new ones
+        let score = reputation.record_at(&peer_id, Misbehaviour::Timeout, start + SCORE_TTL);
+        assert_eq!(score.failed_integrity_checks, 0);
+        assert_eq!(score.timeouts, 1);
+
+        for _ in 0..5 {
+            reputation.record(&peer_id, Misbehaviour::FailedIntegrity);
+        }
+        assert!(reputation.is_banned(&peer_id));
+        assert!(reputation.reset(&peer_id));
+        assert!(!reputation.is_banned(&peer_id));
+        assert!(reputation.scores().is_empty());
+    }
+
+    #[test]
+    fn from_scores_test() {
+        let peer_id = PeerId::random();
+        let reputation = PeerReputation::from_scores(vec![
+            PeerScore {
+                peer_id: peer_id.to_base58(),
+                protocol_violations: 10,
+                last_reported_at: Some(SystemTime::now()),
+                ..Default::default()
+            },
+            PeerScore {
+                peer_id: "not a peer id".to_string(),
+                ..Default::default()
+            },
+        ]);
+
+        assert!(reputation.is_banned(&peer_id));
+        assert_eq!(reputation.scores().len(), 1);
+    }
+}
@@ -0,0 +1,190 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use liberum_core::node_config::UploadLimits;
+use liberum_core::proto::ResultErrorCode;
+use liberum_core::types::UploadStats;
+use libp2p::PeerId;
+
+///! The module enforces the upload limits from the config of the node, so a single
+///! peer can't make the node send the same large object endlessly. Both the object
+///! sender and the transfer protocol take a permit before sending an object, the
+///! permit counts the upload as active until it's dropped.
+///!
+///! The bytes served to a peer are counted in a sliding window of an hour. A peer
+///! which was served nothing in the window always gets the object, so an object
+///! bigger than the quota can still be downloaded once an hour. A rejected request
+///! is answered with the time after which the peer gets under its quota again.
+
+const QUOTA_WINDOW: Duration = Duration::from_secs(60 * 60);
+/// Sent to the peers rejected because too many uploads are active, the uploads
+/// end much sooner than the quota window
+const BUSY_RETRY_AFTER: Duration = Duration::from_secs(5);
+
+/// The upload quotas of a node, shared by its swarm and the transfers
+#[derive(Clone)]
+pub(crate) struct UploadQuotas {
+    state: Arc<Mutex<QuotaState>>,
+}
+
+struct QuotaState {
+    limits: UploadLimits,
+    /// The times and sizes of the uploads to the peers in the quota window
+    served: HashMap<PeerId, VecDeque<(Instant, u64)>>,
+    active: usize,
+    rejected: u64,
+}
+
+/// An upload counted as active until the permit is dropped
+pub(crate) struct UploadPermit {
+    state: Arc<Mutex<QuotaState>>,
+}
+
+impl Drop for UploadPermit {
+    fn drop(&mut self) {
+        let mut state = self.state.lock().expect("Not to be poisoned");
+        state.active = state.active.saturating_sub(1);
+    }
+}
+
+impl UploadQuotas {
+    pub(crate) fn new(limits: UploadLimits) -> Self {
+        UploadQuotas {
+            state: Arc::new(Mutex::new(QuotaState {
+                limits,
+                served: HashMap::new(),
+                active: 0,
+                rejected: 0,
+            })),
+        }
+    }
+
+    /// Applies to the requests from now on, the active uploads are not stopped
+    pub(crate) fn set_limits(&self, limits: UploadLimits) {
+        self.state.lock().expect("Not to be poisoned").limits = limits;
+    }
+
+    /// Counts the upload of the bytes to the peer, if it's within the limits
+    pub(crate) fn acquire(
+        &self,
+        peer: &PeerId,
+        bytes: u64,
+    ) -> Result<UploadPermit, ResultErrorCode> {
+        self.acquire_at(peer, bytes, Instant::now())
+    }
+
+    fn acquire_at(
+        &self,
+        peer: &PeerId,
+        bytes: u64,
+        now: Instant,
+    ) -> Result<UploadPermit, ResultErrorCode> {
+        let mut state = self.state.lock().expect("Not to be poisoned");
+        let limits = state.limits;
+        if limits.max_concurrent_uploads > 0 && state.active >= limits.max_concurrent_uploads {
+            state.rejected += 1;
+            return Err(quota_exceeded(BUSY_RETRY_AFTER));
+        }
+
+        let served = state.served.entry(*peer).or_default();
+        while served
+            .front()
+            .is_some_and(|(at, _)| now.duration_since(*at) >= QUOTA_WINDOW)
+        {
+            served.pop_front();
+        }
+        let served_bytes: u64 = served.iter().map(|(_, bytes)| bytes).sum();
+        if limits.bytes_per_peer_per_hour > 0
+            && !served.is_empty()
+            && served_bytes + bytes > limits.bytes_per_peer_per_hour
+        {
+            // The oldest upload leaves the window first
+            let oldest = served.front().map_or(now, |(at, _)| *at);
+            let retry_after = QUOTA_WINDOW.saturating_sub(now.duration_since(oldest));
+            state.rejected += 1;
+            return Err(quota_exceeded(retry_after));
+        }
+
+        served.push_back((now, bytes));
+        state.active += 1;
+        Ok(UploadPermit {
+            state: self.state.clone(),
+        })
+    }
+
+    pub(crate) fn stats(&self) -> UploadStats {
+        let mut state = self.state.lock().expect("Not to be poisoned");
+        let now = Instant::now();
+        state.served.retain(|_, served| {
+            served.retain(|(at, _)| now.duration_since(*at) < QUOTA_WINDOW);
+            !served.is_empty()
+        });
+
+        UploadStats {
+            active_uploads: state.active,
+            bytes_last_hour: state
+                .served
+                .values()
+                .flatten()
+                .map(|(_, bytes)| bytes)
+                .sum(),
+            rejected_requests: state.rejected,
+        }
+    }
+}
+
+/// Rounded up, so the peer doesn't retry a moment too early
+fn quota_exceeded(retry_after: Duration) -> ResultErrorCode {
+    ResultErrorCode::QuotaExceeded {
+        retry_after_secs: retry_after.as_secs() + (retry_after.subsec_nanos() > 0) as u64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quota_test() {
+        let quotas = UploadQuotas::new(UploadLimits {
+            bytes_per_peer_per_hour: 100,
+            max_concurrent_uploads: 0,
+        });
+        let peer = PeerId::random();
+        let start = Instant::now();
+
+        // The first upload is allowed even over the quota
+        drop(quotas.acquire_at(&peer, 150, start).unwrap());
+        let minute = Duration::from_secs(60);
+        assert_eq!(
+            quotas.acquire_at(&peer, 1, start + minute).err(),
+            Some(ResultErrorCode::QuotaExceeded {
+                retry_after_secs: 59 * 60
+            })
+        );
+        // Other peers have their own quotas
+        assert!(quotas.acquire_at(&PeerId::random(), 50, start).is_ok());
+        assert!(quotas.acquire_at(&peer, 1, start + QUOTA_WINDOW).is_ok());
+        assert_eq!(quotas.stats().rejected_requests, 1);
+    }
+
+    #[test]
+    fn concurrent_uploads_test() {
+        let quotas = UploadQuotas::new(UploadLimits {
+            bytes_per_peer_per_hour: 0,
+            max_concurrent_uploads: 1,
+        });
+        let permit = quotas.acquire(&PeerId::random(), 10).unwrap();
+        assert_eq!(quotas.stats().active_uploads, 1);
+        assert!(matches!(
+            quotas.acquire(&PeerId::random(), 10),
+            Err(ResultErrorCode::QuotaExceeded { .. })
+        ));
+
+        drop(permit);
+        assert_eq!(quotas.stats().active_uploads, 0);
+        assert!(quotas.acquire(&PeerId::random(), 10).is_ok());
+        assert_eq!(quotas.stats().bytes_last_hour, 20);
+    }
+}
@@ -0,0 +1,117 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use liberum_core::atomic_file;
+use liberum_core::node_config::ListenTransport;
+use libp2p::core::muxing::StreamMuxerBox;
+use libp2p::core::transport::Boxed;
+use libp2p::multiaddr::Protocol;
+use libp2p::{identity, Multiaddr, PeerId, Transport};
+use libp2p_webrtc::tokio::Certificate;
+
+///! The module contains the WebRTC direct transport, the one the browsers can
+///! connect to, as they can't open raw QUIC connections. The peers check the hash
+///! of the certificate of the node, which is a part of its WebRTC addresses, instead
+///! of a signature of an authority.
+///!
+///! The certificate is generated when the node is loaded for the first time with the
+///! transport enabled and kept next to the keypair, so the addresses of the node
+///! don't change after a restart. The node listens on the WebRTC addresses in
+///! addition to its main transport and announces them to the peers with identify.
+
+/// The certificate in the directory of the node
+pub const CERTIFICATE_FILE_NAME: &str = "webrtc_certificate.pem";
+
+/// The PEM of the certificate stored in the directory, a new one is generated and
+/// stored if there is none
+pub async fn load_or_generate_certificate(node_dir_path: &Path) -> Result<String> {
+    let path = node_dir_path.join(CERTIFICATE_FILE_NAME);
+    if path.is_file() {
+        let pem = tokio::fs::read_to_string(&path).await?;
+        // Checked when the node is loaded, not when the swarm is already starting
+        Certificate::from_pem(&pem).map_err(|e| anyhow!("Invalid WebRTC certificate: {e}"))?;
+        return Ok(pem);
+    }
+
+    let pem = Certificate::generate(&mut rand::thread_rng())
+        .map_err(|e| anyhow!("Could not generate WebRTC certificate: {e}"))?
+        .serialize_pem();
+    atomic_file::write(&path, &pem).await?;
+    Ok(pem)
+}
+
+/// Whether the node must have the certificate
+pub fn is_enabled(transports: &[ListenTransport]) -> bool {
+    transports
+        .iter()
+        .any(|transport| matches!(transport, ListenTransport::WebRtc { .. }))
+}
+
+pub(crate) fn transport(
+    key: &identity::Keypair,
+    certificate_pem: &str,
+) -> Result<Boxed<(PeerId, StreamMuxerBox)>> {
+    let certificate = Certificate::from_pem(certificate_pem)
+        .map_err(|e| anyhow!("Invalid WebRTC certificate: {e}"))?;
+    Ok(
+        libp2p_webrtc::tokio::Transport::new(key.clone(), certificate)
+            .map(|(peer_id, connection), _| (peer_id, StreamMuxerBox::new(connection)))
+            .boxed(),
+    )
+}
+
+/// The addresses the transports listen on, on all the interfaces
+pub(crate) fn listen_addresses(transports: &[ListenTransport]) -> Vec<Multiaddr> {
+    transports
+        .iter()
+        .flat_map(|transport| match transport {
+            ListenTransport::WebRtc { port } => [
+                format!("/ip6/::/udp/{port}/webrtc-direct"),
+                format!("/ip4/0.0.0.0/udp/{port}/webrtc-direct"),
+            ],
+        })
+        .map(|addr| addr.parse().expect("Address to be valid"))
+        .collect()
+}
+
+pub(crate) fn is_webrtc_address(addr: &Multiaddr) -> bool {
+    addr.iter()
+        .any(|protocol| matches!(protocol, Protocol::WebRTCDirect))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    #[tokio::test]
+    async fn certificate_test() {
+        let tmp_dir = TempDir::new("liberum_tests").unwrap();
+        let pem = load_or_generate_certificate(tmp_dir.path()).await.unwrap();
+        // The same certificate after a restart, so the certificate hash doesn't change
+        assert_eq!(
+            load_or_generate_certificate(tmp_dir.path()).await.unwrap(),
+            pem
+        );
+        let key = identity::Keypair::generate_ed25519();
+        assert!(transport(&key, &pem).is_ok());
+
+        tokio::fs::write(tmp_dir.path().join(CERTIFICATE_FILE_NAME), "not a pem")
+            .await
+            .unwrap();
+        assert!(load_or_generate_certificate(tmp_dir.path()).await.is_err());
+    }
+
+    #[test]
+    fn listen_addresses_test() {
+        let transports = [ListenTransport::WebRtc { port: 9090 }];
+        assert!(is_enabled(&transports));
+        assert!(!is_enabled(&[]));
+        let addresses = listen_addresses(&transports);
+        assert_eq!(addresses.len(), 2);
+        assert!(addresses.iter().all(is_webrtc_address));
+        assert!(!is_webrtc_address(
+            &"/ip4/0.0.0.0/udp/0/quic-v1".parse().unwrap()
+        ));
+    }
+}
@@ -0,0 +1,247 @@
+pub mod memory;
+pub mod sqlite;
+mod write_queue;
+
+use anyhow::{bail, Result};
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use liberum_core::proto::{AccessPolicy, GroupAccessToken, TypedObject, UserGroup};
+use liberum_core::types::{
+    AuditEntry, AuditFilter, Contact, GroupPost, InboxMessage, NodeStatsSample, ObjectProvenance,
+    PeerScore,
+};
+use std::time::SystemTime;
+use uuid::Uuid;
+
+use super::fragment::key::Key;
+use super::FragmentData;
+
+///! The module contains the storage used by the vault. The vault actor handles the
+///! messages and the fragmentation, a backend only stores and loads the data. The
+///! SQLite backend with the fragments in files is the default one, the in-memory
+///! backend is used by the vaults which should not touch the disk.
+
+/// A message this node keeps for a peer which was offline when it was sent
+#[derive(Debug, Clone, PartialEq)]
+pub struct MailboxEntry {
+    pub id: String,
+    pub recipient: String,
+    /// Empty for the entries kept before the sender was recorded
+    pub sender: String,
+    pub stored_at: SystemTime,
+    /// The signed message, as sent by its sender
+    pub message: Vec<u8>,
+}
+
+/// A peer the node connected to before, dialed at its addresses when the node
+/// starts again
+#[derive(Debug, Clone, PartialEq)]
+pub struct KnownPeer {
+    pub peer_id: String,
+    /// The addresses the node dialed the peer at, the last successful one first
+    pub addresses: Vec<String>,
+    pub last_success: SystemTime,
+    /// The failed dials since the last success
+    pub failures: u32,
+}
+
+/// A group the node owns or was invited to
+#[derive(Debug, Clone)]
+pub struct GroupMembership {
+    pub group: UserGroup,
+    /// The membership of the node, None if the node owns the group
+    pub token: Option<GroupAccessToken>,
+}
+
+impl GroupMembership {
+    pub fn id(&self) -> String {
+        self.group.definition.id.to_string()
+    }
+}
+
+/// An object being downloaded, kept with its received chunks so an interrupted
+/// download resumes where it stopped
+#[derive(Debug, Clone, PartialEq)]
+pub struct PartialObject {
+    pub uuid: Uuid,
+    pub size: u64,
+    /// The hashes of all the chunks of the payload, as announced by the provider
+    pub chunk_hashes: Vec<[u8; 32]>,
+}
+
+impl PartialObject {
+    pub fn verify_chunk(&self, index: u64, data: &[u8]) -> bool {
+        self.chunk_hashes
+            .get(index as usize)
+            .is_some_and(|hash| blake3::hash(data).as_bytes() == hash)
+    }
+}
+
+/// The storage of the vault. The objects are keyed by their hashes, the fragments
+/// by the hashes of their contents
+pub trait VaultBackend: Send + Sync + 'static {
+    /// Prepares the storage, called once when the vault is started
+    fn prepare(&self) -> BoxFuture<'_, Result<()>>;
+
+    /// Stores the object, unless it is already stored, as objects are immutable
+    fn store_typed_object(&self, key: Key, object: TypedObject) -> BoxFuture<'_, Result<()>>;
+    fn load_typed_object(&self, key: Key) -> BoxFuture<'_, Result<Option<TypedObject>>>;
+    /// Deletes the object with its popularity
+    fn delete_typed_object(&self, key: Key) -> BoxFuture<'_, Result<()>>;
+    /// Keys and type IDs of all the stored objects
+    fn list_typed_objects(&self) -> BoxFuture<'_, Result<Vec<(Key, Uuid)>>>;
+    /// Keys and stored sizes of all the stored objects, compressed if they are
+    fn list_typed_object_sizes(&self) -> BoxFuture<'_, Result<Vec<(Key, u64)>>>;
+    /// Records where the stored object came from. Like the object, the first
+    /// recorded provenance is kept
+    fn store_provenance(&self, key: Key, provenance: ObjectProvenance)
+        -> BoxFuture<'_, Result<()>>;
+    /// None if the object is not stored or was stored without its provenance
+    fn load_provenance(&self, key: Key) -> BoxFuture<'_, Result<Option<ObjectProvenance>>>;
+
+    /// Counts the object as served to a peer at the time
+    fn record_object_served(&self, key: Key, at: SystemTime) -> BoxFuture<'_, Result<()>>;
+    /// The number of times and the last time the objects were served, of the
+    /// objects served at least once
+    fn load_object_popularity(&self) -> BoxFuture<'_, Result<Vec<(Key, u64, SystemTime)>>>;
+
+    /// Indexes the object under the tags, the tags it already has are kept
+    fn store_tags(&self, object: Key, tags: Vec<String>) -> BoxFuture<'_, Result<()>>;
+    /// The objects indexed under the hash of the tag, with the tag
+    fn find_tagged(&self, tag_hash: Key) -> BoxFuture<'_, Result<Vec<(Key, String)>>>;
+
+    /// Indexes the text of the file, replacing the text indexed for the key
+    fn index_text(&self, key: Key, name: String, text: String) -> BoxFuture<'_, Result<()>>;
+    fn delete_text(&self, key: Key) -> BoxFuture<'_, Result<()>>;
+    fn clear_text_index(&self) -> BoxFuture<'_, Result<()>>;
+    /// The files with all the words of the query, the best matches first, with
+    /// their names and the snippets of the text around the matches
+    fn search_text(
+        &self,
+        query: String,
+        limit: usize,
+    ) -> BoxFuture<'_, Result<Vec<(Key, String, String)>>>;
+
+    /// Links the object to its previous revision, until the version object is deleted
+    fn store_version(&self, version: Key, object: Key, previous: Key) -> BoxFuture<'_, Result<()>>;
+    fn delete_version(&self, version: Key) -> BoxFuture<'_, Result<()>>;
+    /// The revision the object replaces, the one linked last if there are more
+    fn load_previous_version(&self, object: Key) -> BoxFuture<'_, Result<Option<Key>>>;
+    /// The revisions replacing the object, in the order they were linked
+    fn load_next_versions(&self, object: Key) -> BoxFuture<'_, Result<Vec<Key>>>;
+
+    /// Stores the object published by the node, replacing the one with the same key
+    fn store_published_object(&self, key: Key, object: TypedObject) -> BoxFuture<'_, Result<()>>;
+    fn load_published_object(&self, key: Key) -> BoxFuture<'_, Result<Option<TypedObject>>>;
+    fn delete_published_object(&self, key: Key) -> BoxFuture<'_, Result<()>>;
+    fn list_published_objects(&self) -> BoxFuture<'_, Result<Vec<Key>>>;
+
+    /// Stores the access policy of the object, replacing the previous one. The
+    /// objects without a policy are public
+    fn store_access_policy(&self, key: Key, policy: AccessPolicy) -> BoxFuture<'_, Result<()>>;
+    fn load_access_policy(&self, key: Key) -> BoxFuture<'_, Result<Option<AccessPolicy>>>;
+    fn delete_access_policy(&self, key: Key) -> BoxFuture<'_, Result<()>>;
+
+    /// Stores the object being downloaded, replacing the one with the same key and
+    /// dropping its chunks
+    fn store_partial_object(&self, key: Key, partial: PartialObject) -> BoxFuture<'_, Result<()>>;
+    fn load_partial_object(&self, key: Key) -> BoxFuture<'_, Result<Option<PartialObject>>>;
+    /// Stores the chunk of the object being downloaded, replacing the one with the
+    /// same index
+    fn store_partial_chunk(&self, key: Key, index: u64, data: Vec<u8>)
+        -> BoxFuture<'_, Result<()>>;
+    /// The stored chunks of the object, by their indexes
+    fn load_partial_chunks(&self, key: Key) -> BoxFuture<'_, Result<Vec<(u64, Vec<u8>)>>>;
+    /// Deletes the object being downloaded with its chunks
+    fn delete_partial_object(&self, key: Key) -> BoxFuture<'_, Result<()>>;
+
+    /// Stores the score, replacing the one of the same peer
+    fn store_peer_score(&self, score: PeerScore) -> BoxFuture<'_, Result<()>>;
+    fn load_peer_scores(&self) -> BoxFuture<'_, Result<Vec<PeerScore>>>;
+    fn delete_peer_score(&self, peer_id: String) -> BoxFuture<'_, Result<()>>;
+
+    /// Stores the contact, replacing the one with the same peer ID
+    fn store_contact(&self, contact: Contact) -> BoxFuture<'_, Result<()>>;
+    fn load_contacts(&self) -> BoxFuture<'_, Result<Vec<Contact>>>;
+    /// Returns false if there was no such contact
+    fn delete_contact(&self, peer_id: String) -> BoxFuture<'_, Result<bool>>;
+    /// Updates the last seen time, and the address if given, of the contact.
+    /// Does nothing if the peer is not a contact
+    fn touch_contact(
+        &self,
+        peer_id: String,
+        address: Option<String>,
+        seen_at: SystemTime,
+    ) -> BoxFuture<'_, Result<()>>;
+
+    /// Stores the known peer, replacing the one with the same peer ID
+    fn store_known_peer(&self, peer: KnownPeer) -> BoxFuture<'_, Result<()>>;
+    fn load_known_peers(&self) -> BoxFuture<'_, Result<Vec<KnownPeer>>>;
+    fn delete_known_peer(&self, peer_id: String) -> BoxFuture<'_, Result<()>>;
+
+    /// Adds the message to the inbox, a message with the same ID is kept
+    fn store_message(&self, message: InboxMessage) -> BoxFuture<'_, Result<()>>;
+    /// The messages in the inbox, the oldest first
+    fn load_messages(&self) -> BoxFuture<'_, Result<Vec<InboxMessage>>>;
+
+    /// Keeps the message for the recipient, an entry with the same ID is kept
+    fn store_mailbox_entry(&self, entry: MailboxEntry) -> BoxFuture<'_, Result<()>>;
+    /// The entries of the recipient, the oldest first
+    fn load_mailbox_entries(&self, recipient: String) -> BoxFuture<'_, Result<Vec<MailboxEntry>>>;
+    /// The total size of the messages kept for all the recipients
+    fn mailbox_size(&self) -> BoxFuture<'_, Result<u64>>;
+    /// Returns the number of the deleted entries of the recipient
+    fn delete_mailbox_entries(
+        &self,
+        recipient: String,
+        ids: Vec<String>,
+    ) -> BoxFuture<'_, Result<usize>>;
+    /// Deletes the entries of all the recipients stored before the time
+    fn delete_expired_mailbox_entries(&self, before: SystemTime) -> BoxFuture<'_, Result<usize>>;
+
+    /// Stores the group, replacing the one with the same ID
+    fn store_group(&self, membership: GroupMembership) -> BoxFuture<'_, Result<()>>;
+    fn load_groups(&self) -> BoxFuture<'_, Result<Vec<GroupMembership>>>;
+    /// Adds the post to the feed of its group, a post with the same ID is kept
+    fn store_group_post(&self, post: GroupPost) -> BoxFuture<'_, Result<()>>;
+    /// The posts of the group, the oldest first
+    fn load_group_posts(&self, group: String) -> BoxFuture<'_, Result<Vec<GroupPost>>>;
+
+    /// Appends the entry to the audit log, the entries are never changed or removed
+    fn append_audit_entry(&self, entry: AuditEntry) -> BoxFuture<'_, Result<()>>;
+    /// The matching entries, the oldest first
+    fn load_audit_log(&self, filter: AuditFilter) -> BoxFuture<'_, Result<Vec<AuditEntry>>>;
+
+    /// Appends the sample to the history of the stats of the node
+    fn store_stats_sample(&self, sample: NodeStatsSample) -> BoxFuture<'_, Result<()>>;
+    /// The samples taken in the range, the oldest first
+    fn load_stats_samples(
+        &self,
+        since: Option<SystemTime>,
+        until: Option<SystemTime>,
+    ) -> BoxFuture<'_, Result<Vec<NodeStatsSample>>>;
+    /// Deletes the samples taken before the time, returns how many
+    fn delete_stats_samples(&self, before: SystemTime) -> BoxFuture<'_, Result<usize>>;
+
+    /// Stores the fragment and returns the hash of its contents. If the key is
+    /// given, the fragment is stored only if it matches the hash
+    fn store_fragment(&self, key: Option<Key>, data: FragmentData) -> BoxFuture<'_, Result<Key>>;
+    fn load_fragment(&self, key: Key) -> BoxFuture<'_, Result<Option<FragmentData>>>;
+    fn has_fragment(&self, key: Key) -> BoxFuture<'_, Result<bool>>;
+    fn list_fragments(&self) -> BoxFuture<'_, Result<Vec<Key>>>;
+
+    /// Writes the buffered changes, called when the vault is stopped
+    fn flush(&self) -> BoxFuture<'_, Result<()>> {
+        async { Ok(()) }.boxed()
+    }
+}
+
+/// Verifies integrity if the key was provided
+fn check_fragment_key(key: Option<Key>, fragment_key: Key) -> Result<()> {
+    match key {
+        Some(key) if key != fragment_key => {
+            bail!("Fragment integrity check failed, expected key to be {key}, was {fragment_key}")
+        }
+        _ => Ok(()),
+    }
+}
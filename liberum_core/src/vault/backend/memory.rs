@@ -0,0 +1,664 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use anyhow::{anyhow, Result};
+use futures::future::BoxFuture;
+use futures::{stream, FutureExt, StreamExt};
+use liberum_core::proto::{AccessPolicy, TagObject, TypedObject};
+use liberum_core::types::{
+    AuditEntry, AuditFilter, Contact, GroupPost, InboxMessage, NodeStatsSample, ObjectProvenance,
+    PeerScore,
+};
+use tokio_util::bytes::Bytes;
+use uuid::Uuid;
+
+use super::{
+    check_fragment_key, GroupMembership, KnownPeer, MailboxEntry, PartialObject, VaultBackend,
+};
+use crate::vault::fragment::key::Key;
+use crate::vault::fragment::memory::MemoryFragments;
+use crate::vault::FragmentData;
+
+///! The backend keeping everything in memory, lost when the vault is stopped. The
+///! total size of the fragments is limited by the capacity of `MemoryFragments`.
+
+#[derive(Default)]
+pub struct MemoryBackend {
+    state: Mutex<MemoryState>,
+}
+
+#[derive(Default)]
+struct MemoryState {
+    typed_objects: HashMap<Key, TypedObject>,
+    published_objects: HashMap<Key, TypedObject>,
+    access_policies: HashMap<Key, AccessPolicy>,
+    partial_objects: HashMap<Key, (PartialObject, BTreeMap<u64, Vec<u8>>)>,
+    peer_scores: HashMap<String, PeerScore>,
+    contacts: HashMap<String, Contact>,
+    known_peers: HashMap<String, KnownPeer>,
+    messages: Vec<InboxMessage>,
+    mailbox: Vec<MailboxEntry>,
+    groups: HashMap<String, GroupMembership>,
+    group_posts: Vec<GroupPost>,
+    audit_log: Vec<AuditEntry>,
+    stats_samples: Vec<NodeStatsSample>,
+    /// Where the stored objects came from
+    provenance: HashMap<Key, ObjectProvenance>,
+    /// The number of times and the last time the objects were served
+    popularity: HashMap<Key, (u64, SystemTime)>,
+    /// The objects with the tags, by the hashes of the tags
+    tags: HashMap<Key, HashMap<Key, String>>,
+    /// The indexed texts with the names of the files
+    texts: HashMap<Key, (String, String)>,
+    /// The version objects with the revisions they link, in the order they were stored
+    versions: Vec<(Key, Key, Key)>,
+    fragments: MemoryFragments,
+}
+
+impl MemoryBackend {
+    pub fn new(fragments_capacity: usize) -> MemoryBackend {
+        MemoryBackend {
+            state: Mutex::new(MemoryState {
+                fragments: MemoryFragments::new(fragments_capacity),
+                ..Default::default()
+            }),
+        }
+    }
+
+    /// Runs the closure with the locked state, ready to be returned by the trait methods
+    fn with_state<T, F>(&self, f: F) -> BoxFuture<'_, Result<T>>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut MemoryState) -> Result<T>,
+    {
+        let result = f(&mut self.state.lock().unwrap());
+        async move { result }.boxed()
+    }
+}
+
+impl VaultBackend for MemoryBackend {
+    fn prepare(&self) -> BoxFuture<'_, Result<()>> {
+        async { Ok(()) }.boxed()
+    }
+
+    fn store_typed_object(&self, key: Key, object: TypedObject) -> BoxFuture<'_, Result<()>> {
+        self.with_state(|state| {
+            // Objects are immutable, the stored one is kept
+            state.typed_objects.entry(key).or_insert(object);
+            Ok(())
+        })
+    }
+
+    fn load_typed_object(&self, key: Key) -> BoxFuture<'_, Result<Option<TypedObject>>> {
+        self.with_state(|state| Ok(state.typed_objects.get(&key).cloned()))
+    }
+
+    fn delete_typed_object(&self, key: Key) -> BoxFuture<'_, Result<()>> {
+        self.with_state(|state| {
+            state.typed_objects.remove(&key);
+            state.provenance.remove(&key);
+            state.popularity.remove(&key);
+            Ok(())
+        })
+    }
+
+    fn list_typed_objects(&self) -> BoxFuture<'_, Result<Vec<(Key, Uuid)>>> {
+        self.with_state(|state| {
+            Ok(state
+                .typed_objects
+                .iter()
+                .map(|(key, object)| (*key, object.uuid))
+                .collect())
+        })
+    }
+
+    fn list_typed_object_sizes(&self) -> BoxFuture<'_, Result<Vec<(Key, u64)>>> {
+        self.with_state(|state| {
+            Ok(state
+                .typed_objects
+                .iter()
+                .map(|(key, object)| (*key, object.data.len() as u64))
+                .collect())
+        })
+    }
+
+    fn store_provenance(
+        &self,
+        key: Key,
+        provenance: ObjectProvenance,
+    ) -> BoxFuture<'_, Result<()>> {
+        self.with_state(|state| {
+            if state.typed_objects.contains_key(&key) {
+                state.provenance.entry(key).or_insert(provenance);
+            }
+            Ok(())
+        })
+    }
+
+    fn load_provenance(&self, key: Key) -> BoxFuture<'_, Result<Option<ObjectProvenance>>> {
+        self.with_state(|state| Ok(state.provenance.get(&key).cloned()))
+    }
+
+    fn record_object_served(&self, key: Key, at: SystemTime) -> BoxFuture<'_, Result<()>> {
+        self.with_state(|state| {
+            let (count, last_served) = state.popularity.entry(key).or_insert((0, at));
+            *count += 1;
+            *last_served = at;
+            Ok(())
+        })
+    }
+
+    fn load_object_popularity(&self) -> BoxFuture<'_, Result<Vec<(Key, u64, SystemTime)>>> {
+        self.with_state(|state| {
+            Ok(state
+                .popularity
+                .iter()
+                .map(|(key, (count, last_served))| (*key, *count, *last_served))
+                .collect())
+        })
+    }
+
+    fn store_tags(&self, object: Key, tags: Vec<String>) -> BoxFuture<'_, Result<()>> {
+        self.with_state(|state| {
+            for tag in tags {
+                let tag_hash: Key = TagObject::tag_hash(&tag).bytes.into();
+                state
+                    .tags
+                    .entry(tag_hash)
+                    .or_default()
+                    .insert(object, TagObject::normalize(&tag));
+            }
+            Ok(())
+        })
+    }
+
+    fn index_text(&self, key: Key, name: String, text: String) -> BoxFuture<'_, Result<()>> {
+        self.with_state(|state| {
+            state.texts.insert(key, (name, text));
+            Ok(())
+        })
+    }
+
+    fn delete_text(&self, key: Key) -> BoxFuture<'_, Result<()>> {
+        self.with_state(|state| {
+            state.texts.remove(&key);
+            Ok(())
+        })
+    }
+
+    fn clear_text_index(&self) -> BoxFuture<'_, Result<()>> {
+        self.with_state(|state| {
+            state.texts.clear();
+            Ok(())
+        })
+    }
+
+    /// Ranked by the number of the occurrences of the words
+    fn search_text(
+        &self,
+        query: String,
+        limit: usize,
+    ) -> BoxFuture<'_, Result<Vec<(Key, String, String)>>> {
+        self.with_state(|state| {
+            let words: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+            if words.is_empty() {
+                return Ok(vec![]);
+            }
+            let mut matches: Vec<(usize, Key, String, String)> = state
+                .texts
+                .iter()
+                .filter_map(|(key, (name, text))| {
+                    let searched = format!("{name}\n{text}").to_lowercase();
+                    let counts: Vec<usize> = words
+                        .iter()
+                        .map(|word| searched.matches(word.as_str()).count())
+                        .collect();
+                    if counts.contains(&0) {
+                        return None;
+                    }
+                    let snippet = text_snippet(text, &words);
+                    Some((counts.iter().sum(), *key, name.clone(), snippet))
+                })
+                .collect();
+            matches.sort_by(|a, b| b.0.cmp(&a.0));
+
+            Ok(matches
+                .into_iter()
+                .take(limit)
+                .map(|(_, key, name, snippet)| (key, name, snippet))
+                .collect())
+        })
+    }
+
+    fn find_tagged(&self, tag_hash: Key) -> BoxFuture<'_, Result<Vec<(Key, String)>>> {
+        self.with_state(|state| {
+            Ok(state
+                .tags
+                .get(&tag_hash)
+                .map(|objects| {
+                    objects
+                        .iter()
+                        .map(|(key, tag)| (*key, tag.clone()))
+                        .collect()
+                })
+                .unwrap_or_default())
+        })
+    }
+
+    fn store_version(&self, version: Key, object: Key, previous: Key) -> BoxFuture<'_, Result<()>> {
+        self.with_state(|state| {
+            if !state.versions.iter().any(|(key, _, _)| *key == version) {
+                state.versions.push((version, object, previous));
+            }
+            Ok(())
+        })
+    }
+
+    fn delete_version(&self, version: Key) -> BoxFuture<'_, Result<()>> {
+        self.with_state(|state| {
+            state.versions.retain(|(key, _, _)| *key != version);
+            Ok(())
+        })
+    }
+
+    fn load_previous_version(&self, object: Key) -> BoxFuture<'_, Result<Option<Key>>> {
+        self.with_state(|state| {
+            Ok(state
+                .versions
+                .iter()
+                .rev()
+                .find(|(_, key, _)| *key == object)
+                .map(|(_, _, previous)| *previous))
+        })
+    }
+
+    fn load_next_versions(&self, object: Key) -> BoxFuture<'_, Result<Vec<Key>>> {
+        self.with_state(|state| {
+            Ok(state
+                .versions
+                .iter()
+                .filter(|(_, _, previous)| *previous == object)
+                .map(|(_, key, _)| *key)
+                .collect())
+        })
+    }
+
+    fn store_published_object(&self, key: Key, object: TypedObject) -> BoxFuture<'_, Result<()>> {
+        self.with_state(|state| {
+            state.published_objects.insert(key, object);
+            Ok(())
+        })
+    }
+
+    fn load_published_object(&self, key: Key) -> BoxFuture<'_, Result<Option<TypedObject>>> {
+        self.with_state(|state| Ok(state.published_objects.get(&key).cloned()))
+    }
+
+    fn delete_published_object(&self, key: Key) -> BoxFuture<'_, Result<()>> {
+        self.with_state(|state| {
+            state.published_objects.remove(&key);
+            Ok(())
+        })
+    }
+
+    fn list_published_objects(&self) -> BoxFuture<'_, Result<Vec<Key>>> {
+        self.with_state(|state| Ok(state.published_objects.keys().copied().collect()))
+    }
+
+    fn store_access_policy(&self, key: Key, policy: AccessPolicy) -> BoxFuture<'_, Result<()>> {
+        self.with_state(|state| {
+            state.access_policies.insert(key, policy);
+            Ok(())
+        })
+    }
+
+    fn load_access_policy(&self, key: Key) -> BoxFuture<'_, Result<Option<AccessPolicy>>> {
+        self.with_state(|state| Ok(state.access_policies.get(&key).cloned()))
+    }
+
+    fn delete_access_policy(&self, key: Key) -> BoxFuture<'_, Result<()>> {
+        self.with_state(|state| {
+            state.access_policies.remove(&key);
+            Ok(())
+        })
+    }
+
+    fn store_partial_object(&self, key: Key, partial: PartialObject) -> BoxFuture<'_, Result<()>> {
+        self.with_state(|state| {
+            state
+                .partial_objects
+                .insert(key, (partial, BTreeMap::new()));
+            Ok(())
+        })
+    }
+
+    fn load_partial_object(&self, key: Key) -> BoxFuture<'_, Result<Option<PartialObject>>> {
+        self.with_state(|state| Ok(state.partial_objects.get(&key).map(|(p, _)| p.clone())))
+    }
+
+    fn store_partial_chunk(
+        &self,
+        key: Key,
+        index: u64,
+        data: Vec<u8>,
+    ) -> BoxFuture<'_, Result<()>> {
+        self.with_state(|state| {
+            let (_, chunks) = state
+                .partial_objects
+                .get_mut(&key)
+                .ok_or(anyhow!("Object {key} is not being downloaded"))?;
+            chunks.insert(index, data);
+            Ok(())
+        })
+    }
+
+    fn load_partial_chunks(&self, key: Key) -> BoxFuture<'_, Result<Vec<(u64, Vec<u8>)>>> {
+        self.with_state(|state| {
+            Ok(state
+                .partial_objects
+                .get(&key)
+                .map(|(_, chunks)| chunks.clone().into_iter().collect())
+                .unwrap_or_default())
+        })
+    }
+
+    fn delete_partial_object(&self, key: Key) -> BoxFuture<'_, Result<()>> {
+        self.with_state(|state| {
+            state.partial_objects.remove(&key);
+            Ok(())
+        })
+    }
+
+    fn store_peer_score(&self, score: PeerScore) -> BoxFuture<'_, Result<()>> {
+        self.with_state(|state| {
+            state.peer_scores.insert(score.peer_id.clone(), score);
+            Ok(())
+        })
+    }
+
+    fn load_peer_scores(&self) -> BoxFuture<'_, Result<Vec<PeerScore>>> {
+        self.with_state(|state| Ok(state.peer_scores.values().cloned().collect()))
+    }
+
+    fn delete_peer_score(&self, peer_id: String) -> BoxFuture<'_, Result<()>> {
+        self.with_state(move |state| {
+            state.peer_scores.remove(&peer_id);
+            Ok(())
+        })
+    }
+
+    fn store_contact(&self, contact: Contact) -> BoxFuture<'_, Result<()>> {
+        self.with_state(|state| {
+            state.contacts.insert(contact.peer_id.clone(), contact);
+            Ok(())
+        })
+    }
+
+    fn load_contacts(&self) -> BoxFuture<'_, Result<Vec<Contact>>> {
+        self.with_state(|state| Ok(state.contacts.values().cloned().collect()))
+    }
+
+    fn delete_contact(&self, peer_id: String) -> BoxFuture<'_, Result<bool>> {
+        self.with_state(|state| Ok(state.contacts.remove(&peer_id).is_some()))
+    }
+
+    fn touch_contact(
+        &self,
+        peer_id: String,
+        address: Option<String>,
+        seen_at: SystemTime,
+    ) -> BoxFuture<'_, Result<()>> {
+        self.with_state(|state| {
+            if let Some(contact) = state.contacts.get_mut(&peer_id) {
+                contact.last_seen = Some(seen_at);
+                if address.is_some() {
+                    contact.address = address;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn store_known_peer(&self, peer: KnownPeer) -> BoxFuture<'_, Result<()>> {
+        self.with_state(|state| {
+            state.known_peers.insert(peer.peer_id.clone(), peer);
+            Ok(())
+        })
+    }
+
+    fn load_known_peers(&self) -> BoxFuture<'_, Result<Vec<KnownPeer>>> {
+        self.with_state(|state| Ok(state.known_peers.values().cloned().collect()))
+    }
+
+    fn delete_known_peer(&self, peer_id: String) -> BoxFuture<'_, Result<()>> {
+        self.with_state(|state| {
+            state.known_peers.remove(&peer_id);
+            Ok(())
+        })
+    }
+
+    fn store_message(&self, message: InboxMessage) -> BoxFuture<'_, Result<()>> {
+        self.with_state(|state| {
+            if !state.messages.iter().any(|m| m.id == message.id) {
+                state.messages.push(message);
+            }
+            Ok(())
+        })
+    }
+
+    fn load_messages(&self) -> BoxFuture<'_, Result<Vec<InboxMessage>>> {
+        self.with_state(|state| Ok(state.messages.clone()))
+    }
+
+    fn store_mailbox_entry(&self, entry: MailboxEntry) -> BoxFuture<'_, Result<()>> {
+        self.with_state(|state| {
+            let exists = state
+                .mailbox
+                .iter()
+                .any(|e| e.recipient == entry.recipient && e.id == entry.id);
+            if !exists {
+                state.mailbox.push(entry);
+            }
+            Ok(())
+        })
+    }
+
+    fn load_mailbox_entries(&self, recipient: String) -> BoxFuture<'_, Result<Vec<MailboxEntry>>> {
+        self.with_state(|state| {
+            Ok(state
+                .mailbox
+                .iter()
+                .filter(|e| e.recipient == recipient)
+                .cloned()
+                .collect())
+        })
+    }
+
+    fn mailbox_size(&self) -> BoxFuture<'_, Result<u64>> {
+        self.with_state(|state| Ok(state.mailbox.iter().map(|e| e.message.len() as u64).sum()))
+    }
+
+    fn delete_mailbox_entries(
+        &self,
+        recipient: String,
+        ids: Vec<String>,
+    ) -> BoxFuture<'_, Result<usize>> {
+        self.with_state(|state| {
+            let count = state.mailbox.len();
+            state
+                .mailbox
+                .retain(|e| e.recipient != recipient || !ids.contains(&e.id));
+            Ok(count - state.mailbox.len())
+        })
+    }
+
+    fn delete_expired_mailbox_entries(&self, before: SystemTime) -> BoxFuture<'_, Result<usize>> {
+        self.with_state(|state| {
+            let count = state.mailbox.len();
+            state.mailbox.retain(|e| e.stored_at >= before);
+            Ok(count - state.mailbox.len())
+        })
+    }
+
+    fn store_group(&self, membership: GroupMembership) -> BoxFuture<'_, Result<()>> {
+        self.with_state(|state| {
+            state.groups.insert(membership.id(), membership);
+            Ok(())
+        })
+    }
+
+    fn load_groups(&self) -> BoxFuture<'_, Result<Vec<GroupMembership>>> {
+        self.with_state(|state| Ok(state.groups.values().cloned().collect()))
+    }
+
+    fn store_group_post(&self, post: GroupPost) -> BoxFuture<'_, Result<()>> {
+        self.with_state(|state| {
+            let exists = state
+                .group_posts
+                .iter()
+                .any(|p| p.group == post.group && p.id == post.id);
+            if !exists {
+                state.group_posts.push(post);
+            }
+            Ok(())
+        })
+    }
+
+    fn load_group_posts(&self, group: String) -> BoxFuture<'_, Result<Vec<GroupPost>>> {
+        self.with_state(|state| {
+            Ok(state
+                .group_posts
+                .iter()
+                .filter(|p| p.group == group)
+                .cloned()
+                .collect())
+        })
+    }
+
+    fn append_audit_entry(&self, entry: AuditEntry) -> BoxFuture<'_, Result<()>> {
+        self.with_state(|state| {
+            state.audit_log.push(entry);
+            Ok(())
+        })
+    }
+
+    fn load_audit_log(&self, filter: AuditFilter) -> BoxFuture<'_, Result<Vec<AuditEntry>>> {
+        self.with_state(move |state| {
+            let entries: Vec<AuditEntry> = state
+                .audit_log
+                .iter()
+                .filter(|e| filter.matches(e))
+                .cloned()
+                .collect();
+            let skip = match filter.limit {
+                Some(limit) => entries.len().saturating_sub(limit),
+                None => 0,
+            };
+            Ok(entries.into_iter().skip(skip).collect())
+        })
+    }
+
+    fn store_stats_sample(&self, sample: NodeStatsSample) -> BoxFuture<'_, Result<()>> {
+        self.with_state(|state| {
+            state.stats_samples.push(sample);
+            Ok(())
+        })
+    }
+
+    fn load_stats_samples(
+        &self,
+        since: Option<SystemTime>,
+        until: Option<SystemTime>,
+    ) -> BoxFuture<'_, Result<Vec<NodeStatsSample>>> {
+        self.with_state(move |state| {
+            Ok(state
+                .stats_samples
+                .iter()
+                .filter(|s| since.map_or(true, |since| s.at >= since))
+                .filter(|s| until.map_or(true, |until| s.at <= until))
+                .copied()
+                .collect())
+        })
+    }
+
+    fn delete_stats_samples(&self, before: SystemTime) -> BoxFuture<'_, Result<usize>> {
+        self.with_state(move |state| {
+            let count = state.stats_samples.len();
+            state.stats_samples.retain(|s| s.at >= before);
+            Ok(count - state.stats_samples.len())
+        })
+    }
+
+    fn store_fragment(
+        &self,
+        key: Option<Key>,
+        mut data: FragmentData,
+    ) -> BoxFuture<'_, Result<Key>> {
+        async move {
+            let mut hasher = blake3::Hasher::new();
+            let mut fragment = Vec::new();
+
+            while let Some(bytes) = data.next().await {
+                let bytes = bytes?;
+                hasher.update(&bytes);
+                fragment.extend_from_slice(&bytes);
+            }
+
+            let fragment_key = Key::from(*hasher.finalize().as_bytes());
+            check_fragment_key(key, fragment_key)?;
+            self.state
+                .lock()
+                .unwrap()
+                .fragments
+                .insert(fragment_key, Bytes::from(fragment))?;
+
+            Ok(fragment_key)
+        }
+        .boxed()
+    }
+
+    fn load_fragment(&self, key: Key) -> BoxFuture<'_, Result<Option<FragmentData>>> {
+        self.with_state(|state| {
+            let fragment = state.fragments.get(&key);
+            Ok(fragment.map(|bytes| stream::once(async move { Ok(bytes) }).boxed()))
+        })
+    }
+
+    fn has_fragment(&self, key: Key) -> BoxFuture<'_, Result<bool>> {
+        self.with_state(|state| Ok(state.fragments.contains(&key)))
+    }
+
+    fn list_fragments(&self) -> BoxFuture<'_, Result<Vec<Key>>> {
+        self.with_state(|state| Ok(state.fragments.iter().map(|(key, _)| *key).collect()))
+    }
+}
+
+/// The words around the first match, like the snippets of SQLite, with the matched
+/// words in brackets
+fn text_snippet(text: &str, words: &[String]) -> String {
+    const SNIPPET_WORDS: usize = 12;
+    let is_match = |token: &str| {
+        let token = token.to_lowercase();
+        words.iter().any(|word| token.contains(word.as_str()))
+    };
+    let tokens: Vec<&str> = text.split_whitespace().collect();
+    let first = tokens.iter().position(|token| is_match(token)).unwrap_or(0);
+    let start = first.saturating_sub(SNIPPET_WORDS / 2);
+    let end = (start + SNIPPET_WORDS).min(tokens.len());
+
+    let mut snippet: Vec<String> = tokens[start..end]
+        .iter()
+        .map(|token| match is_match(token) {
+            true => format!("[{token}]"),
+            false => token.to_string(),
+        })
+        .collect();
+    if start > 0 {
+        snippet.insert(0, "...".to_string());
+    }
+    if end < tokens.len() {
+        snippet.push("...".to_string());
+    }
+    snippet.join(" ")
+}
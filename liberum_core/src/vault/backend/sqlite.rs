@@ -0,0 +1,2826 @@
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Result};
+use futures::future::BoxFuture;
+use futures::{FutureExt, StreamExt};
+use liberum_core::atomic_file;
+use liberum_core::compression::{Compressed, Compression};
+use liberum_core::node_config::CompressionConfig;
+use liberum_core::proto::{self, AccessPolicy, TagObject, TypedObject};
+use liberum_core::types::{
+    AuditEntry, AuditFilter, AuditRequestKind, Contact, GroupPost, InboxMessage, NodeStatsSample,
+    ObjectProvenance, PeerScore, ProvenanceKind, SignatureStatus, TrustLevel,
+};
+use rusqlite::{params_from_iter, OptionalExtension};
+use tokio::fs::{remove_file, File};
+use tokio::io::AsyncWriteExt;
+use tokio_rusqlite::Connection;
+use tokio_util::io::ReaderStream;
+use tracing::debug;
+use uuid::Uuid;
+
+use super::write_queue::{PendingWrite, WriteQueue};
+use super::{
+    check_fragment_key, GroupMembership, KnownPeer, MailboxEntry, PartialObject, VaultBackend,
+};
+use crate::vault::fragment::key::Key;
+use crate::vault::fragment::FragmentInfo;
+use crate::vault::FragmentData;
+
+///! The default backend of the vault. The objects and the fragment infos are kept
+///! in a SQLite database, the fragments in the files next to it. The payloads of
+///! the objects are compressed, with the algorithm stored next to them.
+
+pub struct SqliteBackend {
+    db: Connection,
+    vault_dir_path: PathBuf,
+    write_queue: Arc<WriteQueue>,
+    compression: CompressionConfig,
+}
+
+impl SqliteBackend {
+    const DEFAULT_VAULT_DATABASE_NAME: &'static str = "vault.db3";
+    const FRAGMENT_DIR_NAME: &'static str = "fragments";
+    const TEMP_DIR_NAME: &'static str = "temp";
+    const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+    pub async fn open(vault_dir_path: &Path) -> Result<SqliteBackend> {
+        Self::ensure_dirs(vault_dir_path).await?;
+        // The fragments being written when the daemon stopped are not in the vault
+        atomic_file::remove_orphans(&Self::temp_dir_path(vault_dir_path)).await?;
+
+        let db_path = Self::default_db_path(vault_dir_path);
+        let db = Connection::open(db_path).await?;
+        Self::configure_db(&db).await?;
+
+        Ok(SqliteBackend {
+            db: db.clone(),
+            vault_dir_path: vault_dir_path.to_path_buf(),
+            write_queue: WriteQueue::start(db),
+            compression: CompressionConfig::default(),
+        })
+    }
+
+    /// Sets the compression of the objects stored from now on, the stored ones are
+    /// read with their own
+    pub fn with_compression(mut self, compression: CompressionConfig) -> SqliteBackend {
+        self.compression = compression;
+        self
+    }
+
+    /// Lets the readers work while the vault is written to, also from other
+    /// connections, e.g. the vault of a stopped node opened for an export
+    async fn configure_db(db: &Connection) -> Result<()> {
+        db.call(|conn| {
+            conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| {
+                row.get::<_, String>(0)
+            })?;
+            // Safe with WAL, the last transactions may be lost only on a power failure
+            conn.pragma_update(None, "synchronous", "NORMAL")?;
+            conn.busy_timeout(Self::BUSY_TIMEOUT)?;
+
+            Ok(())
+        })
+        .await
+        .map_err(|e| anyhow!(e))
+    }
+
+    async fn prepare_db(&self) -> Result<()> {
+        const CREATE_FRAGMENT_TABLE_QUERY: &str = "
+            CREATE TABLE IF NOT EXISTS fragment (
+                hash0 INTEGER NOT NULL,
+                hash1 INTEGER NOT NULL,
+                hash2 INTEGER NOT NULL,
+                hash3 INTEGER NOT NULL,
+                path VARCHAR(255),
+                size INTEGER,
+                PRIMARY KEY (hash0, hash1, hash2, hash3)
+            )
+        ";
+
+        self.db
+            .call(|conn| Ok(conn.execute(CREATE_FRAGMENT_TABLE_QUERY, ())?))
+            .await?;
+
+        const CREATE_TYPED_OBJECT_TABLE_QUERY: &str = "
+            CREATE TABLE IF NOT EXISTS typed_object (
+                hash0 INTEGER NOT NULL,
+                hash1 INTEGER NOT NULL,
+                hash2 INTEGER NOT NULL,
+                hash3 INTEGER NOT NULL,
+                type_id TEXT,
+                data BLOB,
+                compression INTEGER NOT NULL DEFAULT 0,
+                source_peer TEXT,
+                received_at INTEGER,
+                request_kind TEXT,
+                signature_verified INTEGER,
+                PRIMARY KEY (hash0, hash1, hash2, hash3)
+            )
+        ";
+
+        self.db
+            .call(|conn| Ok(conn.execute(CREATE_TYPED_OBJECT_TABLE_QUERY, ())?))
+            .await?;
+        self.add_column("typed_object", "compression", "INTEGER NOT NULL DEFAULT 0")
+            .await?;
+        // The objects stored before are left without the provenance
+        for (column, definition) in [
+            ("source_peer", "TEXT"),
+            ("received_at", "INTEGER"),
+            ("request_kind", "TEXT"),
+            ("signature_verified", "INTEGER"),
+        ] {
+            self.add_column("typed_object", column, definition).await?;
+        }
+
+        const CREATE_PEER_SCORE_TABLE_QUERY: &str = "
+            CREATE TABLE IF NOT EXISTS peer_score (
+                peer_id TEXT NOT NULL PRIMARY KEY,
+                failed_integrity_checks INTEGER NOT NULL,
+                timeouts INTEGER NOT NULL,
+                protocol_violations INTEGER NOT NULL,
+                last_reported_at INTEGER
+            )
+        ";
+
+        self.db
+            .call(|conn| Ok(conn.execute(CREATE_PEER_SCORE_TABLE_QUERY, ())?))
+            .await?;
+        // The scores stored before are expired
+        self.add_column("peer_score", "last_reported_at", "INTEGER")
+            .await?;
+
+        const CREATE_PUBLISHED_OBJECT_TABLE_QUERY: &str = "
+            CREATE TABLE IF NOT EXISTS published_object (
+                hash0 INTEGER NOT NULL,
+                hash1 INTEGER NOT NULL,
+                hash2 INTEGER NOT NULL,
+                hash3 INTEGER NOT NULL,
+                type_id TEXT,
+                data BLOB,
+                compression INTEGER NOT NULL DEFAULT 0,
+                PRIMARY KEY (hash0, hash1, hash2, hash3)
+            )
+        ";
+
+        self.db
+            .call(|conn| Ok(conn.execute(CREATE_PUBLISHED_OBJECT_TABLE_QUERY, ())?))
+            .await?;
+        self.add_column(
+            "published_object",
+            "compression",
+            "INTEGER NOT NULL DEFAULT 0",
+        )
+        .await?;
+
+        const CREATE_ACCESS_POLICY_TABLE_QUERY: &str = "
+            CREATE TABLE IF NOT EXISTS access_policy (
+                hash0 INTEGER NOT NULL,
+                hash1 INTEGER NOT NULL,
+                hash2 INTEGER NOT NULL,
+                hash3 INTEGER NOT NULL,
+                policy BLOB NOT NULL,
+                PRIMARY KEY (hash0, hash1, hash2, hash3)
+            )
+        ";
+
+        self.db
+            .call(|conn| Ok(conn.execute(CREATE_ACCESS_POLICY_TABLE_QUERY, ())?))
+            .await?;
+
+        const CREATE_CONTACT_TABLE_QUERY: &str = "
+            CREATE TABLE IF NOT EXISTS contact (
+                peer_id TEXT NOT NULL PRIMARY KEY,
+                alias TEXT NOT NULL,
+                address TEXT,
+                last_seen INTEGER,
+                trust TEXT NOT NULL
+            )
+        ";
+
+        self.db
+            .call(|conn| Ok(conn.execute(CREATE_CONTACT_TABLE_QUERY, ())?))
+            .await?;
+
+        // The addresses are separated by the new lines
+        const CREATE_KNOWN_PEER_TABLE_QUERY: &str = "
+            CREATE TABLE IF NOT EXISTS known_peer (
+                peer_id TEXT NOT NULL PRIMARY KEY,
+                addresses TEXT NOT NULL,
+                last_success INTEGER NOT NULL,
+                failures INTEGER NOT NULL
+            )
+        ";
+
+        self.db
+            .call(|conn| Ok(conn.execute(CREATE_KNOWN_PEER_TABLE_QUERY, ())?))
+            .await?;
+
+        const CREATE_MESSAGE_TABLE_QUERY: &str = "
+            CREATE TABLE IF NOT EXISTS message (
+                id TEXT NOT NULL PRIMARY KEY,
+                sender TEXT NOT NULL,
+                sent_at INTEGER NOT NULL,
+                received_at INTEGER NOT NULL,
+                encrypted INTEGER NOT NULL,
+                content BLOB NOT NULL
+            )
+        ";
+
+        self.db
+            .call(|conn| Ok(conn.execute(CREATE_MESSAGE_TABLE_QUERY, ())?))
+            .await?;
+
+        const CREATE_MAILBOX_TABLE_QUERY: &str = "
+            CREATE TABLE IF NOT EXISTS mailbox (
+                id TEXT NOT NULL,
+                recipient TEXT NOT NULL,
+                stored_at INTEGER NOT NULL,
+                message BLOB NOT NULL,
+                sender TEXT NOT NULL DEFAULT '',
+                PRIMARY KEY (recipient, id)
+            )
+        ";
+
+        self.db
+            .call(|conn| Ok(conn.execute(CREATE_MAILBOX_TABLE_QUERY, ())?))
+            .await?;
+        self.add_column("mailbox", "sender", "TEXT NOT NULL DEFAULT ''")
+            .await?;
+
+        const CREATE_USER_GROUP_TABLE_QUERY: &str = "
+            CREATE TABLE IF NOT EXISTS user_group (
+                id TEXT NOT NULL PRIMARY KEY,
+                definition BLOB NOT NULL,
+                token BLOB
+            )
+        ";
+
+        self.db
+            .call(|conn| Ok(conn.execute(CREATE_USER_GROUP_TABLE_QUERY, ())?))
+            .await?;
+
+        const CREATE_GROUP_POST_TABLE_QUERY: &str = "
+            CREATE TABLE IF NOT EXISTS group_post (
+                id TEXT NOT NULL,
+                group_id TEXT NOT NULL,
+                author TEXT NOT NULL,
+                posted_at INTEGER NOT NULL,
+                content BLOB NOT NULL,
+                PRIMARY KEY (group_id, id)
+            )
+        ";
+
+        self.db
+            .call(|conn| Ok(conn.execute(CREATE_GROUP_POST_TABLE_QUERY, ())?))
+            .await?;
+
+        const CREATE_AUDIT_LOG_TABLE_QUERY: &str = "
+            CREATE TABLE IF NOT EXISTS audit_log (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                peer_id TEXT NOT NULL,
+                object_id TEXT NOT NULL,
+                kind TEXT NOT NULL,
+                rejection TEXT,
+                at INTEGER NOT NULL
+            )
+        ";
+
+        self.db
+            .call(|conn| Ok(conn.execute(CREATE_AUDIT_LOG_TABLE_QUERY, ())?))
+            .await?;
+
+        const CREATE_STATS_SAMPLE_TABLE_QUERY: &str = "
+            CREATE TABLE IF NOT EXISTS stats_sample (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                at INTEGER NOT NULL,
+                connected_peers INTEGER NOT NULL,
+                bytes_sent INTEGER NOT NULL,
+                bytes_received INTEGER NOT NULL,
+                objects_served INTEGER NOT NULL
+            )
+        ";
+
+        self.db
+            .call(|conn| Ok(conn.execute(CREATE_STATS_SAMPLE_TABLE_QUERY, ())?))
+            .await?;
+
+        const CREATE_PARTIAL_OBJECT_TABLE_QUERY: &str = "
+            CREATE TABLE IF NOT EXISTS partial_object (
+                hash0 INTEGER NOT NULL,
+                hash1 INTEGER NOT NULL,
+                hash2 INTEGER NOT NULL,
+                hash3 INTEGER NOT NULL,
+                type_id TEXT NOT NULL,
+                size INTEGER NOT NULL,
+                chunk_hashes BLOB NOT NULL,
+                PRIMARY KEY (hash0, hash1, hash2, hash3)
+            )
+        ";
+
+        self.db
+            .call(|conn| Ok(conn.execute(CREATE_PARTIAL_OBJECT_TABLE_QUERY, ())?))
+            .await?;
+
+        const CREATE_PARTIAL_CHUNK_TABLE_QUERY: &str = "
+            CREATE TABLE IF NOT EXISTS partial_chunk (
+                hash0 INTEGER NOT NULL,
+                hash1 INTEGER NOT NULL,
+                hash2 INTEGER NOT NULL,
+                hash3 INTEGER NOT NULL,
+                idx INTEGER NOT NULL,
+                data BLOB NOT NULL,
+                PRIMARY KEY (hash0, hash1, hash2, hash3, idx)
+            )
+        ";
+
+        self.db
+            .call(|conn| Ok(conn.execute(CREATE_PARTIAL_CHUNK_TABLE_QUERY, ())?))
+            .await?;
+
+        const CREATE_OBJECT_POPULARITY_TABLE_QUERY: &str = "
+            CREATE TABLE IF NOT EXISTS object_popularity (
+                hash0 INTEGER NOT NULL,
+                hash1 INTEGER NOT NULL,
+                hash2 INTEGER NOT NULL,
+                hash3 INTEGER NOT NULL,
+                fetch_count INTEGER NOT NULL,
+                last_served INTEGER NOT NULL,
+                PRIMARY KEY (hash0, hash1, hash2, hash3)
+            )
+        ";
+
+        self.db
+            .call(|conn| Ok(conn.execute(CREATE_OBJECT_POPULARITY_TABLE_QUERY, ())?))
+            .await?;
+
+        // The hash of the object goes first, so the rows are read with key_from_row
+        const CREATE_OBJECT_TAG_TABLE_QUERY: &str = "
+            CREATE TABLE IF NOT EXISTS object_tag (
+                hash0 INTEGER NOT NULL,
+                hash1 INTEGER NOT NULL,
+                hash2 INTEGER NOT NULL,
+                hash3 INTEGER NOT NULL,
+                tag_hash0 INTEGER NOT NULL,
+                tag_hash1 INTEGER NOT NULL,
+                tag_hash2 INTEGER NOT NULL,
+                tag_hash3 INTEGER NOT NULL,
+                tag TEXT NOT NULL,
+                PRIMARY KEY (tag_hash0, tag_hash1, tag_hash2, tag_hash3, hash0, hash1, hash2, hash3)
+            )
+        ";
+
+        self.db
+            .call(|conn| Ok(conn.execute(CREATE_OBJECT_TAG_TABLE_QUERY, ())?))
+            .await?;
+
+        const CREATE_OBJECT_TEXT_TABLE_QUERY: &str = "
+            CREATE VIRTUAL TABLE IF NOT EXISTS object_text USING fts5 (
+                hash0 UNINDEXED,
+                hash1 UNINDEXED,
+                hash2 UNINDEXED,
+                hash3 UNINDEXED,
+                name,
+                content
+            )
+        ";
+
+        self.db
+            .call(|conn| Ok(conn.execute(CREATE_OBJECT_TEXT_TABLE_QUERY, ())?))
+            .await?;
+
+        // The rowid keeps the order the revisions were linked in
+        const CREATE_OBJECT_VERSION_TABLE_QUERY: &str = "
+            CREATE TABLE IF NOT EXISTS object_version (
+                hash0 INTEGER NOT NULL,
+                hash1 INTEGER NOT NULL,
+                hash2 INTEGER NOT NULL,
+                hash3 INTEGER NOT NULL,
+                object0 INTEGER NOT NULL,
+                object1 INTEGER NOT NULL,
+                object2 INTEGER NOT NULL,
+                object3 INTEGER NOT NULL,
+                previous0 INTEGER NOT NULL,
+                previous1 INTEGER NOT NULL,
+                previous2 INTEGER NOT NULL,
+                previous3 INTEGER NOT NULL,
+                UNIQUE (hash0, hash1, hash2, hash3)
+            )
+        ";
+
+        self.db
+            .call(|conn| Ok(conn.execute(CREATE_OBJECT_VERSION_TABLE_QUERY, ())?))
+            .await?;
+
+        Ok(())
+    }
+
+    /// Adds the column missing in the table created by an older version
+    async fn add_column(
+        &self,
+        table: &'static str,
+        column: &'static str,
+        definition: &'static str,
+    ) -> Result<()> {
+        self.db
+            .call(move |conn| {
+                let exists = conn
+                    .prepare(&format!(
+                        "SELECT 1 FROM pragma_table_info('{table}') WHERE name = ?1"
+                    ))?
+                    .exists([column])?;
+                if !exists {
+                    conn.execute(
+                        &format!("ALTER TABLE {table} ADD COLUMN {column} {definition}"),
+                        (),
+                    )?;
+                }
+
+                Ok(())
+            })
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+
+    fn compress(&self, object: TypedObject) -> (Uuid, Compressed) {
+        (
+            object.uuid,
+            Compressed::new(object.data, self.compression.level()),
+        )
+    }
+
+    async fn store_typed_object(&self, key: Key, object: TypedObject) -> Result<()> {
+        let (uuid, data) = self.compress(object);
+        self.write_queue
+            .write(PendingWrite::TypedObject(key, uuid, data))
+            .await
+    }
+
+    async fn load_typed_object(&self, key: Key) -> Result<Option<TypedObject>> {
+        const SELECT_TYPED_OBJECT_QUERY: &str = "
+            SELECT type_id, compression, data
+            FROM typed_object
+            WHERE hash0 = ?1 AND hash1 = ?2 AND hash2 = ?3 AND hash3 = ?4
+        ";
+
+        self.write_queue.flush().await;
+
+        self.db
+            .call(move |conn| {
+                let mut stmt = conn.prepare(SELECT_TYPED_OBJECT_QUERY)?;
+                let key_u64_slice = key.as_u64_slice_be();
+                let key_as_i64 = [
+                    key_u64_slice[0] as i64,
+                    key_u64_slice[1] as i64,
+                    key_u64_slice[2] as i64,
+                    key_u64_slice[3] as i64,
+                ];
+
+                let typed_object = stmt
+                    .query_row(key_as_i64, |r| {
+                        let uuid: String = r.get(0)?;
+                        let compression: u8 = r.get(1)?;
+                        let data: Vec<u8> = r.get(2)?;
+
+                        Result::Ok((uuid::Uuid::from_str(&uuid).unwrap(), compression, data))
+                    })
+                    .optional()?;
+
+                Ok(typed_object)
+            })
+            .await
+            .map_err(|e| anyhow!(e))?
+            .map(decompress_object)
+            .transpose()
+    }
+
+    async fn delete_typed_object(&self, key: Key) -> Result<()> {
+        const DELETE_TYPED_OBJECT_QUERY: &str = "
+            DELETE FROM typed_object
+            WHERE hash0 = ?1 AND hash1 = ?2 AND hash2 = ?3 AND hash3 = ?4
+        ";
+        const DELETE_OBJECT_POPULARITY_QUERY: &str = "
+            DELETE FROM object_popularity
+            WHERE hash0 = ?1 AND hash1 = ?2 AND hash2 = ?3 AND hash3 = ?4
+        ";
+
+        self.write_queue.flush().await;
+
+        self.db
+            .call(move |conn| {
+                let key_u64: [u64; 4] = key.into();
+                let key_i64: [i64; 4] = [
+                    key_u64[0] as i64,
+                    key_u64[1] as i64,
+                    key_u64[2] as i64,
+                    key_u64[3] as i64,
+                ];
+
+                conn.execute(DELETE_TYPED_OBJECT_QUERY, params_from_iter(key_i64))?;
+                conn.execute(DELETE_OBJECT_POPULARITY_QUERY, params_from_iter(key_i64))?;
+
+                Ok(())
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    async fn list_typed_objects(&self) -> Result<Vec<(Key, Uuid)>> {
+        const SELECT_TYPED_OBJECT_QUERY: &str = "
+            SELECT hash0, hash1, hash2, hash3, type_id
+            FROM typed_object;
+        ";
+
+        self.write_queue.flush().await;
+
+        let objects = self
+            .db
+            .call(|conn| {
+                let mut stmt = conn.prepare(SELECT_TYPED_OBJECT_QUERY)?;
+                let rows = stmt.query_map([], |row| {
+                    let key_i64s: [i64; 4] = [row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?];
+                    let key_u64s: [u64; 4] = [
+                        key_i64s[0] as u64,
+                        key_i64s[1] as u64,
+                        key_i64s[2] as u64,
+                        key_i64s[3] as u64,
+                    ];
+
+                    let key = Key::from(key_u64s);
+                    let type_id_str: String = row.get(4)?;
+                    let type_id = Uuid::from_str(&type_id_str).expect("type id to be correct");
+
+                    Ok((key, type_id))
+                })?;
+
+                let mut objects = Vec::new();
+                for obj in rows {
+                    objects.push(obj?);
+                }
+
+                Ok(objects)
+            })
+            .await?;
+
+        Ok(objects)
+    }
+
+    async fn store_provenance(&self, key: Key, provenance: ObjectProvenance) -> Result<()> {
+        self.write_queue
+            .write(PendingWrite::Provenance {
+                key,
+                source_peer: provenance.source_peer,
+                received_at: unix_secs(provenance.received_at),
+                kind: provenance.kind.to_string(),
+                signature_verified: provenance
+                    .signature
+                    .map(|status| status == SignatureStatus::Verified),
+            })
+            .await
+    }
+
+    async fn load_provenance(&self, key: Key) -> Result<Option<ObjectProvenance>> {
+        const SELECT_PROVENANCE_QUERY: &str = "
+            SELECT source_peer, received_at, request_kind, signature_verified
+            FROM typed_object
+            WHERE hash0 = ?1 AND hash1 = ?2 AND hash2 = ?3 AND hash3 = ?4
+                AND received_at IS NOT NULL
+        ";
+
+        self.write_queue.flush().await;
+
+        let key_u64: [u64; 4] = key.into();
+        let row = self
+            .db
+            .call(move |conn| {
+                let key_i64 = key_u64.map(|part| part as i64);
+                let row = conn
+                    .query_row(SELECT_PROVENANCE_QUERY, params_from_iter(key_i64), |row| {
+                        Ok((
+                            row.get::<_, Option<String>>(0)?,
+                            row.get::<_, i64>(1)?,
+                            row.get::<_, String>(2)?,
+                            row.get::<_, Option<bool>>(3)?,
+                        ))
+                    })
+                    .optional()?;
+
+                Ok(row)
+            })
+            .await
+            .map_err(|e| anyhow!(e))?;
+
+        row.map(|(source_peer, received_at, kind, signature_verified)| {
+            Ok(ObjectProvenance {
+                source_peer,
+                received_at: from_unix_secs(received_at),
+                kind: ProvenanceKind::from_str(&kind)?,
+                signature: signature_verified.map(|verified| match verified {
+                    true => SignatureStatus::Verified,
+                    false => SignatureStatus::Unverified,
+                }),
+            })
+        })
+        .transpose()
+    }
+
+    async fn list_typed_object_sizes(&self) -> Result<Vec<(Key, u64)>> {
+        const SELECT_TYPED_OBJECT_SIZES_QUERY: &str = "
+            SELECT hash0, hash1, hash2, hash3, length(data)
+            FROM typed_object;
+        ";
+
+        self.write_queue.flush().await;
+
+        self.db
+            .call(|conn| {
+                let mut stmt = conn.prepare(SELECT_TYPED_OBJECT_SIZES_QUERY)?;
+                let rows = stmt.query_map([], |row| {
+                    let key = key_from_row(row)?;
+                    let size: i64 = row.get(4)?;
+
+                    Ok((key, size as u64))
+                })?;
+
+                let mut sizes = Vec::new();
+                for size in rows {
+                    sizes.push(size?);
+                }
+
+                Ok(sizes)
+            })
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+
+    async fn record_object_served(&self, key: Key, at: SystemTime) -> Result<()> {
+        const UPSERT_OBJECT_POPULARITY_QUERY: &str = "
+            INSERT INTO object_popularity (hash0, hash1, hash2, hash3, fetch_count, last_served)
+            VALUES (?1, ?2, ?3, ?4, 1, ?5)
+            ON CONFLICT (hash0, hash1, hash2, hash3) DO UPDATE
+            SET fetch_count = fetch_count + 1, last_served = excluded.last_served
+        ";
+
+        self.db
+            .call(move |conn| {
+                let key_u64: [u64; 4] = key.into();
+                conn.execute(
+                    UPSERT_OBJECT_POPULARITY_QUERY,
+                    (
+                        key_u64[0] as i64,
+                        key_u64[1] as i64,
+                        key_u64[2] as i64,
+                        key_u64[3] as i64,
+                        unix_secs(at),
+                    ),
+                )?;
+
+                Ok(())
+            })
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+
+    async fn load_object_popularity(&self) -> Result<Vec<(Key, u64, SystemTime)>> {
+        const SELECT_OBJECT_POPULARITY_QUERY: &str = "
+            SELECT hash0, hash1, hash2, hash3, fetch_count, last_served
+            FROM object_popularity;
+        ";
+
+        self.db
+            .call(|conn| {
+                let mut stmt = conn.prepare(SELECT_OBJECT_POPULARITY_QUERY)?;
+                let rows = stmt.query_map([], |row| {
+                    let key = key_from_row(row)?;
+                    let fetch_count: i64 = row.get(4)?;
+                    let last_served: i64 = row.get(5)?;
+
+                    Ok((key, fetch_count as u64, from_unix_secs(last_served)))
+                })?;
+
+                let mut popularity = Vec::new();
+                for entry in rows {
+                    popularity.push(entry?);
+                }
+
+                Ok(popularity)
+            })
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+
+    async fn store_tags(&self, object: Key, tags: Vec<String>) -> Result<()> {
+        const INSERT_OBJECT_TAG_QUERY: &str = "
+            INSERT OR IGNORE INTO object_tag
+                (hash0, hash1, hash2, hash3, tag_hash0, tag_hash1, tag_hash2, tag_hash3, tag)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+        ";
+
+        let key_u64: [u64; 4] = object.into();
+        self.db
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                for tag in tags {
+                    let tag_hash: Key = TagObject::tag_hash(&tag).bytes.into();
+                    let tag_u64: [u64; 4] = tag_hash.into();
+                    tx.execute(
+                        INSERT_OBJECT_TAG_QUERY,
+                        (
+                            key_u64[0] as i64,
+                            key_u64[1] as i64,
+                            key_u64[2] as i64,
+                            key_u64[3] as i64,
+                            tag_u64[0] as i64,
+                            tag_u64[1] as i64,
+                            tag_u64[2] as i64,
+                            tag_u64[3] as i64,
+                            TagObject::normalize(&tag),
+                        ),
+                    )?;
+                }
+                tx.commit()?;
+
+                Ok(())
+            })
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+
+    async fn find_tagged(&self, tag_hash: Key) -> Result<Vec<(Key, String)>> {
+        const SELECT_OBJECT_TAG_QUERY: &str = "
+            SELECT hash0, hash1, hash2, hash3, tag
+            FROM object_tag
+            WHERE tag_hash0 = ?1 AND tag_hash1 = ?2 AND tag_hash2 = ?3 AND tag_hash3 = ?4;
+        ";
+
+        let tag_u64: [u64; 4] = tag_hash.into();
+        self.db
+            .call(move |conn| {
+                let tag_i64 = tag_u64.map(|part| part as i64);
+                let mut stmt = conn.prepare(SELECT_OBJECT_TAG_QUERY)?;
+                let rows = stmt.query_map(params_from_iter(tag_i64), |row| {
+                    Ok((key_from_row(row)?, row.get(4)?))
+                })?;
+
+                let mut tagged = Vec::new();
+                for entry in rows {
+                    tagged.push(entry?);
+                }
+
+                Ok(tagged)
+            })
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+
+    async fn index_text(&self, key: Key, name: String, text: String) -> Result<()> {
+        const DELETE_OBJECT_TEXT_QUERY: &str = "
+            DELETE FROM object_text
+            WHERE hash0 = ?1 AND hash1 = ?2 AND hash2 = ?3 AND hash3 = ?4
+        ";
+        const INSERT_OBJECT_TEXT_QUERY: &str = "
+            INSERT INTO object_text (hash0, hash1, hash2, hash3, name, content)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+        ";
+
+        let key_u64: [u64; 4] = key.into();
+        self.db
+            .call(move |conn| {
+                let key_i64 = key_u64.map(|part| part as i64);
+                let tx = conn.transaction()?;
+                tx.execute(DELETE_OBJECT_TEXT_QUERY, params_from_iter(key_i64))?;
+                tx.execute(
+                    INSERT_OBJECT_TEXT_QUERY,
+                    (key_i64[0], key_i64[1], key_i64[2], key_i64[3], name, text),
+                )?;
+                tx.commit()?;
+
+                Ok(())
+            })
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+
+    async fn delete_text(&self, key: Key) -> Result<()> {
+        const DELETE_OBJECT_TEXT_QUERY: &str = "
+            DELETE FROM object_text
+            WHERE hash0 = ?1 AND hash1 = ?2 AND hash2 = ?3 AND hash3 = ?4
+        ";
+
+        let key_u64: [u64; 4] = key.into();
+        self.db
+            .call(move |conn| {
+                let key_i64 = key_u64.map(|part| part as i64);
+                conn.execute(DELETE_OBJECT_TEXT_QUERY, params_from_iter(key_i64))?;
+
+                Ok(())
+            })
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+
+    async fn clear_text_index(&self) -> Result<()> {
+        const DELETE_ALL_OBJECT_TEXT_QUERY: &str = "DELETE FROM object_text";
+
+        self.db
+            .call(|conn| {
+                conn.execute(DELETE_ALL_OBJECT_TEXT_QUERY, ())?;
+
+                Ok(())
+            })
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+
+    /// Ranked by the BM25 rank of FTS5
+    async fn search_text(&self, query: String, limit: usize) -> Result<Vec<(Key, String, String)>> {
+        const SEARCH_OBJECT_TEXT_QUERY: &str = "
+            SELECT hash0, hash1, hash2, hash3, name,
+                snippet(object_text, 5, '[', ']', '...', 12)
+            FROM object_text
+            WHERE object_text MATCH ?1
+            ORDER BY rank
+            LIMIT ?2;
+        ";
+
+        let query = fts_query(&query);
+        if query.is_empty() {
+            return Ok(vec![]);
+        }
+        self.db
+            .call(move |conn| {
+                let mut stmt = conn.prepare(SEARCH_OBJECT_TEXT_QUERY)?;
+                let rows = stmt.query_map((query, limit as i64), |row| {
+                    Ok((key_from_row(row)?, row.get(4)?, row.get(5)?))
+                })?;
+
+                let mut matches = Vec::new();
+                for entry in rows {
+                    matches.push(entry?);
+                }
+
+                Ok(matches)
+            })
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+
+    async fn store_version(&self, version: Key, object: Key, previous: Key) -> Result<()> {
+        const INSERT_OBJECT_VERSION_QUERY: &str = "
+            INSERT OR IGNORE INTO object_version
+                (hash0, hash1, hash2, hash3, object0, object1, object2, object3,
+                previous0, previous1, previous2, previous3)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)
+        ";
+
+        let keys_u64: Vec<u64> = [version, object, previous]
+            .into_iter()
+            .flat_map(<Key as Into<[u64; 4]>>::into)
+            .collect();
+        self.db
+            .call(move |conn| {
+                let keys_i64 = keys_u64.into_iter().map(|part| part as i64);
+                conn.execute(INSERT_OBJECT_VERSION_QUERY, params_from_iter(keys_i64))?;
+
+                Ok(())
+            })
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+
+    async fn delete_version(&self, version: Key) -> Result<()> {
+        const DELETE_OBJECT_VERSION_QUERY: &str = "
+            DELETE FROM object_version
+            WHERE hash0 = ?1 AND hash1 = ?2 AND hash2 = ?3 AND hash3 = ?4
+        ";
+
+        let key_u64: [u64; 4] = version.into();
+        self.db
+            .call(move |conn| {
+                let key_i64 = key_u64.map(|part| part as i64);
+                conn.execute(DELETE_OBJECT_VERSION_QUERY, params_from_iter(key_i64))?;
+
+                Ok(())
+            })
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+
+    async fn load_previous_version(&self, object: Key) -> Result<Option<Key>> {
+        const SELECT_PREVIOUS_VERSION_QUERY: &str = "
+            SELECT previous0, previous1, previous2, previous3
+            FROM object_version
+            WHERE object0 = ?1 AND object1 = ?2 AND object2 = ?3 AND object3 = ?4
+            ORDER BY rowid DESC
+            LIMIT 1;
+        ";
+
+        let key_u64: [u64; 4] = object.into();
+        self.db
+            .call(move |conn| {
+                let key_i64 = key_u64.map(|part| part as i64);
+                let previous = conn
+                    .query_row(
+                        SELECT_PREVIOUS_VERSION_QUERY,
+                        params_from_iter(key_i64),
+                        key_from_row,
+                    )
+                    .optional()?;
+
+                Ok(previous)
+            })
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+
+    async fn load_next_versions(&self, object: Key) -> Result<Vec<Key>> {
+        const SELECT_NEXT_VERSIONS_QUERY: &str = "
+            SELECT object0, object1, object2, object3
+            FROM object_version
+            WHERE previous0 = ?1 AND previous1 = ?2 AND previous2 = ?3 AND previous3 = ?4
+            ORDER BY rowid;
+        ";
+
+        let key_u64: [u64; 4] = object.into();
+        self.db
+            .call(move |conn| {
+                let key_i64 = key_u64.map(|part| part as i64);
+                let mut stmt = conn.prepare(SELECT_NEXT_VERSIONS_QUERY)?;
+                let rows = stmt.query_map(params_from_iter(key_i64), key_from_row)?;
+
+                let mut next = Vec::new();
+                for key in rows {
+                    next.push(key?);
+                }
+
+                Ok(next)
+            })
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+
+    async fn store_published_object(&self, key: Key, object: TypedObject) -> Result<()> {
+        let (uuid, data) = self.compress(object);
+        self.write_queue
+            .write(PendingWrite::PublishedObject(key, uuid, data))
+            .await
+    }
+
+    async fn load_published_object(&self, key: Key) -> Result<Option<TypedObject>> {
+        const SELECT_PUBLISHED_OBJECT_QUERY: &str = "
+            SELECT type_id, compression, data
+            FROM published_object
+            WHERE hash0 = ?1 AND hash1 = ?2 AND hash2 = ?3 AND hash3 = ?4
+        ";
+
+        self.write_queue.flush().await;
+
+        let key_u64: [u64; 4] = key.into();
+
+        self.db
+            .call(move |conn| {
+                let key_i64: [i64; 4] = [
+                    key_u64[0] as i64,
+                    key_u64[1] as i64,
+                    key_u64[2] as i64,
+                    key_u64[3] as i64,
+                ];
+
+                let object = conn
+                    .query_row(SELECT_PUBLISHED_OBJECT_QUERY, key_i64, |r| {
+                        let uuid: String = r.get(0)?;
+                        let compression: u8 = r.get(1)?;
+                        let data: Vec<u8> = r.get(2)?;
+
+                        Ok((Uuid::from_str(&uuid).unwrap(), compression, data))
+                    })
+                    .optional()?;
+
+                Ok(object)
+            })
+            .await
+            .map_err(|e| anyhow!(e))?
+            .map(decompress_object)
+            .transpose()
+    }
+
+    async fn delete_published_object(&self, key: Key) -> Result<()> {
+        const DELETE_PUBLISHED_OBJECT_QUERY: &str = "
+            DELETE FROM published_object
+            WHERE hash0 = ?1 AND hash1 = ?2 AND hash2 = ?3 AND hash3 = ?4
+        ";
+
+        self.write_queue.flush().await;
+
+        let key_u64: [u64; 4] = key.into();
+
+        self.db
+            .call(move |conn| {
+                let key_i64: [i64; 4] = [
+                    key_u64[0] as i64,
+                    key_u64[1] as i64,
+                    key_u64[2] as i64,
+                    key_u64[3] as i64,
+                ];
+
+                conn.execute(DELETE_PUBLISHED_OBJECT_QUERY, params_from_iter(key_i64))?;
+
+                Ok(())
+            })
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+
+    async fn store_access_policy(&self, key: Key, policy: AccessPolicy) -> Result<()> {
+        const INSERT_ACCESS_POLICY_QUERY: &str = "
+            INSERT OR REPLACE INTO access_policy (hash0, hash1, hash2, hash3, policy)
+            VALUES (?1, ?2, ?3, ?4, ?5)
+        ";
+
+        let key_u64: [u64; 4] = key.into();
+        let policy = bincode::serialize(&policy)?;
+
+        self.db
+            .call(move |conn| {
+                conn.execute(
+                    INSERT_ACCESS_POLICY_QUERY,
+                    (
+                        key_u64[0] as i64,
+                        key_u64[1] as i64,
+                        key_u64[2] as i64,
+                        key_u64[3] as i64,
+                        policy,
+                    ),
+                )?;
+
+                Ok(())
+            })
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+
+    async fn load_access_policy(&self, key: Key) -> Result<Option<AccessPolicy>> {
+        const SELECT_ACCESS_POLICY_QUERY: &str = "
+            SELECT policy
+            FROM access_policy
+            WHERE hash0 = ?1 AND hash1 = ?2 AND hash2 = ?3 AND hash3 = ?4
+        ";
+
+        let key_u64: [u64; 4] = key.into();
+
+        let policy = self
+            .db
+            .call(move |conn| {
+                let key_i64: [i64; 4] = [
+                    key_u64[0] as i64,
+                    key_u64[1] as i64,
+                    key_u64[2] as i64,
+                    key_u64[3] as i64,
+                ];
+
+                Ok(conn
+                    .query_row(SELECT_ACCESS_POLICY_QUERY, key_i64, |r| {
+                        r.get::<_, Vec<u8>>(0)
+                    })
+                    .optional()?)
+            })
+            .await?;
+
+        Ok(policy.map(|p| bincode::deserialize(&p)).transpose()?)
+    }
+
+    async fn delete_access_policy(&self, key: Key) -> Result<()> {
+        const DELETE_ACCESS_POLICY_QUERY: &str = "
+            DELETE FROM access_policy
+            WHERE hash0 = ?1 AND hash1 = ?2 AND hash2 = ?3 AND hash3 = ?4
+        ";
+
+        let key_u64: [u64; 4] = key.into();
+
+        self.db
+            .call(move |conn| {
+                let key_i64: [i64; 4] = [
+                    key_u64[0] as i64,
+                    key_u64[1] as i64,
+                    key_u64[2] as i64,
+                    key_u64[3] as i64,
+                ];
+
+                conn.execute(DELETE_ACCESS_POLICY_QUERY, params_from_iter(key_i64))?;
+
+                Ok(())
+            })
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+
+    async fn store_partial_object(&self, key: Key, partial: PartialObject) -> Result<()> {
+        const INSERT_PARTIAL_OBJECT_QUERY: &str = "
+            INSERT OR REPLACE INTO partial_object (hash0, hash1, hash2, hash3, type_id, size, chunk_hashes)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+        ";
+        const DELETE_PARTIAL_CHUNKS_QUERY: &str = "
+            DELETE FROM partial_chunk
+            WHERE hash0 = ?1 AND hash1 = ?2 AND hash2 = ?3 AND hash3 = ?4
+        ";
+
+        let key_u64: [u64; 4] = key.into();
+        let chunk_hashes = partial.chunk_hashes.concat();
+
+        self.db
+            .call(move |conn| {
+                let key_i64: [i64; 4] = [
+                    key_u64[0] as i64,
+                    key_u64[1] as i64,
+                    key_u64[2] as i64,
+                    key_u64[3] as i64,
+                ];
+
+                let tx = conn.transaction()?;
+                tx.execute(DELETE_PARTIAL_CHUNKS_QUERY, params_from_iter(key_i64))?;
+                tx.execute(
+                    INSERT_PARTIAL_OBJECT_QUERY,
+                    (
+                        key_i64[0],
+                        key_i64[1],
+                        key_i64[2],
+                        key_i64[3],
+                        partial.uuid.to_string(),
+                        partial.size as i64,
+                        chunk_hashes,
+                    ),
+                )?;
+                tx.commit()?;
+
+                Ok(())
+            })
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+
+    async fn load_partial_object(&self, key: Key) -> Result<Option<PartialObject>> {
+        const SELECT_PARTIAL_OBJECT_QUERY: &str = "
+            SELECT type_id, size, chunk_hashes
+            FROM partial_object
+            WHERE hash0 = ?1 AND hash1 = ?2 AND hash2 = ?3 AND hash3 = ?4
+        ";
+
+        let key_u64: [u64; 4] = key.into();
+
+        let row = self
+            .db
+            .call(move |conn| {
+                let key_i64: [i64; 4] = [
+                    key_u64[0] as i64,
+                    key_u64[1] as i64,
+                    key_u64[2] as i64,
+                    key_u64[3] as i64,
+                ];
+
+                Ok(conn
+                    .query_row(SELECT_PARTIAL_OBJECT_QUERY, key_i64, |r| {
+                        Ok((
+                            r.get::<_, String>(0)?,
+                            r.get::<_, i64>(1)?,
+                            r.get::<_, Vec<u8>>(2)?,
+                        ))
+                    })
+                    .optional()?)
+            })
+            .await?;
+
+        let Some((type_id, size, chunk_hashes)) = row else {
+            return Ok(None);
+        };
+        let chunk_hashes = chunk_hashes
+            .chunks(32)
+            .map(|hash| hash.try_into())
+            .collect::<Result<_, _>>()?;
+        Ok(Some(PartialObject {
+            uuid: Uuid::from_str(&type_id)?,
+            size: size as u64,
+            chunk_hashes,
+        }))
+    }
+
+    async fn store_partial_chunk(&self, key: Key, index: u64, data: Vec<u8>) -> Result<()> {
+        const INSERT_PARTIAL_CHUNK_QUERY: &str = "
+            INSERT OR REPLACE INTO partial_chunk (hash0, hash1, hash2, hash3, idx, data)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+        ";
+
+        let key_u64: [u64; 4] = key.into();
+
+        self.db
+            .call(move |conn| {
+                conn.execute(
+                    INSERT_PARTIAL_CHUNK_QUERY,
+                    (
+                        key_u64[0] as i64,
+                        key_u64[1] as i64,
+                        key_u64[2] as i64,
+                        key_u64[3] as i64,
+                        index as i64,
+                        data,
+                    ),
+                )?;
+
+                Ok(())
+            })
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+
+    async fn load_partial_chunks(&self, key: Key) -> Result<Vec<(u64, Vec<u8>)>> {
+        const SELECT_PARTIAL_CHUNKS_QUERY: &str = "
+            SELECT idx, data
+            FROM partial_chunk
+            WHERE hash0 = ?1 AND hash1 = ?2 AND hash2 = ?3 AND hash3 = ?4
+            ORDER BY idx
+        ";
+
+        let key_u64: [u64; 4] = key.into();
+
+        self.db
+            .call(move |conn| {
+                let key_i64: [i64; 4] = [
+                    key_u64[0] as i64,
+                    key_u64[1] as i64,
+                    key_u64[2] as i64,
+                    key_u64[3] as i64,
+                ];
+
+                let mut stmt = conn.prepare(SELECT_PARTIAL_CHUNKS_QUERY)?;
+                let rows = stmt.query_map(key_i64, |row| {
+                    Ok((row.get::<_, i64>(0)? as u64, row.get::<_, Vec<u8>>(1)?))
+                })?;
+
+                Ok(rows.collect::<Result<Vec<_>, _>>()?)
+            })
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+
+    async fn delete_partial_object(&self, key: Key) -> Result<()> {
+        const DELETE_PARTIAL_OBJECT_QUERY: &str = "
+            DELETE FROM partial_object
+            WHERE hash0 = ?1 AND hash1 = ?2 AND hash2 = ?3 AND hash3 = ?4
+        ";
+        const DELETE_PARTIAL_CHUNKS_QUERY: &str = "
+            DELETE FROM partial_chunk
+            WHERE hash0 = ?1 AND hash1 = ?2 AND hash2 = ?3 AND hash3 = ?4
+        ";
+
+        let key_u64: [u64; 4] = key.into();
+
+        self.db
+            .call(move |conn| {
+                let key_i64: [i64; 4] = [
+                    key_u64[0] as i64,
+                    key_u64[1] as i64,
+                    key_u64[2] as i64,
+                    key_u64[3] as i64,
+                ];
+
+                let tx = conn.transaction()?;
+                tx.execute(DELETE_PARTIAL_CHUNKS_QUERY, params_from_iter(key_i64))?;
+                tx.execute(DELETE_PARTIAL_OBJECT_QUERY, params_from_iter(key_i64))?;
+                tx.commit()?;
+
+                Ok(())
+            })
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+
+    async fn list_published_objects(&self) -> Result<Vec<Key>> {
+        const SELECT_PUBLISHED_OBJECT_QUERY: &str = "
+            SELECT hash0, hash1, hash2, hash3
+            FROM published_object;
+        ";
+
+        self.write_queue.flush().await;
+
+        self.db
+            .call(|conn| {
+                let mut stmt = conn.prepare(SELECT_PUBLISHED_OBJECT_QUERY)?;
+                let rows = stmt.query_map([], |row| {
+                    let key_i64s: [i64; 4] = [row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?];
+                    let key_u64s: [u64; 4] = [
+                        key_i64s[0] as u64,
+                        key_i64s[1] as u64,
+                        key_i64s[2] as u64,
+                        key_i64s[3] as u64,
+                    ];
+
+                    Ok(Key::from(key_u64s))
+                })?;
+
+                let mut keys = Vec::new();
+                for key in rows {
+                    keys.push(key?);
+                }
+
+                Ok(keys)
+            })
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+
+    async fn store_peer_score(&self, score: PeerScore) -> Result<()> {
+        // The scores are written in the background
+        self.write_queue.push(PendingWrite::PeerScore(score)).await;
+        Ok(())
+    }
+
+    async fn load_peer_scores(&self) -> Result<Vec<PeerScore>> {
+        const SELECT_PEER_SCORE_QUERY: &str = "
+            SELECT peer_id, failed_integrity_checks, timeouts, protocol_violations,
+                last_reported_at
+            FROM peer_score;
+        ";
+
+        self.write_queue.flush().await;
+
+        self.db
+            .call(|conn| {
+                let mut stmt = conn.prepare(SELECT_PEER_SCORE_QUERY)?;
+                let rows = stmt.query_map([], |row| {
+                    Ok(PeerScore {
+                        peer_id: row.get(0)?,
+                        failed_integrity_checks: row.get(1)?,
+                        timeouts: row.get(2)?,
+                        protocol_violations: row.get(3)?,
+                        last_reported_at: row.get::<_, Option<i64>>(4)?.map(from_unix_secs),
+                    })
+                })?;
+
+                let mut scores = Vec::new();
+                for score in rows {
+                    scores.push(score?);
+                }
+
+                Ok(scores)
+            })
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+
+    async fn delete_peer_score(&self, peer_id: String) -> Result<()> {
+        const DELETE_PEER_SCORE_QUERY: &str = "DELETE FROM peer_score WHERE peer_id = ?1";
+
+        // The queued score would be written after the delete otherwise
+        self.write_queue.flush().await;
+
+        self.db
+            .call(move |conn| {
+                conn.execute(DELETE_PEER_SCORE_QUERY, [peer_id])?;
+                Ok(())
+            })
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+
+    async fn store_contact(&self, contact: Contact) -> Result<()> {
+        const UPSERT_CONTACT_QUERY: &str = "
+            INSERT OR REPLACE INTO contact (peer_id, alias, address, last_seen, trust)
+            VALUES (?1, ?2, ?3, ?4, ?5)
+        ";
+
+        // The queued last seen updates must not overwrite the new contact
+        self.write_queue.flush().await;
+
+        self.db
+            .call(move |conn| {
+                conn.execute(
+                    UPSERT_CONTACT_QUERY,
+                    (
+                        contact.peer_id,
+                        contact.alias,
+                        contact.address,
+                        contact.last_seen.map(unix_secs),
+                        contact.trust.to_string(),
+                    ),
+                )?;
+
+                Ok(())
+            })
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+
+    async fn load_contacts(&self) -> Result<Vec<Contact>> {
+        const SELECT_CONTACT_QUERY: &str = "
+            SELECT peer_id, alias, address, last_seen, trust
+            FROM contact;
+        ";
+
+        self.write_queue.flush().await;
+
+        self.db
+            .call(|conn| {
+                let mut stmt = conn.prepare(SELECT_CONTACT_QUERY)?;
+                let rows = stmt.query_map([], |row| {
+                    let last_seen: Option<i64> = row.get(3)?;
+                    let trust: String = row.get(4)?;
+                    Ok(Contact {
+                        peer_id: row.get(0)?,
+                        alias: row.get(1)?,
+                        address: row.get(2)?,
+                        last_seen: last_seen.map(from_unix_secs),
+                        // Written by the vault, so always valid
+                        trust: TrustLevel::from_str(&trust).unwrap_or_default(),
+                    })
+                })?;
+
+                let mut contacts = Vec::new();
+                for contact in rows {
+                    contacts.push(contact?);
+                }
+
+                Ok(contacts)
+            })
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+
+    async fn delete_contact(&self, peer_id: String) -> Result<bool> {
+        const DELETE_CONTACT_QUERY: &str = "DELETE FROM contact WHERE peer_id = ?1";
+
+        self.write_queue.flush().await;
+
+        self.db
+            .call(move |conn| Ok(conn.execute(DELETE_CONTACT_QUERY, [peer_id])? > 0))
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+
+    async fn touch_contact(
+        &self,
+        peer_id: String,
+        address: Option<String>,
+        seen_at: SystemTime,
+    ) -> Result<()> {
+        self.write_queue
+            .push(PendingWrite::ContactSeen {
+                peer_id,
+                address,
+                last_seen: unix_secs(seen_at),
+            })
+            .await;
+        Ok(())
+    }
+
+    async fn store_known_peer(&self, peer: KnownPeer) -> Result<()> {
+        self.write_queue
+            .push(PendingWrite::KnownPeer {
+                peer_id: peer.peer_id,
+                addresses: peer.addresses.join("\n"),
+                last_success: unix_secs(peer.last_success),
+                failures: peer.failures,
+            })
+            .await;
+        Ok(())
+    }
+
+    async fn load_known_peers(&self) -> Result<Vec<KnownPeer>> {
+        const SELECT_KNOWN_PEER_QUERY: &str = "
+            SELECT peer_id, addresses, last_success, failures
+            FROM known_peer;
+        ";
+
+        self.write_queue.flush().await;
+
+        self.db
+            .call(|conn| {
+                let mut stmt = conn.prepare(SELECT_KNOWN_PEER_QUERY)?;
+                let rows = stmt.query_map([], |row| {
+                    let addresses: String = row.get(1)?;
+                    Ok(KnownPeer {
+                        peer_id: row.get(0)?,
+                        addresses: addresses.lines().map(str::to_string).collect(),
+                        last_success: from_unix_secs(row.get(2)?),
+                        failures: row.get(3)?,
+                    })
+                })?;
+
+                let mut peers = Vec::new();
+                for peer in rows {
+                    peers.push(peer?);
+                }
+
+                Ok(peers)
+            })
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+
+    async fn delete_known_peer(&self, peer_id: String) -> Result<()> {
+        const DELETE_KNOWN_PEER_QUERY: &str = "DELETE FROM known_peer WHERE peer_id = ?1";
+
+        // The queued update must not store the peer again
+        self.write_queue.flush().await;
+
+        self.db
+            .call(move |conn| {
+                conn.execute(DELETE_KNOWN_PEER_QUERY, [peer_id])?;
+                Ok(())
+            })
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+
+    async fn store_message(&self, message: InboxMessage) -> Result<()> {
+        const INSERT_MESSAGE_QUERY: &str = "
+            INSERT OR IGNORE INTO message (id, sender, sent_at, received_at, encrypted, content)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+        ";
+
+        let content = bincode::serialize(&message.content)?;
+        self.db
+            .call(move |conn| {
+                conn.execute(
+                    INSERT_MESSAGE_QUERY,
+                    (
+                        message.id,
+                        message.sender,
+                        unix_secs(message.sent_at),
+                        unix_secs(message.received_at),
+                        message.encrypted,
+                        content,
+                    ),
+                )?;
+
+                Ok(())
+            })
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+
+    async fn load_messages(&self) -> Result<Vec<InboxMessage>> {
+        const SELECT_MESSAGE_QUERY: &str = "
+            SELECT id, sender, sent_at, received_at, encrypted, content
+            FROM message
+            ORDER BY received_at;
+        ";
+
+        let rows = self
+            .db
+            .call(|conn| {
+                let mut stmt = conn.prepare(SELECT_MESSAGE_QUERY)?;
+                let rows = stmt.query_map([], |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, i64>(2)?,
+                        row.get::<_, i64>(3)?,
+                        row.get::<_, bool>(4)?,
+                        row.get::<_, Vec<u8>>(5)?,
+                    ))
+                })?;
+
+                let mut messages = Vec::new();
+                for row in rows {
+                    messages.push(row?);
+                }
+
+                Ok(messages)
+            })
+            .await?;
+
+        rows.into_iter()
+            .map(|(id, sender, sent_at, received_at, encrypted, content)| {
+                Ok(InboxMessage {
+                    id,
+                    sender,
+                    sent_at: from_unix_secs(sent_at),
+                    received_at: from_unix_secs(received_at),
+                    encrypted,
+                    content: bincode::deserialize(&content)?,
+                })
+            })
+            .collect()
+    }
+
+    async fn store_mailbox_entry(&self, entry: MailboxEntry) -> Result<()> {
+        const INSERT_MAILBOX_QUERY: &str = "
+            INSERT OR IGNORE INTO mailbox (id, recipient, stored_at, message, sender)
+            VALUES (?1, ?2, ?3, ?4, ?5)
+        ";
+
+        self.db
+            .call(move |conn| {
+                conn.execute(
+                    INSERT_MAILBOX_QUERY,
+                    (
+                        entry.id,
+                        entry.recipient,
+                        unix_secs(entry.stored_at),
+                        entry.message,
+                        entry.sender,
+                    ),
+                )?;
+
+                Ok(())
+            })
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+
+    async fn load_mailbox_entries(&self, recipient: String) -> Result<Vec<MailboxEntry>> {
+        const SELECT_MAILBOX_QUERY: &str = "
+            SELECT id, recipient, stored_at, message, sender
+            FROM mailbox
+            WHERE recipient = ?1
+            ORDER BY stored_at, id;
+        ";
+
+        self.db
+            .call(move |conn| {
+                let mut stmt = conn.prepare(SELECT_MAILBOX_QUERY)?;
+                let rows = stmt.query_map([recipient], |row| {
+                    Ok(MailboxEntry {
+                        id: row.get(0)?,
+                        recipient: row.get(1)?,
+                        stored_at: from_unix_secs(row.get(2)?),
+                        message: row.get(3)?,
+                        sender: row.get(4)?,
+                    })
+                })?;
+
+                let mut entries = Vec::new();
+                for entry in rows {
+                    entries.push(entry?);
+                }
+
+                Ok(entries)
+            })
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+
+    async fn mailbox_size(&self) -> Result<u64> {
+        const MAILBOX_SIZE_QUERY: &str = "SELECT COALESCE(SUM(LENGTH(message)), 0) FROM mailbox";
+
+        self.db
+            .call(|conn| Ok(conn.query_row(MAILBOX_SIZE_QUERY, (), |row| row.get(0))?))
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+
+    async fn delete_mailbox_entries(&self, recipient: String, ids: Vec<String>) -> Result<usize> {
+        const DELETE_MAILBOX_QUERY: &str = "DELETE FROM mailbox WHERE recipient = ?1 AND id = ?2";
+
+        self.db
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                let mut deleted = 0;
+                for id in ids {
+                    deleted += tx.execute(DELETE_MAILBOX_QUERY, (&recipient, id))?;
+                }
+                tx.commit()?;
+
+                Ok(deleted)
+            })
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+
+    async fn delete_expired_mailbox_entries(&self, before: SystemTime) -> Result<usize> {
+        const DELETE_EXPIRED_QUERY: &str = "DELETE FROM mailbox WHERE stored_at < ?1";
+
+        let before = unix_secs(before);
+        self.db
+            .call(move |conn| Ok(conn.execute(DELETE_EXPIRED_QUERY, [before])?))
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+
+    async fn store_group(&self, membership: GroupMembership) -> Result<()> {
+        const INSERT_GROUP_QUERY: &str = "
+            INSERT OR REPLACE INTO user_group (id, definition, token)
+            VALUES (?1, ?2, ?3)
+        ";
+
+        let id = membership.id();
+        let definition = bincode::serialize(&membership.group)?;
+        let token = membership
+            .token
+            .as_ref()
+            .map(bincode::serialize)
+            .transpose()?;
+        self.db
+            .call(move |conn| {
+                conn.execute(INSERT_GROUP_QUERY, (id, definition, token))?;
+
+                Ok(())
+            })
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+
+    async fn load_groups(&self) -> Result<Vec<GroupMembership>> {
+        const SELECT_GROUP_QUERY: &str = "SELECT definition, token FROM user_group;";
+
+        let rows = self
+            .db
+            .call(|conn| {
+                let mut stmt = conn.prepare(SELECT_GROUP_QUERY)?;
+                let rows = stmt.query_map([], |row| {
+                    Ok((row.get::<_, Vec<u8>>(0)?, row.get::<_, Option<Vec<u8>>>(1)?))
+                })?;
+
+                let mut groups = Vec::new();
+                for row in rows {
+                    groups.push(row?);
+                }
+
+                Ok(groups)
+            })
+            .await?;
+
+        rows.into_iter()
+            .map(|(definition, token)| {
+                Ok(GroupMembership {
+                    group: bincode::deserialize(&definition)?,
+                    token: token.map(|t| bincode::deserialize(&t)).transpose()?,
+                })
+            })
+            .collect()
+    }
+
+    async fn store_group_post(&self, post: GroupPost) -> Result<()> {
+        const INSERT_GROUP_POST_QUERY: &str = "
+            INSERT OR IGNORE INTO group_post (id, group_id, author, posted_at, content)
+            VALUES (?1, ?2, ?3, ?4, ?5)
+        ";
+
+        let content = bincode::serialize(&post.content)?;
+        self.db
+            .call(move |conn| {
+                conn.execute(
+                    INSERT_GROUP_POST_QUERY,
+                    (
+                        post.id,
+                        post.group,
+                        post.author,
+                        unix_secs(post.posted_at),
+                        content,
+                    ),
+                )?;
+
+                Ok(())
+            })
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+
+    async fn load_group_posts(&self, group: String) -> Result<Vec<GroupPost>> {
+        const SELECT_GROUP_POST_QUERY: &str = "
+            SELECT id, group_id, author, posted_at, content
+            FROM group_post
+            WHERE group_id = ?1
+            ORDER BY posted_at;
+        ";
+
+        let rows = self
+            .db
+            .call(move |conn| {
+                let mut stmt = conn.prepare(SELECT_GROUP_POST_QUERY)?;
+                let rows = stmt.query_map([group], |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, String>(2)?,
+                        row.get::<_, i64>(3)?,
+                        row.get::<_, Vec<u8>>(4)?,
+                    ))
+                })?;
+
+                let mut posts = Vec::new();
+                for row in rows {
+                    posts.push(row?);
+                }
+
+                Ok(posts)
+            })
+            .await?;
+
+        rows.into_iter()
+            .map(|(id, group, author, posted_at, content)| {
+                Ok(GroupPost {
+                    id,
+                    group,
+                    author,
+                    posted_at: from_unix_secs(posted_at),
+                    content: bincode::deserialize(&content)?,
+                })
+            })
+            .collect()
+    }
+
+    async fn append_audit_entry(&self, entry: AuditEntry) -> Result<()> {
+        const INSERT_AUDIT_ENTRY_QUERY: &str = "
+            INSERT INTO audit_log (peer_id, object_id, kind, rejection, at)
+            VALUES (?1, ?2, ?3, ?4, ?5)
+        ";
+
+        self.db
+            .call(move |conn| {
+                conn.execute(
+                    INSERT_AUDIT_ENTRY_QUERY,
+                    (
+                        entry.peer_id,
+                        entry.object_id,
+                        entry.kind.to_string(),
+                        entry.rejection,
+                        unix_secs(entry.at),
+                    ),
+                )?;
+
+                Ok(())
+            })
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+
+    async fn load_audit_log(&self, filter: AuditFilter) -> Result<Vec<AuditEntry>> {
+        // The newest entries are selected, so the limit keeps them
+        const SELECT_AUDIT_LOG_QUERY: &str = "
+            SELECT peer_id, object_id, kind, rejection, at
+            FROM audit_log
+            WHERE (?1 IS NULL OR peer_id = ?1)
+                AND (?2 IS NULL OR object_id = ?2)
+                AND (?3 IS NULL OR kind = ?3)
+                AND (?4 IS NULL OR at >= ?4)
+                AND (?5 = 0 OR rejection IS NOT NULL)
+            ORDER BY id DESC
+            LIMIT ?6;
+        ";
+
+        let params = (
+            filter.peer_id,
+            filter.object_id,
+            filter.kind.map(|k| k.to_string()),
+            filter.since.map(unix_secs),
+            filter.rejected_only,
+            filter.limit.map_or(-1, |l| l as i64),
+        );
+        let rows = self
+            .db
+            .call(move |conn| {
+                let mut stmt = conn.prepare(SELECT_AUDIT_LOG_QUERY)?;
+                let rows = stmt.query_map(params, |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, String>(2)?,
+                        row.get::<_, Option<String>>(3)?,
+                        row.get::<_, i64>(4)?,
+                    ))
+                })?;
+
+                let mut entries = Vec::new();
+                for row in rows {
+                    entries.push(row?);
+                }
+
+                Ok(entries)
+            })
+            .await?;
+
+        rows.into_iter()
+            .rev()
+            .map(|(peer_id, object_id, kind, rejection, at)| {
+                Ok(AuditEntry {
+                    peer_id,
+                    object_id,
+                    kind: AuditRequestKind::from_str(&kind)?,
+                    rejection,
+                    at: from_unix_secs(at),
+                })
+            })
+            .collect()
+    }
+
+    async fn store_stats_sample(&self, sample: NodeStatsSample) -> Result<()> {
+        const INSERT_STATS_SAMPLE_QUERY: &str = "
+            INSERT INTO stats_sample (at, connected_peers, bytes_sent, bytes_received, objects_served)
+            VALUES (?1, ?2, ?3, ?4, ?5)
+        ";
+
+        self.db
+            .call(move |conn| {
+                conn.execute(
+                    INSERT_STATS_SAMPLE_QUERY,
+                    (
+                        unix_secs(sample.at),
+                        sample.connected_peers as i64,
+                        sample.bytes_sent as i64,
+                        sample.bytes_received as i64,
+                        sample.objects_served as i64,
+                    ),
+                )?;
+
+                Ok(())
+            })
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+
+    async fn load_stats_samples(
+        &self,
+        since: Option<SystemTime>,
+        until: Option<SystemTime>,
+    ) -> Result<Vec<NodeStatsSample>> {
+        const SELECT_STATS_SAMPLES_QUERY: &str = "
+            SELECT at, connected_peers, bytes_sent, bytes_received, objects_served
+            FROM stats_sample
+            WHERE (?1 IS NULL OR at >= ?1) AND (?2 IS NULL OR at <= ?2)
+            ORDER BY at, id;
+        ";
+
+        let params = (since.map(unix_secs), until.map(unix_secs));
+        self.db
+            .call(move |conn| {
+                let mut stmt = conn.prepare(SELECT_STATS_SAMPLES_QUERY)?;
+                let rows = stmt.query_map(params, |row| {
+                    Ok(NodeStatsSample {
+                        at: from_unix_secs(row.get(0)?),
+                        connected_peers: row.get::<_, i64>(1)? as usize,
+                        bytes_sent: row.get::<_, i64>(2)? as u64,
+                        bytes_received: row.get::<_, i64>(3)? as u64,
+                        objects_served: row.get::<_, i64>(4)? as u64,
+                    })
+                })?;
+
+                let mut samples = Vec::new();
+                for row in rows {
+                    samples.push(row?);
+                }
+
+                Ok(samples)
+            })
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+
+    async fn delete_stats_samples(&self, before: SystemTime) -> Result<usize> {
+        const DELETE_STATS_SAMPLES_QUERY: &str = "DELETE FROM stats_sample WHERE at < ?1";
+
+        let before = unix_secs(before);
+        self.db
+            .call(move |conn| Ok(conn.execute(DELETE_STATS_SAMPLES_QUERY, [before])?))
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+
+    async fn store_fragment(&self, key: Option<Key>, mut data: FragmentData) -> Result<Key> {
+        let uid = Uuid::new_v4();
+        let random_fragment_path = Self::temp_dir_path(&self.vault_dir_path)
+            .join(format!("{uid}{}", atomic_file::TEMP_FILE_SUFFIX));
+        let mut fragment_file = File::create(&random_fragment_path).await?;
+        let mut hasher = blake3::Hasher::new();
+        let mut fragment_size = 0;
+
+        while let Some(bytes) = data.next().await {
+            let bytes = bytes?;
+            hasher.update(&bytes);
+            fragment_file.write_all(&bytes).await?;
+            fragment_size += bytes.len();
+        }
+
+        fragment_file.flush().await?;
+        drop(fragment_file);
+
+        let key_bytes = hasher.finalize().as_bytes().to_vec();
+        let fragment_key = Key::try_from(key_bytes.clone())?;
+
+        if let Err(e) = check_fragment_key(key, fragment_key) {
+            remove_file(random_fragment_path).await?;
+            return Err(e);
+        }
+
+        let key_string = bs58::encode(&key_bytes).into_string();
+        let valid_fragment_path = Self::fragment_dir_path(&self.vault_dir_path).join(key_string);
+        atomic_file::persist(&random_fragment_path, &valid_fragment_path).await?;
+
+        let fragment_info = FragmentInfo::new(
+            fragment_key.clone(),
+            &valid_fragment_path,
+            fragment_size as u64,
+        );
+        self.store_fragment_info(fragment_info).await?;
+
+        Ok(fragment_key)
+    }
+
+    async fn load_fragment(&self, key: Key) -> Result<Option<FragmentData>> {
+        let fragment_info = self.load_fragment_info(key.clone()).await?;
+
+        if let None = fragment_info {
+            return Ok(None);
+        }
+
+        let fragment_info = fragment_info.unwrap();
+        let fragment_path = fragment_info.path;
+        let fragment_file = File::open(&fragment_path).await?;
+
+        Ok(Some(ReaderStream::new(fragment_file).boxed()))
+    }
+
+    async fn list_fragments(&self) -> Result<Vec<Key>> {
+        const SELECT_FRAGMENT_QUERY: &str = "SELECT hash0, hash1, hash2, hash3 FROM fragment";
+
+        self.db
+            .call(|conn| {
+                let mut stmt = conn.prepare(SELECT_FRAGMENT_QUERY)?;
+                let rows = stmt.query_map([], |row| {
+                    let key_i64s: [i64; 4] = [row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?];
+                    Ok(Key::from(key_i64s.map(|k| k as u64)))
+                })?;
+
+                let mut keys = Vec::new();
+                for key in rows {
+                    keys.push(key?);
+                }
+
+                Ok(keys)
+            })
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+
+    async fn load_fragment_info(&self, key: Key) -> Result<Option<FragmentInfo>> {
+        const SELECT_FRAGMENT_QUERY: &str = "
+            SELECT path, size
+            FROM fragment
+            WHERE hash0 = ?1 AND hash1 = ?2 AND hash2 = ?3 AND hash3 = ?4
+        ";
+
+        self.db
+            .call(move |conn| {
+                let mut stmt = conn.prepare(SELECT_FRAGMENT_QUERY)?;
+                let key_u64_slice = key.as_u64_slice_be();
+                let key_as_i64 = [
+                    key_u64_slice[0] as i64,
+                    key_u64_slice[1] as i64,
+                    key_u64_slice[2] as i64,
+                    key_u64_slice[3] as i64,
+                ];
+
+                let fragment = stmt
+                    .query_row(key_as_i64, |r| {
+                        let path: String = match r.get(0) {
+                            Ok(p) => p,
+                            Err(e) => return Result::Err(e),
+                        };
+
+                        let size: u64 = match r.get(1) {
+                            Ok(s) => s,
+                            Err(e) => return Result::Err(e),
+                        };
+
+                        Result::Ok(FragmentInfo::new(key, Path::new(&path), size))
+                    })
+                    .optional()?;
+
+                Ok(fragment)
+            })
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+
+    async fn store_fragment_info(&self, fragment: FragmentInfo) -> Result<()> {
+        const SELECT_FRAGMENT_QUERY: &str = "SELECT COUNT(*) FROM fragment WHERE hash0 = ?1 AND hash1 = ?2 AND hash2 = ?3 AND hash3 = ?4";
+        const INSERT_FRAGMENT_QUERY: &str = "
+                INSERT INTO fragment (hash0, hash1, hash2, hash3, path, size)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+            ";
+
+        let hash_as_u64 = fragment.hash.as_u64_slice_be();
+
+        let cnt = self
+            .db
+            .call(move |conn| {
+                let key_as_i64 = [
+                    hash_as_u64[0] as i64,
+                    hash_as_u64[1] as i64,
+                    hash_as_u64[2] as i64,
+                    hash_as_u64[3] as i64,
+                ];
+
+                let cnt = conn.query_row(SELECT_FRAGMENT_QUERY, key_as_i64, |r| {
+                    let cnt: usize = r.get(0)?;
+
+                    Ok(cnt)
+                })?;
+
+                Ok(cnt)
+            })
+            .await?;
+
+        if cnt != 0 {
+            return Ok(());
+        }
+
+        self.db
+            .call(move |conn| {
+                conn.execute(
+                    INSERT_FRAGMENT_QUERY,
+                    (
+                        hash_as_u64[0] as i64,
+                        hash_as_u64[1] as i64,
+                        hash_as_u64[2] as i64,
+                        hash_as_u64[3] as i64,
+                        fragment.path.to_str(),
+                        fragment.size,
+                    ),
+                )?;
+
+                Ok(())
+            })
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+
+    async fn ensure_dirs(vault_dir_path: &Path) -> Result<()> {
+        debug!(
+            path = vault_dir_path.display().to_string(),
+            "ensuring vault dir"
+        );
+        tokio::fs::create_dir_all(vault_dir_path).await?;
+
+        let fragment_dir_path = Self::fragment_dir_path(vault_dir_path);
+        debug!(
+            path = fragment_dir_path.display().to_string(),
+            "ensuring fragment dir"
+        );
+        tokio::fs::create_dir_all(fragment_dir_path).await?;
+
+        let temp_dir_path = Self::temp_dir_path(vault_dir_path);
+        debug!(
+            path = temp_dir_path.display().to_string(),
+            "ensuring temp dir"
+        );
+        tokio::fs::create_dir_all(temp_dir_path).await?;
+
+        Ok(())
+    }
+
+    fn fragment_dir_path(vault_dir_path: &Path) -> PathBuf {
+        vault_dir_path.join(Self::FRAGMENT_DIR_NAME)
+    }
+
+    fn temp_dir_path(vault_dir_path: &Path) -> PathBuf {
+        vault_dir_path.join(Self::TEMP_DIR_NAME)
+    }
+
+    fn default_db_path(base_path: &Path) -> PathBuf {
+        base_path.join(Self::DEFAULT_VAULT_DATABASE_NAME)
+    }
+}
+
+/// The object from the row with its compression tag
+fn decompress_object((uuid, compression, data): (Uuid, u8, Vec<u8>)) -> Result<TypedObject> {
+    let compressed = Compressed {
+        compression: Compression::from_tag(compression)?,
+        data,
+    };
+    Ok(TypedObject {
+        uuid,
+        data: compressed.decompress(proto::MAX_OBJECT_SIZE as usize)?,
+    })
+}
+
+impl VaultBackend for SqliteBackend {
+    fn prepare(&self) -> BoxFuture<'_, Result<()>> {
+        self.prepare_db().boxed()
+    }
+
+    fn store_typed_object(&self, key: Key, object: TypedObject) -> BoxFuture<'_, Result<()>> {
+        self.store_typed_object(key, object).boxed()
+    }
+
+    fn load_typed_object(&self, key: Key) -> BoxFuture<'_, Result<Option<TypedObject>>> {
+        self.load_typed_object(key).boxed()
+    }
+
+    fn delete_typed_object(&self, key: Key) -> BoxFuture<'_, Result<()>> {
+        self.delete_typed_object(key).boxed()
+    }
+
+    fn list_typed_objects(&self) -> BoxFuture<'_, Result<Vec<(Key, Uuid)>>> {
+        self.list_typed_objects().boxed()
+    }
+
+    fn list_typed_object_sizes(&self) -> BoxFuture<'_, Result<Vec<(Key, u64)>>> {
+        self.list_typed_object_sizes().boxed()
+    }
+
+    fn store_provenance(
+        &self,
+        key: Key,
+        provenance: ObjectProvenance,
+    ) -> BoxFuture<'_, Result<()>> {
+        self.store_provenance(key, provenance).boxed()
+    }
+
+    fn load_provenance(&self, key: Key) -> BoxFuture<'_, Result<Option<ObjectProvenance>>> {
+        self.load_provenance(key).boxed()
+    }
+
+    fn record_object_served(&self, key: Key, at: SystemTime) -> BoxFuture<'_, Result<()>> {
+        self.record_object_served(key, at).boxed()
+    }
+
+    fn load_object_popularity(&self) -> BoxFuture<'_, Result<Vec<(Key, u64, SystemTime)>>> {
+        self.load_object_popularity().boxed()
+    }
+
+    fn store_tags(&self, object: Key, tags: Vec<String>) -> BoxFuture<'_, Result<()>> {
+        self.store_tags(object, tags).boxed()
+    }
+
+    fn find_tagged(&self, tag_hash: Key) -> BoxFuture<'_, Result<Vec<(Key, String)>>> {
+        self.find_tagged(tag_hash).boxed()
+    }
+
+    fn index_text(&self, key: Key, name: String, text: String) -> BoxFuture<'_, Result<()>> {
+        self.index_text(key, name, text).boxed()
+    }
+
+    fn delete_text(&self, key: Key) -> BoxFuture<'_, Result<()>> {
+        self.delete_text(key).boxed()
+    }
+
+    fn clear_text_index(&self) -> BoxFuture<'_, Result<()>> {
+        self.clear_text_index().boxed()
+    }
+
+    fn search_text(
+        &self,
+        query: String,
+        limit: usize,
+    ) -> BoxFuture<'_, Result<Vec<(Key, String, String)>>> {
+        self.search_text(query, limit).boxed()
+    }
+
+    fn store_version(&self, version: Key, object: Key, previous: Key) -> BoxFuture<'_, Result<()>> {
+        self.store_version(version, object, previous).boxed()
+    }
+
+    fn delete_version(&self, version: Key) -> BoxFuture<'_, Result<()>> {
+        self.delete_version(version).boxed()
+    }
+
+    fn load_previous_version(&self, object: Key) -> BoxFuture<'_, Result<Option<Key>>> {
+        self.load_previous_version(object).boxed()
+    }
+
+    fn load_next_versions(&self, object: Key) -> BoxFuture<'_, Result<Vec<Key>>> {
+        self.load_next_versions(object).boxed()
+    }
+
+    fn store_published_object(&self, key: Key, object: TypedObject) -> BoxFuture<'_, Result<()>> {
+        self.store_published_object(key, object).boxed()
+    }
+
+    fn load_published_object(&self, key: Key) -> BoxFuture<'_, Result<Option<TypedObject>>> {
+        self.load_published_object(key).boxed()
+    }
+
+    fn delete_published_object(&self, key: Key) -> BoxFuture<'_, Result<()>> {
+        self.delete_published_object(key).boxed()
+    }
+
+    fn list_published_objects(&self) -> BoxFuture<'_, Result<Vec<Key>>> {
+        self.list_published_objects().boxed()
+    }
+
+    fn store_access_policy(&self, key: Key, policy: AccessPolicy) -> BoxFuture<'_, Result<()>> {
+        self.store_access_policy(key, policy).boxed()
+    }
+
+    fn load_access_policy(&self, key: Key) -> BoxFuture<'_, Result<Option<AccessPolicy>>> {
+        self.load_access_policy(key).boxed()
+    }
+
+    fn delete_access_policy(&self, key: Key) -> BoxFuture<'_, Result<()>> {
+        self.delete_access_policy(key).boxed()
+    }
+
+    fn store_partial_object(&self, key: Key, partial: PartialObject) -> BoxFuture<'_, Result<()>> {
+        self.store_partial_object(key, partial).boxed()
+    }
+
+    fn load_partial_object(&self, key: Key) -> BoxFuture<'_, Result<Option<PartialObject>>> {
+        self.load_partial_object(key).boxed()
+    }
+
+    fn store_partial_chunk(
+        &self,
+        key: Key,
+        index: u64,
+        data: Vec<u8>,
+    ) -> BoxFuture<'_, Result<()>> {
+        self.store_partial_chunk(key, index, data).boxed()
+    }
+
+    fn load_partial_chunks(&self, key: Key) -> BoxFuture<'_, Result<Vec<(u64, Vec<u8>)>>> {
+        self.load_partial_chunks(key).boxed()
+    }
+
+    fn delete_partial_object(&self, key: Key) -> BoxFuture<'_, Result<()>> {
+        self.delete_partial_object(key).boxed()
+    }
+
+    fn store_peer_score(&self, score: PeerScore) -> BoxFuture<'_, Result<()>> {
+        self.store_peer_score(score).boxed()
+    }
+
+    fn load_peer_scores(&self) -> BoxFuture<'_, Result<Vec<PeerScore>>> {
+        self.load_peer_scores().boxed()
+    }
+
+    fn delete_peer_score(&self, peer_id: String) -> BoxFuture<'_, Result<()>> {
+        self.delete_peer_score(peer_id).boxed()
+    }
+
+    fn store_contact(&self, contact: Contact) -> BoxFuture<'_, Result<()>> {
+        self.store_contact(contact).boxed()
+    }
+
+    fn load_contacts(&self) -> BoxFuture<'_, Result<Vec<Contact>>> {
+        self.load_contacts().boxed()
+    }
+
+    fn delete_contact(&self, peer_id: String) -> BoxFuture<'_, Result<bool>> {
+        self.delete_contact(peer_id).boxed()
+    }
+
+    fn touch_contact(
+        &self,
+        peer_id: String,
+        address: Option<String>,
+        seen_at: SystemTime,
+    ) -> BoxFuture<'_, Result<()>> {
+        self.touch_contact(peer_id, address, seen_at).boxed()
+    }
+
+    fn store_known_peer(&self, peer: KnownPeer) -> BoxFuture<'_, Result<()>> {
+        self.store_known_peer(peer).boxed()
+    }
+
+    fn load_known_peers(&self) -> BoxFuture<'_, Result<Vec<KnownPeer>>> {
+        self.load_known_peers().boxed()
+    }
+
+    fn delete_known_peer(&self, peer_id: String) -> BoxFuture<'_, Result<()>> {
+        self.delete_known_peer(peer_id).boxed()
+    }
+
+    fn store_message(&self, message: InboxMessage) -> BoxFuture<'_, Result<()>> {
+        self.store_message(message).boxed()
+    }
+
+    fn load_messages(&self) -> BoxFuture<'_, Result<Vec<InboxMessage>>> {
+        self.load_messages().boxed()
+    }
+
+    fn store_mailbox_entry(&self, entry: MailboxEntry) -> BoxFuture<'_, Result<()>> {
+        self.store_mailbox_entry(entry).boxed()
+    }
+
+    fn load_mailbox_entries(&self, recipient: String) -> BoxFuture<'_, Result<Vec<MailboxEntry>>> {
+        self.load_mailbox_entries(recipient).boxed()
+    }
+
+    fn mailbox_size(&self) -> BoxFuture<'_, Result<u64>> {
+        self.mailbox_size().boxed()
+    }
+
+    fn delete_mailbox_entries(
+        &self,
+        recipient: String,
+        ids: Vec<String>,
+    ) -> BoxFuture<'_, Result<usize>> {
+        self.delete_mailbox_entries(recipient, ids).boxed()
+    }
+
+    fn delete_expired_mailbox_entries(&self, before: SystemTime) -> BoxFuture<'_, Result<usize>> {
+        self.delete_expired_mailbox_entries(before).boxed()
+    }
+
+    fn store_group(&self, membership: GroupMembership) -> BoxFuture<'_, Result<()>> {
+        self.store_group(membership).boxed()
+    }
+
+    fn load_groups(&self) -> BoxFuture<'_, Result<Vec<GroupMembership>>> {
+        self.load_groups().boxed()
+    }
+
+    fn store_group_post(&self, post: GroupPost) -> BoxFuture<'_, Result<()>> {
+        self.store_group_post(post).boxed()
+    }
+
+    fn load_group_posts(&self, group: String) -> BoxFuture<'_, Result<Vec<GroupPost>>> {
+        self.load_group_posts(group).boxed()
+    }
+
+    fn append_audit_entry(&self, entry: AuditEntry) -> BoxFuture<'_, Result<()>> {
+        self.append_audit_entry(entry).boxed()
+    }
+
+    fn load_audit_log(&self, filter: AuditFilter) -> BoxFuture<'_, Result<Vec<AuditEntry>>> {
+        self.load_audit_log(filter).boxed()
+    }
+
+    fn store_stats_sample(&self, sample: NodeStatsSample) -> BoxFuture<'_, Result<()>> {
+        self.store_stats_sample(sample).boxed()
+    }
+
+    fn load_stats_samples(
+        &self,
+        since: Option<SystemTime>,
+        until: Option<SystemTime>,
+    ) -> BoxFuture<'_, Result<Vec<NodeStatsSample>>> {
+        self.load_stats_samples(since, until).boxed()
+    }
+
+    fn delete_stats_samples(&self, before: SystemTime) -> BoxFuture<'_, Result<usize>> {
+        self.delete_stats_samples(before).boxed()
+    }
+
+    fn store_fragment(&self, key: Option<Key>, data: FragmentData) -> BoxFuture<'_, Result<Key>> {
+        self.store_fragment(key, data).boxed()
+    }
+
+    fn load_fragment(&self, key: Key) -> BoxFuture<'_, Result<Option<FragmentData>>> {
+        self.load_fragment(key).boxed()
+    }
+
+    fn has_fragment(&self, key: Key) -> BoxFuture<'_, Result<bool>> {
+        async move { Ok(self.load_fragment_info(key).await?.is_some()) }.boxed()
+    }
+
+    fn list_fragments(&self) -> BoxFuture<'_, Result<Vec<Key>>> {
+        self.list_fragments().boxed()
+    }
+
+    fn flush(&self) -> BoxFuture<'_, Result<()>> {
+        self.write_queue.flush().map(Ok).boxed()
+    }
+}
+
+/// The key from the first four columns of the row, the parts of the hash
+fn key_from_row(row: &rusqlite::Row) -> rusqlite::Result<Key> {
+    let key_i64s: [i64; 4] = [row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?];
+    let key_u64s: [u64; 4] = [
+        key_i64s[0] as u64,
+        key_i64s[1] as u64,
+        key_i64s[2] as u64,
+        key_i64s[3] as u64,
+    ];
+
+    Ok(Key::from(key_u64s))
+}
+
+/// The words of the search as FTS5 strings, so the operators and the quotes in
+/// them are matched literally. The words must all match
+fn fts_query(query: &str) -> String {
+    query
+        .split_whitespace()
+        .map(|word| format!("\"{}\"", word.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Times are stored as seconds since the Unix epoch
+fn unix_secs(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+fn from_unix_secs(secs: i64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(secs as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn batched_writes_test() {
+        let tmp_dir = TempDir::new("liberum_tests").unwrap();
+        let backend = SqliteBackend::open(tmp_dir.path()).await.unwrap();
+        backend.prepare_db().await.unwrap();
+
+        let mut keys = Vec::new();
+        for i in 0..100u8 {
+            let key = Key::random();
+            let object = TypedObject {
+                uuid: Uuid::new_v4(),
+                data: vec![i],
+            };
+            backend.store_typed_object(key, object).await.unwrap();
+            keys.push(key);
+        }
+
+        // The last writes are still queued, the read flushes them
+        let last = backend.load_typed_object(keys[99]).await.unwrap();
+        assert_eq!(last.unwrap().data, vec![99]);
+
+        // Another connection to the same vault sees all the writes
+        let other = SqliteBackend::open(tmp_dir.path()).await.unwrap();
+        assert_eq!(other.list_typed_objects().await.unwrap().len(), 100);
+    }
+
+    #[tokio::test]
+    async fn provenance_test() {
+        let tmp_dir = TempDir::new("liberum_tests").unwrap();
+        let backend = SqliteBackend::open(tmp_dir.path()).await.unwrap();
+        backend.prepare_db().await.unwrap();
+        let key = Key::random();
+        let provenance = ObjectProvenance {
+            source_peer: Some("peer".to_string()),
+            received_at: from_unix_secs(1000),
+            kind: ProvenanceKind::Stored,
+            signature: Some(SignatureStatus::Verified),
+        };
+
+        // Nothing is recorded without the object
+        backend
+            .store_provenance(key, provenance.clone())
+            .await
+            .unwrap();
+        assert_eq!(backend.load_provenance(key).await.unwrap(), None);
+
+        let object = TypedObject {
+            uuid: Uuid::new_v4(),
+            data: vec![1],
+        };
+        backend.store_typed_object(key, object).await.unwrap();
+        assert_eq!(backend.load_provenance(key).await.unwrap(), None);
+        backend
+            .store_provenance(key, provenance.clone())
+            .await
+            .unwrap();
+        // The first provenance is kept
+        let later = ObjectProvenance {
+            source_peer: None,
+            received_at: from_unix_secs(2000),
+            kind: ProvenanceKind::Downloaded,
+            signature: None,
+        };
+        backend.store_provenance(key, later).await.unwrap();
+        assert_eq!(
+            backend.load_provenance(key).await.unwrap(),
+            Some(provenance)
+        );
+    }
+
+    #[tokio::test]
+    async fn compressed_objects_test() {
+        let tmp_dir = TempDir::new("liberum_tests").unwrap();
+        // The table of an older version, without the compression
+        let db = Connection::open(SqliteBackend::default_db_path(tmp_dir.path()))
+            .await
+            .unwrap();
+        db.call(|conn| {
+            conn.execute(
+                "CREATE TABLE typed_object (
+                    hash0 INTEGER NOT NULL,
+                    hash1 INTEGER NOT NULL,
+                    hash2 INTEGER NOT NULL,
+                    hash3 INTEGER NOT NULL,
+                    type_id TEXT,
+                    data BLOB,
+                    PRIMARY KEY (hash0, hash1, hash2, hash3)
+                )",
+                (),
+            )?;
+            conn.execute(
+                "INSERT INTO typed_object VALUES (1, 2, 3, 4, ?1, x'0102')",
+                [Uuid::nil().to_string()],
+            )?;
+            Ok(())
+        })
+        .await
+        .unwrap();
+
+        let backend = SqliteBackend::open(tmp_dir.path()).await.unwrap();
+        backend.prepare_db().await.unwrap();
+        let old = backend.load_typed_object(Key::from([1u64, 2, 3, 4])).await;
+        assert_eq!(old.unwrap().unwrap().data, vec![1, 2]);
+
+        let text = TypedObject {
+            uuid: Uuid::new_v4(),
+            data: "lorem ipsum dolor sit amet ".repeat(1000).into_bytes(),
+        };
+        let key = Key::random();
+        backend.store_typed_object(key, text.clone()).await.unwrap();
+        let disabled = backend.with_compression(CompressionConfig::disabled());
+        let other_key = Key::random();
+        disabled
+            .store_typed_object(other_key, text.clone())
+            .await
+            .unwrap();
+        assert_eq!(
+            disabled.load_typed_object(key).await.unwrap(),
+            Some(text.clone())
+        );
+        assert_eq!(
+            disabled.load_typed_object(other_key).await.unwrap(),
+            Some(text.clone())
+        );
+
+        let sizes = disabled
+            .db
+            .call(|conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT compression, length(data) FROM typed_object ORDER BY compression, length(data)",
+                )?;
+                let rows =
+                    stmt.query_map([], |r| Ok((r.get::<_, u8>(0)?, r.get::<_, usize>(1)?)))?;
+                Ok(rows.collect::<Result<Vec<_>, _>>()?)
+            })
+            .await
+            .unwrap();
+        assert_eq!(sizes[2].0, Compression::Zstd.tag());
+        assert!(sizes[2].1 * 10 < text.data.len());
+        assert_eq!(sizes[1], (Compression::None.tag(), text.data.len()));
+    }
+
+    #[tokio::test]
+    async fn contacts_test() {
+        let tmp_dir = TempDir::new("liberum_tests").unwrap();
+        let backend = SqliteBackend::open(tmp_dir.path()).await.unwrap();
+        backend.prepare_db().await.unwrap();
+
+        let contact = Contact {
+            peer_id: "peer".to_string(),
+            alias: "alice".to_string(),
+            address: None,
+            last_seen: None,
+            trust: TrustLevel::Trusted,
+        };
+        backend.store_contact(contact.clone()).await.unwrap();
+
+        // Unknown peers are not added by seeing them
+        let seen_at = UNIX_EPOCH + Duration::from_secs(1000);
+        let address = Some("/ip4/1.2.3.4/udp/1234/quic-v1".to_string());
+        backend
+            .touch_contact("other".to_string(), None, seen_at)
+            .await
+            .unwrap();
+        backend
+            .touch_contact("peer".to_string(), address.clone(), seen_at)
+            .await
+            .unwrap();
+        backend
+            .touch_contact("peer".to_string(), None, seen_at)
+            .await
+            .unwrap();
+
+        let contacts = backend.load_contacts().await.unwrap();
+        assert_eq!(
+            contacts,
+            vec![Contact {
+                address,
+                last_seen: Some(seen_at),
+                ..contact
+            }]
+        );
+
+        assert!(backend.delete_contact("peer".to_string()).await.unwrap());
+        assert!(!backend.delete_contact("peer".to_string()).await.unwrap());
+        assert!(backend.load_contacts().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn known_peers_test() {
+        let tmp_dir = TempDir::new("liberum_tests").unwrap();
+        let backend = SqliteBackend::open(tmp_dir.path()).await.unwrap();
+        backend.prepare_db().await.unwrap();
+
+        let peer = KnownPeer {
+            peer_id: "peer".to_string(),
+            addresses: vec![
+                "/ip4/1.2.3.4/udp/1234/quic-v1".to_string(),
+                "/ip6/::1/udp/1234/quic-v1".to_string(),
+            ],
+            last_success: UNIX_EPOCH + Duration::from_secs(1000),
+            failures: 0,
+        };
+        backend.store_known_peer(peer.clone()).await.unwrap();
+        let failed = KnownPeer {
+            failures: 1,
+            ..peer.clone()
+        };
+        backend.store_known_peer(failed.clone()).await.unwrap();
+        assert_eq!(backend.load_known_peers().await.unwrap(), vec![failed]);
+
+        backend.store_known_peer(peer).await.unwrap();
+        backend.delete_known_peer("peer".to_string()).await.unwrap();
+        assert!(backend.load_known_peers().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn audit_log_test() {
+        let tmp_dir = TempDir::new("liberum_tests").unwrap();
+        let backend = SqliteBackend::open(tmp_dir.path()).await.unwrap();
+        backend.prepare_db().await.unwrap();
+
+        let entry = |peer: &str, kind, rejection: Option<&str>, secs| AuditEntry {
+            peer_id: peer.to_string(),
+            object_id: "object".to_string(),
+            kind,
+            rejection: rejection.map(str::to_string),
+            at: UNIX_EPOCH + Duration::from_secs(secs),
+        };
+        let entries = vec![
+            entry("a", AuditRequestKind::Store, None, 1000),
+            entry("b", AuditRequestKind::Get, Some("AccessDenied"), 2000),
+            entry("a", AuditRequestKind::Get, None, 3000),
+        ];
+        for e in &entries {
+            backend.append_audit_entry(e.clone()).await.unwrap();
+        }
+
+        let all = backend
+            .load_audit_log(AuditFilter::default())
+            .await
+            .unwrap();
+        assert_eq!(all, entries);
+
+        let filter = AuditFilter {
+            kind: Some(AuditRequestKind::Get),
+            ..Default::default()
+        };
+        let gets = backend.load_audit_log(filter.clone()).await.unwrap();
+        assert_eq!(gets, entries[1..]);
+
+        let rejected = AuditFilter {
+            rejected_only: true,
+            ..filter.clone()
+        };
+        assert_eq!(
+            backend.load_audit_log(rejected).await.unwrap(),
+            entries[1..2]
+        );
+
+        // The limit keeps the newest entries
+        let newest = AuditFilter {
+            limit: Some(1),
+            ..filter
+        };
+        assert_eq!(backend.load_audit_log(newest).await.unwrap(), entries[2..]);
+    }
+
+    #[tokio::test]
+    async fn stats_samples_test() {
+        let tmp_dir = TempDir::new("liberum_tests").unwrap();
+        let backend = SqliteBackend::open(tmp_dir.path()).await.unwrap();
+        backend.prepare_db().await.unwrap();
+
+        let at = |secs| UNIX_EPOCH + Duration::from_secs(secs);
+        let samples: Vec<NodeStatsSample> = (1..=3)
+            .map(|i| NodeStatsSample {
+                at: at(i * 1000),
+                connected_peers: i as usize,
+                bytes_sent: i * 100,
+                bytes_received: i * 200,
+                objects_served: i,
+            })
+            .collect();
+        for sample in &samples {
+            backend.store_stats_sample(*sample).await.unwrap();
+        }
+
+        assert_eq!(
+            backend.load_stats_samples(None, None).await.unwrap(),
+            samples
+        );
+        assert_eq!(
+            backend
+                .load_stats_samples(Some(at(2000)), Some(at(2500)))
+                .await
+                .unwrap(),
+            samples[1..2]
+        );
+        assert_eq!(backend.delete_stats_samples(at(3000)).await.unwrap(), 2);
+        assert_eq!(
+            backend.load_stats_samples(None, None).await.unwrap(),
+            samples[2..]
+        );
+    }
+}
@@ -0,0 +1,290 @@
+use std::sync::{Arc, Weak};
+use std::time::{Duration, UNIX_EPOCH};
+
+use anyhow::{anyhow, Result};
+use liberum_core::compression::Compressed;
+use liberum_core::types::PeerScore;
+use tokio::sync::{oneshot, Mutex};
+use tokio_rusqlite::Connection;
+use tracing::{debug, error};
+use uuid::Uuid;
+
+use crate::vault::fragment::key::Key;
+
+///! The writes of the SQLite backend are queued and written in batches, each batch
+///! in a single transaction. The queue is flushed when it is full, periodically and
+///! before every read, so the readers always see the queued writes.
+///!
+///! The writes others rely on, like the objects stored for the peers, wait for their
+///! batch to be committed and get their own results. The others, like the peer
+///! scores, are written in the background and their failures are only logged. If a
+///! batch fails, its writes are tried one by one, so only the failing ones are lost.
+
+/// Number of queued writes which makes the queue flush immediately
+pub const WRITE_BATCH_SIZE: usize = 64;
+/// How often the queued writes are flushed if the queue does not fill up
+pub const WRITE_FLUSH_INTERVAL: Duration = Duration::from_millis(200);
+
+pub enum PendingWrite {
+    /// The object with its compressed payload
+    TypedObject(Key, Uuid, Compressed),
+    PublishedObject(Key, Uuid, Compressed),
+    PeerScore(PeerScore),
+    /// The addresses separated by the new lines
+    KnownPeer {
+        peer_id: String,
+        addresses: String,
+        last_success: i64,
+        failures: u32,
+    },
+    /// Updates only an existing object without a provenance
+    Provenance {
+        key: Key,
+        source_peer: Option<String>,
+        received_at: i64,
+        kind: String,
+        signature_verified: Option<bool>,
+    },
+    /// Updates only an existing contact, the address is kept if None
+    ContactSeen {
+        peer_id: String,
+        address: Option<String>,
+        last_seen: i64,
+    },
+}
+
+/// Gets the result of the write once its batch is written
+type WriteResult = oneshot::Sender<Result<()>>;
+
+pub struct WriteQueue {
+    db: Connection,
+    // Locked for the whole flush, so a reader waits for the batch being written
+    pending: Mutex<Vec<(PendingWrite, Option<WriteResult>)>>,
+}
+
+impl WriteQueue {
+    /// Creates the queue together with the task flushing it periodically. The task
+    /// ends when the queue is dropped
+    pub fn start(db: Connection) -> Arc<WriteQueue> {
+        let queue = Arc::new(WriteQueue {
+            db,
+            pending: Mutex::new(Vec::new()),
+        });
+        tokio::spawn(Self::flush_periodically(Arc::downgrade(&queue)));
+
+        queue
+    }
+
+    /// Queues the write to be written in the background, its failure is logged
+    pub async fn push(&self, write: PendingWrite) {
+        let mut pending = self.pending.lock().await;
+        pending.push((write, None));
+        if pending.len() >= WRITE_BATCH_SIZE {
+            self.write_batch(&mut pending).await;
+        }
+    }
+
+    /// Writes the write with the queued ones. Ok once it's committed
+    pub async fn write(&self, write: PendingWrite) -> Result<()> {
+        let (sender, receiver) = oneshot::channel();
+        let mut pending = self.pending.lock().await;
+        pending.push((write, Some(sender)));
+        self.write_batch(&mut pending).await;
+        drop(pending);
+
+        receiver
+            .await
+            .unwrap_or_else(|_| Err(anyhow!("The vault write was dropped")))
+    }
+
+    /// Writes all the queued writes
+    pub async fn flush(&self) {
+        let mut pending = self.pending.lock().await;
+        self.write_batch(&mut pending).await;
+    }
+
+    /// Writes the batch and sends every writer its own result
+    async fn write_batch(&self, pending: &mut Vec<(PendingWrite, Option<WriteResult>)>) {
+        if pending.is_empty() {
+            return;
+        }
+
+        let (writes, senders): (Vec<_>, Vec<_>) = std::mem::take(pending).into_iter().unzip();
+        let batch_len = writes.len();
+        let results = match self.db.call(move |conn| Ok(write_all(conn, &writes))).await {
+            Ok(results) => results
+                .into_iter()
+                .map(|result| result.map_err(|e| anyhow!(e)))
+                .collect(),
+            Err(e) => {
+                let e = e.to_string();
+                (0..batch_len).map(|_| Err(anyhow!("{e}"))).collect()
+            }
+        };
+
+        let mut failed = 0;
+        for (result, sender) in results.into_iter().zip(senders) {
+            match (result, sender) {
+                (result, Some(sender)) => {
+                    failed += result.is_err() as usize;
+                    let _ = sender.send(result);
+                }
+                (Err(e), None) => {
+                    failed += 1;
+                    error!(err = e.to_string(), "Failed to write to the vault");
+                }
+                (Ok(()), None) => {}
+            }
+        }
+        debug!(
+            writes = batch_len,
+            failed = failed,
+            "Vault write batch flushed"
+        );
+    }
+
+    async fn flush_periodically(queue: Weak<WriteQueue>) {
+        loop {
+            tokio::time::sleep(WRITE_FLUSH_INTERVAL).await;
+            let Some(queue) = queue.upgrade() else {
+                return;
+            };
+            queue.flush().await;
+        }
+    }
+}
+
+/// Writes the batch in one transaction. If it fails, the writes are tried one by one
+/// in their order, so only the failing ones are lost
+fn write_all(
+    conn: &mut rusqlite::Connection,
+    writes: &[PendingWrite],
+) -> Vec<rusqlite::Result<()>> {
+    let batch = conn.transaction().and_then(|tx| {
+        for write in writes {
+            write.execute(&tx)?;
+        }
+        tx.commit()
+    });
+    match batch {
+        Ok(()) => writes.iter().map(|_| Ok(())).collect(),
+        Err(_) => writes.iter().map(|write| write.execute(conn)).collect(),
+    }
+}
+
+impl PendingWrite {
+    fn execute(&self, conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+        // Objects are immutable, an already stored one is kept
+        const INSERT_TYPED_OBJECT_QUERY: &str =
+            "INSERT OR IGNORE INTO typed_object (hash0, hash1, hash2, hash3, type_id, data, compression)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)";
+        const UPSERT_PUBLISHED_OBJECT_QUERY: &str = "
+            INSERT OR REPLACE INTO published_object (hash0, hash1, hash2, hash3, type_id, data, compression)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+        ";
+        const UPSERT_PEER_SCORE_QUERY: &str = "
+            INSERT OR REPLACE INTO peer_score (peer_id, failed_integrity_checks, timeouts, protocol_violations, last_reported_at)
+            VALUES (?1, ?2, ?3, ?4, ?5)
+        ";
+        const UPSERT_KNOWN_PEER_QUERY: &str = "
+            INSERT OR REPLACE INTO known_peer (peer_id, addresses, last_success, failures)
+            VALUES (?1, ?2, ?3, ?4)
+        ";
+
+        // Queued after the object, so it's already inserted when this runs
+        const UPDATE_PROVENANCE_QUERY: &str = "
+            UPDATE typed_object
+            SET source_peer = ?5, received_at = ?6, request_kind = ?7, signature_verified = ?8
+            WHERE hash0 = ?1 AND hash1 = ?2 AND hash2 = ?3 AND hash3 = ?4 AND received_at IS NULL
+        ";
+        const UPDATE_CONTACT_SEEN_QUERY: &str = "
+            UPDATE contact SET last_seen = ?2, address = COALESCE(?3, address)
+            WHERE peer_id = ?1
+        ";
+
+        let (query, key, uuid, object) = match self {
+            PendingWrite::TypedObject(key, uuid, object) => {
+                (INSERT_TYPED_OBJECT_QUERY, key, uuid, object)
+            }
+            PendingWrite::PublishedObject(key, uuid, object) => {
+                (UPSERT_PUBLISHED_OBJECT_QUERY, key, uuid, object)
+            }
+            PendingWrite::PeerScore(score) => {
+                let last_reported_at = score
+                    .last_reported_at
+                    .and_then(|at| at.duration_since(UNIX_EPOCH).ok())
+                    .map(|d| d.as_secs() as i64);
+                conn.execute(
+                    UPSERT_PEER_SCORE_QUERY,
+                    (
+                        &score.peer_id,
+                        score.failed_integrity_checks,
+                        score.timeouts,
+                        score.protocol_violations,
+                        last_reported_at,
+                    ),
+                )?;
+                return Ok(());
+            }
+            PendingWrite::KnownPeer {
+                peer_id,
+                addresses,
+                last_success,
+                failures,
+            } => {
+                conn.execute(
+                    UPSERT_KNOWN_PEER_QUERY,
+                    (peer_id, addresses, last_success, failures),
+                )?;
+                return Ok(());
+            }
+            PendingWrite::Provenance {
+                key,
+                source_peer,
+                received_at,
+                kind,
+                signature_verified,
+            } => {
+                let key_u64 = key.as_u64_slice_be();
+                conn.execute(
+                    UPDATE_PROVENANCE_QUERY,
+                    (
+                        key_u64[0] as i64,
+                        key_u64[1] as i64,
+                        key_u64[2] as i64,
+                        key_u64[3] as i64,
+                        source_peer,
+                        received_at,
+                        kind,
+                        signature_verified,
+                    ),
+                )?;
+                return Ok(());
+            }
+            PendingWrite::ContactSeen {
+                peer_id,
+                address,
+                last_seen,
+            } => {
+                conn.execute(UPDATE_CONTACT_SEEN_QUERY, (peer_id, last_seen, address))?;
+                return Ok(());
+            }
+        };
+
+        let key_u64 = key.as_u64_slice_be();
+        conn.execute(
+            query,
+            (
+                key_u64[0] as i64,
+                key_u64[1] as i64,
+                key_u64[2] as i64,
+                key_u64[3] as i64,
+                uuid.to_string(),
+                &object.data,
+                object.compression.tag(),
+            ),
+        )?;
+
+        Ok(())
+    }
+}
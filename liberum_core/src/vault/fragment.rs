@@ -1,4 +1,5 @@
 pub mod key;
+pub mod memory;
 
 use std::path::{Path, PathBuf};
 
@@ -4,7 +4,7 @@ use anyhow::anyhow;
 use anyhow::{Error, Result};
 use rand::Rng;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Eq, Hash)]
 pub struct Key {
     value_bytes: [u8; 32],
 }
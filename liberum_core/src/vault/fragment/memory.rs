@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+
+use anyhow::{bail, Result};
+use tokio_util::bytes::Bytes;
+
+use super::key::Key;
+
+///! The fragments of the in-memory vault. They are kept in a map, the total size of
+///! the fragments is limited, so a long running node without the disk does not use
+///! up all the memory.
+
+/// Default limit of the total size of the fragments kept in memory
+pub const DEFAULT_MEMORY_FRAGMENTS_CAPACITY: usize = 64 * 1024 * 1024;
+
+#[derive(Debug)]
+pub struct MemoryFragments {
+    fragments: HashMap<Key, Bytes>,
+    size: usize,
+    capacity: usize,
+}
+
+impl MemoryFragments {
+    pub fn new(capacity: usize) -> MemoryFragments {
+        MemoryFragments {
+            fragments: HashMap::new(),
+            size: 0,
+            capacity,
+        }
+    }
+
+    /// Stores the fragment, unless it is already stored. Fails if the fragment
+    /// does not fit in the capacity
+    pub fn insert(&mut self, key: Key, data: Bytes) -> Result<()> {
+        if self.fragments.contains_key(&key) {
+            return Ok(());
+        }
+        if self.size + data.len() > self.capacity {
+            bail!(
+                "Fragment {key} of size {} does not fit in memory, {} of {} bytes used",
+                data.len(),
+                self.size,
+                self.capacity
+            );
+        }
+
+        self.size += data.len();
+        self.fragments.insert(key, data);
+
+        Ok(())
+    }
+
+    pub fn get(&self, key: &Key) -> Option<Bytes> {
+        self.fragments.get(key).cloned()
+    }
+
+    pub fn contains(&self, key: &Key) -> bool {
+        self.fragments.contains_key(key)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Key, &Bytes)> {
+        self.fragments.iter()
+    }
+}
+
+impl Default for MemoryFragments {
+    fn default() -> Self {
+        MemoryFragments::new(DEFAULT_MEMORY_FRAGMENTS_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_fragments_capacity_test() {
+        let mut fragments = MemoryFragments::new(10);
+        let key = Key::random();
+        fragments.insert(key, Bytes::from(vec![1; 6])).unwrap();
+        // Storing the same fragment again does not count twice
+        fragments.insert(key, Bytes::from(vec![1; 6])).unwrap();
+        assert_eq!(fragments.size, 6);
+
+        assert!(fragments
+            .insert(Key::random(), Bytes::from(vec![2; 6]))
+            .is_err());
+        assert!(fragments.contains(&key));
+        assert_eq!(fragments.get(&key).unwrap().len(), 6);
+    }
+}
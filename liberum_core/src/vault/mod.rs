@@ -1,17 +1,22 @@
+pub mod backend;
 pub mod fragment;
+pub mod snapshot;
 
 use std::cmp;
+use std::collections::{HashMap, HashSet};
 use std::iter::once;
 use std::iter::successors;
 use std::path::Path;
-use std::path::PathBuf;
-use std::str::FromStr;
+use std::time::SystemTime;
 
 use anyhow::anyhow;
-use anyhow::bail;
 use anyhow::Result;
+use backend::memory::MemoryBackend;
+use backend::sqlite::SqliteBackend;
+use backend::{GroupMembership, MailboxEntry, VaultBackend};
+pub use backend::{KnownPeer, PartialObject};
 use fragment::key::Key;
-use fragment::FragmentInfo;
+use fragment::memory::DEFAULT_MEMORY_FRAGMENTS_CAPACITY;
 use futures::stream::BoxStream;
 use futures::StreamExt;
 use kameo::mailbox::bounded::BoundedMailbox;
@@ -19,32 +24,44 @@ use kameo::message::Context;
 use kameo::message::Message;
 use kameo::messages;
 use kameo::Actor;
+use liberum_core::node_config::{CompressionConfig, TextIndexConfig};
+use liberum_core::parser::parse_typed;
 use liberum_core::parser::ObjectEnum;
+use liberum_core::proto::AccessPolicy;
 use liberum_core::proto::Hash;
+use liberum_core::proto::PlainFileObject;
+use liberum_core::proto::SignedObject;
 use liberum_core::proto::TypedObject;
+use liberum_core::proto::VersionObject;
+use liberum_core::types::AuditEntry;
+use liberum_core::types::AuditFilter;
+use liberum_core::types::Contact;
+use liberum_core::types::GroupPost;
+use liberum_core::types::InboxMessage;
+use liberum_core::types::NodeStatsSample;
+use liberum_core::types::ObjectPopularity;
+use liberum_core::types::ObjectProvenance;
+use liberum_core::types::PeerScore;
+use liberum_core::types::ProvenanceKind;
+use liberum_core::types::SignatureStatus;
+use liberum_core::types::TextMatch;
 use liberum_core::types::TypedObjectInfo;
-use rusqlite::params_from_iter;
-use rusqlite::OptionalExtension;
-use tokio::fs::remove_file;
+use libp2p::PeerId;
 use tokio::fs::File;
 use tokio::io;
 use tokio::io::AsyncReadExt;
 use tokio::io::AsyncSeekExt;
-use tokio::io::AsyncWriteExt;
 use tokio::io::BufReader;
-use tokio_rusqlite::Connection;
 use tokio_util::bytes::Bytes;
 use tokio_util::io::ReaderStream;
 use tracing::debug;
-use uuid::Uuid;
 
 pub struct Vault {
-    db: Connection,
-    // None will cause Vault to store data in memory
-    vault_dir_path: Option<PathBuf>,
+    backend: Box<dyn VaultBackend>,
+    text_index: TextIndexConfig,
 }
 
-type FragmentData = BoxStream<'static, Result<Bytes, io::Error>>;
+pub type FragmentData = BoxStream<'static, Result<Bytes, io::Error>>;
 
 pub struct LoadFragment(Key);
 
@@ -55,7 +72,17 @@ impl Actor for Vault {
         &mut self,
         _: kameo::actor::ActorRef<Self>,
     ) -> std::result::Result<(), kameo::error::BoxError> {
-        self.prepare_db().await?;
+        self.backend.prepare().await?;
+
+        Ok(())
+    }
+
+    async fn on_stop(
+        &mut self,
+        _: kameo::actor::WeakActorRef<Self>,
+        _: kameo::error::ActorStopReason,
+    ) -> std::result::Result<(), kameo::error::BoxError> {
+        self.backend.flush().await?;
 
         Ok(())
     }
@@ -64,59 +91,31 @@ impl Actor for Vault {
 #[messages]
 impl Vault {
     #[message]
-    async fn store_fragment(&self, key: Option<Key>, mut data: FragmentData) -> Result<Key> {
-        let uid = Uuid::new_v4();
-        // TODO: Storing fragments in memory not supported
-        let random_fragment_path =
-            Self::temp_dir_path(self.vault_dir_path.as_ref().unwrap()).join(uid.to_string());
-        let mut fragment_file = File::create(&random_fragment_path).await?;
-        let mut hasher = blake3::Hasher::new();
-        let mut fragment_size = 0;
-
-        while let Some(bytes) = data.next().await {
-            let bytes = bytes?;
-            hasher.update(&bytes);
-            fragment_file.write(&bytes).await?;
-            fragment_size += bytes.len();
-        }
-
-        let key_bytes = hasher.finalize().as_bytes().to_vec();
-        let fragment_key = Key::try_from(key_bytes.clone())?;
-
-        // Verify integrity if key was provided
-        if let Some(key) = key {
-            if key != fragment_key {
-                remove_file(random_fragment_path).await?;
-                bail!(
-                    "Fragment integrity check failed, expected key to be {key}, was {fragment_key}"
-                );
-            }
-        }
-
-        let key_string = bs58::encode(&key_bytes).into_string();
-        // TODO: Storing fragments in memory not supported
-        let valid_fragment_path =
-            Self::fragment_dir_path(self.vault_dir_path.as_ref().unwrap()).join(key_string);
-        tokio::fs::rename(random_fragment_path, &valid_fragment_path).await?;
-
-        let fragment_info = FragmentInfo::new(
-            fragment_key.clone(),
-            &valid_fragment_path,
-            fragment_size as u64,
-        );
-        self.store_fragment_info(fragment_info).await?;
-
-        Ok(fragment_key)
+    async fn store_fragment(&self, key: Option<Key>, data: FragmentData) -> Result<Key> {
+        self.backend.store_fragment(key, data).await
     }
 
     #[message]
     pub async fn store_object(&self, hash: Hash, object: ObjectEnum) -> Result<()> {
+        self.store_object_from(hash, object, None).await
+    }
+
+    /// Stores the object sent by the source peer, None if the node made it itself
+    async fn store_object_from(
+        &self,
+        hash: Hash,
+        object: ObjectEnum,
+        source: Option<PeerId>,
+    ) -> Result<()> {
         let key: Key = hash.bytes.into();
 
         match object {
             ObjectEnum::Empty(_) => {}
             ObjectEnum::Typed(typed_object) => {
-                self.store_typed_object(key, typed_object).await?;
+                self.backend
+                    .store_typed_object(key, typed_object.clone())
+                    .await?;
+                self.index_object(key, typed_object, source).await?;
             }
             _ => return Result::Err(anyhow!("Storing this object type is not supported!")),
         }
@@ -124,82 +123,501 @@ impl Vault {
         return Ok(());
     }
 
+    /// Stores the object with where it came from, the source is None if the node
+    /// made the object itself. The signature of a signed object is checked against
+    /// the key of the source peer
+    #[message]
+    pub async fn store_received_object(
+        &self,
+        hash: Hash,
+        object: TypedObject,
+        kind: ProvenanceKind,
+        source: Option<PeerId>,
+    ) -> Result<()> {
+        let signature = match source {
+            Some(peer) if object.uuid == SignedObject::UUID => Some(
+                TypedObject::try_from_typed::<SignedObject>(&object)
+                    .map_or(SignatureStatus::Unverified, |signed| {
+                        signed.verify_peer(&peer)
+                    }),
+            ),
+            _ => None,
+        };
+        let provenance = ObjectProvenance {
+            source_peer: source.map(|peer| peer.to_base58()),
+            received_at: SystemTime::now(),
+            kind,
+            signature,
+        };
+
+        self.store_object_from(hash.clone(), ObjectEnum::Typed(object), source)
+            .await?;
+        self.backend
+            .store_provenance(hash.bytes.into(), provenance)
+            .await
+    }
+
+    #[message]
+    pub async fn load_provenance(&self, hash: Hash) -> Result<Option<ObjectProvenance>> {
+        self.backend.load_provenance(hash.bytes.into()).await
+    }
+
     #[message]
     pub async fn load_object(&self, hash: Hash) -> Result<Option<ObjectEnum>> {
         let key: Key = hash.bytes.into();
 
-        self.load_typed_object(key)
+        self.backend
+            .load_typed_object(key)
             .await
             .map(|r| r.map(|o| ObjectEnum::Typed(o)))
     }
 
     #[message]
     pub async fn list_typed_objects(&self) -> Result<Vec<TypedObjectInfo>> {
-        const SELECT_TYPED_OBJECT_QUERY: &str = "
-            SELECT hash0, hash1, hash2, hash3, type_id
-            FROM typed_object;
-        ";
-
-        let object_infos = self
-            .db
-            .call(|conn| {
-                let mut stmt = conn.prepare(SELECT_TYPED_OBJECT_QUERY)?;
-                let rows = stmt.query_map([], |row| {
-                    let key_i64s: [i64; 4] = [row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?];
-                    let key_u64s: [u64; 4] = [
-                        key_i64s[0] as u64,
-                        key_i64s[1] as u64,
-                        key_i64s[2] as u64,
-                        key_i64s[3] as u64,
-                    ];
-
-                    let key = Key::from(key_u64s);
-                    let type_id_str: String = row.get(4)?;
-                    let type_id = Uuid::from_str(&type_id_str).expect("type id to be correct");
-
-                    Ok(TypedObjectInfo {
-                        id: key.to_string(),
-                        type_id,
-                    })
-                })?;
-
-                let mut objects = Vec::new();
-                for obj in rows {
-                    objects.push(obj?);
-                }
+        let objects = self.backend.list_typed_objects().await?;
 
-                Ok(objects)
+        Ok(objects
+            .into_iter()
+            .map(|(key, type_id)| TypedObjectInfo {
+                id: key.to_string(),
+                type_id,
             })
-            .await?;
-
-        Ok(object_infos)
+            .collect())
     }
 
+    /// Deletes the object, with its access policy if the node did not publish it
     #[message]
     pub async fn delete_typed_object(&self, hash: Hash) -> Result<()> {
-        const DELETE_TYPED_OBJECT_QUERY: &str = "
-            DELETE FROM typed_object
-            WHERE hash0 = ?1 AND hash1 = ?2 AND hash2 = ?3 AND hash3 = ?4
-        ";
-
-        self.db
-            .call(move |conn| {
-                let key_u64: [u64; 4] = Key::from(hash.bytes).into();
-                let key_i64: [i64; 4] = [
-                    key_u64[0] as i64,
-                    key_u64[1] as i64,
-                    key_u64[2] as i64,
-                    key_u64[3] as i64,
-                ];
-
-                conn.execute(DELETE_TYPED_OBJECT_QUERY, params_from_iter(key_i64))?;
-
-                Ok(())
-            })
+        let key: Key = hash.bytes.into();
+        self.backend.delete_typed_object(key).await?;
+        if self.backend.load_published_object(key).await?.is_none() {
+            self.backend.delete_access_policy(key).await?;
+            self.backend.delete_text(key).await?;
+            self.backend.delete_version(key).await?;
+        }
+        Ok(())
+    }
+
+    #[message]
+    pub async fn store_peer_score(&self, score: PeerScore) -> Result<()> {
+        self.backend.store_peer_score(score).await
+    }
+
+    #[message]
+    pub async fn load_peer_scores(&self) -> Result<Vec<PeerScore>> {
+        self.backend.load_peer_scores().await
+    }
+
+    #[message]
+    pub async fn delete_peer_score(&self, peer_id: String) -> Result<()> {
+        self.backend.delete_peer_score(peer_id).await
+    }
+
+    /// Remembers an object published by this node, so it can be replicated later
+    #[message]
+    pub async fn store_published_object(&self, hash: Hash, object: TypedObject) -> Result<()> {
+        let key: Key = hash.bytes.into();
+        self.backend
+            .store_published_object(key, object.clone())
             .await?;
+        self.index_object(key, object, None).await
+    }
+
+    #[message]
+    pub async fn list_published_objects(&self) -> Result<Vec<Hash>> {
+        let keys = self.backend.list_published_objects().await?;
+
+        Ok(keys
+            .into_iter()
+            .map(|key| Hash {
+                bytes: key.as_u8_slice_be(),
+            })
+            .collect())
+    }
+
+    #[message]
+    pub async fn load_published_object(&self, hash: Hash) -> Result<Option<TypedObject>> {
+        self.backend.load_published_object(hash.bytes.into()).await
+    }
 
+    /// Deletes the published object, with its access policy if the node does not
+    /// store the object for others
+    #[message]
+    pub async fn delete_published_object(&self, hash: Hash) -> Result<()> {
+        let key: Key = hash.bytes.into();
+        self.backend.delete_published_object(key).await?;
+        if self.backend.load_typed_object(key).await?.is_none() {
+            self.backend.delete_access_policy(key).await?;
+            self.backend.delete_text(key).await?;
+            self.backend.delete_version(key).await?;
+        }
+        Ok(())
+    }
+
+    /// Applies to the files stored from now on. Turning the index off removes what
+    /// was indexed
+    #[message]
+    pub async fn set_text_index(&mut self, config: TextIndexConfig) -> Result<()> {
+        self.text_index = config;
+        if !config.enabled {
+            self.backend.clear_text_index().await?;
+        }
         Ok(())
     }
+
+    /// The latest revision of the object, the object itself if it has none. If a
+    /// revision is replaced by more, the one linked last is followed
+    #[message]
+    pub async fn resolve_latest(&self, hash: Hash) -> Result<Hash> {
+        let mut latest: Key = hash.bytes.into();
+        let mut seen = HashSet::from([latest]);
+        while let Some(next) = self.backend.load_next_versions(latest).await?.pop() {
+            if !seen.insert(next) {
+                break;
+            }
+            latest = next;
+        }
+
+        Ok(Hash {
+            bytes: latest.as_u8_slice_be(),
+        })
+    }
+
+    /// The revisions of the object from the latest one back to the first one
+    #[message]
+    pub async fn load_history(&self, hash: Hash) -> Result<Vec<Hash>> {
+        let mut revision: Key = self.resolve_latest(hash).await?.bytes.into();
+        let mut history = vec![revision];
+        while let Some(previous) = self.backend.load_previous_version(revision).await? {
+            if history.contains(&previous) {
+                break;
+            }
+            history.push(previous);
+            revision = previous;
+        }
+
+        Ok(history
+            .into_iter()
+            .map(|key| Hash {
+                bytes: key.as_u8_slice_be(),
+            })
+            .collect())
+    }
+
+    /// The plain text files with all the words of the query, the best matches first
+    #[message]
+    pub async fn search_text(&self, query: String, limit: usize) -> Result<Vec<TextMatch>> {
+        if !self.text_index.enabled {
+            return Err(anyhow!("The full-text index is turned off"));
+        }
+        let matches = self.backend.search_text(query, limit).await?;
+
+        Ok(matches
+            .into_iter()
+            .map(|(key, name, snippet)| TextMatch {
+                id: key.to_string(),
+                name,
+                snippet,
+            })
+            .collect())
+    }
+
+    /// Indexes the object under the tags, for the searches of this node and of the peers
+    #[message]
+    pub async fn store_tags(&self, hash: Hash, tags: Vec<String>) -> Result<()> {
+        self.backend.store_tags(hash.bytes.into(), tags).await
+    }
+
+    /// The objects indexed under the hash of the tag, with the tag
+    #[message]
+    pub async fn find_tagged(&self, tag_hash: Hash) -> Result<Vec<(Hash, String)>> {
+        let tagged = self.backend.find_tagged(tag_hash.bytes.into()).await?;
+
+        Ok(tagged
+            .into_iter()
+            .map(|(key, tag)| {
+                let hash = Hash {
+                    bytes: key.as_u8_slice_be(),
+                };
+                (hash, tag)
+            })
+            .collect())
+    }
+
+    /// Counts a fetch of the object by another peer
+    #[message]
+    pub async fn record_object_served(&self, hash: Hash) -> Result<()> {
+        self.backend
+            .record_object_served(hash.bytes.into(), SystemTime::now())
+            .await
+    }
+
+    /// The objects served to other peers, the most often fetched first
+    #[message]
+    pub async fn load_popular_objects(
+        &self,
+        limit: Option<usize>,
+    ) -> Result<Vec<ObjectPopularity>> {
+        let published: HashSet<Key> = self
+            .backend
+            .list_published_objects()
+            .await?
+            .into_iter()
+            .collect();
+        let mut popularity = self.backend.load_object_popularity().await?;
+        popularity.sort_by(|(_, count_a, last_a), (_, count_b, last_b)| {
+            count_b.cmp(count_a).then(last_b.cmp(last_a))
+        });
+
+        Ok(popularity
+            .into_iter()
+            .take(limit.unwrap_or(usize::MAX))
+            .map(|(key, fetch_count, last_served)| ObjectPopularity {
+                id: key.to_string(),
+                fetch_count,
+                last_served,
+                published: published.contains(&key),
+            })
+            .collect())
+    }
+
+    /// The objects stored for others to delete so the stored objects take at most
+    /// the given bytes, the least often fetched first. The objects published by
+    /// this node are never picked
+    #[message]
+    pub async fn select_for_eviction(&self, max_bytes: u64) -> Result<Vec<Hash>> {
+        let published: HashSet<Key> = self
+            .backend
+            .list_published_objects()
+            .await?
+            .into_iter()
+            .collect();
+        let popularity: HashMap<Key, (u64, SystemTime)> = self
+            .backend
+            .load_object_popularity()
+            .await?
+            .into_iter()
+            .map(|(key, count, last_served)| (key, (count, last_served)))
+            .collect();
+        let sizes = self.backend.list_typed_object_sizes().await?;
+
+        let mut total: u64 = sizes.iter().map(|(_, size)| size).sum();
+        let mut candidates: Vec<(Key, u64)> = sizes
+            .into_iter()
+            .filter(|(key, _)| !published.contains(key))
+            .collect();
+        // The never served objects go first
+        candidates.sort_by_key(|(key, _)| {
+            popularity
+                .get(key)
+                .copied()
+                .unwrap_or((0, SystemTime::UNIX_EPOCH))
+        });
+
+        let mut evicted = vec![];
+        for (key, size) in candidates {
+            if total <= max_bytes {
+                break;
+            }
+            total -= size;
+            evicted.push(Hash {
+                bytes: key.as_u8_slice_be(),
+            });
+        }
+        Ok(evicted)
+    }
+
+    /// Sets who may get the object from this node. Public policies are not stored
+    #[message]
+    pub async fn store_access_policy(&self, hash: Hash, policy: AccessPolicy) -> Result<()> {
+        let key: Key = hash.bytes.into();
+        match policy {
+            AccessPolicy::Public => self.backend.delete_access_policy(key).await,
+            policy => self.backend.store_access_policy(key, policy).await,
+        }
+    }
+
+    /// The access policy of the object, public if none was stored
+    #[message]
+    pub async fn load_access_policy(&self, hash: Hash) -> Result<AccessPolicy> {
+        let policy = self.backend.load_access_policy(hash.bytes.into()).await?;
+        Ok(policy.unwrap_or_default())
+    }
+
+    /// Starts the download of the object, forgetting the chunks received before
+    #[message]
+    pub async fn start_partial_object(&self, hash: Hash, partial: PartialObject) -> Result<()> {
+        self.backend
+            .store_partial_object(hash.bytes.into(), partial)
+            .await
+    }
+
+    /// The object being downloaded with its verified chunks from the first one, up
+    /// to the first missing or corrupted chunk, where the download resumes
+    #[message]
+    pub async fn load_partial_object(
+        &self,
+        hash: Hash,
+    ) -> Result<Option<(PartialObject, Vec<Vec<u8>>)>> {
+        let key: Key = hash.bytes.into();
+        let Some(partial) = self.backend.load_partial_object(key).await? else {
+            return Ok(None);
+        };
+        let chunks = self
+            .backend
+            .load_partial_chunks(key)
+            .await?
+            .into_iter()
+            .enumerate()
+            .take_while(|(i, (index, data))| {
+                *i as u64 == *index && partial.verify_chunk(*index, data)
+            })
+            .map(|(_, (_, data))| data)
+            .collect();
+        Ok(Some((partial, chunks)))
+    }
+
+    #[message]
+    pub async fn store_partial_chunk(&self, hash: Hash, index: u64, data: Vec<u8>) -> Result<()> {
+        self.backend
+            .store_partial_chunk(hash.bytes.into(), index, data)
+            .await
+    }
+
+    #[message]
+    pub async fn delete_partial_object(&self, hash: Hash) -> Result<()> {
+        self.backend.delete_partial_object(hash.bytes.into()).await
+    }
+
+    #[message]
+    pub async fn store_contact(&self, contact: Contact) -> Result<()> {
+        self.backend.store_contact(contact).await
+    }
+
+    #[message]
+    pub async fn load_contacts(&self) -> Result<Vec<Contact>> {
+        self.backend.load_contacts().await
+    }
+
+    /// Returns false if there was no such contact
+    #[message]
+    pub async fn delete_contact(&self, peer_id: String) -> Result<bool> {
+        self.backend.delete_contact(peer_id).await
+    }
+
+    /// Updates the last seen time of the contact, peers not in the contacts are ignored
+    #[message]
+    pub async fn touch_contact(
+        &self,
+        peer_id: String,
+        address: Option<String>,
+        seen_at: SystemTime,
+    ) -> Result<()> {
+        self.backend.touch_contact(peer_id, address, seen_at).await
+    }
+
+    #[message]
+    pub async fn store_known_peer(&self, peer: KnownPeer) -> Result<()> {
+        self.backend.store_known_peer(peer).await
+    }
+
+    #[message]
+    pub async fn load_known_peers(&self) -> Result<Vec<KnownPeer>> {
+        self.backend.load_known_peers().await
+    }
+
+    #[message]
+    pub async fn delete_known_peer(&self, peer_id: String) -> Result<()> {
+        self.backend.delete_known_peer(peer_id).await
+    }
+
+    #[message]
+    pub async fn store_message(&self, message: InboxMessage) -> Result<()> {
+        self.backend.store_message(message).await
+    }
+
+    #[message]
+    pub async fn load_messages(&self) -> Result<Vec<InboxMessage>> {
+        self.backend.load_messages().await
+    }
+
+    #[message]
+    pub async fn store_mailbox_entry(&self, entry: MailboxEntry) -> Result<()> {
+        self.backend.store_mailbox_entry(entry).await
+    }
+
+    #[message]
+    pub async fn load_mailbox_entries(&self, recipient: String) -> Result<Vec<MailboxEntry>> {
+        self.backend.load_mailbox_entries(recipient).await
+    }
+
+    #[message]
+    pub async fn mailbox_size(&self) -> Result<u64> {
+        self.backend.mailbox_size().await
+    }
+
+    #[message]
+    pub async fn delete_mailbox_entries(
+        &self,
+        recipient: String,
+        ids: Vec<String>,
+    ) -> Result<usize> {
+        self.backend.delete_mailbox_entries(recipient, ids).await
+    }
+
+    #[message]
+    pub async fn delete_expired_mailbox_entries(&self, before: SystemTime) -> Result<usize> {
+        self.backend.delete_expired_mailbox_entries(before).await
+    }
+
+    #[message]
+    pub async fn store_group(&self, membership: GroupMembership) -> Result<()> {
+        self.backend.store_group(membership).await
+    }
+
+    #[message]
+    pub async fn load_groups(&self) -> Result<Vec<GroupMembership>> {
+        self.backend.load_groups().await
+    }
+
+    #[message]
+    pub async fn store_group_post(&self, post: GroupPost) -> Result<()> {
+        self.backend.store_group_post(post).await
+    }
+
+    #[message]
+    pub async fn load_group_posts(&self, group: String) -> Result<Vec<GroupPost>> {
+        self.backend.load_group_posts(group).await
+    }
+
+    #[message]
+    pub async fn append_audit_entry(&self, entry: AuditEntry) -> Result<()> {
+        self.backend.append_audit_entry(entry).await
+    }
+
+    #[message]
+    pub async fn load_audit_log(&self, filter: AuditFilter) -> Result<Vec<AuditEntry>> {
+        self.backend.load_audit_log(filter).await
+    }
+
+    #[message]
+    pub async fn store_stats_sample(&self, sample: NodeStatsSample) -> Result<()> {
+        self.backend.store_stats_sample(sample).await
+    }
+
+    #[message]
+    pub async fn load_stats_samples(
+        &self,
+        since: Option<SystemTime>,
+        until: Option<SystemTime>,
+    ) -> Result<Vec<NodeStatsSample>> {
+        self.backend.load_stats_samples(since, until).await
+    }
+
+    #[message]
+    pub async fn delete_stats_samples(&self, before: SystemTime) -> Result<usize> {
+        self.backend.delete_stats_samples(before).await
+    }
 }
 
 impl Message<LoadFragment> for Vault {
@@ -210,35 +628,109 @@ impl Message<LoadFragment> for Vault {
         msg: LoadFragment,
         _: Context<'_, Self, Self::Reply>,
     ) -> impl std::future::Future<Output = Self::Reply> + Send {
-        async move { self.load_fragment(msg.0).await }
+        async move { self.backend.load_fragment(msg.0).await }
     }
 }
 
 impl Vault {
-    const DEFAULT_VAULT_DATABASE_NAME: &'static str = "vault.db3";
-    const FRAGMENT_DIR_NAME: &'static str = "fragments";
-    const TEMP_DIR_NAME: &'static str = "temp";
     const MIN_FRAGMENT_SIZE: u64 = 4096;
 
+    /// The vault with the default SQLite backend, storing everything in the directory
     pub async fn new_on_disk(vault_dir_path: &Path) -> Result<Vault> {
-        Self::ensure_dirs(vault_dir_path).await?;
+        let backend = SqliteBackend::open(vault_dir_path).await?;
 
-        let db_path = Self::default_db_path(vault_dir_path);
-        let db = Connection::open(db_path).await?;
+        Ok(Vault::with_backend(Box::new(backend)))
+    }
 
-        Ok(Vault {
-            db,
-            vault_dir_path: Some(vault_dir_path.to_path_buf()),
-        })
+    /// The vault on disk storing the objects with the compression, the other vaults
+    /// compress with the default one
+    pub async fn new_on_disk_with_compression(
+        vault_dir_path: &Path,
+        compression: CompressionConfig,
+    ) -> Result<Vault> {
+        let backend = SqliteBackend::open(vault_dir_path)
+            .await?
+            .with_compression(compression);
+
+        Ok(Vault::with_backend(Box::new(backend)))
     }
 
+    /// The vault keeping everything in memory, the total size of the fragments is
+    /// limited to `DEFAULT_MEMORY_FRAGMENTS_CAPACITY`
     pub async fn new_in_memory() -> Result<Vault> {
-        let db = Connection::open_in_memory().await?;
+        let backend = MemoryBackend::new(DEFAULT_MEMORY_FRAGMENTS_CAPACITY);
 
-        Ok(Vault {
-            db,
-            vault_dir_path: None,
-        })
+        Ok(Vault::with_backend(Box::new(backend)))
+    }
+
+    pub fn with_backend(backend: Box<dyn VaultBackend>) -> Vault {
+        Vault {
+            backend,
+            text_index: TextIndexConfig::default(),
+        }
+    }
+
+    /// Indexes the text of the plain text files and the revisions linked by the
+    /// version objects. A revision is linked only by its publisher, see
+    /// `is_signed_by_publisher`
+    async fn index_object(
+        &self,
+        key: Key,
+        object: TypedObject,
+        source: Option<PeerId>,
+    ) -> Result<()> {
+        match unsigned(object).await {
+            Some((ObjectEnum::PlainFile(file), _)) if self.text_index.enabled => {
+                if let Some(text) = plain_text(&file, self.text_index.max_indexed_bytes) {
+                    self.backend.index_text(key, file.name, text).await?;
+                }
+            }
+            Some((ObjectEnum::Version(version), Some(signed))) => {
+                let previous: Key = version.previous.bytes.into();
+                if !self
+                    .is_signed_by_publisher(&signed, previous, source)
+                    .await?
+                {
+                    debug!(
+                        version = key.to_string(),
+                        "Not linking the revision, it's not signed by the publisher"
+                    );
+                    return Ok(());
+                }
+                self.backend
+                    .store_version(key, version.object.bytes.into(), previous)
+                    .await?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Whether the version is signed by the publisher of the revision it replaces.
+    /// A version sent by a peer must be signed by the peer, and so must be the
+    /// previous revision. The node links only the revisions it published itself
+    async fn is_signed_by_publisher(
+        &self,
+        version: &SignedObject,
+        previous: Key,
+        source: Option<PeerId>,
+    ) -> Result<bool> {
+        let Some(peer) = source else {
+            return Ok(self
+                .backend
+                .load_published_object(previous)
+                .await?
+                .is_some());
+        };
+        if version.verify_peer(&peer) != SignatureStatus::Verified {
+            return Ok(false);
+        }
+
+        let previous = self.backend.load_typed_object(previous).await?;
+        Ok(previous
+            .filter(|object| object.uuid == SignedObject::UUID)
+            .and_then(|object| TypedObject::try_from_typed::<SignedObject>(&object).ok())
+            .is_some_and(|signed| signed.verify_peer(&peer) == SignatureStatus::Verified))
     }
 
     pub async fn fragment(path: &Path) -> Result<Vec<FragmentData>> {
@@ -303,282 +795,51 @@ impl Vault {
     fn is_power_of_2(number: u64) -> bool {
         number > 0 && (((number) & (number - 1)) == 0)
     }
+}
 
-    async fn prepare_db(&self) -> Result<()> {
-        const CREATE_FRAGMENT_TABLE_QUERY: &str = "
-            CREATE TABLE IF NOT EXISTS fragment (
-                hash0 INTEGER NOT NULL,
-                hash1 INTEGER NOT NULL,
-                hash2 INTEGER NOT NULL,
-                hash3 INTEGER NOT NULL,
-                path VARCHAR(255),
-                size INTEGER,
-                PRIMARY KEY (hash0, hash1, hash2, hash3)
-            )
-        ";
-
-        self.db
-            .call(|conn| Ok(conn.execute(CREATE_FRAGMENT_TABLE_QUERY, ())?))
-            .await?;
-
-        const CREATE_TYPED_OBJECT_TABLE_QUERY: &str = "
-            CREATE TABLE IF NOT EXISTS typed_object (
-                hash0 INTEGER NOT NULL,
-                hash1 INTEGER NOT NULL,
-                hash2 INTEGER NOT NULL,
-                hash3 INTEGER NOT NULL,
-                type_id TEXT,
-                data BLOB,
-                PRIMARY KEY (hash0, hash1, hash2, hash3)
-            )
-        ";
-
-        self.db
-            .call(|conn| Ok(conn.execute(CREATE_TYPED_OBJECT_TABLE_QUERY, ())?))
-            .await?;
-
-        Ok(())
-    }
-
-    async fn load_fragment_info(&self, key: Key) -> Result<Option<FragmentInfo>> {
-        const SELECT_FRAGMENT_QUERY: &str = "
-            SELECT path, size
-            FROM fragment
-            WHERE hash0 = ?1 AND hash1 = ?2 AND hash2 = ?3 AND hash3 = ?4
-        ";
-
-        self.db
-            .call(move |conn| {
-                let mut stmt = conn.prepare(SELECT_FRAGMENT_QUERY)?;
-                let key_u64_slice = key.as_u64_slice_be();
-                let key_as_i64 = [
-                    key_u64_slice[0] as i64,
-                    key_u64_slice[1] as i64,
-                    key_u64_slice[2] as i64,
-                    key_u64_slice[3] as i64,
-                ];
-
-                let fragment = stmt
-                    .query_row(key_as_i64, |r| {
-                        let path: String = match r.get(0) {
-                            Ok(p) => p,
-                            Err(e) => return Result::Err(e),
-                        };
-
-                        let size: u64 = match r.get(1) {
-                            Ok(s) => s,
-                            Err(e) => return Result::Err(e),
-                        };
-
-                        Result::Ok(FragmentInfo::new(key, Path::new(&path), size))
-                    })
-                    .optional()?;
-
-                Ok(fragment)
-            })
-            .await
-            .map_err(|e| anyhow!(e))
-    }
-
-    async fn store_fragment_info(&self, fragment: FragmentInfo) -> Result<()> {
-        const SELECT_FRAGMENT_QUERY: &str = "SELECT COUNT(*) FROM fragment WHERE hash0 = ?1 AND hash1 = ?2 AND hash2 = ?3 AND hash3 = ?4";
-        const INSERT_FRAGMENT_QUERY: &str = "
-                INSERT INTO fragment (hash0, hash1, hash2, hash3, path, size)
-                VALUES (?1, ?2, ?3, ?4, ?5, ?6)
-            ";
-
-        let hash_as_u64 = fragment.hash.as_u64_slice_be();
-
-        let cnt = self
-            .db
-            .call(move |conn| {
-                let key_as_i64 = [
-                    hash_as_u64[0] as i64,
-                    hash_as_u64[1] as i64,
-                    hash_as_u64[2] as i64,
-                    hash_as_u64[3] as i64,
-                ];
-
-                let cnt = conn.query_row(SELECT_FRAGMENT_QUERY, key_as_i64, |r| {
-                    let cnt: usize = r.get(0)?;
-
-                    Ok(cnt)
-                })?;
-
-                Ok(cnt)
-            })
-            .await?;
-
-        if cnt != 0 {
-            return Ok(());
-        }
-
-        self.db
-            .call(move |conn| {
-                conn.execute(
-                    INSERT_FRAGMENT_QUERY,
-                    (
-                        hash_as_u64[0] as i64,
-                        hash_as_u64[1] as i64,
-                        hash_as_u64[2] as i64,
-                        hash_as_u64[3] as i64,
-                        fragment.path.to_str(),
-                        fragment.size,
-                    ),
-                )?;
-
-                Ok(())
-            })
-            .await
-            .map_err(|e| anyhow!(e))
-    }
-
-    // TODO: Loading fragments from in memory not supported
-    async fn load_fragment(&self, key: Key) -> Result<Option<FragmentData>> {
-        let fragment_info = self.load_fragment_info(key.clone()).await?;
-
-        if let None = fragment_info {
-            return Ok(None);
+/// The object inside the signed ones, if it's of a type the vault indexes. The
+/// version objects come with their signatures
+async fn unsigned(object: TypedObject) -> Option<(ObjectEnum, Option<SignedObject>)> {
+    let mut object = object;
+    let mut version_signature = None;
+    loop {
+        let indexed = [
+            SignedObject::UUID,
+            PlainFileObject::UUID,
+            VersionObject::UUID,
+        ];
+        if !indexed.contains(&object.uuid) {
+            return None;
         }
-
-        let fragment_info = fragment_info.unwrap();
-        let fragment_path = fragment_info.path;
-        let fragment_file = File::open(&fragment_path).await?;
-
-        Ok(Some(ReaderStream::new(fragment_file).boxed()))
-    }
-
-    async fn load_typed_object(&self, key: Key) -> Result<Option<TypedObject>> {
-        const SELECT_TYPED_OBJECT_QUERY: &str = "
-            SELECT type_id, data
-            FROM typed_object
-            WHERE hash0 = ?1 AND hash1 = ?2 AND hash2 = ?3 AND hash3 = ?4
-        ";
-
-        self.db
-            .call(move |conn| {
-                let mut stmt = conn.prepare(SELECT_TYPED_OBJECT_QUERY)?;
-                let key_u64_slice = key.as_u64_slice_be();
-                let key_as_i64 = [
-                    key_u64_slice[0] as i64,
-                    key_u64_slice[1] as i64,
-                    key_u64_slice[2] as i64,
-                    key_u64_slice[3] as i64,
-                ];
-
-                let typed_object = stmt
-                    .query_row(key_as_i64, |r| {
-                        let uuid: String = r.get(0)?;
-                        let data: Vec<u8> = r.get(1)?;
-
-                        Result::Ok(TypedObject {
-                            uuid: uuid::Uuid::from_str(&uuid).unwrap(),
-                            data,
-                        })
-                    })
-                    .optional()?;
-
-                Ok(typed_object)
-            })
-            .await
-            .map_err(|e| anyhow!(e))
-    }
-
-    async fn store_typed_object(&self, key: Key, object: TypedObject) -> Result<()> {
-        const SELECT_TYPED_OBJECT_QUERY: &str =
-            "SELECT COUNT(*) FROM typed_object WHERE hash0 = ?1 AND hash1 = ?2 AND hash2 = ?3 AND hash3 = ?4";
-        const INSERT_TYPED_OBJECT_QUERY: &str =
-            "INSERT INTO typed_object (hash0, hash1, hash2, hash3, type_id, data)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6)";
-
-        let hash_as_u64 = key.as_u64_slice_be();
-
-        let cnt = self
-            .db
-            .call(move |conn| {
-                let key_as_i64 = [
-                    hash_as_u64[0] as i64,
-                    hash_as_u64[1] as i64,
-                    hash_as_u64[2] as i64,
-                    hash_as_u64[3] as i64,
-                ];
-
-                let cnt = conn.query_row(SELECT_TYPED_OBJECT_QUERY, key_as_i64, |r| {
-                    let cnt: usize = r.get(0)?;
-
-                    Ok(cnt)
-                })?;
-
-                Ok(cnt)
-            })
-            .await?;
-
-        if cnt != 0 {
-            // Already stored, no need to change as objects are immutable
-            return Ok(());
+        object = match parse_typed(object).await.ok()? {
+            ObjectEnum::Signed(signed) if signed.object.uuid == VersionObject::UUID => {
+                let version = signed.object.clone();
+                version_signature = Some(signed);
+                version
+            }
+            ObjectEnum::Signed(signed) => signed.object,
+            other => return Some((other, version_signature)),
         }
-
-        self.db
-            .call(move |conn| {
-                conn.execute(
-                    INSERT_TYPED_OBJECT_QUERY,
-                    (
-                        hash_as_u64[0] as i64,
-                        hash_as_u64[1] as i64,
-                        hash_as_u64[2] as i64,
-                        hash_as_u64[3] as i64,
-                        object.uuid.to_string(),
-                        object.data,
-                    ),
-                )?;
-
-                Ok(())
-            })
-            .await
-            .map_err(|e| anyhow!(e))
-    }
-
-    async fn ensure_dirs(vault_dir_path: &Path) -> Result<()> {
-        debug!(
-            path = vault_dir_path.display().to_string(),
-            "ensuring vault dir"
-        );
-        tokio::fs::create_dir_all(vault_dir_path).await?;
-
-        let fragment_dir_path = Self::fragment_dir_path(vault_dir_path);
-        debug!(
-            path = fragment_dir_path.display().to_string(),
-            "ensuring fragment dir"
-        );
-        tokio::fs::create_dir_all(fragment_dir_path).await?;
-
-        let temp_dir_path = Self::temp_dir_path(vault_dir_path);
-        debug!(
-            path = temp_dir_path.display().to_string(),
-            "ensuring temp dir"
-        );
-        tokio::fs::create_dir_all(temp_dir_path).await?;
-
-        Ok(())
-    }
-
-    fn fragment_dir_path(vault_dir_path: &Path) -> PathBuf {
-        vault_dir_path.join(Self::FRAGMENT_DIR_NAME)
-    }
-
-    fn temp_dir_path(vault_dir_path: &Path) -> PathBuf {
-        vault_dir_path.join(Self::TEMP_DIR_NAME)
     }
+}
 
-    fn default_db_path(base_path: &Path) -> PathBuf {
-        base_path.join(Self::DEFAULT_VAULT_DATABASE_NAME)
+/// The text of the file, None for the files which are not text or are too big to
+/// be indexed
+fn plain_text(file: &PlainFileObject, max_bytes: u64) -> Option<String> {
+    if file.content.len() as u64 > max_bytes {
+        return None;
     }
+    let text = std::str::from_utf8(&file.content).ok()?;
+    (!text.contains('\0')).then(|| text.to_string())
 }
 
 #[cfg(test)]
 mod tests {
+    use futures::stream;
     use futures::StreamExt as FuturesStreamExt;
     use kameo::request::MessageSend;
+    use liberum_core::proto::{Signature, TagObject};
+    use libp2p::identity::Keypair;
     use pretty_assertions::assert_eq;
     use rand::{rngs::StdRng, Rng, SeedableRng};
     use tempdir::TempDir;
@@ -675,6 +936,47 @@ mod tests {
         assert_eq!(random_bytes, bytes_recollected);
     }
 
+    #[tokio::test]
+    async fn in_memory_fragment_test() {
+        let vault = kameo::spawn(Vault::new_in_memory().await.unwrap());
+        let data = vec![42u8; 5000];
+
+        let key = vault
+            .ask(StoreFragment {
+                key: None,
+                data: stream::once(async { Ok(Bytes::from(vec![42u8; 5000])) }).boxed(),
+            })
+            .send()
+            .await
+            .unwrap();
+        let stored = vault
+            .ask(LoadFragment(key))
+            .send()
+            .await
+            .unwrap()
+            .unwrap()
+            .flat_map(|bt| tokio_stream::iter(bt.unwrap()))
+            .collect::<Vec<u8>>()
+            .await;
+        assert_eq!(stored, data);
+
+        // The integrity is checked like for the fragments on disk
+        let wrong_key = vault
+            .ask(StoreFragment {
+                key: Some(key),
+                data: stream::once(async { Ok(Bytes::from(vec![1u8; 10])) }).boxed(),
+            })
+            .send()
+            .await;
+        assert!(wrong_key.is_err());
+        assert!(vault
+            .ask(LoadFragment(Key::random()))
+            .send()
+            .await
+            .unwrap()
+            .is_none());
+    }
+
     #[test]
     fn fragment_sizes_test() {
         let some_file_size = 45000;
@@ -775,4 +1077,349 @@ mod tests {
 
         assert!(loaded_obj.is_none());
     }
+
+    #[tokio::test]
+    async fn published_object_test() {
+        let tmp_dir = TempDir::new("liberum_tests").unwrap();
+        let vault_dir_path = tmp_dir.path();
+        let vault = Vault::new_on_disk(vault_dir_path).await.unwrap();
+        let vault = kameo::spawn(vault);
+        let hash = Hash { bytes: [7; 32] };
+        let object = TypedObject {
+            uuid: Uuid::new_v4(),
+            data: vec![1, 2, 3],
+        };
+
+        vault
+            .ask(StorePublishedObject {
+                hash: hash.clone(),
+                object: object.clone(),
+            })
+            .send()
+            .await
+            .unwrap();
+
+        let published = vault.ask(ListPublishedObjects).send().await.unwrap();
+        assert_eq!(published, vec![hash.clone()]);
+
+        let loaded_obj = vault
+            .ask(LoadPublishedObject { hash: hash.clone() })
+            .send()
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(loaded_obj.uuid, object.uuid);
+        assert_eq!(loaded_obj.data, object.data);
+
+        vault
+            .ask(DeletePublishedObject { hash })
+            .send()
+            .await
+            .unwrap();
+
+        let published = vault.ask(ListPublishedObjects).send().await.unwrap();
+        assert!(published.is_empty());
+    }
+
+    #[tokio::test]
+    async fn text_search_test() {
+        let tmp_dir = TempDir::new("liberum_tests").unwrap();
+        let vault = kameo::spawn(Vault::new_on_disk(tmp_dir.path()).await.unwrap());
+        let file = |name: &str, content: &[u8]| {
+            let file: TypedObject = PlainFileObject {
+                name: name.to_string(),
+                content: content.to_vec(),
+            }
+            .into();
+            let signed: TypedObject = SignedObject {
+                object: file,
+                signature: Signature { bytes: vec![1; 64] },
+            }
+            .into();
+            ObjectEnum::Typed(signed)
+        };
+        let (notes, binary) = (Hash { bytes: [1; 32] }, Hash { bytes: [2; 32] });
+        let text = b"The quick brown fox jumps over the lazy dog";
+        for (hash, object) in [
+            (&notes, file("notes.txt", text)),
+            (&binary, file("fox.bin", &[0xff, 0xfe, 0])),
+        ] {
+            vault
+                .ask(StoreObject {
+                    hash: hash.clone(),
+                    object,
+                })
+                .send()
+                .await
+                .unwrap();
+        }
+
+        let search = |query: &str| {
+            vault
+                .ask(SearchText {
+                    query: query.to_string(),
+                    limit: 10,
+                })
+                .send()
+        };
+        let matches = search("FOX lazy").await.unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].id, notes.to_string());
+        assert_eq!(matches[0].name, "notes.txt");
+        assert!(matches[0].snippet.contains("[fox]"));
+        // The operators of FTS5 are matched as words
+        assert!(search("fox OR \"cat").await.unwrap().is_empty());
+
+        vault
+            .ask(DeleteTypedObject { hash: notes })
+            .send()
+            .await
+            .unwrap();
+        assert!(search("fox").await.unwrap().is_empty());
+
+        vault
+            .ask(SetTextIndex {
+                config: TextIndexConfig {
+                    enabled: false,
+                    ..Default::default()
+                },
+            })
+            .send()
+            .await
+            .unwrap();
+        assert!(search("fox").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn history_test() {
+        let tmp_dir = TempDir::new("liberum_tests").unwrap();
+        let vault = kameo::spawn(Vault::new_on_disk(tmp_dir.path()).await.unwrap());
+        let publisher = Keypair::generate_ed25519();
+        let publisher_id = publisher.public().to_peer_id();
+        let store = |object: TypedObject, source: PeerId| {
+            let vault = vault.clone();
+            async move {
+                let hash = Hash::try_from(&object).unwrap();
+                vault
+                    .ask(StoreReceivedObject {
+                        hash: hash.clone(),
+                        object,
+                        kind: ProvenanceKind::Stored,
+                        source: Some(source),
+                    })
+                    .send()
+                    .await
+                    .unwrap();
+                hash
+            }
+        };
+        let sign = |object: TypedObject, keypair: &Keypair| -> TypedObject {
+            SignedObject::sign_ed25519(object, keypair.clone())
+                .unwrap()
+                .into()
+        };
+        let revision = |content: &str| {
+            sign(
+                PlainFileObject {
+                    name: "file".to_string(),
+                    content: content.as_bytes().to_vec(),
+                }
+                .into(),
+                &publisher,
+            )
+        };
+        let version = |previous: &Hash, object: &Hash| -> TypedObject {
+            VersionObject {
+                previous: previous.clone(),
+                object: object.clone(),
+            }
+            .into()
+        };
+
+        let revisions = [
+            store(revision("1"), publisher_id).await,
+            store(revision("2"), publisher_id).await,
+            store(revision("3"), publisher_id).await,
+        ];
+        let mut versions = vec![];
+        for pair in revisions.windows(2) {
+            let signed = sign(version(&pair[0], &pair[1]), &publisher);
+            versions.push(store(signed, publisher_id).await);
+        }
+
+        let latest = vault
+            .ask(ResolveLatest {
+                hash: revisions[0].clone(),
+            })
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(latest.bytes, revisions[2].bytes);
+        let history = vault
+            .ask(LoadHistory {
+                hash: revisions[1].clone(),
+            })
+            .send()
+            .await
+            .unwrap();
+        let history: Vec<[u8; 32]> = history.into_iter().map(|h| h.bytes).collect();
+        let expected: Vec<[u8; 32]> = revisions.iter().rev().map(|h| h.bytes).collect();
+        assert_eq!(history, expected);
+
+        // Another peer can't link its object to the revisions of the publisher, nor
+        // can an unsigned version
+        let foreign = Keypair::generate_ed25519();
+        let hijack = Hash { bytes: [7; 32] };
+        let forged = sign(version(&revisions[2], &hijack), &foreign);
+        store(forged, foreign.public().to_peer_id()).await;
+        store(version(&revisions[2], &hijack), publisher_id).await;
+        let latest = vault
+            .ask(ResolveLatest {
+                hash: revisions[0].clone(),
+            })
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(latest.bytes, revisions[2].bytes);
+
+        vault
+            .ask(DeleteTypedObject {
+                hash: versions[1].clone(),
+            })
+            .send()
+            .await
+            .unwrap();
+        let latest = vault
+            .ask(ResolveLatest {
+                hash: revisions[0].clone(),
+            })
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(latest.bytes, revisions[1].bytes);
+    }
+
+    #[tokio::test]
+    async fn tags_test() {
+        let tmp_dir = TempDir::new("liberum_tests").unwrap();
+        let vault = kameo::spawn(Vault::new_on_disk(tmp_dir.path()).await.unwrap());
+        let (first, second) = (Hash { bytes: [1; 32] }, Hash { bytes: [2; 32] });
+        let tags = |tags: &[&str]| tags.iter().map(|tag| tag.to_string()).collect();
+
+        vault
+            .ask(StoreTags {
+                hash: first.clone(),
+                tags: tags(&["Rust", "p2p"]),
+            })
+            .send()
+            .await
+            .unwrap();
+        vault
+            .ask(StoreTags {
+                hash: second.clone(),
+                tags: tags(&["rust "]),
+            })
+            .send()
+            .await
+            .unwrap();
+        // Tagging again is not an error
+        vault
+            .ask(StoreTags {
+                hash: second.clone(),
+                tags: tags(&["rust"]),
+            })
+            .send()
+            .await
+            .unwrap();
+
+        let mut rust = vault
+            .ask(FindTagged {
+                tag_hash: TagObject::tag_hash("RUST"),
+            })
+            .send()
+            .await
+            .unwrap();
+        rust.sort_by_key(|(hash, _)| hash.bytes);
+        assert_eq!(
+            rust,
+            vec![
+                (first.clone(), "rust".to_string()),
+                (second, "rust".to_string())
+            ]
+        );
+        let p2p = vault
+            .ask(FindTagged {
+                tag_hash: TagObject::tag_hash("p2p"),
+            })
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(p2p, vec![(first, "p2p".to_string())]);
+    }
+
+    #[tokio::test]
+    async fn eviction_test() {
+        let vault = kameo::spawn(Vault::new_in_memory().await.unwrap());
+        let object = |data: Vec<u8>| {
+            ObjectEnum::Typed(TypedObject {
+                uuid: Uuid::new_v4(),
+                data,
+            })
+        };
+        let (cold, popular, published) = (
+            Hash { bytes: [1; 32] },
+            Hash { bytes: [2; 32] },
+            Hash { bytes: [3; 32] },
+        );
+        for hash in [&cold, &popular, &published] {
+            vault
+                .ask(StoreObject {
+                    hash: hash.clone(),
+                    object: object(vec![0; 100]),
+                })
+                .send()
+                .await
+                .unwrap();
+        }
+        vault
+            .ask(StorePublishedObject {
+                hash: published.clone(),
+                object: TypedObject {
+                    uuid: Uuid::new_v4(),
+                    data: vec![],
+                },
+            })
+            .send()
+            .await
+            .unwrap();
+        for hash in [&popular, &popular, &published] {
+            vault
+                .ask(RecordObjectServed { hash: hash.clone() })
+                .send()
+                .await
+                .unwrap();
+        }
+
+        let popular_objects = vault
+            .ask(LoadPopularObjects { limit: Some(1) })
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(popular_objects.len(), 1);
+        assert_eq!(popular_objects[0].fetch_count, 2);
+        assert!(!popular_objects[0].published);
+
+        let evicted = vault
+            .ask(SelectForEviction { max_bytes: 200 })
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(evicted, vec![cold.clone()]);
+        let evicted = vault
+            .ask(SelectForEviction { max_bytes: 0 })
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(evicted, vec![cold, popular]);
+    }
 }
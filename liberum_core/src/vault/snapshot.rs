@@ -0,0 +1,338 @@
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{Read, Write};
+
+use anyhow::{anyhow, bail, Result};
+use futures::stream;
+use futures::StreamExt;
+use kameo::messages;
+use liberum_core::owned_file::FileTarget;
+use liberum_core::proto::{self, TypedObject};
+use liberum_core::types::VaultSnapshotSummary;
+use serde::{Deserialize, Serialize};
+use tokio_util::bytes::Bytes;
+use tracing::debug;
+
+use super::fragment::key::Key;
+use super::Vault;
+
+///! The module contains the export of the whole vault to a single archive and the
+///! import of such an archive into another vault, for backups and moving nodes
+///! between machines. The archive is a zstd compressed tar with a manifest listing
+///! the objects and the fragments, every one of them stored in its own entry.
+
+const SNAPSHOT_VERSION: u32 = 1;
+const MANIFEST_PATH: &str = "manifest.json";
+const TYPED_OBJECT_DIR: &str = "typed_object";
+const PUBLISHED_OBJECT_DIR: &str = "published_object";
+const FRAGMENT_DIR: &str = "fragment";
+const ZSTD_LEVEL: i32 = 3;
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct SnapshotManifest {
+    version: u32,
+    typed_objects: Vec<String>,
+    published_objects: Vec<String>,
+    fragments: Vec<String>,
+}
+
+#[messages]
+impl Vault {
+    /// Writes all the typed objects, published objects and fragments to the archive
+    /// at the target
+    #[message]
+    pub async fn export_snapshot(&self, target: FileTarget) -> Result<VaultSnapshotSummary> {
+        let typed_objects = self.load_all_typed_objects().await?;
+        let published_objects = self.load_all_published_objects().await?;
+        let fragments = self.load_all_fragments().await?;
+
+        let manifest = SnapshotManifest {
+            version: SNAPSHOT_VERSION,
+            typed_objects: typed_objects.iter().map(|(k, _)| k.to_string()).collect(),
+            published_objects: published_objects
+                .iter()
+                .map(|(k, _)| k.to_string())
+                .collect(),
+            fragments: fragments.iter().map(|(k, _)| k.to_string()).collect(),
+        };
+        let summary = VaultSnapshotSummary {
+            typed_objects: typed_objects.len(),
+            published_objects: published_objects.len(),
+            fragments: fragments.len(),
+            skipped: 0,
+        };
+
+        let mut entries = vec![(MANIFEST_PATH.to_string(), serde_json::to_vec(&manifest)?)];
+        for (dir, objects) in [
+            (TYPED_OBJECT_DIR, typed_objects),
+            (PUBLISHED_OBJECT_DIR, published_objects),
+        ] {
+            for (key, object) in objects {
+                entries.push((format!("{dir}/{key}"), object.try_into()?));
+            }
+        }
+        for (key, data) in fragments {
+            entries.push((format!("{FRAGMENT_DIR}/{key}"), data));
+        }
+
+        // The archive replaces the target only once it's complete
+        let (file, temp_name) = target.create_temp()?;
+        let temp_file = TempFile {
+            target: &target,
+            name: temp_name,
+        };
+        tokio::task::spawn_blocking(move || write_archive(file, entries)).await??;
+        target.persist(&temp_file.name)?;
+        debug!("Vault snapshot exported: {summary:?}");
+
+        Ok(summary)
+    }
+
+    /// Merges the archive read from the file into the vault. The objects and the
+    /// fragments already in the vault are skipped, the other ones are checked against
+    /// their hashes
+    #[message]
+    pub async fn import_snapshot(&self, file: File) -> Result<VaultSnapshotSummary> {
+        let mut entries = tokio::task::spawn_blocking(move || read_archive(file)).await??;
+        let manifest: SnapshotManifest = serde_json::from_slice(
+            &entries
+                .remove(MANIFEST_PATH)
+                .ok_or(anyhow!("snapshot has no manifest"))?,
+        )?;
+        if manifest.version != SNAPSHOT_VERSION {
+            bail!("unsupported snapshot version {}", manifest.version);
+        }
+
+        let mut summary = VaultSnapshotSummary::default();
+        for id in &manifest.typed_objects {
+            let (key, object) = take_object(&mut entries, TYPED_OBJECT_DIR, id)?;
+            if self.backend.load_typed_object(key).await?.is_some() {
+                summary.skipped += 1;
+                continue;
+            }
+            self.backend.store_typed_object(key, object).await?;
+            summary.typed_objects += 1;
+        }
+
+        for id in &manifest.published_objects {
+            let (key, object) = take_object(&mut entries, PUBLISHED_OBJECT_DIR, id)?;
+            if self.backend.load_published_object(key).await?.is_some() {
+                summary.skipped += 1;
+                continue;
+            }
+            self.backend.store_published_object(key, object).await?;
+            summary.published_objects += 1;
+        }
+
+        for id in &manifest.fragments {
+            let key = Key::try_from(id.clone())?;
+            let data = entries
+                .remove(&format!("{FRAGMENT_DIR}/{id}"))
+                .ok_or(anyhow!("fragment {id} is missing in the snapshot"))?;
+            if self.backend.has_fragment(key).await? {
+                summary.skipped += 1;
+                continue;
+            }
+            let data = stream::once(async { Ok(Bytes::from(data)) }).boxed();
+            self.backend.store_fragment(Some(key), data).await?;
+            summary.fragments += 1;
+        }
+
+        debug!("Vault snapshot imported: {summary:?}");
+        Ok(summary)
+    }
+}
+
+impl Vault {
+    async fn load_all_typed_objects(&self) -> Result<Vec<(Key, TypedObject)>> {
+        let mut objects = Vec::new();
+        for (key, _) in self.backend.list_typed_objects().await? {
+            if let Some(object) = self.backend.load_typed_object(key).await? {
+                objects.push((key, object));
+            }
+        }
+
+        Ok(objects)
+    }
+
+    async fn load_all_published_objects(&self) -> Result<Vec<(Key, TypedObject)>> {
+        let mut objects = Vec::new();
+        for key in self.backend.list_published_objects().await? {
+            if let Some(object) = self.backend.load_published_object(key).await? {
+                objects.push((key, object));
+            }
+        }
+
+        Ok(objects)
+    }
+
+    async fn load_all_fragments(&self) -> Result<Vec<(Key, Vec<u8>)>> {
+        let mut fragments = Vec::new();
+        for key in self.backend.list_fragments().await? {
+            let Some(mut data) = self.backend.load_fragment(key).await? else {
+                continue;
+            };
+            let mut bytes = Vec::new();
+            while let Some(chunk) = data.next().await {
+                bytes.extend_from_slice(&chunk?);
+            }
+            fragments.push((key, bytes));
+        }
+
+        Ok(fragments)
+    }
+}
+
+/// The archive being written, removed when the export fails or is dropped before
+/// the archive is persisted. The directory of the archive is not one of the node,
+/// so a temporary file left there would never be cleaned up
+struct TempFile<'a> {
+    target: &'a FileTarget,
+    name: OsString,
+}
+
+impl Drop for TempFile<'_> {
+    fn drop(&mut self) {
+        // Already moved to the target if the archive was persisted
+        let _ = self.target.remove(&self.name);
+    }
+}
+
+/// Takes the object from the entries and checks that its hash matches the ID
+fn take_object(
+    entries: &mut HashMap<String, Vec<u8>>,
+    dir: &str,
+    id: &str,
+) -> Result<(Key, TypedObject)> {
+    let bytes = entries
+        .remove(&format!("{dir}/{id}"))
+        .ok_or(anyhow!("object {id} is missing in the snapshot"))?;
+    let object = TypedObject::try_from(&bytes)?;
+    let hash = proto::Hash::try_from(&object)?;
+    if hash.to_string() != id {
+        bail!("object {id} in the snapshot has a wrong hash {hash}");
+    }
+
+    Ok((Key::from(hash.bytes), object))
+}
+
+fn write_archive(file: File, entries: Vec<(String, Vec<u8>)>) -> Result<()> {
+    let encoder = zstd::Encoder::new(file, ZSTD_LEVEL)?;
+    let mut builder = tar::Builder::new(encoder);
+
+    for (entry_path, data) in entries {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, entry_path, data.as_slice())?;
+    }
+
+    let mut file = builder.into_inner()?.finish()?;
+    file.flush()?;
+    file.sync_all()?;
+    Ok(())
+}
+
+fn read_archive(file: File) -> Result<HashMap<String, Vec<u8>>> {
+    let mut archive = tar::Archive::new(zstd::Decoder::new(file)?);
+
+    let mut entries = HashMap::new();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let entry_path = entry.path()?.to_string_lossy().to_string();
+        let mut data = Vec::new();
+        entry.read_to_end(&mut data)?;
+        entries.insert(entry_path, data);
+    }
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use kameo::request::MessageSend;
+    use liberum_core::proto::PlainFileObject;
+    use tempdir::TempDir;
+
+    use super::*;
+    use crate::vault::{LoadFragment, LoadPublishedObject, StoreFragment, StorePublishedObject};
+
+    #[tokio::test]
+    async fn snapshot_export_import_test() {
+        let tmp_dir = TempDir::new("liberum_tests").unwrap();
+        let source = kameo::spawn(
+            Vault::new_on_disk(&tmp_dir.path().join("source"))
+                .await
+                .unwrap(),
+        );
+        let object: TypedObject = PlainFileObject {
+            name: "file".to_string(),
+            content: vec![1, 2, 3],
+        }
+        .into();
+        let hash = proto::Hash::try_from(&object).unwrap();
+        source
+            .ask(StorePublishedObject {
+                hash: hash.clone(),
+                object,
+            })
+            .send()
+            .await
+            .unwrap();
+        let data = stream::once(async { Ok(Bytes::from(vec![7u8; 100])) }).boxed();
+        let key = source
+            .ask(StoreFragment { key: None, data })
+            .send()
+            .await
+            .unwrap();
+
+        let snapshot_path = tmp_dir.path().join("snapshot.tar.zst");
+        let exported = source
+            .ask(ExportSnapshot {
+                target: FileTarget::open(&snapshot_path, None).unwrap(),
+            })
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(exported.published_objects, 1);
+        assert_eq!(exported.fragments, 1);
+
+        let target = kameo::spawn(
+            Vault::new_on_disk(&tmp_dir.path().join("target"))
+                .await
+                .unwrap(),
+        );
+        let imported = target
+            .ask(ImportSnapshot {
+                file: File::open(&snapshot_path).unwrap(),
+            })
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(imported.published_objects, 1);
+        assert_eq!(imported.fragments, 1);
+        assert!(target
+            .ask(LoadPublishedObject { hash })
+            .send()
+            .await
+            .unwrap()
+            .is_some());
+        assert!(target
+            .ask(LoadFragment(key))
+            .send()
+            .await
+            .unwrap()
+            .is_some());
+
+        let imported_again = target
+            .ask(ImportSnapshot {
+                file: File::open(&snapshot_path).unwrap(),
+            })
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(imported_again.skipped, 2);
+    }
+}
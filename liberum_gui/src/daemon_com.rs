@@ -1,211 +1,396 @@
-use std::path::Path;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::SystemTime;
 
 use anyhow::{anyhow, bail, Result};
-use liberum_core::{DaemonRequest, DaemonResponse, DaemonResult};
-use tokio::sync::mpsc::{Receiver, Sender};
-use tracing::{debug, error, info};
+use liberum_core::daemon_config;
+use liberum_core::node_config::NodeConfig;
+use liberum_core::types::{Contact, NodeStatsSample, ObjectAccess, TrustLevel, TypedObjectInfo};
+use liberum_core::{DaemonRequest, DaemonResponse};
+use tokio::sync::oneshot::{self, error::TryRecvError};
+use tracing::{debug, error};
+
+///! Communication of the views with the daemon. The requests run on a tokio runtime
+///! thread, so the UI never waits for the daemon. Every request returns a
+///! RequestState, which the view keeps and polls on every frame to get the result.
+
+/// State of a request sent to the daemon
+pub enum RequestState<T> {
+    Idle,
+    Pending(oneshot::Receiver<Result<T>>),
+    Done(Result<T>),
+}
+
+impl<T> Default for RequestState<T> {
+    fn default() -> Self {
+        RequestState::Idle
+    }
+}
+
+impl<T> RequestState<T> {
+    /// Checks whether the result of a pending request has arrived
+    pub fn poll(&mut self) {
+        if let RequestState::Pending(receiver) = self {
+            match receiver.try_recv() {
+                Ok(result) => *self = RequestState::Done(result),
+                Err(TryRecvError::Empty) => {}
+                Err(TryRecvError::Closed) => {
+                    *self = RequestState::Done(Err(anyhow!("Request was cancelled")))
+                }
+            }
+        }
+    }
+
+    pub fn is_pending(&mut self) -> bool {
+        self.poll();
+        matches!(self, RequestState::Pending(_))
+    }
+
+    /// Takes the result of a finished request, the state becomes idle again
+    pub fn take(&mut self) -> Option<Result<T>> {
+        self.poll();
+        match std::mem::take(self) {
+            RequestState::Done(result) => Some(result),
+            state => {
+                *self = state;
+                None
+            }
+        }
+    }
+}
 
 pub struct DaemonCom {
-    pub rt: tokio::runtime::Runtime,
-    pub to_daemon_sender: Sender<DaemonRequest>,
-    pub from_daemon_receiver: Receiver<DaemonResult>,
+    rt: tokio::runtime::Runtime,
+    socket_path: PathBuf,
+    pending_count: Arc<AtomicUsize>,
 }
 
 impl DaemonCom {
     pub fn new() -> Result<Self> {
-        let rt = tokio::runtime::Builder::new_current_thread()
+        let rt = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
             .enable_all()
             .build()?;
-        let path = Path::new("/tmp/liberum-core/");
-        let contact =
-            rt.block_on(async { liberum_core::connect(path.join("liberum-core-socket")).await });
-        let (to_daemon_sender, from_daemon_receiver) = match contact {
-            Ok(c) => c,
-            Err(e) => {
-                error!(
-                    err = e.to_string(),
-                    "Failed to connect to the core. Make sure the core is running!"
-                );
-                Err(anyhow!(e))?
-            }
-        };
+        let socket_path = daemon_config::client_socket_path();
+
+        // Fail early if the daemon is not running
+        if let Err(e) = rt.block_on(liberum_core::connect(socket_path.clone())) {
+            error!(
+                err = e.to_string(),
+                "Failed to connect to the core. Make sure the core is running!"
+            );
+            Err(anyhow!(e))?
+        }
 
         Ok(Self {
             rt,
-            to_daemon_sender,
-            from_daemon_receiver,
+            socket_path,
+            pending_count: Arc::new(AtomicUsize::new(0)),
         })
     }
 
-    pub fn run_node(&mut self, name: &str) -> Result<()> {
-        debug!(name = name.to_string(), "Trying to run node");
-
-        self.rt.block_on(async {
-            self.to_daemon_sender
-                .send(DaemonRequest::StartNode {
-                    node_name: name.to_string(),
-                })
-                .await?;
-
-            match self.from_daemon_receiver.recv().await {
-                Some(r) => info!(response = format!("{r:?}"), "Daemon responds: {:?}", r),
-                None => {
-                    error!("Failed to receive response");
-                }
-            }
+    /// Whether any request is waiting for the daemon. The UI should be repainted
+    /// while there are pending requests, so the results are shown as soon as they arrive
+    pub fn has_pending(&self) -> bool {
+        self.pending_count.load(Ordering::SeqCst) > 0
+    }
 
-            anyhow::Ok(())
-        })?;
+    pub fn run_node(&self, name: &str) -> RequestState<()> {
+        debug!(name = name.to_string(), "Trying to run node");
+        let request = DaemonRequest::StartNode {
+            node_name: name.to_string(),
+        };
 
-        Ok(())
+        self.request(request, |r| match r {
+            DaemonResponse::NodeStarted => Ok(()),
+            _ => bail!("Unexpected response type"),
+        })
     }
 
-    pub fn stop_node(&mut self, name: &str) -> Result<()> {
+    pub fn stop_node(&self, name: &str) -> RequestState<()> {
         debug!(name = name.to_string(), "Trying to stop node");
+        let request = DaemonRequest::StopNode {
+            node_name: name.to_string(),
+        };
 
-        self.rt.block_on(async {
-            self.to_daemon_sender
-                .send(DaemonRequest::StopNode {
-                    node_name: name.to_string(),
-                })
-                .await?;
-
-            match self.from_daemon_receiver.recv().await {
-                Some(r) => info!(response = format!("{r:?}"), "Daemon responds: {:?}", r),
-                None => {
-                    error!("Failed to receive response");
-                }
-            }
+        self.request(request, |r| match r {
+            DaemonResponse::NodeStopped => Ok(()),
+            _ => bail!("Unexpected response type"),
+        })
+    }
 
-            anyhow::Ok(())
-        })?;
+    pub fn create_node(&self, name: &str, id_seed: Option<String>) -> RequestState<()> {
+        debug!(name = name.to_string(), "Trying to create node");
+        let request = DaemonRequest::NewNode {
+            node_name: name.to_string(),
+            id_seed,
+        };
 
-        Ok(())
+        self.request(request, |r| match r {
+            DaemonResponse::NodeCreated => Ok(()),
+            _ => bail!("Unexpected response type"),
+        })
     }
 
-    pub fn create_node(&mut self, name: &str) -> Result<()> {
-        debug!(name = name.to_string(), "Trying to create node");
+    /// Creates a node with the given config and starts it, if `start` is set. The
+    /// node is deleted again if its config could not be saved
+    pub fn create_configured_node(
+        &self,
+        name: &str,
+        id_seed: Option<String>,
+        config: NodeConfig,
+        start: bool,
+    ) -> RequestState<()> {
+        debug!(name = name.to_string(), "Trying to create configured node");
+        let socket_path = self.socket_path.clone();
+        let name = name.to_string();
+
+        self.spawn(async move {
+            let new_node = DaemonRequest::NewNode {
+                node_name: name.clone(),
+                id_seed,
+            };
+            send_request(&socket_path, new_node).await?;
 
-        self.rt.block_on(async {
-            self.to_daemon_sender
-                .send(DaemonRequest::NewNode {
-                    node_name: name.to_string(),
-                    id_seed: None,
-                })
-                .await?;
-
-            match self.from_daemon_receiver.recv().await {
-                Some(r) => info!(response = format!("{r:?}"), "Daemon responds: {:?}", r),
-                None => {
-                    error!("Failed to receive response");
+            let overwrite_config = DaemonRequest::OverwriteNodeConfig {
+                node_name: name.clone(),
+                new_cfg: config,
+            };
+            if let Err(e) = send_request(&socket_path, overwrite_config).await {
+                let delete_node = DaemonRequest::DeleteNode {
+                    node_name: name.clone(),
+                    force: true,
+                    keep_vault: false,
+                };
+                if let Err(delete_err) = send_request(&socket_path, delete_node).await {
+                    return Err(anyhow!(
+                        "{e}, the node {name} could not be deleted: {delete_err}"
+                    ));
                 }
+                return Err(e);
             }
 
-            anyhow::Ok(())
-        })?;
+            if start {
+                let start_node = DaemonRequest::StartNode { node_name: name };
+                send_request(&socket_path, start_node).await?;
+            }
 
-        Ok(())
+            Ok(())
+        })
     }
 
-    pub fn publish_file(&mut self, node_name: &str, file_path: &Path) -> Result<String> {
+    pub fn publish_file(&self, node_name: &str, file_path: &Path) -> RequestState<String> {
         debug!(
             name = node_name.to_string(),
             path = file_path.display().to_string(),
             "Trying to publish file"
         );
+        let request = DaemonRequest::PublishFile {
+            node_name: node_name.to_string(),
+            path: file_path.to_path_buf(),
+            access: ObjectAccess::Public,
+            previous: None,
+        };
 
-        self.rt.block_on(async {
-            self.to_daemon_sender
-                .send(DaemonRequest::PublishFile {
-                    node_name: node_name.to_string(),
-                    path: file_path.to_path_buf(),
-                })
-                .await?;
-
-            match self.from_daemon_receiver.recv().await {
-                Some(r) => {
-                    match r {
-                        Ok(DaemonResponse::FilePublished { id }) => return Ok(id),
-                        Err(e) => {
-                            error!(err = e.to_string(), "Error ocurred while publishing file!");
-                            bail!("Error occured while publishing file: {}", e.to_string());
-                        }
-                        _ => {
-                            error!("Unexpected response type");
-                            bail!("Unexpected response type");
-                        }
-                    };
-                }
-                None => {
-                    error!("Failed to receive response");
-                    bail!("Failed to receive response from the daemon");
-                }
-            }
+        self.request(request, |r| match r {
+            DaemonResponse::FilePublished { id } => Ok(id),
+            _ => bail!("Unexpected response type"),
         })
     }
 
-    pub fn download_file(&mut self, node_name: &str, file_id: &str) -> Result<Vec<u8>> {
-        self.rt.block_on(async {
-            self.to_daemon_sender
-                .send(DaemonRequest::DownloadFile {
-                    node_name: node_name.to_string(),
-                    id: file_id.to_string(),
-                })
-                .await?;
-
-            match self.from_daemon_receiver.recv().await {
-                Some(r) => {
-                    match r {
-                        Ok(DaemonResponse::FileDownloaded { data, .. }) => return Ok(data.content),
-                        Err(e) => {
-                            error!(err = e.to_string(), "Error ocurred while downloading file!");
-                            bail!("Error occured while publishing file: {}", e.to_string());
-                        }
-                        _ => {
-                            error!("Unexpected response type");
-                            bail!("Unexpected response type");
-                        }
-                    };
-                }
-                None => {
-                    error!("Failed to receive response");
-                    bail!("Failed to receive response from the daemon");
-                }
-            }
+    pub fn download_file(&self, node_name: &str, file_id: &str) -> RequestState<Vec<u8>> {
+        let request = DaemonRequest::DownloadFile {
+            node_name: node_name.to_string(),
+            id: file_id.to_string(),
+            group: None,
+            force_network: false,
+        };
+
+        self.request(request, |r| match r {
+            DaemonResponse::FileDownloaded { data, .. } => Ok(data.content),
+            _ => bail!("Unexpected response type"),
         })
     }
 
-    pub fn dial(&mut self, node_name: &str, peer_id: &str, addr: &str) -> Result<()> {
-        self.rt.block_on(async {
-            self.to_daemon_sender
-                .send(DaemonRequest::Dial {
-                    node_name: node_name.to_string(),
-                    peer_id: peer_id.to_string(),
-                    addr: addr.to_string(),
-                })
-                .await?;
-
-            match self.from_daemon_receiver.recv().await {
-                Some(r) => {
-                    match r {
-                        Ok(DaemonResponse::Dialed) => {}
-                        Err(e) => {
-                            error!(err = e.to_string(), "Error ocurred while dialing peer!");
-                            bail!("Error occured while publishing file: {}", e.to_string());
-                        }
-                        _ => {
-                            error!("Unexpected response type");
-                            bail!("Unexpected response type");
-                        }
-                    };
-                }
-                None => {
-                    error!("Failed to receive response");
-                    bail!("Failed to receive response from the daemon");
-                }
-            };
+    /// Dials the peer, the peer ID may be empty. Returns the ID of the connected peer
+    pub fn dial(&self, node_name: &str, peer_id: &str, addr: &str) -> RequestState<String> {
+        let request = DaemonRequest::Dial {
+            node_name: node_name.to_string(),
+            peer_id: Some(peer_id.to_string()).filter(|id| !id.is_empty()),
+            addr: addr.to_string(),
+        };
 
-            Ok(())
+        self.request(request, |r| match r {
+            DaemonResponse::Dialed { peer_id } => Ok(peer_id),
+            _ => bail!("Unexpected response type"),
+        })
+    }
+
+    pub fn get_published_objects(&self, node_name: &str) -> RequestState<Vec<TypedObjectInfo>> {
+        let request = DaemonRequest::GetPublishedObjects {
+            node_name: node_name.to_string(),
+        };
+
+        self.request(request, |r| match r {
+            DaemonResponse::PublishedObjectsList { object_infos } => Ok(object_infos),
+            _ => bail!("Unexpected response type"),
+        })
+    }
+
+    /// Deletes the object from all the nodes providing it.
+    /// Returns the number of successful and failed deletes
+    pub fn delete_object(&self, node_name: &str, object_id: &str) -> RequestState<(u32, u32)> {
+        let request = DaemonRequest::DeleteObject {
+            node_name: node_name.to_string(),
+            object_id: object_id.to_string(),
+        };
+
+        self.request(request, |r| match r {
+            DaemonResponse::ObjectDeleted {
+                deleted_count,
+                failed_count,
+                ..
+            } => Ok((deleted_count, failed_count)),
+            _ => bail!("Unexpected response type"),
+        })
+    }
+
+    pub fn stop_providing(
+        &self,
+        node_name: &str,
+        object_id: &str,
+        keep_in_vault: bool,
+    ) -> RequestState<()> {
+        let request = DaemonRequest::StopProviding {
+            node_name: node_name.to_string(),
+            object_id: object_id.to_string(),
+            keep_in_vault,
+        };
+
+        self.request(request, |r| match r {
+            DaemonResponse::StoppedProviding => Ok(()),
+            _ => bail!("Unexpected response type"),
+        })
+    }
+
+    pub fn disconnect_peer(&self, node_name: &str, peer_id: &str) -> RequestState<()> {
+        let request = DaemonRequest::DisconnectPeer {
+            node_name: node_name.to_string(),
+            peer_id: peer_id.to_string(),
+        };
+
+        self.request(request, |r| match r {
+            DaemonResponse::PeerDisconnected => Ok(()),
+            _ => bail!("Unexpected response type"),
+        })
+    }
+
+    /// The stats of the node recorded since the time
+    pub fn get_stats_history(
+        &self,
+        node_name: &str,
+        since: SystemTime,
+    ) -> RequestState<Vec<NodeStatsSample>> {
+        let request = DaemonRequest::GetNodeStatsHistory {
+            node_name: node_name.to_string(),
+            since: Some(since),
+            until: None,
+        };
+
+        self.request(request, |r| match r {
+            DaemonResponse::NodeStatsHistory(samples) => Ok(samples),
+            _ => bail!("Unexpected response type"),
         })
     }
+
+    pub fn list_contacts(&self, node_name: &str) -> RequestState<Vec<Contact>> {
+        let request = DaemonRequest::ListContacts {
+            node_name: node_name.to_string(),
+        };
+
+        self.request(request, |r| match r {
+            DaemonResponse::Contacts(contacts) => Ok(contacts),
+            _ => bail!("Unexpected response type"),
+        })
+    }
+
+    pub fn add_contact(
+        &self,
+        node_name: &str,
+        peer_id: &str,
+        alias: &str,
+        address: Option<String>,
+    ) -> RequestState<()> {
+        let request = DaemonRequest::AddContact {
+            node_name: node_name.to_string(),
+            peer_id: peer_id.to_string(),
+            alias: alias.to_string(),
+            address,
+            trust: TrustLevel::default(),
+        };
+
+        self.request(request, |r| match r {
+            DaemonResponse::ContactAdded => Ok(()),
+            _ => bail!("Unexpected response type"),
+        })
+    }
+
+    pub fn remove_contact(&self, node_name: &str, peer_id: &str) -> RequestState<()> {
+        let request = DaemonRequest::RemoveContact {
+            node_name: node_name.to_string(),
+            peer_id: peer_id.to_string(),
+        };
+
+        self.request(request, |r| match r {
+            DaemonResponse::ContactRemoved { .. } => Ok(()),
+            _ => bail!("Unexpected response type"),
+        })
+    }
+
+    /// Sends the request and converts the response using `map`
+    fn request<T, F>(&self, request: DaemonRequest, map: F) -> RequestState<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(DaemonResponse) -> Result<T> + Send + 'static,
+    {
+        let socket_path = self.socket_path.clone();
+        self.spawn(async move { send_request(&socket_path, request).await.and_then(map) })
+    }
+
+    fn spawn<T, F>(&self, future: F) -> RequestState<T>
+    where
+        T: Send + 'static,
+        F: Future<Output = Result<T>> + Send + 'static,
+    {
+        let (sender, receiver) = oneshot::channel();
+        let pending_count = self.pending_count.clone();
+        pending_count.fetch_add(1, Ordering::SeqCst);
+
+        self.rt.spawn(async move {
+            let result = future.await;
+            if let Err(e) = &result {
+                error!(err = e.to_string(), "Daemon request failed");
+            }
+            pending_count.fetch_sub(1, Ordering::SeqCst);
+            let _ = sender.send(result);
+        });
+
+        RequestState::Pending(receiver)
+    }
+}
+
+/// Every request uses its own connection, so a slow request like a download
+/// does not hold back the other ones
+async fn send_request(socket_path: &Path, request: DaemonRequest) -> Result<DaemonResponse> {
+    let (to_daemon_sender, mut from_daemon_receiver) =
+        liberum_core::connect(socket_path.to_path_buf()).await?;
+    to_daemon_sender.send(request).await?;
+
+    match from_daemon_receiver.recv().await {
+        Some(Ok(response)) => Ok(response),
+        Some(Err(e)) => bail!("Daemon returned error: {e}"),
+        None => bail!("Failed to receive response from the daemon"),
+    }
 }
@@ -2,12 +2,12 @@ pub mod daemon_com;
 pub mod system_observer;
 pub mod views;
 
-use std::{cell::RefCell, rc::Rc, sync::Arc};
+use std::{cell::RefCell, rc::Rc, sync::Arc, time::Duration};
 
 use anyhow::{anyhow, Result};
 use daemon_com::DaemonCom;
-use egui::Visuals;
-use system_observer::{SystemObserver, SystemState};
+use egui::{Color32, Visuals};
+use system_observer::{DaemonConnectivity, SystemObserver, SystemState};
 use views::{AppView, NodesListView, ViewAction, ViewContext};
 
 use std::sync::Mutex;
@@ -45,8 +45,15 @@ impl eframe::App for MyApp {
             _egui_frame: frame,
         };
 
+        show_daemon_status(&self.system_state, ctx);
         let action = self.current_view.draw(&mut view_ctx);
 
+        // Nothing triggers a repaint when a response from the daemon arrives,
+        // so the views are redrawn until all the requests are done
+        if self.daemon_com.has_pending() {
+            ctx.request_repaint_after(Duration::from_millis(100));
+        }
+
         match action {
             ViewAction::Stay => {}
             ViewAction::SwitchView { view } => {
@@ -58,6 +65,42 @@ impl eframe::App for MyApp {
     }
 }
 
+/// Shows whether the daemon answers, above the view
+fn show_daemon_status(system_state: &Mutex<Option<SystemState>>, ctx: &egui::Context) {
+    let daemon = system_state
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|s| s.daemon.clone())
+        .unwrap_or_default();
+    let (color, text) = match daemon {
+        DaemonConnectivity::Unknown => (Color32::GRAY, "Connecting to the daemon".to_string()),
+        DaemonConnectivity::Connected { health, latency } => (
+            Color32::from_rgb(0, 150, 0),
+            format!(
+                "Daemon {} up for {}s, {} nodes running, {} requests queued, {} ms",
+                health.version,
+                health.uptime.as_secs(),
+                health.running_nodes,
+                health.queued_requests,
+                latency.as_millis()
+            ),
+        ),
+        DaemonConnectivity::Unresponsive => (
+            Color32::from_rgb(200, 120, 0),
+            "The daemon does not answer".to_string(),
+        ),
+        DaemonConnectivity::Disconnected => (
+            Color32::from_rgb(200, 0, 0),
+            "Disconnected from the daemon".to_string(),
+        ),
+    };
+
+    egui::TopBottomPanel::top(egui::Id::new("daemon_status")).show(ctx, |ui| {
+        ui.colored_label(color, text);
+    });
+}
+
 fn main() -> Result<()> {
     tracing_subscriber::fmt()
         .with_max_level(tracing::Level::DEBUG)
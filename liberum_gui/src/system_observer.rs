@@ -1,9 +1,10 @@
-use liberum_core::{node_config::NodeConfig, types::NodeInfo};
+use liberum_core::daemon_config;
+use liberum_core::node_config::NodeConfig;
+use liberum_core::types::{DaemonHealth, NodeEvent, NodeInfo, PeerInfo};
 use std::{
     collections::{HashMap, HashSet},
-    path::Path,
     sync::Arc,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use anyhow::{anyhow, Result};
@@ -17,8 +18,34 @@ use tracing::{debug, error};
 pub struct SystemState {
     pub node_infos: Vec<NodeInfo>,
     pub node_configs: HashMap<String, NodeConfig>,
+    /// Peers of the observed running nodes
+    pub routing_tables: HashMap<String, Vec<PeerInfo>>,
+    /// Events of the observed nodes, collected since the node was first observed
+    pub node_events: HashMap<String, Vec<NodeEvent>>,
+    pub daemon: DaemonConnectivity,
 }
 
+/// Whether the daemon answers the pings of the observer
+#[derive(Default, Clone)]
+pub enum DaemonConnectivity {
+    #[default]
+    Unknown,
+    Connected {
+        health: DaemonHealth,
+        latency: Duration,
+    },
+    /// The ping was not answered in time, the daemon may be overloaded
+    Unresponsive,
+    /// The connection to the daemon was closed
+    Disconnected,
+}
+
+/// Number of the events of a node kept by the observer
+const MAX_NODE_EVENTS: usize = 1000;
+
+/// How long the daemon may take to answer a ping before it's shown as unresponsive
+const PING_TIMEOUT: Duration = Duration::from_secs(2);
+
 pub struct SystemObserver {
     rt: tokio::runtime::Runtime,
     pub system_state: Arc<Mutex<Option<SystemState>>>,
@@ -33,9 +60,8 @@ impl SystemObserver {
             .worker_threads(1)
             .enable_all()
             .build()?;
-        let path = Path::new("/tmp/liberum-core/");
-        let contact =
-            rt.block_on(async { liberum_core::connect(path.join("liberum-core-socket")).await });
+        let socket_path = daemon_config::client_socket_path();
+        let contact = rt.block_on(async { liberum_core::connect(socket_path).await });
         let (to_daemon_sender, from_daemon_receiver) = match contact {
             Ok(c) => c,
             Err(e) => {
@@ -78,9 +104,47 @@ impl SystemObserver {
         let observed_configs = self.observed_node_configs.clone();
 
         let update_loop_handle = self.rt.spawn(async move {
+            let mut node_events: HashMap<String, Vec<NodeEvent>> = HashMap::new();
+
             loop {
                 debug!("Updating state");
 
+                let set_daemon = |daemon: DaemonConnectivity| {
+                    system_state
+                        .lock()
+                        .unwrap()
+                        .get_or_insert_with(SystemState::default)
+                        .daemon = daemon;
+                };
+                let sent_at = Instant::now();
+                if to_daemon_sender.send(DaemonRequest::Ping).await.is_err() {
+                    set_daemon(DaemonConnectivity::Disconnected);
+                    break;
+                }
+                // A late answer is still waited for, the responses come in order
+                let pong =
+                    match tokio::time::timeout(PING_TIMEOUT, from_daemon_receiver.recv()).await {
+                        Ok(pong) => pong,
+                        Err(_) => {
+                            set_daemon(DaemonConnectivity::Unresponsive);
+                            from_daemon_receiver.recv().await
+                        }
+                    };
+                let daemon = match pong {
+                    Some(Ok(DaemonResponse::Pong(health))) => DaemonConnectivity::Connected {
+                        health,
+                        latency: sent_at.elapsed(),
+                    },
+                    Some(_) => panic!("expected pong"),
+                    None => {
+                        set_daemon(DaemonConnectivity::Disconnected);
+                        break;
+                    }
+                };
+                set_daemon(daemon.clone());
+
+                debug!("Got pong");
+
                 to_daemon_sender
                     .send(DaemonRequest::ListNodes)
                     .await
@@ -136,9 +200,72 @@ impl SystemObserver {
 
                 debug!("Got observed configs");
 
+                let mut routing_tables = HashMap::new();
+                let running_node_names = nodes
+                    .iter()
+                    .filter(|n| n.is_running && configs.contains_key(&n.name))
+                    .map(|n| n.name.clone())
+                    .collect::<Vec<String>>();
+
+                for node_name in running_node_names {
+                    to_daemon_sender
+                        .send(DaemonRequest::GetRoutingTable {
+                            node_name: node_name.clone(),
+                        })
+                        .await
+                        .expect("Failed to send message to the daemon");
+
+                    let response = from_daemon_receiver
+                        .recv()
+                        .await
+                        .expect("No response from the daemon");
+
+                    // The node may have stopped in the meantime
+                    match response {
+                        Ok(DaemonResponse::RoutingTable { peers, .. }) => {
+                            routing_tables.insert(node_name, peers);
+                        }
+                        Ok(_) => panic!("expected routing table"),
+                        Err(e) => debug!(err = e.to_string(), "Failed to get routing table"),
+                    }
+                }
+
+                debug!("Got routing tables");
+
+                for node_name in routing_tables.keys() {
+                    let events = node_events.entry(node_name.clone()).or_default();
+                    to_daemon_sender
+                        .send(DaemonRequest::GetNodeEvents {
+                            node_name: node_name.clone(),
+                            since: events.last().map(|e| e.id),
+                        })
+                        .await
+                        .expect("Failed to send message to the daemon");
+
+                    let response = from_daemon_receiver
+                        .recv()
+                        .await
+                        .expect("No response from the daemon");
+
+                    match response {
+                        Ok(DaemonResponse::NodeEvents { events: new_events }) => {
+                            events.extend(new_events);
+                            let overflow = events.len().saturating_sub(MAX_NODE_EVENTS);
+                            events.drain(..overflow);
+                        }
+                        Ok(_) => panic!("expected node events"),
+                        Err(e) => debug!(err = e.to_string(), "Failed to get node events"),
+                    }
+                }
+
+                debug!("Got node events");
+
                 system_state.lock().unwrap().replace(SystemState {
                     node_infos: nodes,
                     node_configs: configs,
+                    routing_tables,
+                    node_events: node_events.clone(),
+                    daemon,
                 });
 
                 tokio::time::sleep(Duration::from_secs(1)).await;
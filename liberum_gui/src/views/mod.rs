@@ -1,3 +1,4 @@
+pub mod node_creation_wizard;
 pub mod node_view;
 pub mod nodes_list_view;
 
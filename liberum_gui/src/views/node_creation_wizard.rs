@@ -0,0 +1,185 @@
+use anyhow::{anyhow, Result};
+use egui::Color32;
+use liberum_core::node_config::{BootstrapNode, NodeConfig};
+
+use crate::daemon_com::RequestState;
+
+use super::ViewContext;
+
+/// Window creating a node together with its configuration in one flow
+#[derive(Default)]
+pub struct NodeCreationWizard {
+    pub opened: bool,
+    name: String,
+    id_seed: String,
+    bootstrap_id: String,
+    bootstrap_addr: String,
+    external_addr: String,
+    config: NodeConfig,
+    status_line: String,
+    request: RequestState<()>,
+    /// The name of the node created by the wizard, for the parent view to show
+    created: Option<String>,
+}
+
+impl NodeCreationWizard {
+    pub fn open(&mut self) {
+        *self = NodeCreationWizard {
+            opened: true,
+            ..Default::default()
+        };
+    }
+
+    /// The name of the node created since the last call, the wizard is closed then
+    pub fn take_created(&mut self) -> Option<String> {
+        self.created.take()
+    }
+
+    pub fn show(&mut self, ctx: &mut ViewContext) {
+        let mut opened = self.opened;
+        let egui_ctx = ctx.egui_ctx;
+
+        if let Some(result) = self.request.take() {
+            match result {
+                Ok(()) => {
+                    self.created = Some(self.name.clone());
+                    self.opened = false;
+                }
+                Err(e) => self.status_line = e.to_string(),
+            }
+        }
+        let busy = self.request.is_pending();
+
+        egui::Window::new("Create node")
+            .open(&mut opened)
+            .show(egui_ctx, |ui| {
+                egui::Grid::new("wizard_grid")
+                    .num_columns(2)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.label("Name");
+                        ui.text_edit_singleline(&mut self.name);
+                        ui.end_row();
+
+                        ui.label("ID seed (testing only)");
+                        ui.text_edit_singleline(&mut self.id_seed);
+                        ui.end_row();
+
+                        ui.label("Bootstrap nodes");
+                        ui.vertical(|ui| self.show_bootstrap_nodes(ui));
+                        ui.end_row();
+
+                        ui.label("External addresses");
+                        ui.vertical(|ui| self.show_external_addresses(ui));
+                        ui.end_row();
+
+                        ui.label("Autostart");
+                        ui.checkbox(&mut self.config.autostart, "Start with the daemon");
+                        ui.end_row();
+                    });
+
+                ui.add_space(10.0);
+
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(!busy, egui::Button::new("Create")).clicked() {
+                        if let Err(e) = self.create(ctx) {
+                            self.status_line = e.to_string();
+                        }
+                    }
+
+                    if busy {
+                        ui.spinner();
+                    }
+                });
+
+                ui.colored_label(Color32::from_rgb(200, 0, 0), &self.status_line);
+            });
+
+        self.opened &= opened;
+    }
+
+    fn show_bootstrap_nodes(&mut self, ui: &mut egui::Ui) {
+        let mut removed = None;
+        for (i, node) in self.config.bootstrap_nodes.iter().enumerate() {
+            ui.horizontal(|ui| {
+                ui.label(format!("{} @ {}", node.id, node.addr));
+                if ui.button("Remove").clicked() {
+                    removed = Some(i);
+                }
+            });
+        }
+        if let Some(i) = removed {
+            self.config.bootstrap_nodes.remove(i);
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("Peer ID:");
+            ui.text_edit_singleline(&mut self.bootstrap_id);
+        });
+        ui.horizontal(|ui| {
+            ui.label("Address:");
+            ui.text_edit_singleline(&mut self.bootstrap_addr);
+            if ui.button("Add").clicked() {
+                match BootstrapNode::from_strings(&self.bootstrap_id, &self.bootstrap_addr) {
+                    Ok(node) => {
+                        self.config.bootstrap_nodes.push(node);
+                        self.bootstrap_id = String::new();
+                        self.bootstrap_addr = String::new();
+                    }
+                    Err(e) => self.status_line = format!("Invalid bootstrap node: {e}"),
+                }
+            }
+        });
+    }
+
+    fn show_external_addresses(&mut self, ui: &mut egui::Ui) {
+        let mut removed = None;
+        for (i, addr) in self.config.external_addresses.iter().enumerate() {
+            ui.horizontal(|ui| {
+                ui.label(addr.to_string());
+                if ui.button("Remove").clicked() {
+                    removed = Some(i);
+                }
+            });
+        }
+        if let Some(i) = removed {
+            self.config.external_addresses.remove(i);
+        }
+
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.external_addr);
+            if ui.button("Add").clicked() {
+                match self.external_addr.parse() {
+                    Ok(addr) => {
+                        self.config.external_addresses.push(addr);
+                        self.external_addr = String::new();
+                    }
+                    Err(e) => self.status_line = format!("Invalid address: {e}"),
+                }
+            }
+        });
+    }
+
+    /// Sends the request creating the node with the config from the wizard.
+    /// The node is started right away if autostart is selected
+    fn create(&mut self, ctx: &mut ViewContext) -> Result<()> {
+        if self.name.is_empty() {
+            return Err(anyhow!("Node name is required"));
+        }
+
+        let id_seed = match self.id_seed.is_empty() {
+            true => None,
+            false => Some(self.id_seed.clone()),
+        };
+
+        self.request = ctx.daemon_com.create_configured_node(
+            &self.name,
+            id_seed,
+            self.config.clone(),
+            self.config.autostart,
+        );
+        self.status_line = String::new();
+
+        Ok(())
+    }
+}
@@ -1,23 +1,72 @@
 use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
 
+use chrono::{DateTime, Local};
 use egui::{Align2, Color32};
 use egui_file::FileDialog;
-use liberum_core::types::NodeInfo;
+use egui_plot::{Line, Plot, PlotPoints};
+use liberum_core::proto::{PlainFileObject, SignedObject};
+use liberum_core::types::{
+    Contact, NodeEvent, NodeEventKind, NodeInfo, NodeStatsSample, TypedObjectInfo,
+};
+use uuid::Uuid;
+
+use crate::daemon_com::RequestState;
 
 use super::{AppView, NodesListView, ViewAction, ViewContext};
 
+/// Number of the events of the view kept for the event log
+const MAX_VIEW_EVENTS: usize = 200;
+
 pub struct NodeView {
     node_name: String,
     file_to_send_path: Option<PathBuf>,
     file_to_send_dialog: Option<FileDialog>,
     file_to_download_id: String,
     config_window_opened: bool,
-    status_line: String,
     download_window_opened: bool,
     download_data: Vec<u8>,
     dial_peer_id: String,
     dial_addr: String,
     dial_history: Vec<(String, String, bool)>,
+    objects_window_opened: bool,
+    peers_window_opened: bool,
+    contacts_window_opened: bool,
+    contact_peer_id: String,
+    contact_alias: String,
+    contact_addr: String,
+    event_log_window_opened: bool,
+    event_log_filter: String,
+    event_log_hidden_kinds: Vec<NodeEventKind>,
+    /// The results of the requests of the view, shown in the event log with the
+    /// events of the node
+    view_events: Vec<NodeEvent>,
+    stats_window_opened: bool,
+    stats_range_hours: u64,
+    stats_metric: StatsMetric,
+    stats: Option<Vec<NodeStatsSample>>,
+    published_objects: Option<Vec<TypedObjectInfo>>,
+    contacts: Option<Vec<Contact>>,
+    node_request: RequestState<()>,
+    publish_request: RequestState<String>,
+    download_request: RequestState<Vec<u8>>,
+    dial_request: RequestState<String>,
+    objects_request: RequestState<Vec<TypedObjectInfo>>,
+    delete_request: RequestState<(u32, u32)>,
+    stop_providing_request: RequestState<()>,
+    disconnect_request: RequestState<()>,
+    contacts_request: RequestState<Vec<Contact>>,
+    contact_change_request: RequestState<()>,
+    stats_request: RequestState<Vec<NodeStatsSample>>,
+}
+
+/// The stat plotted in the stats window
+#[derive(Clone, Copy, PartialEq)]
+enum StatsMetric {
+    ConnectedPeers,
+    BytesSent,
+    BytesReceived,
+    ObjectsServed,
 }
 
 impl NodeView {
@@ -28,12 +77,163 @@ impl NodeView {
             file_to_send_dialog: None,
             file_to_download_id: String::new(),
             config_window_opened: false,
-            status_line: String::new(),
             download_window_opened: false,
             download_data: Vec::new(),
             dial_peer_id: String::new(),
             dial_addr: String::new(),
             dial_history: Vec::new(),
+            objects_window_opened: false,
+            peers_window_opened: false,
+            contacts_window_opened: false,
+            contact_peer_id: String::new(),
+            contact_alias: String::new(),
+            contact_addr: String::new(),
+            event_log_window_opened: false,
+            event_log_filter: String::new(),
+            event_log_hidden_kinds: Vec::new(),
+            view_events: Vec::new(),
+            stats_window_opened: false,
+            stats_range_hours: 24,
+            stats_metric: StatsMetric::ConnectedPeers,
+            stats: None,
+            published_objects: None,
+            contacts: None,
+            node_request: RequestState::Idle,
+            publish_request: RequestState::Idle,
+            download_request: RequestState::Idle,
+            dial_request: RequestState::Idle,
+            objects_request: RequestState::Idle,
+            delete_request: RequestState::Idle,
+            stop_providing_request: RequestState::Idle,
+            disconnect_request: RequestState::Idle,
+            contacts_request: RequestState::Idle,
+            contact_change_request: RequestState::Idle,
+            stats_request: RequestState::Idle,
+        }
+    }
+
+    /// Adds the event to the event log of the view
+    fn log(&mut self, kind: NodeEventKind, message: String) {
+        self.view_events.push(NodeEvent {
+            id: 0,
+            timestamp: SystemTime::now(),
+            kind,
+            message,
+        });
+        let overflow = self.view_events.len().saturating_sub(MAX_VIEW_EVENTS);
+        self.view_events.drain(..overflow);
+    }
+
+    fn log_result(&mut self, kind: NodeEventKind, result: anyhow::Result<String>) {
+        match result {
+            Ok(message) => self.log(kind, message),
+            Err(e) => self.log(NodeEventKind::Error, e.to_string()),
+        }
+    }
+
+    /// Handles the responses of the daemon which arrived since the last frame
+    fn poll_requests(&mut self) {
+        if let Some(Err(e)) = self.node_request.take() {
+            self.log(NodeEventKind::Error, e.to_string());
+        }
+
+        if let Some(result) = self.publish_request.take() {
+            let result = result.map(|id| format!("File published; id={id}"));
+            self.log_result(NodeEventKind::Publish, result);
+        }
+
+        if let Some(result) = self.download_request.take() {
+            match result {
+                Ok(data) => {
+                    self.log(NodeEventKind::Download, "File downloaded".to_string());
+                    self.file_to_download_id = String::new();
+                    self.download_window_opened = true;
+                    self.download_data = data;
+                }
+                Err(e) => self.log(NodeEventKind::Error, e.to_string()),
+            }
+        }
+
+        if let Some(result) = self.dial_request.take() {
+            let success = result.is_ok();
+            match result {
+                Ok(peer_id) => {
+                    let message = format!("Dial {} @ {} successful!", peer_id, self.dial_addr);
+                    self.log(NodeEventKind::Connection, message);
+                    self.dial_peer_id = peer_id;
+                }
+                Err(e) => self.log(NodeEventKind::Error, e.to_string()),
+            }
+
+            self.dial_history
+                .push((self.dial_peer_id.clone(), self.dial_addr.clone(), success));
+
+            if success {
+                self.dial_peer_id = String::new();
+                self.dial_addr = String::new();
+            }
+        }
+
+        if let Some(result) = self.objects_request.take() {
+            match result {
+                Ok(objects) => self.published_objects = Some(objects),
+                Err(e) => {
+                    self.log(NodeEventKind::Error, e.to_string());
+                    self.published_objects = Some(Vec::new());
+                }
+            }
+        }
+
+        if let Some(result) = self.delete_request.take() {
+            let result = result.map(|(deleted, failed)| {
+                format!("Object deleted; deleted={deleted} failed={failed}")
+            });
+            self.log_result(NodeEventKind::Message, result);
+            self.published_objects = None;
+        }
+
+        if let Some(result) = self.stop_providing_request.take() {
+            let result = result.map(|()| "Stopped providing object".to_string());
+            self.log_result(NodeEventKind::Message, result);
+            self.published_objects = None;
+        }
+
+        if let Some(result) = self.contacts_request.take() {
+            match result {
+                Ok(contacts) => self.contacts = Some(contacts),
+                Err(e) => {
+                    self.log(NodeEventKind::Error, e.to_string());
+                    self.contacts = Some(Vec::new());
+                }
+            }
+        }
+
+        if let Some(result) = self.contact_change_request.take() {
+            match result {
+                Ok(()) => {
+                    self.log(NodeEventKind::Message, "Contacts changed".to_string());
+                    self.contact_peer_id = String::new();
+                    self.contact_alias = String::new();
+                    self.contact_addr = String::new();
+                }
+                Err(e) => self.log(NodeEventKind::Error, e.to_string()),
+            }
+            self.contacts = None;
+        }
+
+        if let Some(result) = self.stats_request.take() {
+            match result {
+                Ok(samples) => self.stats = Some(samples),
+                Err(e) => {
+                    self.log(NodeEventKind::Error, e.to_string());
+                    self.stats = Some(Vec::new());
+                }
+            }
+        }
+
+        if let Some(result) = self.disconnect_request.take() {
+            let result = result.map(|()| "Peer disconnected".to_string());
+            self.log_result(NodeEventKind::Connection, result);
         }
     }
 
@@ -101,17 +301,42 @@ impl NodeView {
                 ui.add_space(10.0);
 
                 ui.horizontal(|ui| {
-                    if ui.button("Run").clicked() {
-                        let _ = ctx.daemon_com.run_node(&node_info.name);
+                    let busy = self.node_request.is_pending();
+
+                    if ui.add_enabled(!busy, egui::Button::new("Run")).clicked() {
+                        self.node_request = ctx.daemon_com.run_node(&node_info.name);
                     }
 
-                    if ui.button("Stop").clicked() {
-                        let _ = ctx.daemon_com.stop_node(&node_info.name);
+                    if ui.add_enabled(!busy, egui::Button::new("Stop")).clicked() {
+                        self.node_request = ctx.daemon_com.stop_node(&node_info.name);
                     }
 
                     if ui.button("Config").clicked() {
                         self.config_window_opened = true;
                     }
+
+                    if ui.button("Event log").clicked() {
+                        self.event_log_window_opened = true;
+                    }
+
+                    if ui.button("Peers").clicked() {
+                        self.peers_window_opened = true;
+                    }
+
+                    if ui.button("Contacts").clicked() {
+                        self.contacts_window_opened = true;
+                        self.contacts = None;
+                    }
+
+                    if ui.button("Objects").clicked() {
+                        self.objects_window_opened = true;
+                        self.published_objects = None;
+                    }
+
+                    if ui.button("Stats").clicked() {
+                        self.stats_window_opened = true;
+                        self.stats = None;
+                    }
                 });
 
                 ui.add_space(20.0);
@@ -145,20 +370,25 @@ impl NodeView {
                         }
                     }
 
-                    if ui.button("Publish file").clicked() {
+                    let busy = self.publish_request.is_pending();
+                    if ui
+                        .add_enabled(!busy, egui::Button::new("Publish file"))
+                        .clicked()
+                    {
                         match &self.file_to_send_path {
                             Some(path) => {
-                                let result = ctx.daemon_com.publish_file(&self.node_name, &path);
-                                match result {
-                                    Ok(id) => self.status_line = format!("File published; id={id}"),
-                                    Err(e) => self.status_line = e.to_string(),
-                                };
+                                self.publish_request =
+                                    ctx.daemon_com.publish_file(&self.node_name, path);
                             }
                             None => {
-                                self.status_line = "Error: No file selected".to_string();
+                                self.log(NodeEventKind::Error, "No file selected".to_string());
                             }
                         }
                     }
+
+                    if busy {
+                        ui.spinner();
+                    }
                 });
 
                 ui.add_space(20.0);
@@ -168,20 +398,21 @@ impl NodeView {
                 ui.text_edit_singleline(&mut self.file_to_download_id);
                 ui.add_space(10.0);
 
-                if ui.button("Download").clicked() {
-                    match ctx
-                        .daemon_com
-                        .download_file(&self.node_name, &self.file_to_download_id)
+                ui.horizontal(|ui| {
+                    let busy = self.download_request.is_pending();
+                    if ui
+                        .add_enabled(!busy, egui::Button::new("Download"))
+                        .clicked()
                     {
-                        Ok(data) => {
-                            self.status_line = "File downloaded".to_string();
-                            self.file_to_download_id = String::new();
-                            self.download_window_opened = true;
-                            self.download_data = data;
-                        }
-                        Err(e) => self.status_line = e.to_string(),
+                        self.download_request = ctx
+                            .daemon_com
+                            .download_file(&self.node_name, &self.file_to_download_id);
                     }
-                }
+
+                    if busy {
+                        ui.spinner();
+                    }
+                });
 
                 ui.add_space(20.0);
             });
@@ -192,45 +423,27 @@ impl NodeView {
             .anchor(Align2::RIGHT_TOP, [-16.0, 16.0])
             .show(ctx.egui_ctx, |ui| {
                 egui::TopBottomPanel::top("dial_controls").show_inside(ui, |ui| {
-                    ui.label("PeerID:");
+                    ui.label("PeerID (optional):");
                     ui.text_edit_singleline(&mut self.dial_peer_id);
                     ui.label("Peer address:");
                     ui.text_edit_singleline(&mut self.dial_addr);
 
                     ui.add_space(10.0);
 
-                    if ui.button("Dial").clicked() {
-                        match ctx.daemon_com.dial(
-                            &self.node_name,
-                            &self.dial_peer_id,
-                            &self.dial_addr,
-                        ) {
-                            Ok(_) => {
-                                self.status_line = format!(
-                                    "Dial {} @ {} successful!",
-                                    self.dial_peer_id, self.dial_addr
-                                );
-
-                                self.dial_history.push((
-                                    self.dial_peer_id.clone(),
-                                    self.dial_addr.clone(),
-                                    true,
-                                ));
+                    ui.horizontal(|ui| {
+                        let busy = self.dial_request.is_pending();
+                        if ui.add_enabled(!busy, egui::Button::new("Dial")).clicked() {
+                            self.dial_request = ctx.daemon_com.dial(
+                                &self.node_name,
+                                &self.dial_peer_id,
+                                &self.dial_addr,
+                            );
+                        }
 
-                                self.dial_peer_id = String::new();
-                                self.dial_addr = String::new();
-                            }
-                            Err(e) => {
-                                self.status_line = e.to_string();
-
-                                self.dial_history.push((
-                                    self.dial_peer_id.clone(),
-                                    self.dial_addr.clone(),
-                                    false,
-                                ));
-                            }
+                        if busy {
+                            ui.spinner();
                         }
-                    }
+                    });
 
                     ui.add_space(10.0);
 
@@ -317,6 +530,400 @@ impl NodeView {
             });
     }
 
+    fn show_objects_window(&mut self, ctx: &mut ViewContext) {
+        if !self.objects_window_opened {
+            return;
+        }
+
+        // The list is fetched when the window is opened and refreshed on demand,
+        // the daemon is not asked on every frame
+        if self.published_objects.is_none() && !self.objects_request.is_pending() {
+            self.objects_request = ctx.daemon_com.get_published_objects(&self.node_name);
+        }
+
+        let mut refresh = false;
+        let node_name = self.node_name.clone();
+        let loading = self.published_objects.is_none();
+        let busy = self.delete_request.is_pending() || self.stop_providing_request.is_pending();
+        let objects = self.published_objects.clone().unwrap_or_default();
+        let delete_request = &mut self.delete_request;
+        let stop_providing_request = &mut self.stop_providing_request;
+
+        egui::Window::new("Published objects")
+            .open(&mut self.objects_window_opened)
+            .show(ctx.egui_ctx, |ui| {
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(!loading, egui::Button::new("Refresh"))
+                        .clicked()
+                    {
+                        refresh = true;
+                    }
+
+                    if loading || busy {
+                        ui.spinner();
+                    }
+                });
+
+                ui.add_space(10.0);
+
+                if loading {
+                    ui.label("Loading objects...");
+                    return;
+                }
+
+                if objects.is_empty() {
+                    ui.label("No objects stored by the node");
+                    return;
+                }
+
+                egui::Grid::new("objects_grid")
+                    .num_columns(4)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.label("ID");
+                        ui.label("Type");
+                        ui.label("Type UUID");
+                        ui.label("Actions");
+                        ui.end_row();
+
+                        for object in &objects {
+                            ui.label(&object.id);
+                            ui.label(type_name(&object.type_id));
+                            ui.label(object.type_id.to_string());
+                            ui.horizontal(|ui| {
+                                if ui.add_enabled(!busy, egui::Button::new("Delete")).clicked() {
+                                    *delete_request =
+                                        ctx.daemon_com.delete_object(&node_name, &object.id);
+                                }
+
+                                if ui
+                                    .add_enabled(!busy, egui::Button::new("Stop providing"))
+                                    .clicked()
+                                {
+                                    *stop_providing_request = ctx
+                                        .daemon_com
+                                        .stop_providing(&node_name, &object.id, false);
+                                }
+                            });
+                            ui.end_row();
+                        }
+                    });
+            });
+
+        if refresh {
+            self.published_objects = None;
+        }
+    }
+
+    fn show_peers_window(&mut self, ctx: &mut ViewContext) {
+        let node_name = self.node_name.clone();
+        let busy = self.disconnect_request.is_pending();
+        let disconnect_request = &mut self.disconnect_request;
+
+        egui::Window::new("Peers")
+            .open(&mut self.peers_window_opened)
+            .show(ctx.egui_ctx, |ui| {
+                let system_state = ctx.system_state.lock().unwrap().clone();
+                let peers = match system_state
+                    .as_ref()
+                    .and_then(|s| s.routing_tables.get(&node_name))
+                {
+                    Some(peers) => peers.clone(),
+                    None => {
+                        ui.label("No peers available, is the node running?");
+                        return;
+                    }
+                };
+
+                ui.label(format!(
+                    "Connected: {}, in routing table: {}",
+                    peers.iter().filter(|p| p.connected).count(),
+                    peers.iter().filter(|p| p.in_routing_table).count()
+                ));
+                ui.add_space(10.0);
+
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    egui::Grid::new("peers_grid")
+                        .num_columns(6)
+                        .striped(true)
+                        .show(ui, |ui| {
+                            ui.label("Peer ID");
+                            ui.label("Addresses");
+                            ui.label("Routing table");
+                            ui.label("Latency");
+                            ui.label("Protocol");
+                            ui.label("Actions");
+                            ui.end_row();
+
+                            for peer in &peers {
+                                ui.label(&peer.peer_id);
+                                ui.vertical(|ui| {
+                                    for addr in &peer.addresses {
+                                        ui.label(addr);
+                                    }
+                                });
+                                ui.label(peer.in_routing_table.to_string());
+                                ui.label(
+                                    peer.latency
+                                        .map(|l| format!("{} ms", l.as_millis()))
+                                        .unwrap_or("N/A".to_string()),
+                                );
+                                ui.label(
+                                    peer.object_sender_version
+                                        .map(|v| format!("v{v}"))
+                                        .unwrap_or("N/A".to_string()),
+                                );
+
+                                if peer.connected {
+                                    if ui
+                                        .add_enabled(!busy, egui::Button::new("Disconnect"))
+                                        .clicked()
+                                    {
+                                        *disconnect_request = ctx
+                                            .daemon_com
+                                            .disconnect_peer(&node_name, &peer.peer_id);
+                                    }
+                                } else {
+                                    ui.label("Not connected");
+                                }
+                                ui.end_row();
+                            }
+                        });
+                });
+            });
+    }
+
+    fn show_contacts_window(&mut self, ctx: &mut ViewContext) {
+        if !self.contacts_window_opened {
+            return;
+        }
+
+        // Like the objects, the contacts are fetched when the window is opened
+        if self.contacts.is_none() && !self.contacts_request.is_pending() {
+            self.contacts_request = ctx.daemon_com.list_contacts(&self.node_name);
+        }
+
+        let node_name = self.node_name.clone();
+        let loading = self.contacts.is_none();
+        let busy = self.contact_change_request.is_pending();
+        let contacts = self.contacts.clone().unwrap_or_default();
+        let contact_change_request = &mut self.contact_change_request;
+        let peer_id = &mut self.contact_peer_id;
+        let alias = &mut self.contact_alias;
+        let addr = &mut self.contact_addr;
+
+        egui::Window::new("Contacts")
+            .open(&mut self.contacts_window_opened)
+            .show(ctx.egui_ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Peer ID");
+                    ui.text_edit_singleline(peer_id);
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Alias");
+                    ui.text_edit_singleline(alias);
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Address");
+                    ui.text_edit_singleline(addr);
+                });
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(!busy, egui::Button::new("Add")).clicked() {
+                        let address = Some(addr.trim().to_string()).filter(|a| !a.is_empty());
+                        *contact_change_request =
+                            ctx.daemon_com
+                                .add_contact(&node_name, peer_id.trim(), alias, address);
+                    }
+
+                    if loading || busy {
+                        ui.spinner();
+                    }
+                });
+
+                ui.add_space(10.0);
+
+                if loading {
+                    ui.label("Loading contacts...");
+                    return;
+                }
+
+                if contacts.is_empty() {
+                    ui.label("No contacts");
+                    return;
+                }
+
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    egui::Grid::new("contacts_grid")
+                        .num_columns(6)
+                        .striped(true)
+                        .show(ui, |ui| {
+                            ui.label("Alias");
+                            ui.label("Peer ID");
+                            ui.label("Trust");
+                            ui.label("Address");
+                            ui.label("Last seen");
+                            ui.label("Actions");
+                            ui.end_row();
+
+                            for contact in &contacts {
+                                ui.label(&contact.alias);
+                                ui.label(&contact.peer_id);
+                                ui.label(contact.trust.to_string());
+                                ui.label(contact.address.as_deref().unwrap_or("N/A"));
+                                ui.label(
+                                    contact
+                                        .last_seen
+                                        .map(|t| {
+                                            DateTime::<Local>::from(t)
+                                                .format("%Y-%m-%d %H:%M:%S")
+                                                .to_string()
+                                        })
+                                        .unwrap_or("Never".to_string()),
+                                );
+                                if ui.add_enabled(!busy, egui::Button::new("Remove")).clicked() {
+                                    *contact_change_request =
+                                        ctx.daemon_com.remove_contact(&node_name, &contact.peer_id);
+                                }
+                                ui.end_row();
+                            }
+                        });
+                });
+            });
+    }
+
+    fn show_event_log_window(&mut self, ctx: &mut ViewContext) {
+        let mut events = ctx
+            .system_state
+            .lock()
+            .unwrap()
+            .as_ref()
+            .and_then(|s| s.node_events.get(&self.node_name).cloned())
+            .unwrap_or_default();
+        events.extend(self.view_events.iter().cloned());
+        events.sort_by_key(|e| e.timestamp);
+        let hidden_kinds = &mut self.event_log_hidden_kinds;
+        let filter = &mut self.event_log_filter;
+
+        egui::Window::new("Event log")
+            .open(&mut self.event_log_window_opened)
+            .default_width(600.0)
+            .show(ctx.egui_ctx, |ui| {
+                ui.horizontal(|ui| {
+                    for kind in [
+                        NodeEventKind::Connection,
+                        NodeEventKind::Publish,
+                        NodeEventKind::Download,
+                        NodeEventKind::Error,
+                        NodeEventKind::Message,
+                        NodeEventKind::Restart,
+                    ] {
+                        let mut shown = !hidden_kinds.contains(&kind);
+                        if ui.checkbox(&mut shown, format!("{kind:?}")).changed() {
+                            match shown {
+                                true => hidden_kinds.retain(|k| *k != kind),
+                                false => hidden_kinds.push(kind),
+                            }
+                        }
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Filter:");
+                    ui.text_edit_singleline(filter);
+                });
+                ui.add_space(10.0);
+
+                egui::ScrollArea::vertical()
+                    .stick_to_bottom(true)
+                    .max_height(400.0)
+                    .show(ui, |ui| {
+                        let shown_events = events.iter().filter(|e| {
+                            !hidden_kinds.contains(&e.kind)
+                                && e.message.to_lowercase().contains(&filter.to_lowercase())
+                        });
+
+                        for event in shown_events {
+                            let time = DateTime::<Local>::from(event.timestamp).format("%H:%M:%S");
+                            let color = match event.kind {
+                                NodeEventKind::Error => Color32::from_rgb(200, 0, 0),
+                                _ => Color32::from_rgb(0, 100, 200),
+                            };
+
+                            ui.horizontal(|ui| {
+                                ui.label(time.to_string());
+                                ui.colored_label(color, format!("{:?}", event.kind));
+                                ui.label(&event.message);
+                            });
+                        }
+                    });
+            });
+    }
+
+    fn show_stats_window(&mut self, ctx: &mut ViewContext) {
+        if !self.stats_window_opened {
+            return;
+        }
+
+        // Like the objects, the stats are fetched when the window is opened
+        if self.stats.is_none() && !self.stats_request.is_pending() {
+            let since = SystemTime::now() - Duration::from_secs(self.stats_range_hours * 3600);
+            self.stats_request = ctx.daemon_com.get_stats_history(&self.node_name, since);
+        }
+
+        let mut refresh = false;
+        let loading = self.stats.is_none();
+        let samples = self.stats.clone().unwrap_or_default();
+        let range_hours = &mut self.stats_range_hours;
+        let metric = &mut self.stats_metric;
+
+        egui::Window::new("Stats")
+            .open(&mut self.stats_window_opened)
+            .default_width(500.0)
+            .show(ctx.egui_ctx, |ui| {
+                ui.horizontal(|ui| {
+                    for (hours, label) in [
+                        (1, "1 hour"),
+                        (6, "6 hours"),
+                        (24, "1 day"),
+                        (168, "7 days"),
+                    ] {
+                        if ui.selectable_value(range_hours, hours, label).clicked() {
+                            refresh = true;
+                        }
+                    }
+
+                    if loading {
+                        ui.spinner();
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.selectable_value(metric, StatsMetric::ConnectedPeers, "Connected peers");
+                    ui.selectable_value(metric, StatsMetric::BytesSent, "Bytes sent");
+                    ui.selectable_value(metric, StatsMetric::BytesReceived, "Bytes received");
+                    ui.selectable_value(metric, StatsMetric::ObjectsServed, "Objects served");
+                });
+                ui.add_space(10.0);
+
+                if !loading && samples.is_empty() {
+                    ui.label("No stats recorded in the range, is the node running?");
+                    return;
+                }
+
+                ui.label("Hours ago");
+                Plot::new("stats_plot")
+                    .height(250.0)
+                    .allow_scroll(false)
+                    .show(ui, |plot_ui| {
+                        let points = stats_points(&samples, *metric, SystemTime::now());
+                        plot_ui.line(Line::new(PlotPoints::from(points)));
+                    });
+            });
+
+        if refresh {
+            self.stats = None;
+        }
+    }
+
     fn show_download_window(&mut self, ctx: &mut ViewContext) {
         egui::Window::new("Download info")
             .open(&mut self.download_window_opened)
@@ -339,7 +946,13 @@ impl NodeView {
                         }
                     }
 
-                    ui.label(&self.status_line);
+                    if let Some(event) = self.view_events.last() {
+                        let color = match event.kind {
+                            NodeEventKind::Error => Color32::from_rgb(200, 0, 0),
+                            _ => ui.visuals().text_color(),
+                        };
+                        ui.colored_label(color, &event.message);
+                    }
                 });
             });
 
@@ -355,10 +968,16 @@ impl AppView for NodeView {
     }
 
     fn draw(&mut self, mut ctx: &mut ViewContext) -> ViewAction {
+        self.poll_requests();
         self.show_default_panel(&mut ctx);
         self.show_config_window(&mut ctx);
         self.show_node_window(&mut ctx);
         self.show_download_window(&mut ctx);
+        self.show_objects_window(&mut ctx);
+        self.show_peers_window(&mut ctx);
+        self.show_contacts_window(&mut ctx);
+        self.show_event_log_window(&mut ctx);
+        self.show_stats_window(&mut ctx);
         self.show_dialer_window(&mut ctx);
         self.show_status_bar(&mut ctx)
     }
@@ -369,3 +988,37 @@ impl AppView for NodeView {
             .remove_observed_config(&self.node_name);
     }
 }
+
+/// The points of the stat, the hours before now against its values
+fn stats_points(
+    samples: &[NodeStatsSample],
+    metric: StatsMetric,
+    now: SystemTime,
+) -> Vec<[f64; 2]> {
+    samples
+        .iter()
+        .map(|sample| {
+            let hours_ago = now
+                .duration_since(sample.at)
+                .unwrap_or_default()
+                .as_secs_f64()
+                / 3600.0;
+            let value = match metric {
+                StatsMetric::ConnectedPeers => sample.connected_peers as f64,
+                StatsMetric::BytesSent => sample.bytes_sent as f64,
+                StatsMetric::BytesReceived => sample.bytes_received as f64,
+                StatsMetric::ObjectsServed => sample.objects_served as f64,
+            };
+            [-hours_ago, value]
+        })
+        .collect()
+}
+
+/// Human readable name of the known object types
+fn type_name(type_id: &Uuid) -> &'static str {
+    match *type_id {
+        PlainFileObject::UUID => "Plain file",
+        SignedObject::UUID => "Signed",
+        _ => "Unknown",
+    }
+}
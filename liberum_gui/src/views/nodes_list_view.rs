@@ -1,18 +1,37 @@
 use egui::Color32;
 
+use crate::daemon_com::RequestState;
+
+use super::node_creation_wizard::NodeCreationWizard;
 use super::{AppView, NodeView, ViewAction, ViewContext};
 
 #[derive(Default)]
 pub struct NodesListView {
     create_node_name: String,
+    wizard: NodeCreationWizard,
+    request: RequestState<()>,
+    status_line: String,
+    status_is_error: bool,
 }
 
 impl AppView for NodesListView {
     fn draw(&mut self, ctx: &mut ViewContext) -> ViewAction {
-        let state = ctx.system_state.lock().unwrap();
-        let state = (*state).clone();
+        let state = ctx.system_state.lock().unwrap().clone();
         let mut action = ViewAction::Stay;
 
+        if let Some(result) = self.request.take() {
+            self.status_is_error = result.is_err();
+            self.status_line = match result {
+                Ok(()) => String::new(),
+                Err(e) => e.to_string(),
+            };
+        }
+        if let Some(name) = self.wizard.take_created() {
+            self.status_is_error = false;
+            self.status_line = format!("Node {name} created");
+        }
+        let busy = self.request.is_pending();
+
         egui::CentralPanel::default().show(ctx.egui_ctx, |ui| {
             let state = match state {
                 Some(s) => s,
@@ -31,15 +50,25 @@ impl AppView for NodesListView {
                     ui.horizontal(|ui| {
                         ui.text_edit_singleline(&mut self.create_node_name);
 
-                        if ui.button("Create").clicked() {
-                            ctx.daemon_com
-                                .create_node(&mut self.create_node_name)
-                                .unwrap();
-
+                        if ui.add_enabled(!busy, egui::Button::new("Create")).clicked() {
+                            self.request = ctx.daemon_com.create_node(&self.create_node_name, None);
                             self.create_node_name = String::new();
                         }
+
+                        if busy {
+                            ui.spinner();
+                        }
                     });
 
+                    match self.status_is_error {
+                        true => ui.colored_label(Color32::from_rgb(200, 0, 0), &self.status_line),
+                        false => ui.label(&self.status_line),
+                    };
+
+                    if ui.button("Create with custom settings").clicked() {
+                        self.wizard.open();
+                    }
+
                     ui.add_space(20.0);
 
                     egui::Grid::new("config_grid")
@@ -55,12 +84,12 @@ impl AppView for NodesListView {
                                 ui.label(format!("Is running: {}", n.is_running));
 
                                 ui.horizontal(|ui| {
-                                    if ui.button("Run").clicked() {
-                                        let _ = ctx.daemon_com.run_node(&n.name);
+                                    if ui.add_enabled(!busy, egui::Button::new("Run")).clicked() {
+                                        self.request = ctx.daemon_com.run_node(&n.name);
                                     }
 
-                                    if ui.button("Stop").clicked() {
-                                        let _ = ctx.daemon_com.stop_node(&n.name);
+                                    if ui.add_enabled(!busy, egui::Button::new("Stop")).clicked() {
+                                        self.request = ctx.daemon_com.stop_node(&n.name);
                                     }
 
                                     if ui.button("Show").clicked() {
@@ -78,6 +107,10 @@ impl AppView for NodesListView {
             ui.add_space(10.0);
         });
 
+        if self.wizard.opened {
+            self.wizard.show(ctx);
+        }
+
         action
     }
 }
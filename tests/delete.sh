@@ -52,11 +52,11 @@ FILE_ID=$($CLI_BIN publish-file $N1 "$FILE_NAME")
 
 # init_asserts
 # download file
-DOWNLOAD_1_SUCCESS=$($CLI_BIN -d download-file $N1 "${FILE_ID}")
+DOWNLOAD_1_SUCCESS=$($CLI_BIN -d download-file $N1 "${FILE_ID}" -o -)
 should_contain "$DOWNLOAD_1_SUCCESS" "${FILE_CONTENT}"
-DOWNLOAD_2_SUCCESS=$($CLI_BIN -d download-file $N2 "${FILE_ID}")
+DOWNLOAD_2_SUCCESS=$($CLI_BIN -d download-file $N2 "${FILE_ID}" -o -)
 should_contain "$DOWNLOAD_2_SUCCESS" "${FILE_CONTENT}"
-DOWNLOAD_3_SUCCESS=$($CLI_BIN -d download-file $N3 "${FILE_ID}")
+DOWNLOAD_3_SUCCESS=$($CLI_BIN -d download-file $N3 "${FILE_ID}" -o -)
 should_contain "$DOWNLOAD_3_SUCCESS" "${FILE_CONTENT}"
 
 # # delete file
@@ -74,22 +74,22 @@ should_contain "$DELETE_1_FAIL" "Successful deletes: 0"
 should_contain "$DELETE_1_FAIL" "Failed deletes: 2"
 
 # # try to download file
-DOWNLOAD_1_FAIL=$($CLI_BIN -d download-file $N1 "${FILE_ID}")
+DOWNLOAD_1_FAIL=$($CLI_BIN -d download-file $N1 "${FILE_ID}" -o -)
 should_contain "$DOWNLOAD_1_FAIL" "Fail"
-DOWNLOAD_2_FAIL=$($CLI_BIN -d download-file $N2 "${FILE_ID}")
+DOWNLOAD_2_FAIL=$($CLI_BIN -d download-file $N2 "${FILE_ID}" -o -)
 should_contain "$DOWNLOAD_2_FAIL" "Fail"
-DOWNLOAD_3_FAIL=$($CLI_BIN -d download-file $N3 "${FILE_ID}")
+DOWNLOAD_3_FAIL=$($CLI_BIN -d download-file $N3 "${FILE_ID}" -o -)
 should_contain "$DOWNLOAD_3_FAIL" "Fail"
 
 # publish
 FILE_ID=$($CLI_BIN publish-file $N1 "$FILE_NAME")
 
 # # try to download
-DOWNLOAD_1_SUCCESS=$($CLI_BIN -d download-file $N1 "${FILE_ID}")
+DOWNLOAD_1_SUCCESS=$($CLI_BIN -d download-file $N1 "${FILE_ID}" -o -)
 should_contain "$DOWNLOAD_1_SUCCESS" "${FILE_CONTENT}"
-DOWNLOAD_2_SUCCESS=$($CLI_BIN -d download-file $N2 "${FILE_ID}")
+DOWNLOAD_2_SUCCESS=$($CLI_BIN -d download-file $N2 "${FILE_ID}" -o -)
 should_contain "$DOWNLOAD_2_SUCCESS" "${FILE_CONTENT}"
-DOWNLOAD_3_SUCCESS=$($CLI_BIN -d download-file $N3 "${FILE_ID}")
+DOWNLOAD_3_SUCCESS=$($CLI_BIN -d download-file $N3 "${FILE_ID}" -o -)
 should_contain "$DOWNLOAD_3_SUCCESS" "${FILE_CONTENT}"
 
 # delete
@@ -99,11 +99,11 @@ should_contain "$DELETE_1_SUCCESS" "Successful deletes: 2"
 should_contain "$DELETE_1_SUCCESS" "Failed deletes: 0"
 
 # try to download
-DOWNLOAD_1_FAIL=$($CLI_BIN -d download-file $N1 "${FILE_ID}")
+DOWNLOAD_1_FAIL=$($CLI_BIN -d download-file $N1 "${FILE_ID}" -o -)
 should_contain "$DOWNLOAD_1_FAIL" "Fail"
-DOWNLOAD_2_FAIL=$($CLI_BIN -d download-file $N2 "${FILE_ID}")
+DOWNLOAD_2_FAIL=$($CLI_BIN -d download-file $N2 "${FILE_ID}" -o -)
 should_contain "$DOWNLOAD_2_FAIL" "Fail"
-DOWNLOAD_3_FAIL=$($CLI_BIN -d download-file $N3 "${FILE_ID}")
+DOWNLOAD_3_FAIL=$($CLI_BIN -d download-file $N3 "${FILE_ID}" -o -)
 should_contain "$DOWNLOAD_3_FAIL" "Fail"
 
 # cleanup
@@ -80,7 +80,7 @@ for (( i = $INIT_COUNT+1; i <= $((INIT_COUNT + NODE_COUNT)); i++ )); do
 
     sleep 0.1
 
-    RESULT=$($CLI_BIN -d download-file ${N} "${FILE_ID}" 2> /dev/null)
+    RESULT=$($CLI_BIN -d download-file ${N} "${FILE_ID}" -o - 2> /dev/null)
     if [[ "$RESULT" == "$FILE_CONTENT" ]]; then
         COUNT_PASS=$((COUNT_PASS+1))
     else
@@ -58,7 +58,7 @@ FILE_ID=$($CLI_BIN publish-file ${N_NAMES[0]} "$FILE_NAME" 2> /dev/null)
 
 init_asserts
 
-RESULT=$($CLI_BIN -d download-file ${N_NAMES[$(($NODE_COUNT - 1))]} "${FILE_ID}" 2> /dev/null)
+RESULT=$($CLI_BIN -d download-file ${N_NAMES[$(($NODE_COUNT - 1))]} "${FILE_ID}" -o - 2> /dev/null)
 should_be_equal "$RESULT" "$FILE_CONTENT"
 
 
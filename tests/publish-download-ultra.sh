@@ -109,40 +109,40 @@ FILE4_HASH=$($CLI_BIN publish-file $N4 "$FILE4_NAME" 2> /dev/null)
 
 init_asserts
 
-RESULT11=$(cargo run -p liberum_cli download-file $N1 "${FILE1_HASH}" 2> /dev/null)
+RESULT11=$(cargo run -p liberum_cli download-file $N1 "${FILE1_HASH}" -o - 2> /dev/null)
 should_contain "$RESULT11" "${FILE1_CONTENT}"
-RESULT12=$(cargo run -p liberum_cli download-file $N1 "${FILE2_HASH}" 2> /dev/null)
+RESULT12=$(cargo run -p liberum_cli download-file $N1 "${FILE2_HASH}" -o - 2> /dev/null)
 should_contain "$RESULT12" "${FILE2_CONTENT}"
-RESULT13=$(cargo run -p liberum_cli download-file $N1 "${FILE3_HASH}" 2> /dev/null)
+RESULT13=$(cargo run -p liberum_cli download-file $N1 "${FILE3_HASH}" -o - 2> /dev/null)
 should_contain "$RESULT13" "${FILE3_CONTENT}"
-RESULT14=$(cargo run -p liberum_cli download-file $N1 "${FILE4_HASH}" 2> /dev/null)
+RESULT14=$(cargo run -p liberum_cli download-file $N1 "${FILE4_HASH}" -o - 2> /dev/null)
 should_contain "$RESULT14" "${FILE4_CONTENT}"
 
-RESULT21=$(cargo run -p liberum_cli download-file $N2 "${FILE1_HASH}" 2> /dev/null)
+RESULT21=$(cargo run -p liberum_cli download-file $N2 "${FILE1_HASH}" -o - 2> /dev/null)
 should_contain "$RESULT21" "${FILE1_CONTENT}"
-RESULT22=$(cargo run -p liberum_cli download-file $N2 "${FILE2_HASH}" 2> /dev/null)
+RESULT22=$(cargo run -p liberum_cli download-file $N2 "${FILE2_HASH}" -o - 2> /dev/null)
 should_contain "$RESULT22" "${FILE2_CONTENT}"
-RESULT23=$(cargo run -p liberum_cli download-file $N2 "${FILE3_HASH}" 2> /dev/null)
+RESULT23=$(cargo run -p liberum_cli download-file $N2 "${FILE3_HASH}" -o - 2> /dev/null)
 should_contain "$RESULT23" "${FILE3_CONTENT}"
-RESULT24=$(cargo run -p liberum_cli download-file $N2 "${FILE4_HASH}" 2> /dev/null)
+RESULT24=$(cargo run -p liberum_cli download-file $N2 "${FILE4_HASH}" -o - 2> /dev/null)
 should_contain "$RESULT24" "${FILE4_CONTENT}"
 
-RESULT31=$(cargo run -p liberum_cli download-file $N3 "${FILE1_HASH}" 2> /dev/null)
+RESULT31=$(cargo run -p liberum_cli download-file $N3 "${FILE1_HASH}" -o - 2> /dev/null)
 should_contain "$RESULT31" "${FILE1_CONTENT}"
-RESULT32=$(cargo run -p liberum_cli download-file $N3 "${FILE2_HASH}" 2> /dev/null)
+RESULT32=$(cargo run -p liberum_cli download-file $N3 "${FILE2_HASH}" -o - 2> /dev/null)
 should_contain "$RESULT32" "${FILE2_CONTENT}"
-RESULT33=$(cargo run -p liberum_cli download-file $N3 "${FILE3_HASH}" 2> /dev/null)
+RESULT33=$(cargo run -p liberum_cli download-file $N3 "${FILE3_HASH}" -o - 2> /dev/null)
 should_contain "$RESULT33" "${FILE3_CONTENT}"
-RESULT34=$(cargo run -p liberum_cli download-file $N3 "${FILE4_HASH}" 2> /dev/null)
+RESULT34=$(cargo run -p liberum_cli download-file $N3 "${FILE4_HASH}" -o - 2> /dev/null)
 should_contain "$RESULT34" "${FILE4_CONTENT}"
 
-RESULT41=$(cargo run -p liberum_cli download-file $N4 "${FILE1_HASH}" 2> /dev/null)
+RESULT41=$(cargo run -p liberum_cli download-file $N4 "${FILE1_HASH}" -o - 2> /dev/null)
 should_contain "$RESULT41" "${FILE1_CONTENT}"
-RESULT42=$(cargo run -p liberum_cli download-file $N4 "${FILE2_HASH}" 2> /dev/null)
+RESULT42=$(cargo run -p liberum_cli download-file $N4 "${FILE2_HASH}" -o - 2> /dev/null)
 should_contain "$RESULT42" "${FILE2_CONTENT}"
-RESULT43=$(cargo run -p liberum_cli download-file $N4 "${FILE3_HASH}" 2> /dev/null)
+RESULT43=$(cargo run -p liberum_cli download-file $N4 "${FILE3_HASH}" -o - 2> /dev/null)
 should_contain "$RESULT43" "${FILE3_CONTENT}"
-RESULT44=$(cargo run -p liberum_cli download-file $N4 "${FILE4_HASH}" 2> /dev/null)
+RESULT44=$(cargo run -p liberum_cli download-file $N4 "${FILE4_HASH}" -o - 2> /dev/null)
 should_contain "$RESULT44" "${FILE4_CONTENT}"
 
 
@@ -44,7 +44,7 @@ FILE_HASH=$($CLI_BIN publish-file $N1 "$FILE_NAME" 2> /dev/null)
 # download file
 PROVIDERS_RESULT=$($CLI_BIN -d get-providers $N2 "${FILE_HASH}" 2> /dev/null)
 should_not_be_equal "$PROVIDERS_RESULT" ""
-RESULT1=$($CLI_BIN -d download-file $N2 "${FILE_HASH}" 2> /dev/null)
+RESULT1=$($CLI_BIN -d download-file $N2 "${FILE_HASH}" -o - 2> /dev/null)
 should_contain "$RESULT1" "${FILE_CONTENT}"
 
 # cleanup